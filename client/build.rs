@@ -0,0 +1,13 @@
+// Generates the gRPC types of proto/keeper.proto with the grpc feature, parsing it with protox so
+// the build needs no protoc.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/keeper.proto");
+        let descriptors =
+            protox::compile(["proto/keeper.proto"], ["proto"]).expect("Invalid proto/keeper.proto");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("Could not generate the gRPC types");
+    }
+}
@@ -0,0 +1,228 @@
+use std::{ops::Add, str::FromStr};
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal, ParseBigDecimalError, ToPrimitive};
+use serde::{Deserialize, Serialize};
+
+// The amount types shared by the indexer and the keeper, so USD values, token amounts and prices
+// keep their decimals wherever they are read, stored or estimated with.
+
+// USD values are fixed point numbers with 30 decimals, prices having 30 decimals minus the token ones.
+pub const USD_DECIMALS: i64 = 30;
+// Market tokens and the execution fee token carry 18 decimals.
+pub const MARKET_TOKEN_DECIMALS: i64 = 18;
+
+// Divides a raw amount by 10^decimals, None when the amount or the decimals are unknown.
+pub fn scale(amount: Option<BigDecimal>, decimals: Option<i64>) -> Option<BigDecimal> {
+    let (digits, exponent) = amount?.as_bigint_and_exponent();
+    Some(BigDecimal::new(digits, exponent + decimals?))
+}
+
+// Divides a raw amount by 10^decimals as a float, for the estimations working in f64.
+fn scale_f64(amount: &BigDecimal, decimals: i64) -> f64 {
+    amount.to_f64().unwrap_or(f64::NAN) / 10f64.powi(decimals as i32)
+}
+
+// Returns a raw amount as an integer, None when it has a fraction or does not fit 128 bits.
+fn to_u128(amount: &BigDecimal) -> Option<u128> {
+    match amount.is_integer() {
+        true => amount.with_scale(0).as_bigint_and_exponent().0.to_u128(),
+        false => None,
+    }
+}
+
+// A USD value as emitted by the protocol, with USD_DECIMALS decimals.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Usd(BigDecimal);
+
+// A token amount as emitted by the protocol, in the smallest unit of its token.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TokenAmount(BigDecimal);
+
+// A price as emitted by the protocol, the USD value of the smallest unit of a token, with
+// USD_DECIMALS minus the token decimals decimals.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Price(BigDecimal);
+
+impl Usd {
+    pub fn from_raw(raw: BigDecimal) -> Self {
+        Usd(raw)
+    }
+
+    // A USD value read by the keeper from the DataStore.
+    pub fn from_u128(raw: u128) -> Self {
+        Usd(BigDecimal::from(BigInt::from(raw)))
+    }
+
+    // The raw value, as stored in the unscaled columns.
+    pub fn raw(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    // The value in USD.
+    pub fn to_usd(&self) -> BigDecimal {
+        scale(Some(self.0.clone()), Some(USD_DECIMALS)).expect("USD decimals are known")
+    }
+
+    // The value in USD as a float, for the liquidation and risk estimations.
+    pub fn to_usd_f64(&self) -> f64 {
+        scale_f64(&self.0, USD_DECIMALS)
+    }
+
+    // Applies a protocol factor to the value, without rounding.
+    // @factor: The raw factor.
+    // @decimals: The decimals of the factor.
+    pub fn apply_factor(&self, factor: u128, decimals: i64) -> Usd {
+        let value = &self.0 * BigDecimal::from(BigInt::from(factor));
+        Usd(scale(Some(value), Some(decimals)).expect("Factor decimals are known"))
+    }
+}
+
+impl Add for Usd {
+    type Output = Usd;
+
+    fn add(self, other: Usd) -> Usd {
+        Usd(self.0 + other.0)
+    }
+}
+
+impl TokenAmount {
+    pub fn from_raw(raw: BigDecimal) -> Self {
+        TokenAmount(raw)
+    }
+
+    // A token amount read by the keeper from the DataStore.
+    pub fn from_u128(raw: u128) -> Self {
+        TokenAmount(BigDecimal::from(BigInt::from(raw)))
+    }
+
+    // The raw amount, as stored in the unscaled columns.
+    pub fn raw(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    // The amount in whole tokens, None when the token decimals are unknown.
+    // @decimals: The decimals of the token.
+    pub fn to_tokens(&self, decimals: Option<i64>) -> Option<BigDecimal> {
+        scale(Some(self.0.clone()), decimals)
+    }
+
+    // The amount in whole tokens as a float, for the liquidation and risk estimations.
+    // @decimals: The decimals of the token.
+    pub fn to_tokens_f64(&self, decimals: i64) -> f64 {
+        scale_f64(&self.0, decimals)
+    }
+}
+
+impl From<i64> for TokenAmount {
+    fn from(raw: i64) -> Self {
+        TokenAmount(BigDecimal::from(raw))
+    }
+}
+
+impl Price {
+    pub fn from_raw(raw: BigDecimal) -> Self {
+        Price(raw)
+    }
+
+    // A price computed by the keeper from a feed price, which fits 128 bits.
+    pub fn from_u128(raw: u128) -> Self {
+        Price(BigDecimal::from(BigInt::from(raw)))
+    }
+
+    // The raw price, as stored in the unscaled columns.
+    pub fn raw(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    // The raw price as sent to the oracle, None when it has a fraction or does not fit 128 bits.
+    pub fn to_u128(&self) -> Option<u128> {
+        to_u128(&self.0)
+    }
+
+    // The price in USD of one whole token, None when the token decimals are unknown.
+    // @token_decimals: The decimals of the token the price is for.
+    pub fn to_usd_per_token(&self, token_decimals: Option<i64>) -> Option<BigDecimal> {
+        scale(
+            Some(self.0.clone()),
+            token_decimals.map(|decimals| USD_DECIMALS - decimals),
+        )
+    }
+
+    // The price in USD of one whole token as a float, for the liquidation and risk estimations.
+    // @token_decimals: The decimals of the token the price is for.
+    pub fn to_usd_per_token_f64(&self, token_decimals: i64) -> f64 {
+        scale_f64(&self.0, USD_DECIMALS - token_decimals)
+    }
+
+    // The USD value of an amount of the token the price is for, the token decimals cancelling out.
+    // @amount: The amount, in the smallest unit of the token.
+    pub fn value_of(&self, amount: &TokenAmount) -> Usd {
+        Usd(&self.0 * &amount.0)
+    }
+}
+
+// Raw amounts are read from the decimal text of the unscaled columns.
+impl FromStr for Usd {
+    type Err = ParseBigDecimalError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        raw.parse().map(Usd)
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = ParseBigDecimalError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        raw.parse().map(TokenAmount)
+    }
+}
+
+impl FromStr for Price {
+    type Err = ParseBigDecimalError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        raw.parse().map(Price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price() {
+        // 3000 USD for a token with 18 decimals.
+        let price = Price::from_u128(3000 * 10u128.pow(12));
+        assert_eq!(price.to_u128(), Some(3000 * 10u128.pow(12)));
+        assert_eq!(price.to_usd_per_token_f64(18), 3000.0);
+        assert_eq!(
+            price.to_usd_per_token(Some(18)),
+            Some(BigDecimal::from(3000))
+        );
+        let value = price.value_of(&"500000000000000000".parse().unwrap());
+        assert_eq!(value.to_usd_f64(), 1500.0);
+        // A 0.5 factor with 20 decimals halves the value, down to fractions of its raw unit.
+        assert_eq!(
+            (value + Usd::from_u128(1)).apply_factor(5 * 10u128.pow(19), 20),
+            "750000000000000000000000000000000.5".parse().unwrap()
+        );
+        assert_eq!(Price::from_u128(u128::MAX).to_u128(), Some(u128::MAX));
+        assert_eq!("1.5".parse::<Price>().unwrap().to_u128(), None);
+        assert_eq!(
+            Price::from_raw(BigDecimal::from(BigInt::from(u128::MAX) + 1)).to_u128(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_token_amount() {
+        let amount: TokenAmount = "1500000".parse().unwrap();
+        assert_eq!(amount.to_tokens_f64(6), 1.5);
+        assert_eq!(amount.to_tokens(None), None);
+        assert!("0x10".parse::<TokenAmount>().is_err());
+    }
+}
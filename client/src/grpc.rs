@@ -0,0 +1,43 @@
+// The gRPC messages, client and server of proto/keeper.proto, shared by the keeper serving them and
+// the bots streaming from it, e.g.:
+//
+// let mut client = KeeperClient::connect("http://127.0.0.1:50051").await?;
+// let mut orders = client.stream_orders(StreamOrdersRequest { market: None }).await?.into_inner();
+// while let Some(order) = orders.message().await? { ... }
+tonic::include_proto!("satoru.keeper.v1");
+
+pub use keeper_client::KeeperClient;
+pub use keeper_server::{Keeper, KeeperServer};
+
+use crate::types;
+
+// The metadata key requests send their API key in, as the X-Api-Key header of the admin API.
+pub const API_KEY_METADATA: &str = "x-api-key";
+
+impl From<types::Position> for Position {
+    fn from(position: types::Position) -> Self {
+        Position {
+            account: position.account,
+            market: position.market,
+            collateral_token: position.collateral_token,
+            is_long: position.is_long,
+            size_in_usd: position.size_in_usd,
+            cumulative_borrowing_fees_usd: position.cumulative_borrowing_fees_usd,
+            pending_borrowing_fees_usd: position.pending_borrowing_fees_usd,
+        }
+    }
+}
+
+impl From<Position> for types::Position {
+    fn from(position: Position) -> Self {
+        types::Position {
+            account: position.account,
+            market: position.market,
+            collateral_token: position.collateral_token,
+            is_long: position.is_long,
+            size_in_usd: position.size_in_usd,
+            cumulative_borrowing_fees_usd: position.cumulative_borrowing_fees_usd,
+            pending_borrowing_fees_usd: position.pending_borrowing_fees_usd,
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::time::Duration;
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+use url::Url;
+
+pub mod amounts;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod polling;
+pub mod sentry;
+pub mod types;
+
+use types::{
+    AccountDelegation, ContractUpgrade, DailyVwap, DecisionRecord, DelegationRequest, FlowBucket,
+    FrontendFlowReport, KeeperPnl, KeeperStatus, KillSwitchRequest, KillSwitchStatus,
+    MaintenanceRequest, MaintenanceWindow, MarketBacklog, OrderFee, PnlPeriod, Position,
+    StandbyStatus,
+};
+
+// The header the admin API reads API keys from.
+const API_KEY_HEADER: &str = "X-Api-Key";
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Invalid URL: {0}")]
+    UrlError(#[from] url::ParseError),
+    #[error("Request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    // The API answered with an error status, its body holding the reason.
+    #[error("Admin API answered {status}: {body}")]
+    ApiError { status: StatusCode, body: String },
+}
+
+// A typed client of the keeper admin API, for bots integrating against a keeper without writing
+// its HTTP types. Routes needing an API key get one with with_api_key when API_AUTH_ENABLED is set.
+// @base_url: The URL the admin API is served at, e.g. http://127.0.0.1:8081.
+// @api_key: The API key sent with every request, if any.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    api_key: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        Ok(Client {
+            http: reqwest::Client::new(),
+            base_url: Url::parse(base_url)?,
+            api_key: None,
+        })
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_owned());
+        self
+    }
+
+    // Returns the URL of a route with its query parameters.
+    // @path: The path of the route, e.g. /pnl.
+    // @query: The query parameters, those set to None being left out.
+    fn url(&self, path: &str, query: &[(&str, Option<String>)]) -> Result<Url, ClientError> {
+        let mut url = self.base_url.join(path.trim_start_matches('/'))?;
+        let query: Vec<_> = query
+            .iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| (*name, value)))
+            .collect();
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        Ok(url)
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.http.request(method, url);
+        match &self.api_key {
+            Some(api_key) => request.header(API_KEY_HEADER, api_key),
+            None => request,
+        }
+    }
+
+    async fn send_request(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ClientError::ApiError { status, body });
+        }
+        Ok(response)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        Ok(self.send_request(request).await?.json().await?)
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, Option<String>)],
+    ) -> Result<T, ClientError> {
+        let url = self.url(path, query)?;
+        self.send(self.request(Method::GET, url)).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<T, ClientError> {
+        let request = self.request(Method::POST, self.url(path, &[])?);
+        match body {
+            Some(body) => self.send(request.json(body)).await,
+            None => self.send(request).await,
+        }
+    }
+
+    pub async fn keepers(&self) -> Result<Vec<KeeperStatus>, ClientError> {
+        self.get("/keepers", &[]).await
+    }
+
+    pub async fn backlog(&self) -> Result<Vec<MarketBacklog>, ClientError> {
+        self.get("/backlog", &[]).await
+    }
+
+    pub async fn pnl(&self, period: PnlPeriod) -> Result<Vec<KeeperPnl>, ClientError> {
+        self.get("/pnl", &[("period", Some(period.as_str().to_owned()))])
+            .await
+    }
+
+    // @key: The key of the action, as indexed.
+    pub async fn decisions(&self, key: &str) -> Result<Vec<DecisionRecord>, ClientError> {
+        self.get("/decisions", &[("key", Some(key.to_owned()))])
+            .await
+    }
+
+    // @key: The key of the action, as indexed.
+    pub async fn fees(&self, key: &str) -> Result<Vec<OrderFee>, ClientError> {
+        self.get("/fees", &[("key", Some(key.to_owned()))]).await
+    }
+
+    // @account: The account to list the fees of the actions of, as indexed.
+    // @limit: The most actions returned, 100 when None.
+    pub async fn account_fees(
+        &self,
+        account: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<OrderFee>, ClientError> {
+        self.get(
+            "/fees",
+            &[
+                ("account", Some(account.to_owned())),
+                ("limit", limit.map(|limit| limit.to_string())),
+            ],
+        )
+        .await
+    }
+
+    // @account: The account to list the positions of, as indexed, every account when None.
+    pub async fn positions(&self, account: Option<&str>) -> Result<Vec<Position>, ClientError> {
+        self.get("/positions", &[("account", account.map(str::to_owned))])
+            .await
+    }
+
+    pub async fn kill_switch(&self) -> Result<KillSwitchStatus, ClientError> {
+        self.get("/kill-switch", &[]).await
+    }
+
+    // Engages or disengages the kill switch, with an operator key.
+    pub async fn set_kill_switch(
+        &self,
+        request: &KillSwitchRequest,
+    ) -> Result<KillSwitchStatus, ClientError> {
+        self.post("/kill-switch", Some(request)).await
+    }
+
+    pub async fn maintenance_windows(&self) -> Result<Vec<MaintenanceWindow>, ClientError> {
+        self.get("/maintenance", &[]).await
+    }
+
+    // Schedules a maintenance window with an operator key, returns every window.
+    pub async fn schedule_maintenance(
+        &self,
+        request: &MaintenanceRequest,
+    ) -> Result<Vec<MaintenanceWindow>, ClientError> {
+        self.post("/maintenance", Some(request)).await
+    }
+
+    // @account: The account, as hex.
+    pub async fn delegation(&self, account: &str) -> Result<AccountDelegation, ClientError> {
+        self.get("/delegations", &[("account", Some(account.to_owned()))])
+            .await
+    }
+
+    // Registers the managed execution preferences of an account, with a key of the account.
+    pub async fn set_delegation(
+        &self,
+        account: &str,
+        request: &DelegationRequest,
+    ) -> Result<AccountDelegation, ClientError> {
+        let url = self.url("/delegations", &[("account", Some(account.to_owned()))])?;
+        self.send(self.request(Method::PUT, url).json(request))
+            .await
+    }
+
+    pub async fn delete_delegation(&self, account: &str) -> Result<(), ClientError> {
+        let url = self.url("/delegations", &[("account", Some(account.to_owned()))])?;
+        self.send_request(self.request(Method::DELETE, url)).await?;
+        Ok(())
+    }
+
+    pub async fn pending_upgrades(&self) -> Result<Vec<ContractUpgrade>, ClientError> {
+        self.get("/upgrades", &[]).await
+    }
+
+    pub async fn standby(&self) -> Result<StandbyStatus, ClientError> {
+        self.get("/standby", &[]).await
+    }
+
+    // Promotes a standby to active at once, with an operator key.
+    pub async fn promote_standby(&self) -> Result<StandbyStatus, ClientError> {
+        self.post::<(), _>("/standby/promote", None).await
+    }
+
+    // @market: The market token address, as hex.
+    // @days: How many days back the series starts, 30 when None.
+    pub async fn market_vwap(
+        &self,
+        market: &str,
+        days: Option<i64>,
+    ) -> Result<Vec<DailyVwap>, ClientError> {
+        self.get(
+            "/markets/vwap",
+            &[
+                ("market", Some(market.to_owned())),
+                ("days", days.map(|days| days.to_string())),
+            ],
+        )
+        .await
+    }
+
+    // @days: How many days back the orders are counted, 30 when None.
+    pub async fn frontend_stats(
+        &self,
+        bucket: FlowBucket,
+        days: Option<u64>,
+    ) -> Result<Vec<FrontendFlowReport>, ClientError> {
+        self.get(
+            "/stats/frontends",
+            &[
+                ("bucket", Some(bucket.as_str().to_owned())),
+                ("days", days.map(|days| days.to_string())),
+            ],
+        )
+        .await
+    }
+
+    // Polls a read route, the admin API serving no WebSocket, yielding its answers when they
+    // change, e.g. to react to the kill switch getting engaged or a standby getting promoted.
+    // Subscriptions are polled from a Tokio runtime.
+    // @path: The path of the route, e.g. /kill-switch.
+    // @every: The delay between two polls.
+    pub fn subscribe<T>(&self, path: &str, every: Duration) -> Subscription<T>
+    where
+        T: DeserializeOwned + PartialEq,
+    {
+        let mut ticks = interval(every);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Subscription {
+            client: self.clone(),
+            path: path.to_owned(),
+            ticks,
+            last: None,
+        }
+    }
+}
+
+// A subscription to a read route of the admin API, built with Client::subscribe.
+// @last: The last answer yielded, answers equal to it getting skipped.
+pub struct Subscription<T> {
+    client: Client,
+    path: String,
+    ticks: Interval,
+    last: Option<T>,
+}
+
+impl<T: DeserializeOwned + PartialEq + Clone> Subscription<T> {
+    // Waits for the next answer differing from the last one, the first poll yielding at once.
+    // Errors get returned as they happen, the subscription polling again on the next call.
+    pub async fn next(&mut self) -> Result<T, ClientError> {
+        loop {
+            self.ticks.tick().await;
+            let value: T = self.client.get(&self.path, &[]).await?;
+            if self.last.as_ref() != Some(&value) {
+                self.last = Some(value.clone());
+                return Ok(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let client = Client::new("http://127.0.0.1:8081/admin/").unwrap();
+        assert_eq!(
+            client.url("/pnl", &[]).unwrap().as_str(),
+            "http://127.0.0.1:8081/admin/pnl"
+        );
+        let url = client
+            .url(
+                "/markets/vwap",
+                &[("market", Some("0x1".to_owned())), ("days", None)],
+            )
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:8081/admin/markets/vwap?market=0x1"
+        );
+    }
+
+    #[test]
+    fn test_standby_status() {
+        let status: StandbyStatus = serde_json::from_str(
+            r#"{"active":true,"instance_id":"keeper-2","nonce":"0x2a","leader_lease_ms":3000,
+                "last_promotion":"failover","promoted_at":1704103200}"#,
+        )
+        .unwrap();
+        assert_eq!(status.last_promotion, Some(types::Promotion::Failover));
+        assert_eq!(status.nonce.as_deref(), Some("0x2a"));
+    }
+}
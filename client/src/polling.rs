@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use tokio::{sync::Notify, time::timeout};
+
+// A struct representing an adaptive polling interval, the shortest after a poll found progress
+// and doubling on every idle poll up to the longest, so quiet periods cost few RPC calls.
+// @min: The interval after an active poll.
+// @max: The longest interval.
+// @current: The interval before the next idle poll.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub min: Duration,
+    pub max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Backoff {
+            min,
+            max: max.max(min),
+            current: min,
+        }
+    }
+
+    // Returns the interval to wait before the next poll.
+    // @active: Whether the last poll found progress.
+    pub fn next(&mut self, active: bool) -> Duration {
+        if active {
+            self.current = self.min;
+        }
+        let interval = self.current;
+        self.current = (self.current * 2).min(self.max);
+        interval
+    }
+}
+
+// Waits for the interval to elapse or for a wake up, whichever comes first.
+// @interval: The polling interval.
+// @wakeup: Notified when the next poll may find progress, e.g. on a database notification.
+pub async fn wait(interval: Duration, wakeup: &Notify) {
+    let _ = timeout(interval, wakeup.notified()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Instant};
+
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(backoff.next(false), Duration::from_millis(100));
+        assert_eq!(backoff.next(false), Duration::from_millis(200));
+        assert_eq!(backoff.next(false), Duration::from_millis(400));
+        assert_eq!(backoff.next(false), Duration::from_millis(500));
+        assert_eq!(backoff.next(false), Duration::from_millis(500));
+        assert_eq!(backoff.next(true), Duration::from_millis(100));
+        assert_eq!(backoff.next(false), Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_wait_wakes_up() {
+        let wakeup = Arc::new(Notify::new());
+        let notifier = Arc::clone(&wakeup);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            notifier.notify_waiters();
+        });
+        let start = Instant::now();
+        wait(Duration::from_secs(10), &wakeup).await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    panic,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use url::Url;
+
+// The reporter set up at startup, errors are only logged when no DSN is configured.
+static REPORTER: OnceLock<SentryReporter> = OnceLock::new();
+// Tells apart the events reported within the same nanosecond.
+static EVENT_COUNTER: AtomicU64 = AtomicU64::new(0);
+// How long a report may take, panics waiting for theirs before going on.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A struct representing the Sentry project events get reported to, parsed from a DSN formatted as
+// https://<public_key>@<host>/<project_id>.
+// @public_key: The key authenticating the reports.
+// @store_url: The endpoint events get posted to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentryDsn {
+    pub public_key: String,
+    pub store_url: String,
+}
+
+impl SentryDsn {
+    pub fn parse(dsn: &str) -> Option<Self> {
+        let url = Url::parse(dsn).ok()?;
+        let public_key = url.username();
+        let project_id = url.path().trim_matches('/');
+        if public_key.is_empty() || project_id.is_empty() {
+            return None;
+        }
+        let port = url
+            .port()
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        Some(SentryDsn {
+            public_key: public_key.to_owned(),
+            store_url: format!(
+                "{}://{}{}/api/{}/store/",
+                url.scheme(),
+                url.host_str()?,
+                port,
+                project_id
+            ),
+        })
+    }
+}
+
+// A struct reporting panics and errors to Sentry, so production incidents do not get lost in
+// the container logs.
+// @dsn: The project events get reported to.
+// @binary: The binary reporting, e.g. keeper.
+// @version: The version of the binary, e.g. its CARGO_PKG_VERSION.
+// @environment: The deployment environment, e.g. mainnet.
+#[derive(Debug, Clone)]
+pub struct SentryReporter {
+    pub dsn: SentryDsn,
+    pub binary: String,
+    pub version: String,
+    pub environment: Option<String>,
+}
+
+impl SentryReporter {
+    // Builds the event of an error or panic.
+    // @level: The level of the event (error, fatal).
+    // @message: The error message.
+    // @tags: The context of the error, e.g. the key of the order it happened on.
+    // @backtrace: The stack trace of a panic, if any.
+    pub fn build_event(
+        &self,
+        level: &str,
+        message: &str,
+        tags: &[(&str, &str)],
+        backtrace: Option<String>,
+    ) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before unix epoch");
+        let counter = EVENT_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut tags: HashMap<&str, &str> = tags.iter().copied().collect();
+        tags.insert("binary", &self.binary);
+        json!({
+            "event_id": format!("{:016x}{:016x}", now.as_nanos() as u64, counter),
+            "timestamp": now.as_secs_f64(),
+            "platform": "rust",
+            "level": level,
+            "logger": self.binary,
+            "release": format!("{}@{}", self.binary, self.version),
+            "environment": self.environment,
+            "message": { "formatted": message },
+            "tags": tags,
+            "extra": { "backtrace": backtrace },
+        })
+    }
+
+    // Posts an event to the Sentry project, failures being logged only.
+    // @event: The event to report.
+    pub async fn send(&self, event: &Value) {
+        let result = reqwest::Client::new()
+            .post(&self.dsn.store_url)
+            .header(
+                "X-Sentry-Auth",
+                format!(
+                    "Sentry sentry_version=7, sentry_client={}/{}, sentry_key={}",
+                    self.binary, self.version, self.dsn.public_key
+                ),
+            )
+            .json(event)
+            .timeout(SEND_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            eprintln!("Could not report event to Sentry: {:?}", e);
+        }
+    }
+}
+
+// Sets up the reporting of the panics and errors of a binary when a DSN is configured.
+// @binary: The binary reporting, e.g. keeper.
+// @version: The version of the binary, e.g. its CARGO_PKG_VERSION.
+// @dsn: The SENTRY_DSN of the binary, reporting being left off when None.
+// @environment: The SENTRY_ENVIRONMENT of the binary.
+pub fn init(binary: &str, version: &str, dsn: Option<String>, environment: Option<String>) {
+    let dsn = match dsn {
+        Some(dsn) => SentryDsn::parse(&dsn).expect("SENTRY_DSN must be a valid DSN"),
+        None => return,
+    };
+    let reporter = SentryReporter {
+        dsn,
+        binary: binary.to_owned(),
+        version: version.to_owned(),
+        environment,
+    };
+    if REPORTER.set(reporter).is_err() {
+        return;
+    }
+    println!("Reporting panics and errors to Sentry");
+
+    // Panics are reported before the default hook prints them, the process may be about to exit.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(reporter) = REPORTER.get() {
+            let location = info
+                .location()
+                .map(|location| location.to_string())
+                .unwrap_or_default();
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "panic".to_owned()),
+            };
+            let thread_name = thread::current().name().unwrap_or("unnamed").to_owned();
+            let event = reporter.build_event(
+                "fatal",
+                &format!("panicked at {}: {}", location, message),
+                &[("thread", &thread_name)],
+                Some(Backtrace::force_capture().to_string()),
+            );
+            // The panicking thread may be a runtime worker, the event gets sent from its own runtime.
+            let reporter = reporter.clone();
+            let sender = thread::spawn(move || {
+                if let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    runtime.block_on(reporter.send(&event));
+                }
+            });
+            let _ = sender.join();
+        }
+        default_hook(info);
+    }));
+}
+
+// Reports an error in the background, with its context, when reporting is set up.
+// @message: The error message.
+// @tags: The context of the error, e.g. the key of the order it happened on.
+pub fn capture_error(message: &str, tags: &[(&str, &str)]) {
+    if let Some(reporter) = REPORTER.get() {
+        let event = reporter.build_event("error", message, tags, None);
+        let reporter = reporter.clone();
+        tokio::spawn(async move { reporter.send(&event).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dsn() {
+        assert_eq!(
+            SentryDsn::parse("https://abc123@o1.ingest.sentry.io/42"),
+            Some(SentryDsn {
+                public_key: "abc123".to_owned(),
+                store_url: "https://o1.ingest.sentry.io/api/42/store/".to_owned(),
+            })
+        );
+        assert_eq!(
+            SentryDsn::parse("http://key@localhost:9000/7")
+                .unwrap()
+                .store_url,
+            "http://localhost:9000/api/7/store/"
+        );
+        assert!(SentryDsn::parse("https://o1.ingest.sentry.io/42").is_none());
+        assert!(SentryDsn::parse("https://abc123@o1.ingest.sentry.io").is_none());
+    }
+
+    #[test]
+    fn test_build_event() {
+        let reporter = SentryReporter {
+            dsn: SentryDsn::parse("https://abc123@o1.ingest.sentry.io/42").unwrap(),
+            binary: "keeper".to_owned(),
+            version: "0.1.0".to_owned(),
+            environment: Some("testnet".to_owned()),
+        };
+        let event = reporter.build_event("error", "reverted", &[("order_key", "0x1a")], None);
+        let other = reporter.build_event("error", "reverted", &[], None);
+
+        assert_eq!(event["event_id"].as_str().unwrap().len(), 32);
+        assert_ne!(event["event_id"], other["event_id"]);
+        assert_eq!(event["message"]["formatted"], "reverted");
+        assert_eq!(event["tags"]["order_key"], "0x1a");
+        assert_eq!(event["tags"]["binary"], "keeper");
+        assert_eq!(event["release"], "keeper@0.1.0");
+        assert_eq!(event["environment"], "testnet");
+    }
+}
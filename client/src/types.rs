@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+
+// The types below mirror the JSON the keeper admin API answers with, the keeper tests checking
+// they still deserialize what it serializes. Amounts are decimal strings, as served.
+
+// A registered keeper instance, from GET /keepers.
+// @alive: Whether the instance sent a heartbeat within the heartbeat timeout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeeperStatus {
+    pub id: String,
+    pub version: String,
+    pub mode: String,
+    pub features: Vec<String>,
+    pub account_address: String,
+    pub started_at: String,
+    pub last_heartbeat: String,
+    pub alive: bool,
+}
+
+// The actions of a market awaiting keeper action, from GET /backlog.
+// @action_type: The table of the actions (orders, deposits, withdrawals).
+// @pending_amount: Their size in USD for orders, token amounts for deposits and withdrawals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketBacklog {
+    pub market: Option<String>,
+    pub action_type: String,
+    pub pending: i64,
+    pub pending_amount: String,
+    pub oldest_time_stamp: Option<String>,
+}
+
+// The periods the keeper PnL is aggregated over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PnlPeriod {
+    Daily,
+    Weekly,
+}
+
+impl PnlPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PnlPeriod::Daily => "daily",
+            PnlPeriod::Weekly => "weekly",
+        }
+    }
+}
+
+// The keeper PnL over a period, from GET /pnl.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeeperPnl {
+    pub period_start: String,
+    pub transactions: i64,
+    pub fees_paid: String,
+    pub fees_earned: String,
+    pub pnl: String,
+}
+
+// A decision the keeper made on an action, from GET /decisions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub key: String,
+    pub table_name: String,
+    pub decision: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+// The fee economics of an action the keeper settled, from GET /fees.
+// @keeper_fee: The part of the execution fee paid to the keeper.
+// @refund: The part of the execution fee refunded to the trader.
+// @gas_spent: The fees the keeper paid for its transactions on the action.
+// @keeper_pnl: The keeper fee minus the gas spent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderFee {
+    pub key: String,
+    pub table_name: String,
+    pub account: String,
+    pub transaction_hash: String,
+    pub execution_fee: String,
+    pub keeper_fee: String,
+    pub refund: String,
+    pub gas_spent: String,
+    pub keeper_pnl: String,
+    pub created_at: String,
+}
+
+// An open position with its borrowing fees, from GET /positions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub account: String,
+    pub market: String,
+    pub collateral_token: String,
+    pub is_long: bool,
+    pub size_in_usd: String,
+    pub cumulative_borrowing_fees_usd: String,
+    pub pending_borrowing_fees_usd: String,
+}
+
+// A maintenance window, from GET /maintenance.
+// @id: The id of the window, None for the windows configured with MAINTENANCE_WINDOWS.
+// @starts_at: When the window starts, as a unix timestamp.
+// @ends_at: When the window ends, as a unix timestamp, excluded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    pub id: Option<i64>,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub reason: String,
+}
+
+// A contract upgrade waiting for an acknowledgment, from GET /upgrades.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractUpgrade {
+    pub id: i64,
+    pub contract: String,
+    pub address: String,
+    pub previous_class_hash: Option<String>,
+    pub class_hash: String,
+    pub block_number: Option<i64>,
+}
+
+// The state of the kill switch, from GET /kill-switch.
+// @engaged: Whether outgoing transactions are stopped.
+// @reason: The reason the operator engaged the switch with, if engaged by an operator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KillSwitchStatus {
+    pub engaged: bool,
+    pub reason: Option<String>,
+    pub paused_on_chain: bool,
+    pub maintenance: Option<MaintenanceWindow>,
+    pub pending_upgrades: Vec<ContractUpgrade>,
+}
+
+// The body of POST /kill-switch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KillSwitchRequest {
+    pub engaged: bool,
+    pub reason: Option<String>,
+}
+
+// The body of POST /maintenance, at least one of ends_at and duration_secs being set.
+// @starts_at: When the window starts, as a unix timestamp, now when unset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceRequest {
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub duration_secs: Option<i64>,
+    pub reason: Option<String>,
+}
+
+// How a standby got promoted to active.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Promotion {
+    Manual,
+    Failover,
+}
+
+// The role of a keeper instance, from GET /standby.
+// @nonce: The nonce of the keeper account at the last poll, as hex.
+// @promoted_at: When the instance last got promoted, as a unix timestamp.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StandbyStatus {
+    pub active: bool,
+    pub instance_id: String,
+    pub nonce: Option<String>,
+    pub leader_lease_ms: Option<u64>,
+    pub last_promotion: Option<Promotion>,
+    pub promoted_at: Option<u64>,
+}
+
+// The volume-weighted average execution price of a market over a UTC day, from GET /markets/vwap.
+// @day: The day, as YYYY-MM-DD.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyVwap {
+    pub day: String,
+    pub vwap: Option<String>,
+    pub increase_vwap: Option<String>,
+    pub decrease_vwap: Option<String>,
+    pub increase_volume_usd: String,
+    pub decrease_volume_usd: String,
+    pub executions: i64,
+}
+
+// The time buckets the order flow of the frontends is broken down into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowBucket {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl FlowBucket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlowBucket::Hourly => "hourly",
+            FlowBucket::Daily => "daily",
+            FlowBucket::Weekly => "weekly",
+        }
+    }
+}
+
+// The order flow of a frontend over a time bucket, from GET /stats/frontends.
+// @ui_fee_receiver: The UI fee receiver the frontend creates its orders with, as indexed.
+// @maker_share: The share of its maker and taker orders which are maker ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrontendFlowReport {
+    pub bucket_start: String,
+    pub ui_fee_receiver: String,
+    pub orders: i64,
+    pub executed_orders: i64,
+    pub volume_usd: String,
+    pub maker_orders: i64,
+    pub taker_orders: i64,
+    pub maker_share: Option<f64>,
+}
+
+// The managed execution preferences of an account, from GET /delegations.
+// @stale_order_secs: How long its orders stay pending before being stale, set when they get
+// cancelled.
+// @notification_channels: The channels it gets notified on (webhook, telegram).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountDelegation {
+    pub account: String,
+    pub auto_claim_funding: bool,
+    pub auto_cancel_stale_orders: bool,
+    pub stale_order_secs: Option<i64>,
+    pub notification_channels: Vec<String>,
+}
+
+// The body of PUT /delegations, every action being opted out of when unset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DelegationRequest {
+    pub auto_claim_funding: bool,
+    pub auto_cancel_stale_orders: bool,
+    pub stale_order_secs: Option<i64>,
+    pub notification_channels: Vec<String>,
+}
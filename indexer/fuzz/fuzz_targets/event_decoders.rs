@@ -0,0 +1,72 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use satoru_indexer::events::event::{Event, GenericEvent};
+use satoru_indexer::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+
+// Bytes a decoded event may take per byte of event data once serialized, decoders copying or
+// widening the felts they read but never repeating them.
+const MAX_GROWTH: usize = 64;
+// Bytes of a decoded event made of the metadata and field names, whatever the data.
+const MAX_OVERHEAD: usize = 4096;
+
+// Decodes the event as an event type and checks its size stays proportional to the data, as the
+// stores serialize it.
+fn decode<T: Event>(event: &GenericEvent) {
+    let decoded = serde_json::to_string(&T::from_generic_event(event.clone()))
+        .expect("Decoded event failing to serialize");
+    assert!(
+        decoded.len() <= MAX_GROWTH * (event.data.len() + 1) + MAX_OVERHEAD,
+        "{} decoded to {} bytes from {} bytes of data",
+        std::any::type_name::<T>(),
+        decoded.len(),
+        event.data.len()
+    );
+}
+
+// Feeds arbitrary event keys and data to every decoder, the first line being the key and the rest
+// the comma separated felts, so decoders never panic on what a node returns.
+fuzz_target!(|input: &[u8]| {
+    let input = String::from_utf8_lossy(input);
+    let (key, data) = input.split_once('\n').unwrap_or(("", &input));
+    let event = GenericEvent {
+        block_number: 1,
+        timestamp: Some("1700000000".to_owned()),
+        transaction_hash: "0a".to_owned(),
+        key: Some(key.to_owned()),
+        data: data.to_owned(),
+        sender_address: Some("0b".to_owned()),
+    };
+    decode::<ClaimableFundingAmountPerSizeUpdated>(&event);
+    decode::<CumulativeBorrowingFactorUpdated>(&event);
+    decode::<Deposit>(&event);
+    decode::<DepositCancelled>(&event);
+    decode::<DepositExecuted>(&event);
+    decode::<FundingFeeAmountPerSizeUpdated>(&event);
+    decode::<MarketCreated>(&event);
+    decode::<MarketParamUpdated>(&event);
+    decode::<Order>(&event);
+    decode::<OrderCancelled>(&event);
+    decode::<OrderExecuted>(&event);
+    decode::<OrderFrozen>(&event);
+    decode::<OrderUpdated>(&event);
+    decode::<PoolAmountUpdated>(&event);
+    decode::<PositionDecrease>(&event);
+    decode::<PositionIncrease>(&event);
+    decode::<SwapFeesCollected>(&event);
+    decode::<SwapInfo>(&event);
+    decode::<Withdrawal>(&event);
+    decode::<WithdrawalCancelled>(&event);
+    decode::<WithdrawalExecuted>(&event);
+});
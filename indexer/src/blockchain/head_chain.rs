@@ -1,30 +1,131 @@
-use sqlx::postgres::PgPool;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sqlx::any::{AnyKind, AnyPool};
 use sqlx::Error;
 
+// The shard used when none is configured, it resumes from the legacy last_indexed_block table.
+pub const DEFAULT_SHARD: &str = "default";
+
+// The cursors of the indexer shards, kept in the Postgres or SQLite database of the store.
+#[derive(Clone)]
 pub struct HeadChain {
-    pool: PgPool,
+    pool: AnyPool,
+    shard: String,
 }
 
 impl HeadChain {
-    pub fn new(pool: PgPool) -> Self {
-        HeadChain { pool }
+    pub fn new(pool: AnyPool, shard: String) -> Self {
+        HeadChain { pool, shard }
     }
 
     pub async fn get_last_block_indexed(&self) -> Result<i64, Error> {
+        let row: Option<(i64,)> =
+            sqlx::query_as("SELECT block_number FROM indexer_cursors WHERE shard = $1")
+                .bind(&self.shard)
+                .fetch_optional(&self.pool)
+                .await?;
+        let cursor = row.map(|row| row.0).unwrap_or(0);
+        if self.shard != DEFAULT_SHARD {
+            return Ok(cursor);
+        }
         let row: (Option<i64>,) =
             sqlx::query_as("SELECT MAX(block_number) FROM last_indexed_block")
                 .fetch_one(&self.pool)
                 .await?;
-        Ok(row.0.unwrap_or(0))
+        Ok(cursor.max(row.0.unwrap_or(0)))
     }
 
     pub async fn update_last_block_indexed(&self, block_number: i64) -> Result<(), Error> {
-        sqlx::query!(
-            "INSERT INTO last_indexed_block (block_number) VALUES ($1) ON CONFLICT (id) DO UPDATE SET block_number = $1",
-            block_number
+        sqlx::query(
+            "INSERT INTO indexer_cursors (shard, block_number, heartbeat_at) VALUES ($1, $2, $3)
+             ON CONFLICT (shard) DO UPDATE SET block_number = $2, heartbeat_at = $3, updated_at = CURRENT_TIMESTAMP",
         )
+        .bind(&self.shard)
+        .bind(block_number)
+        .bind(now())
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+
+    // Marks the shard as live, shards without a heartbeat for SHARD_TTL_SECS no longer holding their
+    // events and block range.
+    pub async fn heartbeat(&self) -> Result<(), Error> {
+        sqlx::query("UPDATE indexer_cursors SET heartbeat_at = $1 WHERE shard = $2")
+            .bind(now())
+            .bind(&self.shard)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Registers the events and block range of this shard in the cursor table, refusing to start when
+    // another live shard already indexes one of its events over an overlapping block range. The
+    // check and the registration run in one transaction holding the write lock of the table, so two
+    // shards starting together cannot both pass it.
+    // @events: The events of the shard.
+    // @from_block: The first block of the shard.
+    // @to_block: The last block of the shard, None when it follows the chain head.
+    // @ttl_secs: How long a shard without heartbeat still holds its events and blocks.
+    pub async fn register_shard(
+        &self,
+        events: &[&str],
+        from_block: i64,
+        to_block: Option<i64>,
+        ttl_secs: i64,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        // SQLite transactions take the write lock of the database with their first write, the
+        // registration below, Postgres ones need the table lock for the shards they do not update.
+        if self.pool.any_kind() == AnyKind::Postgres {
+            sqlx::query("LOCK TABLE indexer_cursors IN SHARE ROW EXCLUSIVE MODE")
+                .execute(&mut tx)
+                .await?;
+        }
+        let now = now();
+        sqlx::query(
+            "INSERT INTO indexer_cursors (shard, block_number, events, from_block, to_block, heartbeat_at) VALUES ($1, 0, $2, $3, $4, $5)
+             ON CONFLICT (shard) DO UPDATE SET events = $2, from_block = $3, to_block = $4, heartbeat_at = $5, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(&self.shard)
+        .bind(events.join(","))
+        .bind(from_block)
+        .bind(to_block)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+
+        let shards: Vec<(String, String, i64, Option<i64>)> = sqlx::query_as(
+            "SELECT shard, events, from_block, to_block FROM indexer_cursors
+             WHERE shard <> $1 AND heartbeat_at > $2",
+        )
+        .bind(&self.shard)
+        .bind(now - ttl_secs)
+        .fetch_all(&mut tx)
+        .await?;
+        for (shard, shard_events, shard_from_block, shard_to_block) in shards {
+            let shares_events = shard_events.split(',').any(|event| events.contains(&event));
+            let shares_blocks = from_block <= shard_to_block.unwrap_or(i64::MAX)
+                && shard_from_block <= to_block.unwrap_or(i64::MAX);
+            if shares_events && shares_blocks {
+                // Dropping the transaction rolls the registration back.
+                return Err(Error::Configuration(
+                    format!(
+                        "shard {} overlaps shard {}, they index the same events and blocks",
+                        self.shard, shard
+                    )
+                    .into(),
+                ));
+            }
+        }
+        tx.commit().await
+    }
+}
+
+// The current unix timestamp of the shard heartbeats.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64
 }
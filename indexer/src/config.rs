@@ -1,5 +1,7 @@
 use std::env;
 
+use crate::blockchain::head_chain::DEFAULT_SHARD;
+
 pub fn get_database_url() -> String {
     env::var("DATABASE_URL").expect("DATABASE_URL must be set")
 }
@@ -18,3 +20,169 @@ pub fn get_from_block() -> u64 {
 pub fn get_contract_address() -> String {
     env::var("CONTRACT_ADDRESS").expect("CONTRACT_ADDRESS must be set")
 }
+
+// None when unset, the indexed orders are then never reconciled with the DataStore.
+pub fn get_data_store_address() -> Option<String> {
+    env::var("DATA_STORE")
+        .ok()
+        .filter(|address| !address.is_empty())
+}
+
+pub fn get_reconciliation_sample_size() -> i64 {
+    env::var("RECONCILIATION_SAMPLE_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<i64>().ok())
+        .unwrap_or(20)
+}
+
+pub fn get_reconciliation_interval_secs() -> u64 {
+    env::var("RECONCILIATION_INTERVAL_SECS")
+        .ok()
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .unwrap_or(300)
+}
+
+// None when unset, panics and errors are then only logged.
+pub fn get_sentry_dsn() -> Option<String> {
+    env::var("SENTRY_DSN").ok().filter(|dsn| !dsn.is_empty())
+}
+
+pub fn get_sentry_environment() -> Option<String> {
+    env::var("SENTRY_ENVIRONMENT")
+        .ok()
+        .filter(|environment| !environment.is_empty())
+}
+
+pub fn get_shard_name() -> String {
+    env::var("INDEXER_SHARD").unwrap_or(DEFAULT_SHARD.to_owned())
+}
+
+// None when unset, every event is then indexed.
+pub fn get_indexed_events() -> Option<Vec<String>> {
+    let events: Vec<String> = env::var("INDEXED_EVENTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|event| event.trim().to_owned())
+        .filter(|event| !event.is_empty())
+        .collect();
+    Some(events).filter(|events| !events.is_empty())
+}
+
+// Events or event families never indexed by any shard, e.g. all but the orders for a keeper
+// without analytics, empty when unset.
+pub fn get_disabled_events() -> Vec<String> {
+    env::var("DISABLED_EVENTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|event| event.trim().to_owned())
+        .filter(|event| !event.is_empty())
+        .collect()
+}
+
+// Seconds a shard without heartbeat still holds its events and block range against other shards,
+// e.g. once crashed. Live shards send a heartbeat every third of it.
+pub fn get_shard_ttl_secs() -> u64 {
+    env::var("SHARD_TTL_SECS")
+        .ok()
+        .and_then(|ttl| ttl.parse::<u64>().ok())
+        .filter(|ttl| *ttl > 0)
+        .unwrap_or(300)
+}
+
+// None when unset, the shard then follows the chain head.
+pub fn get_to_block() -> Option<u64> {
+    env::var("TO_BLOCK")
+        .ok()
+        .filter(|to_block| !to_block.is_empty())
+        .map(|to_block| {
+            to_block
+                .parse::<u64>()
+                .expect("TO_BLOCK must be a valid u64 number")
+        })
+}
+
+// Scaled amount columns are only filled in when enabled, the raw amounts staying authoritative.
+pub fn get_normalize_amounts() -> bool {
+    env::var("NORMALIZE_AMOUNTS")
+        .map(|normalize| normalize == "true" || normalize == "1")
+        .unwrap_or(false)
+}
+
+// Shortest interval between two polls of the pending events, used while new events arrive.
+pub fn get_poll_min_interval_ms() -> u64 {
+    env::var("POLL_MIN_INTERVAL_MS")
+        .ok()
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .unwrap_or(1000)
+}
+
+// The polling interval doubles on every idle poll up to this one.
+pub fn get_poll_max_interval_ms() -> u64 {
+    env::var("POLL_MAX_INTERVAL_MS")
+        .ok()
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .unwrap_or(30000)
+}
+
+// None when unset, the indexer then only wakes up at the end of its polling interval.
+pub fn get_poll_wake_channel() -> Option<String> {
+    env::var("POLL_WAKE_CHANNEL")
+        .ok()
+        .filter(|channel| !channel.is_empty())
+}
+
+// Events the load test generates per second.
+pub fn get_loadtest_rate() -> u64 {
+    env::var("LOADTEST_RATE")
+        .ok()
+        .and_then(|rate| rate.parse::<u64>().ok())
+        .filter(|rate| *rate > 0)
+        .unwrap_or(100)
+}
+
+pub fn get_loadtest_duration_secs() -> u64 {
+    env::var("LOADTEST_DURATION_SECS")
+        .ok()
+        .and_then(|duration| duration.parse::<u64>().ok())
+        .unwrap_or(60)
+}
+
+// Number of synthetic traders the orders get spread over.
+pub fn get_loadtest_accounts() -> u64 {
+    env::var("LOADTEST_ACCOUNTS")
+        .ok()
+        .and_then(|accounts| accounts.parse::<u64>().ok())
+        .filter(|accounts| *accounts > 0)
+        .unwrap_or(100)
+}
+
+// Markets the synthetic orders trade, comma separated, a synthetic market when unset.
+pub fn get_loadtest_markets() -> Vec<String> {
+    env::var("LOADTEST_MARKETS")
+        .ok()
+        .map(|markets| {
+            markets
+                .split(',')
+                .map(|market| market.trim().to_owned())
+                .filter(|market| !market.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// How long synthetic orders wait for their execution or cancellation.
+pub fn get_loadtest_settlement_delay_secs() -> u64 {
+    env::var("LOADTEST_SETTLEMENT_DELAY_SECS")
+        .ok()
+        .and_then(|delay| delay.parse::<u64>().ok())
+        .unwrap_or(2)
+}
+
+// Block number of the first synthetic event, far above the chain so it never collides with indexed
+// events.
+pub fn get_loadtest_from_block() -> i64 {
+    env::var("LOADTEST_FROM_BLOCK")
+        .ok()
+        .and_then(|block| block.parse::<i64>().ok())
+        .unwrap_or(1_000_000_000)
+}
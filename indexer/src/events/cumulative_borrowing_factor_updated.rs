@@ -0,0 +1,44 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::events::funding_fee_amount_per_size_updated::parse_bool;
+use crate::events::order_updated::parse_u256;
+use crate::store::Store;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CumulativeBorrowingFactorUpdated {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub market: Option<String>,
+    pub is_long: Option<bool>,
+    pub delta: Option<BigDecimal>,
+    pub next_value: Option<BigDecimal>,
+}
+
+#[async_trait]
+impl Event for CumulativeBorrowingFactorUpdated {
+    fn event_key() -> &'static str {
+        "0237215cac2e30d52faa22ac5f257cc28b963bc20a6aaada17b5814ab489013e"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        CumulativeBorrowingFactorUpdated {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            market: data_parts.first().cloned().unwrap_or(None),
+            is_long: parse_bool(data_parts.get(1)),
+            delta: parse_u256(data_parts.get(2), data_parts.get(3)),
+            next_value: parse_u256(data_parts.get(4), data_parts.get(5)),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_cumulative_borrowing_factor_updated(self).await
+    }
+}
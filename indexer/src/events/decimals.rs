@@ -0,0 +1,4 @@
+// The amount types are shared with the keeper through satoru-client.
+pub use satoru_client::amounts::{
+    scale, Price, TokenAmount, Usd, MARKET_TOKEN_DECIMALS, USD_DECIMALS,
+};
@@ -1,7 +1,9 @@
+use crate::events::decimals::TokenAmount;
 use crate::events::event::{Event, GenericEvent};
+use crate::events::order_updated::parse_felt;
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,11 +19,11 @@ pub struct Deposit {
     pub initial_short_token: Option<String>,
     pub long_token_swap_path: Option<String>,
     pub short_token_swap_path: Option<String>,
-    pub initial_long_token_amount: Option<i64>,
-    pub initial_short_token_amount: Option<i64>,
-    pub min_market_tokens: Option<i64>,
+    pub initial_long_token_amount: Option<TokenAmount>,
+    pub initial_short_token_amount: Option<TokenAmount>,
+    pub min_market_tokens: Option<TokenAmount>,
     pub updated_at_block: Option<i64>,
-    pub execution_fee: Option<i64>,
+    pub execution_fee: Option<TokenAmount>,
     pub callback_gas_limit: Option<i64>,
 }
 
@@ -38,7 +40,7 @@ impl Event for Deposit {
             block_number: event.block_number,
             transaction_hash: event.transaction_hash,
             key: event.key,
-            account: data_parts.get(0).cloned().unwrap_or(None),
+            account: data_parts.first().cloned().unwrap_or(None),
             receiver: data_parts.get(1).cloned().unwrap_or(None),
             callback_contract: data_parts.get(2).cloned().unwrap_or(None),
             market: data_parts.get(3).cloned().unwrap_or(None),
@@ -46,48 +48,20 @@ impl Event for Deposit {
             initial_short_token: data_parts.get(5).cloned().unwrap_or(None),
             long_token_swap_path: data_parts.get(6).cloned().unwrap_or(None),
             short_token_swap_path: data_parts.get(7).cloned().unwrap_or(None),
-            initial_long_token_amount: data_parts
-                .get(8)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
-            initial_short_token_amount: data_parts
-                .get(9)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
-            min_market_tokens: data_parts
-                .get(10)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+            initial_long_token_amount: parse_felt(data_parts.get(8)).map(TokenAmount::from_raw),
+            initial_short_token_amount: parse_felt(data_parts.get(9)).map(TokenAmount::from_raw),
+            min_market_tokens: parse_felt(data_parts.get(10)).map(TokenAmount::from_raw),
             updated_at_block: data_parts
                 .get(11)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
-            execution_fee: data_parts
-                .get(12)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| i64::from_str_radix(v, 16).ok())),
+            execution_fee: parse_felt(data_parts.get(12)).map(TokenAmount::from_raw),
             callback_gas_limit: data_parts
                 .get(13)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| i64::from_str_radix(v, 16).ok())),
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO deposits (
-                block_number, transaction_hash, key, account, receiver, callback_contract,
-                market, initial_long_token, initial_short_token, long_token_swap_path, short_token_swap_path,
-                initial_long_token_amount, initial_short_token_amount, min_market_tokens, updated_at_block,
-                execution_fee, callback_gas_limit
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6,
-                $7, $8, $9, $10, $11,
-                $12, $13, $14, $15,
-                $16, $17
-            )",
-                self.block_number, self.transaction_hash, self.key, self.account,
-                self.receiver, self.callback_contract, self.market, self.initial_long_token,
-                self.initial_short_token, self.long_token_swap_path, self.short_token_swap_path,
-                self.initial_long_token_amount, self.initial_short_token_amount, self.min_market_tokens,
-                self.updated_at_block, self.execution_fee, self.callback_gas_limit
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_deposit(self).await
     }
 }
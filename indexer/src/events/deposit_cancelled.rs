@@ -0,0 +1,37 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositCancelled {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[async_trait]
+impl Event for DepositCancelled {
+    fn event_key() -> &'static str {
+        "00056e709adf36c0cb909b41ebecb620a44a31b6dc3867b92c2acf971785cdb5"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        DepositCancelled {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: data_parts.first().cloned().unwrap_or(None),
+            reason: data_parts.get(1).cloned().unwrap_or(None),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_deposit_cancelled(self).await
+    }
+}
@@ -0,0 +1,35 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositExecuted {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+}
+
+#[async_trait]
+impl Event for DepositExecuted {
+    fn event_key() -> &'static str {
+        "0056020a9644603d22d7b029b5649a55d708b88d9049150f146ac26c4107b880"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        DepositExecuted {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: data_parts.first().cloned().unwrap_or(None),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_deposit_executed(self).await
+    }
+}
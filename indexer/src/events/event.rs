@@ -1,13 +1,16 @@
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[async_trait]
 pub trait Event: Debug + Serialize + for<'de> Deserialize<'de> {
     fn event_key() -> &'static str;
+    fn needs_sender_address() -> bool {
+        false
+    }
     fn from_generic_event(event: GenericEvent) -> Self;
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error>;
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error>;
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,4 +20,5 @@ pub struct GenericEvent {
     pub transaction_hash: String,
     pub key: Option<String>,
     pub data: String,
+    pub sender_address: Option<String>,
 }
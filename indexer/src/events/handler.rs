@@ -1,31 +1,37 @@
 use crate::blockchain::head_chain::HeadChain;
 use crate::config::get_contract_address;
 use crate::events::event::{Event, GenericEvent};
-use sqlx::postgres::PgPool;
-use starknet::core::types::{BlockId, BlockTag, EventFilter, FieldElement, MaybePendingBlockWithTxHashes};
+use crate::store::Store;
+use starknet::core::types::{
+    BlockId, BlockTag, EmittedEvent, EventFilter, FieldElement, InvokeTransaction,
+    MaybePendingBlockWithTxHashes, Transaction,
+};
 use starknet::providers::jsonrpc::{HttpTransport, JsonRpcClient};
 use starknet::providers::Provider;
 use std::collections::HashMap;
 
 pub struct EventIndexer<'a> {
     provider: &'a JsonRpcClient<HttpTransport>,
-    pool: &'a PgPool,
+    store: &'a dyn Store,
     event_processors: HashMap<&'static str, Box<dyn EventProcessor + Send + Sync>>,
     head_chain: HeadChain,
+    to_block: Option<u64>,
 }
 
 impl<'a> EventIndexer<'a> {
     pub fn new(
         provider: &'a JsonRpcClient<HttpTransport>,
-        pool: &'a PgPool,
+        store: &'a dyn Store,
         event_processors: HashMap<&'static str, Box<dyn EventProcessor + Send + Sync>>,
         head_chain: HeadChain,
+        to_block: Option<u64>,
     ) -> Self {
         EventIndexer {
             provider,
-            pool,
+            store,
             event_processors,
             head_chain,
+            to_block,
         }
     }
 
@@ -39,7 +45,10 @@ impl<'a> EventIndexer<'a> {
         let contract_address = FieldElement::from_hex_be(&get_contract_address()).unwrap();
         let event_filter = EventFilter {
             from_block: Some(BlockId::Number(from_block)),
-            to_block: Some(BlockId::Tag(BlockTag::Latest)),
+            to_block: Some(
+                self.to_block
+                    .map_or(BlockId::Tag(BlockTag::Latest), BlockId::Number),
+            ),
             address: Some(contract_address),
             keys: Some(vec![keys]),
         };
@@ -51,31 +60,79 @@ impl<'a> EventIndexer<'a> {
             .map_err(|e| sqlx::Error::Protocol(format!("{:?}", e)))?;
 
         for event in events_page.events {
-            let block = self.provider.get_block_with_tx_hashes(BlockId::Number(event.block_number)).await.unwrap();
-            let timestamp = match block {
-                MaybePendingBlockWithTxHashes::Block(block) => Some(block.timestamp.to_string()),
-                MaybePendingBlockWithTxHashes::PendingBlock(block) => Some(block.timestamp.to_string()),
-            };
-            let generic_event = GenericEvent {
-                block_number: event.block_number as i64,
-                timestamp,
-                transaction_hash: hex::encode(event.transaction_hash.to_bytes_be()),
-                key: event.keys.first().map(|k| hex::encode(k.to_bytes_be())),
-                data: event.data.iter().map(|fe| hex::encode(fe.to_bytes_be())).collect::<Vec<_>>().join(","),
-            };
-            if let Some(key) = event.keys.first() {
-                let key_str = hex::encode(key.to_bytes_be());
-                if let Some(processor) = self.event_processors.get(&key_str.as_str()) {
-                    processor.process_event(generic_event.clone(), &self.pool).await?;
-                }
-                self.head_chain.update_last_block_indexed(event.block_number as i64).await?;
-            }
+            self.process_emitted_event(event).await?;
         }
 
         Ok(())
     }
 
-    pub async fn fetch_pending_events(&self) -> Result<(), sqlx::Error> {
+    async fn process_emitted_event(&self, event: EmittedEvent) -> Result<(), sqlx::Error> {
+        let block = self
+            .provider
+            .get_block_with_tx_hashes(BlockId::Number(event.block_number))
+            .await
+            .unwrap();
+        let timestamp = match block {
+            MaybePendingBlockWithTxHashes::Block(block) => Some(block.timestamp.to_string()),
+            MaybePendingBlockWithTxHashes::PendingBlock(block) => Some(block.timestamp.to_string()),
+        };
+        let key_str = event.keys.first().map(|k| hex::encode(k.to_bytes_be()));
+        let processor = key_str
+            .as_ref()
+            .and_then(|key| self.event_processors.get(key.as_str()));
+
+        // The sender of the transaction is only fetched for the events attributing it, e.g. the keeper executing an order.
+        let sender_address = match processor {
+            Some(processor) if processor.needs_sender_address() => {
+                self.get_sender_address(event.transaction_hash).await?
+            }
+            _ => None,
+        };
+
+        let generic_event = GenericEvent {
+            block_number: event.block_number as i64,
+            timestamp,
+            transaction_hash: hex::encode(event.transaction_hash.to_bytes_be()),
+            key: key_str.clone(),
+            data: event
+                .data
+                .iter()
+                .map(|fe| hex::encode(fe.to_bytes_be()))
+                .collect::<Vec<_>>()
+                .join(","),
+            sender_address,
+        };
+        if key_str.is_some() {
+            self.store.archive_event(&generic_event).await?;
+            if let Some(processor) = processor {
+                processor.process_event(generic_event, self.store).await?;
+            }
+            self.head_chain
+                .update_last_block_indexed(event.block_number as i64)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_sender_address(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let transaction = self
+            .provider
+            .get_transaction_by_hash(transaction_hash)
+            .await
+            .map_err(|e| sqlx::Error::Protocol(format!("{:?}", e)))?;
+        let sender_address = match transaction {
+            Transaction::Invoke(InvokeTransaction::V1(tx)) => Some(tx.sender_address),
+            Transaction::Invoke(InvokeTransaction::V0(tx)) => Some(tx.contract_address),
+            _ => None,
+        };
+        Ok(sender_address.map(|address| hex::encode(address.to_bytes_be())))
+    }
+
+    // Processes the events of the pending block, returns how many it has.
+    pub async fn fetch_pending_events(&self) -> Result<usize, sqlx::Error> {
         let event_filter = EventFilter {
             from_block: Some(BlockId::Tag(BlockTag::Pending)),
             to_block: Some(BlockId::Tag(BlockTag::Pending)),
@@ -98,35 +155,24 @@ impl<'a> EventIndexer<'a> {
             .await
             .map_err(|e| sqlx::Error::Protocol(format!("{:?}", e)))?;
 
+        let events = events_page.events.len();
         for event in events_page.events {
-            let block = self.provider.get_block_with_tx_hashes(BlockId::Number(event.block_number)).await.unwrap();
-            let timestamp = match block {
-                MaybePendingBlockWithTxHashes::Block(block) => Some(block.timestamp.to_string()),
-                MaybePendingBlockWithTxHashes::PendingBlock(block) => Some(block.timestamp.to_string()),
-            };
-            let generic_event = GenericEvent {
-                block_number: event.block_number as i64,
-                timestamp,
-                transaction_hash: hex::encode(event.transaction_hash.to_bytes_be()),
-                key: event.keys.first().map(|k| hex::encode(k.to_bytes_be())),
-                data: event.data.iter().map(|fe| hex::encode(fe.to_bytes_be())).collect::<Vec<_>>().join(","),
-            };
-            if let Some(key) = event.keys.first() {
-                let key_str = hex::encode(key.to_bytes_be());
-                if let Some(processor) = self.event_processors.get(&key_str.as_str()) {
-                    processor.process_event(generic_event.clone(), &self.pool).await?;
-                }
-                self.head_chain.update_last_block_indexed(event.block_number as i64).await?;
-            }
+            self.process_emitted_event(event).await?;
         }
 
-        Ok(())
+        Ok(events)
     }
 }
 
 #[async_trait::async_trait]
 pub trait EventProcessor {
-    async fn process_event(&self, event: GenericEvent, pool: &PgPool) -> Result<(), sqlx::Error>;
+    fn event_name(&self) -> &'static str;
+    fn needs_sender_address(&self) -> bool;
+    async fn process_event(
+        &self,
+        event: GenericEvent,
+        store: &dyn Store,
+    ) -> Result<(), sqlx::Error>;
 }
 
 pub struct GenericEventProcessor<T: Event + Send + Sync> {
@@ -135,9 +181,24 @@ pub struct GenericEventProcessor<T: Event + Send + Sync> {
 
 #[async_trait::async_trait]
 impl<T: Event + Send + Sync> EventProcessor for GenericEventProcessor<T> {
-    async fn process_event(&self, event: GenericEvent, pool: &PgPool) -> Result<(), sqlx::Error> {
+    fn event_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default()
+    }
+
+    fn needs_sender_address(&self) -> bool {
+        T::needs_sender_address()
+    }
+
+    async fn process_event(
+        &self,
+        event: GenericEvent,
+        store: &dyn Store,
+    ) -> Result<(), sqlx::Error> {
         let specific_event = T::from_generic_event(event);
         println!("Inserting event: {:?}", specific_event);
-        specific_event.insert(pool).await
+        specific_event.insert(store).await
     }
 }
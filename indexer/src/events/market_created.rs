@@ -1,7 +1,7 @@
 use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,7 +30,7 @@ impl Event for MarketCreated {
             block_number: event.block_number,
             transaction_hash: event.transaction_hash,
             key: event.key,
-            creator: data_parts.get(0).cloned().unwrap_or(None),
+            creator: data_parts.first().cloned().unwrap_or(None),
             market_token: data_parts.get(1).cloned().unwrap_or(None),
             index_token: data_parts.get(2).cloned().unwrap_or(None),
             long_token: data_parts.get(3).cloned().unwrap_or(None),
@@ -39,27 +39,7 @@ impl Event for MarketCreated {
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO market_created (
-                block_number, transaction_hash, key, creator, market_token, index_token,
-                long_token, short_token, market_type
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6,
-                $7, $8, $9
-            )",
-            self.block_number,
-            self.transaction_hash,
-            self.key,
-            self.creator,
-            self.market_token,
-            self.index_token,
-            self.long_token,
-            self.short_token,
-            self.market_type
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_market_created(self).await
     }
 }
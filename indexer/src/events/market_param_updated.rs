@@ -0,0 +1,67 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::events::funding_fee_amount_per_size_updated::parse_bool;
+use crate::events::order_updated::parse_u256;
+use crate::store::Store;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+// A config change of a market parameter, e.g. a fee factor, a reserve factor or the max open
+// interest, so the parameters in force at each block can be read back.
+// @parameter: The name of the parameter, e.g. borrowing_factor.
+// @is_long: The side the parameter applies to, None when it applies to both sides.
+// @value: The new value of the parameter.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MarketParamUpdated {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub market: Option<String>,
+    pub parameter: Option<String>,
+    pub is_long: Option<bool>,
+    pub value: Option<BigDecimal>,
+}
+
+#[async_trait]
+impl Event for MarketParamUpdated {
+    fn event_key() -> &'static str {
+        "024624f910faf1571ebbd06d3f04c44f324da208cf25b989eaf915cb01ccf20f"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        MarketParamUpdated {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            market: data_parts.first().cloned().unwrap_or(None),
+            parameter: parse_short_string(data_parts.get(1)),
+            is_long: parse_bool(data_parts.get(2)),
+            value: parse_u256(data_parts.get(3), data_parts.get(4)),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_market_param_updated(self).await
+    }
+}
+
+// Decodes a Cairo short string from its hex felt, None when it is not printable ASCII.
+// @value: The hex felt, without 0x.
+pub fn parse_short_string(value: Option<&Option<String>>) -> Option<String> {
+    let felt = value?.as_ref()?.trim_start_matches('0');
+    if felt.is_empty() || felt.len() > 62 || !felt.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let padded = format!("{:0>width$}", felt, width = felt.len() + felt.len() % 2);
+    let bytes = (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&padded[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if !bytes.iter().all(|byte| byte.is_ascii_graphic()) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
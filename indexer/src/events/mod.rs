@@ -1,11 +1,25 @@
+pub mod claimable_funding_amount_per_size_updated;
+pub mod cumulative_borrowing_factor_updated;
+pub mod decimals;
 pub mod deposit;
+pub mod deposit_cancelled;
+pub mod deposit_executed;
 pub mod event;
+pub mod funding_fee_amount_per_size_updated;
 pub mod market_created;
+pub mod market_param_updated;
 pub mod order;
+pub mod order_cancelled;
 pub mod order_executed;
+pub mod order_frozen;
+pub mod order_updated;
 pub mod pool_amount_updated;
+pub mod position_decrease;
+pub mod position_increase;
 pub mod swap_fees_collected;
 pub mod swap_info;
 pub mod withdrawal;
+pub mod withdrawal_cancelled;
+pub mod withdrawal_executed;
 
 pub mod handler;
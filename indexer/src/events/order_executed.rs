@@ -1,21 +1,27 @@
 use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrderExecuted {
     pub block_number: i64,
+    pub timestamp: Option<String>,
     pub transaction_hash: String,
     pub key: Option<String>,
     pub secondary_order_type: Option<String>,
+    pub keeper: Option<String>,
 }
 
 #[async_trait]
 impl Event for OrderExecuted {
     fn event_key() -> &'static str {
-        "0392fd46c9dd1864ee8b38c8d7dd91cb8e1080856b554ce6d5560dae09b41181"
+        "000f10f06595d3d707241f604672ec4b6ae50eb82728ec2f3c65f6789e897760"
+    }
+
+    fn needs_sender_address() -> bool {
+        true
     }
 
     fn from_generic_event(event: GenericEvent) -> Self {
@@ -23,26 +29,15 @@ impl Event for OrderExecuted {
             event.data.split(',').map(|s| Some(s.to_string())).collect();
         OrderExecuted {
             block_number: event.block_number,
+            timestamp: event.timestamp,
             transaction_hash: event.transaction_hash,
-            key: event.key,
-            secondary_order_type: data_parts.get(0).cloned().unwrap_or(None),
+            key: data_parts.first().cloned().unwrap_or(None),
+            secondary_order_type: data_parts.get(1).cloned().unwrap_or(None),
+            keeper: event.sender_address,
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO order_executed (
-                block_number, transaction_hash, key, secondary_order_type
-            ) VALUES (
-                $1, $2, $3, $4
-            )",
-            self.block_number,
-            self.transaction_hash,
-            self.key,
-            self.secondary_order_type
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_order_executed(self).await
     }
 }
@@ -0,0 +1,37 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderFrozen {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[async_trait]
+impl Event for OrderFrozen {
+    fn event_key() -> &'static str {
+        "033fdba4e5f6b272d64068005c56e7adbaa6d8de035ca1f7b08422c6dc9fe606"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        OrderFrozen {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: data_parts.first().cloned().unwrap_or(None),
+            reason: data_parts.get(1).cloned().unwrap_or(None),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_order_frozen(self).await
+    }
+}
@@ -0,0 +1,63 @@
+use crate::events::decimals::{Price, TokenAmount, Usd};
+use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
+use async_trait::async_trait;
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderUpdated {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+    pub size_delta_usd: Option<Usd>,
+    pub acceptable_price: Option<Price>,
+    pub trigger_price: Option<Price>,
+    pub min_output_amount: Option<TokenAmount>,
+}
+
+#[async_trait]
+impl Event for OrderUpdated {
+    fn event_key() -> &'static str {
+        "00b670ed7b7ee8ccb350963a7dea39493daff6e7a43ab021a0e4ac2d652d359e"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        OrderUpdated {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: data_parts.first().cloned().unwrap_or(None),
+            size_delta_usd: parse_u256(data_parts.get(1), data_parts.get(2)).map(Usd::from_raw),
+            acceptable_price: parse_u256(data_parts.get(3), data_parts.get(4)).map(Price::from_raw),
+            trigger_price: parse_u256(data_parts.get(5), data_parts.get(6)).map(Price::from_raw),
+            min_output_amount: parse_u256(data_parts.get(7), data_parts.get(8))
+                .map(TokenAmount::from_raw),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_order_updated(self).await
+    }
+}
+
+// Decodes an amount serialized as a single felt.
+pub fn parse_felt(felt: Option<&Option<String>>) -> Option<BigDecimal> {
+    BigInt::parse_bytes(felt?.as_ref()?.as_bytes(), 16).map(BigDecimal::from)
+}
+
+// Decodes a u256 serialized as its low and high 128 bits felts.
+pub fn parse_u256(
+    low: Option<&Option<String>>,
+    high: Option<&Option<String>>,
+) -> Option<BigDecimal> {
+    let low = u128::from_str_radix(low?.as_ref()?, 16).ok()?;
+    let high = u128::from_str_radix(high?.as_ref()?, 16).ok()?;
+    Some(BigDecimal::from(
+        (BigInt::from(high) << 128) + BigInt::from(low),
+    ))
+}
@@ -1,7 +1,7 @@
 use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -19,7 +19,7 @@ pub struct PoolAmountUpdated {
 #[async_trait]
 impl Event for PoolAmountUpdated {
     fn event_key() -> &'static str {
-        "03534d650a9b8eb67820f87038b8e8b36b741c6f7eb14d1a7ac5027e80fd4a82"
+        "00cb25a2cd3af50847ea41e39e0b572ab9c2d43065dcf3460884e62301851853"
     }
 
     fn from_generic_event(event: GenericEvent) -> Self {
@@ -29,32 +29,21 @@ impl Event for PoolAmountUpdated {
             block_number: event.block_number,
             transaction_hash: event.transaction_hash,
             key: event.key,
-            market: data_parts.get(0).cloned().unwrap_or(None),
+            market: data_parts.first().cloned().unwrap_or(None),
             token: data_parts.get(1).cloned().unwrap_or(None),
             delta_mag: data_parts
                 .get(2)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             delta_sign: data_parts
                 .get(3)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<bool>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<bool>().ok())),
             next_value: data_parts
                 .get(4)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO pool_amount_updated (
-                block_number, transaction_hash, key, market, token, delta_mag, delta_sign, next_value
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8
-            )",
-                self.block_number, self.transaction_hash, self.key, self.market, self.token,
-                self.delta_mag, self.delta_sign, self.next_value
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_pool_amount_updated(self).await
     }
 }
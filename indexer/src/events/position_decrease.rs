@@ -0,0 +1,57 @@
+use crate::events::decimals::{Price, TokenAmount, Usd};
+use crate::events::event::{Event, GenericEvent};
+use crate::events::order_updated::parse_u256;
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+// A position getting decreased or closed. Only the position identity and its execution get decoded,
+// the keeper reading the position itself from the DataStore once notified.
+// @execution_price: The price the size delta got executed at, at the index token protocol precision.
+// @size_delta_usd: The size delta, in USD.
+// @size_delta_in_tokens: The size delta, in index tokens.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PositionDecrease {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+    pub account: Option<String>,
+    pub market: Option<String>,
+    pub collateral_token: Option<String>,
+    pub execution_price: Option<Price>,
+    pub size_delta_usd: Option<Usd>,
+    pub size_delta_in_tokens: Option<TokenAmount>,
+}
+
+#[async_trait]
+impl Event for PositionDecrease {
+    fn event_key() -> &'static str {
+        "03d51b51b408d7c62dcc47cc558da5ce6a6e0fd129a427ebce150f52b0e5171a"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        PositionDecrease {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: event.key,
+            account: data_parts.first().cloned().unwrap_or(None),
+            market: data_parts.get(1).cloned().unwrap_or(None),
+            collateral_token: data_parts.get(2).cloned().unwrap_or(None),
+            // After the position state and the funding and borrowing factors, as u256 pairs.
+            execution_price: parse_u256(data_parts.get(17), data_parts.get(18))
+                .map(Price::from_raw),
+            size_delta_usd: parse_u256(data_parts.get(27), data_parts.get(28)).map(Usd::from_raw),
+            size_delta_in_tokens: parse_u256(data_parts.get(29), data_parts.get(30))
+                .map(TokenAmount::from_raw),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_position_decrease(self).await
+    }
+}
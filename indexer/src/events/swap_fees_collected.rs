@@ -1,7 +1,7 @@
 use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -34,58 +34,32 @@ impl Event for SwapFeesCollected {
             block_number: event.block_number,
             transaction_hash: event.transaction_hash,
             key: event.key,
-            market: data_parts.get(0).cloned().unwrap_or(None),
+            market: data_parts.first().cloned().unwrap_or(None),
             token: data_parts.get(1).cloned().unwrap_or(None),
             token_price: data_parts
                 .get(2)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             action: data_parts.get(3).cloned().unwrap_or(None),
             fee_receiver_amount: data_parts
                 .get(4)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             fee_amount_for_pool: data_parts
                 .get(5)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             amount_after_fees: data_parts
                 .get(6)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             ui_fee_receiver: data_parts.get(7).cloned().unwrap_or(None),
             ui_fee_receiver_factor: data_parts
                 .get(8)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             ui_fee_amount: data_parts
                 .get(9)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO swap_fees_collected (
-                block_number, transaction_hash, key, market, token, token_price,
-                action, fee_receiver_amount, fee_amount_for_pool, amount_after_fees,
-                ui_fee_receiver, ui_fee_receiver_factor, ui_fee_amount
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6,
-                $7, $8, $9, $10,
-                $11, $12, $13
-            )",
-            self.block_number,
-            self.transaction_hash,
-            self.key,
-            self.market,
-            self.token,
-            self.token_price,
-            self.action,
-            self.fee_receiver_amount,
-            self.fee_amount_for_pool,
-            self.amount_after_fees,
-            self.ui_fee_receiver,
-            self.ui_fee_receiver_factor,
-            self.ui_fee_amount
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_swap_fees_collected(self).await
     }
 }
@@ -1,7 +1,7 @@
 use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPool;
 use std::fmt::Debug;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -38,60 +38,42 @@ impl Event for SwapInfo {
             block_number: event.block_number,
             transaction_hash: event.transaction_hash,
             key: event.key,
-            order_key: data_parts.get(0).cloned().unwrap_or(None),
+            order_key: data_parts.first().cloned().unwrap_or(None),
             market: data_parts.get(1).cloned().unwrap_or(None),
             receiver: data_parts.get(2).cloned().unwrap_or(None),
             token_in: data_parts.get(3).cloned().unwrap_or(None),
             token_out: data_parts.get(4).cloned().unwrap_or(None),
             token_in_price: data_parts
                 .get(5)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             token_out_price: data_parts
                 .get(6)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             amount_in: data_parts
                 .get(7)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             amount_in_after_fees: data_parts
                 .get(8)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             amount_out: data_parts
                 .get(9)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             price_impact_usd_mag: data_parts
                 .get(10)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             price_impact_usd_sign: data_parts
                 .get(11)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<bool>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<bool>().ok())),
             price_impact_amount_mag: data_parts
                 .get(12)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<i64>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<i64>().ok())),
             price_impact_amount_sign: data_parts
                 .get(13)
-                .and_then(|s| s.as_ref().map(|v| v.parse::<bool>().ok()).flatten()),
+                .and_then(|s| s.as_ref().and_then(|v| v.parse::<bool>().ok())),
         }
     }
 
-    async fn insert(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO swap_info (
-                block_number, transaction_hash, key, order_key, market, receiver,
-                token_in, token_out, token_in_price, token_out_price, amount_in, amount_in_after_fees,
-                amount_out, price_impact_usd_mag, price_impact_usd_sign, price_impact_amount_mag,
-                price_impact_amount_sign
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17
-            )",
-                self.block_number, self.transaction_hash, self.key, self.order_key,
-                self.market, self.receiver, self.token_in, self.token_out,
-                self.token_in_price, self.token_out_price, self.amount_in,
-                self.amount_in_after_fees, self.amount_out, self.price_impact_usd_mag,
-                self.price_impact_usd_sign, self.price_impact_amount_mag,
-                self.price_impact_amount_sign
-        )
-        .execute(pool)
-        .await?;
-        Ok(())
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_swap_info(self).await
     }
 }
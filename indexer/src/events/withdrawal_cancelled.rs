@@ -0,0 +1,37 @@
+use crate::events::event::{Event, GenericEvent};
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WithdrawalCancelled {
+    pub block_number: i64,
+    pub timestamp: Option<String>,
+    pub transaction_hash: String,
+    pub key: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[async_trait]
+impl Event for WithdrawalCancelled {
+    fn event_key() -> &'static str {
+        "0123e46ff00e99354cd600fed716e4d5ed6346a3b5ff71e771307cac571b479e"
+    }
+
+    fn from_generic_event(event: GenericEvent) -> Self {
+        let data_parts: Vec<Option<String>> =
+            event.data.split(',').map(|s| Some(s.to_string())).collect();
+        WithdrawalCancelled {
+            block_number: event.block_number,
+            timestamp: event.timestamp,
+            transaction_hash: event.transaction_hash,
+            key: data_parts.first().cloned().unwrap_or(None),
+            reason: data_parts.get(1).cloned().unwrap_or(None),
+        }
+    }
+
+    async fn insert(&self, store: &dyn Store) -> Result<(), sqlx::Error> {
+        store.insert_withdrawal_cancelled(self).await
+    }
+}
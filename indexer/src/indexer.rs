@@ -0,0 +1,527 @@
+use satoru_client::sentry;
+use sqlx::any::AnyPoolOptions;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::Error;
+use starknet::core::types::FieldElement;
+use starknet::providers::Provider;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::Duration;
+
+use crate::blockchain::head_chain::HeadChain;
+use crate::events::event::Event;
+use crate::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+use crate::store::postgres::PgStore;
+#[cfg(feature = "sqlite")]
+use crate::store::sqlite::SqliteStore;
+use crate::store::Store;
+use crate::{config, events, polling, provider, reconciliation};
+
+// A struct representing the shard an indexer run indexes.
+// @shard: The name of the shard, its cursor being kept under it.
+// @from_block: The first block of the shard.
+// @to_block: The last block of the shard, the shard following the chain head when None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexerParams {
+    pub shard: String,
+    pub from_block: u64,
+    pub to_block: Option<u64>,
+}
+
+impl IndexerParams {
+    pub fn from_env() -> Self {
+        IndexerParams {
+            shard: config::get_shard_name(),
+            from_block: config::get_from_block(),
+            to_block: config::get_to_block(),
+        }
+    }
+
+    // Returns the shard indexing a past block range once then stopping, named after the range so
+    // backfills of different ranges keep their own cursor.
+    // @from_block: The first block to index.
+    // @to_block: The last block to index.
+    pub fn backfill(from_block: u64, to_block: u64) -> Self {
+        IndexerParams {
+            shard: format!("backfill-{}-{}", from_block, to_block),
+            from_block,
+            to_block: Some(to_block),
+        }
+    }
+}
+
+// A processor of an indexed event.
+type Processor = Box<dyn events::handler::EventProcessor + Send + Sync>;
+
+// The families of events enabled or disabled together, named after what they index.
+pub const EVENT_FAMILIES: [(&str, &[&str]); 9] = [
+    (
+        "orders",
+        &[
+            "Order",
+            "OrderUpdated",
+            "OrderFrozen",
+            "OrderExecuted",
+            "OrderCancelled",
+        ],
+    ),
+    (
+        "deposits",
+        &["Deposit", "DepositExecuted", "DepositCancelled"],
+    ),
+    (
+        "withdrawals",
+        &["Withdrawal", "WithdrawalExecuted", "WithdrawalCancelled"],
+    ),
+    ("markets", &["MarketCreated", "MarketParamUpdated"]),
+    ("swaps", &["SwapInfo", "SwapFeesCollected"]),
+    ("pools", &["PoolAmountUpdated"]),
+    (
+        "funding",
+        &[
+            "FundingFeeAmountPerSizeUpdated",
+            "ClaimableFundingAmountPerSizeUpdated",
+        ],
+    ),
+    ("borrowing", &["CumulativeBorrowingFactorUpdated"]),
+    ("positions", &["PositionIncrease", "PositionDecrease"]),
+];
+
+// Expands the event families of a list of events, so families and events can be mixed. Names of
+// no family are kept as they are.
+// @names: The events and event families.
+pub fn expand_event_families(names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .flat_map(
+            |name| match EVENT_FAMILIES.iter().find(|(family, _)| family == name) {
+                Some((_, events)) => events.iter().map(|event| event.to_string()).collect(),
+                None => vec![name.clone()],
+            },
+        )
+        .collect()
+}
+
+// Keeps the processors of the indexed events minus the disabled ones, the events of the others
+// then never getting fetched, decoded nor stored.
+// @event_processors: The processors of every event.
+// @indexed_events: The events and event families indexed, every one when None.
+// @disabled_events: The events and event families never indexed.
+pub fn select_event_processors(
+    event_processors: &mut HashMap<&'static str, Processor>,
+    indexed_events: Option<&[String]>,
+    disabled_events: &[String],
+) -> Result<(), String> {
+    let disabled_events = expand_event_families(disabled_events);
+    if let Some(unknown) = disabled_events.iter().find(|event| {
+        !event_processors
+            .values()
+            .any(|processor| processor.event_name() == event.as_str())
+    }) {
+        return Err(format!(
+            "DISABLED_EVENTS entry {} is neither an event nor an event family",
+            unknown
+        ));
+    }
+    let indexed_events = indexed_events.map(expand_event_families);
+    if let Some(unknown) = indexed_events.iter().flatten().find(|event| {
+        !event_processors
+            .values()
+            .any(|processor| processor.event_name() == event.as_str())
+    }) {
+        return Err(format!(
+            "INDEXED_EVENTS entry {} is neither an event nor an event family",
+            unknown
+        ));
+    }
+    event_processors.retain(|_, processor| {
+        let name = processor.event_name();
+        indexed_events
+            .as_ref()
+            .is_none_or(|events| events.iter().any(|event| event == name))
+            && !disabled_events.iter().any(|event| event == name)
+    });
+    // Fetching events of no key would fetch every event.
+    if event_processors.is_empty() {
+        return Err("every INDEXED_EVENTS entry is disabled by DISABLED_EVENTS".to_owned());
+    }
+    Ok(())
+}
+
+// Returns the processors of every indexed event, by event key.
+pub fn get_event_processors(
+) -> HashMap<&'static str, Box<dyn events::handler::EventProcessor + Send + Sync>> {
+    let mut event_processors: HashMap<
+        &'static str,
+        Box<dyn events::handler::EventProcessor + Send + Sync>,
+    > = HashMap::new();
+    event_processors.insert(
+        Order::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<Order> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        Deposit::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<Deposit> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        Withdrawal::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<Withdrawal> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        MarketCreated::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<MarketCreated> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        SwapFeesCollected::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<SwapFeesCollected> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        SwapInfo::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<SwapInfo> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        PoolAmountUpdated::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<PoolAmountUpdated> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        OrderExecuted::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<OrderExecuted> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        OrderUpdated::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<OrderUpdated> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        OrderFrozen::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<OrderFrozen> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        OrderCancelled::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<OrderCancelled> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        FundingFeeAmountPerSizeUpdated::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<FundingFeeAmountPerSizeUpdated> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        ClaimableFundingAmountPerSizeUpdated::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<ClaimableFundingAmountPerSizeUpdated> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        CumulativeBorrowingFactorUpdated::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<CumulativeBorrowingFactorUpdated> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        MarketParamUpdated::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<MarketParamUpdated> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        PositionIncrease::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<PositionIncrease> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        PositionDecrease::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<PositionDecrease> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        DepositExecuted::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<DepositExecuted> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        DepositCancelled::event_key(),
+        Box::new(events::handler::GenericEventProcessor::<DepositCancelled> {
+            _marker: std::marker::PhantomData,
+        }),
+    );
+    event_processors.insert(
+        WithdrawalExecuted::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<WithdrawalExecuted> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors.insert(
+        WithdrawalCancelled::event_key(),
+        Box::new(
+            events::handler::GenericEventProcessor::<WithdrawalCancelled> {
+                _marker: std::marker::PhantomData,
+            },
+        ),
+    );
+    event_processors
+}
+
+// Opens the store of a database URL, with its pool for Postgres ones. The SQLite store of sqlite:
+// URLs, behind the sqlite feature, only indexes the events: orders are neither reconciled with the
+// DataStore nor notified.
+async fn open_store(database_url: &str) -> Result<(Box<dyn Store>, Option<PgPool>), Error> {
+    if database_url.starts_with("sqlite:") {
+        #[cfg(feature = "sqlite")]
+        return Ok((Box::new(SqliteStore::connect(database_url).await?), None));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(Error::Configuration(
+            "sqlite databases need the sqlite feature".into(),
+        ));
+    }
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(database_url)
+        .await?;
+    Ok((Box::new(PgStore::new(pool.clone())), Some(pool)))
+}
+
+// Keeps the shard live while it runs, long backfills included.
+// @head_chain: The cursor of the shard.
+// @interval: The delay between two heartbeats.
+async fn send_heartbeats(head_chain: HeadChain, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = head_chain.heartbeat().await {
+            eprintln!("Error sending the shard heartbeat: {:?}", e);
+        }
+    }
+}
+
+// Indexes the events of a shard, following the chain head once caught up unless the shard has a
+// block range.
+// @params: The shard to index and its block range.
+pub async fn run_indexer(params: IndexerParams) -> Result<(), Error> {
+    let database_url = crate::config::get_database_url();
+    // The store comes first, the SQLite one creating the tables of the cursors.
+    let (store, pool) = open_store(&database_url).await?;
+
+    let provider = provider::get_provider().unwrap();
+
+    let cursors = AnyPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_url)
+        .await?;
+    let head_chain = HeadChain::new(cursors, params.shard);
+    let last_block_indexed = head_chain.get_last_block_indexed().await?;
+    let latest_block_on_chain = provider
+        .block_number()
+        .await
+        .map_err(|e| sqlx::Error::Protocol(format!("{:?}", e)))?;
+
+    let start_block = if last_block_indexed > 0 {
+        last_block_indexed + 1
+    } else {
+        params.from_block as i64
+    };
+    let to_block = params.to_block;
+    if let Some(to_block) = to_block {
+        if params.from_block > to_block {
+            return Err(Error::Configuration(
+                format!(
+                    "FROM_BLOCK {} is after TO_BLOCK {}",
+                    params.from_block, to_block
+                )
+                .into(),
+            ));
+        }
+        // A block range shard resumed once done has nothing left to index.
+        if start_block > to_block as i64 {
+            return Ok(());
+        }
+    }
+
+    let mut event_processors = get_event_processors();
+
+    // Each shard only indexes its own events, shards share the cursor table to stay disjoint, and
+    // no shard indexes the disabled ones.
+    select_event_processors(
+        &mut event_processors,
+        config::get_indexed_events().as_deref(),
+        &config::get_disabled_events(),
+    )
+    .map_err(|e| Error::Configuration(e.into()))?;
+    let shard_events: Vec<&str> = event_processors
+        .values()
+        .map(|processor| processor.event_name())
+        .collect();
+    let shard_ttl_secs = config::get_shard_ttl_secs();
+    head_chain
+        .register_shard(
+            &shard_events,
+            params.from_block as i64,
+            to_block.map(|block| block as i64),
+            shard_ttl_secs as i64,
+        )
+        .await?;
+    tokio::spawn(send_heartbeats(
+        head_chain.clone(),
+        Duration::from_secs((shard_ttl_secs / 3).max(1)),
+    ));
+
+    let indexer = events::handler::EventIndexer::new(
+        &provider,
+        store.as_ref(),
+        event_processors,
+        head_chain,
+        to_block,
+    );
+
+    // Samples of the indexed orders get checked against the DataStore while indexing.
+    if let (Some(data_store), Some(pool)) = (config::get_data_store_address(), &pool) {
+        let data_store =
+            FieldElement::from_hex_be(&data_store).expect("DATA_STORE must be a valid address");
+        tokio::spawn(reconciliation::start_reconciliation(
+            provider::get_provider().unwrap(),
+            pool.clone(),
+            data_store,
+        ));
+    }
+
+    if start_block <= latest_block_on_chain as i64 {
+        if let Err(e) = indexer.fetch_and_process_events(start_block as u64).await {
+            eprintln!("Error fetching and processing events: {:?}", e);
+            sentry::capture_error(&format!("could not process events: {:?}", e), &[]);
+        } else {
+            println!("Initial fetch and process completed successfully.");
+        }
+    }
+
+    // Block range shards stop at the end of their range, only the last one follows pending blocks.
+    if to_block.is_some() {
+        return Ok(());
+    }
+
+    // Pending events get polled fast while they change, less and less often while they do not.
+    let wakeup = Arc::new(Notify::new());
+    if let (Some(channel), Some(pool)) = (config::get_poll_wake_channel(), &pool) {
+        tokio::spawn(polling::wake_on_notify(
+            pool.clone(),
+            channel,
+            Arc::clone(&wakeup),
+        ));
+    }
+    let mut backoff = polling::Backoff::new(
+        Duration::from_millis(config::get_poll_min_interval_ms()),
+        Duration::from_millis(config::get_poll_max_interval_ms()),
+    );
+    let mut pending_events = 0;
+    loop {
+        let active = match indexer.fetch_pending_events().await {
+            Ok(events) => {
+                let active = events != pending_events;
+                pending_events = events;
+                active
+            }
+            Err(e) => {
+                eprintln!("Error processing pending events: {:?}", e);
+                sentry::capture_error(&format!("could not process pending events: {:?}", e), &[]);
+                false
+            }
+        };
+        polling::wait(backoff.next(active), &wakeup).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(indexed_events: Option<&[String]>, disabled_events: &[String]) -> Vec<String> {
+        let mut event_processors = get_event_processors();
+        select_event_processors(&mut event_processors, indexed_events, disabled_events).unwrap();
+        let mut events: Vec<String> = event_processors
+            .values()
+            .map(|processor| processor.event_name().to_owned())
+            .collect();
+        events.sort();
+        events
+    }
+
+    #[test]
+    fn test_select_event_processors() {
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            selected(
+                Some(&names(&["positions", "SwapInfo"])),
+                &names(&["PositionDecrease"])
+            ),
+            names(&["PositionIncrease", "SwapInfo"])
+        );
+        assert_eq!(selected(None, &[]).len(), get_event_processors().len());
+
+        let mut event_processors = get_event_processors();
+        assert!(select_event_processors(
+            &mut event_processors,
+            Some(&names(&["OrderExecutd"])),
+            &[]
+        )
+        .is_err());
+        assert!(select_event_processors(
+            &mut event_processors,
+            Some(&names(&["swaps"])),
+            &names(&["SwapInfo", "SwapFeesCollected"])
+        )
+        .is_err());
+    }
+}
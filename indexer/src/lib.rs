@@ -0,0 +1,10 @@
+pub mod blockchain;
+pub mod config;
+pub mod events;
+pub mod indexer;
+pub mod loadtest;
+pub mod polling;
+pub mod provider;
+pub mod rebuild;
+pub mod reconciliation;
+pub mod store;
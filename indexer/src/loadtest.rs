@@ -0,0 +1,346 @@
+use sqlx::postgres::PgPoolOptions;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::config;
+use crate::events::event::{Event, GenericEvent};
+use crate::events::{
+    order::Order, order_cancelled::OrderCancelled, order_executed::OrderExecuted,
+    order_updated::OrderUpdated, position_decrease::PositionDecrease,
+    position_increase::PositionIncrease,
+};
+use crate::indexer::get_event_processors;
+use crate::store::postgres::PgStore;
+use crate::store::Store;
+
+// Order types as emitted, see events::order::OrderType.
+const MARKET_INCREASE: u64 = 2;
+const LIMIT_INCREASE: u64 = 3;
+const MARKET_DECREASE: u64 = 4;
+// Prefix of the synthetic keys, accounts and transaction hashes, so they are told apart from real ones.
+const SYNTHETIC_PREFIX: u64 = 0x10ad;
+// Number of events sharing a block.
+const EVENTS_PER_BLOCK: u64 = 10;
+// One USD with the protocol precision of 30 decimals.
+const ONE_USD: u128 = 1_000_000_000_000_000_000_000_000_000_000;
+
+// A struct holding the shape of a synthetic event stream.
+// @rate: The events generated per second.
+// @duration: How long events get generated for.
+// @accounts: The number of traders the orders get spread over.
+// @markets: The markets traded, as 64 char hex addresses.
+// @settlement_delay: How long orders wait for their execution or cancellation, as keepers take to
+// settle them.
+// @from_block: The block number of the first event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadParams {
+    pub rate: u64,
+    pub duration: Duration,
+    pub accounts: u64,
+    pub markets: Vec<String>,
+    pub settlement_delay: Duration,
+    pub from_block: i64,
+}
+
+impl LoadParams {
+    pub fn from_env() -> Self {
+        let markets = config::get_loadtest_markets();
+        LoadParams {
+            rate: config::get_loadtest_rate(),
+            duration: Duration::from_secs(config::get_loadtest_duration_secs()),
+            accounts: config::get_loadtest_accounts(),
+            markets: match markets.is_empty() {
+                true => vec![synthetic_felt(1, 0)],
+                false => markets,
+            },
+            settlement_delay: Duration::from_secs(config::get_loadtest_settlement_delay_secs()),
+            from_block: config::get_loadtest_from_block(),
+        }
+    }
+
+    // Number of events generated over the duration.
+    pub fn events(&self) -> u64 {
+        self.rate * self.duration.as_secs()
+    }
+}
+
+// Returns a 64 char hex felt starting with the synthetic prefix.
+// @kind: What the felt stands for, e.g. 1 for markets and 2 for accounts.
+// @index: The index of the felt among its kind.
+fn synthetic_felt(kind: u64, index: u64) -> String {
+    format!("{:04x}{:04x}{:056x}", SYNTHETIC_PREFIX, kind, index)
+}
+
+fn felt(value: u128) -> String {
+    format!("{:064x}", value)
+}
+
+// Splits an amount in the felts of its high and low 64 bits, as the order event emits them.
+fn u128_felts(value: u128) -> [String; 2] {
+    [felt(value >> 64), felt(value & u64::MAX as u128)]
+}
+
+// Splits an amount in the felts of its low and high 128 bits, as the u256 of the events.
+fn u256_felts(value: u128) -> [String; 2] {
+    [felt(value), felt(0)]
+}
+
+// The events settling an order, by event key, as their data felts.
+type Settlement = Vec<(&'static str, Vec<String>)>;
+
+// An iterator generating the lifecycle events of synthetic trades, by event key: market increases
+// created, executed and increasing a position, market decreases created, executed and decreasing
+// it, and limit increases created, updated then cancelled. Orders get settled the settlement delay
+// after their creation, trades overlapping in the meantime.
+pub struct SyntheticStream {
+    params: LoadParams,
+    // The xorshift state the trades get drawn from, the stream being the same on every run.
+    seed: u64,
+    trades: u64,
+    events: u64,
+    pending: VecDeque<(&'static str, GenericEvent)>,
+    // The settlement events of the created orders, with the number of events they are due after.
+    settling: VecDeque<(u64, Settlement)>,
+}
+
+impl SyntheticStream {
+    pub fn new(params: LoadParams) -> Self {
+        SyntheticStream {
+            params,
+            seed: 0x2545_f491_4f6c_dd1d,
+            trades: 0,
+            events: 0,
+            pending: VecDeque::new(),
+            settling: VecDeque::new(),
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    // Queues an event, keyed by its selector as the indexer fetches it.
+    fn event(&mut self, event_key: &'static str, data: Vec<String>) {
+        let block_number = self.params.from_block + (self.events / EVENTS_PER_BLOCK) as i64;
+        // Timestamps are the generation time, for the keeper to see fresh orders.
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before unix epoch")
+            .as_secs();
+        self.pending.push_back((
+            event_key,
+            GenericEvent {
+                block_number,
+                timestamp: Some(timestamp.to_string()),
+                transaction_hash: synthetic_felt(4, self.events),
+                key: Some(event_key.to_owned()),
+                data: data.join(","),
+                sender_address: Some(synthetic_felt(5, 0)),
+            },
+        ));
+        self.events += 1;
+    }
+
+    // Queues the creation of the next order, and schedules its settlement.
+    fn next_trade(&mut self) {
+        let trade = self.trades;
+        self.trades += 1;
+        let draw = self.next_random();
+        let account = synthetic_felt(2, draw % self.params.accounts);
+        let market = self.params.markets[(draw >> 16) as usize % self.params.markets.len()].clone();
+        let collateral_token = synthetic_felt(3, 0);
+        let order_key = synthetic_felt(6, trade);
+        let size_delta_usd = (1_000 + (draw >> 32) % 99_000) as u128 * ONE_USD;
+        let price = (1_000 + (draw >> 40) % 3_000) as u128 * ONE_USD / 10u128.pow(18);
+        let order_type = match draw % 20 {
+            0..=11 => MARKET_INCREASE,
+            12..=16 => MARKET_DECREASE,
+            _ => LIMIT_INCREASE,
+        };
+
+        let mut data = vec![
+            order_key.clone(),
+            order_key.clone(),
+            felt(order_type as u128),
+            felt(0),
+            account.clone(),
+            account.clone(),
+            felt(0),
+            felt(0),
+            market.clone(),
+            collateral_token.clone(),
+            felt(0),
+        ];
+        for amount in [
+            size_delta_usd,
+            size_delta_usd / price / 10,
+            price,
+            price,
+            1_000_000_000_000_000,
+            0,
+            0,
+        ] {
+            data.extend(u128_felts(amount));
+        }
+        data.extend([felt(0), felt(1), felt(0)]);
+        self.event(Order::event_key(), data);
+
+        let position = vec![account, market, collateral_token];
+        let settlement = match order_type {
+            LIMIT_INCREASE => {
+                let mut updated = vec![order_key.clone()];
+                for amount in [size_delta_usd, price, price, 0] {
+                    updated.extend(u256_felts(amount));
+                }
+                vec![
+                    (OrderUpdated::event_key(), updated),
+                    (OrderCancelled::event_key(), vec![order_key, felt(0)]),
+                ]
+            }
+            _ => {
+                let position_event = match order_type {
+                    MARKET_INCREASE => PositionIncrease::event_key(),
+                    _ => PositionDecrease::event_key(),
+                };
+                vec![
+                    (OrderExecuted::event_key(), vec![order_key, felt(0)]),
+                    (position_event, position),
+                ]
+            }
+        };
+        let delay = (self.params.settlement_delay.as_secs_f64() * self.params.rate as f64) as u64;
+        self.settling.push_back((self.events + delay, settlement));
+    }
+}
+
+impl Iterator for SyntheticStream {
+    type Item = (&'static str, GenericEvent);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            match self.settling.front() {
+                Some((due, _)) if *due <= self.events => {
+                    let (_, settlement) = self.settling.pop_front()?;
+                    for (event_key, data) in settlement {
+                        self.event(event_key, data);
+                    }
+                }
+                _ => self.next_trade(),
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+// A struct representing how the indexer kept up with a synthetic event stream.
+// @events: The events processed.
+// @failed: The events the store failed to insert.
+// @elapsed: How long processing all the events took.
+// @per_event: The events processed, by event name.
+// @latencies: How long each event took to process, sorted.
+// @max_lag: How far behind the generation schedule the processing got.
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub events: u64,
+    pub failed: u64,
+    pub elapsed: Duration,
+    pub per_event: BTreeMap<&'static str, u64>,
+    pub latencies: Vec<Duration>,
+    pub max_lag: Duration,
+}
+
+impl LoadReport {
+    // Returns the events processed per second.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            elapsed if elapsed > 0.0 => self.events as f64 / elapsed,
+            _ => 0.0,
+        }
+    }
+
+    // Returns the latency a share of the events got processed within, e.g. 0.99 for the p99.
+    // @quantile: The share of the events, between 0 and 1.
+    pub fn latency(&self, quantile: f64) -> Duration {
+        match self.latencies.len() {
+            0 => Duration::ZERO,
+            len => self.latencies[((len - 1) as f64 * quantile).round() as usize],
+        }
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} events in {:.1}s, {:.1} events/s, {} failed",
+            self.events,
+            self.elapsed.as_secs_f64(),
+            self.throughput(),
+            self.failed
+        )?;
+        writeln!(
+            f,
+            "latency p50 {:?}, p99 {:?}, max {:?}, max lag behind the stream {:?}",
+            self.latency(0.5),
+            self.latency(0.99),
+            self.latency(1.0),
+            self.max_lag
+        )?;
+        for (event, count) in &self.per_event {
+            writeln!(f, "  {}: {}", event, count)?;
+        }
+        Ok(())
+    }
+}
+
+// Feeds a synthetic event stream to the event processors of the indexer at the rate of the
+// params, as events fetched from the chain, and reports how it kept up. Events are processed one
+// at a time like the indexer does, so the lag grows once the rate is above what it sustains.
+// @params: The shape of the stream.
+// @store: The store the events get inserted into.
+pub async fn run_load_test(params: LoadParams, store: &dyn Store) -> LoadReport {
+    let processors = get_event_processors();
+    let rate = params.rate as f64;
+    let events = params.events();
+    let mut report = LoadReport::default();
+    let start = Instant::now();
+    for (index, (event_key, event)) in SyntheticStream::new(params)
+        .take(events as usize)
+        .enumerate()
+    {
+        let scheduled = start + Duration::from_secs_f64(index as f64 / rate);
+        sleep_until(scheduled).await;
+        let processor = match processors.get(event_key) {
+            Some(processor) => processor,
+            None => continue,
+        };
+        let started = Instant::now();
+        report.max_lag = report.max_lag.max(started - scheduled);
+        if let Err(e) = processor.process_event(event, store).await {
+            eprintln!("Error processing synthetic event: {:?}", e);
+            report.failed += 1;
+        }
+        report.latencies.push(started.elapsed());
+        *report.per_event.entry(processor.event_name()).or_default() += 1;
+        report.events += 1;
+    }
+    report.elapsed = start.elapsed();
+    report.latencies.sort();
+    report
+}
+
+// Runs the load test through the Postgres store of DATABASE_URL, as the indexer writes the events
+// the keepers get notified of.
+// @params: The shape of the stream.
+pub async fn run_database_load_test(params: LoadParams) -> Result<LoadReport, sqlx::Error> {
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&config::get_database_url())
+        .await?;
+    Ok(run_load_test(params, &PgStore::new(pool)).await)
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use sqlx::postgres::PgListener;
+use sqlx::{Pool, Postgres};
+use tokio::sync::Notify;
+
+// The adaptive polling interval of the pending events, shared with the keeper.
+pub use satoru_client::polling::{wait, Backoff};
+
+// Wakes the polling loop up on every notification of a PostgreSQL channel, e.g. one a block
+// notifier sends on every new block.
+// @pool: A connection pool for PostgreSQL.
+// @channel: The channel to listen to.
+// @wakeup: Notified on every notification.
+pub async fn wake_on_notify(pool: Pool<Postgres>, channel: String, wakeup: Arc<Notify>) {
+    let mut listener = match PgListener::connect_with(&pool).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not connect the wake up listener: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = listener.listen(&channel).await {
+        eprintln!("Could not listen to {}: {:?}", channel, e);
+        return;
+    }
+    loop {
+        match listener.recv().await {
+            Ok(_) => wakeup.notify_waiters(),
+            Err(e) => eprintln!("Wake up listener error: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::Error;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::blockchain::head_chain;
+use crate::events::event::GenericEvent;
+use crate::events::handler::EventProcessor;
+use crate::indexer::get_event_processors;
+use crate::store::postgres::PgStore;
+use crate::store::Store;
+
+// The tables derived from the archived events, wiped before a rebuild: the event tables, the
+// ledgers and history the store derives from them, and the daily aggregates the keeper rolls their
+// detail rows into. Views over them need no rebuild.
+pub const DERIVED_TABLES: [&str; 26] = [
+    "orders",
+    "deposits",
+    "withdrawals",
+    "market_created",
+    "swap_fees_collected",
+    "swap_info",
+    "pool_amount_updated",
+    "order_executed",
+    "order_cancelled",
+    "order_updated",
+    "order_frozen",
+    "deposit_executed",
+    "deposit_cancelled",
+    "withdrawal_executed",
+    "withdrawal_cancelled",
+    "funding_fee_amount_per_size_updated",
+    "claimable_funding_amount_per_size_updated",
+    "funding_payments",
+    "cumulative_borrowing_factor_updated",
+    "borrowing_fee_accruals",
+    "position_increase",
+    "position_decrease",
+    "market_params_history",
+    "archived_daily_funding",
+    "archived_daily_swap_volumes",
+    "archived_daily_swap_fees",
+];
+
+// Number of archived events loaded at once.
+const PAGE_SIZE: i64 = 1000;
+
+// A struct representing the outcome of a rebuild.
+// @events: The archived events replayed.
+// @skipped: The archived events no handler decodes, e.g. of events removed since.
+// @per_event: The events replayed per event name.
+// @last_block: The last block replayed, None when the archive had none up to the rebuild block.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RebuildReport {
+    pub events: u64,
+    pub skipped: u64,
+    pub per_event: BTreeMap<&'static str, u64>,
+    pub last_block: Option<i64>,
+}
+
+// Replays archived events through their handlers in the order given, so the same events always
+// derive the same rows.
+// @event_processors: The processors of every event, by event key.
+// @events: The archived events, in block order then archive order.
+// @store: The store the derived rows get written to.
+// @report: The report the replayed events get counted in.
+pub async fn replay_events(
+    event_processors: &HashMap<&'static str, Box<dyn EventProcessor + Send + Sync>>,
+    events: Vec<GenericEvent>,
+    store: &dyn Store,
+    report: &mut RebuildReport,
+) -> Result<(), Error> {
+    for event in events {
+        let block_number = event.block_number;
+        let processor = event
+            .key
+            .as_deref()
+            .and_then(|key| event_processors.get(key));
+        match processor {
+            Some(processor) => {
+                processor.process_event(event, store).await?;
+                report.events += 1;
+                *report.per_event.entry(processor.event_name()).or_default() += 1;
+            }
+            None => report.skipped += 1,
+        }
+        report.last_block = Some(block_number);
+    }
+    Ok(())
+}
+
+// A row of the raw_events archive.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ArchivedEvent {
+    id: i64,
+    block_number: i64,
+    time_stamp: Option<String>,
+    transaction_hash: String,
+    key: String,
+    data: String,
+    sender_address: Option<String>,
+}
+
+// Loads a page of the archived events up to a block, after an event.
+// @pool: The database the archive is in.
+// @to_block: The last block loaded.
+// @after: The block and archive id of the last event loaded, every event being after (0, 0).
+async fn get_archived_events(
+    pool: &PgPool,
+    to_block: i64,
+    after: (i64, i64),
+) -> Result<Vec<ArchivedEvent>, Error> {
+    sqlx::query_as::<_, ArchivedEvent>(
+        "SELECT id, block_number, time_stamp, transaction_hash, key, data, sender_address
+         FROM raw_events
+         WHERE block_number <= $1 AND (block_number, id) > ($2, $3)
+         ORDER BY block_number, id
+         LIMIT $4",
+    )
+    .bind(to_block)
+    .bind(after.0)
+    .bind(after.1)
+    .bind(PAGE_SIZE)
+    .fetch_all(pool)
+    .await
+}
+
+// Wipes the derived tables and rebuilds them from the archived events up to a block, their rows
+// then only depending on the archive and the tokens table amounts get normalized with. The cursor
+// of every shard is moved back to the block, so the indexers resume after it. Refused while a
+// shard is live, the cursor table staying locked until the rebuild is done so no shard starts or
+// moves its cursor meanwhile. The keepers should be stopped too, the replayed inserts notifying
+// them as new events.
+// @pool: The database the archive and derived tables are in.
+// @to_block: The last block rebuilt.
+// @shard_ttl_secs: How long a shard without heartbeat is still considered live.
+pub async fn rebuild_derived_tables(
+    pool: &PgPool,
+    to_block: i64,
+    shard_ttl_secs: i64,
+) -> Result<RebuildReport, Error> {
+    let mut cursors = pool.begin().await?;
+    sqlx::query("LOCK TABLE indexer_cursors IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut cursors)
+        .await?;
+    let live_shards: Vec<String> =
+        sqlx::query_scalar("SELECT shard FROM indexer_cursors WHERE heartbeat_at > $1")
+            .bind(head_chain::now() - shard_ttl_secs)
+            .fetch_all(&mut cursors)
+            .await?;
+    if !live_shards.is_empty() {
+        return Err(Error::Configuration(
+            format!(
+                "shards {} are live, stop them before rebuilding",
+                live_shards.join(", ")
+            )
+            .into(),
+        ));
+    }
+
+    sqlx::query(&format!("TRUNCATE {}", DERIVED_TABLES.join(", ")))
+        .execute(pool)
+        .await?;
+    let event_processors = get_event_processors();
+    let store = PgStore::new(pool.clone());
+    let mut report = RebuildReport::default();
+    let mut after = (0, 0);
+    loop {
+        let page = get_archived_events(pool, to_block, after).await?;
+        let last = match page.last() {
+            Some(event) => (event.block_number, event.id),
+            None => break,
+        };
+        let events = page
+            .into_iter()
+            .map(|event| GenericEvent {
+                block_number: event.block_number,
+                timestamp: event.time_stamp,
+                transaction_hash: event.transaction_hash,
+                key: Some(event.key),
+                data: event.data,
+                sender_address: event.sender_address,
+            })
+            .collect();
+        replay_events(&event_processors, events, &store, &mut report).await?;
+        println!("Rebuilt {} events up to block {}", report.events, last.0);
+        after = last;
+    }
+    sqlx::query(
+        "UPDATE indexer_cursors SET block_number = LEAST(block_number, $1), updated_at = NOW()",
+    )
+    .bind(to_block)
+    .execute(&mut cursors)
+    .await?;
+    sqlx::query("UPDATE last_indexed_block SET block_number = LEAST(block_number, $1)")
+        .bind(to_block)
+        .execute(&mut cursors)
+        .await?;
+    cursors.commit().await?;
+    Ok(report)
+}
+
+// Runs a rebuild of the derived tables up to a block, printing what got replayed.
+// @to_block: The last block rebuilt.
+pub async fn run_rebuild(to_block: u64) -> Result<(), Error> {
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&crate::config::get_database_url())
+        .await?;
+    let report = rebuild_derived_tables(
+        &pool,
+        to_block as i64,
+        crate::config::get_shard_ttl_secs() as i64,
+    )
+    .await?;
+    println!(
+        "Rebuilt the derived tables from {} archived events up to block {}, {} skipped",
+        report.events,
+        report.last_block.unwrap_or_default(),
+        report.skipped
+    );
+    for (event, count) in &report.per_event {
+        println!("{}: {}", event, count);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event::Event;
+    use crate::events::{order_cancelled::OrderCancelled, order_executed::OrderExecuted};
+    use crate::store::memory::MemoryStore;
+    use serde_json::Value;
+
+    fn archived_event(block_number: i64, key: &str, data: &str) -> GenericEvent {
+        GenericEvent {
+            block_number,
+            timestamp: Some((1_700_000_000 + block_number).to_string()),
+            transaction_hash: format!("{:02x}", block_number),
+            key: Some(key.to_owned()),
+            data: data.to_owned(),
+            sender_address: Some("0b".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_events() {
+        let events = vec![
+            archived_event(10, OrderExecuted::event_key(), "01,02"),
+            archived_event(11, OrderCancelled::event_key(), "03,00"),
+            archived_event(12, "ff", "00"),
+        ];
+        let processors = get_event_processors();
+        let rebuild = || async {
+            let store = MemoryStore::default();
+            let mut report = RebuildReport::default();
+            replay_events(&processors, events.clone(), &store, &mut report)
+                .await
+                .unwrap();
+            (store, report)
+        };
+        let (store, report) = rebuild().await;
+        assert_eq!(report.events, 2);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.per_event.get("OrderExecuted"), Some(&1));
+        assert_eq!(report.last_block, Some(12));
+        let executed: Vec<OrderExecuted> = store.select("order_executed");
+        assert_eq!(executed[0].keeper.as_deref(), Some("0b"));
+        assert_eq!(store.count("raw_events"), 0);
+
+        // The same archive derives the same rows.
+        let (other, _) = rebuild().await;
+        for table in ["order_executed", "order_cancelled"] {
+            assert_eq!(store.select::<Value>(table), other.select::<Value>(table));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::BigDecimal;
+use satoru_client::sentry::capture_error;
+use sqlx::postgres::PgPool;
+use starknet::core::types::{BlockId, BlockTag, FieldElement, FunctionCall};
+use starknet::core::utils::get_selector_from_name;
+use starknet::providers::jsonrpc::{HttpTransport, JsonRpcClient};
+use starknet::providers::Provider;
+use tokio::time::{sleep, Duration};
+
+use crate::config::{get_reconciliation_interval_secs, get_reconciliation_sample_size};
+use crate::events::order::OrderType;
+
+// A struct representing a field of an indexed order disagreeing with the DataStore.
+// @key: The key of the order.
+// @field: The name of the field.
+// @indexed: The value stored in the database.
+// @on_chain: The value read from the DataStore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub key: String,
+    pub field: String,
+    pub indexed: String,
+    pub on_chain: String,
+}
+
+// A struct representing the fields of an indexed order checked against the DataStore.
+#[derive(Debug, Clone, Default)]
+pub struct IndexedOrder {
+    pub key: String,
+    pub order_type: Option<String>,
+    pub account: Option<String>,
+    pub receiver: Option<String>,
+    pub callback_contract: Option<String>,
+    pub ui_fee_receiver: Option<String>,
+    pub market: Option<String>,
+    pub initial_collateral_token: Option<String>,
+    pub size_delta_usd: Option<BigDecimal>,
+    pub initial_collateral_delta_amount: Option<BigDecimal>,
+    pub trigger_price: Option<BigDecimal>,
+    pub acceptable_price: Option<BigDecimal>,
+    pub callback_gas_limit: Option<BigDecimal>,
+    pub min_output_amount: Option<BigDecimal>,
+    pub is_long: Option<bool>,
+    // Whether the order got updated since created, its sizes and prices then differing legitimately.
+    pub updated: bool,
+}
+
+// Reads the felt at an index of a DataStore struct.
+fn felt_at(data: &[FieldElement], index: usize) -> Option<FieldElement> {
+    data.get(index).copied()
+}
+
+// Reads the u256 made of the low and high felts at an index of a DataStore struct.
+fn u256_at(data: &[FieldElement], index: usize) -> Option<BigDecimal> {
+    let low = BigInt::from_bytes_be(Sign::Plus, &felt_at(data, index)?.to_bytes_be());
+    let high = BigInt::from_bytes_be(Sign::Plus, &felt_at(data, index + 1)?.to_bytes_be());
+    Some(BigDecimal::from((high << 128) + low))
+}
+
+fn same_felt(indexed: &str, on_chain: FieldElement) -> bool {
+    FieldElement::from_hex_be(indexed).is_ok_and(|indexed| indexed == on_chain)
+}
+
+// Compares an indexed order with the Order struct returned by DataStore.get_order, returns the
+// mismatching fields.
+// @order: The indexed order.
+// @data: The serialized Order struct.
+pub fn compare_order(order: &IndexedOrder, data: &[FieldElement]) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    let mut flag = |field: &str, indexed: String, on_chain: String| {
+        mismatches.push(Mismatch {
+            key: order.key.clone(),
+            field: field.to_owned(),
+            indexed,
+            on_chain,
+        })
+    };
+
+    let addresses = [
+        ("account", &order.account, 3),
+        ("receiver", &order.receiver, 4),
+        ("callback_contract", &order.callback_contract, 5),
+        ("ui_fee_receiver", &order.ui_fee_receiver, 6),
+        ("market", &order.market, 7),
+        (
+            "initial_collateral_token",
+            &order.initial_collateral_token,
+            8,
+        ),
+    ];
+    for (field, indexed, index) in addresses {
+        if let (Some(indexed), Some(on_chain)) = (indexed, felt_at(data, index)) {
+            if !same_felt(indexed, on_chain) {
+                flag(field, indexed.clone(), format!("{:#x}", on_chain));
+            }
+        }
+    }
+    if let Some(on_chain) = felt_at(data, 1) {
+        let on_chain = format!("{:064x}", on_chain)
+            .parse::<OrderType>()
+            .map_or(format!("{:#x}", on_chain), |order_type| {
+                format!("{:?}", order_type)
+            });
+        if order.order_type.as_ref() != Some(&on_chain) {
+            flag(
+                "order_type",
+                order.order_type.clone().unwrap_or_default(),
+                on_chain,
+            );
+        }
+    }
+
+    // The amounts follow the swap path, serialized as its length and its addresses.
+    let swap_path_len = match felt_at(data, 9).and_then(|len| u32::try_from(len).ok()) {
+        Some(len) => len as usize,
+        None => return mismatches,
+    };
+    let amounts_at = 10 + swap_path_len;
+    let mut amounts = vec![
+        (
+            "initial_collateral_delta_amount",
+            &order.initial_collateral_delta_amount,
+            amounts_at + 2,
+        ),
+        (
+            "callback_gas_limit",
+            &order.callback_gas_limit,
+            amounts_at + 10,
+        ),
+    ];
+    if !order.updated {
+        amounts.extend([
+            ("size_delta_usd", &order.size_delta_usd, amounts_at),
+            ("trigger_price", &order.trigger_price, amounts_at + 4),
+            ("acceptable_price", &order.acceptable_price, amounts_at + 6),
+            (
+                "min_output_amount",
+                &order.min_output_amount,
+                amounts_at + 12,
+            ),
+        ]);
+    }
+    for (field, indexed, index) in amounts {
+        if let (Some(indexed), Some(on_chain)) = (indexed, u256_at(data, index)) {
+            if *indexed != on_chain {
+                flag(field, indexed.to_string(), on_chain.to_string());
+            }
+        }
+    }
+    if let (Some(indexed), Some(on_chain)) = (order.is_long, felt_at(data, amounts_at + 15)) {
+        if indexed != (on_chain == FieldElement::ONE) {
+            flag(
+                "is_long",
+                indexed.to_string(),
+                (on_chain == FieldElement::ONE).to_string(),
+            );
+        }
+    }
+    mismatches
+}
+
+// Samples the most recent orders still pending, the DataStore removing settled ones.
+async fn sample_pending_orders(
+    pool: &PgPool,
+    sample_size: i64,
+) -> Result<Vec<IndexedOrder>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT felt_out(o.key) AS "key!", o.order_type, felt_out(o.account) AS account, o.receiver,
+            o.callback_contract, o.ui_fee_receiver, felt_out(o.market) AS market, o.initial_collateral_token,
+            o.size_delta_usd, o.initial_collateral_delta_amount, o.trigger_price, o.acceptable_price,
+            o.callback_gas_limit, o.min_output_amount, o.is_long,
+            EXISTS (SELECT 1 FROM order_updated u WHERE u.key = o.key) AS "updated!"
+        FROM orders o
+        WHERE o.key IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM order_executed e WHERE e.key = o.key)
+            AND NOT EXISTS (SELECT 1 FROM order_cancelled c WHERE c.key = o.key)
+        ORDER BY o.block_number DESC
+        LIMIT $1"#,
+        sample_size
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| IndexedOrder {
+            key: row.key,
+            order_type: row.order_type,
+            account: row.account,
+            receiver: row.receiver,
+            callback_contract: row.callback_contract,
+            ui_fee_receiver: row.ui_fee_receiver,
+            market: row.market,
+            initial_collateral_token: row.initial_collateral_token,
+            size_delta_usd: row.size_delta_usd,
+            initial_collateral_delta_amount: row.initial_collateral_delta_amount,
+            trigger_price: row.trigger_price,
+            acceptable_price: row.acceptable_price,
+            callback_gas_limit: row.callback_gas_limit,
+            min_output_amount: row.min_output_amount,
+            is_long: row.is_long,
+            updated: row.updated,
+        })
+        .collect())
+}
+
+async fn record_mismatch(pool: &PgPool, mismatch: &Mismatch) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO reconciliation_mismatches (key, field, indexed, on_chain) VALUES ($1, $2, $3, $4)",
+        mismatch.key,
+        mismatch.field,
+        mismatch.indexed,
+        mismatch.on_chain
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Checks a sample of recent pending orders against the DataStore, recording every mismatching
+// field. Returns the number of mismatches found.
+// @provider: The provider used to read the DataStore.
+// @pool: The database pool.
+// @data_store: The DataStore address.
+pub async fn reconcile_orders(
+    provider: &JsonRpcClient<HttpTransport>,
+    pool: &PgPool,
+    data_store: FieldElement,
+) -> Result<usize, sqlx::Error> {
+    let mut mismatches = 0;
+    for order in sample_pending_orders(pool, get_reconciliation_sample_size()).await? {
+        let key = match FieldElement::from_hex_be(&order.key) {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        let call = FunctionCall {
+            contract_address: data_store,
+            entry_point_selector: get_selector_from_name("get_order").expect("Invalid selector"),
+            calldata: vec![key],
+        };
+        let data = match provider.call(call, BlockId::Tag(BlockTag::Latest)).await {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!(
+                    "Could not read order {} from the DataStore: {:?}",
+                    order.key, e
+                );
+                continue;
+            }
+        };
+        // Orders settled since the sample was taken read as empty.
+        if felt_at(&data, 0) != Some(key) {
+            continue;
+        }
+        for mismatch in compare_order(&order, &data) {
+            eprintln!("ALERT: indexed order mismatch {:?}", mismatch);
+            capture_error(
+                &format!(
+                    "indexed order {} mismatches the DataStore on {}: {} indexed, {} on chain",
+                    mismatch.key, mismatch.field, mismatch.indexed, mismatch.on_chain
+                ),
+                &[("order_key", &mismatch.key)],
+            );
+            record_mismatch(pool, &mismatch).await?;
+            mismatches += 1;
+        }
+    }
+    Ok(mismatches)
+}
+
+// Periodically reconciles the indexed orders with the DataStore, catching decoder drift.
+pub async fn start_reconciliation(
+    provider: JsonRpcClient<HttpTransport>,
+    pool: PgPool,
+    data_store: FieldElement,
+) {
+    loop {
+        match reconcile_orders(&provider, &pool, data_store).await {
+            Ok(mismatches) => println!("Reconciliation found {} mismatches", mismatches),
+            Err(e) => eprintln!("Error reconciling orders: {:?}", e),
+        }
+        sleep(Duration::from_secs(get_reconciliation_interval_secs())).await;
+    }
+}
@@ -0,0 +1,431 @@
+use crate::events::event::GenericEvent;
+use crate::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+use crate::store::Store;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// An in-memory store keeping the inserted events per table, named as in sql/db_setup.sql, so the
+// decoders and the indexing pipeline get tested without a database.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tables: Mutex<HashMap<&'static str, Vec<Value>>>,
+}
+
+impl MemoryStore {
+    fn push<T: Serialize>(&self, table: &'static str, event: &T) -> Result<(), sqlx::Error> {
+        let row = serde_json::to_value(event).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        self.tables
+            .lock()
+            .unwrap()
+            .entry(table)
+            .or_default()
+            .push(row);
+        Ok(())
+    }
+
+    // Returns the number of events inserted in a table.
+    pub fn count(&self, table: &str) -> usize {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(table)
+            .map_or(0, |rows| rows.len())
+    }
+
+    // Returns the events of a table matching a predicate, in insertion order.
+    pub fn select_where<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        predicate: impl Fn(&T) -> bool,
+    ) -> Vec<T> {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(table)
+            .into_iter()
+            .flatten()
+            .map(|row| serde_json::from_value(row.clone()).expect("Invalid stored event"))
+            .filter(|event| predicate(event))
+            .collect()
+    }
+
+    // Returns every event of a table, in insertion order.
+    pub fn select<T: DeserializeOwned>(&self, table: &str) -> Vec<T> {
+        self.select_where(table, |_| true)
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn archive_event(&self, event: &GenericEvent) -> Result<(), sqlx::Error> {
+        self.push("raw_events", event)
+    }
+
+    async fn insert_order(&self, event: &Order) -> Result<(), sqlx::Error> {
+        self.push("orders", event)
+    }
+
+    async fn insert_deposit(&self, event: &Deposit) -> Result<(), sqlx::Error> {
+        self.push("deposits", event)
+    }
+
+    async fn insert_withdrawal(&self, event: &Withdrawal) -> Result<(), sqlx::Error> {
+        self.push("withdrawals", event)
+    }
+
+    async fn insert_market_created(&self, event: &MarketCreated) -> Result<(), sqlx::Error> {
+        self.push("market_created", event)
+    }
+
+    async fn insert_swap_fees_collected(
+        &self,
+        event: &SwapFeesCollected,
+    ) -> Result<(), sqlx::Error> {
+        self.push("swap_fees_collected", event)
+    }
+
+    async fn insert_swap_info(&self, event: &SwapInfo) -> Result<(), sqlx::Error> {
+        self.push("swap_info", event)
+    }
+
+    async fn insert_pool_amount_updated(
+        &self,
+        event: &PoolAmountUpdated,
+    ) -> Result<(), sqlx::Error> {
+        self.push("pool_amount_updated", event)
+    }
+
+    async fn insert_order_executed(&self, event: &OrderExecuted) -> Result<(), sqlx::Error> {
+        self.push("order_executed", event)
+    }
+
+    async fn insert_order_updated(&self, event: &OrderUpdated) -> Result<(), sqlx::Error> {
+        self.push("order_updated", event)
+    }
+
+    async fn insert_order_frozen(&self, event: &OrderFrozen) -> Result<(), sqlx::Error> {
+        self.push("order_frozen", event)
+    }
+
+    async fn insert_order_cancelled(&self, event: &OrderCancelled) -> Result<(), sqlx::Error> {
+        self.push("order_cancelled", event)
+    }
+
+    async fn insert_deposit_executed(&self, event: &DepositExecuted) -> Result<(), sqlx::Error> {
+        self.push("deposit_executed", event)
+    }
+
+    async fn insert_deposit_cancelled(&self, event: &DepositCancelled) -> Result<(), sqlx::Error> {
+        self.push("deposit_cancelled", event)
+    }
+
+    async fn insert_withdrawal_executed(
+        &self,
+        event: &WithdrawalExecuted,
+    ) -> Result<(), sqlx::Error> {
+        self.push("withdrawal_executed", event)
+    }
+
+    async fn insert_withdrawal_cancelled(
+        &self,
+        event: &WithdrawalCancelled,
+    ) -> Result<(), sqlx::Error> {
+        self.push("withdrawal_cancelled", event)
+    }
+
+    async fn insert_funding_fee_amount_per_size_updated(
+        &self,
+        event: &FundingFeeAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        self.push("funding_fee_amount_per_size_updated", event)
+    }
+
+    async fn insert_claimable_funding_amount_per_size_updated(
+        &self,
+        event: &ClaimableFundingAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        self.push("claimable_funding_amount_per_size_updated", event)
+    }
+
+    async fn insert_cumulative_borrowing_factor_updated(
+        &self,
+        event: &CumulativeBorrowingFactorUpdated,
+    ) -> Result<(), sqlx::Error> {
+        self.push("cumulative_borrowing_factor_updated", event)
+    }
+
+    async fn insert_position_increase(&self, event: &PositionIncrease) -> Result<(), sqlx::Error> {
+        self.push("position_increase", event)
+    }
+
+    async fn insert_position_decrease(&self, event: &PositionDecrease) -> Result<(), sqlx::Error> {
+        self.push("position_decrease", event)
+    }
+
+    async fn insert_market_param_updated(
+        &self,
+        event: &MarketParamUpdated,
+    ) -> Result<(), sqlx::Error> {
+        self.push("market_params_history", event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event::Event;
+    use crate::events::handler::{EventProcessor, GenericEventProcessor};
+
+    fn generic_event(data: &str) -> GenericEvent {
+        GenericEvent {
+            block_number: 10,
+            timestamp: Some("1700000000".to_owned()),
+            transaction_hash: "0a".to_owned(),
+            key: None,
+            data: data.to_owned(),
+            sender_address: Some("0b".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_select() {
+        let store = MemoryStore::default();
+        for key in ["01", "02"] {
+            OrderCancelled::from_generic_event(generic_event(&format!("{},00", key)))
+                .insert(&store)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.count("order_cancelled"), 2);
+        assert_eq!(store.count("orders"), 0);
+        let cancelled: Vec<OrderCancelled> = store
+            .select_where("order_cancelled", |event: &OrderCancelled| {
+                event.key.as_deref() == Some("02")
+            });
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].block_number, 10);
+    }
+
+    #[tokio::test]
+    async fn test_process_event() {
+        let store = MemoryStore::default();
+        let processor = GenericEventProcessor::<OrderExecuted> {
+            _marker: std::marker::PhantomData,
+        };
+        processor
+            .process_event(generic_event("01,02"), &store)
+            .await
+            .unwrap();
+
+        let executed: Vec<OrderExecuted> = store.select("order_executed");
+        assert_eq!(executed.len(), 1);
+        assert_eq!(executed[0].key.as_deref(), Some("01"));
+        assert_eq!(executed[0].secondary_order_type.as_deref(), Some("02"));
+        assert_eq!(executed[0].keeper.as_deref(), Some("0b"));
+    }
+
+    #[tokio::test]
+    async fn test_market_param_updated() {
+        use bigdecimal::{num_bigint::BigInt, BigDecimal};
+
+        // max_open_interest, for the long side, set to 2^128 + 5.
+        let store = MemoryStore::default();
+        MarketParamUpdated::from_generic_event(generic_event(
+            "0c,6d61785f6f70656e5f696e746572657374,01,05,01",
+        ))
+        .insert(&store)
+        .await
+        .unwrap();
+        MarketParamUpdated::from_generic_event(generic_event("0c,72657365727665,02,0a,00,ff"))
+            .insert(&store)
+            .await
+            .unwrap();
+
+        let params: Vec<MarketParamUpdated> = store.select("market_params_history");
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].parameter.as_deref(), Some("max_open_interest"));
+        assert_eq!(params[0].is_long, Some(true));
+        assert_eq!(
+            params[0].value,
+            Some(BigDecimal::from((BigInt::from(1) << 128) + 5))
+        );
+        // A parameter of both sides, the trailing felt being ignored.
+        assert_eq!(params[1].parameter.as_deref(), Some("reserve"));
+        assert_eq!(params[1].is_long, None);
+        assert_eq!(params[1].value, Some(10.into()));
+    }
+
+    #[tokio::test]
+    async fn test_position_execution() {
+        use crate::events::decimals::{Price, TokenAmount, Usd};
+        use bigdecimal::BigDecimal;
+
+        // The identity, then 7 u256 of the position state and factors before the execution price.
+        let mut data = vec!["0a", "0b", "0c"];
+        data.extend(["00"; 14]);
+        data.extend(["07d0", "00"]);
+        data.extend(["00"; 8]);
+        data.extend(["64", "00", "02", "00"]);
+        let store = MemoryStore::default();
+        PositionIncrease::from_generic_event(generic_event(&data.join(",")))
+            .insert(&store)
+            .await
+            .unwrap();
+        // The identity only, as decoded before the executions were.
+        PositionDecrease::from_generic_event(generic_event("0a,0b,0c"))
+            .insert(&store)
+            .await
+            .unwrap();
+
+        let increases: Vec<PositionIncrease> = store.select("position_increase");
+        assert_eq!(increases[0].market.as_deref(), Some("0b"));
+        assert_eq!(
+            increases[0].execution_price,
+            Some(Price::from_raw(BigDecimal::from(2000)))
+        );
+        assert_eq!(
+            increases[0].size_delta_usd,
+            Some(Usd::from_raw(BigDecimal::from(100)))
+        );
+        assert_eq!(
+            increases[0].size_delta_in_tokens,
+            Some(TokenAmount::from_raw(BigDecimal::from(2)))
+        );
+        let decreases: Vec<PositionDecrease> = store.select("position_decrease");
+        assert_eq!(decreases[0].collateral_token.as_deref(), Some("0c"));
+        assert_eq!(decreases[0].execution_price, None);
+    }
+
+    #[tokio::test]
+    async fn test_deposit_amounts() {
+        use crate::events::decimals::TokenAmount;
+
+        // 10 ETH of long tokens, past i64 once carrying 18 decimals, and the amounts as hex felts.
+        let data = "0a,0b,0c,0d,0e,0f,00,00,8ac7230489e80000,0f4240,00,2a,38d7ea4c68000,00";
+        let store = MemoryStore::default();
+        Deposit::from_generic_event(generic_event(data))
+            .insert(&store)
+            .await
+            .unwrap();
+
+        let deposits: Vec<Deposit> = store.select("deposits");
+        let amount = |raw: u64| Some(TokenAmount::from_raw(raw.into()));
+        assert_eq!(
+            deposits[0].initial_long_token_amount,
+            amount(10_000_000_000_000_000_000)
+        );
+        assert_eq!(deposits[0].initial_short_token_amount, amount(1_000_000));
+        assert_eq!(deposits[0].updated_at_block, Some(42));
+        assert_eq!(deposits[0].execution_fee, amount(1_000_000_000_000_000));
+    }
+
+    #[test]
+    fn test_select_event_processors() {
+        use crate::indexer::{get_event_processors, select_event_processors, EVENT_FAMILIES};
+
+        // Every event is in exactly one family.
+        let processors = get_event_processors();
+        let family_events: Vec<&str> = EVENT_FAMILIES
+            .iter()
+            .flat_map(|(_, events)| events.iter().copied())
+            .collect();
+        assert_eq!(family_events.len(), processors.len());
+        assert!(processors
+            .values()
+            .all(|processor| family_events.contains(&processor.event_name())));
+
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut processors = get_event_processors();
+        select_event_processors(
+            &mut processors,
+            Some(&names(&["orders", "Deposit"])),
+            &names(&["OrderFrozen"]),
+        )
+        .unwrap();
+        let mut indexed: Vec<&str> = processors
+            .values()
+            .map(|processor| processor.event_name())
+            .collect();
+        indexed.sort();
+        assert_eq!(
+            indexed,
+            vec![
+                "Deposit",
+                "Order",
+                "OrderCancelled",
+                "OrderExecuted",
+                "OrderUpdated"
+            ]
+        );
+
+        let mut processors = get_event_processors();
+        select_event_processors(&mut processors, None, &names(&["swaps", "pools"])).unwrap();
+        assert_eq!(processors.len(), family_events.len() - 3);
+        assert!(select_event_processors(&mut processors, None, &names(&["Swaps"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_test() {
+        use crate::events::decimals::Usd;
+        use crate::events::order::OrderType;
+        use crate::loadtest::{run_load_test, LoadParams};
+        use tokio::time::Duration;
+
+        let store = MemoryStore::default();
+        let params = LoadParams {
+            rate: 200,
+            duration: Duration::from_secs(1),
+            accounts: 10,
+            markets: vec![format!("{:064x}", 1)],
+            settlement_delay: Duration::from_millis(100),
+            from_block: 1_000_000_000,
+        };
+        let report = run_load_test(params, &store).await;
+
+        assert_eq!(report.events, 200);
+        assert_eq!(report.failed, 0);
+        assert_eq!(report.latencies.len(), 200);
+        assert_eq!(report.per_event.values().sum::<u64>(), 200);
+        // Orders get settled 20 events after their creation, the last ones staying pending.
+        let orders: Vec<Order> = store.select("orders");
+        let settled = store.count("order_executed") + store.count("order_cancelled");
+        assert!(settled < orders.len() && settled + 20 >= orders.len());
+        for order in &orders {
+            assert!(matches!(
+                order.order_type,
+                Some(
+                    OrderType::MarketIncrease
+                        | OrderType::MarketDecrease
+                        | OrderType::LimitIncrease
+                )
+            ));
+            assert_eq!(
+                order.market.as_deref(),
+                Some(format!("{:064x}", 1).as_str())
+            );
+            let size = order.size_delta_usd.as_ref().map(Usd::to_usd).unwrap();
+            assert!(size >= 1_000.into() && size < 100_000.into());
+            assert_eq!(order.is_long, Some(true));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(test)]
+pub mod memory;
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::events::decimals::{Usd, MARKET_TOKEN_DECIMALS};
+use crate::events::event::GenericEvent;
+use crate::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+// Where the decoded events get stored, so decoders do not depend on a database. Every event type
+// has its own insert, a store being free to derive tables or aggregates from it.
+#[async_trait]
+pub trait Store: Send + Sync {
+    // Archives a fetched event as handed to its handler, every derived table getting rebuilt from
+    // the archive by rebuild::rebuild_derived_tables.
+    async fn archive_event(&self, event: &GenericEvent) -> Result<(), sqlx::Error>;
+    async fn insert_order(&self, event: &Order) -> Result<(), sqlx::Error>;
+    async fn insert_deposit(&self, event: &Deposit) -> Result<(), sqlx::Error>;
+    async fn insert_withdrawal(&self, event: &Withdrawal) -> Result<(), sqlx::Error>;
+    async fn insert_market_created(&self, event: &MarketCreated) -> Result<(), sqlx::Error>;
+    async fn insert_swap_fees_collected(
+        &self,
+        event: &SwapFeesCollected,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_swap_info(&self, event: &SwapInfo) -> Result<(), sqlx::Error>;
+    async fn insert_pool_amount_updated(
+        &self,
+        event: &PoolAmountUpdated,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_order_executed(&self, event: &OrderExecuted) -> Result<(), sqlx::Error>;
+    async fn insert_order_updated(&self, event: &OrderUpdated) -> Result<(), sqlx::Error>;
+    async fn insert_order_frozen(&self, event: &OrderFrozen) -> Result<(), sqlx::Error>;
+    async fn insert_order_cancelled(&self, event: &OrderCancelled) -> Result<(), sqlx::Error>;
+    async fn insert_deposit_executed(&self, event: &DepositExecuted) -> Result<(), sqlx::Error>;
+    async fn insert_deposit_cancelled(&self, event: &DepositCancelled) -> Result<(), sqlx::Error>;
+    async fn insert_withdrawal_executed(
+        &self,
+        event: &WithdrawalExecuted,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_withdrawal_cancelled(
+        &self,
+        event: &WithdrawalCancelled,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_funding_fee_amount_per_size_updated(
+        &self,
+        event: &FundingFeeAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_claimable_funding_amount_per_size_updated(
+        &self,
+        event: &ClaimableFundingAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_cumulative_borrowing_factor_updated(
+        &self,
+        event: &CumulativeBorrowingFactorUpdated,
+    ) -> Result<(), sqlx::Error>;
+    async fn insert_position_increase(&self, event: &PositionIncrease) -> Result<(), sqlx::Error>;
+    async fn insert_position_decrease(&self, event: &PositionDecrease) -> Result<(), sqlx::Error>;
+    async fn insert_market_param_updated(
+        &self,
+        event: &MarketParamUpdated,
+    ) -> Result<(), sqlx::Error>;
+}
+
+// Scales the size, initial collateral, trigger and acceptable prices and execution fee of an order
+// by their decimals, for the stores normalizing amounts. Prices are per smallest unit of the index
+// token.
+// @collateral_decimals: The decimals of the initial collateral token.
+// @index_token_decimals: The decimals of the index token of the market.
+pub fn scale_order_amounts(
+    event: &Order,
+    collateral_decimals: Option<i64>,
+    index_token_decimals: Option<i64>,
+) -> [Option<BigDecimal>; 5] {
+    [
+        event.size_delta_usd.as_ref().map(Usd::to_usd),
+        event
+            .initial_collateral_delta_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(collateral_decimals)),
+        event
+            .trigger_price
+            .as_ref()
+            .and_then(|price| price.to_usd_per_token(index_token_decimals)),
+        event
+            .acceptable_price
+            .as_ref()
+            .and_then(|price| price.to_usd_per_token(index_token_decimals)),
+        event
+            .execution_fee
+            .as_ref()
+            .and_then(|fee| fee.to_tokens(Some(MARKET_TOKEN_DECIMALS))),
+    ]
+}
+
+// Scales the initial long and short token amounts and the min market tokens of a deposit.
+pub fn scale_deposit_amounts(
+    event: &Deposit,
+    long_token_decimals: Option<i64>,
+    short_token_decimals: Option<i64>,
+) -> [Option<BigDecimal>; 3] {
+    [
+        event
+            .initial_long_token_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(long_token_decimals)),
+        event
+            .initial_short_token_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(short_token_decimals)),
+        event
+            .min_market_tokens
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(Some(MARKET_TOKEN_DECIMALS))),
+    ]
+}
+
+// Scales the market token amount and the min long and short token amounts of a withdrawal, the
+// withdrawn long and short tokens being the ones of the market.
+pub fn scale_withdrawal_amounts(
+    event: &Withdrawal,
+    long_token_decimals: Option<i64>,
+    short_token_decimals: Option<i64>,
+) -> [Option<BigDecimal>; 3] {
+    [
+        event
+            .market_token_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(Some(MARKET_TOKEN_DECIMALS))),
+        event
+            .min_long_token_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(long_token_decimals)),
+        event
+            .min_short_token_amount
+            .as_ref()
+            .and_then(|amount| amount.to_tokens(short_token_decimals)),
+    ]
+}
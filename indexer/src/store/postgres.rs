@@ -0,0 +1,856 @@
+use crate::config::get_normalize_amounts;
+use crate::events::decimals::{Price, TokenAmount, Usd};
+use crate::events::event::GenericEvent;
+use crate::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+use crate::store::{scale_deposit_amounts, scale_order_amounts, scale_withdrawal_amounts, Store};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use sqlx::postgres::PgPool;
+
+// The Postgres store, writing the events to the tables of sql/db_setup.sql.
+// @normalize_amounts: Whether the scaled amounts get stored too, NORMALIZE_AMOUNTS being read once.
+pub struct PgStore {
+    pool: PgPool,
+    normalize_amounts: bool,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool) -> Self {
+        PgStore {
+            pool,
+            normalize_amounts: get_normalize_amounts(),
+        }
+    }
+}
+
+#[async_trait]
+impl Store for PgStore {
+    // Events fetched again, e.g. once their pending block got included, keep their place in the
+    // archive.
+    async fn archive_event(&self, event: &GenericEvent) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO raw_events (
+                block_number, time_stamp, transaction_hash, key, data, sender_address
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6
+            ) ON CONFLICT (transaction_hash, key, md5(data)) DO UPDATE
+            SET block_number = EXCLUDED.block_number, time_stamp = EXCLUDED.time_stamp",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.data,
+            event.sender_address
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order(&self, event: &Order) -> Result<(), sqlx::Error> {
+        let [size_delta_usd_scaled, initial_collateral_delta_amount_scaled, trigger_price_scaled, acceptable_price_scaled, execution_fee_scaled] =
+            if self.normalize_amounts {
+                let collateral_decimals =
+                    get_token_decimals(&self.pool, event.initial_collateral_token.as_deref())
+                        .await?;
+                let index_token_decimals =
+                    get_index_token_decimals(&self.pool, event.market.as_deref()).await?;
+                scale_order_amounts(event, collateral_decimals, index_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query!(
+            "INSERT INTO orders (
+                block_number, time_stamp, transaction_hash, key, order_type, decrease_position_swap_type, account,
+                receiver, callback_contract, ui_fee_receiver, market, initial_collateral_token, swap_path,
+                size_delta_usd, initial_collateral_delta_amount, trigger_price, acceptable_price,
+                execution_fee, callback_gas_limit, min_output_amount, updated_at_block, is_long, is_frozen,
+                size_delta_usd_scaled, initial_collateral_delta_amount_scaled, trigger_price_scaled,
+                acceptable_price_scaled, execution_fee_scaled
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5, $6,
+                felt_in($7), $8, $9, $10, felt_in($11), $12,
+                $13, $14, $15, $16,
+                $17, $18, $19, $20, $21, $22, $23,
+                $24, $25, $26,
+                $27, $28
+            )",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.order_type.as_ref().map(|ot| format!("{:?}", ot)),
+            event.decrease_position_swap_type.as_ref().map(|dt| format!("{:?}", dt)),
+            event.account,
+            event.receiver,
+            event.callback_contract,
+            event.ui_fee_receiver,
+            event.market,
+            event.initial_collateral_token,
+            event.swap_path.as_ref().map(|sp| sp.join(",")),
+            event.size_delta_usd.as_ref().map(Usd::raw),
+            event.initial_collateral_delta_amount.as_ref().map(TokenAmount::raw),
+            event.trigger_price.as_ref().map(Price::raw),
+            event.acceptable_price.as_ref().map(Price::raw),
+            event.execution_fee.as_ref().map(TokenAmount::raw),
+            event.callback_gas_limit,
+            event.min_output_amount.as_ref().map(TokenAmount::raw),
+            event.updated_at_block,
+            event.is_long,
+            event.is_frozen,
+            size_delta_usd_scaled,
+            initial_collateral_delta_amount_scaled,
+            trigger_price_scaled,
+            acceptable_price_scaled,
+            execution_fee_scaled
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_deposit(&self, event: &Deposit) -> Result<(), sqlx::Error> {
+        let [initial_long_token_amount_scaled, initial_short_token_amount_scaled, min_market_tokens_scaled] =
+            if self.normalize_amounts {
+                let long_token_decimals =
+                    get_token_decimals(&self.pool, event.initial_long_token.as_deref()).await?;
+                let short_token_decimals =
+                    get_token_decimals(&self.pool, event.initial_short_token.as_deref()).await?;
+                scale_deposit_amounts(event, long_token_decimals, short_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query!(
+            "INSERT INTO deposits (
+                block_number, transaction_hash, key, account, receiver, callback_contract,
+                market, initial_long_token, initial_short_token, long_token_swap_path, short_token_swap_path,
+                initial_long_token_amount, initial_short_token_amount, min_market_tokens, updated_at_block,
+                execution_fee, callback_gas_limit, initial_long_token_amount_scaled,
+                initial_short_token_amount_scaled, min_market_tokens_scaled
+            ) VALUES (
+                $1, $2, felt_in($3), felt_in($4), $5, $6,
+                felt_in($7), $8, $9, $10, $11,
+                $12, $13, $14, $15,
+                $16, $17, $18,
+                $19, $20
+            )",
+                event.block_number, event.transaction_hash, event.key, event.account,
+                event.receiver, event.callback_contract, event.market, event.initial_long_token,
+                event.initial_short_token, event.long_token_swap_path, event.short_token_swap_path,
+                event.initial_long_token_amount.as_ref().map(TokenAmount::raw),
+                event.initial_short_token_amount.as_ref().map(TokenAmount::raw),
+                event.min_market_tokens.as_ref().map(TokenAmount::raw), event.updated_at_block,
+                event.execution_fee.as_ref().map(TokenAmount::raw), event.callback_gas_limit, initial_long_token_amount_scaled,
+                initial_short_token_amount_scaled, min_market_tokens_scaled
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_withdrawal(&self, event: &Withdrawal) -> Result<(), sqlx::Error> {
+        let [market_token_amount_scaled, min_long_token_amount_scaled, min_short_token_amount_scaled] =
+            if self.normalize_amounts {
+                // The withdrawn long and short tokens are the ones of the market.
+                let market_tokens = sqlx::query!(
+                    "SELECT long_token, short_token FROM market_created WHERE market_token = $1 LIMIT 1",
+                    event.market
+                )
+                .fetch_optional(&self.pool)
+                .await?;
+                let (long_token, short_token) = market_tokens
+                    .map(|tokens| (tokens.long_token, tokens.short_token))
+                    .unwrap_or_default();
+                let long_token_decimals =
+                    get_token_decimals(&self.pool, long_token.as_deref()).await?;
+                let short_token_decimals =
+                    get_token_decimals(&self.pool, short_token.as_deref()).await?;
+                scale_withdrawal_amounts(event, long_token_decimals, short_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query!(
+            "INSERT INTO withdrawals (
+                block_number, transaction_hash, key, account, receiver, callback_contract,
+                market, long_token_swap_path, short_token_swap_path, market_token_amount,
+                min_long_token_amount, min_short_token_amount, updated_at_block, execution_fee,
+                callback_gas_limit, market_token_amount_scaled, min_long_token_amount_scaled,
+                min_short_token_amount_scaled
+            ) VALUES (
+                $1, $2, felt_in($3), felt_in($4), $5, $6,
+                felt_in($7), $8, $9, $10,
+                $11, $12, $13, $14,
+                $15, $16, $17,
+                $18
+            )",
+            event.block_number,
+            event.transaction_hash,
+            event.key,
+            event.account,
+            event.receiver,
+            event.callback_contract,
+            event.market,
+            event.long_token_swap_path,
+            event.short_token_swap_path,
+            event.market_token_amount.as_ref().map(TokenAmount::raw),
+            event.min_long_token_amount.as_ref().map(TokenAmount::raw),
+            event.min_short_token_amount.as_ref().map(TokenAmount::raw),
+            event.updated_at_block,
+            event.execution_fee.as_ref().map(TokenAmount::raw),
+            event.callback_gas_limit,
+            market_token_amount_scaled,
+            min_long_token_amount_scaled,
+            min_short_token_amount_scaled
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_market_created(&self, event: &MarketCreated) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO market_created (
+                block_number, transaction_hash, key, creator, market_token, index_token,
+                long_token, short_token, market_type
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6,
+                $7, $8, $9
+            )",
+            event.block_number,
+            event.transaction_hash,
+            event.key,
+            event.creator,
+            event.market_token,
+            event.index_token,
+            event.long_token,
+            event.short_token,
+            event.market_type
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_swap_fees_collected(
+        &self,
+        event: &SwapFeesCollected,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO swap_fees_collected (
+                block_number, transaction_hash, key, market, token, token_price,
+                action, fee_receiver_amount, fee_amount_for_pool, amount_after_fees,
+                ui_fee_receiver, ui_fee_receiver_factor, ui_fee_amount
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6,
+                $7, $8, $9, $10,
+                $11, $12, $13
+            )",
+            event.block_number,
+            event.transaction_hash,
+            event.key,
+            event.market,
+            event.token,
+            event.token_price,
+            event.action,
+            event.fee_receiver_amount,
+            event.fee_amount_for_pool,
+            event.amount_after_fees,
+            event.ui_fee_receiver,
+            event.ui_fee_receiver_factor,
+            event.ui_fee_amount
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_swap_info(&self, event: &SwapInfo) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO swap_info (
+                block_number, transaction_hash, key, order_key, market, receiver,
+                token_in, token_out, token_in_price, token_out_price, amount_in, amount_in_after_fees,
+                amount_out, price_impact_usd_mag, price_impact_usd_sign, price_impact_amount_mag,
+                price_impact_amount_sign
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17
+            )",
+                event.block_number, event.transaction_hash, event.key, event.order_key,
+                event.market, event.receiver, event.token_in, event.token_out,
+                event.token_in_price, event.token_out_price, event.amount_in,
+                event.amount_in_after_fees, event.amount_out, event.price_impact_usd_mag,
+                event.price_impact_usd_sign, event.price_impact_amount_mag,
+                event.price_impact_amount_sign
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_pool_amount_updated(
+        &self,
+        event: &PoolAmountUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO pool_amount_updated (
+                block_number, transaction_hash, key, market, token, delta_mag, delta_sign, next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            )",
+                event.block_number, event.transaction_hash, event.key, event.market, event.token,
+                event.delta_mag, event.delta_sign, event.next_value
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_executed(&self, event: &OrderExecuted) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO order_executed (
+                block_number, time_stamp, transaction_hash, key, secondary_order_type, keeper
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5, $6
+            )",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.secondary_order_type,
+            event.keeper
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_updated(&self, event: &OrderUpdated) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO order_updated (
+                block_number, time_stamp, transaction_hash, key, size_delta_usd, acceptable_price,
+                trigger_price, min_output_amount
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5, $6,
+                $7, $8
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.size_delta_usd.as_ref().map(Usd::raw),
+            event.acceptable_price.as_ref().map(Price::raw),
+            event.trigger_price.as_ref().map(Price::raw),
+            event.min_output_amount.as_ref().map(TokenAmount::raw)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_frozen(&self, event: &OrderFrozen) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO order_frozen (
+                block_number, time_stamp, transaction_hash, key, reason
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_cancelled(&self, event: &OrderCancelled) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO order_cancelled (
+                block_number, time_stamp, transaction_hash, key, reason
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_deposit_executed(&self, event: &DepositExecuted) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO deposit_executed (
+                block_number, time_stamp, transaction_hash, key
+            ) VALUES (
+                $1, $2, $3, felt_in($4)
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_deposit_cancelled(&self, event: &DepositCancelled) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO deposit_cancelled (
+                block_number, time_stamp, transaction_hash, key, reason
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_withdrawal_executed(
+        &self,
+        event: &WithdrawalExecuted,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO withdrawal_executed (
+                block_number, time_stamp, transaction_hash, key
+            ) VALUES (
+                $1, $2, $3, felt_in($4)
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_withdrawal_cancelled(
+        &self,
+        event: &WithdrawalCancelled,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO withdrawal_cancelled (
+                block_number, time_stamp, transaction_hash, key, reason
+            ) VALUES (
+                $1, $2, $3, felt_in($4), $5
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_funding_fee_amount_per_size_updated(
+        &self,
+        event: &FundingFeeAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO funding_fee_amount_per_size_updated (
+                block_number, time_stamp, transaction_hash, market, collateral_token, is_long, delta,
+                next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7,
+                $8
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.market,
+            event.collateral_token,
+            event.is_long,
+            event.delta,
+            event.next_value
+        )
+        .execute(&self.pool)
+        .await?;
+        record_funding_payments(
+            &self.pool,
+            event.block_number,
+            event.timestamp.as_deref(),
+            &event.transaction_hash,
+            (
+                event.market.as_deref(),
+                event.collateral_token.as_deref(),
+                event.is_long,
+            ),
+            event.delta.as_ref(),
+            "paid",
+        )
+        .await
+    }
+
+    async fn insert_claimable_funding_amount_per_size_updated(
+        &self,
+        event: &ClaimableFundingAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO claimable_funding_amount_per_size_updated (
+                block_number, time_stamp, transaction_hash, market, collateral_token, is_long, delta,
+                next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7,
+                $8
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.market,
+            event.collateral_token,
+            event.is_long,
+            event.delta,
+            event.next_value
+        )
+        .execute(&self.pool)
+        .await?;
+        record_funding_payments(
+            &self.pool,
+            event.block_number,
+            event.timestamp.as_deref(),
+            &event.transaction_hash,
+            (
+                event.market.as_deref(),
+                event.collateral_token.as_deref(),
+                event.is_long,
+            ),
+            event.delta.as_ref(),
+            "received",
+        )
+        .await
+    }
+
+    async fn insert_cumulative_borrowing_factor_updated(
+        &self,
+        event: &CumulativeBorrowingFactorUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO cumulative_borrowing_factor_updated (
+                block_number, time_stamp, transaction_hash, market, is_long, delta, next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.market,
+            event.is_long,
+            event.delta,
+            event.next_value
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Open positions accrue borrowing fees on every update of the factor of their side.
+        if let (Some(market), Some(is_long), Some(delta)) =
+            (&event.market, event.is_long, &event.delta)
+        {
+            sqlx::query_scalar!(
+                "SELECT record_borrowing_fee_accruals($1, $2, $3, $4, $5, $6)",
+                event.block_number,
+                event.timestamp,
+                event.transaction_hash,
+                market,
+                is_long,
+                delta
+            )
+            .fetch_one(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_position_increase(&self, event: &PositionIncrease) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO position_increase (
+                block_number, time_stamp, transaction_hash, key, account, market, collateral_token,
+                execution_price, size_delta_usd, size_delta_in_tokens
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.account,
+            event.market,
+            event.collateral_token,
+            event.execution_price.as_ref().map(Price::raw),
+            event.size_delta_usd.as_ref().map(Usd::raw),
+            event.size_delta_in_tokens.as_ref().map(TokenAmount::raw)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_position_decrease(&self, event: &PositionDecrease) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO position_decrease (
+                block_number, time_stamp, transaction_hash, key, account, market, collateral_token,
+                execution_price, size_delta_usd, size_delta_in_tokens
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.key,
+            event.account,
+            event.market,
+            event.collateral_token,
+            event.execution_price.as_ref().map(Price::raw),
+            event.size_delta_usd.as_ref().map(Usd::raw),
+            event.size_delta_in_tokens.as_ref().map(TokenAmount::raw)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_market_param_updated(
+        &self,
+        event: &MarketParamUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO market_params_history (
+                block_number, time_stamp, transaction_hash, market, parameter, is_long, value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            ) ON CONFLICT DO NOTHING",
+            event.block_number,
+            event.timestamp,
+            event.transaction_hash,
+            event.market,
+            event.parameter,
+            event.is_long,
+            event.value
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+// Returns the decimals of a token listed in the tokens table, None when it is not.
+async fn get_token_decimals(
+    pool: &PgPool,
+    token: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let token = match token {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    let decimals = sqlx::query_scalar!("SELECT decimals FROM tokens WHERE address = $1", token)
+        .fetch_optional(pool)
+        .await?;
+    Ok(decimals.map(i64::from))
+}
+
+// Returns the decimals of the index token of a market, None when the market or its index token are unknown.
+async fn get_index_token_decimals(
+    pool: &PgPool,
+    market: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let market = match market {
+        Some(market) => market,
+        None => return Ok(None),
+    };
+    let index_token = sqlx::query_scalar!(
+        "SELECT index_token FROM market_created WHERE market_token = $1 LIMIT 1",
+        market
+    )
+    .fetch_optional(pool)
+    .await?
+    .flatten();
+    get_token_decimals(pool, index_token.as_deref()).await
+}
+
+// Records the funding paid or received by the positions open on a market side, on an update of its
+// funding amount per size.
+// @side: The market, collateral token and direction of the positions.
+// @delta: The increase of the funding amount per size.
+// @direction: Whether the positions paid or received the funding.
+async fn record_funding_payments(
+    pool: &PgPool,
+    block_number: i64,
+    timestamp: Option<&str>,
+    transaction_hash: &str,
+    side: (Option<&str>, Option<&str>, Option<bool>),
+    delta: Option<&BigDecimal>,
+    direction: &str,
+) -> Result<(), sqlx::Error> {
+    let (market, collateral_token, is_long, delta) = match (side, delta) {
+        ((Some(market), Some(collateral_token), Some(is_long)), Some(delta)) => {
+            (market, collateral_token, is_long, delta)
+        }
+        _ => return Ok(()),
+    };
+    sqlx::query_scalar!(
+        "SELECT record_funding_payments($1, $2, $3, $4, $5, $6, $7, $8)",
+        block_number,
+        timestamp,
+        transaction_hash,
+        market,
+        collateral_token,
+        is_long,
+        delta,
+        direction
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // A position side of an ETH market with USDC and ETH collateral, recorded far past the chain
+    // head and removed afterwards.
+    const BLOCK: i64 = 1_000_000_000;
+    const ACCOUNT: &str = "00000000000000000000000000000000000000000000000000000000000000ac";
+    const MARKET: &str = "000000000000000000000000000000000000000000000000000000000000000a";
+    const USDC: &str = "0000000000000000000000000000000000000000000000000000000000000c01";
+    const ETH: &str = "0000000000000000000000000000000000000000000000000000000000000e01";
+
+    async fn execute_increase(pool: &PgPool, block: i64, key: &str, collateral: &str, size: i64) {
+        let transaction_hash = format!("funding-test-{}", block);
+        // Swapped into its collateral, the order initial collateral token is not the position one.
+        sqlx::query(
+            "INSERT INTO orders (block_number, transaction_hash, key, order_type, account, market,
+                 initial_collateral_token, is_long)
+             VALUES ($1, $2, felt_in($3), 'MarketIncrease', felt_in($4), felt_in($5), $6, TRUE)",
+        )
+        .bind(block)
+        .bind(&transaction_hash)
+        .bind(key)
+        .bind(ACCOUNT)
+        .bind(MARKET)
+        .bind(ETH)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO order_executed (block_number, transaction_hash, key)
+             VALUES ($1, $2, felt_in($3))",
+        )
+        .bind(block)
+        .bind(&transaction_hash)
+        .bind(key)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO position_increase (block_number, transaction_hash, account, market,
+                 collateral_token, size_delta_usd)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(block)
+        .bind(&transaction_hash)
+        .bind(ACCOUNT)
+        .bind(MARKET)
+        .bind(collateral)
+        .bind(BigDecimal::from(size))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn clean_up(pool: &PgPool) {
+        for table in [
+            "orders",
+            "order_executed",
+            "position_increase",
+            "funding_payments",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE block_number >= $1", table))
+                .bind(BLOCK)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_funding_payments() {
+        // Runs against the schema of DATABASE_URL, skipped without a database.
+        let pool = match std::env::var("DATABASE_URL") {
+            Ok(url) => match PgPool::connect(&url).await {
+                Ok(pool) => pool,
+                Err(_) => return,
+            },
+            Err(_) => return,
+        };
+        clean_up(&pool).await;
+        let key = |value: u64| format!("{:064x}", value);
+        execute_increase(&pool, BLOCK, &key(0xf1), USDC, 1000).await;
+        execute_increase(&pool, BLOCK + 1, &key(0xf2), ETH, 500).await;
+        let payments = |direction: &'static str, collateral: &'static str| {
+            let pool = pool.clone();
+            async move {
+                record_funding_payments(
+                    &pool,
+                    BLOCK + 2,
+                    None,
+                    &format!("funding-test-{}-{}", direction, collateral),
+                    (Some(MARKET), Some(collateral), Some(true)),
+                    Some(&BigDecimal::from_str("2000000000000000000000000000000").unwrap()),
+                    direction,
+                )
+                .await
+                .unwrap();
+                sqlx::query_as::<_, (String, BigDecimal)>(
+                    "SELECT account, amount::NUMERIC FROM funding_payments
+                     WHERE transaction_hash = $1",
+                )
+                .bind(format!("funding-test-{}-{}", direction, collateral))
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+            }
+        };
+        // Funding gets paid by the positions with the collateral of the update only.
+        assert_eq!(
+            payments("paid", USDC).await,
+            vec![(ACCOUNT.to_owned(), BigDecimal::from(2000))]
+        );
+        // The claimable funding in a token accrues to every position of the side.
+        assert_eq!(
+            payments("received", USDC).await,
+            vec![(ACCOUNT.to_owned(), BigDecimal::from(3000))]
+        );
+        clean_up(&pool).await;
+    }
+}
@@ -0,0 +1,1177 @@
+use crate::config::get_normalize_amounts;
+use crate::events::decimals::{Price, TokenAmount, Usd};
+use crate::events::event::GenericEvent;
+use crate::events::{
+    claimable_funding_amount_per_size_updated::ClaimableFundingAmountPerSizeUpdated,
+    cumulative_borrowing_factor_updated::CumulativeBorrowingFactorUpdated, deposit::Deposit,
+    deposit_cancelled::DepositCancelled, deposit_executed::DepositExecuted,
+    funding_fee_amount_per_size_updated::FundingFeeAmountPerSizeUpdated,
+    market_created::MarketCreated, market_param_updated::MarketParamUpdated, order::Order,
+    order_cancelled::OrderCancelled, order_executed::OrderExecuted, order_frozen::OrderFrozen,
+    order_updated::OrderUpdated, pool_amount_updated::PoolAmountUpdated,
+    position_decrease::PositionDecrease, position_increase::PositionIncrease,
+    swap_fees_collected::SwapFeesCollected, swap_info::SwapInfo, withdrawal::Withdrawal,
+    withdrawal_cancelled::WithdrawalCancelled, withdrawal_executed::WithdrawalExecuted,
+};
+use crate::store::{scale_deposit_amounts, scale_order_amounts, scale_withdrawal_amounts, Store};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+// The SQLite store, writing the events to the tables of sql/sqlite for local development and
+// deployments without Postgres. Amounts are stored as decimal strings, and the funding and
+// borrowing ledgers the Postgres functions record get computed here.
+// @normalize_amounts: Whether the scaled amounts get stored too, NORMALIZE_AMOUNTS being read once.
+pub struct SqliteStore {
+    pool: SqlitePool,
+    normalize_amounts: bool,
+}
+
+impl SqliteStore {
+    // Opens the database of a sqlite: URL, creating it when missing, and applies the migrations of
+    // sql/sqlite.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("../sql/sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| sqlx::Error::Migrate(Box::new(e)))?;
+        Ok(SqliteStore {
+            pool,
+            normalize_amounts: get_normalize_amounts(),
+        })
+    }
+
+    // Returns the decimals of a token listed in the tokens table, None when it is not.
+    async fn get_token_decimals(&self, token: Option<&str>) -> Result<Option<i64>, sqlx::Error> {
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        sqlx::query_scalar("SELECT decimals FROM tokens WHERE address = $1")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    // Returns the long and short tokens of a market, None when the market is unknown.
+    async fn get_market_tokens(
+        &self,
+        market: Option<&str>,
+    ) -> Result<(Option<String>, Option<String>), sqlx::Error> {
+        let tokens: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+            "SELECT long_token, short_token FROM market_created WHERE market_token = $1 LIMIT 1",
+        )
+        .bind(market)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(tokens.unwrap_or_default())
+    }
+
+    // Returns the decimals of the index token of a market, None when the market or its index token
+    // are unknown.
+    async fn get_index_token_decimals(
+        &self,
+        market: Option<&str>,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let index_token: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT index_token FROM market_created WHERE market_token = $1 LIMIT 1",
+        )
+        .bind(market)
+        .fetch_optional(&self.pool)
+        .await?;
+        self.get_token_decimals(index_token.flatten().as_deref())
+            .await
+    }
+
+    // Records the funding paid or received by the positions open on a market side, on an update of
+    // its funding amount per size, as record_funding_payments of sql/db_setup.sql does.
+    // @side: The market, collateral token and direction of the positions.
+    // @delta: The increase of the funding amount per size.
+    // @direction: Whether the positions paid or received the funding.
+    async fn record_funding_payments(
+        &self,
+        block_number: i64,
+        timestamp: Option<&str>,
+        transaction_hash: &str,
+        side: (Option<&str>, Option<&str>, Option<bool>),
+        delta: Option<&BigDecimal>,
+        direction: &str,
+    ) -> Result<(), sqlx::Error> {
+        let (market, collateral_token, is_long, delta) = match (side, delta) {
+            ((Some(market), Some(collateral_token), Some(is_long)), Some(delta)) => {
+                (market, collateral_token, is_long, delta)
+            }
+            _ => return Ok(()),
+        };
+        let changes: Vec<(String, Option<String>, bool)> = sqlx::query_as(
+            "SELECT c.account, c.size_delta_usd, c.is_increase
+             FROM (
+               SELECT block_number, transaction_hash, account, market, collateral_token,
+                 size_delta_usd, TRUE AS is_increase
+               FROM position_increase
+               UNION ALL
+               SELECT block_number, transaction_hash, account, market, collateral_token,
+                 size_delta_usd, FALSE
+               FROM position_decrease
+             ) c
+             WHERE c.market = $1
+               AND c.block_number < $2
+               AND ($3 = 'received' OR c.collateral_token = $4)
+               AND EXISTS (
+                 SELECT 1 FROM order_executed oe
+                 JOIN orders o ON o.key = oe.key
+                 WHERE oe.transaction_hash = c.transaction_hash
+                   AND o.account = c.account
+                   AND o.market = c.market
+                   AND o.is_long = $5
+               )",
+        )
+        .bind(market)
+        .bind(block_number)
+        .bind(direction)
+        .bind(collateral_token)
+        .bind(is_long)
+        .fetch_all(&self.pool)
+        .await?;
+        let sizes = sum_sizes(changes.into_iter());
+        for (account, size_in_usd) in sizes {
+            sqlx::query(
+                "INSERT INTO funding_payments (
+                    block_number, time_stamp, transaction_hash, account, market, collateral_token,
+                    is_long, direction, size_in_usd, amount_per_size_delta, amount
+                ) VALUES (
+                    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11
+                ) ON CONFLICT DO NOTHING",
+            )
+            .bind(block_number)
+            .bind(timestamp)
+            .bind(transaction_hash)
+            .bind(&account)
+            .bind(market)
+            .bind(collateral_token)
+            .bind(is_long)
+            .bind(direction)
+            .bind(size_in_usd.to_string())
+            .bind(delta.to_string())
+            .bind(apply_delta(&size_in_usd, delta).to_string())
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Records the borrowing fees accrued by the positions open on a market side when its
+    // cumulative borrowing factor gets updated, as record_borrowing_fee_accruals of
+    // sql/db_setup.sql does.
+    async fn record_borrowing_fee_accruals(
+        &self,
+        event: &CumulativeBorrowingFactorUpdated,
+        market: &str,
+        is_long: bool,
+        delta: &BigDecimal,
+    ) -> Result<(), sqlx::Error> {
+        let changes: Vec<(String, String, Option<String>, bool)> = sqlx::query_as(
+            "SELECT c.account, c.collateral_token, c.size_delta_usd, c.is_increase
+             FROM (
+               SELECT block_number, transaction_hash, account, market, collateral_token,
+                 size_delta_usd, TRUE AS is_increase
+               FROM position_increase
+               UNION ALL
+               SELECT block_number, transaction_hash, account, market, collateral_token,
+                 size_delta_usd, FALSE
+               FROM position_decrease
+             ) c
+             WHERE c.market = $1
+               AND c.block_number < $2
+               AND EXISTS (
+                 SELECT 1 FROM order_executed oe
+                 JOIN orders o ON o.key = oe.key
+                 WHERE oe.transaction_hash = c.transaction_hash
+                   AND o.account = c.account
+                   AND o.market = c.market
+                   AND o.is_long = $3
+               )",
+        )
+        .bind(market)
+        .bind(event.block_number)
+        .bind(is_long)
+        .fetch_all(&self.pool)
+        .await?;
+        let sizes = sum_sizes(changes.into_iter().map(
+            |(account, collateral_token, size_delta_usd, is_increase)| {
+                ((account, collateral_token), size_delta_usd, is_increase)
+            },
+        ));
+        for ((account, collateral_token), size_in_usd) in sizes {
+            sqlx::query(
+                "INSERT INTO borrowing_fee_accruals (
+                    block_number, time_stamp, transaction_hash, account, market, collateral_token,
+                    is_long, size_in_usd, borrowing_factor_delta, amount_usd
+                ) VALUES (
+                    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+                ) ON CONFLICT DO NOTHING",
+            )
+            .bind(event.block_number)
+            .bind(&event.timestamp)
+            .bind(&event.transaction_hash)
+            .bind(&account)
+            .bind(market)
+            .bind(&collateral_token)
+            .bind(is_long)
+            .bind(size_in_usd.to_string())
+            .bind(delta.to_string())
+            .bind(apply_delta(&size_in_usd, delta).to_string())
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+// Returns a decimal amount as stored, NUMERIC amounts being TEXT columns.
+fn text(amount: Option<&BigDecimal>) -> Option<String> {
+    amount.map(BigDecimal::to_string)
+}
+
+// Sums the size deltas of the increases and decreases of each position, keeping the open ones.
+// Stored sizes that are not decimals count as 0.
+fn sum_sizes<K: Ord>(
+    changes: impl Iterator<Item = (K, Option<String>, bool)>,
+) -> BTreeMap<K, BigDecimal> {
+    let mut sizes = BTreeMap::new();
+    for (position, size_delta_usd, is_increase) in changes {
+        let size_delta_usd = size_delta_usd
+            .and_then(|size| BigDecimal::from_str(&size).ok())
+            .unwrap_or_default();
+        let size: &mut BigDecimal = sizes.entry(position).or_default();
+        if is_increase {
+            *size += size_delta_usd;
+        } else {
+            *size -= size_delta_usd;
+        }
+    }
+    sizes.retain(|_, size| *size > BigDecimal::from(0));
+    sizes
+}
+
+// Returns a size in USD times a delta per size, divided by the 10^30 float precision and
+// truncated, as TRUNC(size * delta / 1e30) in Postgres.
+fn apply_delta(size_in_usd: &BigDecimal, delta: &BigDecimal) -> BigDecimal {
+    let (digits, scale) = (size_in_usd * delta).into_bigint_and_exponent();
+    BigDecimal::new(digits, scale + 30).with_scale(0)
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn archive_event(&self, event: &GenericEvent) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO raw_events (
+                block_number, time_stamp, transaction_hash, key, data, sender_address
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6
+            ) ON CONFLICT (transaction_hash, key, data) DO UPDATE
+            SET block_number = excluded.block_number, time_stamp = excluded.time_stamp",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.data)
+        .bind(&event.sender_address)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order(&self, event: &Order) -> Result<(), sqlx::Error> {
+        let [size_delta_usd_scaled, initial_collateral_delta_amount_scaled, trigger_price_scaled, acceptable_price_scaled, execution_fee_scaled] =
+            if self.normalize_amounts {
+                let collateral_decimals = self
+                    .get_token_decimals(event.initial_collateral_token.as_deref())
+                    .await?;
+                let index_token_decimals = self
+                    .get_index_token_decimals(event.market.as_deref())
+                    .await?;
+                scale_order_amounts(event, collateral_decimals, index_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query(
+            "INSERT INTO orders (
+                block_number, time_stamp, transaction_hash, key, order_type, decrease_position_swap_type, account,
+                receiver, callback_contract, ui_fee_receiver, market, initial_collateral_token, swap_path,
+                size_delta_usd, initial_collateral_delta_amount, trigger_price, acceptable_price,
+                execution_fee, callback_gas_limit, min_output_amount, updated_at_block, is_long, is_frozen,
+                size_delta_usd_scaled, initial_collateral_delta_amount_scaled, trigger_price_scaled,
+                acceptable_price_scaled, execution_fee_scaled
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+                $20, $21, $22, $23, $24, $25, $26, $27, $28
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(event.order_type.as_ref().map(|ot| format!("{:?}", ot)))
+        .bind(
+            event
+                .decrease_position_swap_type
+                .as_ref()
+                .map(|dt| format!("{:?}", dt)),
+        )
+        .bind(&event.account)
+        .bind(&event.receiver)
+        .bind(&event.callback_contract)
+        .bind(&event.ui_fee_receiver)
+        .bind(&event.market)
+        .bind(&event.initial_collateral_token)
+        .bind(event.swap_path.as_ref().map(|sp| sp.join(",")))
+        .bind(text(event.size_delta_usd.as_ref().map(Usd::raw)))
+        .bind(text(
+            event
+                .initial_collateral_delta_amount
+                .as_ref()
+                .map(TokenAmount::raw),
+        ))
+        .bind(text(event.trigger_price.as_ref().map(Price::raw)))
+        .bind(text(event.acceptable_price.as_ref().map(Price::raw)))
+        .bind(text(event.execution_fee.as_ref().map(TokenAmount::raw)))
+        .bind(text(event.callback_gas_limit.as_ref()))
+        .bind(text(event.min_output_amount.as_ref().map(TokenAmount::raw)))
+        .bind(event.updated_at_block)
+        .bind(event.is_long)
+        .bind(event.is_frozen)
+        .bind(text(size_delta_usd_scaled.as_ref()))
+        .bind(text(initial_collateral_delta_amount_scaled.as_ref()))
+        .bind(text(trigger_price_scaled.as_ref()))
+        .bind(text(acceptable_price_scaled.as_ref()))
+        .bind(text(execution_fee_scaled.as_ref()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_deposit(&self, event: &Deposit) -> Result<(), sqlx::Error> {
+        let [initial_long_token_amount_scaled, initial_short_token_amount_scaled, min_market_tokens_scaled] =
+            if self.normalize_amounts {
+                let long_token_decimals = self
+                    .get_token_decimals(event.initial_long_token.as_deref())
+                    .await?;
+                let short_token_decimals = self
+                    .get_token_decimals(event.initial_short_token.as_deref())
+                    .await?;
+                scale_deposit_amounts(event, long_token_decimals, short_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query(
+            "INSERT INTO deposits (
+                block_number, transaction_hash, key, account, receiver, callback_contract,
+                market, initial_long_token, initial_short_token, long_token_swap_path, short_token_swap_path,
+                initial_long_token_amount, initial_short_token_amount, min_market_tokens, updated_at_block,
+                execution_fee, callback_gas_limit, initial_long_token_amount_scaled,
+                initial_short_token_amount_scaled, min_market_tokens_scaled
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+                $20
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.account)
+        .bind(&event.receiver)
+        .bind(&event.callback_contract)
+        .bind(&event.market)
+        .bind(&event.initial_long_token)
+        .bind(&event.initial_short_token)
+        .bind(&event.long_token_swap_path)
+        .bind(&event.short_token_swap_path)
+        .bind(text(event.initial_long_token_amount.as_ref().map(TokenAmount::raw)))
+        .bind(text(event.initial_short_token_amount.as_ref().map(TokenAmount::raw)))
+        .bind(text(event.min_market_tokens.as_ref().map(TokenAmount::raw)))
+        .bind(event.updated_at_block)
+        .bind(text(event.execution_fee.as_ref().map(TokenAmount::raw)))
+        .bind(event.callback_gas_limit)
+        .bind(text(initial_long_token_amount_scaled.as_ref()))
+        .bind(text(initial_short_token_amount_scaled.as_ref()))
+        .bind(text(min_market_tokens_scaled.as_ref()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_withdrawal(&self, event: &Withdrawal) -> Result<(), sqlx::Error> {
+        let [market_token_amount_scaled, min_long_token_amount_scaled, min_short_token_amount_scaled] =
+            if self.normalize_amounts {
+                let (long_token, short_token) =
+                    self.get_market_tokens(event.market.as_deref()).await?;
+                let long_token_decimals = self.get_token_decimals(long_token.as_deref()).await?;
+                let short_token_decimals = self.get_token_decimals(short_token.as_deref()).await?;
+                scale_withdrawal_amounts(event, long_token_decimals, short_token_decimals)
+            } else {
+                Default::default()
+            };
+
+        sqlx::query(
+            "INSERT INTO withdrawals (
+                block_number, transaction_hash, key, account, receiver, callback_contract,
+                market, long_token_swap_path, short_token_swap_path, market_token_amount,
+                min_long_token_amount, min_short_token_amount, updated_at_block, execution_fee,
+                callback_gas_limit, market_token_amount_scaled, min_long_token_amount_scaled,
+                min_short_token_amount_scaled
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.account)
+        .bind(&event.receiver)
+        .bind(&event.callback_contract)
+        .bind(&event.market)
+        .bind(&event.long_token_swap_path)
+        .bind(&event.short_token_swap_path)
+        .bind(text(
+            event.market_token_amount.as_ref().map(TokenAmount::raw),
+        ))
+        .bind(text(
+            event.min_long_token_amount.as_ref().map(TokenAmount::raw),
+        ))
+        .bind(text(
+            event.min_short_token_amount.as_ref().map(TokenAmount::raw),
+        ))
+        .bind(event.updated_at_block)
+        .bind(text(event.execution_fee.as_ref().map(TokenAmount::raw)))
+        .bind(event.callback_gas_limit)
+        .bind(text(market_token_amount_scaled.as_ref()))
+        .bind(text(min_long_token_amount_scaled.as_ref()))
+        .bind(text(min_short_token_amount_scaled.as_ref()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_market_created(&self, event: &MarketCreated) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO market_created (
+                block_number, transaction_hash, key, creator, market_token, index_token,
+                long_token, short_token, market_type
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.creator)
+        .bind(&event.market_token)
+        .bind(&event.index_token)
+        .bind(&event.long_token)
+        .bind(&event.short_token)
+        .bind(&event.market_type)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_swap_fees_collected(
+        &self,
+        event: &SwapFeesCollected,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO swap_fees_collected (
+                block_number, transaction_hash, key, market, token, token_price,
+                action, fee_receiver_amount, fee_amount_for_pool, amount_after_fees,
+                ui_fee_receiver, ui_fee_receiver_factor, ui_fee_amount
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.market)
+        .bind(&event.token)
+        .bind(event.token_price)
+        .bind(&event.action)
+        .bind(event.fee_receiver_amount)
+        .bind(event.fee_amount_for_pool)
+        .bind(event.amount_after_fees)
+        .bind(&event.ui_fee_receiver)
+        .bind(event.ui_fee_receiver_factor)
+        .bind(event.ui_fee_amount)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_swap_info(&self, event: &SwapInfo) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO swap_info (
+                block_number, transaction_hash, key, order_key, market, receiver,
+                token_in, token_out, token_in_price, token_out_price, amount_in, amount_in_after_fees,
+                amount_out, price_impact_usd_mag, price_impact_usd_sign, price_impact_amount_mag,
+                price_impact_amount_sign
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.order_key)
+        .bind(&event.market)
+        .bind(&event.receiver)
+        .bind(&event.token_in)
+        .bind(&event.token_out)
+        .bind(event.token_in_price)
+        .bind(event.token_out_price)
+        .bind(event.amount_in)
+        .bind(event.amount_in_after_fees)
+        .bind(event.amount_out)
+        .bind(event.price_impact_usd_mag)
+        .bind(event.price_impact_usd_sign)
+        .bind(event.price_impact_amount_mag)
+        .bind(event.price_impact_amount_sign)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_pool_amount_updated(
+        &self,
+        event: &PoolAmountUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO pool_amount_updated (
+                block_number, transaction_hash, key, market, token, delta_mag, delta_sign, next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.market)
+        .bind(&event.token)
+        .bind(event.delta_mag)
+        .bind(event.delta_sign)
+        .bind(event.next_value)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_executed(&self, event: &OrderExecuted) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO order_executed (
+                block_number, time_stamp, transaction_hash, key, secondary_order_type, keeper
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6
+            )",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(&event.secondary_order_type)
+        .bind(&event.keeper)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_updated(&self, event: &OrderUpdated) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO order_updated (
+                block_number, time_stamp, transaction_hash, key, size_delta_usd, acceptable_price,
+                trigger_price, min_output_amount
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            ) ON CONFLICT DO NOTHING",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.key)
+        .bind(text(event.size_delta_usd.as_ref().map(Usd::raw)))
+        .bind(text(event.acceptable_price.as_ref().map(Price::raw)))
+        .bind(text(event.trigger_price.as_ref().map(Price::raw)))
+        .bind(text(event.min_output_amount.as_ref().map(TokenAmount::raw)))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_order_frozen(&self, event: &OrderFrozen) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "order_frozen",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            Some(&event.reason),
+        )
+        .await
+    }
+
+    async fn insert_order_cancelled(&self, event: &OrderCancelled) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "order_cancelled",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            Some(&event.reason),
+        )
+        .await
+    }
+
+    async fn insert_deposit_executed(&self, event: &DepositExecuted) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "deposit_executed",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            None,
+        )
+        .await
+    }
+
+    async fn insert_deposit_cancelled(&self, event: &DepositCancelled) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "deposit_cancelled",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            Some(&event.reason),
+        )
+        .await
+    }
+
+    async fn insert_withdrawal_executed(
+        &self,
+        event: &WithdrawalExecuted,
+    ) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "withdrawal_executed",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            None,
+        )
+        .await
+    }
+
+    async fn insert_withdrawal_cancelled(
+        &self,
+        event: &WithdrawalCancelled,
+    ) -> Result<(), sqlx::Error> {
+        insert_settlement(
+            &self.pool,
+            "withdrawal_cancelled",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            &event.key,
+            Some(&event.reason),
+        )
+        .await
+    }
+
+    async fn insert_funding_fee_amount_per_size_updated(
+        &self,
+        event: &FundingFeeAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        insert_amount_per_size_update(
+            &self.pool,
+            "funding_fee_amount_per_size_updated",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            (&event.market, &event.collateral_token, event.is_long),
+            (&event.delta, &event.next_value),
+        )
+        .await?;
+        self.record_funding_payments(
+            event.block_number,
+            event.timestamp.as_deref(),
+            &event.transaction_hash,
+            (
+                event.market.as_deref(),
+                event.collateral_token.as_deref(),
+                event.is_long,
+            ),
+            event.delta.as_ref(),
+            "paid",
+        )
+        .await
+    }
+
+    async fn insert_claimable_funding_amount_per_size_updated(
+        &self,
+        event: &ClaimableFundingAmountPerSizeUpdated,
+    ) -> Result<(), sqlx::Error> {
+        insert_amount_per_size_update(
+            &self.pool,
+            "claimable_funding_amount_per_size_updated",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            (&event.market, &event.collateral_token, event.is_long),
+            (&event.delta, &event.next_value),
+        )
+        .await?;
+        self.record_funding_payments(
+            event.block_number,
+            event.timestamp.as_deref(),
+            &event.transaction_hash,
+            (
+                event.market.as_deref(),
+                event.collateral_token.as_deref(),
+                event.is_long,
+            ),
+            event.delta.as_ref(),
+            "received",
+        )
+        .await
+    }
+
+    async fn insert_cumulative_borrowing_factor_updated(
+        &self,
+        event: &CumulativeBorrowingFactorUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO cumulative_borrowing_factor_updated (
+                block_number, time_stamp, transaction_hash, market, is_long, delta, next_value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            ) ON CONFLICT DO NOTHING",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.market)
+        .bind(event.is_long)
+        .bind(text(event.delta.as_ref()))
+        .bind(text(event.next_value.as_ref()))
+        .execute(&self.pool)
+        .await?;
+
+        // Open positions accrue borrowing fees on every update of the factor of their side.
+        if let (Some(market), Some(is_long), Some(delta)) =
+            (&event.market, event.is_long, &event.delta)
+        {
+            self.record_borrowing_fee_accruals(event, market, is_long, delta)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_position_increase(&self, event: &PositionIncrease) -> Result<(), sqlx::Error> {
+        insert_position_change(
+            &self.pool,
+            "position_increase",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            (
+                &event.key,
+                &event.account,
+                &event.market,
+                &event.collateral_token,
+            ),
+            [
+                event.execution_price.as_ref().map(Price::raw),
+                event.size_delta_usd.as_ref().map(Usd::raw),
+                event.size_delta_in_tokens.as_ref().map(TokenAmount::raw),
+            ],
+        )
+        .await
+    }
+
+    async fn insert_position_decrease(&self, event: &PositionDecrease) -> Result<(), sqlx::Error> {
+        insert_position_change(
+            &self.pool,
+            "position_decrease",
+            (
+                event.block_number,
+                &event.timestamp,
+                &event.transaction_hash,
+            ),
+            (
+                &event.key,
+                &event.account,
+                &event.market,
+                &event.collateral_token,
+            ),
+            [
+                event.execution_price.as_ref().map(Price::raw),
+                event.size_delta_usd.as_ref().map(Usd::raw),
+                event.size_delta_in_tokens.as_ref().map(TokenAmount::raw),
+            ],
+        )
+        .await
+    }
+
+    async fn insert_market_param_updated(
+        &self,
+        event: &MarketParamUpdated,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO market_params_history (
+                block_number, time_stamp, transaction_hash, market, parameter, is_long, value
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            ) ON CONFLICT DO NOTHING",
+        )
+        .bind(event.block_number)
+        .bind(&event.timestamp)
+        .bind(&event.transaction_hash)
+        .bind(&event.market)
+        .bind(&event.parameter)
+        .bind(event.is_long)
+        .bind(text(event.value.as_ref()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+// Inserts the execution, cancellation or freeze of an action, with its reason for the tables having
+// one.
+// @transaction: The block number, timestamp and hash of the transaction settling the action.
+async fn insert_settlement(
+    pool: &SqlitePool,
+    table: &str,
+    transaction: (i64, &Option<String>, &String),
+    key: &Option<String>,
+    reason: Option<&Option<String>>,
+) -> Result<(), sqlx::Error> {
+    let (block_number, timestamp, transaction_hash) = transaction;
+    let query = match reason {
+        Some(_) => format!(
+            "INSERT INTO {} (block_number, time_stamp, transaction_hash, key, reason)
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+            table
+        ),
+        None => format!(
+            "INSERT INTO {} (block_number, time_stamp, transaction_hash, key)
+             VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+            table
+        ),
+    };
+    let mut query = sqlx::query(&query)
+        .bind(block_number)
+        .bind(timestamp)
+        .bind(transaction_hash)
+        .bind(key);
+    if let Some(reason) = reason {
+        query = query.bind(reason);
+    }
+    query.execute(pool).await?;
+    Ok(())
+}
+
+// Inserts an update of the funding fee or claimable funding amount per size of a market side.
+// @side: The market, collateral token and direction of the updated side.
+// @values: The delta and next value of the amount per size.
+async fn insert_amount_per_size_update(
+    pool: &SqlitePool,
+    table: &str,
+    transaction: (i64, &Option<String>, &String),
+    side: (&Option<String>, &Option<String>, Option<bool>),
+    values: (&Option<BigDecimal>, &Option<BigDecimal>),
+) -> Result<(), sqlx::Error> {
+    let (block_number, timestamp, transaction_hash) = transaction;
+    sqlx::query(&format!(
+        "INSERT INTO {} (
+            block_number, time_stamp, transaction_hash, market, collateral_token, is_long, delta,
+            next_value
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8
+        ) ON CONFLICT DO NOTHING",
+        table
+    ))
+    .bind(block_number)
+    .bind(timestamp)
+    .bind(transaction_hash)
+    .bind(side.0)
+    .bind(side.1)
+    .bind(side.2)
+    .bind(text(values.0.as_ref()))
+    .bind(text(values.1.as_ref()))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Inserts a position increase or decrease.
+// @position: The key, account, market and collateral token of the position.
+// @amounts: The execution price, size delta in USD and size delta in tokens.
+async fn insert_position_change(
+    pool: &SqlitePool,
+    table: &str,
+    transaction: (i64, &Option<String>, &String),
+    position: (
+        &Option<String>,
+        &Option<String>,
+        &Option<String>,
+        &Option<String>,
+    ),
+    amounts: [Option<&BigDecimal>; 3],
+) -> Result<(), sqlx::Error> {
+    let (block_number, timestamp, transaction_hash) = transaction;
+    sqlx::query(&format!(
+        "INSERT INTO {} (
+            block_number, time_stamp, transaction_hash, key, account, market, collateral_token,
+            execution_price, size_delta_usd, size_delta_in_tokens
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+        ) ON CONFLICT DO NOTHING",
+        table
+    ))
+    .bind(block_number)
+    .bind(timestamp)
+    .bind(transaction_hash)
+    .bind(position.0)
+    .bind(position.1)
+    .bind(position.2)
+    .bind(position.3)
+    .bind(text(amounts[0]))
+    .bind(text(amounts[1]))
+    .bind(text(amounts[2]))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::head_chain::HeadChain;
+    use sqlx::any::AnyPoolOptions;
+
+    const ACCOUNT: &str = "00000000000000000000000000000000000000000000000000000000000000ac";
+    const MARKET: &str = "000000000000000000000000000000000000000000000000000000000000000a";
+    const USDC: &str = "0000000000000000000000000000000000000000000000000000000000000c01";
+
+    fn amount(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let path = std::env::temp_dir().join(format!("satoru-indexer-{}.db", std::process::id()));
+        let url = format!("sqlite:{}", path.display());
+        let store = SqliteStore::connect(&url).await.unwrap();
+
+        // The cursors are in the same database, through the Any driver.
+        let cursors = AnyPoolOptions::new().connect(&url).await.unwrap();
+        let head_chain = HeadChain::new(cursors.clone(), "sqlite".to_owned());
+        head_chain
+            .register_shard(&["OrderExecuted"], 0, None, 300)
+            .await
+            .unwrap();
+        // Live shards keep their events and blocks, expired ones release them.
+        let other = HeadChain::new(cursors, "other".to_owned());
+        assert!(other
+            .register_shard(&["OrderExecuted"], 10, Some(20), 300)
+            .await
+            .is_err());
+        other
+            .register_shard(&["OrderCancelled"], 10, Some(20), 300)
+            .await
+            .unwrap();
+        other
+            .register_shard(&["OrderExecuted"], 10, Some(20), 0)
+            .await
+            .unwrap();
+        head_chain.update_last_block_indexed(12).await.unwrap();
+        assert_eq!(head_chain.get_last_block_indexed().await.unwrap(), 12);
+
+        let key = Some(format!("{:064x}", 0xf1));
+        store
+            .insert_order(&Order {
+                block_number: 10,
+                timestamp: None,
+                transaction_hash: "0x10".to_owned(),
+                key: key.clone(),
+                order_type: Some(crate::events::order::OrderType::MarketIncrease),
+                decrease_position_swap_type: None,
+                account: Some(ACCOUNT.to_owned()),
+                receiver: None,
+                callback_contract: None,
+                ui_fee_receiver: None,
+                market: Some(MARKET.to_owned()),
+                initial_collateral_token: Some(USDC.to_owned()),
+                swap_path: None,
+                size_delta_usd: amount("1000000000000000000000000000000000").map(Usd::from_raw),
+                initial_collateral_delta_amount: None,
+                trigger_price: None,
+                acceptable_price: None,
+                execution_fee: None,
+                callback_gas_limit: None,
+                min_output_amount: None,
+                updated_at_block: None,
+                is_long: Some(true),
+                is_frozen: Some(false),
+            })
+            .await
+            .unwrap();
+        store
+            .insert_order_executed(&OrderExecuted {
+                block_number: 11,
+                timestamp: None,
+                transaction_hash: "0x11".to_owned(),
+                key,
+                secondary_order_type: None,
+                keeper: None,
+            })
+            .await
+            .unwrap();
+        store
+            .insert_position_increase(&PositionIncrease {
+                block_number: 11,
+                timestamp: None,
+                transaction_hash: "0x11".to_owned(),
+                key: None,
+                account: Some(ACCOUNT.to_owned()),
+                market: Some(MARKET.to_owned()),
+                collateral_token: Some(USDC.to_owned()),
+                execution_price: None,
+                size_delta_usd: amount("1000000000000000000000000000000000").map(Usd::from_raw),
+                size_delta_in_tokens: None,
+            })
+            .await
+            .unwrap();
+        store
+            .insert_funding_fee_amount_per_size_updated(&FundingFeeAmountPerSizeUpdated {
+                block_number: 12,
+                timestamp: None,
+                transaction_hash: "0x12".to_owned(),
+                market: Some(MARKET.to_owned()),
+                collateral_token: Some(USDC.to_owned()),
+                is_long: Some(true),
+                delta: amount("2000000"),
+                next_value: amount("2000000"),
+            })
+            .await
+            .unwrap();
+        store
+            .insert_cumulative_borrowing_factor_updated(&CumulativeBorrowingFactorUpdated {
+                block_number: 12,
+                timestamp: None,
+                transaction_hash: "0x12".to_owned(),
+                market: Some(MARKET.to_owned()),
+                is_long: Some(true),
+                delta: amount("3000000"),
+                next_value: amount("3000000"),
+            })
+            .await
+            .unwrap();
+
+        // The 34 digits sizes are kept exact as text, as NUMERIC in Postgres.
+        let payments: Vec<(String, String, String)> =
+            sqlx::query_as("SELECT account, size_in_usd, amount FROM funding_payments")
+                .fetch_all(&store.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            payments,
+            vec![(
+                ACCOUNT.to_owned(),
+                "1000000000000000000000000000000000".to_owned(),
+                "2000000000".to_owned()
+            )]
+        );
+        let accruals: Vec<(String, String)> =
+            sqlx::query_as("SELECT collateral_token, amount_usd FROM borrowing_fee_accruals")
+                .fetch_all(&store.pool)
+                .await
+                .unwrap();
+        assert_eq!(accruals, vec![(USDC.to_owned(), "3000000000".to_owned())]);
+
+        // Deposit amounts past 64 bits are kept exact as text too.
+        store
+            .insert_deposit(&Deposit {
+                block_number: 13,
+                transaction_hash: "0x13".to_owned(),
+                key: None,
+                account: Some(ACCOUNT.to_owned()),
+                receiver: None,
+                callback_contract: None,
+                market: Some(MARKET.to_owned()),
+                initial_long_token: None,
+                initial_short_token: Some(USDC.to_owned()),
+                long_token_swap_path: None,
+                short_token_swap_path: None,
+                initial_long_token_amount: amount("10000000000000000000")
+                    .map(TokenAmount::from_raw),
+                initial_short_token_amount: None,
+                min_market_tokens: None,
+                updated_at_block: None,
+                execution_fee: None,
+                callback_gas_limit: None,
+            })
+            .await
+            .unwrap();
+        let deposits: Vec<(String,)> =
+            sqlx::query_as("SELECT initial_long_token_amount FROM deposits")
+                .fetch_all(&store.pool)
+                .await
+                .unwrap();
+        assert_eq!(deposits, vec![("10000000000000000000".to_owned(),)]);
+
+        // Migrations applied once are not applied again.
+        SqliteStore::connect(&url).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let size = BigDecimal::from_str("1500000000000000000000000000000").unwrap();
+        assert_eq!(
+            apply_delta(&size, &BigDecimal::from(3)),
+            BigDecimal::from(4)
+        );
+    }
+}
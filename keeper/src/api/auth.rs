@@ -0,0 +1,257 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorTooManyRequests, ErrorUnauthorized},
+    http::Method,
+    middleware::Next,
+    web, Error,
+};
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+// The header clients send their API key in.
+const API_KEY_HEADER: &str = "X-Api-Key";
+// The window the requests of a key are limited over.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+// The routes not reading any account data, callable with account keys.
+const ACCOUNT_FREE_ROUTES: [&str; 2] = ["/orders/preview", "/positions/liquidation-price"];
+// The routes account keys can write to, for their own account only.
+const ACCOUNT_WRITE_ROUTES: [&str; 4] = ["/webhooks", "/relay", "/watches", "/delegations"];
+// The routes operator keys can write to, pausing and resuming the transactions of the keepers or
+// promoting a standby, with the routes nested under them, e.g. /maintenance/{id}.
+const OPERATOR_WRITE_ROUTES: [&str; 3] = ["/kill-switch", "/maintenance", "/standby"];
+// The routes served without API key, holding no data, e.g. the status page reading its data with
+// a viewer key.
+const PUBLIC_ROUTES: [&str; 1] = ["/dashboard"];
+
+// A struct representing an API key, as stored in the api_keys table.
+// @name: The name of the key, its rate limit being tracked under it.
+// @scope: The role of the key (viewer, operator, admin, account), read-only keys being viewer ones.
+// @account: The only account an account key can read the data of.
+// @requests_per_minute: The maximum number of requests per minute, no limit when 0.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ApiKey {
+    pub name: String,
+    pub scope: String,
+    pub account: Option<String>,
+    pub requests_per_minute: i32,
+}
+
+impl ApiKey {
+    // Checks the key can call a route. Viewer keys can call every read route, e.g. for dashboards,
+    // operator keys can also pause and resume transactions with the kill switch and maintenance
+    // windows or promote a standby, and admin keys can call every route, e.g. to acknowledge
+    // contract upgrades. Account keys are restricted to the requests filtered on their account,
+    // writing only its callback, watch and delegation or relaying its outside executions.
+    // @method: The method of the request.
+    // @path: The path of the request.
+    // @account: The account the request is filtered on, if any.
+    pub fn allows(&self, method: &Method, path: &str, account: Option<&str>) -> bool {
+        let write = method != Method::GET && method != Method::HEAD;
+        match self.scope.as_str() {
+            "admin" => true,
+            "operator" => !write || is_nested_route(&OPERATOR_WRITE_ROUTES, path),
+            "viewer" | "read-only" => !write,
+            "account" if write && !ACCOUNT_WRITE_ROUTES.contains(&path) => false,
+            // Accounts are compared as felts, the indexer stores them without 0x prefix.
+            "account" => match (account, self.account.as_deref()) {
+                (Some(account), Some(key_account)) => {
+                    let account = FieldElement::from_hex_be(account);
+                    account.is_ok() && account.ok() == FieldElement::from_hex_be(key_account).ok()
+                }
+                (None, _) => ACCOUNT_FREE_ROUTES.contains(&path),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+// Returns whether a path is one of the routes or nested under one of them.
+// @routes: The routes.
+// @path: The path of the request.
+fn is_nested_route(routes: &[&str], path: &str) -> bool {
+    routes.iter().any(|route| {
+        path.strip_prefix(route)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+// Loads an API key not revoked, keys being stored hashed.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The API key sent by the client.
+pub async fn get_api_key(pool: &Pool<Postgres>, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>(
+        "SELECT name, scope, account, requests_per_minute FROM api_keys
+         WHERE key_hash = encode(sha256(convert_to($1, 'UTF8')), 'hex') AND revoked_at IS NULL",
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await
+}
+
+// A struct limiting how many requests are served per API key and minute, shared by the API workers.
+// @requests: The recent requests per key name.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    requests: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    // Counts a request of a key, returns false if the key is over its limit.
+    // @key: The API key.
+    pub fn allow(&self, key: &ApiKey) -> bool {
+        self.allow_at(key, Instant::now())
+    }
+
+    fn allow_at(&self, key: &ApiKey, now: Instant) -> bool {
+        if key.requests_per_minute <= 0 {
+            return true;
+        }
+        let mut requests = self.requests.lock().unwrap();
+        let key_requests = requests.entry(key.name.clone()).or_default();
+        while key_requests
+            .front()
+            .is_some_and(|request| now.duration_since(*request) >= RATE_LIMIT_WINDOW)
+        {
+            key_requests.pop_front();
+        }
+        if key_requests.len() >= key.requests_per_minute as usize {
+            return false;
+        }
+        key_requests.push_back(now);
+        true
+    }
+}
+
+// Returns whether a request gets served without API key.
+// @method: The method of the request.
+// @path: The path of the request.
+pub fn is_public_route(method: &Method, path: &str) -> bool {
+    method == Method::GET && PUBLIC_ROUTES.contains(&path)
+}
+
+// Middleware authenticating requests with their API key, checking its scope and rate limit
+// before serving them.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if is_public_route(req.method(), req.path()) {
+        return next.call(req).await;
+    }
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|key| key.to_str().ok())
+        .ok_or_else(|| ErrorUnauthorized("missing API key"))?
+        .to_owned();
+    let pool = req
+        .app_data::<web::Data<Pool<Postgres>>>()
+        .ok_or_else(|| ErrorInternalServerError("no database"))?;
+    let api_key = get_api_key(pool, &key)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorUnauthorized("invalid API key"))?;
+
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|query| query.into_inner())
+        .unwrap_or_default();
+    let account = query.get("account").map(|account| account.as_str());
+    if !api_key.allows(req.method(), req.path(), account) {
+        return Err(ErrorForbidden("route not allowed for the API key"));
+    }
+    let rate_limiter = req
+        .app_data::<web::Data<RateLimiter>>()
+        .ok_or_else(|| ErrorInternalServerError("no rate limiter"))?;
+    if !rate_limiter.allow(&api_key) {
+        return Err(ErrorTooManyRequests("rate limit exceeded"));
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_key(scope: &str, account: Option<&str>) -> ApiKey {
+        ApiKey {
+            name: "ui".to_owned(),
+            scope: scope.to_owned(),
+            account: account.map(|account| account.to_owned()),
+            requests_per_minute: 2,
+        }
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        let read_only = api_key("read-only", None);
+        assert!(read_only.allows(&Method::GET, "/pnl", None));
+        assert!(!read_only.allows(&Method::POST, "/pnl", None));
+        let viewer = api_key("viewer", None);
+        assert!(viewer.allows(&Method::GET, "/kill-switch", None));
+        assert!(!viewer.allows(&Method::POST, "/kill-switch", None));
+
+        let account = api_key("account", Some("0x1a"));
+        assert!(account.allows(&Method::GET, "/positions", Some("1a")));
+        assert!(!account.allows(&Method::GET, "/positions", Some("1b")));
+        assert!(!account.allows(&Method::GET, "/positions", None));
+        assert!(account.allows(&Method::GET, "/orders/preview", None));
+        assert!(account.allows(&Method::PUT, "/webhooks", Some("1a")));
+        assert!(account.allows(&Method::DELETE, "/watches", Some("1a")));
+        assert!(account.allows(&Method::PUT, "/delegations", Some("0x1a")));
+        assert!(!account.allows(&Method::PUT, "/webhooks", Some("1b")));
+        assert!(account.allows(&Method::POST, "/relay", Some("1a")));
+        assert!(!account.allows(&Method::POST, "/relay", Some("1b")));
+        assert!(!account.allows(&Method::POST, "/kill-switch", None));
+        assert!(!read_only.allows(&Method::PUT, "/webhooks", Some("1a")));
+        assert!(!api_key("root", None).allows(&Method::GET, "/pnl", None));
+
+        let operator = api_key("operator", None);
+        assert!(operator.allows(&Method::POST, "/kill-switch", None));
+        assert!(operator.allows(&Method::DELETE, "/maintenance/3", None));
+        assert!(operator.allows(&Method::GET, "/upgrades", None));
+        assert!(operator.allows(&Method::POST, "/standby/promote", None));
+        assert!(!operator.allows(&Method::POST, "/upgrades/1/acknowledge", None));
+        assert!(!operator.allows(&Method::POST, "/maintenance-extra", None));
+        assert!(!operator.allows(&Method::PUT, "/webhooks", Some("1a")));
+        assert!(!read_only.allows(&Method::POST, "/kill-switch", None));
+
+        let admin = api_key("admin", None);
+        assert!(admin.allows(&Method::POST, "/upgrades/1/acknowledge", None));
+        assert!(admin.allows(&Method::PUT, "/webhooks", Some("1b")));
+    }
+
+    #[test]
+    fn test_public_routes() {
+        assert!(is_public_route(&Method::GET, "/dashboard"));
+        assert!(!is_public_route(&Method::GET, "/dashboard/status"));
+        assert!(!is_public_route(&Method::POST, "/dashboard"));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let rate_limiter = RateLimiter::default();
+        let key = api_key("read-only", None);
+        let now = Instant::now();
+
+        assert!(rate_limiter.allow_at(&key, now));
+        assert!(rate_limiter.allow_at(&key, now));
+        assert!(!rate_limiter.allow_at(&key, now + Duration::from_secs(30)));
+        assert!(rate_limiter.allow_at(&key, now + Duration::from_secs(60)));
+
+        let unlimited = ApiKey {
+            requests_per_minute: 0,
+            ..api_key("read-only", None)
+        };
+        for _ in 0..5 {
+            assert!(rate_limiter.allow_at(&unlimited, now));
+        }
+    }
+}
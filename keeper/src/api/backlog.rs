@@ -0,0 +1,13 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::backlog::get_backlog;
+
+// Returns the actions awaiting keeper action per market, largest backlogs first.
+#[get("/backlog")]
+pub async fn get_market_backlog(pool: web::Data<Pool<Postgres>>) -> impl Responder {
+    match get_backlog(&pool).await {
+        Ok(backlog) => HttpResponse::Ok().json(backlog),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Mutex;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    dashboard::{get_dashboard_status, ChainStatus},
+    killswitch::KillSwitch,
+};
+
+// The status page, reading the status route every few seconds.
+const DASHBOARD_PAGE: &str = include_str!("dashboard.html");
+
+// Serves the status page, for operators without a monitoring stack. The page holds no data, the
+// API key it reads the status with being taken from the URL fragment, e.g. /dashboard#<key>.
+#[get("/dashboard")]
+pub async fn get_dashboard() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DASHBOARD_PAGE)
+}
+
+// Returns the indexer lag, pending jobs, recent executions, keeper balance and kill switch state
+// shown on the status page.
+#[get("/dashboard/status")]
+pub async fn get_dashboard_data(
+    pool: web::Data<Pool<Postgres>>,
+    chain_status: web::Data<Mutex<ChainStatus>>,
+    kill_switch: web::Data<KillSwitch>,
+) -> impl Responder {
+    let chain = chain_status.lock().unwrap().clone();
+    match get_dashboard_status(&pool, chain, &kill_switch).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
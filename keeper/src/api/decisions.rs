@@ -0,0 +1,24 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::decisions::get_decisions;
+
+// The query parameters of the decisions route.
+// @key: The key of the action, as indexed.
+#[derive(Deserialize, Debug)]
+pub struct DecisionsQuery {
+    pub key: String,
+}
+
+// Returns every decision the keeper made on an action, oldest first.
+#[get("/decisions")]
+pub async fn get_action_decisions(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<DecisionsQuery>,
+) -> impl Responder {
+    match get_decisions(&pool, &query.key).await {
+        Ok(decisions) => HttpResponse::Ok().json(decisions),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,101 @@
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    delegation::{delete_delegation, get_delegation, save_delegation, AccountDelegation},
+    webhooks::to_webhook_account,
+};
+
+// The query parameters of the delegation routes, account keys only managing their own preferences.
+// @account: The account the preferences are for.
+#[derive(Deserialize, Debug)]
+pub struct DelegationQuery {
+    pub account: String,
+}
+
+// The body of the delegation registration route, every action being opted out of when unset.
+// @auto_claim_funding: Whether the funding fees of the account get claimed for it.
+// @auto_cancel_stale_orders: Whether its orders pending for too long get cancelled for it.
+// @stale_order_secs: How long its orders stay pending before being stale.
+// @notification_channels: The channels it gets notified on (webhook, telegram).
+#[derive(Deserialize, Debug)]
+pub struct DelegationRequest {
+    #[serde(default)]
+    pub auto_claim_funding: bool,
+    #[serde(default)]
+    pub auto_cancel_stale_orders: bool,
+    pub stale_order_secs: Option<i64>,
+    #[serde(default)]
+    pub notification_channels: Vec<String>,
+}
+
+// Returns the managed execution preferences of an account.
+#[get("/delegations")]
+pub async fn get_account_delegation(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<DelegationQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match get_delegation(&pool, &account).await {
+        Ok(Some(delegation)) => HttpResponse::Ok().json(delegation),
+        Ok(None) => HttpResponse::NotFound().body("no delegation for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Registers the managed execution preferences of an account, the account-service keepers then
+// acting on its behalf for the actions it opted into.
+#[put("/delegations")]
+pub async fn set_account_delegation(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<DelegationQuery>,
+    request: web::Json<DelegationRequest>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    let request = request.into_inner();
+    // Each channel gets notified once, in the order given.
+    let mut notification_channels: Vec<String> = vec![];
+    for channel in request.notification_channels {
+        if !notification_channels.contains(&channel) {
+            notification_channels.push(channel);
+        }
+    }
+    let delegation = AccountDelegation {
+        account,
+        auto_claim_funding: request.auto_claim_funding,
+        auto_cancel_stale_orders: request.auto_cancel_stale_orders,
+        stale_order_secs: request.stale_order_secs,
+        notification_channels,
+    };
+    if let Err(e) = delegation.check() {
+        return HttpResponse::BadRequest().body(e);
+    }
+    match save_delegation(&pool, &delegation).await {
+        Ok(()) => HttpResponse::Ok().json(delegation),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Removes the preferences of an account, nothing getting done on its behalf anymore.
+#[delete("/delegations")]
+pub async fn delete_account_delegation(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<DelegationQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match delete_delegation(&pool, &account).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body("no delegation for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
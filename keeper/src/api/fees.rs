@@ -0,0 +1,36 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::fees::get_order_fees;
+
+// The most actions the fees route answers with.
+const MAX_FEES: i64 = 500;
+
+// The query parameters of the fees route, one of key and account being required.
+// @key: The key of the action, as indexed.
+// @account: The account to list the actions of, as indexed.
+// @limit: The most actions returned, 100 by default.
+#[derive(Deserialize, Debug)]
+pub struct FeesQuery {
+    pub key: Option<String>,
+    pub account: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// Returns the fee economics of an action or of the actions of an account, most recent first: the
+// execution fee paid, the keeper fee, the refund and the gas the keeper spent.
+#[get("/fees")]
+pub async fn get_action_fees(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<FeesQuery>,
+) -> impl Responder {
+    if query.key.is_none() && query.account.is_none() {
+        return HttpResponse::BadRequest().body("key or account is required");
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_FEES);
+    match get_order_fees(&pool, query.key.as_deref(), query.account.as_deref(), limit).await {
+        Ok(fees) => HttpResponse::Ok().json(fees),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
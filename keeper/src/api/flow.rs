@@ -0,0 +1,47 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::flow::{get_frontend_flow, FlowBucket, MAX_FLOW_DAYS};
+
+// The query parameters of the frontend stats route.
+// @bucket: The time buckets of the breakdown (hourly, daily, weekly), daily by default.
+// @days: How many days back the orders are counted, 30 by default.
+#[derive(Deserialize, Debug)]
+pub struct FrontendFlowQuery {
+    pub bucket: Option<String>,
+    pub days: Option<u64>,
+}
+
+// Returns the order counts, volume and maker/taker mix per UI fee receiver and time bucket, most
+// recent buckets first.
+#[get("/stats/frontends")]
+pub async fn get_frontend_stats(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<FrontendFlowQuery>,
+) -> impl Responder {
+    let bucket = match query
+        .bucket
+        .as_deref()
+        .unwrap_or("daily")
+        .parse::<FlowBucket>()
+    {
+        Ok(bucket) => bucket,
+        Err(_) => return HttpResponse::BadRequest().body("bucket must be hourly, daily or weekly"),
+    };
+    let days = query.days.unwrap_or(30);
+    if days == 0 || days > MAX_FLOW_DAYS {
+        return HttpResponse::BadRequest()
+            .body(format!("days must be between 1 and {}", MAX_FLOW_DAYS));
+    }
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default();
+    match get_frontend_flow(&pool, bucket, now_secs.saturating_sub(days * 86_400)).await {
+        Ok(flow) => HttpResponse::Ok().json(flow),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
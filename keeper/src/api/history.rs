@@ -0,0 +1,38 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::{
+    competition::to_indexed_address,
+    history::{get_account_history, to_history_csv},
+};
+
+// The query parameters of the history route.
+// @account: The account to export the history of.
+#[derive(Deserialize, Debug)]
+pub struct HistoryQuery {
+    pub account: String,
+}
+
+// Returns the complete trade and funding history of an account as a CSV file, for tax tools.
+#[get("/history")]
+pub async fn get_account_history_csv(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<HistoryQuery>,
+) -> impl Responder {
+    let account = match FieldElement::from_hex_be(&query.account) {
+        Ok(account) => to_indexed_address(account),
+        Err(_) => return HttpResponse::BadRequest().body("account must be a hex address"),
+    };
+    match get_account_history(&pool, &account).await {
+        Ok(entries) => HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"history-{}.csv\"", account),
+            ))
+            .body(to_history_csv(&entries)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,13 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::registry::get_keepers;
+
+// Returns the registered keeper instances, whether they are alive and how they are configured.
+#[get("/keepers")]
+pub async fn get_keeper_instances(pool: web::Data<Pool<Postgres>>) -> impl Responder {
+    match get_keepers(&pool).await {
+        Ok(keepers) => HttpResponse::Ok().json(keepers),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
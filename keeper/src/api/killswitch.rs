@@ -0,0 +1,185 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    clock::get_system_timestamp,
+    killswitch::{
+        delete_maintenance_window, load_maintenance_windows, save_kill_switch,
+        save_maintenance_window, KillSwitch,
+    },
+};
+
+// The body of the kill switch route.
+// @engaged: Whether to stop outgoing transactions.
+// @reason: Why the switch gets engaged, logged and reported with the paused jobs.
+#[derive(Deserialize, Debug)]
+pub struct KillSwitchRequest {
+    pub engaged: bool,
+    pub reason: Option<String>,
+}
+
+// Returns whether outgoing transactions are stopped, and why.
+#[get("/kill-switch")]
+pub async fn get_kill_switch(kill_switch: web::Data<KillSwitch>) -> impl Responder {
+    HttpResponse::Ok().json(kill_switch.status())
+}
+
+// Engages or disengages the kill switch, at once on this instance and within a poll interval on
+// the others sharing the database. The DataStore pause flag cannot be cleared from here.
+#[post("/kill-switch")]
+pub async fn set_kill_switch(
+    pool: web::Data<Pool<Postgres>>,
+    kill_switch: web::Data<KillSwitch>,
+    request: web::Json<KillSwitchRequest>,
+) -> impl Responder {
+    let reason = match request.engaged {
+        true => Some(
+            request
+                .reason
+                .clone()
+                .unwrap_or_else(|| "engaged through the admin API".to_owned()),
+        ),
+        false => None,
+    };
+    if let Err(e) = save_kill_switch(&pool, reason.as_deref()).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    kill_switch.set_operator_reason(reason);
+    HttpResponse::Ok().json(kill_switch.status())
+}
+
+// The body of the maintenance window route.
+// @starts_at: When the window starts, as a unix timestamp, now when unset.
+// @ends_at: When the window ends, as a unix timestamp, at least one of it and duration_secs being
+// set.
+// @duration_secs: How long the window lasts, when ends_at is unset.
+// @reason: Why the keeper is under maintenance, logged and reported with the paused jobs.
+#[derive(Deserialize, Debug)]
+pub struct MaintenanceRequest {
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+    pub duration_secs: Option<i64>,
+    pub reason: Option<String>,
+}
+
+impl MaintenanceRequest {
+    // Returns the start and end of the window, or why they are invalid.
+    // @now: The current unix timestamp.
+    fn bounds(&self, now: i64) -> Result<(i64, i64), &'static str> {
+        let starts_at = self.starts_at.unwrap_or(now);
+        let ends_at = match (self.ends_at, self.duration_secs) {
+            (Some(_), Some(_)) => return Err("ends_at and duration_secs are exclusive"),
+            (Some(ends_at), None) => ends_at,
+            (None, Some(duration)) if duration > 0 => starts_at.saturating_add(duration),
+            (None, Some(_)) => return Err("duration_secs must be positive"),
+            (None, None) => return Err("ends_at or duration_secs must be set"),
+        };
+        if ends_at <= starts_at {
+            return Err("the window must end after it starts");
+        }
+        if ends_at <= now {
+            return Err("the window already ended");
+        }
+        Ok((starts_at, ends_at))
+    }
+}
+
+// Reloads the scheduled maintenance windows of this instance, the others within a poll interval.
+async fn reload_maintenance_windows(
+    pool: &Pool<Postgres>,
+    kill_switch: &KillSwitch,
+) -> Result<(), sqlx::Error> {
+    kill_switch.set_scheduled_windows(load_maintenance_windows(pool).await?);
+    Ok(())
+}
+
+// Returns the maintenance windows which did not end yet, configured or scheduled.
+#[get("/maintenance")]
+pub async fn get_maintenance_windows(kill_switch: web::Data<KillSwitch>) -> impl Responder {
+    let now = get_system_timestamp() as i64;
+    let windows: Vec<_> = kill_switch
+        .maintenance_windows()
+        .into_iter()
+        .filter(|window| window.ends_at > now)
+        .collect();
+    HttpResponse::Ok().json(windows)
+}
+
+// Schedules a maintenance window, e.g. for a coordinated contract upgrade, every instance sharing
+// the database stopping its transactions while it lasts and resuming them once it ends.
+#[post("/maintenance")]
+pub async fn schedule_maintenance_window(
+    pool: web::Data<Pool<Postgres>>,
+    kill_switch: web::Data<KillSwitch>,
+    request: web::Json<MaintenanceRequest>,
+) -> impl Responder {
+    let (starts_at, ends_at) = match request.bounds(get_system_timestamp() as i64) {
+        Ok(bounds) => bounds,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let reason = request
+        .reason
+        .clone()
+        .filter(|reason| !reason.trim().is_empty())
+        .unwrap_or_else(|| "scheduled maintenance".to_owned());
+    if let Err(e) = save_maintenance_window(&pool, starts_at, ends_at, &reason).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    if let Err(e) = reload_maintenance_windows(&pool, &kill_switch).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok().json(kill_switch.maintenance_windows())
+}
+
+// Cancels a scheduled maintenance window, or ends an ongoing one early. Configured windows can
+// only be removed from the MAINTENANCE_WINDOWS variable.
+#[delete("/maintenance/{id}")]
+pub async fn cancel_maintenance_window(
+    pool: web::Data<Pool<Postgres>>,
+    kill_switch: web::Data<KillSwitch>,
+    id: web::Path<i64>,
+) -> impl Responder {
+    match delete_maintenance_window(&pool, id.into_inner()).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::NotFound().body("no maintenance window with this id"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+    match reload_maintenance_windows(&pool, &kill_switch).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_bounds() {
+        let request = |starts_at, ends_at, duration_secs| MaintenanceRequest {
+            starts_at,
+            ends_at,
+            duration_secs,
+            reason: None,
+        };
+        assert_eq!(
+            request(None, None, Some(600)).bounds(1000),
+            Ok((1000, 1600))
+        );
+        assert_eq!(
+            request(Some(2000), Some(3000), None).bounds(1000),
+            Ok((2000, 3000))
+        );
+        // An ongoing window can be scheduled, to record a maintenance already started.
+        assert_eq!(
+            request(Some(500), Some(1500), None).bounds(1000),
+            Ok((500, 1500))
+        );
+        assert!(request(None, None, None).bounds(1000).is_err());
+        assert!(request(None, Some(1500), Some(600)).bounds(1000).is_err());
+        assert!(request(None, None, Some(0)).bounds(1000).is_err());
+        assert!(request(Some(2000), Some(2000), None).bounds(1000).is_err());
+        assert!(request(Some(500), Some(900), None).bounds(1000).is_err());
+    }
+}
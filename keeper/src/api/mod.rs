@@ -0,0 +1,24 @@
+pub mod auth;
+pub mod backlog;
+pub mod dashboard;
+pub mod decisions;
+pub mod delegations;
+pub mod fees;
+pub mod flow;
+pub mod history;
+pub mod keepers;
+pub mod killswitch;
+pub mod orders;
+pub mod pnl;
+pub mod portfolio;
+pub mod positions;
+pub mod relay;
+#[cfg(feature = "liquidation")]
+pub mod risk;
+pub mod server;
+pub mod standby;
+pub mod upgrades;
+pub mod vwap;
+#[cfg(feature = "liquidation")]
+pub mod watches;
+pub mod webhooks;
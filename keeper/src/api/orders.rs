@@ -0,0 +1,73 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    market_config::MarketConfigs,
+    preview::{get_open_interest, PreviewOrder, PreviewParams},
+    trace::get_execution_trace,
+};
+
+// The query parameters of the order preview route, amounts in USD not scaled by their decimals.
+// @market: The market of the order, as indexed.
+// @is_long: Whether the order is on the long side.
+// @is_increase: Whether the order increases a position, true by default.
+// @size_delta_usd: The size of the order.
+// @collateral_usd: The collateral deposited by an increase, 0 by default.
+// @index_price: The current index token price.
+#[derive(Deserialize, Debug)]
+pub struct OrderPreviewQuery {
+    pub market: String,
+    pub is_long: bool,
+    pub is_increase: Option<bool>,
+    pub size_delta_usd: f64,
+    pub collateral_usd: Option<f64>,
+    pub index_price: f64,
+}
+
+// Returns the execution trace of an order: the order as indexed, the keeper execution job and
+// every decision the keeper made on it.
+#[get("/orders/{key}/execution-trace")]
+pub async fn get_order_execution_trace(
+    pool: web::Data<Pool<Postgres>>,
+    key: web::Path<String>,
+) -> impl Responder {
+    match get_execution_trace(&pool, &key).await {
+        Ok(trace) if trace.is_empty() => HttpResponse::NotFound().body("unknown order"),
+        Ok(trace) => HttpResponse::Ok().json(trace),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Returns the estimated execution price, price impact, fees and leverage of a prospective order,
+// priced against the indexed open interest of its market. Served along the keeper, the market
+// parameters are the DataStore ones, the configured ones otherwise.
+#[get("/orders/preview")]
+pub async fn get_order_preview(
+    pool: web::Data<Pool<Postgres>>,
+    market_configs: Option<web::Data<MarketConfigs>>,
+    query: web::Query<OrderPreviewQuery>,
+) -> impl Responder {
+    if query.size_delta_usd <= 0.0 || query.index_price <= 0.0 {
+        return HttpResponse::BadRequest().body("size_delta_usd and index_price must be positive");
+    }
+    let params = match market_configs {
+        Some(market_configs) => match market_configs.get_indexed(&query.market).await {
+            Ok(market_config) => market_config.preview_params(),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        None => PreviewParams::from_env(),
+    };
+    let open_interest = match get_open_interest(&pool, &query.market).await {
+        Ok(open_interest) => open_interest,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let order = PreviewOrder {
+        is_long: query.is_long,
+        is_increase: query.is_increase.unwrap_or(true),
+        size_delta_usd: query.size_delta_usd,
+        collateral_usd: query.collateral_usd.unwrap_or(0.0),
+        index_price: query.index_price,
+    };
+    HttpResponse::Ok().json(params.preview(&open_interest, &order))
+}
@@ -0,0 +1,33 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::pnl::{get_keeper_pnl, PnlPeriod};
+
+// The query parameters of the PnL route.
+// @period: The period the PnL is aggregated over (daily, weekly), daily by default.
+#[derive(Deserialize, Debug)]
+pub struct PnlQuery {
+    pub period: Option<String>,
+}
+
+// Returns the keeper PnL per period, most recent first.
+#[get("/pnl")]
+pub async fn get_pnl(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<PnlQuery>,
+) -> impl Responder {
+    let period = match query
+        .period
+        .as_deref()
+        .unwrap_or("daily")
+        .parse::<PnlPeriod>()
+    {
+        Ok(period) => period,
+        Err(_) => return HttpResponse::BadRequest().body("period must be daily or weekly"),
+    };
+    match get_keeper_pnl(&pool, period).await {
+        Ok(pnl) => HttpResponse::Ok().json(pnl),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
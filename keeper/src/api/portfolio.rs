@@ -0,0 +1,29 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::portfolio::{get_portfolio, parse_accounts};
+
+// The query parameters of the portfolio route.
+// @accounts: The accounts to combine, as comma separated hex addresses.
+#[derive(Deserialize, Debug)]
+pub struct PortfolioQuery {
+    pub accounts: String,
+}
+
+// Returns the combined exposure per market, collateral, PnL and pending orders of accounts, e.g.
+// the sub-accounts of a fund, computed from the indexed tables in one request.
+#[get("/portfolio")]
+pub async fn get_accounts_portfolio(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<PortfolioQuery>,
+) -> impl Responder {
+    let accounts = match parse_accounts(&query.accounts) {
+        Ok(accounts) => accounts,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_portfolio(&pool, &accounts).await {
+        Ok(portfolio) => HttpResponse::Ok().json(portfolio),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,80 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::positions::get_positions;
+#[cfg(feature = "liquidation")]
+use crate::{
+    liquidation::{LiquidationParams, PositionState},
+    market_config::MarketConfigs,
+};
+
+// The query parameters of the positions route.
+// @account: The account to list the positions of, as indexed, every account when unset.
+#[derive(Deserialize, Debug)]
+pub struct PositionsQuery {
+    pub account: Option<String>,
+}
+
+// The query parameters of the liquidation price route, amounts not scaled by their decimals.
+// @market: The market of the position, its min collateral factor applying.
+// @is_long: Whether the position is long.
+// @size_in_usd: The position size in USD.
+// @size_in_tokens: The position size in index tokens.
+// @collateral_amount: The collateral amount in collateral tokens.
+// @collateral_price: The price of the collateral token, 1 by default.
+// @collateral_is_index_token: Whether the collateral is the index token, false by default.
+// @pending_fees_usd: The borrowing and funding fees still to be paid, 0 by default.
+#[cfg(feature = "liquidation")]
+#[derive(Deserialize, Debug)]
+pub struct LiquidationPriceQuery {
+    pub market: String,
+    pub is_long: bool,
+    pub size_in_usd: f64,
+    pub size_in_tokens: f64,
+    pub collateral_amount: f64,
+    pub collateral_price: Option<f64>,
+    pub collateral_is_index_token: Option<bool>,
+    pub pending_fees_usd: Option<f64>,
+}
+
+// Returns the open positions with their cumulative and pending borrowing fees.
+#[get("/positions")]
+pub async fn get_open_positions(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<PositionsQuery>,
+) -> impl Responder {
+    match get_positions(&pool, query.account.as_deref()).await {
+        Ok(positions) => HttpResponse::Ok().json(positions),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Returns the estimated liquidation price of a position, null if it never gets liquidated. Served
+// along the keeper, the collateral factor of the market is the DataStore one.
+#[cfg(feature = "liquidation")]
+#[get("/positions/liquidation-price")]
+pub async fn get_liquidation_price(
+    market_configs: Option<web::Data<MarketConfigs>>,
+    query: web::Query<LiquidationPriceQuery>,
+) -> impl Responder {
+    let params = match market_configs {
+        Some(market_configs) => match market_configs.get_indexed(&query.market).await {
+            Ok(market_config) => {
+                LiquidationParams::from_env().with_market_config(&query.market, &market_config)
+            }
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        None => LiquidationParams::from_env(),
+    };
+    let position = PositionState {
+        is_long: query.is_long,
+        size_in_usd: query.size_in_usd,
+        size_in_tokens: query.size_in_tokens,
+        collateral_amount: query.collateral_amount,
+        collateral_price: query.collateral_price.unwrap_or(1.0),
+        collateral_is_index_token: query.collateral_is_index_token.unwrap_or(false),
+        pending_fees_usd: query.pending_fees_usd.unwrap_or(0.0),
+    };
+    HttpResponse::Ok().json(params.estimate(&query.market, &position))
+}
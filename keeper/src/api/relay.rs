@@ -0,0 +1,55 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starknet::core::types::FieldElement;
+
+use crate::{error::KeeperError, relay::Relayer};
+
+// The query parameters of the relay route, account keys only relaying the executions of their
+// own account.
+// @account: The trader account the outside execution runs on.
+#[derive(Deserialize, Debug)]
+pub struct RelayQuery {
+    pub account: String,
+}
+
+// The body of the relay route.
+// @typed_data: The SNIP-12 typed data of the SNIP-9 outside execution.
+// @signature: The felts of the trader signature of the typed data.
+#[derive(Deserialize, Debug)]
+pub struct RelayRequest {
+    pub typed_data: Value,
+    pub signature: Vec<String>,
+}
+
+// Relays an outside execution signed by a trader, e.g. a gasless order creation, the keeper
+// sending it and paying its fee once the trader account validated it.
+#[post("/relay")]
+pub async fn relay_outside_execution(
+    relayer: web::Data<Relayer>,
+    query: web::Query<RelayQuery>,
+    request: web::Json<RelayRequest>,
+) -> impl Responder {
+    let account = match FieldElement::from_hex_be(&query.account) {
+        Ok(account) => account,
+        Err(_) => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    let signature = match request
+        .signature
+        .iter()
+        .map(|felt| FieldElement::from_hex_be(felt))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(signature) => signature,
+        Err(_) => return HttpResponse::BadRequest().body("invalid signature"),
+    };
+    match relayer.relay(account, &request.typed_data, signature).await {
+        Ok(transaction_hash) => HttpResponse::Ok()
+            .json(json!({ "transaction_hash": format!("{:#x}", transaction_hash) })),
+        Err(e @ KeeperError::RelayError(_)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e @ (KeeperError::KillSwitchEngaged(_) | KeeperError::StandbyError(_))) => {
+            HttpResponse::ServiceUnavailable().body(e.to_string())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
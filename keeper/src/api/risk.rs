@@ -0,0 +1,18 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::risk::{build_risk_report, RiskParams, PRICE_SHOCKS};
+
+// Returns which indexed positions index price shocks of ±5, 10 and 20% would liquidate at the
+// current prices, with the bad debt and pool impact of each market, for the risk committee.
+#[get("/risk/liquidations")]
+pub async fn get_liquidation_risk(pool: web::Data<Pool<Postgres>>) -> impl Responder {
+    let params = match RiskParams::from_env() {
+        Ok(params) => params,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match build_risk_report(&pool, &params, &PRICE_SHOCKS).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::{Arc, Mutex};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Server, ServiceRequest, ServiceResponse},
+    middleware::{from_fn, Condition, Next},
+    web, App, Error, HttpServer,
+};
+use log::info;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    config, dashboard::ChainStatus, db::with_query_origin, killswitch::KillSwitch,
+    market_config::MarketConfigs, relay::Relayer, standby::Standby,
+};
+
+use super::{
+    auth::{authenticate, RateLimiter},
+    backlog::get_market_backlog,
+    dashboard::{get_dashboard, get_dashboard_data},
+    decisions::get_action_decisions,
+    delegations::{delete_account_delegation, get_account_delegation, set_account_delegation},
+    fees::get_action_fees,
+    flow::get_frontend_stats,
+    history::get_account_history_csv,
+    keepers::get_keeper_instances,
+    killswitch::{
+        cancel_maintenance_window, get_kill_switch, get_maintenance_windows,
+        schedule_maintenance_window, set_kill_switch,
+    },
+    orders::{get_order_execution_trace, get_order_preview},
+    pnl::get_pnl,
+    portfolio::get_accounts_portfolio,
+    positions::get_open_positions,
+    relay::relay_outside_execution,
+    standby::{get_standby, promote_standby},
+    upgrades::{acknowledge_contract_upgrade, get_pending_upgrades},
+    vwap::get_market_daily_vwap,
+    webhooks::{delete_account_webhook, get_account_webhook, set_account_webhook},
+};
+
+// Registers the liquidation price, account watch and risk report routes, built with the liquidation feature
+// only.
+#[cfg(feature = "liquidation")]
+fn configure_liquidation(config: &mut web::ServiceConfig) {
+    config
+        .service(super::positions::get_liquidation_price)
+        .service(super::risk::get_liquidation_risk)
+        .service(super::watches::get_account_watch)
+        .service(super::watches::set_account_watch)
+        .service(super::watches::delete_account_watch);
+}
+
+#[cfg(not(feature = "liquidation"))]
+fn configure_liquidation(_config: &mut web::ServiceConfig) {}
+
+// Middleware serving requests with their route as the origin of their queries, so slow queries
+// get logged with the route issuing them. Routes are logged as patterns, without their parameters.
+async fn scope_query_origin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let route = req.match_pattern().unwrap_or_else(|| req.path().to_owned());
+    with_query_origin(format!("{} {}", req.method(), route), next.call(req)).await
+}
+
+// Binds the admin API operators use to monitor the keeper, the returned server has to be awaited
+// or spawned to serve requests. With API_AUTH_ENABLED set, requests are authenticated with the
+// API keys of the api_keys table and rate limited per key.
+// @pool: The connection pool of the admin API, apart from the keeper one.
+// @kill_switch: The kill switch of the keeper, engaged through the API.
+// @chain_status: What the keeper last read from the chain, shown on the dashboard.
+// @relayer: The relay of the outside executions of traders, the relay route being served with one only.
+// @standby: The role of the executing instance, the standby routes being served with one only.
+// @market_configs: The market configurations of the keeper, markets being previewed with the
+// configured parameters without them.
+// @address: The address to bind, e.g. 127.0.0.1:8081.
+pub fn start_admin_api(
+    pool: Pool<Postgres>,
+    kill_switch: Arc<KillSwitch>,
+    chain_status: Arc<Mutex<ChainStatus>>,
+    relayer: Option<Arc<Relayer>>,
+    standby: Option<Arc<Standby>>,
+    market_configs: Option<Arc<MarketConfigs>>,
+    address: String,
+) -> std::io::Result<Server> {
+    info!("Admin API listening on {}", address);
+    let auth_enabled = config::get_api_auth_enabled();
+    let rate_limiter = web::Data::new(RateLimiter::default());
+    let kill_switch = web::Data::from(kill_switch);
+    let chain_status = web::Data::from(chain_status);
+    let relayer = relayer.map(web::Data::from);
+    let standby = standby.map(web::Data::from);
+    let market_configs = market_configs.map(web::Data::from);
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(rate_limiter.clone())
+            .app_data(kill_switch.clone())
+            .app_data(chain_status.clone())
+            .wrap(Condition::new(auth_enabled, from_fn(authenticate)))
+            .wrap(from_fn(scope_query_origin))
+            .service(get_pnl)
+            .service(get_action_fees)
+            .service(get_market_backlog)
+            .service(get_keeper_instances)
+            .service(get_action_decisions)
+            .service(get_order_execution_trace)
+            .service(get_order_preview)
+            .service(get_open_positions)
+            .service(get_accounts_portfolio)
+            .service(get_frontend_stats)
+            .service(get_market_daily_vwap)
+            .service(get_account_history_csv)
+            .configure(configure_liquidation)
+            .service(get_kill_switch)
+            .service(set_kill_switch)
+            .service(get_maintenance_windows)
+            .service(schedule_maintenance_window)
+            .service(cancel_maintenance_window)
+            .service(get_pending_upgrades)
+            .service(acknowledge_contract_upgrade)
+            .service(get_account_webhook)
+            .service(set_account_webhook)
+            .service(delete_account_webhook)
+            .service(get_account_delegation)
+            .service(set_account_delegation)
+            .service(delete_account_delegation)
+            .service(get_dashboard)
+            .service(get_dashboard_data)
+            .configure(|config| {
+                if let Some(relayer) = &relayer {
+                    config
+                        .app_data(relayer.clone())
+                        .service(relay_outside_execution);
+                }
+            })
+            .configure(|config| {
+                if let Some(market_configs) = &market_configs {
+                    config.app_data(market_configs.clone());
+                }
+            })
+            .configure(|config| {
+                if let Some(standby) = &standby {
+                    config
+                        .app_data(standby.clone())
+                        .service(get_standby)
+                        .service(promote_standby);
+                }
+            })
+    })
+    .bind(address)?
+    .run())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use crate::{
+        delegation::AccountDelegation,
+        fees::OrderFee,
+        flow::{FrontendFlow, FrontendFlowReport},
+        killswitch::{KillSwitchStatus, MaintenanceWindow},
+        standby::{Promotion, StandbyStatus},
+        upgrades::ContractUpgrade,
+    };
+
+    // Serializes an answer of the API and reads it back with the client types.
+    fn to_client<T: DeserializeOwned>(answer: &impl Serialize) -> T {
+        serde_json::from_value(serde_json::to_value(answer).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_client_types() {
+        let kill_switch = KillSwitchStatus {
+            engaged: true,
+            reason: None,
+            paused_on_chain: false,
+            maintenance: Some(MaintenanceWindow {
+                id: Some(3),
+                starts_at: 1704103200,
+                ends_at: 1704105000,
+                reason: "DataStore upgrade".to_owned(),
+            }),
+            pending_upgrades: vec![ContractUpgrade {
+                id: 1,
+                contract: "data_store".to_owned(),
+                address: "0x1".to_owned(),
+                previous_class_hash: None,
+                class_hash: "0x2".to_owned(),
+                block_number: Some(10),
+            }],
+        };
+        let client: satoru_client::types::KillSwitchStatus = to_client(&kill_switch);
+        assert_eq!(client.maintenance.unwrap().id, Some(3));
+        assert_eq!(client.pending_upgrades[0].class_hash, "0x2");
+
+        let standby = StandbyStatus {
+            active: true,
+            instance_id: "keeper-2".to_owned(),
+            nonce: Some("0x2a".to_owned()),
+            leader_lease_ms: Some(3000),
+            last_promotion: Some(Promotion::Manual),
+            promoted_at: Some(1704103200),
+        };
+        let client: satoru_client::types::StandbyStatus = to_client(&standby);
+        assert_eq!(
+            client.last_promotion,
+            Some(satoru_client::types::Promotion::Manual)
+        );
+
+        // The flow of a frontend gets flattened into its report.
+        let report = FrontendFlowReport {
+            flow: FrontendFlow {
+                bucket_start: "2024-01-01T00:00:00Z".to_owned(),
+                ui_fee_receiver: "1a".to_owned(),
+                orders: 4,
+                executed_orders: 3,
+                volume_usd: "1500".to_owned(),
+                maker_orders: 1,
+                taker_orders: 3,
+            },
+            maker_share: Some(0.25),
+        };
+        let client: satoru_client::types::FrontendFlowReport = to_client(&report);
+        assert_eq!(client.orders, 4);
+        assert_eq!(client.maker_share, Some(0.25));
+
+        let delegation = AccountDelegation {
+            account: "0x1a".to_owned(),
+            auto_claim_funding: true,
+            auto_cancel_stale_orders: true,
+            stale_order_secs: Some(86_400),
+            notification_channels: vec!["telegram".to_owned()],
+        };
+        let client: satoru_client::types::AccountDelegation = to_client(&delegation);
+        assert_eq!(client.stale_order_secs, Some(86_400));
+
+        let fee = OrderFee {
+            key: "2a".to_owned(),
+            table_name: "orders".to_owned(),
+            account: "1a".to_owned(),
+            transaction_hash: "0x3".to_owned(),
+            execution_fee: "1000".to_owned(),
+            keeper_fee: "300".to_owned(),
+            refund: "700".to_owned(),
+            gas_spent: "250".to_owned(),
+            keeper_pnl: "50".to_owned(),
+            created_at: "2024-01-01 00:00:00+00".to_owned(),
+        };
+        let client: satoru_client::types::OrderFee = to_client(&fee);
+        assert_eq!(client.refund, "700");
+    }
+}
@@ -0,0 +1,23 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::standby::Standby;
+
+// Returns whether the instance executes or stands by, with its nonce view and last promotion.
+#[get("/standby")]
+pub async fn get_standby(standby: web::Data<Standby>) -> impl Responder {
+    HttpResponse::Ok().json(standby.status())
+}
+
+// Promotes the standby to active at once, e.g. during a deploy, taking the leader lease from the
+// active instance, which stands by within a poll interval and finishes the jobs it runs.
+#[post("/standby/promote")]
+pub async fn promote_standby(
+    pool: web::Data<Pool<Postgres>>,
+    standby: web::Data<Standby>,
+) -> impl Responder {
+    match standby.promote_manually(&pool).await {
+        Ok(_) => HttpResponse::Ok().json(standby.status()),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,40 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    killswitch::KillSwitch,
+    upgrades::{acknowledge_upgrade, load_pending_upgrades},
+};
+
+// Returns the contract upgrades waiting for an acknowledgment, transactions being stopped while
+// there is any.
+#[get("/upgrades")]
+pub async fn get_pending_upgrades(pool: web::Data<Pool<Postgres>>) -> impl Responder {
+    match load_pending_upgrades(&pool).await {
+        Ok(upgrades) => HttpResponse::Ok().json(upgrades),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Acknowledges a contract upgrade once its new logic got reviewed, at once on this instance and
+// within a poll interval on the others sharing the database. Transactions resume once every
+// pending upgrade got acknowledged.
+#[post("/upgrades/{id}/acknowledge")]
+pub async fn acknowledge_contract_upgrade(
+    pool: web::Data<Pool<Postgres>>,
+    kill_switch: web::Data<KillSwitch>,
+    id: web::Path<i64>,
+) -> impl Responder {
+    match acknowledge_upgrade(&pool, id.into_inner()).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::NotFound().body("no pending upgrade with this id"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+    match load_pending_upgrades(&pool).await {
+        Ok(upgrades) => {
+            kill_switch.set_pending_upgrades(upgrades);
+            HttpResponse::Ok().json(kill_switch.status())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,38 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::vwap::{get_market_vwap, MAX_VWAP_DAYS};
+
+// The query parameters of the VWAP route.
+// @market: The market token address.
+// @days: How many days back the series starts, 30 by default.
+#[derive(Deserialize, Debug)]
+pub struct VwapQuery {
+    pub market: String,
+    pub days: Option<i64>,
+}
+
+// Returns the daily volume-weighted average execution price of a market, oldest day first.
+#[get("/markets/vwap")]
+pub async fn get_market_daily_vwap(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<VwapQuery>,
+) -> impl Responder {
+    let market = match FieldElement::from_hex_be(&query.market) {
+        Ok(market) => market,
+        Err(_) => {
+            return HttpResponse::BadRequest().body(format!("invalid market {}", query.market))
+        }
+    };
+    let days = query.days.unwrap_or(30);
+    if !(1..=MAX_VWAP_DAYS).contains(&days) {
+        return HttpResponse::BadRequest()
+            .body(format!("days must be between 1 and {}", MAX_VWAP_DAYS));
+    }
+    match get_market_vwap(&pool, market, days).await {
+        Ok(series) => HttpResponse::Ok().json(series),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
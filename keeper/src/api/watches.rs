@@ -0,0 +1,116 @@
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    watch::{delete_watch, get_watch, save_watch, AccountWatch},
+    webhooks::to_webhook_account,
+};
+
+// The query parameters of the watch routes, account keys only managing their own watch.
+// @account: The account watched.
+#[derive(Deserialize, Debug)]
+pub struct WatchQuery {
+    pub account: String,
+}
+
+// The body of the watch registration route, at least one threshold being set.
+// @max_leverage: The leverage above which positions get alerted on.
+// @min_liquidation_distance: The distance to liquidation, as a fraction of the index price, below
+// which positions get alerted on, e.g. 0.05 for 5%.
+// @telegram_chat_id: The Telegram chat alerts also get sent to, besides the account webhook.
+#[derive(Deserialize, Debug)]
+pub struct WatchRequest {
+    pub max_leverage: Option<f64>,
+    pub min_liquidation_distance: Option<f64>,
+    pub telegram_chat_id: Option<String>,
+}
+
+// Checks the thresholds of a watch registration.
+// @request: The body of the registration.
+fn check_watch_request(request: &WatchRequest) -> Result<(), &'static str> {
+    if request.max_leverage.is_none() && request.min_liquidation_distance.is_none() {
+        return Err("max_leverage or min_liquidation_distance must be set");
+    }
+    if request
+        .max_leverage
+        .is_some_and(|leverage| !leverage.is_finite() || leverage <= 0.0)
+    {
+        return Err("max_leverage must be positive");
+    }
+    if request
+        .min_liquidation_distance
+        .is_some_and(|distance| !(distance > 0.0 && distance < 1.0))
+    {
+        return Err("min_liquidation_distance must be between 0 and 1");
+    }
+    if request
+        .telegram_chat_id
+        .as_deref()
+        .is_some_and(|chat_id| chat_id.trim().is_empty())
+    {
+        return Err("telegram_chat_id must not be empty");
+    }
+    Ok(())
+}
+
+// Returns the watch of an account.
+#[get("/watches")]
+pub async fn get_account_watch(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WatchQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match get_watch(&pool, &account).await {
+        Ok(Some(watch)) => HttpResponse::Ok().json(watch),
+        Ok(None) => HttpResponse::NotFound().body("no watch for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Registers the watch of an account, its positions then getting alerted on to its webhook and
+// Telegram chat once too leveraged or too close to liquidation.
+#[put("/watches")]
+pub async fn set_account_watch(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WatchQuery>,
+    request: web::Json<WatchRequest>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    if let Err(e) = check_watch_request(&request) {
+        return HttpResponse::BadRequest().body(e);
+    }
+    let watch = AccountWatch {
+        account,
+        max_leverage: request.max_leverage,
+        min_liquidation_distance: request.min_liquidation_distance,
+        telegram_chat_id: request.telegram_chat_id.clone(),
+    };
+    match save_watch(&pool, &watch).await {
+        Ok(()) => HttpResponse::Ok().json(watch),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Stops watching an account.
+#[delete("/watches")]
+pub async fn delete_account_watch(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WatchQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match delete_watch(&pool, &account).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body("no watch for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,91 @@
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+
+use crate::webhooks::{
+    delete_webhook, get_webhook, resolve_webhook_url, save_webhook, to_webhook_account, Webhook,
+};
+
+// The query parameters of the webhook routes, account keys only managing their own callback.
+// @account: The account the callback gets the order receipts of.
+#[derive(Deserialize, Debug)]
+pub struct WebhookQuery {
+    pub account: String,
+}
+
+// The body of the webhook registration route.
+// @url: The http or https URL receipts get posted to.
+// @secret: The secret receipts get signed with, in the X-Keeper-Signature header.
+#[derive(Deserialize, Debug)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub secret: String,
+}
+
+// Returns the callback URL of an account, without its secret.
+#[get("/webhooks")]
+pub async fn get_account_webhook(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WebhookQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match get_webhook(&pool, &account).await {
+        Ok(Some(webhook)) => {
+            HttpResponse::Ok().json(json!({ "account": webhook.account, "url": webhook.url }))
+        }
+        Ok(None) => HttpResponse::NotFound().body("no webhook for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Registers the callback of an account, the receipt of every order of the account executed or
+// failed by the keeper then getting posted to it, signed with its secret.
+#[put("/webhooks")]
+pub async fn set_account_webhook(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WebhookQuery>,
+    request: web::Json<WebhookRequest>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    if let Err(e) = resolve_webhook_url(&request.url).await {
+        return HttpResponse::BadRequest().body(e);
+    }
+    if request.secret.is_empty() {
+        return HttpResponse::BadRequest().body("the secret must not be empty");
+    }
+    let webhook = Webhook {
+        account,
+        url: request.url.clone(),
+        secret: request.secret.clone(),
+    };
+    match save_webhook(&pool, &webhook).await {
+        Ok(()) => {
+            HttpResponse::Ok().json(json!({ "account": webhook.account, "url": webhook.url }))
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// Removes the callback of an account.
+#[delete("/webhooks")]
+pub async fn delete_account_webhook(
+    pool: web::Data<Pool<Postgres>>,
+    query: web::Query<WebhookQuery>,
+) -> impl Responder {
+    let account = match to_webhook_account(&query.account) {
+        Some(account) => account,
+        None => return HttpResponse::BadRequest().body("invalid account"),
+    };
+    match delete_webhook(&pool, &account).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body("no webhook for the account"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
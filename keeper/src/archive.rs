@@ -0,0 +1,177 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+use sqlx::{error::Error, Pool, Postgres};
+
+// Seconds in a day, detail rows getting archived by whole UTC days.
+const DAY_SECS: u64 = 86_400;
+
+// The indexed time_stamp of a detail row in seconds, NULL when it is not a number.
+const TIME_STAMP_SECS: &str = "CASE WHEN time_stamp ~ '^[0-9]+$' THEN time_stamp::BIGINT END";
+
+// A struct representing a detail table rolled into daily aggregates before getting pruned.
+// @table: The detail table.
+// @statement: Deletes the rows before $1, in seconds, adding them to the aggregates in the same
+// statement, and returns how many got deleted. {day} and {before} get replaced by the UTC day of a
+// row and whether it is before $1.
+struct ArchivedTable {
+    table: &'static str,
+    statement: &'static str,
+}
+
+const ARCHIVED_TABLES: [ArchivedTable; 4] = [
+    ArchivedTable {
+        table: "keeper_transaction_fees",
+        statement: "WITH moved AS (
+                DELETE FROM keeper_transaction_fees WHERE created_at < to_timestamp($1) RETURNING *
+            ), moved_actions AS (
+                DELETE FROM keeper_transaction_actions a USING moved
+                WHERE a.transaction_hash = moved.transaction_hash
+            ), archived AS (
+                INSERT INTO archived_daily_keeper_pnl (day, transactions, fees_paid, fees_earned, pnl)
+                SELECT (created_at AT TIME ZONE 'UTC')::DATE, COUNT(*), SUM(actual_fee),
+                    SUM(execution_fee_earned), SUM(execution_fee_earned) - SUM(actual_fee)
+                FROM moved GROUP BY 1
+                ON CONFLICT (day) DO UPDATE SET
+                    transactions = archived_daily_keeper_pnl.transactions + EXCLUDED.transactions,
+                    fees_paid = archived_daily_keeper_pnl.fees_paid + EXCLUDED.fees_paid,
+                    fees_earned = archived_daily_keeper_pnl.fees_earned + EXCLUDED.fees_earned,
+                    pnl = archived_daily_keeper_pnl.pnl + EXCLUDED.pnl
+            )
+            SELECT COUNT(*) FROM moved",
+    },
+    ArchivedTable {
+        table: "funding_payments",
+        statement: "WITH moved AS (
+                DELETE FROM funding_payments WHERE {before} RETURNING *
+            ), archived AS (
+                INSERT INTO archived_daily_funding (
+                    day, account, market, collateral_token, is_long, direction, payments, amount
+                )
+                SELECT {day}, account, market, collateral_token, is_long, direction, COUNT(*),
+                    SUM(amount)
+                FROM moved GROUP BY 1, 2, 3, 4, 5, 6
+                ON CONFLICT (day, account, market, collateral_token, is_long, direction)
+                DO UPDATE SET payments = archived_daily_funding.payments + EXCLUDED.payments,
+                    amount = archived_daily_funding.amount + EXCLUDED.amount
+            )
+            SELECT COUNT(*) FROM moved",
+    },
+    ArchivedTable {
+        table: "swap_info",
+        statement: "WITH moved AS (
+                DELETE FROM swap_info WHERE {before} RETURNING *
+            ), archived AS (
+                INSERT INTO archived_daily_swap_volumes (
+                    day, market, token_in, token_out, swaps, amount_in, amount_out
+                )
+                SELECT {day}, COALESCE(market, ''), COALESCE(token_in, ''),
+                    COALESCE(token_out, ''), COUNT(*), COALESCE(SUM(amount_in), 0),
+                    COALESCE(SUM(amount_out), 0)
+                FROM moved GROUP BY 1, 2, 3, 4
+                ON CONFLICT (day, market, token_in, token_out) DO UPDATE SET
+                    swaps = archived_daily_swap_volumes.swaps + EXCLUDED.swaps,
+                    amount_in = archived_daily_swap_volumes.amount_in + EXCLUDED.amount_in,
+                    amount_out = archived_daily_swap_volumes.amount_out + EXCLUDED.amount_out
+            )
+            SELECT COUNT(*) FROM moved",
+    },
+    ArchivedTable {
+        table: "swap_fees_collected",
+        statement: "WITH moved AS (
+                DELETE FROM swap_fees_collected WHERE {before} RETURNING *
+            ), archived AS (
+                INSERT INTO archived_daily_swap_fees (
+                    day, market, token, action, collections, fee_receiver_amount,
+                    fee_amount_for_pool, ui_fee_amount
+                )
+                SELECT {day}, COALESCE(market, ''), COALESCE(token, ''), COALESCE(action, ''),
+                    COUNT(*), COALESCE(SUM(fee_receiver_amount), 0),
+                    COALESCE(SUM(fee_amount_for_pool), 0), COALESCE(SUM(ui_fee_amount), 0)
+                FROM moved GROUP BY 1, 2, 3, 4
+                ON CONFLICT (day, market, token, action) DO UPDATE SET
+                    collections = archived_daily_swap_fees.collections + EXCLUDED.collections,
+                    fee_receiver_amount =
+                        archived_daily_swap_fees.fee_receiver_amount + EXCLUDED.fee_receiver_amount,
+                    fee_amount_for_pool =
+                        archived_daily_swap_fees.fee_amount_for_pool + EXCLUDED.fee_amount_for_pool,
+                    ui_fee_amount = archived_daily_swap_fees.ui_fee_amount + EXCLUDED.ui_fee_amount
+            )
+            SELECT COUNT(*) FROM moved",
+    },
+];
+
+// Returns the first second of the UTC day the retention starts in, detail rows before it getting
+// archived so no day gets archived in part.
+// @now_secs: The current time, in seconds.
+// @retention_secs: How long detail rows are kept.
+pub fn get_archive_cutoff(now_secs: u64, retention_secs: u64) -> u64 {
+    now_secs.saturating_sub(retention_secs) / DAY_SECS * DAY_SECS
+}
+
+// Returns the statement archiving a detail table, with the expressions of its rows filled in.
+// @archived: The detail table.
+fn get_archive_statement(archived: &ArchivedTable) -> String {
+    archived
+        .statement
+        .replace("{before}", &format!("{} < $1", TIME_STAMP_SECS))
+        .replace(
+            "{day}",
+            &format!(
+                "(to_timestamp({}) AT TIME ZONE 'UTC')::DATE",
+                TIME_STAMP_SECS
+            ),
+        )
+}
+
+// Rolls the detail rows older than the retention into their daily aggregates, deleting them in the
+// same statement so none gets counted twice or lost. Returns how many rows got archived per table.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @retention_secs: How long detail rows are kept.
+pub async fn archive_detail_rows(
+    pool: &Pool<Postgres>,
+    retention_secs: u64,
+) -> Result<Vec<(&'static str, i64)>, Error> {
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default();
+    let cutoff = get_archive_cutoff(now_secs, retention_secs);
+    let mut archived = vec![];
+    for table in &ARCHIVED_TABLES {
+        let rows: i64 = sqlx::query_scalar(&get_archive_statement(table))
+            .bind(cutoff as i64)
+            .fetch_one(pool)
+            .await?;
+        if rows > 0 {
+            info!("Archived {} rows of {}", rows, table.table);
+        }
+        archived.push((table.table, rows));
+    }
+    Ok(archived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_cutoff() {
+        // 2024-01-31 13:00:00 UTC, with a retention of 30 days.
+        assert_eq!(get_archive_cutoff(1706706000, 30 * DAY_SECS), 1704067200);
+        assert_eq!(get_archive_cutoff(1706706000, 0), 1706659200);
+        assert_eq!(get_archive_cutoff(10, DAY_SECS), 0);
+    }
+
+    #[test]
+    fn test_archive_statement() {
+        let statement = get_archive_statement(&ARCHIVED_TABLES[1]);
+        assert!(!statement.contains('{'));
+        assert!(statement.contains(&format!("WHERE {} < $1", TIME_STAMP_SECS)));
+        // The keeper fees are timestamped by the database.
+        assert_eq!(
+            get_archive_statement(&ARCHIVED_TABLES[0]),
+            ARCHIVED_TABLES[0].statement
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+// A struct representing the actions of a market awaiting keeper action.
+// @market: The market of the actions.
+// @action_type: The table of the actions (orders, deposits, withdrawals).
+// @pending: The number of actions neither executed nor cancelled yet.
+// @pending_amount: Their size in USD for orders, token amounts for deposits and withdrawals.
+// @oldest_time_stamp: The creation timestamp of the oldest pending action.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct MarketBacklog {
+    pub market: Option<String>,
+    pub action_type: String,
+    pub pending: i64,
+    pub pending_amount: String,
+    pub oldest_time_stamp: Option<String>,
+}
+
+// Loads the per market backlog derived from the indexed created, executed and cancelled events.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn get_backlog(pool: &Pool<Postgres>) -> Result<Vec<MarketBacklog>, Error> {
+    sqlx::query_as::<_, MarketBacklog>(
+        "SELECT market, action_type, pending, pending_amount::TEXT AS pending_amount, oldest_time_stamp
+         FROM keeper_backlog ORDER BY pending DESC",
+    )
+    .fetch_all(pool)
+    .await
+}
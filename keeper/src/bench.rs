@@ -0,0 +1,299 @@
+use cainome::cairo_serde::ContractAddress;
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{Account, Call, SingleOwnerAccount},
+    core::{
+        types::{ExecuteInvocation, FieldElement, TransactionTrace},
+        utils::get_selector_from_name,
+    },
+    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    signers::LocalWallet,
+};
+
+use crate::{
+    contracts::{Contracts, SetPricesTemplate},
+    error::KeeperError,
+    history::scale_amount,
+    snapshot::{get_simulation_result, SimulationResult},
+    trade::{order::handle::get_order_calls_with_prices, price::spread::PriceSpreads},
+    types::SatoruAction,
+};
+
+// Decimals of the fee tokens, ETH and STRK.
+const FEE_DECIMALS: u32 = 18;
+// Method of the Oracle the executions set the price of their market with.
+const SET_PRIMARY_PRICE: &str = "set_primary_price";
+
+// A struct representing the prices an execution gets benchmarked with.
+// @name: The name of the composition in the report.
+// @tokens: The tokens the SetPricesParams carry prices for.
+// @set_primary_price: Whether the multicall sets the primary price before executing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OracleComposition {
+    pub name: &'static str,
+    pub tokens: Vec<ContractAddress>,
+    pub set_primary_price: bool,
+}
+
+// Returns the oracle compositions an execution gets benchmarked at: the configured one of its
+// market, the same without the primary price call, and the prices of every feed, as a batch of
+// executions across the markets sharing its prices would send.
+// @market_tokens: The tokens the market of the execution gets prices for.
+// @feed_tokens: The tokens of every market with feeds.
+pub fn get_oracle_compositions(
+    market_tokens: &[ContractAddress],
+    feed_tokens: &[ContractAddress],
+) -> Vec<OracleComposition> {
+    let mut compositions = vec![
+        OracleComposition {
+            name: "configured",
+            tokens: market_tokens.to_vec(),
+            set_primary_price: true,
+        },
+        OracleComposition {
+            name: "without primary price",
+            tokens: market_tokens.to_vec(),
+            set_primary_price: false,
+        },
+    ];
+    let mut all_tokens = market_tokens.to_vec();
+    for token in feed_tokens {
+        if !all_tokens.contains(token) {
+            all_tokens.push(*token);
+        }
+    }
+    if all_tokens.len() > market_tokens.len() {
+        compositions.push(OracleComposition {
+            name: "all feeds",
+            tokens: all_tokens,
+            set_primary_price: true,
+        });
+    }
+    compositions
+}
+
+// A struct representing the resources an execution took when simulated.
+// @gas_consumed: The gas of the execution.
+// @overall_fee: The fee of the execution, in the smallest unit of the fee token.
+// @steps: The Cairo steps of the execution, None when it reverted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResources {
+    pub gas_consumed: u128,
+    pub overall_fee: u128,
+    pub steps: Option<u64>,
+}
+
+// A struct representing the simulation of an execution at an oracle composition.
+// @composition: The name of the oracle composition.
+// @tokens: The number of tokens prices got sent for.
+// @calls: The number of calls of the multicall, hooks included.
+// @simulation: How the simulated execution went.
+// @resources: The resources it took, None when it could not be simulated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub composition: &'static str,
+    pub tokens: usize,
+    pub calls: usize,
+    pub simulation: SimulationResult,
+    pub resources: Option<BenchResources>,
+}
+
+impl BenchResult {
+    // Returns the line of the result in the report, its fee relative to the baseline one.
+    // @baseline_fee: The fee of the configured composition, None when it could not be simulated.
+    pub fn format(&self, baseline_fee: Option<u128>) -> String {
+        let outcome = match &self.simulation {
+            SimulationResult::Succeeded => "succeeded".to_owned(),
+            SimulationResult::Reverted {
+                error: Some(error), ..
+            } => format!("reverted with {}", error),
+            SimulationResult::Reverted { reason, .. } => format!("reverted: {}", reason),
+            SimulationResult::Failed { reason } => format!("failed: {}", reason),
+        };
+        let mut line = format!(
+            "{} ({} tokens, {} calls): {}",
+            self.composition, self.tokens, self.calls, outcome
+        );
+        if let Some(resources) = &self.resources {
+            line.push_str(&format!(
+                ", {} gas, fee {}",
+                resources.gas_consumed,
+                scale_amount(&resources.overall_fee.to_string(), FEE_DECIMALS)
+            ));
+            if let Some(steps) = resources.steps {
+                line.push_str(&format!(", {} steps", steps));
+            }
+            if let Some(baseline_fee) = baseline_fee.filter(|fee| *fee > 0) {
+                line.push_str(&format!(
+                    ", x{:.2}",
+                    resources.overall_fee as f64 / baseline_fee as f64
+                ));
+            }
+        }
+        line
+    }
+}
+
+// Simulates an execution multicall without broadcasting it, skipping the signature validation and
+// the fee charge, returning the resources it took.
+// @account: The account simulating the execution.
+// @composition: The oracle composition of the calls.
+// @calls: The calls of the execution.
+async fn simulate_composition(
+    account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    composition: &OracleComposition,
+    calls: Vec<Call>,
+) -> BenchResult {
+    let calls_count = calls.len();
+    let (simulation, resources) = match account
+        .execute(calls)
+        .max_fee(FieldElement::ZERO)
+        .simulate(true, true)
+        .await
+    {
+        Ok(simulation) => {
+            let steps = match &simulation.transaction_trace {
+                TransactionTrace::Invoke(trace) => match &trace.execute_invocation {
+                    ExecuteInvocation::Success(invocation) => {
+                        Some(invocation.execution_resources.steps)
+                    }
+                    ExecuteInvocation::Reverted(_) => None,
+                },
+                _ => None,
+            };
+            let fee = simulation.fee_estimation;
+            (
+                get_simulation_result(&simulation.transaction_trace),
+                Some(BenchResources {
+                    gas_consumed: u128::try_from(fee.gas_consumed).unwrap_or(u128::MAX),
+                    overall_fee: u128::try_from(fee.overall_fee).unwrap_or(u128::MAX),
+                    steps,
+                }),
+            )
+        }
+        Err(e) => (
+            SimulationResult::Failed {
+                reason: format!("{:?}", e),
+            },
+            None,
+        ),
+    };
+    BenchResult {
+        composition: composition.name,
+        tokens: composition.tokens.len(),
+        calls: calls_count,
+        simulation,
+        resources,
+    }
+}
+
+// Simulates the execution of an order at every oracle composition, so operators tune the batching
+// and fee multipliers with the resources each one takes.
+// @contracts: The keeper contracts, the simulations skipping the signature validation and fees of
+// their account.
+// @order: The order to execute.
+pub async fn run_execution_bench(
+    contracts: &Contracts,
+    order: &SatoruAction,
+) -> Result<Vec<BenchResult>, KeeperError> {
+    let set_primary_price =
+        get_selector_from_name(SET_PRIMARY_PRICE).expect("Invalid selector name");
+    let mut feed_tokens: Vec<ContractAddress> = contracts
+        .market_feeds
+        .markets
+        .values()
+        .flat_map(|feed| feed.tokens())
+        .collect();
+    feed_tokens.sort_by_key(|token| token.0);
+    let spreads = PriceSpreads::from_env();
+    let mut results = vec![];
+    for composition in get_oracle_compositions(
+        &contracts.set_prices_for(&order.market).tokens,
+        &feed_tokens,
+    ) {
+        let template = SetPricesTemplate::for_tokens(
+            &spreads,
+            &contracts.token_registry,
+            composition.tokens.clone(),
+        )?;
+        let calls: Vec<Call> = get_order_calls_with_prices(contracts, order.clone(), &template)
+            .await?
+            .into_iter()
+            .filter(|call| composition.set_primary_price || call.selector != set_primary_price)
+            .collect();
+        results.push(simulate_composition(&contracts.account, &composition, calls).await);
+    }
+    Ok(results)
+}
+
+// Loads an indexed order by key.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the order, as indexed.
+pub async fn load_order(
+    pool: &Pool<Postgres>,
+    key: &str,
+) -> Result<Option<SatoruAction>, sqlx::Error> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT row_to_json(o)::TEXT FROM orders o WHERE o.key = felt_in($1)")
+            .bind(key)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.and_then(|(row_data,)| serde_json::from_str::<SatoruAction>(&row_data).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(address: u8) -> ContractAddress {
+        ContractAddress::from(FieldElement::from(address))
+    }
+
+    #[test]
+    fn test_oracle_compositions() {
+        let compositions = get_oracle_compositions(
+            &[token(1), token(2)],
+            &[token(2), token(3), token(1), token(4)],
+        );
+        assert_eq!(compositions.len(), 3);
+        assert!(compositions[0].set_primary_price && !compositions[1].set_primary_price);
+        assert_eq!(compositions[1].tokens, vec![token(1), token(2)]);
+        assert_eq!(
+            compositions[2].tokens,
+            vec![token(1), token(2), token(3), token(4)]
+        );
+        // Without other feeds, every price is already the market's.
+        assert_eq!(get_oracle_compositions(&[token(1)], &[token(1)]).len(), 2);
+    }
+
+    #[test]
+    fn test_format_result() {
+        let result = BenchResult {
+            composition: "all feeds",
+            tokens: 4,
+            calls: 2,
+            simulation: SimulationResult::Succeeded,
+            resources: Some(BenchResources {
+                gas_consumed: 1200,
+                overall_fee: 3_000_000_000_000_000,
+                steps: Some(45000),
+            }),
+        };
+        assert_eq!(
+            result.format(Some(2_000_000_000_000_000)),
+            "all feeds (4 tokens, 2 calls): succeeded, 1200 gas, fee 0.003, 45000 steps, x1.50"
+        );
+        let reverted = BenchResult {
+            simulation: SimulationResult::Reverted {
+                reason: "0x0".to_owned(),
+                error: Some("EMPTY_ORDER".to_owned()),
+            },
+            resources: None,
+            ..result
+        };
+        assert_eq!(
+            reverted.format(None),
+            "all feeds (4 tokens, 2 calls): reverted with EMPTY_ORDER"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+// The subcommands of the keeper binary, each running one component so they can be deployed as one
+// process or one process per component.
+pub const USAGE: &str = "usage: satoru-keeper [--profile <name>] <all | index | backfill <from_block> <to_block> | rebuild --to <block> | execute | api | liquidate | competition | history <account> | watch | risk | loadtest | replay-context <file> | replay-prices <file> [account] | bench-execution --order <key>>";
+
+// An enum representing the component a run of the keeper binary runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // Runs the indexer, trigger engine and executor in one process, restarting them on failures.
+    All,
+    // Indexes the events of the indexer shard configured, following the chain head.
+    Index,
+    // Indexes a past block range once, then exits.
+    Backfill {
+        from_block: u64,
+        to_block: u64,
+    },
+    // Rebuilds the tables derived from the indexed events from the raw event archive, up to a
+    // block, then exits.
+    Rebuild {
+        to_block: u64,
+    },
+    // Executes the actions indexed, serving the admin API alongside.
+    Execute,
+    // Serves the admin API only, e.g. next to keepers without one.
+    Api,
+    // The liquidation keeper, liquidation orders being executed by the execute subcommand so far.
+    Liquidate,
+    // Prints how the keeper does against the other keepers.
+    Competition,
+    // Prints the trade and funding history of an account as CSV.
+    History {
+        account: String,
+    },
+    // Watches the positions of the registered accounts, alerting them when at risk.
+    Watch,
+    // Prints which positions index price shocks would liquidate, with the bad debt and pool impact.
+    Risk,
+    // Feeds synthetic events through the indexer, reporting its throughput and the keeper queue.
+    LoadTest,
+    // Replays an execution snapshot on the block it got taken at, comparing the simulations.
+    ReplayContext {
+        path: String,
+    },
+    // Feeds the prices of a price log to the trigger and liquidation engines, printing what they
+    // did, for the orders and positions of an account only when given.
+    ReplayPrices {
+        path: String,
+        account: Option<String>,
+    },
+    // Simulates the execution of an order at several oracle compositions, printing the resources
+    // and fees of each.
+    BenchExecution {
+        order: String,
+    },
+}
+
+// Takes the --profile flag out of the arguments, returning the profile of the config file it
+// selects and the other arguments.
+// @args: The arguments, without the binary name.
+pub fn take_profile(args: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    let mut profile = None;
+    let mut rest = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = match arg.strip_prefix("--profile") {
+            Some("") => args.next().cloned(),
+            Some(name) => name.strip_prefix('=').map(|name| name.to_owned()),
+            None => {
+                rest.push(arg.clone());
+                continue;
+            }
+        };
+        match name.filter(|name| !name.is_empty()) {
+            Some(name) if profile.is_none() => profile = Some(name),
+            Some(_) => return Err("--profile given twice".to_owned()),
+            None => return Err(USAGE.to_owned()),
+        }
+    }
+    Ok((profile, rest))
+}
+
+impl Command {
+    // Parses the arguments of the binary, the launch parameters of the separate binaries being
+    // kept as aliases.
+    // @args: The arguments, without the binary name.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        match args[..] {
+            ["all"] => Ok(Command::All),
+            ["index"] => Ok(Command::Index),
+            ["backfill", from_block, to_block] => {
+                let parse = |block: &str| {
+                    block
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid block number {}", block))
+                };
+                let (from_block, to_block) = (parse(from_block)?, parse(to_block)?);
+                if from_block > to_block {
+                    return Err(format!(
+                        "from block {} after to block {}",
+                        from_block, to_block
+                    ));
+                }
+                Ok(Command::Backfill {
+                    from_block,
+                    to_block,
+                })
+            }
+            ["rebuild", "--to", to_block] => Ok(Command::Rebuild {
+                to_block: to_block
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid block number {}", to_block))?,
+            }),
+            ["execute"] | ["execution"] => Ok(Command::Execute),
+            ["api"] => Ok(Command::Api),
+            ["liquidate"] | ["liquidation"] => Ok(Command::Liquidate),
+            ["competition"] => Ok(Command::Competition),
+            ["history", account] => Ok(Command::History {
+                account: account.to_owned(),
+            }),
+            ["watch"] => Ok(Command::Watch),
+            ["risk"] => Ok(Command::Risk),
+            ["loadtest"] => Ok(Command::LoadTest),
+            ["replay-context", path] => Ok(Command::ReplayContext {
+                path: path.to_owned(),
+            }),
+            ["replay-prices", path] => Ok(Command::ReplayPrices {
+                path: path.to_owned(),
+                account: None,
+            }),
+            ["replay-prices", path, account] => Ok(Command::ReplayPrices {
+                path: path.to_owned(),
+                account: Some(account.to_owned()),
+            }),
+            ["bench-execution", "--order", order] => Ok(Command::BenchExecution {
+                order: order.to_owned(),
+            }),
+            _ => Err(USAGE.to_owned()),
+        }
+    }
+
+    // Returns the component errors and panics get reported under.
+    pub fn component(&self) -> &'static str {
+        match self {
+            Command::Index | Command::Backfill { .. } | Command::Rebuild { .. } => "indexer",
+            _ => "keeper",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse(&["all"]), Ok(Command::All));
+        assert_eq!(parse(&["index"]), Ok(Command::Index));
+        assert_eq!(parse(&["execute"]), Ok(Command::Execute));
+        assert_eq!(parse(&["execution"]), Ok(Command::Execute));
+        assert_eq!(parse(&["liquidation"]), Ok(Command::Liquidate));
+        assert_eq!(parse(&["loadtest"]), Ok(Command::LoadTest));
+        assert_eq!(parse(&["watch"]), Ok(Command::Watch));
+        assert_eq!(parse(&["risk"]), Ok(Command::Risk));
+        assert_eq!(
+            parse(&["history", "0x12"]),
+            Ok(Command::History {
+                account: "0x12".to_owned(),
+            })
+        );
+        assert!(parse(&["history"]).is_err());
+        assert_eq!(
+            parse(&["replay-context", "snapshots/0x12.json"]),
+            Ok(Command::ReplayContext {
+                path: "snapshots/0x12.json".to_owned(),
+            })
+        );
+        assert_eq!(
+            parse(&["replay-prices", "prices.log", "0x12"]),
+            Ok(Command::ReplayPrices {
+                path: "prices.log".to_owned(),
+                account: Some("0x12".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse(&["bench-execution", "--order", "0x12"]),
+            Ok(Command::BenchExecution {
+                order: "0x12".to_owned(),
+            })
+        );
+        assert!(parse(&["bench-execution", "0x12"]).is_err());
+        assert_eq!(
+            parse(&["backfill", "100", "200"]),
+            Ok(Command::Backfill {
+                from_block: 100,
+                to_block: 200,
+            })
+        );
+        assert!(parse(&["backfill", "200", "100"]).is_err());
+        assert!(parse(&["backfill", "100"]).is_err());
+        assert_eq!(
+            parse(&["rebuild", "--to", "300"]),
+            Ok(Command::Rebuild { to_block: 300 })
+        );
+        assert!(parse(&["rebuild", "300"]).is_err());
+        assert_eq!(
+            parse(&["rebuild", "--to", "300"]).unwrap().component(),
+            "indexer"
+        );
+        assert_eq!(parse(&[]), Err(USAGE.to_owned()));
+        assert_eq!(parse(&["index"]).unwrap().component(), "indexer");
+        assert_eq!(parse(&["api"]).unwrap().component(), "keeper");
+    }
+
+    #[test]
+    fn test_take_profile() {
+        let take = |args: &[&str]| {
+            take_profile(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(take(&["execute"]), Ok((None, vec!["execute".to_owned()])));
+        for args in [
+            &["--profile", "sepolia", "execute"][..],
+            &["execute", "--profile", "sepolia"],
+            &["--profile=sepolia", "execute"],
+        ] {
+            assert_eq!(
+                take(args),
+                Ok((Some("sepolia".to_owned()), vec!["execute".to_owned()]))
+            );
+        }
+        assert!(take(&["execute", "--profile"]).is_err());
+        assert!(take(&["--profile=", "execute"]).is_err());
+        assert!(take(&["--profile", "a", "--profile", "b", "execute"]).is_err());
+        assert!(take(&["--profiles", "execute"]).is_err());
+    }
+}
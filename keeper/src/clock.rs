@@ -0,0 +1,284 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use starknet::{
+    accounts::{ConnectedAccount, SingleOwnerAccount},
+    core::types::{BlockId, BlockTag, MaybePendingBlockWithTxHashes},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+use crate::{config, error::KeeperError};
+
+// Returns the system time as a unix timestamp.
+pub fn get_system_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_secs()
+}
+
+// Returns the timestamp of the latest block.
+// @account: The keeper account, used to read the chain.
+pub async fn get_block_timestamp(
+    account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+) -> Result<u64, KeeperError> {
+    match account
+        .provider()
+        .get_block_with_tx_hashes(BlockId::Tag(BlockTag::Latest))
+        .await
+    {
+        Ok(MaybePendingBlockWithTxHashes::Block(block)) => Ok(block.timestamp),
+        Ok(MaybePendingBlockWithTxHashes::PendingBlock(block)) => Ok(block.timestamp),
+        Err(e) => Err(KeeperError::ClockSkew(format!(
+            "could not get latest block timestamp: {:?}",
+            e
+        ))),
+    }
+}
+
+// Seconds between the NTP epoch (1900) and the unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Reads the transmit timestamp of an SNTP response as a unix timestamp, None if the response is
+// malformed or from an unsynchronized server.
+// @response: The SNTP response packet.
+fn parse_ntp_response(response: &[u8]) -> Option<u64> {
+    if response.len() < 48 || response[1] == 0 {
+        return None;
+    }
+    let secs = u32::from_be_bytes(response[40..44].try_into().ok()?) as u64;
+    secs.checked_sub(NTP_UNIX_OFFSET)
+}
+
+// Returns the time of an NTP server as a unix timestamp, from an SNTP v4 client request.
+// @server: The host:port of the NTP server.
+pub async fn get_ntp_timestamp(server: &str) -> Result<u64, KeeperError> {
+    let query = async {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(server).await?;
+        // LI 0, version 4, client mode.
+        let mut request = [0u8; 48];
+        request[0] = 0x23;
+        socket.send(&request).await?;
+        let mut response = [0u8; 48];
+        let read = socket.recv(&mut response).await?;
+        Ok::<_, std::io::Error>(response[..read].to_vec())
+    };
+    match timeout(NTP_TIMEOUT, query).await {
+        Ok(Ok(response)) => parse_ntp_response(&response)
+            .ok_or_else(|| KeeperError::ClockSkew(format!("invalid NTP response from {}", server))),
+        Ok(Err(e)) => Err(KeeperError::ClockSkew(format!(
+            "could not query NTP server {}: {}",
+            server, e
+        ))),
+        Err(_) => Err(KeeperError::ClockSkew(format!(
+            "NTP server {} timed out",
+            server
+        ))),
+    }
+}
+
+// Checks the system time, the latest block timestamp and a price feed timestamp agree, prices
+// timestamped too far from the block time getting rejected by the oracle.
+// @system_timestamp: The system time.
+// @block_timestamp: The timestamp of the latest block.
+// @price_timestamp: The timestamp of the price to send, if any.
+// @max_skew_secs: The largest tolerated difference between two of them.
+pub fn check_clock_skew(
+    system_timestamp: u64,
+    block_timestamp: u64,
+    price_timestamp: Option<u64>,
+    max_skew_secs: u64,
+) -> Result<(), KeeperError> {
+    let system_skew = system_timestamp.abs_diff(block_timestamp);
+    if system_skew > max_skew_secs {
+        return Err(KeeperError::ClockSkew(format!(
+            "system time {} is {}s away from block time {}",
+            system_timestamp, system_skew, block_timestamp
+        )));
+    }
+    if let Some(price_timestamp) = price_timestamp {
+        let price_skew = price_timestamp.abs_diff(block_timestamp);
+        if price_skew > max_skew_secs {
+            return Err(KeeperError::ClockSkew(format!(
+                "price timestamp {} is {}s away from block time {}",
+                price_timestamp, price_skew, block_timestamp
+            )));
+        }
+    }
+    Ok(())
+}
+
+// A struct pausing executions while the keeper clocks disagree, so no rejectable prices get sent.
+// @max_skew_secs: The largest tolerated difference between the clocks.
+// @ntp_server: The NTP server the system time gets checked against, if any.
+// @ntp_interval_secs: The delay between two NTP checks.
+// @ntp_checked_at: The system time of the last NTP check.
+// @ntp_skew: The skew the last NTP check found, in seconds.
+// @paused: Whether executions are paused on a skew.
+#[derive(Debug, Default)]
+pub struct Clock {
+    pub max_skew_secs: u64,
+    ntp_server: Option<String>,
+    ntp_interval_secs: u64,
+    ntp_checked_at: AtomicU64,
+    ntp_skew: AtomicU64,
+    paused: AtomicBool,
+}
+
+impl Clock {
+    pub fn new(max_skew_secs: u64) -> Self {
+        Clock {
+            max_skew_secs,
+            ..Default::default()
+        }
+    }
+
+    // Checks the system time against an NTP server at most once per interval.
+    // @server: The host:port of the NTP server.
+    // @interval_secs: The delay between two NTP checks.
+    pub fn with_ntp(mut self, server: String, interval_secs: u64) -> Self {
+        self.ntp_server = Some(server);
+        self.ntp_interval_secs = interval_secs;
+        self
+    }
+
+    pub fn from_env() -> Self {
+        let clock = Clock::new(config::get_max_clock_skew_secs());
+        match config::get_ntp_server() {
+            Some(server) => clock.with_ntp(server, config::get_ntp_check_interval_secs()),
+            None => clock,
+        }
+    }
+
+    // Checks the system time against the NTP server once the last check is older than the
+    // interval, reusing the skew it found otherwise. An unreachable server only gets logged, the
+    // block time still bounding the system time.
+    async fn check_ntp(&self) -> Result<(), KeeperError> {
+        let server = match &self.ntp_server {
+            Some(server) => server,
+            None => return Ok(()),
+        };
+        let system_timestamp = get_system_timestamp();
+        let checked_at = self.ntp_checked_at.load(Ordering::Relaxed);
+        if system_timestamp.saturating_sub(checked_at) >= self.ntp_interval_secs {
+            self.ntp_checked_at
+                .store(system_timestamp, Ordering::Relaxed);
+            match get_ntp_timestamp(server).await {
+                Ok(ntp_timestamp) => self.ntp_skew.store(
+                    get_system_timestamp().abs_diff(ntp_timestamp),
+                    Ordering::Relaxed,
+                ),
+                Err(e) => warn!("NTP sanity check skipped: {}", e),
+            }
+        }
+        let skew = self.ntp_skew.load(Ordering::Relaxed);
+        if skew > self.max_skew_secs {
+            return Err(KeeperError::ClockSkew(format!(
+                "system time is {}s away from NTP server {}",
+                skew, server
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Reconciles the system time with the latest block timestamp, pausing executions and alerting
+    // when they skew apart, resuming once they agree again. Returns the latest block timestamp.
+    // @account: The keeper account, used to read the chain.
+    pub async fn reconcile(
+        &self,
+        account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+    ) -> Result<u64, KeeperError> {
+        let block_timestamp = get_block_timestamp(account).await?;
+        match self.check_ntp().await.and_then(|_| {
+            check_clock_skew(
+                get_system_timestamp(),
+                block_timestamp,
+                None,
+                self.max_skew_secs,
+            )
+        }) {
+            Ok(()) => {
+                if self.paused.swap(false, Ordering::Relaxed) {
+                    info!("Clocks agree again, resuming executions");
+                }
+                Ok(block_timestamp)
+            }
+            Err(e) => {
+                if !self.paused.swap(true, Ordering::Relaxed) {
+                    warn!("ALERT: {}, pausing executions", e);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trade::price::utils::PriceInfo;
+
+    use super::*;
+
+    #[test]
+    fn test_check_clock_skew() {
+        assert!(check_clock_skew(1000, 1000, Some(1000), 0).is_ok());
+        assert!(check_clock_skew(1030, 1000, Some(970), 30).is_ok());
+        assert!(check_clock_skew(1031, 1000, None, 30).is_err());
+        assert!(check_clock_skew(969, 1000, None, 30).is_err());
+        assert!(check_clock_skew(1000, 1000, Some(1031), 30).is_err());
+    }
+
+    #[test]
+    fn test_check_pragma_price_timestamp() {
+        let price_info = PriceInfo {
+            decimals: 8,
+            num_sources_aggregated: 4,
+            pair_id: "ETH/USD".to_owned(),
+            price: "0x4f8b06508e".to_owned(),
+            timestamp: 1711110660000,
+        };
+        assert_eq!(price_info.timestamp_secs(), 1711110660);
+        assert!(check_clock_skew(
+            1711110680,
+            1711110670,
+            Some(price_info.timestamp_secs()),
+            60
+        )
+        .is_ok());
+        assert!(check_clock_skew(1711110680, 1711110670, Some(price_info.timestamp), 60).is_err());
+    }
+
+    #[test]
+    fn test_parse_ntp_response() {
+        let mut response = [0u8; 48];
+        response[1] = 2;
+        response[40..44].copy_from_slice(&((1711110660 + NTP_UNIX_OFFSET) as u32).to_be_bytes());
+        assert_eq!(parse_ntp_response(&response), Some(1711110660));
+        assert_eq!(parse_ntp_response(&response[..40]), None);
+        // Stratum 0 responses are kiss-of-death packets.
+        response[1] = 0;
+        assert_eq!(parse_ntp_response(&response), None);
+    }
+
+    #[test]
+    fn test_clock_starts_unpaused() {
+        assert!(!Clock::new(30).is_paused());
+    }
+}
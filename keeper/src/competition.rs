@@ -0,0 +1,153 @@
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+use starknet::core::types::FieldElement;
+
+// A struct representing the executions attributed to one keeper address by the indexer.
+// @keeper: The address that sent the execution transactions, hex encoded without 0x.
+// @executions: The number of orders (liquidations included) it executed.
+// @median_latency_secs: The median delay between the order creation and its execution.
+// @fees_captured: The sum of the execution fees of the orders it executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeeperStats {
+    pub keeper: String,
+    pub executions: i64,
+    pub median_latency_secs: Option<f64>,
+    pub fees_captured: f64,
+}
+
+// A struct representing how our keeper does against its competitors.
+// @executions: The number of executions won by our keeper.
+// @total_executions: The number of executions made by all keepers.
+// @win_rate: The share of the executions won by our keeper.
+// @median_latency_secs: The median latency of our keeper.
+// @best_competitor_median_latency_secs: The lowest median latency among the other keepers.
+// @latency_difference_secs: Our median latency minus the best competitor one, negative when we are faster.
+// @fees_captured: The execution fees captured by our keeper.
+// @fee_capture_rate: The share of all the execution fees captured by our keeper.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompetitionReport {
+    pub executions: i64,
+    pub total_executions: i64,
+    pub win_rate: f64,
+    pub median_latency_secs: Option<f64>,
+    pub best_competitor_median_latency_secs: Option<f64>,
+    pub latency_difference_secs: Option<f64>,
+    pub fees_captured: f64,
+    pub fee_capture_rate: f64,
+}
+
+// Encodes an address the way the indexer stores it, 64 hex characters without 0x.
+// @address: The address to encode.
+pub fn to_indexed_address(address: FieldElement) -> String {
+    address
+        .to_bytes_be()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Loads the execution stats of every keeper seen executing orders.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn get_keeper_stats(pool: &Pool<Postgres>) -> Result<Vec<KeeperStats>, Error> {
+    let rows: Vec<(String, i64, Option<f64>, f64)> = sqlx::query_as(
+        "SELECT keeper, executions, median_latency_secs, fees_captured::FLOAT8
+         FROM keeper_competition_stats ORDER BY executions DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(keeper, executions, median_latency_secs, fees_captured)| KeeperStats {
+                keeper,
+                executions,
+                median_latency_secs,
+                fees_captured,
+            },
+        )
+        .collect())
+}
+
+// Compares our keeper with the other keepers.
+// @stats: The execution stats of every keeper.
+// @keeper: The address of our keeper, as encoded by the indexer.
+pub fn build_competition_report(stats: &[KeeperStats], keeper: &str) -> CompetitionReport {
+    let ours = stats.iter().find(|stats| stats.keeper == keeper);
+    let total_executions: i64 = stats.iter().map(|stats| stats.executions).sum();
+    let total_fees: f64 = stats.iter().map(|stats| stats.fees_captured).sum();
+
+    let executions = ours.map_or(0, |stats| stats.executions);
+    let fees_captured = ours.map_or(0.0, |stats| stats.fees_captured);
+    let median_latency_secs = ours.and_then(|stats| stats.median_latency_secs);
+    let best_competitor_median_latency_secs = stats
+        .iter()
+        .filter(|stats| stats.keeper != keeper)
+        .filter_map(|stats| stats.median_latency_secs)
+        .reduce(f64::min);
+
+    CompetitionReport {
+        executions,
+        total_executions,
+        win_rate: ratio(executions as f64, total_executions as f64),
+        median_latency_secs,
+        best_competitor_median_latency_secs,
+        latency_difference_secs: median_latency_secs
+            .zip(best_competitor_median_latency_secs)
+            .map(|(ours, best)| ours - best),
+        fees_captured,
+        fee_capture_rate: ratio(fees_captured, total_fees),
+    }
+}
+
+fn ratio(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(keeper: &str, executions: i64, latency: f64, fees: f64) -> KeeperStats {
+        KeeperStats {
+            keeper: keeper.to_owned(),
+            executions,
+            median_latency_secs: Some(latency),
+            fees_captured: fees,
+        }
+    }
+
+    #[test]
+    fn test_to_indexed_address() {
+        assert_eq!(
+            to_indexed_address(FieldElement::from_hex_be("0x1234").unwrap()),
+            format!("{:0>64}", "1234")
+        );
+    }
+
+    #[test]
+    fn test_build_competition_report() {
+        let all_stats = vec![
+            stats("ours", 3, 12.0, 30.0),
+            stats("other", 5, 8.0, 60.0),
+            stats("slow", 2, 20.0, 10.0),
+        ];
+        let report = build_competition_report(&all_stats, "ours");
+        assert_eq!(report.executions, 3);
+        assert_eq!(report.total_executions, 10);
+        assert_eq!(report.win_rate, 0.3);
+        assert_eq!(report.best_competitor_median_latency_secs, Some(8.0));
+        assert_eq!(report.latency_difference_secs, Some(4.0));
+        assert_eq!(report.fee_capture_rate, 0.3);
+
+        let report = build_competition_report(&all_stats, "unknown");
+        assert_eq!(report.executions, 0);
+        assert_eq!(report.win_rate, 0.0);
+        assert_eq!(report.latency_difference_secs, None);
+    }
+}
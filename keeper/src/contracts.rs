@@ -0,0 +1,726 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use cainome::cairo_serde::{ContractAddress, U256};
+use log::info;
+use serde_json::Value;
+use starknet::{
+    accounts::SingleOwnerAccount,
+    core::{
+        types::{BlockId, BlockTag, FieldElement, FunctionCall},
+        utils::{cairo_short_string_to_felt, get_selector_from_name},
+    },
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+
+use crate::{
+    config,
+    error::KeeperError,
+    market_config::MarketConfigs,
+    trade::{
+        deposit::handle::DepositHandler,
+        hooks::ExecutionHooks,
+        order::{
+            handle::{DataStore, Oracle, OrderHandler},
+            version::{NextOrderHandler, OrderHandlerVersion},
+        },
+        price::{
+            bounds::PriceBounds, feeds::MarketFeeds, spread::PriceSpreads, stable::StablePrices,
+            tokens::TokenRegistry,
+        },
+        withdrawal::handle::WithdrawalHandler,
+    },
+};
+
+// The keeper account the contract instances read the chain and build their calls through.
+pub type KeeperAccount = Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>;
+
+// The Satoru contracts the keeper calls, named as the env variables holding their address.
+pub const CONTRACT_NAMES: [&str; 5] = [
+    "DATA_STORE",
+    "ORACLE",
+    "ORDER_HANDLER",
+    "DEPOSIT_HANDLER",
+    "WITHDRAWAL_HANDLER",
+];
+
+// Method of the registry contract returning the address of a contract from its name.
+const REGISTRY_METHOD: &str = "get_contract_address";
+
+// A struct representing a deployed contract.
+// @address: The contract address.
+// @class_hash: The class hash the contract is expected to have, if known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContractEntry {
+    pub address: FieldElement,
+    pub class_hash: Option<FieldElement>,
+}
+
+// A struct representing the addresses of the keeper contracts, set in the env or discovered at
+// startup, which the contract instances, checks and monitors get built from.
+// @addresses: The addresses, keyed by contract env variable name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractAddresses {
+    addresses: HashMap<String, FieldElement>,
+}
+
+impl ContractAddresses {
+    // Resolves the keeper contract addresses, configured ones taking precedence over the
+    // discovered ones. Contracts with neither stay without an address.
+    // @entries: The contracts discovered from the manifest and/or the registry.
+    // @configured: Returns the address configured for a contract env variable, if any.
+    pub fn resolve(
+        entries: &HashMap<String, ContractEntry>,
+        configured: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, KeeperError> {
+        let mut addresses = HashMap::new();
+        for name in CONTRACT_NAMES {
+            let address = match configured(name) {
+                Some(address) => FieldElement::from_hex_be(&address).map_err(|_| {
+                    KeeperError::ContractDiscoveryError(format!("invalid address for {}", name))
+                })?,
+                None => match entries.get(name) {
+                    Some(entry) => entry.address,
+                    None => continue,
+                },
+            };
+            addresses.insert(name.to_owned(), address);
+        }
+        Ok(ContractAddresses { addresses })
+    }
+
+    // Returns the address of a contract, the keeper contracts from the resolved addresses, any
+    // other contract, e.g. NEXT_ORDER_HANDLER, from its env variable.
+    // @name: The contract env variable name.
+    pub fn get(&self, name: &str) -> Result<FieldElement, KeeperError> {
+        if CONTRACT_NAMES.contains(&name) {
+            return self.addresses.get(name).copied().ok_or_else(|| {
+                KeeperError::ContractDiscoveryError(format!("no address for {}", name))
+            });
+        }
+        match std::env::var(name) {
+            Ok(address) if !address.is_empty() => {
+                FieldElement::from_hex_be(&address).map_err(|_| {
+                    KeeperError::ContractDiscoveryError(format!("invalid address for {}", name))
+                })
+            }
+            _ => Err(KeeperError::ContractDiscoveryError(format!(
+                "no address for {}",
+                name
+            ))),
+        }
+    }
+}
+
+// Normalizes a contract name, e.g. OrderHandler, order_handler and ORDER_HANDLER all give
+// orderhandler.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// Parses a deployment manifest into the keeper contracts, keyed by env variable name.
+// Contracts are listed under `contracts` or at the root, either as an address or as an object
+// with an `address` and an optional `class_hash`.
+// @manifest: The manifest JSON.
+pub fn parse_manifest(manifest: &str) -> Result<HashMap<String, ContractEntry>, KeeperError> {
+    let invalid = |reason: String| KeeperError::ContractDiscoveryError(reason);
+    let manifest: Value =
+        serde_json::from_str(manifest).map_err(|e| invalid(format!("invalid manifest: {}", e)))?;
+    let contracts = manifest
+        .get("contracts")
+        .unwrap_or(&manifest)
+        .as_object()
+        .ok_or_else(|| invalid("manifest contracts must be an object".to_owned()))?;
+    let to_felt = |name: &str, value: &Value| {
+        value
+            .as_str()
+            .and_then(|felt| FieldElement::from_hex_be(felt).ok())
+            .ok_or_else(|| invalid(format!("invalid felt for {}", name)))
+    };
+
+    let mut entries = HashMap::new();
+    for (name, contract) in contracts {
+        let env_name = match CONTRACT_NAMES
+            .iter()
+            .find(|known| normalize(known) == normalize(name))
+        {
+            Some(env_name) => env_name.to_string(),
+            None => continue,
+        };
+        let entry = match contract {
+            Value::Object(fields) => ContractEntry {
+                address: to_felt(
+                    name,
+                    fields
+                        .get("address")
+                        .ok_or_else(|| invalid(format!("no address for {}", name)))?,
+                )?,
+                class_hash: match fields.get("class_hash") {
+                    Some(class_hash) => Some(to_felt(name, class_hash)?),
+                    None => None,
+                },
+            },
+            address => ContractEntry {
+                address: to_felt(name, address)?,
+                class_hash: None,
+            },
+        };
+        entries.insert(env_name, entry);
+    }
+    Ok(entries)
+}
+
+// Reads the keeper contracts from the on-chain registry, each looked up by its lowercase name
+// as a short string, e.g. 'order_handler'.
+// @provider: The provider used to call the registry.
+// @registry: The registry contract address.
+pub async fn read_registry<P: Provider + Sync>(
+    provider: &P,
+    registry: FieldElement,
+) -> Result<HashMap<String, ContractEntry>, KeeperError> {
+    let mut entries = HashMap::new();
+    for name in CONTRACT_NAMES {
+        let result = provider
+            .call(
+                FunctionCall {
+                    contract_address: registry,
+                    entry_point_selector: get_selector_from_name(REGISTRY_METHOD)
+                        .expect("Invalid registry method name"),
+                    calldata: vec![cairo_short_string_to_felt(&name.to_lowercase())
+                        .expect("Invalid contract name")],
+                },
+                BlockId::Tag(BlockTag::Latest),
+            )
+            .await
+            .map_err(|e| {
+                KeeperError::ContractDiscoveryError(format!(
+                    "could not read {} from the registry: {:?}",
+                    name, e
+                ))
+            })?;
+        match result.first() {
+            Some(address) if *address != FieldElement::ZERO => {
+                entries.insert(
+                    name.to_owned(),
+                    ContractEntry {
+                        address: *address,
+                        class_hash: None,
+                    },
+                );
+            }
+            _ => info!("Contract {} not in the registry", name),
+        }
+    }
+    Ok(entries)
+}
+
+// Checks a contract is deployed with its expected class hash. A mismatching class hash only passes
+// once the operator acknowledged it, e.g. after reviewing a contract upgrade.
+// @name: The contract name.
+// @expected: The expected class hash.
+// @actual: The class hash of the deployed contract.
+// @acknowledged: The class hashes the operator acknowledged for the contract.
+pub fn check_class_hash(
+    name: &str,
+    expected: FieldElement,
+    actual: FieldElement,
+    acknowledged: &[FieldElement],
+) -> Result<(), KeeperError> {
+    if expected == actual {
+        return Ok(());
+    }
+    if acknowledged.contains(&actual) {
+        info!(
+            "{} has acknowledged class hash {:#x} instead of {:#x}",
+            name, actual, expected
+        );
+        return Ok(());
+    }
+    Err(KeeperError::ContractDiscoveryError(format!(
+        "{} has class hash {:#x}, expected {:#x}, acknowledge it in ACKNOWLEDGED_CLASS_HASHES to run against it",
+        name, actual, expected
+    )))
+}
+
+// Parses NAME:class_hash entries, keyed by contract env variable name.
+// @entries: The entries to parse.
+fn parse_class_hashes(entries: Vec<String>) -> Result<Vec<(String, FieldElement)>, KeeperError> {
+    entries
+        .iter()
+        .map(|entry| {
+            let invalid = || {
+                KeeperError::ContractDiscoveryError(format!("invalid class hash entry {}", entry))
+            };
+            let (name, class_hash) = entry.split_once(':').ok_or_else(invalid)?;
+            let name = CONTRACT_NAMES
+                .iter()
+                .find(|known| normalize(known) == normalize(name))
+                .ok_or_else(invalid)?;
+            Ok((
+                name.to_string(),
+                FieldElement::from_hex_be(class_hash.trim()).map_err(|_| invalid())?,
+            ))
+        })
+        .collect()
+}
+
+// Verifies the keeper contracts have their expected class hashes, refusing to run against a
+// mismatched or upgraded contract the operator did not acknowledge.
+// @provider: The provider used to read the class hashes.
+// @addresses: The keeper contract addresses.
+// @expected: The expected class hashes, keyed by contract env variable name.
+pub async fn verify_class_hashes<P: Provider + Sync>(
+    provider: &P,
+    addresses: &ContractAddresses,
+    expected: &HashMap<String, FieldElement>,
+) -> Result<(), KeeperError> {
+    let acknowledged = parse_class_hashes(config::get_acknowledged_class_hashes())?;
+    for name in CONTRACT_NAMES {
+        let expected = match expected.get(name) {
+            Some(expected) => *expected,
+            None => continue,
+        };
+        let address = addresses.get(name)?;
+        let actual = provider
+            .get_class_hash_at(BlockId::Tag(BlockTag::Latest), address)
+            .await
+            .map_err(|e| {
+                KeeperError::ContractDiscoveryError(format!(
+                    "could not get class hash of {}: {:?}",
+                    name, e
+                ))
+            })?;
+        let acknowledged: Vec<FieldElement> = acknowledged
+            .iter()
+            .filter(|(contract, _)| contract == name)
+            .map(|(_, class_hash)| *class_hash)
+            .collect();
+        check_class_hash(name, expected, actual, &acknowledged)?;
+    }
+    Ok(())
+}
+
+// Discovers the keeper contracts from the deployment manifest and/or the on-chain registry, the
+// manifest taking precedence, and returns their addresses. Addresses set in the env are kept,
+// empty ones get replaced. The contracts are then checked against their expected class hashes,
+// configured ones taking precedence over the manifest ones.
+// @provider: The provider used to read the registry and the class hashes.
+pub async fn load_contracts<P: Provider + Sync>(
+    provider: &P,
+) -> Result<ContractAddresses, KeeperError> {
+    let mut entries = match config::get_contract_registry() {
+        Some(registry) => {
+            let registry = FieldElement::from_hex_be(&registry).map_err(|_| {
+                KeeperError::ContractDiscoveryError(format!("invalid registry {}", registry))
+            })?;
+            read_registry(provider, registry).await?
+        }
+        None => HashMap::new(),
+    };
+    if let Some(path) = config::get_contracts_manifest() {
+        let manifest = fs::read_to_string(&path).map_err(|e| {
+            KeeperError::ContractDiscoveryError(format!("could not read {}: {}", path, e))
+        })?;
+        entries.extend(parse_manifest(&manifest)?);
+    }
+
+    let addresses = ContractAddresses::resolve(&entries, |name| {
+        std::env::var(name)
+            .ok()
+            .filter(|address| !address.is_empty())
+    })?;
+    let mut expected: HashMap<String, FieldElement> = entries
+        .iter()
+        .filter_map(|(name, entry)| {
+            entry
+                .class_hash
+                .map(|class_hash| (name.clone(), class_hash))
+        })
+        .collect();
+    expected.extend(parse_class_hashes(config::get_expected_class_hashes())?);
+    verify_class_hashes(provider, &addresses, &expected).await?;
+    Ok(addresses)
+}
+
+// A struct representing the SetPricesParams fields sent with every execution, which only depend
+// on the configuration, the oracle block numbers of the action being added by each handler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetPricesTemplate {
+    pub signer_info: U256,
+    pub tokens: Vec<ContractAddress>,
+    pub compacted_oracle_timestamps: Vec<u64>,
+    pub compacted_decimals: Vec<U256>,
+    pub compacted_min_prices: Vec<U256>,
+    pub compacted_min_prices_indexes: Vec<U256>,
+    pub compacted_max_prices: Vec<U256>,
+    pub compacted_max_prices_indexes: Vec<U256>,
+    pub signatures: Vec<Vec<FieldElement>>,
+}
+
+impl SetPricesTemplate {
+    // @spreads: The spreads applied to the prices.
+    // @token_registry: The decimals of the tokens.
+    pub fn new(
+        spreads: &PriceSpreads,
+        token_registry: &TokenRegistry,
+    ) -> Result<Self, KeeperError> {
+        let placeholder = FieldElement::from_hex_be("0x").expect("Cannot convert string to felt");
+        SetPricesTemplate::for_tokens(
+            spreads,
+            token_registry,
+            vec![
+                ContractAddress::from(placeholder),
+                ContractAddress::from(placeholder),
+            ],
+        )
+    }
+
+    // Builds the fields sent for the tokens of a market, e.g. its index, long and short tokens,
+    // with placeholder prices until the execution prices them.
+    // @spreads: The spreads applied to the prices.
+    // @token_registry: The decimals of the tokens.
+    // @tokens: The tokens prices get sent for.
+    pub fn for_tokens(
+        spreads: &PriceSpreads,
+        token_registry: &TokenRegistry,
+        tokens: Vec<ContractAddress>,
+    ) -> Result<Self, KeeperError> {
+        let placeholder = FieldElement::from_hex_be("0x").expect("Cannot convert string to felt");
+        let prices: Vec<u128> = [10000, 500000]
+            .into_iter()
+            .cycle()
+            .take(tokens.len())
+            .collect();
+        let (compacted_min_prices, compacted_max_prices) =
+            spreads.compacted(token_registry, &tokens, &prices)?;
+        Ok(SetPricesTemplate {
+            signer_info: U256 { low: 1, high: 0 },
+            compacted_oracle_timestamps: [171119803, 10]
+                .into_iter()
+                .cycle()
+                .take(tokens.len())
+                .collect(),
+            compacted_decimals: token_registry.compacted_decimals(&tokens)?,
+            compacted_min_prices,
+            compacted_min_prices_indexes: vec![U256 { low: 0, high: 0 }],
+            compacted_max_prices,
+            compacted_max_prices_indexes: vec![U256 { low: 0, high: 0 }],
+            signatures: vec![vec![placeholder, placeholder]; tokens.len()],
+            tokens,
+        })
+    }
+
+    // Returns the fields with the fetched prices of the tokens, the min and max prices being
+    // spread around them.
+    // @spreads: The spreads applied to the prices.
+    // @token_registry: The tokens the spreads are configured for by symbol.
+    // @prices: The compacted prices of the tokens, in the order of the tokens.
+    // @timestamps: The timestamps of the prices in seconds, in the order of the tokens.
+    pub fn with_prices(
+        &self,
+        spreads: &PriceSpreads,
+        token_registry: &TokenRegistry,
+        prices: &[u128],
+        timestamps: Vec<u64>,
+    ) -> Result<Self, KeeperError> {
+        if prices.len() != self.tokens.len() || timestamps.len() != self.tokens.len() {
+            return Err(KeeperError::ExecutionError(format!(
+                "{} prices for {} tokens",
+                prices.len(),
+                self.tokens.len()
+            )));
+        }
+        let (compacted_min_prices, compacted_max_prices) =
+            spreads.compacted(token_registry, &self.tokens, prices)?;
+        Ok(SetPricesTemplate {
+            compacted_oracle_timestamps: timestamps,
+            compacted_min_prices,
+            compacted_max_prices,
+            ..self.clone()
+        })
+    }
+}
+
+// A struct representing the keeper contracts and the configuration of the calls made to them,
+// built once at startup rather than for every execution.
+// @account: The keeper account, used for reads.
+// @data_store: The DataStore instance.
+// @oracle: The Oracle instance.
+// @order_handler: The OrderHandler instance.
+// @order_handler_version: The ABI version of the OrderHandler.
+// @next_order_handler: The handler the orders of its markets get executed against instead, during
+// a staged upgrade.
+// @deposit_handler: The DepositHandler instance.
+// @withdrawal_handler: The WithdrawalHandler instance.
+// @price_bounds: The bounds the fetched prices are checked against.
+// @token_registry: The decimals of the tokens, used to scale the fetched prices.
+// @stable_prices: The stablecoins priced without any feed.
+// @spreads: The spreads applied around the fetched prices.
+// @max_clock_skew_secs: The largest tolerated difference between the clocks and the price timestamps.
+// @share_block_prices: Whether the executions priced at the same block share the prices fetched.
+// @market_feeds: The oracle feeds of the tokens of each market.
+// @set_prices: The constant SetPricesParams fields of the markets without feeds.
+// @market_set_prices: The constant SetPricesParams fields of each market with feeds.
+// @hooks: The calls the execution multicalls get composed with.
+// @market_configs: The market configurations read from the DataStore.
+// @addresses: The keeper contract addresses the instances got built from.
+pub struct Contracts {
+    pub account: KeeperAccount,
+    pub data_store: DataStore<KeeperAccount>,
+    pub oracle: Oracle<KeeperAccount>,
+    pub order_handler: OrderHandler<KeeperAccount>,
+    pub order_handler_version: OrderHandlerVersion,
+    pub next_order_handler: Option<NextOrderHandler>,
+    pub deposit_handler: DepositHandler<KeeperAccount>,
+    pub withdrawal_handler: WithdrawalHandler<KeeperAccount>,
+    pub price_bounds: PriceBounds,
+    pub token_registry: TokenRegistry,
+    pub stable_prices: StablePrices,
+    pub spreads: PriceSpreads,
+    pub max_clock_skew_secs: u64,
+    pub share_block_prices: bool,
+    pub market_feeds: MarketFeeds,
+    pub set_prices: SetPricesTemplate,
+    pub market_set_prices: HashMap<FieldElement, SetPricesTemplate>,
+    pub hooks: ExecutionHooks,
+    pub market_configs: Arc<MarketConfigs>,
+    pub addresses: ContractAddresses,
+}
+
+impl Contracts {
+    // Builds the contracts from the addresses returned by load_contracts.
+    // @account: The keeper account the instances get built with.
+    // @addresses: The keeper contract addresses.
+    pub fn from_env(
+        account: KeeperAccount,
+        addresses: &ContractAddresses,
+    ) -> Result<Self, KeeperError> {
+        let address = |name: &str| addresses.get(name);
+        let token_registry = TokenRegistry::from_env();
+        let spreads = PriceSpreads::from_env();
+        let market_feeds = MarketFeeds::from_env()?;
+        market_feeds.check_tokens(&token_registry)?;
+        let market_set_prices = market_feeds
+            .markets
+            .iter()
+            .map(|(market, feed)| {
+                Ok((
+                    *market,
+                    SetPricesTemplate::for_tokens(&spreads, &token_registry, feed.tokens())?,
+                ))
+            })
+            .collect::<Result<_, KeeperError>>()?;
+        Ok(Contracts {
+            data_store: DataStore::new(address("DATA_STORE")?, Arc::clone(&account)),
+            market_configs: Arc::new(MarketConfigs::new(DataStore::new(
+                address("DATA_STORE")?,
+                Arc::clone(&account),
+            ))),
+            oracle: Oracle::new(address("ORACLE")?, Arc::clone(&account)),
+            order_handler: OrderHandler::new(address("ORDER_HANDLER")?, Arc::clone(&account)),
+            order_handler_version: OrderHandlerVersion::from_config(
+                config::get_order_handler_version(),
+            )?,
+            next_order_handler: NextOrderHandler::from_env()?,
+            deposit_handler: DepositHandler::new(address("DEPOSIT_HANDLER")?, Arc::clone(&account)),
+            withdrawal_handler: WithdrawalHandler::new(
+                address("WITHDRAWAL_HANDLER")?,
+                Arc::clone(&account),
+            ),
+            price_bounds: PriceBounds::from_env(),
+            stable_prices: StablePrices::from_env(),
+            max_clock_skew_secs: config::get_max_clock_skew_secs(),
+            share_block_prices: config::get_share_batch_prices(),
+            set_prices: SetPricesTemplate::new(&spreads, &token_registry)?,
+            spreads,
+            market_set_prices,
+            market_feeds,
+            hooks: ExecutionHooks::from_env()?,
+            token_registry,
+            account,
+            addresses: addresses.clone(),
+        })
+    }
+
+    // Returns the constant SetPricesParams fields of a market.
+    // @market: The market of the action, as stored by the indexer.
+    pub fn set_prices_for(&self, market: &str) -> &SetPricesTemplate {
+        FieldElement::from_hex_be(market)
+            .ok()
+            .and_then(|market| self.market_set_prices.get(&market))
+            .unwrap_or(&self.set_prices)
+    }
+
+    // Returns the address and ABI version of the handler the orders of a market get executed
+    // against.
+    // @market: The market of the order, as stored by the indexer.
+    pub fn order_handler_for(&self, market: &str) -> (FieldElement, OrderHandlerVersion) {
+        match &self.next_order_handler {
+            Some(next) if next.handles(market) => (next.address, next.version),
+            _ => (self.order_handler.address, self.order_handler_version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let entries = parse_manifest(
+            r#"{
+                "contracts": {
+                    "OrderHandler": { "address": "0x12", "class_hash": "0x34" },
+                    "data_store": "0x56",
+                    "MarketToken": "0x78"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries["ORDER_HANDLER"],
+            ContractEntry {
+                address: FieldElement::from_hex_be("0x12").unwrap(),
+                class_hash: Some(FieldElement::from_hex_be("0x34").unwrap()),
+            }
+        );
+        assert_eq!(
+            entries["DATA_STORE"].address,
+            FieldElement::from_hex_be("0x56").unwrap()
+        );
+        assert!(parse_manifest(r#"{ "ORACLE": { "class_hash": "0x1" } }"#).is_err());
+        assert!(parse_manifest(r#"{ "ORACLE": "not a felt" }"#).is_err());
+    }
+
+    #[test]
+    fn test_resolve_addresses() {
+        let entry = |address: u8| ContractEntry {
+            address: FieldElement::from(address),
+            class_hash: None,
+        };
+        let entries = HashMap::from([
+            ("ORACLE".to_owned(), entry(1)),
+            ("DATA_STORE".to_owned(), entry(2)),
+        ]);
+        let addresses = ContractAddresses::resolve(&entries, |name| match name {
+            "ORACLE" => Some("0x3".to_owned()),
+            "ORDER_HANDLER" => Some("0x4".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(addresses.get("ORACLE").unwrap(), FieldElement::THREE);
+        assert_eq!(addresses.get("DATA_STORE").unwrap(), FieldElement::TWO);
+        assert_eq!(
+            addresses.get("ORDER_HANDLER").unwrap(),
+            FieldElement::from(4u8)
+        );
+        assert!(addresses.get("DEPOSIT_HANDLER").is_err());
+        assert!(ContractAddresses::resolve(&entries, |_| Some("not a felt".to_owned())).is_err());
+    }
+
+    #[test]
+    fn test_check_class_hash() {
+        let (one, two) = (FieldElement::ONE, FieldElement::TWO);
+        assert!(check_class_hash("ORACLE", one, one, &[]).is_ok());
+        assert!(check_class_hash("ORACLE", one, two, &[]).is_err());
+        assert!(check_class_hash("ORACLE", one, two, &[one]).is_err());
+        assert!(check_class_hash("ORACLE", one, two, &[two]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_class_hashes() {
+        assert_eq!(
+            parse_class_hashes(vec!["OrderHandler:0x12".to_owned()]).unwrap(),
+            vec![(
+                "ORDER_HANDLER".to_owned(),
+                FieldElement::from_hex_be("0x12").unwrap()
+            )]
+        );
+        assert!(parse_class_hashes(vec!["ORDER_HANDLER".to_owned()]).is_err());
+        assert!(parse_class_hashes(vec!["MARKET_TOKEN:0x12".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_set_prices_template() {
+        let template =
+            SetPricesTemplate::new(&PriceSpreads::default(), &TokenRegistry::default()).unwrap();
+        assert_eq!(template.tokens.len(), 2);
+        assert_eq!(template.signer_info, U256 { low: 1, high: 0 });
+        assert_eq!(template.compacted_min_prices, template.compacted_max_prices);
+        assert_eq!(template.signatures.len(), template.tokens.len());
+
+        let tokens: Vec<ContractAddress> = (1..4u8)
+            .map(|token| ContractAddress::from(FieldElement::from(token)))
+            .collect();
+        let template = SetPricesTemplate::for_tokens(
+            &PriceSpreads::default(),
+            &TokenRegistry::default(),
+            tokens,
+        )
+        .unwrap();
+        assert_eq!(template.tokens.len(), 3);
+        assert_eq!(
+            template.compacted_oracle_timestamps,
+            vec![171119803, 10, 171119803]
+        );
+        assert_eq!(template.signatures.len(), 3);
+    }
+
+    #[test]
+    fn test_set_prices_with_fetched_prices() {
+        let token_registry = TokenRegistry::from_env();
+        let eth = token_registry
+            .tokens
+            .iter()
+            .find(|token| token.symbol == "eth")
+            .unwrap()
+            .clone();
+        let spreads = PriceSpreads {
+            default_bps: 10,
+            bps: HashMap::new(),
+        };
+        let template = SetPricesTemplate::for_tokens(
+            &spreads,
+            &token_registry,
+            vec![ContractAddress::from(eth.address)],
+        )
+        .unwrap();
+        // 3000 USD with 8 feed decimals, compacted with the 8 oracle decimals of ETH.
+        let price = eth.to_compacted_price(3000 * 10u128.pow(8), 8).unwrap();
+        assert_eq!(price, 30_000_000);
+        let priced = template
+            .with_prices(&spreads, &token_registry, &[price], vec![1711110660])
+            .unwrap();
+        assert_eq!(
+            priced.compacted_min_prices,
+            vec![U256 {
+                low: 29_970_000,
+                high: 0
+            }]
+        );
+        assert_eq!(
+            priced.compacted_max_prices,
+            vec![U256 {
+                low: 30_030_000,
+                high: 0
+            }]
+        );
+        assert_eq!(priced.compacted_oracle_timestamps, vec![1711110660]);
+        assert_eq!(priced.compacted_decimals, template.compacted_decimals);
+        // Prices not fitting their compacted slot are rejected rather than truncated.
+        assert!(template
+            .with_prices(&spreads, &token_registry, &[1 << 32], vec![1711110660])
+            .is_err());
+        assert!(template
+            .with_prices(&spreads, &token_registry, &[], vec![])
+            .is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::error;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{Account, ConnectedAccount, SingleOwnerAccount},
+    core::{
+        types::{BlockId, BlockTag, FieldElement, FunctionCall},
+        utils::get_selector_from_name,
+    },
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+use tokio::time::sleep;
+
+use crate::{
+    clock::get_system_timestamp,
+    config,
+    error::KeeperError,
+    killswitch::{KillSwitch, KillSwitchStatus},
+};
+
+// Number of finished jobs listed on the dashboard.
+const RECENT_JOBS: i64 = 20;
+
+// A struct representing what the keeper last read from the chain, for the dashboard.
+// @latest_block: The latest block number.
+// @balance: The fee token balance of the keeper account, in its smallest unit.
+// @updated_at: When the chain got read, as a unix timestamp.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ChainStatus {
+    pub latest_block: Option<u64>,
+    pub balance: Option<String>,
+    pub updated_at: Option<u64>,
+}
+
+// A struct representing the jobs of a status, e.g. 3 claimed jobs.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct JobCount {
+    pub status: String,
+    pub jobs: i64,
+}
+
+// A struct representing a finished job listed on the dashboard.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct RecentJob {
+    pub key: String,
+    pub table_name: String,
+    pub status: String,
+    pub outcome: Option<String>,
+    pub failure_reason: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub updated_at: String,
+}
+
+// A struct representing what the dashboard shows operators without a monitoring stack.
+// @chain: What the keeper last read from the chain.
+// @indexed_block: The last block indexed.
+// @indexer_lag: The number of blocks the indexer is behind the chain, if both are known.
+// @pending_jobs: The jobs claimed or submitted, per status.
+// @recent_jobs: The last finished jobs.
+// @kill_switch: Whether outgoing transactions are stopped, and why.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DashboardStatus {
+    pub chain: ChainStatus,
+    pub indexed_block: Option<i64>,
+    pub indexer_lag: Option<i64>,
+    pub pending_jobs: Vec<JobCount>,
+    pub recent_jobs: Vec<RecentJob>,
+    pub kill_switch: KillSwitchStatus,
+}
+
+// Returns the number of blocks the indexer is behind the chain, 0 when it is ahead of the last
+// chain read.
+// @latest_block: The latest block number.
+// @indexed_block: The last block indexed.
+pub fn get_indexer_lag(latest_block: Option<u64>, indexed_block: Option<i64>) -> Option<i64> {
+    Some((latest_block? as i64 - indexed_block?).max(0))
+}
+
+// Returns a u256 balance in decimal, or in hex when it does not fit in a u128.
+// @low: The low 128 bits of the balance.
+// @high: The high 128 bits of the balance.
+pub fn format_balance(low: FieldElement, high: FieldElement) -> Option<String> {
+    let low = u128::try_from(low).ok()?;
+    match u128::try_from(high).ok()? {
+        0 => Some(low.to_string()),
+        high => Some(format!("{:#x}{:032x}", high, low)),
+    }
+}
+
+// Reads the latest block and the fee token balance of the keeper account.
+// @account: The keeper account.
+// @fee_token: The token the keeper pays its fees in.
+pub async fn read_chain_status(
+    account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    fee_token: FieldElement,
+) -> Result<ChainStatus, KeeperError> {
+    let to_error = |e| KeeperError::ExecutionError(format!("could not read chain status: {:?}", e));
+    let latest_block = account.provider().block_number().await.map_err(to_error)?;
+    let balance = account
+        .provider()
+        .call(
+            FunctionCall {
+                contract_address: fee_token,
+                entry_point_selector: get_selector_from_name("balanceOf")
+                    .expect("Invalid selector name"),
+                calldata: vec![account.address()],
+            },
+            BlockId::Tag(BlockTag::Latest),
+        )
+        .await
+        .map_err(to_error)?;
+    // Balances are u256, split in low and high felts.
+    let balance = match balance[..] {
+        [low, high] => format_balance(low, high),
+        _ => None,
+    };
+    Ok(ChainStatus {
+        latest_block: Some(latest_block),
+        balance,
+        updated_at: Some(get_system_timestamp()),
+    })
+}
+
+// Reads the chain status every interval, for the dashboard to show it without reading the chain
+// on every refresh.
+// @status: The chain status shown on the dashboard.
+// @account: The keeper account.
+pub async fn watch_chain_status(
+    status: &Mutex<ChainStatus>,
+    account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+) {
+    let fee_token = FieldElement::from_hex_be(&config::get_fee_token_address())
+        .expect("Invalid fee token address");
+    let interval = Duration::from_secs(config::get_dashboard_refresh_secs());
+    loop {
+        match read_chain_status(account, fee_token).await {
+            Ok(chain_status) => *status.lock().unwrap() = chain_status,
+            Err(e) => error!("{}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+// Loads the status shown on the dashboard.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @chain: What the keeper last read from the chain.
+// @kill_switch: The kill switch of the keeper.
+pub async fn get_dashboard_status(
+    pool: &Pool<Postgres>,
+    chain: ChainStatus,
+    kill_switch: &KillSwitch,
+) -> Result<DashboardStatus, sqlx::Error> {
+    let indexed_block: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(block_number) FROM last_indexed_block")
+            .fetch_one(pool)
+            .await?;
+    let pending_jobs = sqlx::query_as::<_, JobCount>(
+        "SELECT status, COUNT(*) AS jobs FROM keeper_jobs WHERE status IN ('claimed', 'submitted')
+         GROUP BY status ORDER BY status",
+    )
+    .fetch_all(pool)
+    .await?;
+    let recent_jobs = sqlx::query_as::<_, RecentJob>(
+        "SELECT key, table_name, status, outcome, failure_reason, transaction_hash,
+             updated_at::TEXT AS updated_at
+         FROM keeper_jobs WHERE status IN ('done', 'failed') ORDER BY keeper_jobs.updated_at DESC
+         LIMIT $1",
+    )
+    .bind(RECENT_JOBS)
+    .fetch_all(pool)
+    .await?;
+    Ok(DashboardStatus {
+        indexer_lag: get_indexer_lag(chain.latest_block, indexed_block),
+        chain,
+        indexed_block,
+        pending_jobs,
+        recent_jobs,
+        kill_switch: kill_switch.status(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_indexer_lag() {
+        assert_eq!(get_indexer_lag(Some(120), Some(100)), Some(20));
+        assert_eq!(get_indexer_lag(Some(100), Some(101)), Some(0));
+        assert_eq!(get_indexer_lag(None, Some(100)), None);
+        assert_eq!(get_indexer_lag(Some(100), None), None);
+    }
+
+    #[test]
+    fn test_format_balance() {
+        let balance = |low: u128, high: u128| format_balance(low.into(), high.into());
+        assert_eq!(
+            balance(1_500_000_000_000_000_000, 0),
+            Some("1500000000000000000".to_owned())
+        );
+        assert_eq!(
+            balance(1, 1),
+            Some("0x100000000000000000000000000000001".to_owned())
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::{future::Future, str::FromStr, time::Duration};
+
+use log::LevelFilter;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, Pool, Postgres,
+};
+
+use crate::config;
+
+tokio::task_local! {
+    // Where the queries of a task come from, e.g. the admin API route of a request.
+    static QUERY_ORIGIN: String;
+}
+
+// Runs a future with the origin its queries get logged with when slow.
+// @origin: The origin, e.g. GET /pnl.
+// @future: The future.
+pub async fn with_query_origin<F: Future>(origin: String, future: F) -> F::Output {
+    QUERY_ORIGIN.scope(origin, future).await
+}
+
+// Returns the origin of the queries of the current task, None outside of with_query_origin.
+pub fn get_query_origin() -> Option<String> {
+    QUERY_ORIGIN.try_with(|origin| origin.clone()).ok()
+}
+
+// A struct holding how the connections of a pool get set up, the keeper and the admin API using
+// their own pools so analytics queries of the API never hold the connections execution needs.
+// @max_connections: The most connections of the pool.
+// @statement_timeout: How long a statement runs before Postgres cancels it, never when None.
+// @slow_query: How long a statement runs before getting logged as slow, never when None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    pub statement_timeout: Option<Duration>,
+    pub slow_query: Option<Duration>,
+}
+
+impl PoolSettings {
+    // The settings of the pool of the keeper components.
+    pub fn keeper() -> Self {
+        PoolSettings {
+            max_connections: config::get_database_max_connections(),
+            statement_timeout: config::get_statement_timeout_ms().map(Duration::from_millis),
+            slow_query: config::get_slow_query_ms().map(Duration::from_millis),
+        }
+    }
+
+    // The settings of the pool of the admin API.
+    pub fn api() -> Self {
+        PoolSettings {
+            max_connections: config::get_api_database_max_connections(),
+            statement_timeout: config::get_api_statement_timeout_ms().map(Duration::from_millis),
+            slow_query: config::get_slow_query_ms().map(Duration::from_millis),
+        }
+    }
+
+    // Returns the options the connections of the pool get opened with.
+    // @url: The URL of the database.
+    pub fn connect_options(&self, url: &str) -> Result<PgConnectOptions, sqlx::Error> {
+        let mut options = PgConnectOptions::from_str(url)?;
+        if let Some(timeout) = self.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis())]);
+        }
+        Ok(match self.slow_query {
+            Some(slow_query) => options.log_slow_statements(LevelFilter::Warn, slow_query),
+            None => options.log_slow_statements(LevelFilter::Off, Duration::MAX),
+        })
+    }
+
+    // Opens the pool on DATABASE_URL.
+    pub async fn connect(&self) -> Result<Pool<Postgres>, sqlx::Error> {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .connect_with(self.connect_options(&config::get_database_url())?)
+            .await
+    }
+}
+
+// Opens a pool on DATABASE_URL for the tests reading back what the keeper records, None when no
+// database with the keeper tables is reachable, those tests getting skipped.
+#[cfg(test)]
+pub async fn connect_test_pool() -> Option<Pool<Postgres>> {
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .acquire_timeout(Duration::from_secs(2))
+        .connect(&config::get_database_url())
+        .await
+        .ok();
+    let ready = match &pool {
+        Some(pool) => sqlx::query("SELECT 1 FROM keeper_jobs LIMIT 1")
+            .execute(pool)
+            .await
+            .is_ok(),
+        None => false,
+    };
+    if !ready {
+        eprintln!("Skipped, no database with the keeper tables at DATABASE_URL");
+    }
+    pool.filter(|_| ready)
+}
+
+// Returns a key no indexed action has, so tests writing to the keeper tables never collide.
+// @name: The name of the test.
+#[cfg(test)]
+pub fn test_key(name: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Clock before the epoch")
+        .as_nanos();
+    format!("0xtest-{}-{:x}", name, nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_options() {
+        let settings = PoolSettings {
+            max_connections: 5,
+            statement_timeout: Some(Duration::from_secs(15)),
+            slow_query: Some(Duration::from_millis(500)),
+        };
+        let options = settings
+            .connect_options("postgres://postgres@localhost:5432/zohal")
+            .unwrap();
+        assert_eq!(options.get_options(), Some("-c statement_timeout=15000"));
+        let options = PoolSettings {
+            statement_timeout: None,
+            ..settings
+        }
+        .connect_options("postgres://postgres@localhost:5432/zohal")
+        .unwrap();
+        assert_eq!(options.get_options(), None);
+    }
+
+    #[tokio::test]
+    async fn test_query_origin() {
+        assert_eq!(get_query_origin(), None);
+        let origin = with_query_origin("GET /pnl".to_owned(), async { get_query_origin() }).await;
+        assert_eq!(origin.as_deref(), Some("GET /pnl"));
+    }
+}
@@ -0,0 +1,172 @@
+use log::{error, info};
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+// An enum representing a decision the keeper made on an action.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    // The action got executed.
+    Executed,
+    // The action was left alone, e.g. its execution would revert or another run claimed it.
+    Skipped,
+    // The execution was put off, e.g. on a clock skew or a stale price revert.
+    Deferred,
+    // The action was refused by an operator policy or the account throttle.
+    Rejected,
+    // The keeper gave up on the action after its execution failed.
+    Dropped,
+}
+
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Executed => "executed",
+            Decision::Skipped => "skipped",
+            Decision::Deferred => "deferred",
+            Decision::Rejected => "rejected",
+            Decision::Dropped => "dropped",
+        }
+    }
+}
+
+// A struct representing a decision as recorded in the audit log.
+// @key: The key of the action.
+// @table_name: The table the action comes from (orders, deposits, withdrawals).
+// @decision: The decision made.
+// @reason: Why the keeper made it.
+// @created_at: When it was made.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct DecisionRecord {
+    pub key: String,
+    pub table_name: String,
+    pub decision: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+// Records a decision in the audit log. The log never holds executions back, failures to record
+// only get reported.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @table: The table the action comes from.
+// @key: The key of the action.
+// @decision: The decision made.
+// @reason: Why the keeper made it.
+pub async fn record_decision(
+    pool: &Pool<Postgres>,
+    table: &str,
+    key: &str,
+    decision: Decision,
+    reason: &str,
+) {
+    // Logged too, the log file keeping the history of the decisions when the database is lost.
+    info!(
+        "Decision {} on {} {}: {}",
+        decision.as_str(),
+        table,
+        key,
+        reason
+    );
+    let result = sqlx::query(
+        "INSERT INTO keeper_decisions (key, table_name, decision, reason) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(key)
+    .bind(table)
+    .bind(decision.as_str())
+    .bind(reason)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        error!("Could not record decision on {}: {:?}", key, e);
+    }
+}
+
+// Loads the decisions made on an action, oldest first.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+pub async fn get_decisions(pool: &Pool<Postgres>, key: &str) -> Result<Vec<DecisionRecord>, Error> {
+    sqlx::query_as::<_, DecisionRecord>(
+        "SELECT key, table_name, decision, reason, created_at::TEXT AS created_at
+         FROM keeper_decisions WHERE key = $1 ORDER BY id",
+    )
+    .bind(key)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{connect_test_pool, test_key},
+        trade::{
+            callback::{check_callback_gas, CallbackGasCheck},
+            policy::PolicyDecision,
+            strategy::StrategyDecision,
+        },
+    };
+
+    #[test]
+    fn test_decision_as_str() {
+        assert_eq!(Decision::Skipped.as_str(), "skipped");
+        assert_eq!(Decision::Deferred.as_str(), "deferred");
+        assert_eq!(Decision::Rejected.as_str(), "rejected");
+    }
+
+    #[tokio::test]
+    async fn test_record_decisions() {
+        let Some(pool) = connect_test_pool().await else {
+            return;
+        };
+        let key = test_key("decisions");
+        // An unprofitable callback skipped, then the account of the action throttled.
+        let check = CallbackGasCheck {
+            execution_gas: 100,
+            skip_unprofitable: true,
+        };
+        let skipped = match check.decide(&key, check_callback_gas(300, 0, 1_000, 100, 3)) {
+            PolicyDecision::Skip(reason) => StrategyDecision::Skip(reason),
+            decision => panic!("Unexpected decision {:?}", decision),
+        };
+        let rejected = StrategyDecision::Reject("account 0xa throttled".to_owned());
+        for decision in [skipped, rejected] {
+            let (decision, reason) = decision.recorded().unwrap();
+            record_decision(&pool, "orders", &key, decision, reason).await;
+        }
+
+        let decisions = get_decisions(&pool, &key).await.unwrap();
+        let recorded: Vec<(&str, &str, &str)> = decisions
+            .iter()
+            .map(|record| {
+                (
+                    record.table_name.as_str(),
+                    record.decision.as_str(),
+                    record.reason.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            recorded,
+            [
+                (
+                    "orders",
+                    "skipped",
+                    "unprofitable, execution fee 1000 below the 1200 wei the execution and its callback cost at gas price 3 wei"
+                ),
+                ("orders", "rejected", "account 0xa throttled"),
+            ]
+        );
+        assert!(decisions.iter().all(|record| record.key == key));
+        assert!(get_decisions(&pool, &test_key("none"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        sqlx::query("DELETE FROM keeper_decisions WHERE key = $1")
+            .bind(&key)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
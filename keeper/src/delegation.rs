@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+// The channels an account can be notified on, through its webhook or its watch Telegram chat.
+pub const NOTIFICATION_CHANNELS: [&str; 2] = ["webhook", "telegram"];
+
+// An enum representing what the account-service keepers may do on behalf of an account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelegatedAction {
+    // Claiming the funding fees of the account.
+    ClaimFunding,
+    // Cancelling the orders of the account left pending for too long.
+    CancelStaleOrders,
+}
+
+impl DelegatedAction {
+    // The column of account_delegations the action gets opted into with.
+    fn column(&self) -> &'static str {
+        match self {
+            DelegatedAction::ClaimFunding => "auto_claim_funding",
+            DelegatedAction::CancelStaleOrders => "auto_cancel_stale_orders",
+        }
+    }
+}
+
+// A struct representing the preferences a trader registered for the managed execution of its
+// account, as stored in the account_delegations table. The account-service keepers read them
+// before acting on its behalf, never acting on accounts without.
+// @account: The account, as a 0x prefixed felt like the webhook ones.
+// @auto_claim_funding: Whether its funding fees get claimed for it.
+// @auto_cancel_stale_orders: Whether its orders pending for longer than stale_order_secs get
+// cancelled for it.
+// @stale_order_secs: How long its orders stay pending before being stale, set when they get
+// cancelled.
+// @notification_channels: The channels it gets notified on of the actions taken for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AccountDelegation {
+    pub account: String,
+    pub auto_claim_funding: bool,
+    pub auto_cancel_stale_orders: bool,
+    pub stale_order_secs: Option<i64>,
+    pub notification_channels: Vec<String>,
+}
+
+impl AccountDelegation {
+    // Checks the preferences can be registered.
+    pub fn check(&self) -> Result<(), String> {
+        match (self.auto_cancel_stale_orders, self.stale_order_secs) {
+            (true, None) => {
+                return Err("stale_order_secs must be set to cancel stale orders".to_owned())
+            }
+            (_, Some(secs)) if secs <= 0 => {
+                return Err("stale_order_secs must be positive".to_owned())
+            }
+            _ => {}
+        }
+        match self
+            .notification_channels
+            .iter()
+            .find(|channel| !NOTIFICATION_CHANNELS.contains(&channel.as_str()))
+        {
+            Some(channel) => Err(format!(
+                "unknown notification channel {}, expected one of {}",
+                channel,
+                NOTIFICATION_CHANNELS.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Returns whether the account opted into an action.
+    // @action: The action.
+    pub fn allows(&self, action: DelegatedAction) -> bool {
+        match action {
+            DelegatedAction::ClaimFunding => self.auto_claim_funding,
+            DelegatedAction::CancelStaleOrders => self.auto_cancel_stale_orders,
+        }
+    }
+}
+
+// Registers the preferences of an account, replacing the previous ones.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @delegation: The preferences of the account.
+pub async fn save_delegation(
+    pool: &Pool<Postgres>,
+    delegation: &AccountDelegation,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO account_delegations (account, auto_claim_funding, auto_cancel_stale_orders,
+         stale_order_secs, notification_channels) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (account) DO UPDATE SET auto_claim_funding = $2,
+         auto_cancel_stale_orders = $3, stale_order_secs = $4, notification_channels = $5,
+         updated_at = NOW()",
+    )
+    .bind(&delegation.account)
+    .bind(delegation.auto_claim_funding)
+    .bind(delegation.auto_cancel_stale_orders)
+    .bind(delegation.stale_order_secs)
+    .bind(&delegation.notification_channels)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Removes the preferences of an account, the account-service keepers then no longer acting on its
+// behalf. Returns whether there were any.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_delegations table.
+pub async fn delete_delegation(pool: &Pool<Postgres>, account: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM account_delegations WHERE account = $1")
+        .bind(account)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Loads the preferences of an account, if any.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_delegations table.
+pub async fn get_delegation(
+    pool: &Pool<Postgres>,
+    account: &str,
+) -> Result<Option<AccountDelegation>, sqlx::Error> {
+    sqlx::query_as::<_, AccountDelegation>(
+        "SELECT account, auto_claim_funding, auto_cancel_stale_orders, stale_order_secs,
+         notification_channels FROM account_delegations WHERE account = $1",
+    )
+    .bind(account)
+    .fetch_optional(pool)
+    .await
+}
+
+// Loads the preferences of the accounts opted into an action, for the account-service keepers to
+// act on.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @action: The action.
+pub async fn get_delegations(
+    pool: &Pool<Postgres>,
+    action: DelegatedAction,
+) -> Result<Vec<AccountDelegation>, sqlx::Error> {
+    sqlx::query_as::<_, AccountDelegation>(&format!(
+        "SELECT account, auto_claim_funding, auto_cancel_stale_orders, stale_order_secs,
+         notification_channels FROM account_delegations WHERE {} ORDER BY created_at",
+        action.column()
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delegation() -> AccountDelegation {
+        AccountDelegation {
+            account: "0x1a".to_owned(),
+            auto_claim_funding: true,
+            auto_cancel_stale_orders: false,
+            stale_order_secs: None,
+            notification_channels: vec!["webhook".to_owned()],
+        }
+    }
+
+    #[test]
+    fn test_check_delegation() {
+        assert!(delegation().check().is_ok());
+        let mut stale = AccountDelegation {
+            auto_cancel_stale_orders: true,
+            ..delegation()
+        };
+        assert!(stale.check().is_err());
+        stale.stale_order_secs = Some(0);
+        assert!(stale.check().is_err());
+        stale.stale_order_secs = Some(86_400);
+        assert!(stale.check().is_ok());
+        let email = AccountDelegation {
+            notification_channels: vec!["email".to_owned()],
+            ..delegation()
+        };
+        assert!(email.check().unwrap_err().contains("email"));
+    }
+
+    #[test]
+    fn test_delegated_actions() {
+        let delegation = delegation();
+        assert!(delegation.allows(DelegatedAction::ClaimFunding));
+        assert!(!delegation.allows(DelegatedAction::CancelStaleOrders));
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use satoru_client::sentry::capture_error;
+use sqlx::{Error, Pool, Postgres};
+use starknet::{
+    accounts::ConnectedAccount,
+    core::{
+        types::{BlockId, BlockTag, FieldElement, FunctionCall},
+        utils::get_selector_from_name,
+    },
+    providers::Provider,
+};
+use tokio::time::sleep;
+
+use crate::{config, error::KeeperError, executor::KeeperContext, types::SatoruAction};
+
+// Order keys read from the DataStore at once.
+const KEYS_PAGE_SIZE: u32 = 100;
+
+// The order types, in the order of the OrderType enum variants.
+const ORDER_TYPES: [&str; 8] = [
+    "MarketSwap",
+    "LimitSwap",
+    "MarketIncrease",
+    "LimitIncrease",
+    "MarketDecrease",
+    "LimitDecrease",
+    "StopLossDecrease",
+    "Liquidation",
+];
+
+// The decrease position swap types, in the order of the DecreasePositionSwapType enum variants.
+const DECREASE_POSITION_SWAP_TYPES: [&str; 3] = [
+    "NoSwap",
+    "SwapPnlTokenToCollateralToken",
+    "SwapCollateralTokenToPnlToken",
+];
+
+fn divergence_error(reason: String) -> KeeperError {
+    KeeperError::DivergenceError(reason)
+}
+
+// An enum representing how the indexer diverges from the DataStore on an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DivergenceKind {
+    // The order is pending in the DataStore but not in the indexer, its creation never getting
+    // indexed or a settlement getting indexed by mistake, so no keeper ever gets notified of it.
+    Unindexed,
+    // The order is pending in the indexer but not in the DataStore, its execution or cancellation
+    // never getting indexed.
+    Stale,
+}
+
+impl DivergenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DivergenceKind::Unindexed => "unindexed",
+            DivergenceKind::Stale => "stale",
+        }
+    }
+}
+
+// Returns the orders pending on one side only, unindexed then stale, each sorted by key.
+// @on_chain: The keys of the orders pending in the DataStore.
+// @indexed: The keys of the orders pending in the indexer.
+pub fn find_divergences(
+    on_chain: &HashSet<FieldElement>,
+    indexed: &HashSet<FieldElement>,
+) -> Vec<(FieldElement, DivergenceKind)> {
+    let mut unindexed: Vec<FieldElement> = on_chain.difference(indexed).copied().collect();
+    let mut stale: Vec<FieldElement> = indexed.difference(on_chain).copied().collect();
+    unindexed.sort();
+    stale.sort();
+    unindexed
+        .into_iter()
+        .map(|key| (key, DivergenceKind::Unindexed))
+        .chain(stale.into_iter().map(|key| (key, DivergenceKind::Stale)))
+        .collect()
+}
+
+// A struct representing the divergences seen by the last check, an order only diverging once seen
+// by two checks in a row, as orders get created and settled between the reads of both sides and
+// the indexer lags behind the chain.
+#[derive(Debug, Clone, Default)]
+pub struct DivergenceWatch {
+    pub previous: HashSet<(FieldElement, DivergenceKind)>,
+}
+
+impl DivergenceWatch {
+    // Returns the divergences also seen by the previous check, remembering the new ones.
+    // @divergences: The divergences found by this check.
+    pub fn confirm(
+        &mut self,
+        divergences: Vec<(FieldElement, DivergenceKind)>,
+    ) -> Vec<(FieldElement, DivergenceKind)> {
+        let confirmed = divergences
+            .iter()
+            .filter(|divergence| self.previous.contains(divergence))
+            .copied()
+            .collect();
+        self.previous = divergences.into_iter().collect();
+        confirmed
+    }
+}
+
+// Formats a felt as the indexer stores it, without 0x prefix and padded, so repaired orders get
+// claimed under the same key as indexed ones.
+fn to_indexed_felt(felt: FieldElement) -> String {
+    format!("{:064x}", felt)
+}
+
+// Reads the u128 amount of the low and high felts at an index, saturating the larger ones.
+fn u128_at(data: &[FieldElement], index: usize) -> Option<u128> {
+    let low = u128::try_from(*data.get(index)?).ok()?;
+    match *data.get(index + 1)? == FieldElement::ZERO {
+        true => Some(low),
+        false => Some(u128::MAX),
+    }
+}
+
+// Builds the action of an order from the Order struct returned by DataStore.get_order, as the
+// indexer would have stored it, None for a removed or malformed order.
+// @data: The serialized Order struct.
+pub fn parse_order(data: &[FieldElement]) -> Option<SatoruAction> {
+    let felt = |index: usize| data.get(index).copied();
+    let variant = |index: usize| {
+        felt(index)
+            .and_then(|value| u64::try_from(value).ok())
+            .map(|value| value as usize)
+    };
+    let account = felt(3)?;
+    if account == FieldElement::ZERO {
+        return None;
+    }
+    let swap_path_len = variant(9)?;
+    let swap_path: Vec<String> = data
+        .get(10..10 + swap_path_len)?
+        .iter()
+        .map(|token| to_indexed_felt(*token))
+        .collect();
+    let amounts_at = 10 + swap_path_len;
+    Some(SatoruAction {
+        key: to_indexed_felt(felt(0)?),
+        order_type: Some(ORDER_TYPES.get(variant(1)?)?.to_string()),
+        decrease_position_swap_type: Some(
+            DECREASE_POSITION_SWAP_TYPES.get(variant(2)?)?.to_string(),
+        ),
+        account: to_indexed_felt(account),
+        receiver: to_indexed_felt(felt(4)?),
+        callback_contract: to_indexed_felt(felt(5)?),
+        ui_fee_receiver: to_indexed_felt(felt(6)?),
+        market: to_indexed_felt(felt(7)?),
+        initial_collateral_token: Some(to_indexed_felt(felt(8)?)),
+        swap_path: Some(swap_path.join(",")),
+        size_delta_usd: Some(u128_at(data, amounts_at)?),
+        initial_collateral_delta_amount: Some(u128_at(data, amounts_at + 2)?),
+        trigger_price: Some(u128_at(data, amounts_at + 4)?),
+        acceptable_price: Some(u128_at(data, amounts_at + 6)?),
+        execution_fee: u128_at(data, amounts_at + 8)?,
+        callback_gas_limit: u128_at(data, amounts_at + 10)?,
+        min_output_amount: Some(u128_at(data, amounts_at + 12)?),
+        updated_at_block: u64::try_from(felt(amounts_at + 14)?).ok()?,
+        is_long: Some(felt(amounts_at + 15)? == FieldElement::ONE),
+        is_frozen: Some(felt(amounts_at + 16)? == FieldElement::ONE),
+        ..Default::default()
+    })
+}
+
+// Loads the keys of the orders the indexer holds pending, neither executed nor cancelled.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_indexed_pending_orders(
+    pool: &Pool<Postgres>,
+) -> Result<HashSet<FieldElement>, Error> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT felt_out(o.key) FROM orders o
+         WHERE o.key IS NOT NULL
+             AND NOT EXISTS (SELECT 1 FROM order_executed e WHERE e.key = o.key)
+             AND NOT EXISTS (SELECT 1 FROM order_cancelled c WHERE c.key = o.key)",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|(key,)| FieldElement::from_hex_be(key).ok())
+        .collect())
+}
+
+// Loads the keys of the orders pending in the DataStore, page by page.
+// @context: The keeper context, the DataStore being read through its contracts.
+async fn load_on_chain_pending_orders(
+    context: &KeeperContext,
+) -> Result<HashSet<FieldElement>, KeeperError> {
+    let data_store = &context.contracts.data_store;
+    let to_error = |e| divergence_error(format!("{:?}", e));
+    let count = data_store
+        .get_order_count()
+        .call()
+        .await
+        .map_err(to_error)?;
+    let mut keys = HashSet::new();
+    for start in (0..count).step_by(KEYS_PAGE_SIZE as usize) {
+        let end = start.saturating_add(KEYS_PAGE_SIZE).min(count);
+        keys.extend(
+            data_store
+                .get_order_keys(&start, &end)
+                .call()
+                .await
+                .map_err(to_error)?,
+        );
+    }
+    Ok(keys)
+}
+
+// Reads an order from the DataStore as its action, None once removed.
+// @context: The keeper context.
+// @key: The key of the order.
+async fn read_order(
+    context: &KeeperContext,
+    key: FieldElement,
+) -> Result<Option<SatoruAction>, KeeperError> {
+    let data = context
+        .account
+        .provider()
+        .call(
+            FunctionCall {
+                contract_address: context.contracts.data_store.address,
+                entry_point_selector: get_selector_from_name("get_order")
+                    .expect("Invalid selector"),
+                calldata: vec![key],
+            },
+            BlockId::Tag(BlockTag::Latest),
+        )
+        .await
+        .map_err(|e| divergence_error(format!("could not read order {:#x}: {:?}", key, e)))?;
+    Ok(parse_order(&data))
+}
+
+// Records a divergence in the keeper_divergences table, returns whether it is a new one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the order.
+// @kind: How the indexer diverges on it.
+// @repaired: Whether the keeper handled the order from the DataStore.
+pub async fn record_divergence(
+    pool: &Pool<Postgres>,
+    key: FieldElement,
+    kind: DivergenceKind,
+    repaired: bool,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO keeper_divergences (key, kind, repaired) VALUES ($1, $2, $3)
+         ON CONFLICT (key, kind) DO NOTHING",
+    )
+    .bind(to_indexed_felt(key))
+    .bind(kind.as_str())
+    .bind(repaired)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// A struct representing how the indexer gets cross-checked with the DataStore.
+// @interval: The delay between two checks, never checked when None.
+// @auto_repair: Whether the unindexed orders get handled from the DataStore.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DivergenceParams {
+    pub interval: Option<Duration>,
+    pub auto_repair: bool,
+}
+
+impl DivergenceParams {
+    pub fn from_env() -> Self {
+        DivergenceParams {
+            interval: config::get_divergence_check_interval_secs().map(Duration::from_secs),
+            auto_repair: config::get_divergence_auto_repair(),
+        }
+    }
+}
+
+// Compares the pending orders of the DataStore with the indexed ones, alerting on the new
+// divergences seen twice in a row. Unindexed orders get handed to `repair` when auto-repair is on,
+// stale ones being left to a rebuild of the indexer. Returns the divergences confirmed.
+// @context: The keeper context.
+// @params: How the indexer gets cross-checked.
+// @watch: The divergences seen by the last check.
+// @repair: Handles an unindexed order as a new one.
+pub async fn check_divergences<F: Fn(SatoruAction)>(
+    context: &KeeperContext,
+    params: &DivergenceParams,
+    watch: &mut DivergenceWatch,
+    repair: &F,
+) -> Result<usize, KeeperError> {
+    let on_chain = load_on_chain_pending_orders(context).await?;
+    let indexed = load_indexed_pending_orders(&context.pool)
+        .await
+        .map_err(|e| divergence_error(format!("{:?}", e)))?;
+    let confirmed = watch.confirm(find_divergences(&on_chain, &indexed));
+    let mut per_kind: HashMap<DivergenceKind, usize> = HashMap::new();
+    for (key, kind) in &confirmed {
+        *per_kind.entry(*kind).or_default() += 1;
+        let repaired = match (kind, params.auto_repair) {
+            (DivergenceKind::Unindexed, true) => match read_order(context, *key).await? {
+                Some(order) => {
+                    repair(order);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        };
+        match record_divergence(&context.pool, *key, *kind, repaired).await {
+            Ok(true) => {
+                warn!(
+                    "ALERT: order {:#x} is {} in the indexer{}",
+                    key,
+                    kind.as_str(),
+                    if repaired {
+                        ", handled from the DataStore"
+                    } else {
+                        ""
+                    }
+                );
+                capture_error(
+                    &format!("order {} in the indexer", kind.as_str()),
+                    &[("key", &to_indexed_felt(*key))],
+                );
+            }
+            Ok(false) => {}
+            Err(e) => error!("Could not record divergence on order {:#x}: {:?}", key, e),
+        }
+    }
+    if !confirmed.is_empty() {
+        info!(
+            "Indexer diverges from the DataStore on {} unindexed and {} stale orders",
+            per_kind.get(&DivergenceKind::Unindexed).unwrap_or(&0),
+            per_kind.get(&DivergenceKind::Stale).unwrap_or(&0)
+        );
+    }
+    Ok(confirmed.len())
+}
+
+// Cross-checks the indexer with the DataStore every interval, so orders the indexer silently
+// dropped get caught before they become missed executions.
+// @context: The keeper context.
+// @params: How the indexer gets cross-checked.
+// @repair: Handles an unindexed order as a new one.
+pub async fn run_divergence_watchdog<F: Fn(SatoruAction)>(
+    context: Arc<KeeperContext>,
+    params: DivergenceParams,
+    repair: F,
+) {
+    let interval = match params.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    let mut watch = DivergenceWatch::default();
+    loop {
+        sleep(interval).await;
+        // The active keeper checks, a standby repairing nothing until promoted.
+        if !context.standby.is_active() {
+            continue;
+        }
+        if let Err(e) = check_divergences(&context, &params, &mut watch, &repair).await {
+            error!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt(value: u64) -> FieldElement {
+        FieldElement::from(value)
+    }
+
+    fn keys(values: &[u64]) -> HashSet<FieldElement> {
+        values.iter().map(|value| felt(*value)).collect()
+    }
+
+    #[test]
+    fn test_find_divergences() {
+        assert_eq!(
+            find_divergences(&keys(&[1, 2, 4]), &keys(&[2, 3])),
+            vec![
+                (felt(1), DivergenceKind::Unindexed),
+                (felt(4), DivergenceKind::Unindexed),
+                (felt(3), DivergenceKind::Stale)
+            ]
+        );
+        assert!(find_divergences(&keys(&[1]), &keys(&[1])).is_empty());
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut watch = DivergenceWatch::default();
+        let unindexed = |value| (felt(value), DivergenceKind::Unindexed);
+        assert!(watch.confirm(vec![unindexed(1), unindexed(2)]).is_empty());
+        // 2 got indexed meanwhile, 3 is new.
+        assert_eq!(
+            watch.confirm(vec![unindexed(1), unindexed(3)]),
+            vec![unindexed(1)]
+        );
+        assert_eq!(watch.confirm(vec![unindexed(3)]), vec![unindexed(3)]);
+    }
+
+    #[test]
+    fn test_parse_order() {
+        let mut data: Vec<FieldElement> = [0xa, 2, 0, 0xb, 0xb, 0, 0, 0xc, 0xd, 1, 0xe]
+            .into_iter()
+            .map(felt)
+            .collect();
+        // The amounts, then the updated block, is_long and is_frozen.
+        data.extend([5000, 0, 10, 0, 0, 0, 2000, 0, 7, 0, 0, 0, 0, 0, 100, 1, 0].map(felt));
+        let order = parse_order(&data).unwrap();
+        assert_eq!(order.key, format!("{:064x}", 0xa));
+        assert_eq!(order.order_type.as_deref(), Some("MarketIncrease"));
+        assert_eq!(order.decrease_position_swap_type.as_deref(), Some("NoSwap"));
+        assert_eq!(order.market, format!("{:064x}", 0xc));
+        assert_eq!(order.swap_path, Some(format!("{:064x}", 0xe)));
+        assert_eq!(order.size_delta_usd, Some(5000));
+        assert_eq!(order.acceptable_price, Some(2000));
+        assert_eq!(order.execution_fee, 7);
+        assert_eq!(order.updated_at_block, 100);
+        assert_eq!(order.is_long, Some(true));
+        assert_eq!(order.is_frozen, Some(false));
+
+        // A removed order reads as a zeroed one.
+        let removed = vec![FieldElement::ZERO; data.len()];
+        assert!(parse_order(&removed).is_none());
+        assert!(parse_order(&data[..12]).is_none());
+    }
+}
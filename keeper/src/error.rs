@@ -12,4 +12,52 @@ pub enum KeeperError {
     PublicKeyNotSet(),
     #[error("Wrong launch params")]
     WrongParam(),
+    #[error("Contract discovery failed: {0}")]
+    ContractDiscoveryError(String),
+    #[error("Execution failed: {0}")]
+    ExecutionError(String),
+    #[error("Clock skew: {0}")]
+    ClockSkew(String),
+    #[error("Price out of bounds: {0}")]
+    PriceOutOfBounds(String),
+    #[error("Self-test failed: {0}")]
+    SelfTestError(String),
+    #[error("Session error: {0}")]
+    SessionError(String),
+    #[error("Paymaster error: {0}")]
+    PaymasterError(String),
+    #[error("Schedule error: {0}")]
+    ScheduleError(String),
+    #[error("Feed configuration error: {0}")]
+    FeedError(String),
+    #[error("Execution hook error: {0}")]
+    HookError(String),
+    #[error("Relay rejected: {0}")]
+    RelayError(String),
+    #[error("Profile error: {0}")]
+    ProfileError(String),
+    #[error("Account watch error: {0}")]
+    WatchError(String),
+    #[error("Upgrade check failed: {0}")]
+    UpgradeCheckError(String),
+    #[error("Execution snapshot error: {0}")]
+    SnapshotError(String),
+    #[error("Price log error: {0}")]
+    PriceLogError(String),
+    #[error("Data-quality check failed: {0}")]
+    DataQualityError(String),
+    #[error("Standby error: {0}")]
+    StandbyError(String),
+    #[error("Risk report error: {0}")]
+    RiskError(String),
+    #[error("Market configuration error: {0}")]
+    MarketConfigError(String),
+    #[error("Divergence check failed: {0}")]
+    DivergenceError(String),
+    #[error("gRPC service error: {0}")]
+    GrpcError(String),
+    #[error("Kill switch engaged: {0}")]
+    KillSwitchEngaged(String),
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    ConfigError(Vec<String>),
 }
@@ -0,0 +1,454 @@
+#[cfg(feature = "liquidation")]
+use std::sync::RwLock;
+use std::{sync::Arc, time::Duration};
+
+use log::{error, info};
+use satoru_client::sentry::capture_error;
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{Account, Call, ConnectedAccount, SingleOwnerAccount},
+    core::types::{FieldElement, TransactionReceipt},
+    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    signers::LocalWallet,
+};
+use tokio::{sync::Notify, time::sleep};
+
+use crate::{
+    clock::Clock,
+    config,
+    contracts::Contracts,
+    decisions::{record_decision, Decision},
+    error::KeeperError,
+    fees::{get_receipt_fee_split, record_order_fee, record_transaction_action},
+    killswitch::KillSwitch,
+    pnl::record_transaction_fee,
+    quality::{record_execution_price, take_oracle_reading},
+    snapshot::record_failed_execution,
+    standby::Standby,
+    state::{load_action_attempt, mark_job_finished, mark_job_submitted, record_job_attempt},
+    submitter::Submitter,
+    sweeper::JobLease,
+    trade::{
+        batch::CallBatcher,
+        callback::CallbackGasCheck,
+        caps::check_increase_caps,
+        congestion::GasThrottle,
+        crash::CrashMode,
+        deposit::handle::get_deposit_calls,
+        expiry::OrderExpiry,
+        order::handle::get_order_calls,
+        policy::{ExecutionPolicies, PolicyDecision},
+        queue::ExecutionQueue,
+        receipt::{get_actual_fee, get_execution_outcome, wait_for_receipt, ExecutionOutcome},
+        requeue::{RequeueDecision, RequeuePolicies},
+        schedule::MarketSchedules,
+        slippage::SlippageCheck,
+        strategy::KeeperStrategy,
+        throttle::AccountThrottle,
+        withdrawal::handle::get_withdrawal_calls,
+    },
+    types::SatoruAction,
+    webhooks::WebhookSender,
+};
+
+// Delay before checking the clocks again when a job is paused on a skew.
+const CLOCK_SKEW_PAUSE: Duration = Duration::from_secs(10);
+// Delay before checking the kill switch again when a job is paused on it.
+const KILL_SWITCH_PAUSE: Duration = Duration::from_secs(5);
+// Delay before checking the gas price again when a job is paused on congestion.
+const CONGESTION_PAUSE: Duration = Duration::from_secs(15);
+
+// Builds the execution calls of an action using the handler of its table.
+async fn get_execution_calls(
+    contracts: &Contracts,
+    table: &str,
+    action: SatoruAction,
+) -> Result<Vec<Call>, KeeperError> {
+    match table {
+        "orders" => get_order_calls(contracts, action).await,
+        "deposits" => get_deposit_calls(contracts, action).await,
+        "withdrawals" => get_withdrawal_calls(contracts, action).await,
+        other => Err(KeeperError::ExecutionError(format!(
+            "no handler for table {}",
+            other
+        ))),
+    }
+}
+
+// Returns the outcome of an execution not worth sending: a trigger order older than the TTL,
+// which would execute on a stale intent, or an increase order above a market cap, which would
+// revert on it. None when the execution gets sent, including when the chain cannot be read.
+// @context: The keeper context.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The action to execute.
+async fn hold_execution(
+    context: &KeeperContext,
+    table: &str,
+    action: &SatoruAction,
+) -> Option<ExecutionOutcome> {
+    match context.expiry.check(&context.account, table, action).await {
+        Ok(Some(age)) => return Some(ExecutionOutcome::Expired(age)),
+        Ok(None) => {}
+        Err(e) => error!("Could not check expiry of job {}: {}", action.key, e),
+    }
+    match check_increase_caps(&context.contracts, table, action).await {
+        Ok(PolicyDecision::Defer(reason)) => Some(ExecutionOutcome::Capped(reason)),
+        Ok(_) => None,
+        Err(e) => {
+            error!("Could not check caps of job {}: {}", action.key, e);
+            None
+        }
+    }
+}
+
+// A struct representing what the executions of the keeper share.
+// @account: The keeper account, used for reads and receipts.
+// @contracts: The keeper contracts the executions get built with.
+// @submitter: The keeper account sending the execution transactions, possibly through a session key.
+// @batcher: The batching of the executions sent through the submitter.
+// @pool: A connection pool for PostgreSQL.
+// @policies: The requeue policies applied to reverted executions.
+// @execution_policies: The operator policies deciding which actions get executed.
+// @callback_gas: The validation of the callback gas limits, checked before claiming actions.
+// @slippage: The enforcement of the acceptable prices of market orders, checked before claiming them.
+// @throttle: The per account limit on executed orders.
+// @clock: The clock reconciliation pausing executions on skew.
+// @expiry: The TTL of the trigger orders, checked before every execution.
+// @schedules: The trading hours of the markets, outside of which executions are held.
+// @kill_switch: The emergency stop of the outgoing transactions, pausing the jobs while engaged.
+// @positions: The open positions as of the last DataStore scan.
+// @crash_mode: The flash crash mode, engaged by bursts of liquidations.
+// @queue: The priority queue the executions wait in for a slot to be sent.
+// @gas_throttle: The deferral of the low value executions while network fees spike.
+// @webhooks: The receipts of the finished orders posted to the callbacks of their accounts.
+// @strategy: The strategy deciding whether new actions get executed, before claiming them.
+// @wakeup: Notified on every database notification, waking the receipt polls up at once.
+// @standby: The role of the instance, standbys claiming no new action until promoted.
+pub struct KeeperContext {
+    pub account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+    pub contracts: Contracts,
+    pub submitter: Arc<Submitter>,
+    pub batcher: CallBatcher,
+    pub pool: Pool<Postgres>,
+    pub policies: RequeuePolicies,
+    pub execution_policies: ExecutionPolicies,
+    pub callback_gas: CallbackGasCheck,
+    pub slippage: SlippageCheck,
+    pub throttle: AccountThrottle,
+    pub clock: Clock,
+    pub expiry: OrderExpiry,
+    pub schedules: MarketSchedules,
+    pub kill_switch: Arc<KillSwitch>,
+    #[cfg(feature = "liquidation")]
+    pub positions: RwLock<crate::scanner::PositionBook>,
+    pub crash_mode: Arc<CrashMode>,
+    pub queue: ExecutionQueue,
+    pub gas_throttle: GasThrottle,
+    pub webhooks: WebhookSender,
+    pub strategy: Arc<dyn KeeperStrategy>,
+    pub wakeup: Notify,
+    pub standby: Arc<Standby>,
+    // The token the keeper pays its fees in, parsed once from FEE_TOKEN_ADDRESS.
+    pub fee_token: FieldElement,
+}
+
+// Records how the transaction settling an action split its execution fee between the keeper and
+// the trader, along the gas the keeper spent on it.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @keeper: The address of the keeper account.
+// @fee_token: The token the keeper pays its fees in.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The settled action.
+// @transaction_hash: The hash of the settling transaction.
+// @receipt: Its receipt.
+async fn record_settlement_fee(
+    pool: &Pool<Postgres>,
+    keeper: FieldElement,
+    fee_token: FieldElement,
+    table: &str,
+    action: &SatoruAction,
+    transaction_hash: FieldElement,
+    receipt: &TransactionReceipt,
+) {
+    let account = FieldElement::from_hex_be(&action.account).unwrap_or_default();
+    let key = FieldElement::from_hex_be(&action.key).unwrap_or_default();
+    let split = get_receipt_fee_split(receipt, fee_token, keeper, account, key);
+    if let Err(e) = record_order_fee(
+        pool,
+        table,
+        &action.key,
+        &action.account,
+        transaction_hash,
+        action.execution_fee,
+        split,
+    )
+    .await
+    {
+        error!(
+            "Could not persist fee economics of job {}: {:?}",
+            action.key, e
+        );
+    }
+}
+
+// Executes a claimed action and tracks its transactions until the action settles, requeuing
+// it according to the requeue policies when the execution reverts.
+// @context: The keeper context.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The action to execute.
+// @attempts: The number of executions already made for the action.
+// @pending_transaction: A transaction sent by a previous run that still awaits its receipt.
+pub async fn execute_job(
+    context: Arc<KeeperContext>,
+    table: String,
+    action: SatoruAction,
+    mut attempts: u32,
+    mut pending_transaction: Option<FieldElement>,
+) {
+    let KeeperContext {
+        account,
+        contracts,
+        batcher,
+        pool,
+        policies,
+        clock,
+        schedules,
+        kill_switch,
+        crash_mode,
+        queue,
+        gas_throttle,
+        webhooks,
+        wakeup,
+        fee_token,
+        ..
+    } = context.as_ref();
+    let key = action.key.clone();
+    let key_felt = FieldElement::from_hex_be(&key).expect("Cannot convert string to felt");
+    let mut last_transaction = pending_transaction;
+    // The calls of the last execution sent, snapshotted when it reverts for good.
+    let mut last_calls: Option<Vec<Call>> = None;
+    // Refreshed while running, so the sweeper never takes the job over.
+    let _lease = JobLease::hold(pool.clone(), key.clone());
+
+    // The attempts outlive the jobs, so an action whose job got lost, e.g. with a wiped jobs
+    // table, never gets resubmitted: its last transaction gets awaited instead, and a finished
+    // action gets skipped.
+    if pending_transaction.is_none() {
+        match load_action_attempt(pool, &key).await {
+            Ok(Some(attempt)) => {
+                if let Some(outcome) = attempt.outcome {
+                    let reason = format!("already {} in a previous run", outcome);
+                    info!("Job {} skipped: {}", key, reason);
+                    record_decision(pool, &table, &key, Decision::Skipped, &reason).await;
+                    if let Err(e) = mark_job_finished(pool, &key, &ExecutionOutcome::Settled).await
+                    {
+                        error!("Could not persist finished job {}: {:?}", key, e);
+                    }
+                    return;
+                }
+                attempts = attempts.max(attempt.attempts);
+                pending_transaction = attempt.transaction_hash;
+                last_transaction = pending_transaction;
+            }
+            Ok(None) => {}
+            Err(e) => error!("Could not load attempts of job {}: {:?}", key, e),
+        }
+    }
+
+    loop {
+        let outcome = match pending_transaction.take() {
+            Some(transaction_hash) => {
+                last_transaction = Some(transaction_hash);
+                match wait_for_receipt(account.provider(), transaction_hash, wakeup).await {
+                    Ok(receipt) => {
+                        let outcome = get_execution_outcome(&receipt, &table, key_felt);
+                        // Reverted transactions still pay their fee, the keeper fee of settled
+                        // actions getting earned when their settlement is recorded below.
+                        if let Err(e) = record_transaction_fee(
+                            pool,
+                            &key,
+                            transaction_hash,
+                            get_actual_fee(&receipt),
+                        )
+                        .await
+                        {
+                            error!("Could not persist fee of job {}: {:?}", key, e);
+                        }
+                        // Executions and cancellations both settle the execution fee.
+                        if matches!(
+                            outcome,
+                            ExecutionOutcome::Executed | ExecutionOutcome::Cancelled(_)
+                        ) {
+                            record_settlement_fee(
+                                pool,
+                                account.address(),
+                                *fee_token,
+                                &table,
+                                &action,
+                                transaction_hash,
+                                &receipt,
+                            )
+                            .await;
+                        }
+                        outcome
+                    }
+                    Err(e) => {
+                        error!("Could not get receipt of job {}: {:?}", key, e);
+                        capture_error(
+                            &format!("could not get receipt: {:?}", e),
+                            &[("order_key", &key), ("table", &table)],
+                        );
+                        return;
+                    }
+                }
+            }
+            None => {
+                // A clock skew pauses the job without counting an attempt, until the clocks agree.
+                let block_timestamp = match clock.reconcile(account).await {
+                    Ok(block_timestamp) => block_timestamp,
+                    Err(e) => {
+                        info!("Job {} paused: {}", key, e);
+                        record_decision(pool, &table, &key, Decision::Deferred, &e.to_string())
+                            .await;
+                        sleep(CLOCK_SKEW_PAUSE).await;
+                        continue;
+                    }
+                };
+                // So does a closed market, until it opens.
+                if let Some(secs) = schedules.secs_until_open(&action.market, block_timestamp) {
+                    let reason = format!("market {} closed, opening in {}s", action.market, secs);
+                    info!("Job {} paused: {}", key, reason);
+                    record_decision(pool, &table, &key, Decision::Deferred, &reason).await;
+                    sleep(Duration::from_secs(secs)).await;
+                    continue;
+                }
+                // So does the kill switch, until it gets disengaged.
+                if let Err(e) = kill_switch.check() {
+                    info!("Job {} paused: {}", key, e);
+                    record_decision(pool, &table, &key, Decision::Deferred, &e.to_string()).await;
+                    sleep(KILL_SWITCH_PAUSE).await;
+                    continue;
+                }
+                // So does a gas price spike for low value executions, until fees normalize.
+                if let Some(reason) = gas_throttle.defer_reason(&table, &action) {
+                    info!("Job {} paused: {}", key, reason);
+                    record_decision(pool, &table, &key, Decision::Deferred, &reason).await;
+                    sleep(CONGESTION_PAUSE).await;
+                    continue;
+                }
+                // The slot is held until the execution got sent, receipts being awaited without it.
+                let _slot = queue.acquire(&table, &action).await;
+                attempts = match record_job_attempt(pool, &table, &key).await {
+                    Ok(attempts) => attempts,
+                    Err(e) => {
+                        error!("Could not persist attempt of job {}: {:?}", key, e);
+                        attempts + 1
+                    }
+                };
+                match hold_execution(&context, &table, &action).await {
+                    Some(outcome) => outcome,
+                    None => {
+                        // Liquidations wait for more of them to batch with during a flash crash.
+                        let window = crash_mode.batch_window(batcher.window, &table, &action);
+                        let sent =
+                            match get_execution_calls(contracts, &table, action.clone()).await {
+                                Ok(calls) => {
+                                    last_calls = Some(calls.clone());
+                                    batcher.send_within(calls, window).await
+                                }
+                                Err(e) => Err(e),
+                            };
+                        match sent {
+                            Ok(transaction_hash) => {
+                                if let Err(e) =
+                                    mark_job_submitted(pool, &key, transaction_hash).await
+                                {
+                                    error!("Could not persist submitted job {}: {:?}", key, e);
+                                }
+                                if let Err(e) =
+                                    record_transaction_action(pool, transaction_hash, &key).await
+                                {
+                                    error!("Could not persist transaction of job {}: {:?}", key, e);
+                                }
+                                pending_transaction = Some(transaction_hash);
+                                continue;
+                            }
+                            Err(KeeperError::ExecutionError(reason)) => {
+                                ExecutionOutcome::Reverted(reason)
+                            }
+                            // The switch got engaged while the execution was being built or batched.
+                            Err(e @ KeeperError::KillSwitchEngaged(_)) => {
+                                info!("Job {} paused: {}", key, e);
+                                sleep(KILL_SWITCH_PAUSE).await;
+                                continue;
+                            }
+                            Err(e) => ExecutionOutcome::Reverted(e.to_string()),
+                        }
+                    }
+                }
+            }
+        };
+
+        let requeue = match &outcome {
+            ExecutionOutcome::Reverted(reason) => Some((
+                format!("reverted with {}", reason),
+                policies.decide(reason, attempts),
+            )),
+            ExecutionOutcome::Capped(reason) => Some((
+                format!("deferred, {}", reason),
+                policies.decide_capped(attempts),
+            )),
+            _ => None,
+        };
+        if let Some((status, RequeueDecision::Retry(delay))) = requeue {
+            info!(
+                "Job {} {} (attempt {}), retrying in {:?}",
+                key, status, attempts, delay
+            );
+            record_decision(
+                pool,
+                &table,
+                &key,
+                Decision::Deferred,
+                &format!("{}, retrying in {:?}", status, delay),
+            )
+            .await;
+            sleep(delay).await;
+            continue;
+        }
+
+        info!("Job {} finished with outcome {:?}", key, outcome);
+        if let ExecutionOutcome::Reverted(reason) = &outcome {
+            capture_error(
+                &format!("execution reverted: {}", reason),
+                &[("order_key", &key), ("table", &table)],
+            );
+            if let Some(calls) = last_calls.take() {
+                if config::get_execution_snapshots_enabled() {
+                    record_failed_execution(pool, account, &table, &action, calls, reason).await;
+                }
+            }
+        }
+        if let (Some(reading), ExecutionOutcome::Executed, Some(transaction_hash)) =
+            (take_oracle_reading(&key), &outcome, last_transaction)
+        {
+            if let Err(e) = record_execution_price(pool, &key, transaction_hash, reading).await {
+                error!("Could not persist execution price of job {}: {:?}", key, e);
+            }
+        }
+        let decision = match outcome {
+            ExecutionOutcome::Executed => Decision::Executed,
+            _ => Decision::Dropped,
+        };
+        record_decision(pool, &table, &key, decision, &format!("{:?}", outcome)).await;
+        if let Err(e) = mark_job_finished(pool, &key, &outcome).await {
+            error!("Could not persist finished job {}: {:?}", key, e);
+        }
+        if table == "orders" {
+            webhooks
+                .notify(pool, &action, &outcome, last_transaction)
+                .await;
+        }
+        return;
+    }
+}
@@ -0,0 +1,395 @@
+use serde::Serialize;
+use sqlx::{Error, Pool, Postgres};
+use starknet::core::{
+    types::{Event, FieldElement, TransactionReceipt},
+    utils::get_selector_from_name,
+};
+
+use crate::pnl::book_keeper_pnl;
+
+// A struct representing how the execution fee of an action got split by its execution.
+// @keeper_fee: The part of the execution fee paid to the keeper.
+// @refund: The part of the execution fee refunded to the trader.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExecutionFeeSplit {
+    pub keeper_fee: u128,
+    pub refund: u128,
+}
+
+fn selector(name: &str) -> FieldElement {
+    get_selector_from_name(name).expect("Invalid event name")
+}
+
+// The events the handlers emit when settling an action, its key being their first data member. The
+// execution fee of an action gets paid after its settlement event.
+const SETTLEMENT_EVENTS: [&str; 7] = [
+    "OrderExecuted",
+    "OrderCancelled",
+    "OrderFrozen",
+    "DepositExecuted",
+    "DepositCancelled",
+    "WithdrawalExecuted",
+    "WithdrawalCancelled",
+];
+
+fn to_u128(felt: Option<&FieldElement>) -> u128 {
+    felt.and_then(|felt| u128::try_from(*felt).ok())
+        .unwrap_or_default()
+}
+
+// Reads the split from the KeeperExecutionFee and ExecutionFeeRefund events of the EventEmitter,
+// their data being the keeper or receiver followed by the amount. None without any.
+// @events: The events emitted by the execution transaction.
+// @keeper: The address of the keeper account.
+// @account: The account of the action.
+fn get_split_from_fee_events(
+    events: &[Event],
+    keeper: FieldElement,
+    account: FieldElement,
+) -> Option<ExecutionFeeSplit> {
+    let (keeper_fee_event, refund_event) = (
+        selector("KeeperExecutionFee"),
+        selector("ExecutionFeeRefund"),
+    );
+    let mut split = None;
+    for event in events {
+        match event.keys.first() {
+            Some(name) if *name == keeper_fee_event && event.data.first() == Some(&keeper) => {
+                split.get_or_insert(ExecutionFeeSplit::default()).keeper_fee +=
+                    to_u128(event.data.get(1));
+            }
+            Some(name) if *name == refund_event && event.data.first() == Some(&account) => {
+                split.get_or_insert(ExecutionFeeSplit::default()).refund +=
+                    to_u128(event.data.get(1));
+            }
+            _ => {}
+        }
+    }
+    split
+}
+
+// Returns the events emitted while settling an action, from its settlement event to the next one,
+// batches settling their actions one after the other. Every event when the transaction settled no
+// action, e.g. with handlers not emitting settlement events, none when it settled others only.
+// @events: The events emitted by the execution transaction.
+// @key: The key of the action.
+fn get_action_events(events: &[Event], key: FieldElement) -> &[Event] {
+    let settlements: Vec<FieldElement> = SETTLEMENT_EVENTS
+        .iter()
+        .map(|name| selector(name))
+        .collect();
+    let is_settlement = |event: &Event| {
+        event
+            .keys
+            .first()
+            .is_some_and(|name| settlements.contains(name))
+    };
+    let start = match events
+        .iter()
+        .position(|event| is_settlement(event) && event.data.first() == Some(&key))
+    {
+        Some(start) => start,
+        None if !events.iter().any(is_settlement) => return events,
+        None => return &[],
+    };
+    let end = events[start + 1..]
+        .iter()
+        .position(is_settlement)
+        .map_or(events.len(), |end| start + 1 + end);
+    &events[start..end]
+}
+
+// Returns the sender, recipient and amount of a fee token Transfer event, from its data or, for
+// tokens indexing the addresses, from its keys.
+fn get_transfer(event: &Event) -> Option<(FieldElement, FieldElement, u128)> {
+    match (event.keys.as_slice(), event.data.as_slice()) {
+        ([_, from, to], [low, ..]) => Some((*from, *to, to_u128(Some(low)))),
+        ([_], [from, to, low, ..]) => Some((*from, *to, to_u128(Some(low)))),
+        _ => None,
+    }
+}
+
+// Returns how the execution fee of an action got split, read from the EventEmitter fee events or,
+// for handlers without them, from the fee token transfers to the keeper and the trader. As the
+// trader may also receive the fee token as the output of the order, the transfers are a fallback.
+// Only the events emitted while settling the action are read, batches settling several.
+// @events: The events emitted by the execution transaction.
+// @fee_token: The address of the token execution fees get paid in.
+// @keeper: The address of the keeper account.
+// @account: The account of the action, execution fees being refunded to it.
+// @key: The key of the action.
+pub fn get_execution_fee_split(
+    events: &[Event],
+    fee_token: FieldElement,
+    keeper: FieldElement,
+    account: FieldElement,
+    key: FieldElement,
+) -> ExecutionFeeSplit {
+    let events = get_action_events(events, key);
+    if let Some(split) = get_split_from_fee_events(events, keeper, account) {
+        return split;
+    }
+    let transfer = selector("Transfer");
+    events
+        .iter()
+        .filter(|event| event.from_address == fee_token && event.keys.first() == Some(&transfer))
+        .filter_map(get_transfer)
+        .fold(
+            ExecutionFeeSplit::default(),
+            |mut split, (_, to, amount)| {
+                if to == keeper {
+                    split.keeper_fee += amount;
+                } else if to == account {
+                    split.refund += amount;
+                }
+                split
+            },
+        )
+}
+
+// Returns how the execution fee of an action got split by the execution transaction of a receipt.
+// @receipt: The receipt of the execution transaction.
+// @fee_token: The address of the token execution fees get paid in.
+// @keeper: The address of the keeper account.
+// @account: The account of the action.
+// @key: The key of the action.
+pub fn get_receipt_fee_split(
+    receipt: &TransactionReceipt,
+    fee_token: FieldElement,
+    keeper: FieldElement,
+    account: FieldElement,
+    key: FieldElement,
+) -> ExecutionFeeSplit {
+    match receipt {
+        TransactionReceipt::Invoke(receipt) => {
+            get_execution_fee_split(&receipt.events, fee_token, keeper, account, key)
+        }
+        _ => ExecutionFeeSplit::default(),
+    }
+}
+
+// Records that the keeper sent an execution transaction for an action, the fee of a transaction
+// batching several actions being shared between them.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @transaction_hash: The hash of the execution transaction.
+// @key: The key of the action.
+pub async fn record_transaction_action(
+    pool: &Pool<Postgres>,
+    transaction_hash: FieldElement,
+    key: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO keeper_transaction_actions (transaction_hash, key) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(format!("{:#x}", transaction_hash))
+    .bind(key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Records the fee economics of a settled action, the gas spent summing its share of the fees of
+// every transaction the keeper sent for it, reverted ones included, batches sharing their fee
+// evenly between their actions, and adds its keeper fee to the PnL of the settling transaction. The
+// transaction fees have to be recorded first, transactions recorded without their actions being the
+// action of their fee only.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @key: The key of the action.
+// @account: The account of the action, as indexed.
+// @transaction_hash: The hash of the transaction settling the action.
+// @execution_fee: The execution fee the trader paid.
+// @split: How the execution settling the action split it.
+pub async fn record_order_fee(
+    pool: &Pool<Postgres>,
+    table: &str,
+    key: &str,
+    account: &str,
+    transaction_hash: FieldElement,
+    execution_fee: u128,
+    split: ExecutionFeeSplit,
+) -> Result<(), Error> {
+    // The keeper fee gets earned by the settling transaction, in the same statement so a settlement
+    // recorded twice, e.g. by the jobs of a batch or after a restart, books it once.
+    sqlx::query(&book_keeper_pnl(
+        "recorded AS (
+             INSERT INTO keeper_order_fees
+                 (key, table_name, account, transaction_hash, execution_fee, keeper_fee, refund,
+                  gas_spent)
+             SELECT $1, $2, $3, $4, $5::NUMERIC, $6::NUMERIC, $7::NUMERIC,
+                 COALESCE(SUM(TRUNC(f.actual_fee / GREATEST(a.actions, 1))), 0)
+             FROM keeper_transaction_fees f
+             CROSS JOIN LATERAL (
+                 SELECT COUNT(*) AS actions, BOOL_OR(key = $1) AS sent
+                 FROM keeper_transaction_actions WHERE transaction_hash = f.transaction_hash
+             ) a
+             WHERE a.sent OR (a.actions = 0 AND f.key = $1)
+             ON CONFLICT (key) DO NOTHING
+             RETURNING keeper_fee
+         ), earned AS (
+             UPDATE keeper_transaction_fees f
+             SET execution_fee_earned = f.execution_fee_earned + recorded.keeper_fee
+             FROM recorded WHERE f.transaction_hash = $4
+             RETURNING f.created_at, recorded.keeper_fee
+         ), booked AS (
+             SELECT created_at, 0 AS transactions, 0::NUMERIC AS fees_paid,
+                 keeper_fee AS fees_earned
+             FROM earned
+         )",
+    ))
+    .bind(key)
+    .bind(table)
+    .bind(account)
+    .bind(format!("{:#x}", transaction_hash))
+    .bind(execution_fee.to_string())
+    .bind(split.keeper_fee.to_string())
+    .bind(split.refund.to_string())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// A struct representing the fee economics of an action the keeper settled.
+// Amounts are decimal strings of the fee token smallest unit, they do not fit in JSON numbers.
+// @execution_fee: The execution fee the trader paid.
+// @keeper_fee: The part of it paid to the keeper.
+// @refund: The part of it refunded to the trader.
+// @gas_spent: The fees the keeper paid for its transactions on the action.
+// @keeper_pnl: The keeper fee minus the gas spent.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct OrderFee {
+    pub key: String,
+    pub table_name: String,
+    pub account: String,
+    pub transaction_hash: String,
+    pub execution_fee: String,
+    pub keeper_fee: String,
+    pub refund: String,
+    pub gas_spent: String,
+    pub keeper_pnl: String,
+    pub created_at: String,
+}
+
+// Loads the fee economics of an action or of the actions of an account, most recent first.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action, as indexed.
+// @account: The account of the actions, as indexed.
+// @limit: The most actions loaded.
+pub async fn get_order_fees(
+    pool: &Pool<Postgres>,
+    key: Option<&str>,
+    account: Option<&str>,
+    limit: i64,
+) -> Result<Vec<OrderFee>, Error> {
+    sqlx::query_as::<_, OrderFee>(
+        "SELECT key, table_name, account, transaction_hash, execution_fee::TEXT AS execution_fee,
+             keeper_fee::TEXT AS keeper_fee, refund::TEXT AS refund, gas_spent::TEXT AS gas_spent,
+             (keeper_fee - gas_spent)::TEXT AS keeper_pnl, created_at::TEXT AS created_at
+         FROM keeper_order_fees
+         WHERE ($1::TEXT IS NULL OR key = $1) AND ($2::TEXT IS NULL OR account = $2)
+         ORDER BY created_at DESC LIMIT $3",
+    )
+    .bind(key)
+    .bind(account)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felt(value: u64) -> FieldElement {
+        FieldElement::from(value)
+    }
+
+    fn event(from: u64, keys: Vec<FieldElement>, data: &[u64]) -> Event {
+        Event {
+            from_address: felt(from),
+            keys,
+            data: data.iter().map(|value| felt(*value)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_execution_fee_split() {
+        let (fee_token, keeper, account) = (felt(0xe7), felt(0xa), felt(0xb));
+        let events = vec![
+            event(0x1, vec![selector("KeeperExecutionFee")], &[0xa, 300]),
+            event(0x1, vec![selector("ExecutionFeeRefund")], &[0xb, 700]),
+            event(0xe7, vec![selector("Transfer")], &[0x2, 0xa, 300, 0]),
+        ];
+        assert_eq!(
+            get_execution_fee_split(&events, fee_token, keeper, account, felt(0x1)),
+            ExecutionFeeSplit {
+                keeper_fee: 300,
+                refund: 700
+            }
+        );
+
+        // Without fee events, the fee token transfers to the keeper and the account are read.
+        let events = vec![
+            event(0xe7, vec![selector("Transfer")], &[0x2, 0xa, 300, 0]),
+            event(
+                0xe7,
+                vec![selector("Transfer"), felt(0x2), felt(0xb)],
+                &[700, 0],
+            ),
+            event(0xe8, vec![selector("Transfer")], &[0x2, 0xb, 50, 0]),
+        ];
+        assert_eq!(
+            get_execution_fee_split(&events, fee_token, keeper, account, felt(0x1)),
+            ExecutionFeeSplit {
+                keeper_fee: 300,
+                refund: 700
+            }
+        );
+        assert_eq!(
+            get_execution_fee_split(&[], fee_token, keeper, account, felt(0x1)),
+            ExecutionFeeSplit::default()
+        );
+    }
+
+    #[test]
+    fn test_batch_execution_fee_split() {
+        let (fee_token, keeper, account) = (felt(0xe7), felt(0xa), felt(0xb));
+        // Two orders of the account executed in a batch, then a deposit cancelled.
+        let events = vec![
+            event(0x1, vec![selector("OrderExecuted")], &[0x11, 0]),
+            event(0x1, vec![selector("KeeperExecutionFee")], &[0xa, 300]),
+            event(0x1, vec![selector("ExecutionFeeRefund")], &[0xb, 700]),
+            event(0x1, vec![selector("OrderExecuted")], &[0x12, 0]),
+            event(0x1, vec![selector("KeeperExecutionFee")], &[0xa, 400]),
+            event(0x1, vec![selector("ExecutionFeeRefund")], &[0xb, 100]),
+            event(0x1, vec![selector("DepositCancelled")], &[0x13, 0]),
+            event(0xe7, vec![selector("Transfer")], &[0x2, 0xa, 50, 0]),
+        ];
+        let split = |key| get_execution_fee_split(&events, fee_token, keeper, account, felt(key));
+        assert_eq!(
+            split(0x11),
+            ExecutionFeeSplit {
+                keeper_fee: 300,
+                refund: 700
+            }
+        );
+        assert_eq!(
+            split(0x12),
+            ExecutionFeeSplit {
+                keeper_fee: 400,
+                refund: 100
+            }
+        );
+        // Without fee events, the transfers following its settlement are read.
+        assert_eq!(
+            split(0x13),
+            ExecutionFeeSplit {
+                keeper_fee: 50,
+                refund: 0
+            }
+        );
+        // An action the transaction did not settle got none of its fees.
+        assert_eq!(split(0x14), ExecutionFeeSplit::default());
+    }
+}
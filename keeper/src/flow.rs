@@ -0,0 +1,162 @@
+use serde::Serialize;
+use sqlx::{error::Error, Pool, Postgres};
+
+use crate::history::scale_amount;
+
+// Decimals of the USD amounts of the protocol.
+const USD_DECIMALS: u32 = 30;
+// Most days the order flow gets broken down over.
+pub const MAX_FLOW_DAYS: u64 = 365;
+
+// Orders resting on the book until their trigger price, the others taking the market price.
+const MAKER_ORDER_TYPES: [&str; 4] = [
+    "LimitSwap",
+    "LimitIncrease",
+    "LimitDecrease",
+    "StopLossDecrease",
+];
+const TAKER_ORDER_TYPES: [&str; 3] = ["MarketSwap", "MarketIncrease", "MarketDecrease"];
+
+// An enum representing the time buckets the order flow is broken down into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowBucket {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl FlowBucket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlowBucket::Hourly => "hourly",
+            FlowBucket::Daily => "daily",
+            FlowBucket::Weekly => "weekly",
+        }
+    }
+
+    // The Postgres date_trunc field matching the bucket.
+    fn date_trunc_field(&self) -> &'static str {
+        match self {
+            FlowBucket::Hourly => "hour",
+            FlowBucket::Daily => "day",
+            FlowBucket::Weekly => "week",
+        }
+    }
+}
+
+impl std::str::FromStr for FlowBucket {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<FlowBucket, Self::Err> {
+        match input {
+            "hourly" => Ok(FlowBucket::Hourly),
+            "daily" => Ok(FlowBucket::Daily),
+            "weekly" => Ok(FlowBucket::Weekly),
+            _ => Err(()),
+        }
+    }
+}
+
+// A struct representing the orders a frontend created over a time bucket.
+// @bucket_start: The start of the bucket, in UTC.
+// @ui_fee_receiver: The UI fee receiver the frontend creates its orders with, as indexed.
+// @orders: The number of orders created.
+// @executed_orders: The number of those which got executed.
+// @volume_usd: The size of the executed orders, in USD.
+// @maker_orders: The number of limit and stop-loss orders created.
+// @taker_orders: The number of market orders created.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct FrontendFlow {
+    pub bucket_start: String,
+    pub ui_fee_receiver: String,
+    pub orders: i64,
+    pub executed_orders: i64,
+    pub volume_usd: String,
+    pub maker_orders: i64,
+    pub taker_orders: i64,
+}
+
+// Returns the share of the orders created which rest on the book, None without maker nor taker
+// orders.
+// @maker_orders: The number of limit and stop-loss orders.
+// @taker_orders: The number of market orders.
+pub fn get_maker_share(maker_orders: i64, taker_orders: i64) -> Option<f64> {
+    let total = maker_orders + taker_orders;
+    (total > 0).then(|| maker_orders as f64 / total as f64)
+}
+
+// A struct representing the order flow of a frontend over a time bucket, as reported.
+// @maker_share: The share of its maker and taker orders which are maker ones.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrontendFlowReport {
+    #[serde(flatten)]
+    pub flow: FrontendFlow,
+    pub maker_share: Option<f64>,
+}
+
+// Loads the order counts, volume and maker/taker mix per UI fee receiver and time bucket, so the
+// protocol team sees which frontends drive the flow. Orders are bucketed by creation time, most
+// recent buckets first and frontends by volume within a bucket.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @bucket: The time buckets of the breakdown.
+// @since_secs: The unix timestamp the orders are counted from.
+pub async fn get_frontend_flow(
+    pool: &Pool<Postgres>,
+    bucket: FlowBucket,
+    since_secs: u64,
+) -> Result<Vec<FrontendFlowReport>, Error> {
+    let mut flow = sqlx::query_as::<_, FrontendFlow>(
+        "SELECT to_char(date_trunc($1, to_timestamp(o.time_stamp::BIGINT) AT TIME ZONE 'UTC'),
+                 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS bucket_start,
+             COALESCE(felt_out(o.ui_fee_receiver), '') AS ui_fee_receiver,
+             COUNT(*) AS orders,
+             COUNT(*) FILTER (WHERE e.key IS NOT NULL) AS executed_orders,
+             COALESCE(SUM(o.size_delta_usd) FILTER (WHERE e.key IS NOT NULL), 0)::TEXT
+             AS volume_usd,
+             COUNT(*) FILTER (WHERE o.order_type = ANY($3)) AS maker_orders,
+             COUNT(*) FILTER (WHERE o.order_type = ANY($4)) AS taker_orders
+         FROM orders o
+         LEFT JOIN (SELECT DISTINCT key FROM order_executed) e ON e.key = o.key
+         WHERE o.time_stamp ~ '^[0-9]+$' AND o.time_stamp::BIGINT >= $2
+         GROUP BY 1, 2
+         ORDER BY 1 DESC, COALESCE(SUM(o.size_delta_usd) FILTER (WHERE e.key IS NOT NULL), 0) DESC,
+             2",
+    )
+    .bind(bucket.date_trunc_field())
+    .bind(since_secs as i64)
+    .bind(MAKER_ORDER_TYPES.to_vec())
+    .bind(TAKER_ORDER_TYPES.to_vec())
+    .fetch_all(pool)
+    .await?;
+    for frontend in &mut flow {
+        frontend.volume_usd = scale_amount(&frontend.volume_usd, USD_DECIMALS);
+    }
+    Ok(flow
+        .into_iter()
+        .map(|flow| FrontendFlowReport {
+            maker_share: get_maker_share(flow.maker_orders, flow.taker_orders),
+            flow,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_bucket_round_trip() {
+        for bucket in [FlowBucket::Hourly, FlowBucket::Daily, FlowBucket::Weekly] {
+            assert_eq!(bucket.as_str().parse::<FlowBucket>(), Ok(bucket));
+        }
+        assert!("monthly".parse::<FlowBucket>().is_err());
+    }
+
+    #[test]
+    fn test_maker_share() {
+        assert_eq!(get_maker_share(1, 3), Some(0.25));
+        assert_eq!(get_maker_share(2, 0), Some(1.0));
+        // Liquidations and swaps of other kinds are neither.
+        assert_eq!(get_maker_share(0, 0), None);
+    }
+}
@@ -0,0 +1,489 @@
+use std::{collections::HashMap, net::SocketAddr, pin::Pin, sync::OnceLock, time::Duration};
+
+use actix_web::http::Method;
+use log::{error, info};
+use satoru_client::grpc::{
+    GetOrderRequest, GetPositionsRequest, Keeper, KeeperServer, Order, Position, Positions, Price,
+    StreamOrdersRequest, StreamPricesRequest, API_KEY_METADATA,
+};
+use sqlx::{postgres::PgListener, Pool, Postgres};
+use starknet::core::types::FieldElement;
+use tokio::{sync::broadcast, task, time::sleep};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    api::auth::{get_api_key, ApiKey, RateLimiter},
+    bench::load_order,
+    config,
+    error::KeeperError,
+    positions::{self, get_positions},
+    trade::price::utils::PriceInfo,
+    types::{Payload, PositionPayload, SatoruAction},
+};
+
+// How many messages a stream buffers for its slowest client, which misses the older ones.
+const STREAM_CAPACITY: usize = 1024;
+
+// The prices fetched by the keeper, published whether the price log records them or not.
+static PRICES: OnceLock<broadcast::Sender<Price>> = OnceLock::new();
+
+type MessageStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+fn price_sender() -> &'static broadcast::Sender<Price> {
+    PRICES.get_or_init(|| broadcast::channel(STREAM_CAPACITY).0)
+}
+
+// Publishes a price fetched by the keeper to the price streams.
+// @price_info: The price, as Pragma answers with it.
+pub fn publish_price(price_info: &PriceInfo) {
+    match to_price(price_info) {
+        // Sending only fails without any stream open.
+        Some(price) => {
+            let _ = price_sender().send(price);
+        }
+        None => error!("Could not stream invalid price {}", price_info.price),
+    }
+}
+
+// Converts a price from the hex Pragma answers with to the decimal string of the protobuf message.
+fn to_price(price_info: &PriceInfo) -> Option<Price> {
+    let price = u128::from_str_radix(price_info.price.trim_start_matches("0x"), 16).ok()?;
+    Some(Price {
+        pair_id: price_info.pair_id.clone(),
+        price: price.to_string(),
+        decimals: price_info.decimals,
+        num_sources_aggregated: price_info.num_sources_aggregated,
+        timestamp: price_info.timestamp,
+    })
+}
+
+// Whether a price is of one of the tokens, named as the base of its pair, every price when empty.
+fn is_price_of(price: &Price, tokens: &[String]) -> bool {
+    let base = price.pair_id.split('/').next().unwrap_or_default();
+    tokens.is_empty() || tokens.iter().any(|token| token.eq_ignore_ascii_case(base))
+}
+
+fn amount(amount: Option<u128>) -> String {
+    amount.map(|amount| amount.to_string()).unwrap_or_default()
+}
+
+// Converts an indexed order to its protobuf message, u128 amounts becoming decimal strings.
+// @action: The order, as indexed.
+// @status: The status of the order, see the order_lifecycle view.
+fn to_order(action: SatoruAction, status: String) -> Order {
+    Order {
+        block_number: action.block_number,
+        transaction_hash: action.transaction_hash,
+        key: action.key,
+        account: action.account,
+        receiver: action.receiver,
+        callback_contract: action.callback_contract,
+        ui_fee_receiver: action.ui_fee_receiver,
+        market: action.market,
+        execution_fee: action.execution_fee.to_string(),
+        callback_gas_limit: action.callback_gas_limit.to_string(),
+        updated_at_block: action.updated_at_block,
+        order_type: action.order_type.unwrap_or_default(),
+        decrease_position_swap_type: action.decrease_position_swap_type.unwrap_or_default(),
+        initial_collateral_token: action.initial_collateral_token.unwrap_or_default(),
+        swap_path: action
+            .swap_path
+            .map(|path| {
+                path.split(',')
+                    .filter(|market| !market.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        size_delta_usd: amount(action.size_delta_usd),
+        initial_collateral_delta_amount: amount(action.initial_collateral_delta_amount),
+        trigger_price: amount(action.trigger_price),
+        acceptable_price: amount(action.acceptable_price),
+        min_output_amount: amount(action.min_output_amount),
+        is_long: action.is_long.unwrap_or_default(),
+        is_frozen: action.is_frozen.unwrap_or_default(),
+        status,
+    }
+}
+
+fn to_position(position: positions::Position) -> Position {
+    Position {
+        account: position.account,
+        market: position.market,
+        collateral_token: position.collateral_token,
+        is_long: position.is_long,
+        size_in_usd: position.size_in_usd,
+        cumulative_borrowing_fees_usd: position.cumulative_borrowing_fees_usd,
+        pending_borrowing_fees_usd: position.pending_borrowing_fees_usd,
+    }
+}
+
+// Compares addresses as felts, the indexer storing them without 0x prefix.
+fn same_felt(a: &str, b: &str) -> bool {
+    match (FieldElement::from_hex_be(a), FieldElement::from_hex_be(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// Returns the positions to stream after a position change of an account on a market: the open
+// ones, and the previously open ones now closed with a zero size.
+// @previous: The open positions of the account on the market before the change.
+// @current: The open positions of the account on the market after the change.
+fn changed_positions(previous: Vec<Position>, current: &[Position]) -> Vec<Position> {
+    let closed = previous.into_iter().filter(|position| {
+        !current.iter().any(|open| {
+            same_felt(&open.collateral_token, &position.collateral_token)
+                && open.is_long == position.is_long
+        })
+    });
+    current
+        .iter()
+        .cloned()
+        .chain(closed.map(|position| Position {
+            size_in_usd: "0".to_owned(),
+            pending_borrowing_fees_usd: "0".to_owned(),
+            ..position
+        }))
+        .collect()
+}
+
+// Publishes the orders and positions notified by the indexer to the streams, until the
+// notifications fail.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @orders: The sender of the order streams.
+// @positions: The sender of the position streams.
+async fn publish_changes(
+    pool: &Pool<Postgres>,
+    orders: &broadcast::Sender<Order>,
+    positions: &broadcast::Sender<Position>,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener
+        .listen_all(["orders_update", "positions_update"])
+        .await?;
+    // The open positions by account and market, to stream their closing.
+    let mut open: HashMap<(String, String), Vec<Position>> = HashMap::new();
+    for position in get_positions(pool, None).await? {
+        open.entry((position.account.clone(), position.market.clone()))
+            .or_default()
+            .push(to_position(position));
+    }
+    loop {
+        let notification = listener.recv().await?;
+        if notification.channel() == "orders_update" {
+            match serde_json::from_str::<Payload>(notification.payload()) {
+                Ok(payload) if payload.table == "orders" => {
+                    let status = match payload.row_data.is_frozen {
+                        Some(true) => "frozen",
+                        _ => "pending",
+                    };
+                    let _ = orders.send(to_order(payload.row_data, status.to_owned()));
+                }
+                Ok(_) => {}
+                Err(e) => error!("Could not decode order notification: {}", e),
+            }
+            continue;
+        }
+        let event = match serde_json::from_str::<PositionPayload>(notification.payload()) {
+            Ok(payload) => payload.row_data,
+            Err(e) => {
+                error!("Could not decode position notification: {}", e);
+                continue;
+            }
+        };
+        let current: Vec<Position> = get_positions(pool, Some(&event.account))
+            .await?
+            .into_iter()
+            .filter(|position| same_felt(&position.market, &event.market))
+            .map(to_position)
+            .collect();
+        let previous = open
+            .remove(&(event.account.clone(), event.market.clone()))
+            .unwrap_or_default();
+        for position in changed_positions(previous, &current) {
+            let _ = positions.send(position);
+        }
+        if !current.is_empty() {
+            open.insert((event.account, event.market), current);
+        }
+    }
+}
+
+// The gRPC service of the keeper, authenticating requests as the admin API does.
+// @pool: A connection pool for PostgreSQL.
+// @auth_enabled: Whether requests must carry an API key.
+// @rate_limiter: The requests served per API key and minute, apart from the admin API ones.
+// @orders: The sender of the order streams.
+// @positions: The sender of the position streams.
+pub struct KeeperService {
+    pool: Pool<Postgres>,
+    auth_enabled: bool,
+    rate_limiter: RateLimiter,
+    orders: broadcast::Sender<Order>,
+    positions: broadcast::Sender<Position>,
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    error!("gRPC request failed: {}", e);
+    Status::internal("database error")
+}
+
+// Subscribes to a stream sender, the messages a lagging client missed being skipped.
+fn subscribe<T: Clone + Send + 'static>(
+    sender: &broadcast::Sender<T>,
+    filter: impl Fn(&T) -> bool + Send + 'static,
+) -> impl Stream<Item = Result<T, Status>> + Send {
+    BroadcastStream::new(sender.subscribe()).filter_map(move |message| match message {
+        Ok(message) if filter(&message) => Some(Ok(message)),
+        _ => None,
+    })
+}
+
+impl KeeperService {
+    // Authenticates a request by its API key, counting it against the key rate limit. None when
+    // authentication is disabled.
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<Option<ApiKey>, Status> {
+        if !self.auth_enabled {
+            return Ok(None);
+        }
+        let key = request
+            .metadata()
+            .get(API_KEY_METADATA)
+            .and_then(|key| key.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Missing API key"))?;
+        let api_key = get_api_key(&self.pool, key)
+            .await
+            .map_err(internal)?
+            .ok_or_else(|| Status::unauthenticated("Invalid API key"))?;
+        if !self.rate_limiter.allow(&api_key) {
+            return Err(Status::resource_exhausted("Rate limit exceeded"));
+        }
+        Ok(Some(api_key))
+    }
+
+    // Checks an API key can read a route of the admin API the request mirrors.
+    // @api_key: The API key of the request, None when authentication is disabled.
+    // @path: The admin API route.
+    // @account: The account the request is filtered on, if any.
+    #[allow(clippy::result_large_err)]
+    fn authorize(
+        api_key: Option<&ApiKey>,
+        path: &str,
+        account: Option<&str>,
+    ) -> Result<(), Status> {
+        match api_key {
+            Some(api_key) if !api_key.allows(&Method::GET, path, account) => Err(
+                Status::permission_denied(format!("API key {} cannot read {}", api_key.name, path)),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Keeper for KeeperService {
+    type StreamOrdersStream = MessageStream<Order>;
+    type StreamPositionsStream = MessageStream<Position>;
+    type StreamPricesStream = MessageStream<Price>;
+
+    async fn get_order(
+        &self,
+        request: Request<GetOrderRequest>,
+    ) -> Result<Response<Order>, Status> {
+        let api_key = self.authenticate(&request).await?;
+        let key = FieldElement::from_hex_be(&request.get_ref().key)
+            .map(|key| format!("{:#x}", key))
+            .map_err(|_| Status::invalid_argument("Invalid order key"))?;
+        let action = load_order(&self.pool, &key)
+            .await
+            .map_err(internal)?
+            .ok_or_else(|| Status::not_found(format!("Order {} not indexed", key)))?;
+        Self::authorize(api_key.as_ref(), "/orders", Some(&action.account))?;
+        let (status,): (String,) =
+            sqlx::query_as("SELECT status FROM order_lifecycle WHERE key = $1")
+                .bind(&key)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(internal)?;
+        Ok(Response::new(to_order(action, status)))
+    }
+
+    async fn get_positions(
+        &self,
+        request: Request<GetPositionsRequest>,
+    ) -> Result<Response<Positions>, Status> {
+        let api_key = self.authenticate(&request).await?;
+        let account = request.get_ref().account.as_deref();
+        Self::authorize(api_key.as_ref(), "/positions", account)?;
+        let positions = get_positions(&self.pool, account)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(to_position)
+            .collect();
+        Ok(Response::new(Positions { positions }))
+    }
+
+    async fn stream_orders(
+        &self,
+        request: Request<StreamOrdersRequest>,
+    ) -> Result<Response<Self::StreamOrdersStream>, Status> {
+        let api_key = self.authenticate(&request).await?;
+        Self::authorize(api_key.as_ref(), "/orders", None)?;
+        let market = request.into_inner().market;
+        let orders = subscribe(&self.orders, move |order: &Order| {
+            market
+                .as_deref()
+                .is_none_or(|market| same_felt(market, &order.market))
+        });
+        Ok(Response::new(Box::pin(orders)))
+    }
+
+    // Streams the open positions first, then their changes.
+    async fn stream_positions(
+        &self,
+        request: Request<GetPositionsRequest>,
+    ) -> Result<Response<Self::StreamPositionsStream>, Status> {
+        let api_key = self.authenticate(&request).await?;
+        let account = request.into_inner().account;
+        Self::authorize(api_key.as_ref(), "/positions", account.as_deref())?;
+        // Subscribing first, not to miss the changes made while loading the open positions.
+        let filter = account.clone();
+        let changes = subscribe(&self.positions, move |position: &Position| {
+            filter
+                .as_deref()
+                .is_none_or(|account| same_felt(account, &position.account))
+        });
+        let open: Vec<Position> = get_positions(&self.pool, account.as_deref())
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(to_position)
+            .collect();
+        Ok(Response::new(Box::pin(
+            tokio_stream::iter(open.into_iter().map(Ok)).chain(changes),
+        )))
+    }
+
+    async fn stream_prices(
+        &self,
+        request: Request<StreamPricesRequest>,
+    ) -> Result<Response<Self::StreamPricesStream>, Status> {
+        let api_key = self.authenticate(&request).await?;
+        Self::authorize(api_key.as_ref(), "/prices", None)?;
+        let tokens = request.into_inner().tokens;
+        let prices = subscribe(price_sender(), move |price: &Price| {
+            is_price_of(price, &tokens)
+        });
+        Ok(Response::new(Box::pin(prices)))
+    }
+}
+
+// Serves the gRPC service, streaming the orders and positions the indexer notifies and the prices
+// the keeper fetches.
+// @pool: A connection pool for PostgreSQL.
+// @address: The address to listen on.
+pub async fn serve(pool: Pool<Postgres>, address: &str) -> Result<(), KeeperError> {
+    let address: SocketAddr = address.parse().map_err(|e| {
+        KeeperError::ConfigError(vec![format!("Invalid GRPC_LISTEN_ADDR {}: {}", address, e)])
+    })?;
+    let orders = broadcast::channel(STREAM_CAPACITY).0;
+    let positions = broadcast::channel(STREAM_CAPACITY).0;
+    let (listener_pool, listener_orders, listener_positions) =
+        (pool.clone(), orders.clone(), positions.clone());
+    task::spawn(async move {
+        loop {
+            if let Err(e) =
+                publish_changes(&listener_pool, &listener_orders, &listener_positions).await
+            {
+                error!("gRPC streams stopped, restarting them: {}", e);
+            }
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+    let service = KeeperService {
+        pool,
+        auth_enabled: config::get_api_auth_enabled(),
+        rate_limiter: RateLimiter::default(),
+        orders,
+        positions,
+    };
+    info!("Serving gRPC on {}", address);
+    Server::builder()
+        .add_service(KeeperServer::new(service))
+        .serve(address)
+        .await
+        .map_err(|e| KeeperError::GrpcError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(collateral_token: &str, is_long: bool, size_in_usd: &str) -> Position {
+        Position {
+            account: "a1".to_owned(),
+            market: "m1".to_owned(),
+            collateral_token: collateral_token.to_owned(),
+            is_long,
+            size_in_usd: size_in_usd.to_owned(),
+            cumulative_borrowing_fees_usd: "5".to_owned(),
+            pending_borrowing_fees_usd: "2".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_to_order() {
+        let action: SatoruAction = serde_json::from_str(
+            r#"{"block_number": 7, "transaction_hash": "0x7", "key": "0x1", "account": "a1",
+                "receiver": "a1", "callback_contract": "0x0", "ui_fee_receiver": "0x0",
+                "market": "m1", "execution_fee": 340282366920938463463374607431768211455,
+                "callback_gas_limit": 0, "updated_at_block": 7, "order_type": "MarketIncrease",
+                "swap_path": "m2,m3", "size_delta_usd": 1000, "is_long": true}"#,
+        )
+        .unwrap();
+        let order = to_order(action, "pending".to_owned());
+        assert_eq!(order.execution_fee, u128::MAX.to_string());
+        assert_eq!(order.swap_path, vec!["m2", "m3"]);
+        assert_eq!(order.size_delta_usd, "1000");
+        assert_eq!(order.trigger_price, "");
+        assert!(order.is_long && !order.is_frozen);
+        assert_eq!(order.status, "pending");
+    }
+
+    #[test]
+    fn test_prices() {
+        let price = to_price(&PriceInfo {
+            decimals: 8,
+            num_sources_aggregated: 3,
+            pair_id: "ETH/USD".to_owned(),
+            price: "0x5f5e100".to_owned(),
+            timestamp: 1_700_000_000_000,
+        })
+        .unwrap();
+        assert_eq!(price.price, "100000000");
+        assert!(is_price_of(&price, &[]));
+        assert!(is_price_of(&price, &["btc".to_owned(), "eth".to_owned()]));
+        assert!(!is_price_of(&price, &["BTC".to_owned()]));
+    }
+
+    #[test]
+    fn test_changed_positions() {
+        // The short got closed, the long increased.
+        let previous = vec![position("0x1", true, "10"), position("1", false, "20")];
+        let current = vec![position("0x01", true, "30")];
+        assert_eq!(
+            changed_positions(previous, &current),
+            vec![
+                position("0x01", true, "30"),
+                Position {
+                    pending_borrowing_fees_usd: "0".to_owned(),
+                    ..position("1", false, "0")
+                },
+            ]
+        );
+        assert!(changed_positions(vec![], &[]).is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use crate::logging::format_timestamp;
+
+// Decimals of the USD amounts and prices of the protocol.
+const USD_DECIMALS: u32 = 30;
+// Decimals of the execution fees, paid in ETH.
+const FEE_DECIMALS: u32 = 18;
+
+// The columns of the trade history CSV.
+pub const HISTORY_CSV_HEADER: &str = "date,type,action,market,collateral_token,side,size_usd,acceptable_price_usd,execution_fee_eth,realized_pnl,pnl_currency,transaction_hash";
+
+// A struct representing an entry of the trade and funding history of an account, amounts being
+// the raw indexed ones.
+// @kind: The kind of entry (trade, funding, borrowing).
+// @action: The order type of a trade, or whether the fee got paid or received.
+// @size_usd: The size of the trade, or of the position the fee applied to, with 30 decimals.
+// @price: The acceptable price of a trade, the indexer not decoding execution prices.
+// @price_decimals: The decimals of the price, 30 minus the index token ones, None when unknown.
+// @execution_fee: The execution fee of a trade, in wei.
+// @amount: The fee received, negative when paid, in the pnl currency.
+// @amount_decimals: The decimals of the amount, None when the collateral token is unknown.
+// @currency: The currency of the amount, USD or the collateral token.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct HistoryEntry {
+    pub block_number: i64,
+    pub time_stamp: Option<String>,
+    pub transaction_hash: String,
+    pub kind: String,
+    pub action: Option<String>,
+    pub market: Option<String>,
+    pub collateral_token: Option<String>,
+    pub is_long: Option<bool>,
+    pub size_usd: Option<String>,
+    pub price: Option<String>,
+    pub price_decimals: Option<i32>,
+    pub execution_fee: Option<String>,
+    pub amount: Option<String>,
+    pub amount_decimals: Option<i32>,
+    pub currency: Option<String>,
+}
+
+// Scales a raw integer amount by its decimals, exactly, e.g. 1500 with 3 decimals being 1.5.
+// Amounts that are not integers, e.g. already scaled, are returned as they are.
+// @raw: The raw amount, possibly negative.
+// @decimals: The decimals of the amount.
+pub fn scale_amount(raw: &str, decimals: u32) -> String {
+    let (sign, digits) = match raw.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", raw),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return raw.to_owned();
+    }
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let integer = match integer.trim_start_matches('0') {
+        "" => "0",
+        integer => integer,
+    };
+    let fraction = fraction.trim_end_matches('0');
+    match (fraction.is_empty(), integer == "0" && fraction.is_empty()) {
+        (_, true) => "0".to_owned(),
+        (true, false) => format!("{}{}", sign, integer),
+        (false, false) => format!("{}{}.{}", sign, integer, fraction),
+    }
+}
+
+// Quotes a CSV field when it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
+
+impl HistoryEntry {
+    // Formats the entry as a CSV line of HISTORY_CSV_HEADER, amounts scaled by their decimals and
+    // the date in RFC 3339 UTC, the raw amount being kept when its decimals are unknown.
+    pub fn to_csv(&self) -> String {
+        let scale = |amount: &Option<String>, decimals: Option<u32>| match (amount, decimals) {
+            (Some(amount), Some(decimals)) => scale_amount(amount, decimals),
+            (Some(amount), None) => amount.clone(),
+            (None, _) => String::new(),
+        };
+        let decimals = |decimals: Option<i32>| decimals.and_then(|d| u32::try_from(d).ok());
+        let date = self
+            .time_stamp
+            .as_deref()
+            .and_then(|time_stamp| time_stamp.parse::<u128>().ok())
+            .map(|secs| format_timestamp(secs * 1000))
+            .unwrap_or_default();
+        let side = match self.is_long {
+            Some(true) => "long",
+            Some(false) => "short",
+            None => "",
+        };
+        [
+            date,
+            self.kind.clone(),
+            self.action.clone().unwrap_or_default(),
+            self.market.clone().unwrap_or_default(),
+            self.collateral_token.clone().unwrap_or_default(),
+            side.to_owned(),
+            scale(&self.size_usd, Some(USD_DECIMALS)),
+            scale(&self.price, decimals(self.price_decimals)),
+            scale(&self.execution_fee, Some(FEE_DECIMALS)),
+            scale(&self.amount, decimals(self.amount_decimals)),
+            self.currency.clone().unwrap_or_default(),
+            self.transaction_hash.clone(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+// Formats the history of an account as a CSV file, with its header.
+// @entries: The history entries, oldest first.
+pub fn to_history_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from(HISTORY_CSV_HEADER);
+    csv.push('\n');
+    for entry in entries {
+        csv.push_str(&entry.to_csv());
+        csv.push('\n');
+    }
+    csv
+}
+
+// Loads the complete trade and funding history of an account from the indexed tables, oldest
+// first: its executed orders, the funding its positions paid or received and the borrowing fees
+// they accrued. Trades have no realized PnL, the indexer not decoding execution prices.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as indexed.
+pub async fn get_account_history(
+    pool: &Pool<Postgres>,
+    account: &str,
+) -> Result<Vec<HistoryEntry>, Error> {
+    // Token addresses are compared as felts, whatever their prefix and padding.
+    sqlx::query_as::<_, HistoryEntry>(
+        "WITH tokens_by_felt AS (
+             SELECT ltrim(regexp_replace(lower(address), '^0x', ''), '0') AS felt, symbol, decimals
+             FROM tokens
+         ),
+         markets_by_felt AS (
+             SELECT ltrim(felt_out(market_token), '0') AS felt, ltrim(felt_out(index_token), '0')
+             AS index_token FROM market_created
+         )
+         SELECT oe.block_number, oe.time_stamp, oe.transaction_hash, 'trade' AS kind,
+             o.order_type AS action, felt_out(o.market) AS market,
+             o.initial_collateral_token AS collateral_token, o.is_long,
+             o.size_delta_usd::TEXT AS size_usd, o.acceptable_price::TEXT AS price,
+             30 - t.decimals AS price_decimals, o.execution_fee::TEXT AS execution_fee,
+             NULL::TEXT AS amount, NULL::INTEGER AS amount_decimals, NULL::TEXT AS currency
+         FROM orders o
+         JOIN order_executed oe ON oe.key = o.key
+         LEFT JOIN markets_by_felt m ON m.felt = ltrim(felt_out(o.market), '0')
+         LEFT JOIN tokens_by_felt t ON t.felt = m.index_token
+         WHERE o.account = felt_in($1)
+         UNION ALL
+         SELECT f.block_number, f.time_stamp, f.transaction_hash, 'funding', f.direction, f.market,
+             f.collateral_token, f.is_long, f.size_in_usd::TEXT, NULL, NULL, NULL,
+             (CASE WHEN f.direction = 'paid' THEN -f.amount ELSE f.amount END)::TEXT, t.decimals,
+             COALESCE(t.symbol, f.collateral_token)
+         FROM funding_payments f
+         LEFT JOIN tokens_by_felt t
+             ON t.felt = ltrim(regexp_replace(lower(f.collateral_token), '^0x', ''), '0')
+         WHERE f.account = $1
+         UNION ALL
+         SELECT a.block_number, a.time_stamp, a.transaction_hash, 'borrowing', 'paid', a.market,
+             a.collateral_token, a.is_long, a.size_in_usd::TEXT, NULL, NULL, NULL,
+             (-a.amount_usd)::TEXT, 30, 'USD'
+         FROM borrowing_fee_accruals a
+         WHERE a.account = $1
+         ORDER BY block_number, transaction_hash, kind",
+    )
+    .bind(account)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_amount() {
+        assert_eq!(scale_amount("1500", 3), "1.5");
+        assert_eq!(scale_amount("15", 3), "0.015");
+        assert_eq!(scale_amount("-15", 3), "-0.015");
+        assert_eq!(scale_amount("2000", 3), "2");
+        assert_eq!(scale_amount("0", 30), "0");
+        assert_eq!(scale_amount("-0", 3), "0");
+        assert_eq!(scale_amount("42", 0), "42");
+        assert_eq!(scale_amount("1.5", 3), "1.5");
+        assert_eq!(
+            scale_amount("1000000000000000000000000000000000", 30),
+            "1000"
+        );
+    }
+
+    #[test]
+    fn test_history_csv() {
+        let trade = HistoryEntry {
+            block_number: 10,
+            time_stamp: Some("1704067200".to_owned()),
+            transaction_hash: "0a".to_owned(),
+            kind: "trade".to_owned(),
+            action: Some("MarketIncrease".to_owned()),
+            market: Some("0b".to_owned()),
+            collateral_token: Some("0c".to_owned()),
+            is_long: Some(true),
+            size_usd: Some(format!("1000{}", "0".repeat(30))),
+            price: Some("3500000000000000".to_owned()),
+            price_decimals: Some(12),
+            execution_fee: Some("1000000000000000".to_owned()),
+            amount: None,
+            amount_decimals: None,
+            currency: None,
+        };
+        let funding = HistoryEntry {
+            kind: "funding".to_owned(),
+            action: Some("paid".to_owned()),
+            price: None,
+            price_decimals: None,
+            execution_fee: None,
+            amount: Some("-25".to_owned()),
+            // The collateral token is unknown, the raw amount is kept.
+            amount_decimals: None,
+            currency: Some("USDC, bridged".to_owned()),
+            ..trade.clone()
+        };
+        assert_eq!(
+            to_history_csv(&[trade, funding]),
+            format!(
+                "{}\n{}\n{}\n",
+                HISTORY_CSV_HEADER,
+                "2024-01-01T00:00:00.000Z,trade,MarketIncrease,0b,0c,long,1000,3500,0.001,,,0a",
+                "2024-01-01T00:00:00.000Z,funding,paid,0b,0c,long,1000,,,-25,\"USDC, bridged\",0a"
+            )
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use cainome::cairo_serde::ContractAddress;
+use starknet::core::types::FieldElement;
+use starknet_crypto::poseidon_hash_many;
+
+// Computes the key of a position, as position_utils::get_position_key does.
+// @account: The account of the position.
+// @market: The market of the position.
+// @collateral_token: The collateral token of the position.
+// @is_long: Whether the position is long.
+pub fn position_key(
+    account: ContractAddress,
+    market: ContractAddress,
+    collateral_token: ContractAddress,
+    is_long: bool,
+) -> FieldElement {
+    poseidon_hash_many(&[
+        account.into(),
+        market.into(),
+        collateral_token.into(),
+        FieldElement::from(is_long as u8),
+    ])
+}
+
+// Computes the key of an order, deposit or withdrawal from the DataStore nonce it got created
+// with, as nonce_utils::compute_key does.
+// @data_store: The DataStore address.
+// @nonce: The nonce of the DataStore after it got incremented by the creation.
+pub fn action_key(data_store: ContractAddress, nonce: FieldElement) -> FieldElement {
+    poseidon_hash_many(&[data_store.into(), nonce])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(value: u64) -> ContractAddress {
+        ContractAddress::from(FieldElement::from(value))
+    }
+
+    fn felt(hex: &str) -> FieldElement {
+        FieldElement::from_hex_be(hex).unwrap()
+    }
+
+    // The reference keys below got computed with the Poseidon of starknet-types-core, an
+    // implementation independent of starknet-crypto, for a DataStore, an account and an ETH/USD
+    // market. They are not keys read from a Satoru deployment: an order key from the OrderCreated
+    // event of a deployment transaction, and a position key from its PositionIncrease event, still
+    // need to be added here with the hash of their transaction.
+    const DATA_STORE: &str = "0x0549539f6d6a8a8ff1d1b6dc0da31c3c85e0ea05ebbcaa2d6ea6b6c1933c8a92";
+    const ACCOUNT: &str = "0x04eaf2b6750a3c4a2c6a7e4d7f5de6d8b7d4d0a3f7fa6e9b1b2b4132c3a0d6ee";
+    const MARKET: &str = "0x0122cd6989d2429f580a0bff5e70cdb84b2bff4f8d19cee6b30a15d08c447e85";
+    const ETH: &str = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+
+    #[test]
+    fn test_position_key() {
+        let (account, market, eth) = (
+            ContractAddress::from(felt(ACCOUNT)),
+            ContractAddress::from(felt(MARKET)),
+            ContractAddress::from(felt(ETH)),
+        );
+        assert_eq!(
+            position_key(account, market, eth, true),
+            felt("0x3b86cb6692262ce1fafd0b470c19f008bcddd2a471d5d0af3e55b25de91f9a4")
+        );
+        assert_eq!(
+            position_key(account, market, eth, false),
+            felt("0x3288ac39e160b09b5e4dd0e39f55a2d194c4a6bca497a57c3ee9bfeb57accb9")
+        );
+
+        let key =
+            |account, is_long| position_key(address(account), address(2), address(3), is_long);
+        assert_ne!(key(1, true), key(4, true));
+        // The components are hashed in the order of the contract, not as a set.
+        assert_ne!(
+            position_key(address(1), address(2), address(3), true),
+            position_key(address(1), address(3), address(2), true)
+        );
+    }
+
+    #[test]
+    fn test_action_key() {
+        let data_store = ContractAddress::from(felt(DATA_STORE));
+        // The first and the 42nd action created through the DataStore.
+        assert_eq!(
+            action_key(data_store, FieldElement::ONE),
+            felt("0x32c1812ead4da33c33da0848327cb6a3bc609d674306e8f217a5339d872872e")
+        );
+        assert_eq!(
+            action_key(data_store, FieldElement::from(42u8)),
+            felt("0xcbbebe6b5fce3f9fa5f38bbbf5a717ffe1222faa7ae3a66b3a89cb4c560e15")
+        );
+        // poseidon_hash_many([1, 2]).
+        assert_eq!(
+            action_key(address(1), FieldElement::TWO),
+            felt("0x371cb6995ea5e7effcd2e174de264b5b407027a75a231a70c2c8d196107f0e7")
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use serde::Serialize;
+use sqlx::{Error, Pool, Postgres};
+use starknet::core::types::FieldElement;
+use tokio::time::sleep;
+
+use crate::{
+    clock::get_system_timestamp,
+    config,
+    contracts::Contracts,
+    error::KeeperError,
+    logging::format_timestamp,
+    upgrades::{load_pending_upgrades, ContractUpgrade},
+};
+
+// A struct representing a maintenance window, e.g. for a coordinated contract upgrade, during which
+// no transaction gets sent. Jobs in flight keep waiting for their receipts and the others resume once
+// it ends, the indexer ingesting all along.
+// @id: The id of the window in the keeper_maintenance_windows table, None for configured windows.
+// @starts_at: When the window starts, as a unix timestamp.
+// @ends_at: When the window ends, as a unix timestamp, excluded.
+// @reason: Why the keeper is under maintenance.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct MaintenanceWindow {
+    pub id: Option<i64>,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub reason: String,
+}
+
+impl MaintenanceWindow {
+    // Parses a configured window formatted as start-end, unix timestamps.
+    // @entry: The window.
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let (starts_at, ends_at) = entry
+            .split_once('-')
+            .and_then(|(start, end)| {
+                Some((
+                    start.trim().parse::<i64>().ok()?,
+                    end.trim().parse::<i64>().ok()?,
+                ))
+            })
+            .ok_or_else(|| format!("maintenance window {} must be start-end", entry))?;
+        if starts_at >= ends_at {
+            return Err(format!(
+                "maintenance window {} must end after it starts",
+                entry
+            ));
+        }
+        Ok(MaintenanceWindow {
+            id: None,
+            starts_at,
+            ends_at,
+            reason: "scheduled maintenance".to_owned(),
+        })
+    }
+
+    // Returns whether the window is ongoing.
+    // @now: The current unix timestamp.
+    pub fn is_active(&self, now: u64) -> bool {
+        let now = now as i64;
+        self.starts_at <= now && now < self.ends_at
+    }
+}
+
+// A struct representing the state of the kill switch, as exposed to operators.
+// @engaged: Whether outgoing transactions are stopped.
+// @reason: The reason the operator engaged the switch with, if engaged by an operator.
+// @paused_on_chain: Whether the DataStore pause flag is set.
+// @maintenance: The ongoing maintenance window, if any.
+// @pending_upgrades: The contract upgrades waiting for an operator acknowledgment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KillSwitchStatus {
+    pub engaged: bool,
+    pub reason: Option<String>,
+    pub paused_on_chain: bool,
+    pub maintenance: Option<MaintenanceWindow>,
+    pub pending_upgrades: Vec<ContractUpgrade>,
+}
+
+// A struct representing the emergency stop of the keeper, checked before every submission. It gets
+// engaged by an operator through the admin API, shared with the other instances through the
+// database, mirrored from a pause flag of the DataStore, during maintenance windows, or until
+// the contract upgrades detected get acknowledged. Only
+// outgoing transactions stop, the keeper keeps listening, monitoring and serving the admin API.
+// @pause_key: The DataStore key of the on-chain pause flag, not mirrored when None.
+// @configured_windows: The maintenance windows of the MAINTENANCE_WINDOWS variable.
+// @operator_reason: The reason the switch got engaged with by an operator, disengaged when None.
+// @paused_on_chain: Whether the DataStore pause flag is set.
+// @scheduled_windows: The maintenance windows scheduled through the admin API, shared through the
+// database.
+// @in_maintenance: Whether a maintenance window was ongoing at the last poll, for its start and
+// end to be logged.
+// @pending_upgrades: The contract upgrades no operator acknowledged yet, shared through the
+// database.
+#[derive(Debug, Default)]
+pub struct KillSwitch {
+    pub pause_key: Option<FieldElement>,
+    pub configured_windows: Vec<MaintenanceWindow>,
+    operator_reason: Mutex<Option<String>>,
+    paused_on_chain: AtomicBool,
+    scheduled_windows: Mutex<Vec<MaintenanceWindow>>,
+    in_maintenance: AtomicBool,
+    pending_upgrades: Mutex<Vec<ContractUpgrade>>,
+}
+
+impl KillSwitch {
+    pub fn new(pause_key: Option<FieldElement>) -> Self {
+        KillSwitch {
+            pause_key,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_env() -> Self {
+        KillSwitch {
+            configured_windows: config::get_maintenance_windows()
+                .iter()
+                .map(|entry| MaintenanceWindow::parse(entry).unwrap_or_else(|e| panic!("{}", e)))
+                .collect(),
+            ..KillSwitch::new(config::get_kill_switch_pause_key().map(|key| {
+                FieldElement::from_hex_be(&key)
+                    .unwrap_or_else(|_| panic!("Invalid kill switch pause key {}", key))
+            }))
+        }
+    }
+
+    pub fn status(&self) -> KillSwitchStatus {
+        let reason = self.operator_reason.lock().unwrap().clone();
+        let paused_on_chain = self.paused_on_chain.load(Ordering::Relaxed);
+        let maintenance = self.active_maintenance(get_system_timestamp());
+        let pending_upgrades = self.pending_upgrades.lock().unwrap().clone();
+        KillSwitchStatus {
+            engaged: reason.is_some()
+                || paused_on_chain
+                || maintenance.is_some()
+                || !pending_upgrades.is_empty(),
+            reason,
+            paused_on_chain,
+            maintenance,
+            pending_upgrades,
+        }
+    }
+
+    // Returns every maintenance window, configured or scheduled, the earliest first.
+    pub fn maintenance_windows(&self) -> Vec<MaintenanceWindow> {
+        let mut windows = self.configured_windows.clone();
+        windows.extend(self.scheduled_windows.lock().unwrap().iter().cloned());
+        windows.sort_by_key(|window| (window.starts_at, window.ends_at));
+        windows
+    }
+
+    // Returns the ongoing maintenance window ending last, if any.
+    // @now: The current unix timestamp.
+    pub fn active_maintenance(&self, now: u64) -> Option<MaintenanceWindow> {
+        self.maintenance_windows()
+            .into_iter()
+            .filter(|window| window.is_active(now))
+            .max_by_key(|window| window.ends_at)
+    }
+
+    // Checks no transaction can get sent, returns why when the switch is engaged.
+    pub fn check(&self) -> Result<(), KeeperError> {
+        self.check_at(get_system_timestamp())
+    }
+
+    // Checks no transaction can get sent at a given time, returns why when the switch is engaged.
+    // @now: The current unix timestamp.
+    pub fn check_at(&self, now: u64) -> Result<(), KeeperError> {
+        if let Some(reason) = self.operator_reason.lock().unwrap().as_ref() {
+            return Err(KeeperError::KillSwitchEngaged(reason.clone()));
+        }
+        if self.paused_on_chain.load(Ordering::Relaxed) {
+            return Err(KeeperError::KillSwitchEngaged(
+                "DataStore pause flag set".to_owned(),
+            ));
+        }
+        if let Some(window) = self.active_maintenance(now) {
+            return Err(KeeperError::KillSwitchEngaged(format!(
+                "{} until {}",
+                window.reason,
+                format_timestamp(window.ends_at.max(0) as u128 * 1000)
+            )));
+        }
+        if let Some(upgrade) = self.pending_upgrades.lock().unwrap().first() {
+            return Err(KeeperError::KillSwitchEngaged(upgrade.describe()));
+        }
+        Ok(())
+    }
+
+    // Replaces the contract upgrades waiting for an acknowledgment, alerting when transactions
+    // stop or resume for them.
+    // @upgrades: The upgrades, as stored in the database.
+    pub fn set_pending_upgrades(&self, upgrades: Vec<ContractUpgrade>) {
+        let mut pending_upgrades = self.pending_upgrades.lock().unwrap();
+        match (pending_upgrades.is_empty(), upgrades.first()) {
+            (true, Some(upgrade)) => warn!("ALERT: {}, stopping transactions", upgrade.describe()),
+            (false, None) => info!("Contract upgrades acknowledged, resuming transactions"),
+            _ => {}
+        }
+        *pending_upgrades = upgrades;
+    }
+
+    // Replaces the maintenance windows scheduled through the admin API.
+    // @windows: The windows, as stored in the database.
+    pub fn set_scheduled_windows(&self, windows: Vec<MaintenanceWindow>) {
+        *self.scheduled_windows.lock().unwrap() = windows;
+    }
+
+    // Alerts on the start and end of the maintenance windows, as seen at a poll.
+    // @now: The current unix timestamp.
+    pub fn log_maintenance(&self, now: u64) {
+        let window = self.active_maintenance(now);
+        match (
+            self.in_maintenance
+                .swap(window.is_some(), Ordering::Relaxed),
+            window,
+        ) {
+            (false, Some(window)) => warn!(
+                "ALERT: maintenance window started: {}, stopping transactions until {}",
+                window.reason,
+                format_timestamp(window.ends_at.max(0) as u128 * 1000)
+            ),
+            (true, None) => info!("Maintenance window ended, resuming transactions"),
+            _ => {}
+        }
+    }
+
+    // Engages the switch with a reason, or disengages it when None, alerting on every change.
+    // @reason: The reason given by the operator.
+    pub fn set_operator_reason(&self, reason: Option<String>) {
+        let mut operator_reason = self.operator_reason.lock().unwrap();
+        match (operator_reason.is_some(), &reason) {
+            (false, Some(reason)) => {
+                warn!(
+                    "ALERT: kill switch engaged: {}, stopping transactions",
+                    reason
+                )
+            }
+            (true, None) => info!("Kill switch disengaged, resuming transactions"),
+            _ => {}
+        }
+        *operator_reason = reason;
+    }
+
+    // Mirrors the DataStore pause flag, alerting on every change.
+    // @paused: Whether the flag is set.
+    pub fn set_paused_on_chain(&self, paused: bool) {
+        match (self.paused_on_chain.swap(paused, Ordering::Relaxed), paused) {
+            (false, true) => warn!("ALERT: DataStore pause flag set, stopping transactions"),
+            (true, false) => info!("DataStore pause flag cleared, resuming transactions"),
+            _ => {}
+        }
+    }
+}
+
+// Loads the reason the kill switch got engaged with by an operator, None when disengaged.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_kill_switch(pool: &Pool<Postgres>) -> Result<Option<String>, Error> {
+    let reason =
+        sqlx::query_scalar::<_, String>("SELECT reason FROM keeper_kill_switch WHERE engaged")
+            .fetch_optional(pool)
+            .await?;
+    Ok(reason)
+}
+
+// Engages the kill switch of every keeper instance sharing the database, or disengages it.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @reason: The reason given by the operator, disengaging the switch when None.
+pub async fn save_kill_switch(pool: &Pool<Postgres>, reason: Option<&str>) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO keeper_kill_switch (id, engaged, reason) VALUES (TRUE, $1, COALESCE($2, ''))
+         ON CONFLICT (id) DO UPDATE SET engaged = $1, reason = COALESCE($2, ''), updated_at = NOW()",
+    )
+    .bind(reason.is_some())
+    .bind(reason)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Loads the maintenance windows scheduled through the admin API which did not end yet.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_maintenance_windows(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<MaintenanceWindow>, Error> {
+    sqlx::query_as::<_, MaintenanceWindow>(
+        "SELECT id, starts_at, ends_at, reason FROM keeper_maintenance_windows
+         WHERE ends_at > EXTRACT(EPOCH FROM NOW()) ORDER BY starts_at",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Schedules a maintenance window for every keeper instance sharing the database, returns its id.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @starts_at: When the window starts, as a unix timestamp.
+// @ends_at: When the window ends, as a unix timestamp.
+// @reason: Why the keeper is under maintenance.
+pub async fn save_maintenance_window(
+    pool: &Pool<Postgres>,
+    starts_at: i64,
+    ends_at: i64,
+    reason: &str,
+) -> Result<i64, Error> {
+    sqlx::query_scalar(
+        "INSERT INTO keeper_maintenance_windows (starts_at, ends_at, reason) VALUES ($1, $2, $3)
+         RETURNING id",
+    )
+    .bind(starts_at)
+    .bind(ends_at)
+    .bind(reason)
+    .fetch_one(pool)
+    .await
+}
+
+// Cancels a scheduled maintenance window, or ends it early, returns whether there was one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @id: The id of the window.
+pub async fn delete_maintenance_window(pool: &Pool<Postgres>, id: i64) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM keeper_maintenance_windows WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Keeps the kill switch in sync with the database and the DataStore pause flag forever, failed
+// reads leaving the switch as it is until the next poll.
+// @kill_switch: The kill switch of the keeper.
+// @pool: A connection pool for PostgreSQL.
+// @contracts: The keeper contracts, the DataStore the pause flag is read from, the flag not being
+// mirrored without them.
+pub async fn watch_kill_switch(
+    kill_switch: &KillSwitch,
+    pool: &Pool<Postgres>,
+    contracts: Option<&Contracts>,
+) {
+    let interval = Duration::from_secs(config::get_kill_switch_poll_interval_secs());
+    loop {
+        match load_kill_switch(pool).await {
+            Ok(reason) => kill_switch.set_operator_reason(reason),
+            Err(e) => error!("Could not load kill switch: {:?}", e),
+        }
+        match load_maintenance_windows(pool).await {
+            Ok(windows) => kill_switch.set_scheduled_windows(windows),
+            Err(e) => error!("Could not load maintenance windows: {:?}", e),
+        }
+        kill_switch.log_maintenance(get_system_timestamp());
+        match load_pending_upgrades(pool).await {
+            Ok(upgrades) => kill_switch.set_pending_upgrades(upgrades),
+            Err(e) => error!("Could not load contract upgrades: {:?}", e),
+        }
+        if let (Some(pause_key), Some(contracts)) = (kill_switch.pause_key, contracts) {
+            match contracts.data_store.get_bool(&pause_key).call().await {
+                Ok(paused) => kill_switch.set_paused_on_chain(paused),
+                Err(e) => error!("Could not read DataStore pause flag: {:?}", e),
+            }
+        }
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_switch() {
+        let kill_switch = KillSwitch::new(None);
+        assert!(kill_switch.check().is_ok());
+        assert!(!kill_switch.status().engaged);
+
+        kill_switch.set_operator_reason(Some("incident".to_owned()));
+        assert!(matches!(
+            kill_switch.check(),
+            Err(KeeperError::KillSwitchEngaged(reason)) if reason == "incident"
+        ));
+        kill_switch.set_paused_on_chain(true);
+        kill_switch.set_operator_reason(None);
+        assert!(kill_switch.check().is_err());
+        assert_eq!(
+            kill_switch.status(),
+            KillSwitchStatus {
+                engaged: true,
+                reason: None,
+                paused_on_chain: true,
+                maintenance: None,
+                pending_upgrades: vec![],
+            }
+        );
+
+        kill_switch.set_paused_on_chain(false);
+        assert!(kill_switch.check().is_ok());
+    }
+
+    #[test]
+    fn test_parse_maintenance_window() {
+        assert_eq!(
+            MaintenanceWindow::parse("1704067200-1704070800"),
+            Ok(MaintenanceWindow {
+                id: None,
+                starts_at: 1704067200,
+                ends_at: 1704070800,
+                reason: "scheduled maintenance".to_owned(),
+            })
+        );
+        assert!(MaintenanceWindow::parse("1704070800-1704067200").is_err());
+        assert!(MaintenanceWindow::parse("1704067200").is_err());
+        assert!(MaintenanceWindow::parse("start-end").is_err());
+    }
+
+    #[test]
+    fn test_maintenance_windows() {
+        let kill_switch = KillSwitch {
+            configured_windows: vec![MaintenanceWindow::parse("100-200").unwrap()],
+            ..KillSwitch::new(None)
+        };
+        kill_switch.set_scheduled_windows(vec![MaintenanceWindow {
+            id: Some(1),
+            starts_at: 150,
+            ends_at: 300,
+            reason: "upgrade".to_owned(),
+        }]);
+        assert!(kill_switch.check_at(99).is_ok());
+        assert!(kill_switch.check_at(100).is_err());
+        // Overlapping windows, the one ending last holding the transactions.
+        assert!(matches!(
+            kill_switch.check_at(160),
+            Err(KeeperError::KillSwitchEngaged(reason)) if reason.starts_with("upgrade until")
+        ));
+        assert!(kill_switch.check_at(299).is_err());
+        assert!(kill_switch.check_at(300).is_ok());
+        assert_eq!(kill_switch.maintenance_windows().len(), 2);
+
+        kill_switch.set_scheduled_windows(vec![]);
+        assert!(kill_switch.check_at(250).is_ok());
+    }
+
+    #[test]
+    fn test_pending_upgrades() {
+        let kill_switch = KillSwitch::new(None);
+        kill_switch.set_pending_upgrades(vec![ContractUpgrade {
+            id: 1,
+            contract: "ORDER_HANDLER".to_owned(),
+            address: "0x12".to_owned(),
+            previous_class_hash: Some("0x1".to_owned()),
+            class_hash: "0x2".to_owned(),
+            block_number: Some(7),
+        }]);
+        assert!(matches!(
+            kill_switch.check(),
+            Err(KeeperError::KillSwitchEngaged(reason)) if reason.starts_with("ORDER_HANDLER upgraded")
+        ));
+        assert!(kill_switch.status().engaged);
+
+        kill_switch.set_pending_upgrades(vec![]);
+        assert!(kill_switch.check().is_ok());
+    }
+}
@@ -1,4 +1,63 @@
+#[cfg(feature = "api")]
+pub mod api;
+pub mod archive;
+pub mod backlog;
+pub mod bench;
+pub mod cli;
+pub mod clock;
+pub mod competition;
+pub mod config;
+pub mod contracts;
+pub mod dashboard;
+pub mod db;
+pub mod decisions;
+pub mod delegation;
+pub mod divergence;
 pub mod error;
+pub mod executor;
+pub mod fees;
+pub mod flow;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod history;
+pub mod keys;
+pub mod killswitch;
+#[cfg(feature = "liquidation")]
+pub mod liquidation;
 pub mod listen_db;
+#[cfg(feature = "indexer")]
+pub mod loadtest;
+pub mod logging;
+pub mod market_config;
+pub mod metrics;
+pub mod paymaster;
+pub mod pnl;
+pub mod portfolio;
+pub mod positions;
+pub mod preview;
+pub mod pricelog;
+pub mod profile;
+pub mod quality;
+pub mod registry;
+pub mod relay;
+#[cfg(feature = "liquidation")]
+pub mod risk;
+#[cfg(feature = "liquidation")]
+pub mod scanner;
+pub mod selftest;
+pub mod session;
+pub mod snapshot;
+pub mod standby;
+pub mod startup;
+pub mod state;
+pub mod submitter;
+pub mod supervisor;
+pub mod sweeper;
+pub mod trace;
 pub mod trade;
 pub mod types;
+pub mod upgrades;
+pub mod vwap;
+#[cfg(feature = "liquidation")]
+pub mod watch;
+pub mod webhooks;
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use starknet::core::types::FieldElement;
+
+use crate::{config, market_config::MarketConfig};
+
+// A struct holding the market parameters positions get liquidated by.
+// @min_collateral_usd: The smallest collateral in USD a position keeps.
+// @default_min_collateral_factor: The smallest collateral to size ratio in markets without one.
+// @min_collateral_factors: The collateral to size ratios per market, markets in lowercase hex.
+// @rank_step: The width of the distance to liquidation candidates are grouped by, as a fraction of
+// the index price.
+#[derive(Debug, Clone, Default)]
+pub struct LiquidationParams {
+    pub min_collateral_usd: f64,
+    pub default_min_collateral_factor: f64,
+    pub min_collateral_factors: HashMap<String, f64>,
+    pub rank_step: f64,
+}
+
+// A struct representing the current state of a position, amounts in USD and tokens, not scaled by
+// their decimals.
+// @is_long: Whether the position is long.
+// @size_in_usd: The position size in USD.
+// @size_in_tokens: The position size in index tokens.
+// @collateral_amount: The collateral amount in collateral tokens.
+// @collateral_price: The price of the collateral token.
+// @collateral_is_index_token: Whether the collateral is the index token, its value then following
+// the index price.
+// @pending_fees_usd: The borrowing and funding fees still to be paid by the position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionState {
+    pub is_long: bool,
+    pub size_in_usd: f64,
+    pub size_in_tokens: f64,
+    pub collateral_amount: f64,
+    pub collateral_price: f64,
+    pub collateral_is_index_token: bool,
+    pub pending_fees_usd: f64,
+}
+
+// A struct representing the estimated liquidation price of a position.
+// @liquidation_price: The index price the position becomes liquidatable at, None if it never does.
+// @min_collateral_usd: The collateral the position must keep, after PnL and fees.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidationEstimate {
+    pub liquidation_price: Option<f64>,
+    pub min_collateral_usd: f64,
+}
+
+// A struct representing a position the liquidation keeper may liquidate.
+// @key: The position key.
+// @market: The market of the position.
+// @position: The current state of the position.
+// @price: The current index price of the market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidationCandidate {
+    pub key: FieldElement,
+    pub market: String,
+    pub position: PositionState,
+    pub price: f64,
+}
+
+impl LiquidationParams {
+    pub fn from_env() -> Self {
+        LiquidationParams {
+            min_collateral_usd: config::get_min_collateral_usd(),
+            default_min_collateral_factor: config::get_default_min_collateral_factor(),
+            min_collateral_factors: config::get_min_collateral_factors().into_iter().collect(),
+            rank_step: config::get_liquidation_rank_step_bps() / 10000.0,
+        }
+    }
+
+    // Applies the collateral factor of a market as set in the DataStore over the configured one,
+    // markets without one keeping theirs.
+    // @market: The market, as the positions of the market get estimated with.
+    // @market_config: The configuration of the market.
+    pub fn with_market_config(mut self, market: &str, market_config: &MarketConfig) -> Self {
+        if market_config.min_collateral_factor > 0.0 {
+            self.min_collateral_factors
+                .insert(market.to_lowercase(), market_config.min_collateral_factor);
+        }
+        self
+    }
+
+    // Returns the collateral a position must keep, the largest of the min collateral and the
+    // collateral factor of its market applied to its size.
+    // @market: The market of the position.
+    // @size_in_usd: The position size in USD.
+    pub fn min_collateral(&self, market: &str, size_in_usd: f64) -> f64 {
+        let factor = *self
+            .min_collateral_factors
+            .get(&market.to_lowercase())
+            .unwrap_or(&self.default_min_collateral_factor);
+        self.min_collateral_usd.max(size_in_usd * factor)
+    }
+
+    // Estimates the index price a position gets liquidatable at, the price its collateral after
+    // PnL and pending fees falls to the min collateral at. Price impact and closing fees are not
+    // accounted for.
+    // @market: The market of the position.
+    // @position: The current state of the position.
+    pub fn estimate(&self, market: &str, position: &PositionState) -> LiquidationEstimate {
+        let min_collateral_usd = self.min_collateral(market, position.size_in_usd);
+        // Collateral value moving with the index price, and collateral value fixed in USD.
+        let (collateral_tokens, collateral_usd) = match position.collateral_is_index_token {
+            true => (position.collateral_amount, 0.0),
+            false => (0.0, position.collateral_amount * position.collateral_price),
+        };
+        // Solves collateral + PnL - fees = min collateral for the index price, the PnL being
+        // size_in_tokens * price - size_in_usd for longs and the opposite for shorts.
+        let (numerator, denominator) = match position.is_long {
+            true => (
+                position.size_in_usd + position.pending_fees_usd + min_collateral_usd
+                    - collateral_usd,
+                position.size_in_tokens + collateral_tokens,
+            ),
+            false => (
+                position.size_in_usd + collateral_usd
+                    - position.pending_fees_usd
+                    - min_collateral_usd,
+                position.size_in_tokens - collateral_tokens,
+            ),
+        };
+        let liquidation_price = Some(numerator / denominator)
+            .filter(|price| denominator != 0.0 && price.is_finite() && *price > 0.0);
+        LiquidationEstimate {
+            liquidation_price,
+            min_collateral_usd,
+        }
+    }
+
+    // Checks whether a position is liquidatable at an index price, for liquidations to be
+    // double-checked before being sent.
+    // @market: The market of the position.
+    // @position: The current state of the position.
+    // @price: The index price.
+    pub fn is_liquidatable(&self, market: &str, position: &PositionState, price: f64) -> bool {
+        let pnl = match position.is_long {
+            true => position.size_in_tokens * price - position.size_in_usd,
+            false => position.size_in_usd - position.size_in_tokens * price,
+        };
+        let collateral_usd = match position.collateral_is_index_token {
+            true => position.collateral_amount * price,
+            false => position.collateral_amount * position.collateral_price,
+        };
+        collateral_usd + pnl - position.pending_fees_usd
+            < self.min_collateral(market, position.size_in_usd)
+    }
+
+    // Returns how far the index price is from liquidating a position, as a fraction of the price,
+    // negative once past the liquidation price and infinite for positions never liquidated.
+    // @market: The market of the position.
+    // @position: The current state of the position.
+    // @price: The index price.
+    pub fn distance_to_liquidation(
+        &self,
+        market: &str,
+        position: &PositionState,
+        price: f64,
+    ) -> f64 {
+        match self.estimate(market, position).liquidation_price {
+            Some(liquidation_price) if position.is_long => (price - liquidation_price) / price,
+            Some(liquidation_price) => (liquidation_price - price) / price,
+            None => f64::INFINITY,
+        }
+    }
+
+    // Orders liquidation candidates the riskiest first, so the positions closest to or deepest
+    // past their liquidation price get liquidated before the others when prices crash. Candidates
+    // within the same rank step of distance are ordered by size, the largest first, their bad debt
+    // costing the pool the most.
+    // @candidates: The positions to order.
+    pub fn rank(&self, candidates: Vec<LiquidationCandidate>) -> Vec<LiquidationCandidate> {
+        let mut ranked: Vec<(f64, LiquidationCandidate)> = candidates
+            .into_iter()
+            .map(|candidate| {
+                let distance = self.distance_to_liquidation(
+                    &candidate.market,
+                    &candidate.position,
+                    candidate.price,
+                );
+                let step = match self.rank_step > 0.0 {
+                    true => (distance / self.rank_step).floor(),
+                    false => distance,
+                };
+                (step, candidate)
+            })
+            .collect();
+        ranked.sort_by(|(step_a, a), (step_b, b)| {
+            step_a
+                .total_cmp(step_b)
+                .then(b.position.size_in_usd.total_cmp(&a.position.size_in_usd))
+                .then(a.key.cmp(&b.key))
+        });
+        ranked.into_iter().map(|(_, candidate)| candidate).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> LiquidationParams {
+        LiquidationParams {
+            min_collateral_usd: 1.0,
+            default_min_collateral_factor: 0.01,
+            min_collateral_factors: HashMap::from([("0x12".to_owned(), 0.05)]),
+            rank_step: 0.001,
+        }
+    }
+
+    // A 10x position of 10000 USD opened at 2000 USD, backed by 1000 USDC.
+    fn position(is_long: bool) -> PositionState {
+        PositionState {
+            is_long,
+            size_in_usd: 10000.0,
+            size_in_tokens: 5.0,
+            collateral_amount: 1000.0,
+            collateral_price: 1.0,
+            collateral_is_index_token: false,
+            pending_fees_usd: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_min_collateral() {
+        let params = params();
+        assert_eq!(params.min_collateral("0x13", 10000.0), 100.0);
+        assert_eq!(params.min_collateral("0x12", 10000.0), 500.0);
+        assert_eq!(params.min_collateral("0x13", 50.0), 1.0);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let params = params();
+        let long = params.estimate("0x13", &position(true));
+        assert_eq!(long.liquidation_price, Some(1822.0));
+        assert_eq!(long.min_collateral_usd, 100.0);
+        let short = params.estimate("0x13", &position(false));
+        assert_eq!(short.liquidation_price, Some(2178.0));
+
+        for (is_long, price) in [(true, 1822.0), (false, 2178.0)] {
+            assert!(!params.is_liquidatable("0x13", &position(is_long), price));
+            let moved = if is_long { price - 1.0 } else { price + 1.0 };
+            assert!(params.is_liquidatable("0x13", &position(is_long), moved));
+        }
+    }
+
+    #[test]
+    fn test_estimate_index_collateral() {
+        let params = params();
+        // A long backed by 0.5 ETH, its collateral value falling with the index price.
+        let mut long = position(true);
+        long.collateral_amount = 0.5;
+        long.collateral_is_index_token = true;
+        assert_eq!(
+            params.estimate("0x13", &long).liquidation_price,
+            Some(10110.0 / 5.5)
+        );
+        // A short backed by as many index tokens as its size is hedged, it never gets liquidated.
+        let mut short = position(false);
+        short.size_in_tokens = 0.5;
+        short.collateral_amount = 0.5;
+        short.collateral_is_index_token = true;
+        assert_eq!(params.estimate("0x13", &short).liquidation_price, None);
+    }
+
+    #[test]
+    fn test_rank() {
+        let params = params();
+        let candidate = |key: u8, price: f64, size_in_usd: f64| {
+            let mut position = position(true);
+            position.size_in_tokens *= size_in_usd / position.size_in_usd;
+            position.collateral_amount *= size_in_usd / position.size_in_usd;
+            position.size_in_usd = size_in_usd;
+            LiquidationCandidate {
+                key: FieldElement::from(key),
+                market: "0x13".to_owned(),
+                position,
+                price,
+            }
+        };
+        // Longs liquidated at around 1822 USD: 1 and 2 are past it, 3 close to it and 4 far from it,
+        // 5 within the same step as 3 but larger.
+        let ranked = params.rank(vec![
+            candidate(4, 2000.0, 10000.0),
+            candidate(3, 1830.0, 10000.0),
+            candidate(1, 1700.0, 10000.0),
+            candidate(2, 1800.0, 10000.0),
+            candidate(5, 1829.0, 20000.0),
+        ]);
+        let keys: Vec<FieldElement> = ranked.iter().map(|candidate| candidate.key).collect();
+        assert_eq!(keys, [1u8, 2, 5, 3, 4].map(FieldElement::from).to_vec());
+        assert!(params.distance_to_liquidation("0x13", &ranked[0].position, 1700.0) < 0.0);
+    }
+}
@@ -17,9 +17,6 @@ pub async fn start_listening<T: DeserializeOwned + Sized + Debug>(
     channels: Vec<&str>,
     call_back: impl Fn(T) -> JoinHandle<()>,
 ) -> Result<(), Error> {
-    // Initiate the logger.
-    env_logger::init();
-
     let mut listener: PgListener = PgListener::connect_with(pool)
         .await
         .expect("Could not connect to pool.");
@@ -33,7 +30,8 @@ pub async fn start_listening<T: DeserializeOwned + Sized + Debug>(
             );
 
             let strr = notification.payload().to_owned();
-            let payload: T = serde_json::from_str::<T>(&strr).expect("Could not decode payload.");
+            let payload: T = serde_json::from_str::<T>(&strr)
+                .unwrap_or_else(|e| panic!("Could not decode payload {}: {}", strr, e));
             info!("Payload {:?}", payload);
 
             call_back(payload);
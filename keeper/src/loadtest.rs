@@ -0,0 +1,140 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::error;
+use satoru_indexer::loadtest::{run_database_load_test, LoadParams, LoadReport};
+use sqlx::{Pool, Postgres};
+use tokio::time::sleep;
+
+use crate::backlog::get_backlog;
+
+// Delay between two samples of the keeper queue.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// A struct representing the keeper queue at a point of a load test.
+// @elapsed: The time since the load test started.
+// @pending_actions: The indexed actions neither executed nor cancelled yet.
+// @jobs: The keeper jobs claimed or submitted, per status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueSample {
+    pub elapsed: Duration,
+    pub pending_actions: i64,
+    pub jobs: Vec<(String, i64)>,
+}
+
+impl fmt::Display for QueueSample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>4}s: {} actions pending",
+            self.elapsed.as_secs(),
+            self.pending_actions
+        )?;
+        for (status, jobs) in &self.jobs {
+            write!(f, ", {} {}", jobs, status)?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the sample the most actions were pending at, the point the keeper fell the furthest
+// behind the stream.
+// @samples: The samples of the load test.
+pub fn peak_sample(samples: &[QueueSample]) -> Option<&QueueSample> {
+    samples
+        .iter()
+        .max_by_key(|sample| (sample.pending_actions, std::cmp::Reverse(sample.elapsed)))
+}
+
+// Reads the keeper queue.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @elapsed: The time since the load test started.
+pub async fn sample_queue(
+    pool: &Pool<Postgres>,
+    elapsed: Duration,
+) -> Result<QueueSample, sqlx::Error> {
+    let pending_actions = get_backlog(pool)
+        .await?
+        .iter()
+        .map(|backlog| backlog.pending)
+        .sum();
+    let jobs = sqlx::query_as::<_, (String, i64)>(
+        "SELECT status, COUNT(*) FROM keeper_jobs WHERE status IN ('claimed', 'submitted')
+         GROUP BY status ORDER BY status",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(QueueSample {
+        elapsed,
+        pending_actions,
+        jobs,
+    })
+}
+
+// Feeds a synthetic event stream through the indexer into the database the keepers listen to,
+// sampling their queue every second, then prints how the indexer and the keepers kept up.
+// @pool: A reference to a connection pool for PostgreSQL, the keeper queue being read through it.
+pub async fn run_keeper_load_test(pool: Pool<Postgres>) -> Result<LoadReport, String> {
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let start = Instant::now();
+
+    let sampler = {
+        let (pool, samples) = (pool.clone(), Arc::clone(&samples));
+        tokio::spawn(async move {
+            loop {
+                match sample_queue(&pool, start.elapsed()).await {
+                    Ok(sample) => {
+                        println!("{}", sample);
+                        samples.lock().unwrap().push(sample);
+                    }
+                    Err(e) => error!("Could not sample the keeper queue: {:?}", e),
+                }
+                sleep(SAMPLE_INTERVAL).await;
+            }
+        })
+    };
+    let report = run_database_load_test(LoadParams::from_env()).await;
+    sampler.abort();
+    let report = report.map_err(|e| format!("could not run the load test: {:?}", e))?;
+
+    println!("{}", report);
+    let peak = peak_sample(&samples.lock().unwrap()).cloned();
+    if let Some(peak) = peak {
+        println!("Keeper queue peak: {}", peak);
+    }
+    if let Ok(last) = sample_queue(&pool, start.elapsed()).await {
+        println!("Keeper queue at the end: {}", last);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(elapsed: u64, pending_actions: i64) -> QueueSample {
+        QueueSample {
+            elapsed: Duration::from_secs(elapsed),
+            pending_actions,
+            jobs: vec![("claimed".to_owned(), 2), ("submitted".to_owned(), 1)],
+        }
+    }
+
+    #[test]
+    fn test_peak_sample() {
+        assert_eq!(peak_sample(&[]), None);
+        let samples = [sample(1, 5), sample(2, 40), sample(3, 40), sample(4, 10)];
+        assert_eq!(peak_sample(&samples), Some(&samples[1]));
+    }
+
+    #[test]
+    fn test_display_sample() {
+        assert_eq!(
+            sample(12, 340).to_string(),
+            "  12s: 340 actions pending, 2 claimed, 1 submitted"
+        );
+    }
+}
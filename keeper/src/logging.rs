@@ -0,0 +1,324 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+
+use crate::{config, db::get_query_origin};
+
+// The prefix of the keeper log targets, the logs of the dependencies being kept to warnings.
+const KEEPER_TARGET: &str = "keeper_satoru";
+
+// An enum representing how logs get written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    // One human readable line per log.
+    Pretty,
+    // One JSON object per line, for log collectors.
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "pretty" => Some(LogFormat::Pretty),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Formats a unix timestamp in milliseconds as an RFC 3339 UTC date, e.g. 2024-01-01T00:00:00.000Z.
+// @timestamp_ms: The unix timestamp in milliseconds.
+pub fn format_timestamp(timestamp_ms: u128) -> String {
+    let secs = (timestamp_ms / 1000) as i64;
+    let (days, day_secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // Converts the days since the epoch to a civil date, as in Howard Hinnant's civil_from_days.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        day_secs / 3600,
+        day_secs % 3600 / 60,
+        day_secs % 60,
+        timestamp_ms % 1000
+    )
+}
+
+// Formats a log as a line, without its line break.
+// @format: The format of the line.
+// @timestamp_ms: When the log got emitted, as a unix timestamp in milliseconds.
+// @record: The log.
+// @origin: Where the task logging comes from, e.g. the admin API route of a slow query, if known.
+pub fn format_record(
+    format: LogFormat,
+    timestamp_ms: u128,
+    record: &Record,
+    origin: Option<&str>,
+) -> String {
+    match format {
+        LogFormat::Pretty => {
+            let line = format!(
+                "{} {:<5} {} {}",
+                format_timestamp(timestamp_ms),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            match origin {
+                Some(origin) => format!("{} (from {})", line, origin),
+                None => line,
+            }
+        }
+        LogFormat::Json => {
+            let mut line = json!({
+                "timestamp": format_timestamp(timestamp_ms),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let Some(origin) = origin {
+                line["origin"] = json!(origin);
+            }
+            line.to_string()
+        }
+    }
+}
+
+// A struct representing a log file rotated once it gets too large or too old, the rotated files
+// being renamed with a numbered suffix, e.g. keeper.log.1 for the most recent one.
+// @path: The path of the current file.
+// @max_bytes: The size the file gets rotated at, never rotated on size when None.
+// @max_age: The age the file gets rotated at, never rotated on age when None.
+// @max_files: The number of rotated files kept, older ones getting deleted.
+// @file: The current file.
+// @size: The size of the current file.
+// @opened_at: When the current file got opened.
+pub struct RotatingFile {
+    pub path: PathBuf,
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+    pub max_files: usize,
+    file: File,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: &Path,
+        max_bytes: Option<u64>,
+        max_age: Option<Duration>,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RotatingFile {
+            path: path.to_owned(),
+            max_bytes,
+            max_age,
+            max_files,
+            size: file.metadata()?.len(),
+            file,
+            opened_at: Instant::now(),
+        })
+    }
+
+    // Returns the path of a rotated file, 1 being the most recent one.
+    // @index: The index of the rotated file.
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    // Shifts the rotated files, deleting the oldest one, and reopens an empty file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        *self = RotatingFile::open(&self.path, self.max_bytes, self.max_age, self.max_files)?;
+        Ok(())
+    }
+
+    // Appends a line, rotating the file first if the line would make it too large or it got too old.
+    // @line: The line, without its line break.
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_size = line.len() as u64 + 1;
+        let too_large = self
+            .max_bytes
+            .is_some_and(|max_bytes| self.size > 0 && self.size + line_size > max_bytes);
+        let too_old = self
+            .max_age
+            .is_some_and(|max_age| self.opened_at.elapsed() >= max_age);
+        if too_large || too_old {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line_size;
+        Ok(())
+    }
+}
+
+// A struct representing the keeper logger, writing to stdout and to a rotating JSON lines file,
+// so deployments without a log collector still keep the history of the keeper actions.
+// @level: The level of the keeper logs, dependencies only logging their warnings.
+// @format: The format of the stdout logs.
+// @file: The rotating file logs also get written to as JSON lines, if any.
+pub struct KeeperLogger {
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl KeeperLogger {
+    pub fn from_env() -> io::Result<Self> {
+        let file = match config::get_log_file() {
+            Some(path) => Some(Mutex::new(RotatingFile::open(
+                Path::new(&path),
+                config::get_log_file_max_bytes(),
+                config::get_log_file_rotation_secs().map(Duration::from_secs),
+                config::get_log_file_max_files(),
+            )?)),
+            None => None,
+        };
+        Ok(KeeperLogger {
+            level: config::get_log_level()
+                .parse()
+                .expect("LOG_LEVEL must be a valid level"),
+            format: LogFormat::parse(&config::get_log_format())
+                .expect("LOG_FORMAT must be pretty or json"),
+            file,
+        })
+    }
+}
+
+impl Log for KeeperLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match metadata.target().starts_with(KEEPER_TARGET) {
+            true => metadata.level() <= self.level,
+            false => metadata.level() <= LevelFilter::Warn,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before unix epoch")
+            .as_millis();
+        let origin = get_query_origin();
+        println!(
+            "{}",
+            format_record(self.format, timestamp_ms, record, origin.as_deref())
+        );
+        if let Some(file) = &self.file {
+            let line = format_record(LogFormat::Json, timestamp_ms, record, origin.as_deref());
+            if let Err(e) = file.lock().unwrap().write_line(&line) {
+                eprintln!("Could not write to the log file: {:?}", e);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().file.flush();
+        }
+    }
+}
+
+// Sets up the keeper logger as the logger of the process.
+pub fn init() {
+    let logger = KeeperLogger::from_env().expect("Could not open the log file.");
+    log::set_max_level(logger.level.max(LevelFilter::Warn));
+    // The logger lives as long as the process.
+    log::set_logger(Box::leak(Box::new(logger))).expect("Logger already set.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_timestamp(1704067200123), "2024-01-01T00:00:00.123Z");
+        assert_eq!(format_timestamp(1709208000000), "2024-02-29T12:00:00.000Z");
+    }
+
+    #[test]
+    fn test_format_record() {
+        let args = format_args!("Job {} paused", "0x1a");
+        let record = Record::builder()
+            .args(args)
+            .level(log::Level::Info)
+            .target("keeper_satoru::executor")
+            .build();
+
+        assert_eq!(
+            format_record(LogFormat::Pretty, 0, &record, None),
+            "1970-01-01T00:00:00.000Z INFO  keeper_satoru::executor Job 0x1a paused"
+        );
+        let line: serde_json::Value =
+            serde_json::from_str(&format_record(LogFormat::Json, 0, &record, None)).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "Job 0x1a paused");
+        assert!(line.get("origin").is_none());
+
+        assert!(
+            format_record(LogFormat::Pretty, 0, &record, Some("GET /pnl"))
+                .ends_with("Job 0x1a paused (from GET /pnl)")
+        );
+        let line: serde_json::Value = serde_json::from_str(&format_record(
+            LogFormat::Json,
+            0,
+            &record,
+            Some("GET /pnl"),
+        ))
+        .unwrap();
+        assert_eq!(line["origin"], "GET /pnl");
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let directory = std::env::temp_dir().join(format!("keeper-logs-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("keeper.log");
+        let mut file = RotatingFile::open(&path, Some(10), None, 2).unwrap();
+
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(file.rotated_path(1)).unwrap(), "third\n");
+        assert_eq!(
+            fs::read_to_string(file.rotated_path(2)).unwrap(),
+            "second\n"
+        );
+        assert!(!file.rotated_path(3).exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
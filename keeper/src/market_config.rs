@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use cainome::cairo_serde::U256;
+use starknet::core::{types::FieldElement, utils::cairo_short_string_to_felt};
+use starknet_crypto::poseidon_hash_many;
+
+use crate::{
+    config, contracts::KeeperAccount, error::KeeperError, preview::PreviewParams,
+    trade::order::handle::DataStore,
+};
+
+// Decimals of the factors stored in the DataStore, as precision::FLOAT_PRECISION.
+const FACTOR_DECIMALS: i32 = 20;
+
+// Computes the DataStore key of a market parameter, as the keys module does, the parameter name
+// hashed with the market and the other arguments of the key.
+// @name: The parameter name, e.g. POSITION_FEE_FACTOR.
+// @market: The market token address.
+// @args: The other arguments, e.g. whether the factor applies to positive impacts.
+pub fn market_key(name: &str, market: FieldElement, args: &[FieldElement]) -> FieldElement {
+    let mut data = vec![
+        cairo_short_string_to_felt(name).expect("Invalid short string"),
+        market,
+    ];
+    data.extend_from_slice(args);
+    poseidon_hash_many(&data)
+}
+
+fn flag(value: bool) -> FieldElement {
+    FieldElement::from(value as u8)
+}
+
+// Converts a factor read from the DataStore to a fraction.
+fn to_factor(value: U256) -> f64 {
+    (value.high as f64 * 2f64.powi(128) + value.low as f64) / 10f64.powi(FACTOR_DECIMALS)
+}
+
+// A struct representing the configuration of a market, as set in the DataStore, its factors as
+// fractions.
+// @positive_position_fee_factor: The fee of the orders improving the open interest balance.
+// @negative_position_fee_factor: The fee of the orders worsening it.
+// @positive_swap_fee_factor: The fee of the swaps improving the pool balance.
+// @negative_swap_fee_factor: The fee of the swaps worsening it.
+// @positive_position_impact_factor: The impact factor of orders improving the open interest balance.
+// @negative_position_impact_factor: The impact factor of orders worsening it.
+// @position_impact_exponent_factor: The exponent the open interest imbalance is raised to.
+// @funding_factor: The funding paid per second, applied to the open interest imbalance raised to the
+// funding exponent.
+// @funding_exponent_factor: The exponent the imbalance is raised to for funding.
+// @long_borrowing_factor: The borrowing fee per second of the longs.
+// @short_borrowing_factor: The borrowing fee per second of the shorts.
+// @min_collateral_factor: The smallest collateral to size ratio of a position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarketConfig {
+    pub positive_position_fee_factor: f64,
+    pub negative_position_fee_factor: f64,
+    pub positive_swap_fee_factor: f64,
+    pub negative_swap_fee_factor: f64,
+    pub positive_position_impact_factor: f64,
+    pub negative_position_impact_factor: f64,
+    pub position_impact_exponent_factor: f64,
+    pub funding_factor: f64,
+    pub funding_exponent_factor: f64,
+    pub long_borrowing_factor: f64,
+    pub short_borrowing_factor: f64,
+    pub min_collateral_factor: f64,
+}
+
+impl MarketConfig {
+    // Returns the keys of the market configuration, in the order of its fields.
+    // @market: The market token address.
+    pub fn keys(market: FieldElement) -> [FieldElement; 12] {
+        [
+            market_key("POSITION_FEE_FACTOR", market, &[flag(true)]),
+            market_key("POSITION_FEE_FACTOR", market, &[flag(false)]),
+            market_key("SWAP_FEE_FACTOR", market, &[flag(true)]),
+            market_key("SWAP_FEE_FACTOR", market, &[flag(false)]),
+            market_key("POSITION_IMPACT_FACTOR", market, &[flag(true)]),
+            market_key("POSITION_IMPACT_FACTOR", market, &[flag(false)]),
+            market_key("POSITION_IMPACT_EXPONENT_FACTOR", market, &[]),
+            market_key("FUNDING_FACTOR", market, &[]),
+            market_key("FUNDING_EXPONENT_FACTOR", market, &[]),
+            market_key("BORROWING_FACTOR", market, &[flag(true)]),
+            market_key("BORROWING_FACTOR", market, &[flag(false)]),
+            market_key("MIN_COLLATERAL_FACTOR", market, &[]),
+        ]
+    }
+
+    // Builds the configuration from the values read at its keys.
+    // @values: The values, in the order of the keys.
+    pub fn from_values(values: [U256; 12]) -> Self {
+        let factors = values.map(to_factor);
+        MarketConfig {
+            positive_position_fee_factor: factors[0],
+            negative_position_fee_factor: factors[1],
+            positive_swap_fee_factor: factors[2],
+            negative_swap_fee_factor: factors[3],
+            positive_position_impact_factor: factors[4],
+            negative_position_impact_factor: factors[5],
+            position_impact_exponent_factor: factors[6],
+            funding_factor: factors[7],
+            funding_exponent_factor: factors[8],
+            long_borrowing_factor: factors[9],
+            short_borrowing_factor: factors[10],
+            min_collateral_factor: factors[11],
+        }
+    }
+
+    // The largest leverage a position opens with, None when the market has no collateral factor.
+    pub fn max_leverage(&self) -> Option<f64> {
+        Some(self.min_collateral_factor)
+            .filter(|factor| *factor > 0.0)
+            .map(|factor| 1.0 / factor)
+    }
+
+    // The parameters orders of the market get previewed with, charged the fee of the orders
+    // worsening the balance, the larger one.
+    pub fn preview_params(&self) -> PreviewParams {
+        PreviewParams {
+            position_fee_factor: self
+                .negative_position_fee_factor
+                .max(self.positive_position_fee_factor),
+            positive_impact_factor: self.positive_position_impact_factor,
+            negative_impact_factor: self.negative_position_impact_factor,
+            impact_exponent: self.position_impact_exponent_factor,
+        }
+    }
+}
+
+// Reads the configuration of a market from the DataStore.
+// @data_store: The DataStore.
+// @market: The market token address.
+pub async fn read_market_config(
+    data_store: &DataStore<KeeperAccount>,
+    market: FieldElement,
+) -> Result<MarketConfig, KeeperError> {
+    let mut values = [U256 { low: 0, high: 0 }; 12];
+    for (value, key) in values.iter_mut().zip(MarketConfig::keys(market)) {
+        *value = data_store.get_u256(&key).call().await.map_err(|e| {
+            KeeperError::MarketConfigError(format!(
+                "could not read the configuration of market {:#x}: {:?}",
+                market, e
+            ))
+        })?;
+    }
+    Ok(MarketConfig::from_values(values))
+}
+
+// A struct caching the configuration of the markets, read from the DataStore at most once per
+// MARKET_CONFIG_CACHE_SECS, shared by everything pricing or liquidating positions.
+// @ttl: How long a configuration gets served before being read again.
+// @configs: The configurations read, with when they got read, by market.
+pub struct MarketConfigs {
+    data_store: DataStore<KeeperAccount>,
+    ttl: Duration,
+    configs: RwLock<HashMap<FieldElement, (Instant, MarketConfig)>>,
+}
+
+impl MarketConfigs {
+    // @data_store: The DataStore the configurations get read from.
+    pub fn new(data_store: DataStore<KeeperAccount>) -> Self {
+        MarketConfigs {
+            data_store,
+            ttl: Duration::from_secs(config::get_market_config_cache_secs()),
+            configs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // Returns the configuration of a market cached less than the cache duration ago, if any.
+    // @market: The market token address.
+    pub fn cached(&self, market: FieldElement) -> Option<MarketConfig> {
+        self.configs
+            .read()
+            .unwrap()
+            .get(&market)
+            .filter(|(read_at, _)| read_at.elapsed() < self.ttl)
+            .map(|(_, config)| config.clone())
+    }
+
+    // Returns the configuration of a market, read again from the DataStore once its cache expired.
+    // @market: The market token address.
+    pub async fn get(&self, market: FieldElement) -> Result<MarketConfig, KeeperError> {
+        if let Some(config) = self.cached(market) {
+            return Ok(config);
+        }
+        let config = read_market_config(&self.data_store, market).await?;
+        self.configs
+            .write()
+            .unwrap()
+            .insert(market, (Instant::now(), config.clone()));
+        Ok(config)
+    }
+
+    // Returns the configuration of a market given as indexed, without 0x prefix.
+    // @market: The market token address, as indexed.
+    pub async fn get_indexed(&self, market: &str) -> Result<MarketConfig, KeeperError> {
+        let market = FieldElement::from_hex_be(market)
+            .map_err(|_| KeeperError::MarketConfigError(format!("invalid market {}", market)))?;
+        self.get(market).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_key() {
+        let market = FieldElement::from(0x12u64);
+        assert_eq!(
+            market_key("POSITION_FEE_FACTOR", market, &[flag(true)]),
+            poseidon_hash_many(&[
+                cairo_short_string_to_felt("POSITION_FEE_FACTOR").unwrap(),
+                market,
+                FieldElement::ONE
+            ])
+        );
+        let keys = MarketConfig::keys(market);
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[0], MarketConfig::keys(FieldElement::from(0x13u64))[0]);
+    }
+
+    #[test]
+    fn test_from_values() {
+        let factor = |bps: u128| U256 {
+            low: bps * 10u128.pow(16),
+            high: 0,
+        };
+        let mut values = [factor(0); 12];
+        values[0] = factor(5);
+        values[1] = factor(7);
+        values[6] = factor(20000);
+        values[11] = factor(100);
+        let config = MarketConfig::from_values(values);
+        assert_eq!(config.positive_position_fee_factor, 0.0005);
+        assert_eq!(config.position_impact_exponent_factor, 2.0);
+        assert_eq!(config.max_leverage(), Some(100.0));
+        assert_eq!(config.preview_params().position_fee_factor, 0.0007);
+        assert_eq!(MarketConfig::default().max_leverage(), None);
+        assert!(to_factor(U256 { low: 0, high: 1 }) > 3.4e18);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{sync::Arc, time::Duration};
+
+use log::error;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+use tokio::time::sleep;
+
+use crate::{
+    config,
+    dashboard::{get_indexer_lag, read_chain_status, ChainStatus},
+    executor::KeeperContext,
+};
+
+// Seconds in a day.
+const DAY_SECS: u64 = 86_400;
+
+// A struct representing how the keeper metrics get snapshotted.
+// @interval: The delay between two snapshots, metrics never snapshotted when None.
+// @retention: How long snapshots are kept, forever when None.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricsParams {
+    pub interval: Option<Duration>,
+    pub retention: Option<Duration>,
+}
+
+impl MetricsParams {
+    pub fn from_env() -> Self {
+        MetricsParams {
+            interval: config::get_metrics_history_interval_secs().map(Duration::from_secs),
+            retention: config::get_metrics_history_retention_days()
+                .map(|days| Duration::from_secs(days.saturating_mul(DAY_SECS))),
+        }
+    }
+}
+
+// A struct representing the jobs of the keeper_jobs table, the finished ones over a window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, sqlx::FromRow)]
+pub struct JobMetrics {
+    pub claimed_jobs: i64,
+    pub submitted_jobs: i64,
+    pub executed_jobs: i64,
+    pub failed_jobs: i64,
+}
+
+// A struct representing a snapshot of the keeper metrics, as stored in the
+// keeper_metrics_history table.
+// @chain: What the keeper read from the chain, empty when the read failed.
+// @indexed_block: The last block indexed.
+// @pending_actions: The actions indexed but neither executed nor cancelled yet.
+// @jobs: The jobs in flight, and the ones finished since the previous snapshot.
+// @running_executions: The executions sent, when the queue limits them.
+// @queued_executions: The executions waiting for a slot to be sent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    pub chain: ChainStatus,
+    pub indexed_block: Option<i64>,
+    pub pending_actions: i64,
+    pub jobs: JobMetrics,
+    pub running_executions: usize,
+    pub queued_executions: usize,
+}
+
+impl MetricsSnapshot {
+    // Returns the share of the finished jobs which executed their action, None when no job
+    // finished.
+    pub fn success_rate(&self) -> Option<f64> {
+        let finished = self.jobs.executed_jobs + self.jobs.failed_jobs;
+        match finished {
+            0 => None,
+            finished => Some(self.jobs.executed_jobs as f64 / finished as f64),
+        }
+    }
+}
+
+// Counts the jobs in flight and the ones which finished over a window.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @window_secs: The window the finished jobs get counted over.
+pub async fn get_job_metrics(
+    pool: &Pool<Postgres>,
+    window_secs: u64,
+) -> Result<JobMetrics, sqlx::Error> {
+    sqlx::query_as::<_, JobMetrics>(
+        "SELECT COUNT(*) FILTER (WHERE status = 'claimed') AS claimed_jobs,
+             COUNT(*) FILTER (WHERE status = 'submitted') AS submitted_jobs,
+             COUNT(*) FILTER (WHERE status = 'done'
+                 AND updated_at > NOW() - make_interval(secs => $1)) AS executed_jobs,
+             COUNT(*) FILTER (WHERE status = 'failed'
+                 AND updated_at > NOW() - make_interval(secs => $1)) AS failed_jobs
+         FROM keeper_jobs",
+    )
+    .bind(window_secs as f64)
+    .fetch_one(pool)
+    .await
+}
+
+// Takes a snapshot of the keeper metrics, the chain metrics being left empty when the chain
+// cannot be read so the database ones still get recorded.
+// @context: The keeper context.
+// @fee_token: The token the keeper pays its fees in.
+// @window_secs: The window the finished jobs get counted over.
+pub async fn take_snapshot(
+    context: &KeeperContext,
+    fee_token: FieldElement,
+    window_secs: u64,
+) -> Result<MetricsSnapshot, sqlx::Error> {
+    let chain = match read_chain_status(&context.account, fee_token).await {
+        Ok(chain) => chain,
+        Err(e) => {
+            error!("{}", e);
+            ChainStatus::default()
+        }
+    };
+    let indexed_block: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(block_number) FROM last_indexed_block")
+            .fetch_one(&context.pool)
+            .await?;
+    let pending_actions: i64 =
+        sqlx::query_scalar("SELECT COALESCE(SUM(pending), 0)::BIGINT FROM keeper_backlog")
+            .fetch_one(&context.pool)
+            .await?;
+    let jobs = get_job_metrics(&context.pool, window_secs).await?;
+    let (running_executions, queued_executions) = context.queue.depth();
+    Ok(MetricsSnapshot {
+        chain,
+        indexed_block,
+        pending_actions,
+        jobs,
+        running_executions,
+        queued_executions,
+    })
+}
+
+// Records a snapshot of the keeper metrics.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @instance_id: The id of the keeper instance the snapshot got taken by.
+// @snapshot: The snapshot.
+pub async fn record_snapshot(
+    pool: &Pool<Postgres>,
+    instance_id: &str,
+    snapshot: &MetricsSnapshot,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO keeper_metrics_history (instance_id, latest_block, indexed_block, indexer_lag,
+             balance, pending_actions, claimed_jobs, submitted_jobs, running_executions,
+             queued_executions, executed_jobs, failed_jobs, success_rate)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+    )
+    .bind(instance_id)
+    .bind(snapshot.chain.latest_block.map(|block| block as i64))
+    .bind(snapshot.indexed_block)
+    .bind(get_indexer_lag(
+        snapshot.chain.latest_block,
+        snapshot.indexed_block,
+    ))
+    .bind(&snapshot.chain.balance)
+    .bind(snapshot.pending_actions)
+    .bind(snapshot.jobs.claimed_jobs)
+    .bind(snapshot.jobs.submitted_jobs)
+    .bind(snapshot.running_executions as i64)
+    .bind(snapshot.queued_executions as i64)
+    .bind(snapshot.jobs.executed_jobs)
+    .bind(snapshot.jobs.failed_jobs)
+    .bind(snapshot.success_rate())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Deletes the snapshots older than the retention, returns how many got deleted.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @retention_secs: How long snapshots are kept.
+pub async fn prune_metrics_history(
+    pool: &Pool<Postgres>,
+    retention_secs: u64,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "DELETE FROM keeper_metrics_history WHERE recorded_at < NOW() - make_interval(secs => $1)",
+    )
+    .bind(retention_secs as f64)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Snapshots the keeper metrics every interval into the keeper_metrics_history table, pruning the
+// snapshots past the retention, so incidents can be looked into without a monitoring stack.
+// @context: The keeper context.
+// @params: How the metrics get snapshotted.
+// @instance_id: The id of the keeper instance.
+pub async fn run_metrics_history(
+    context: Arc<KeeperContext>,
+    params: MetricsParams,
+    instance_id: String,
+) {
+    let interval = match params.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    let fee_token = FieldElement::from_hex_be(&config::get_fee_token_address())
+        .expect("Invalid fee token address");
+    loop {
+        sleep(interval).await;
+        let recorded = match take_snapshot(&context, fee_token, interval.as_secs()).await {
+            Ok(snapshot) => record_snapshot(&context.pool, &instance_id, &snapshot).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recorded {
+            error!("Could not snapshot keeper metrics: {:?}", e);
+        }
+        if let Some(retention) = params.retention {
+            if let Err(e) = prune_metrics_history(&context.pool, retention.as_secs()).await {
+                error!("Could not prune keeper metrics: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_success_rate() {
+        let snapshot = |executed_jobs, failed_jobs| MetricsSnapshot {
+            chain: ChainStatus::default(),
+            indexed_block: None,
+            pending_actions: 0,
+            jobs: JobMetrics {
+                executed_jobs,
+                failed_jobs,
+                ..JobMetrics::default()
+            },
+            running_executions: 0,
+            queued_executions: 0,
+        };
+        assert_eq!(snapshot(3, 1).success_rate(), Some(0.75));
+        assert_eq!(snapshot(0, 2).success_rate(), Some(0.0));
+        assert_eq!(snapshot(0, 0).success_rate(), None);
+    }
+}
@@ -0,0 +1,526 @@
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use starknet::{
+    accounts::Call,
+    core::{
+        types::FieldElement,
+        utils::{cairo_short_string_to_felt, get_selector_from_name, starknet_keccak},
+    },
+    signers::{LocalWallet, Signer},
+};
+use starknet_crypto::poseidon_hash_many;
+
+use crate::{config, error::KeeperError, trade::crash::CrashMode};
+
+// SNIP-12 revision 1 types of a SNIP-9 version 2 outside execution.
+const DOMAIN_TYPE: &str = "\"StarknetDomain\"(\"name\":\"shortstring\",\"version\":\"shortstring\",\"chainId\":\"shortstring\",\"revision\":\"shortstring\")";
+const CALL_TYPE: &str =
+    "\"Call\"(\"To\":\"ContractAddress\",\"Selector\":\"selector\",\"Calldata\":\"felt*\")";
+const OUTSIDE_EXECUTION_TYPE: &str = "\"OutsideExecution\"(\"Caller\":\"ContractAddress\",\"Nonce\":\"felt\",\"Execute After\":\"u128\",\"Execute Before\":\"u128\",\"Calls\":\"Call*\")";
+const DOMAIN_NAME: &str = "Account.execute_from_outside";
+
+// A struct representing a SNIP-9 outside execution, which the paymaster sends on behalf of the
+// keeper account once signed by it.
+// @caller: The only address allowed to send the execution.
+// @nonce: The outside execution nonce, never reused.
+// @execute_after: The unix timestamp after which it can be sent.
+// @execute_before: The unix timestamp before which it must be sent.
+// @calls: The calls executed by the keeper account.
+#[derive(Debug, Clone)]
+pub struct OutsideExecution {
+    pub caller: FieldElement,
+    pub nonce: FieldElement,
+    pub execute_after: u64,
+    pub execute_before: u64,
+    pub calls: Vec<Call>,
+}
+
+impl OutsideExecution {
+    // Reads the outside execution message of SNIP-12 typed data built by the paymaster.
+    pub fn from_typed_data(typed_data: &Value) -> Result<Self, KeeperError> {
+        if typed_data["primaryType"] != "OutsideExecution" {
+            return Err(paymaster_error("typed data is not an outside execution"));
+        }
+        let message = &typed_data["message"];
+        let calls = message["Calls"]
+            .as_array()
+            .ok_or_else(|| paymaster_error("typed data without calls"))?
+            .iter()
+            .map(|call| {
+                Ok(Call {
+                    to: parse_felt(&call["To"])?,
+                    selector: parse_felt(&call["Selector"])?,
+                    calldata: call["Calldata"]
+                        .as_array()
+                        .ok_or_else(|| paymaster_error("call without calldata"))?
+                        .iter()
+                        .map(parse_felt)
+                        .collect::<Result<Vec<_>, _>>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, KeeperError>>()?;
+        Ok(OutsideExecution {
+            caller: parse_felt(&message["Caller"])?,
+            nonce: parse_felt(&message["Nonce"])?,
+            execute_after: parse_u64(&message["Execute After"])?,
+            execute_before: parse_u64(&message["Execute Before"])?,
+            calls,
+        })
+    }
+
+    // Computes the SNIP-12 revision 1 message hash the keeper account signs.
+    // @chain_id: The chain id of the network.
+    // @account: The keeper account address.
+    pub fn message_hash(&self, chain_id: FieldElement, account: FieldElement) -> FieldElement {
+        let domain_hash = poseidon_hash_many(&[
+            starknet_keccak(DOMAIN_TYPE.as_bytes()),
+            cairo_short_string_to_felt(DOMAIN_NAME).expect("Invalid short string"),
+            FieldElement::TWO,
+            chain_id,
+            FieldElement::ONE,
+        ]);
+        let call_type_hash = starknet_keccak(CALL_TYPE.as_bytes());
+        let calls_hashes: Vec<FieldElement> = self
+            .calls
+            .iter()
+            .map(|call| {
+                poseidon_hash_many(&[
+                    call_type_hash,
+                    call.to,
+                    call.selector,
+                    poseidon_hash_many(&call.calldata),
+                ])
+            })
+            .collect();
+        let outside_execution_type = format!("{}{}", OUTSIDE_EXECUTION_TYPE, CALL_TYPE);
+        let struct_hash = poseidon_hash_many(&[
+            starknet_keccak(outside_execution_type.as_bytes()),
+            self.caller,
+            self.nonce,
+            FieldElement::from(self.execute_after),
+            FieldElement::from(self.execute_before),
+            poseidon_hash_many(&calls_hashes),
+        ]);
+        poseidon_hash_many(&[
+            cairo_short_string_to_felt("StarkNet Message").expect("Invalid short string"),
+            domain_hash,
+            account,
+            struct_hash,
+        ])
+    }
+}
+
+// Calls do not implement PartialEq.
+fn same_call(call: &Call, other: &Call) -> bool {
+    call.to == other.to && call.selector == other.selector && call.calldata == other.calldata
+}
+
+fn paymaster_error(reason: &str) -> KeeperError {
+    KeeperError::PaymasterError(reason.to_owned())
+}
+
+fn parse_felt(value: &Value) -> Result<FieldElement, KeeperError> {
+    let value = value
+        .as_str()
+        .ok_or_else(|| paymaster_error("expected a felt string"))?;
+    match value.starts_with("0x") {
+        true => FieldElement::from_hex_be(value),
+        false => FieldElement::from_dec_str(value),
+    }
+    .map_err(|_| KeeperError::PaymasterError(format!("invalid felt {}", value)))
+}
+
+fn parse_u64(value: &Value) -> Result<u64, KeeperError> {
+    let bytes = parse_felt(value)?.to_bytes_be();
+    if bytes[..24].iter().any(|byte| *byte != 0) {
+        return Err(paymaster_error("timestamp out of range"));
+    }
+    Ok(u64::from_be_bytes(bytes[24..].try_into().unwrap()))
+}
+
+// A struct representing how executions get paid through the paymaster.
+// @url: The SNIP-29 JSON-RPC endpoint of the paymaster.
+// @api_key: The paymaster API key, if any.
+// @gas_token: The token fees are paid in, sponsored executions when None.
+// @fee_recipient: The only address the fees may be transferred to, set along the gas token.
+// @max_fee: The largest fee paid per execution in gas token units, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymasterConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub gas_token: Option<FieldElement>,
+    pub fee_recipient: Option<FieldElement>,
+    pub max_fee: Option<u128>,
+}
+
+impl PaymasterConfig {
+    // None when no paymaster is configured, executions are then paid by the keeper account.
+    pub fn from_env() -> Option<Self> {
+        let url = config::get_paymaster_url()?;
+        let gas_token = config::get_paymaster_gas_token().map(|gas_token| {
+            FieldElement::from_hex_be(&gas_token).expect("Invalid paymaster gas token")
+        });
+        // Paid executions transfer the fee to an address the keeper knows beforehand.
+        let fee_recipient = gas_token.map(|_| {
+            let fee_recipient = config::get_paymaster_fee_recipient()
+                .expect("PAYMASTER_FEE_RECIPIENT must be set along PAYMASTER_GAS_TOKEN");
+            FieldElement::from_hex_be(&fee_recipient).expect("Invalid paymaster fee recipient")
+        });
+        Some(PaymasterConfig {
+            url,
+            api_key: config::get_paymaster_api_key(),
+            gas_token,
+            fee_recipient,
+            max_fee: config::get_paymaster_max_fee(),
+        })
+    }
+
+    fn fee_mode(&self) -> Value {
+        match self.gas_token {
+            Some(gas_token) => {
+                json!({ "mode": "default", "gas_token": format!("{:#x}", gas_token) })
+            }
+            None => json!({ "mode": "sponsored" }),
+        }
+    }
+
+    // Checks the paymaster executes the keeper calls and nothing else, besides the fee transfer in
+    // the gas token to the fee recipient, within the max fee, when fees are not sponsored.
+    // @calls: The calls sent to the paymaster.
+    // @outside_execution: The outside execution built by the paymaster.
+    pub fn check_outside_execution(
+        &self,
+        calls: &[Call],
+        outside_execution: &OutsideExecution,
+    ) -> Result<(), KeeperError> {
+        let built_calls = &outside_execution.calls;
+        let own_calls_match = built_calls.len() >= calls.len()
+            && calls
+                .iter()
+                .zip(built_calls)
+                .all(|(call, built_call)| same_call(call, built_call));
+        if !own_calls_match {
+            return Err(paymaster_error(
+                "outside execution does not match the calls",
+            ));
+        }
+        match (&built_calls[calls.len()..], self.gas_token) {
+            ([], _) => Ok(()),
+            ([fee_transfer], Some(gas_token)) => {
+                if fee_transfer.to != gas_token
+                    || fee_transfer.selector != get_selector_from_name("transfer").unwrap()
+                {
+                    return Err(paymaster_error("unexpected call in outside execution"));
+                }
+                // transfer(recipient, amount) with the amount as a u256 of two limbs.
+                let (recipient, fee) = match fee_transfer.calldata.as_slice() {
+                    [recipient, fee, fee_high] if *fee_high == FieldElement::ZERO => (
+                        *recipient,
+                        u128::try_from(*fee)
+                            .map_err(|_| paymaster_error("invalid fee transfer"))?,
+                    ),
+                    _ => return Err(paymaster_error("invalid fee transfer")),
+                };
+                if Some(recipient) != self.fee_recipient {
+                    return Err(KeeperError::PaymasterError(format!(
+                        "fee transferred to {:#x} instead of the fee recipient",
+                        recipient
+                    )));
+                }
+                match self.max_fee {
+                    Some(max_fee) if fee > max_fee => Err(KeeperError::PaymasterError(format!(
+                        "fee {} above the max fee {}",
+                        fee, max_fee
+                    ))),
+                    _ => Ok(()),
+                }
+            }
+            _ => Err(paymaster_error("unexpected call in outside execution")),
+        }
+    }
+}
+
+// A struct representing the keeper account sending its executions through a paymaster.
+// @config: The paymaster configuration.
+// @signer: The key signing the outside executions.
+// @address: The keeper account address.
+// @chain_id: The chain id of the network.
+// @crash_mode: The flash crash mode, raising the max fee while engaged.
+pub struct PaymasterAccount {
+    pub config: PaymasterConfig,
+    signer: LocalWallet,
+    address: FieldElement,
+    chain_id: FieldElement,
+    crash_mode: Arc<CrashMode>,
+    client: reqwest::Client,
+}
+
+impl PaymasterAccount {
+    pub fn new(
+        config: PaymasterConfig,
+        signer: LocalWallet,
+        address: FieldElement,
+        chain_id: FieldElement,
+        crash_mode: Arc<CrashMode>,
+    ) -> Self {
+        PaymasterAccount {
+            config,
+            signer,
+            address,
+            chain_id,
+            crash_mode,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, KeeperError> {
+        let mut request = self.client.post(&self.config.url).json(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }));
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header("x-paymaster-api-key", api_key);
+        }
+        let response: Value = request
+            .send()
+            .await
+            .map_err(|e| KeeperError::PaymasterError(format!("{} failed: {}", method, e)))?
+            .json()
+            .await
+            .map_err(|e| KeeperError::PaymasterError(format!("{} failed: {}", method, e)))?;
+        match response.get("error") {
+            Some(error) => Err(KeeperError::PaymasterError(format!(
+                "{} failed: {}",
+                method, error
+            ))),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    // Sends a multicall through the paymaster, returns its transaction hash.
+    // @calls: The calls of the multicall.
+    pub async fn send(&self, calls: Vec<Call>) -> Result<FieldElement, KeeperError> {
+        let parameters = json!({ "version": "0x1", "fee_mode": self.config.fee_mode() });
+        let built = self
+            .request(
+                "paymaster_buildTransaction",
+                json!({
+                    "transaction": {
+                        "type": "invoke",
+                        "invoke": {
+                            "user_address": format!("{:#x}", self.address),
+                            "calls": calls.iter().map(|call| json!({
+                                "to": format!("{:#x}", call.to),
+                                "selector": format!("{:#x}", call.selector),
+                                "calldata": call.calldata.iter().map(|felt| format!("{:#x}", felt)).collect::<Vec<_>>(),
+                            })).collect::<Vec<_>>(),
+                        },
+                    },
+                    "parameters": parameters,
+                }),
+            )
+            .await?;
+        let typed_data = &built["typed_data"];
+        let outside_execution = OutsideExecution::from_typed_data(typed_data)?;
+        // The max fee gets raised during flash crashes, liquidations outbidding the congestion.
+        PaymasterConfig {
+            max_fee: self.crash_mode.fee_cap(self.config.max_fee),
+            ..self.config.clone()
+        }
+        .check_outside_execution(&calls, &outside_execution)?;
+
+        let message_hash = outside_execution.message_hash(self.chain_id, self.address);
+        let signature = self
+            .signer
+            .sign_hash(&message_hash)
+            .await
+            .map_err(|e| KeeperError::PaymasterError(format!("signature failed: {:?}", e)))?;
+        let signature = [signature.r, signature.s];
+
+        let executed = self
+            .request(
+                "paymaster_executeTransaction",
+                json!({
+                    "transaction": {
+                        "type": "invoke",
+                        "invoke": {
+                            "user_address": format!("{:#x}", self.address),
+                            "typed_data": typed_data,
+                            "signature": signature.iter().map(|felt| format!("{:#x}", felt)).collect::<Vec<_>>(),
+                        },
+                    },
+                    "parameters": parameters,
+                }),
+            )
+            .await?;
+        parse_felt(&executed["transaction_hash"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(to: &str, method: &str, calldata: Vec<FieldElement>) -> Call {
+        Call {
+            to: FieldElement::from_hex_be(to).unwrap(),
+            selector: get_selector_from_name(method).unwrap(),
+            calldata,
+        }
+    }
+
+    fn paymaster_config(gas_token: Option<&str>, max_fee: Option<u128>) -> PaymasterConfig {
+        PaymasterConfig {
+            url: "http://localhost".to_owned(),
+            api_key: None,
+            gas_token: gas_token.map(|gas_token| FieldElement::from_hex_be(gas_token).unwrap()),
+            fee_recipient: gas_token.map(|_| FieldElement::from_hex_be("0x56").unwrap()),
+            max_fee,
+        }
+    }
+
+    fn outside_execution(calls: Vec<Call>) -> OutsideExecution {
+        OutsideExecution {
+            caller: cairo_short_string_to_felt("ANY_CALLER").unwrap(),
+            nonce: FieldElement::from_hex_be("0x1").unwrap(),
+            execute_after: 0,
+            execute_before: 1000,
+            calls,
+        }
+    }
+
+    #[test]
+    fn test_outside_execution_from_typed_data() {
+        let typed_data = json!({
+            "primaryType": "OutsideExecution",
+            "message": {
+                "Caller": "0x414e595f43414c4c4552",
+                "Nonce": "0x1",
+                "Execute After": "0x0",
+                "Execute Before": "1000",
+                "Calls": [{
+                    "To": "0x12",
+                    "Selector": format!("{:#x}", get_selector_from_name("execute_order").unwrap()),
+                    "Calldata": ["0x3"],
+                }],
+            },
+        });
+        let execution = OutsideExecution::from_typed_data(&typed_data).unwrap();
+        let expected = outside_execution(vec![call(
+            "0x12",
+            "execute_order",
+            vec![FieldElement::THREE],
+        )]);
+        assert_eq!(execution.caller, expected.caller);
+        assert_eq!(execution.execute_before, 1000);
+        assert_eq!(execution.calls.len(), 1);
+        assert!(same_call(&execution.calls[0], &expected.calls[0]));
+        assert!(OutsideExecution::from_typed_data(&json!({ "primaryType": "Mail" })).is_err());
+    }
+
+    #[test]
+    fn test_message_hash() {
+        let account = FieldElement::from_hex_be("0x5").unwrap();
+        let chain_id = cairo_short_string_to_felt("SN_SEPOLIA").unwrap();
+        let execution = outside_execution(vec![
+            call("0x12", "execute_order", vec![FieldElement::THREE]),
+            call(
+                "0x34",
+                "transfer",
+                vec![
+                    FieldElement::from_hex_be("0x56").unwrap(),
+                    FieldElement::from(100u8),
+                    FieldElement::ZERO,
+                ],
+            ),
+        ]);
+        // Reference SNIP-12 revision 1 hash of the same typed data.
+        assert_eq!(
+            execution.message_hash(chain_id, account),
+            FieldElement::from_hex_be(
+                "0x583822052e3e01d2d7ddd01cb54e83c0bcca3a5980df4f7d5db9965fdb5de2b"
+            )
+            .unwrap()
+        );
+        let other_execution = outside_execution(vec![call("0x12", "execute_deposit", vec![])]);
+        assert_ne!(
+            execution.message_hash(chain_id, account),
+            other_execution.message_hash(chain_id, account)
+        );
+        assert_ne!(
+            execution.message_hash(chain_id, account),
+            execution.message_hash(FieldElement::TWO, account)
+        );
+    }
+
+    #[test]
+    fn test_check_outside_execution() {
+        let calls = vec![call("0x12", "execute_order", vec![])];
+        let transfer = |recipient: &str, fee: u64, fee_high: u64| {
+            call(
+                "0x34",
+                "transfer",
+                vec![
+                    FieldElement::from_hex_be(recipient).unwrap(),
+                    FieldElement::from(fee),
+                    FieldElement::from(fee_high),
+                ],
+            )
+        };
+        let fee_transfer = |fee: u64| transfer("0x56", fee, 0);
+
+        let sponsored = paymaster_config(None, None);
+        assert!(sponsored
+            .check_outside_execution(&calls, &outside_execution(calls.clone()))
+            .is_ok());
+        assert!(sponsored
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), fee_transfer(10)])
+            )
+            .is_err());
+
+        let paid = paymaster_config(Some("0x34"), Some(100));
+        assert!(paid
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), fee_transfer(100)])
+            )
+            .is_ok());
+        assert!(paid
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), fee_transfer(101)])
+            )
+            .is_err());
+        assert!(paid
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![call("0x12", "execute_deposit", vec![])])
+            )
+            .is_err());
+        // The fee goes to the fee recipient only, and its high limb is never ignored.
+        assert!(paid
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), transfer("0x57", 100, 0)])
+            )
+            .is_err());
+        let uncapped = paymaster_config(Some("0x34"), None);
+        assert!(uncapped
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), fee_transfer(1000)])
+            )
+            .is_ok());
+        assert!(uncapped
+            .check_outside_execution(
+                &calls,
+                &outside_execution(vec![calls[0].clone(), transfer("0x56", 100, 1)])
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+use starknet::core::types::FieldElement;
+
+// An enum representing the periods the keeper PnL is aggregated over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PnlPeriod {
+    Daily,
+    Weekly,
+}
+
+impl PnlPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PnlPeriod::Daily => "daily",
+            PnlPeriod::Weekly => "weekly",
+        }
+    }
+
+    // The Postgres date_trunc field matching the period.
+    fn date_trunc_field(&self) -> &'static str {
+        match self {
+            PnlPeriod::Daily => "day",
+            PnlPeriod::Weekly => "week",
+        }
+    }
+}
+
+impl std::str::FromStr for PnlPeriod {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<PnlPeriod, Self::Err> {
+        match input {
+            "daily" => Ok(PnlPeriod::Daily),
+            "weekly" => Ok(PnlPeriod::Weekly),
+            _ => Err(()),
+        }
+    }
+}
+
+// A struct representing the keeper PnL over one period.
+// Amounts are decimal strings of the fee token smallest unit, they do not fit in JSON numbers.
+// @period_start: The start of the period.
+// @transactions: The number of execution transactions sent over the period.
+// @fees_paid: The actual fees paid for those transactions.
+// @fees_earned: The keeper fees earned from the actions they settled, net of their refunds.
+// @pnl: The fees earned minus the fees paid.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeeperPnl {
+    pub period_start: String,
+    pub transactions: i64,
+    pub fees_paid: String,
+    pub fees_earned: String,
+    pub pnl: String,
+}
+
+// Returns a statement adding the rows of its booked CTE to the keeper PnL of every period, each row
+// having the created_at, transactions, fees_paid and fees_earned columns.
+// @ctes: The common table expressions of the statement, booked being one of them.
+pub fn book_keeper_pnl(ctes: &str) -> String {
+    let periods = [PnlPeriod::Daily, PnlPeriod::Weekly]
+        .iter()
+        .map(|period| format!("('{}', '{}')", period.as_str(), period.date_trunc_field()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "WITH {ctes}
+         INSERT INTO keeper_pnl (period, period_start, transactions, fees_paid, fees_earned, pnl)
+         SELECT periods.period, date_trunc(periods.field, created_at), transactions, fees_paid,
+             fees_earned, fees_earned - fees_paid
+         FROM booked CROSS JOIN (VALUES {periods}) periods (period, field)
+         ON CONFLICT (period, period_start) DO UPDATE SET
+             transactions = keeper_pnl.transactions + EXCLUDED.transactions,
+             fees_paid = keeper_pnl.fees_paid + EXCLUDED.fees_paid,
+             fees_earned = keeper_pnl.fees_earned + EXCLUDED.fees_earned,
+             pnl = keeper_pnl.pnl + EXCLUDED.pnl"
+    )
+}
+
+// Records the fee paid for an execution transaction and adds it to the aggregated PnL, once per
+// transaction. The keeper fees it earns get added as the actions it settled get recorded.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action the transaction was sent for.
+// @transaction_hash: The hash of the execution transaction.
+// @actual_fee: The fee paid, read from the transaction receipt.
+pub async fn record_transaction_fee(
+    pool: &Pool<Postgres>,
+    key: &str,
+    transaction_hash: FieldElement,
+    actual_fee: FieldElement,
+) -> Result<(), Error> {
+    sqlx::query(&book_keeper_pnl(
+        "booked AS (
+             INSERT INTO keeper_transaction_fees
+                 (transaction_hash, key, actual_fee, execution_fee_earned)
+             VALUES ($1, $2, $3::NUMERIC, 0) ON CONFLICT (transaction_hash) DO NOTHING
+             RETURNING created_at, 1 AS transactions, actual_fee AS fees_paid,
+                 execution_fee_earned AS fees_earned
+         )",
+    ))
+    .bind(format!("{:#x}", transaction_hash))
+    .bind(key)
+    .bind(actual_fee.to_string())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Loads the keeper PnL over a period, most recent first.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @period: The period the PnL is aggregated over.
+pub async fn get_keeper_pnl(
+    pool: &Pool<Postgres>,
+    period: PnlPeriod,
+) -> Result<Vec<KeeperPnl>, Error> {
+    let rows: Vec<(String, i64, String, String, String)> = sqlx::query_as(
+        "SELECT period_start::TEXT, transactions, fees_paid::TEXT, fees_earned::TEXT, pnl::TEXT
+         FROM keeper_pnl WHERE period = $1 ORDER BY period_start DESC",
+    )
+    .bind(period.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(period_start, transactions, fees_paid, fees_earned, pnl)| KeeperPnl {
+                period_start,
+                transactions,
+                fees_paid,
+                fees_earned,
+                pnl,
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pnl_period_round_trip() {
+        for period in [PnlPeriod::Daily, PnlPeriod::Weekly] {
+            assert_eq!(period.as_str().parse::<PnlPeriod>(), Ok(period));
+        }
+        assert!("monthly".parse::<PnlPeriod>().is_err());
+    }
+
+    #[test]
+    fn test_book_keeper_pnl() {
+        let statement = book_keeper_pnl("booked AS (SELECT 1)");
+        assert!(statement.starts_with("WITH booked AS (SELECT 1)"));
+        assert!(statement.contains("(VALUES ('daily', 'day'), ('weekly', 'week'))"));
+    }
+}
@@ -0,0 +1,284 @@
+use serde::Serialize;
+use sqlx::{error::Error, Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::{competition::to_indexed_address, history::scale_amount};
+
+// Decimals of the USD amounts and prices of the protocol.
+const USD_DECIMALS: u32 = 30;
+// Most accounts a portfolio gets computed over, e.g. the sub-accounts of a fund.
+pub const MAX_PORTFOLIO_ACCOUNTS: usize = 100;
+
+// Parses a comma separated list of accounts, returning them as indexed without duplicates.
+// @accounts: The accounts, as hex addresses.
+pub fn parse_accounts(accounts: &str) -> Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = vec![];
+    for account in accounts
+        .split(',')
+        .map(str::trim)
+        .filter(|account| !account.is_empty())
+    {
+        let account = FieldElement::from_hex_be(account)
+            .map_err(|_| format!("invalid account {}", account))?;
+        let account = to_indexed_address(account);
+        if !parsed.contains(&account) {
+            parsed.push(account);
+        }
+    }
+    match parsed.len() {
+        0 => Err("no account given".to_owned()),
+        count if count > MAX_PORTFOLIO_ACCOUNTS => Err(format!(
+            "{} accounts given, at most {} allowed",
+            count, MAX_PORTFOLIO_ACCOUNTS
+        )),
+        _ => Ok(parsed),
+    }
+}
+
+// A struct representing the combined open positions of the accounts on a market, in USD.
+// @market: The market, as indexed.
+// @long_size_usd: The size of the long positions.
+// @short_size_usd: The size of the short positions.
+// @net_size_usd: The long size minus the short size.
+// @pending_borrowing_fees_usd: The borrowing fees the positions still have to pay.
+// @positions: The number of open positions.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct MarketExposure {
+    pub market: String,
+    pub long_size_usd: String,
+    pub short_size_usd: String,
+    pub net_size_usd: String,
+    pub pending_borrowing_fees_usd: String,
+    pub positions: i64,
+}
+
+// A struct representing an amount of a token, combined over the accounts.
+// @token: The token address, as indexed.
+// @symbol: The symbol of the token in the tokens table, if any.
+// @amount: The amount, scaled by the token decimals when known, raw otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenAmount {
+    pub token: String,
+    pub symbol: Option<String>,
+    pub amount: String,
+}
+
+// A struct representing a raw token amount read from the tables.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+struct TokenAmountRow {
+    token: String,
+    symbol: Option<String>,
+    decimals: Option<i32>,
+    amount: String,
+}
+
+impl From<TokenAmountRow> for TokenAmount {
+    fn from(row: TokenAmountRow) -> Self {
+        TokenAmount {
+            amount: match row.decimals {
+                Some(decimals) => scale_amount(&row.amount, decimals as u32),
+                None => row.amount,
+            },
+            token: row.token,
+            symbol: row.symbol,
+        }
+    }
+}
+
+// A struct representing the PnL of the accounts from the fees of their positions, the indexer not
+// decoding execution prices.
+// @funding: The funding received minus the funding paid, per collateral token, the archived
+// funding included.
+// @borrowing_fees_usd: The borrowing fees accrued, negative as paid.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PortfolioPnl {
+    pub funding: Vec<TokenAmount>,
+    pub borrowing_fees_usd: String,
+}
+
+// A struct representing an order of the accounts neither executed nor cancelled yet.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct PendingOrder {
+    pub key: String,
+    pub account: String,
+    pub market: String,
+    pub order_type: Option<String>,
+    pub is_long: Option<bool>,
+    pub size_delta_usd: Option<String>,
+    pub trigger_price: Option<String>,
+    pub block_number: i64,
+}
+
+// A struct representing the combined portfolio of accounts, e.g. the sub-accounts of a fund.
+// @accounts: The accounts, as indexed.
+// @exposure: The open positions per market, largest first.
+// @collateral: The collateral of the open positions per token, deposits minus withdrawals.
+// @pnl: The PnL from the fees of the positions.
+// @pending_orders: The orders neither executed nor cancelled, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Portfolio {
+    pub accounts: Vec<String>,
+    pub exposure: Vec<MarketExposure>,
+    pub collateral: Vec<TokenAmount>,
+    pub pnl: PortfolioPnl,
+    pub pending_orders: Vec<PendingOrder>,
+}
+
+// Loads the combined portfolio of accounts from the indexed tables, in one query per section
+// whatever the number of accounts.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @accounts: The accounts, as indexed.
+pub async fn get_portfolio(pool: &Pool<Postgres>, accounts: &[String]) -> Result<Portfolio, Error> {
+    let mut exposure = sqlx::query_as::<_, MarketExposure>(
+        "SELECT market,
+             COALESCE(SUM(size_in_usd) FILTER (WHERE is_long), 0)::TEXT AS long_size_usd,
+             COALESCE(SUM(size_in_usd) FILTER (WHERE NOT is_long), 0)::TEXT AS short_size_usd,
+             SUM(CASE WHEN is_long THEN size_in_usd ELSE -size_in_usd END)::TEXT
+             AS net_size_usd,
+             SUM(pending_borrowing_fees_usd)::TEXT AS pending_borrowing_fees_usd,
+             COUNT(*) AS positions
+         FROM position_borrowing_fees
+         WHERE account = ANY($1)
+         GROUP BY market
+         ORDER BY SUM(size_in_usd) DESC",
+    )
+    .bind(accounts)
+    .fetch_all(pool)
+    .await?;
+    for market in &mut exposure {
+        for amount in [
+            &mut market.long_size_usd,
+            &mut market.short_size_usd,
+            &mut market.net_size_usd,
+            &mut market.pending_borrowing_fees_usd,
+        ] {
+            *amount = scale_amount(amount, USD_DECIMALS);
+        }
+    }
+
+    // Token addresses are compared as felts, whatever their prefix and padding.
+    let collateral = sqlx::query_as::<_, TokenAmountRow>(
+        "WITH tokens_by_felt AS (
+             SELECT ltrim(regexp_replace(lower(address), '^0x', ''), '0') AS felt, symbol, decimals
+             FROM tokens
+         ),
+         positions AS (
+             SELECT o.initial_collateral_token AS collateral_token,
+                 SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease')
+                     THEN o.size_delta_usd ELSE -o.size_delta_usd END) AS size_in_usd,
+                 SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease')
+                     THEN o.initial_collateral_delta_amount
+                     ELSE -o.initial_collateral_delta_amount END) AS collateral_amount
+             FROM orders o
+             JOIN order_executed oe ON oe.key = o.key
+             WHERE o.account = ANY(SELECT felt_in(a) FROM unnest($1) a)
+                 AND o.order_type IN ('MarketIncrease', 'LimitIncrease', 'MarketDecrease',
+                     'LimitDecrease', 'StopLossDecrease', 'Liquidation')
+             GROUP BY o.account, o.market, o.initial_collateral_token, o.is_long
+         )
+         SELECT p.collateral_token AS token, t.symbol, t.decimals,
+             GREATEST(SUM(COALESCE(p.collateral_amount, 0)), 0)::TEXT AS amount
+         FROM positions p
+         LEFT JOIN tokens_by_felt t
+             ON t.felt = ltrim(regexp_replace(lower(p.collateral_token), '^0x', ''), '0')
+         WHERE p.size_in_usd > 0
+         GROUP BY p.collateral_token, t.symbol, t.decimals
+         ORDER BY p.collateral_token",
+    )
+    .bind(accounts)
+    .fetch_all(pool)
+    .await?;
+
+    let funding = sqlx::query_as::<_, TokenAmountRow>(
+        "WITH tokens_by_felt AS (
+             SELECT ltrim(regexp_replace(lower(address), '^0x', ''), '0') AS felt, symbol, decimals
+             FROM tokens
+         )
+         SELECT f.collateral_token AS token, t.symbol, t.decimals,
+             SUM(CASE WHEN f.direction = 'paid' THEN -f.amount ELSE f.amount END)::TEXT AS amount
+         FROM (
+             SELECT account, collateral_token, direction, amount FROM funding_payments
+             UNION ALL
+             SELECT account, collateral_token, direction, amount FROM archived_daily_funding
+         ) f
+         LEFT JOIN tokens_by_felt t
+             ON t.felt = ltrim(regexp_replace(lower(f.collateral_token), '^0x', ''), '0')
+         WHERE f.account = ANY($1)
+         GROUP BY f.collateral_token, t.symbol, t.decimals
+         ORDER BY f.collateral_token",
+    )
+    .bind(accounts)
+    .fetch_all(pool)
+    .await?;
+    let borrowing_fees_usd: String = sqlx::query_scalar(
+        "SELECT (-COALESCE(SUM(amount_usd), 0))::TEXT FROM borrowing_fee_accruals
+         WHERE account = ANY($1)",
+    )
+    .bind(accounts)
+    .fetch_one(pool)
+    .await?;
+
+    let pending_orders = sqlx::query_as::<_, PendingOrder>(
+        "SELECT felt_out(o.key) AS key, felt_out(o.account) AS account,
+             felt_out(o.market) AS market, o.order_type, o.is_long,
+             o.size_delta_usd::TEXT AS size_delta_usd, o.trigger_price::TEXT AS trigger_price,
+             o.block_number
+         FROM orders o
+         WHERE o.account = ANY(SELECT felt_in(a) FROM unnest($1) a)
+             AND NOT EXISTS (SELECT 1 FROM order_executed e WHERE e.key = o.key)
+             AND NOT EXISTS (SELECT 1 FROM order_cancelled c WHERE c.key = o.key)
+         ORDER BY o.block_number",
+    )
+    .bind(accounts)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Portfolio {
+        accounts: accounts.to_vec(),
+        exposure,
+        collateral: collateral.into_iter().map(TokenAmount::from).collect(),
+        pnl: PortfolioPnl {
+            funding: funding.into_iter().map(TokenAmount::from).collect(),
+            borrowing_fees_usd: scale_amount(&borrowing_fees_usd, USD_DECIMALS),
+        },
+        pending_orders,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accounts() {
+        let accounts = parse_accounts("0x1a, 0x01a,1b,").unwrap();
+        assert_eq!(
+            accounts,
+            vec![
+                format!("{}1a", "0".repeat(62)),
+                format!("{}1b", "0".repeat(62))
+            ]
+        );
+        assert!(parse_accounts("0x1a,zz").is_err());
+        assert!(parse_accounts(" , ").is_err());
+        let too_many: Vec<String> = (1..=MAX_PORTFOLIO_ACCOUNTS + 1)
+            .map(|account| format!("{:#x}", account))
+            .collect();
+        assert!(parse_accounts(&too_many.join(",")).is_err());
+    }
+
+    #[test]
+    fn test_token_amount() {
+        let amount = |decimals| {
+            TokenAmount::from(TokenAmountRow {
+                token: "0c".to_owned(),
+                symbol: Some("USDC".to_owned()),
+                decimals,
+                amount: "-1500000".to_owned(),
+            })
+            .amount
+        };
+        assert_eq!(amount(Some(6)), "-1.5");
+        assert_eq!(amount(None), "-1500000");
+    }
+}
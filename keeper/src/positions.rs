@@ -0,0 +1,43 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+// A struct representing an open position with the borrowing fees it accrued.
+// @account: The account of the position.
+// @market: The market of the position.
+// @collateral_token: The collateral token of the position.
+// @is_long: Whether the position is long.
+// @size_in_usd: The position size in USD, with 30 decimals.
+// @cumulative_borrowing_fees_usd: The borrowing fees accrued since the position got first opened.
+// @pending_borrowing_fees_usd: The borrowing fees accrued since its last increase or decrease,
+// still to be paid.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct Position {
+    pub account: String,
+    pub market: String,
+    pub collateral_token: String,
+    pub is_long: bool,
+    pub size_in_usd: String,
+    pub cumulative_borrowing_fees_usd: String,
+    pub pending_borrowing_fees_usd: String,
+}
+
+// Loads the open positions derived from the indexed orders, largest first.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account to load the positions of, every account when None.
+pub async fn get_positions(
+    pool: &Pool<Postgres>,
+    account: Option<&str>,
+) -> Result<Vec<Position>, Error> {
+    sqlx::query_as::<_, Position>(
+        "SELECT account, market, collateral_token, is_long, size_in_usd::TEXT AS size_in_usd,
+         cumulative_borrowing_fees_usd::TEXT AS cumulative_borrowing_fees_usd,
+         pending_borrowing_fees_usd::TEXT AS pending_borrowing_fees_usd
+         FROM position_borrowing_fees WHERE $1::TEXT IS NULL OR account = $1
+         ORDER BY position_borrowing_fees.size_in_usd DESC",
+    )
+    .bind(account)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use crate::config;
+
+// A struct representing the open interest of a market per side, in USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, sqlx::FromRow)]
+pub struct OpenInterest {
+    pub long_usd: f64,
+    pub short_usd: f64,
+}
+
+// A struct holding the market parameters orders get priced with.
+// @position_fee_factor: The fee charged on the order size, as a fraction of it.
+// @positive_impact_factor: The impact factor of orders improving the open interest balance.
+// @negative_impact_factor: The impact factor of orders worsening it.
+// @impact_exponent: The exponent the open interest imbalance is raised to.
+#[derive(Debug, Clone, Default)]
+pub struct PreviewParams {
+    pub position_fee_factor: f64,
+    pub positive_impact_factor: f64,
+    pub negative_impact_factor: f64,
+    pub impact_exponent: f64,
+}
+
+// A struct representing a prospective order, amounts in USD not scaled by their decimals.
+// @is_long: Whether the order increases or decreases a long position.
+// @is_increase: Whether the order increases the position.
+// @size_delta_usd: The size of the order.
+// @collateral_usd: The collateral deposited by an increase.
+// @index_price: The current index token price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewOrder {
+    pub is_long: bool,
+    pub is_increase: bool,
+    pub size_delta_usd: f64,
+    pub collateral_usd: f64,
+    pub index_price: f64,
+}
+
+// A struct representing the estimated outcome of an order.
+// @execution_price: The price the order executes at, the index price adjusted by the price impact.
+// @price_impact_usd: The price impact, positive when the order improves the market balance.
+// @position_fee_usd: The position fee charged on the order size.
+// @leverage: The leverage of the position opened by an increase, None for decreases.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderPreview {
+    pub execution_price: f64,
+    pub price_impact_usd: f64,
+    pub position_fee_usd: f64,
+    pub leverage: Option<f64>,
+}
+
+// Loads the open interest of a market from the executed increase and decrease orders, zero for
+// markets without any.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @market: The market, as indexed.
+pub async fn get_open_interest(pool: &Pool<Postgres>, market: &str) -> Result<OpenInterest, Error> {
+    let open_interest = sqlx::query_as::<_, OpenInterest>(
+        "SELECT (long_open_interest_usd / 1e30)::FLOAT8 AS long_usd,
+         (short_open_interest_usd / 1e30)::FLOAT8 AS short_usd
+         FROM market_open_interest WHERE market = $1",
+    )
+    .bind(market)
+    .fetch_optional(pool)
+    .await?;
+    Ok(open_interest.unwrap_or_default())
+}
+
+impl PreviewParams {
+    pub fn from_env() -> Self {
+        let (positive_impact_factor, negative_impact_factor) =
+            config::get_position_impact_factors();
+        PreviewParams {
+            position_fee_factor: config::get_position_fee_factor(),
+            positive_impact_factor,
+            negative_impact_factor,
+            impact_exponent: config::get_position_impact_exponent(),
+        }
+    }
+
+    // Returns the price impact of an order changing the open interest, the difference between the
+    // imbalances before and after it raised to the impact exponent. An order flipping the
+    // imbalance side gets the positive impact of closing it and the negative impact of the new one.
+    // @open_interest: The open interest of the market before the order.
+    // @order: The order.
+    pub fn price_impact(&self, open_interest: &OpenInterest, order: &PreviewOrder) -> f64 {
+        let delta = match order.is_increase {
+            true => order.size_delta_usd,
+            false => -order.size_delta_usd,
+        };
+        let next = match order.is_long {
+            true => OpenInterest {
+                long_usd: open_interest.long_usd + delta,
+                ..*open_interest
+            },
+            false => OpenInterest {
+                short_usd: open_interest.short_usd + delta,
+                ..*open_interest
+            },
+        };
+        let initial_diff = (open_interest.long_usd - open_interest.short_usd).abs();
+        let next_diff = (next.long_usd - next.short_usd).abs();
+        let flipped = (open_interest.long_usd < open_interest.short_usd)
+            != (next.long_usd < next.short_usd)
+            && initial_diff > 0.0
+            && next_diff > 0.0;
+        let initial = initial_diff.powf(self.impact_exponent);
+        let next = next_diff.powf(self.impact_exponent);
+        match (flipped, next_diff < initial_diff) {
+            (true, _) => initial * self.positive_impact_factor - next * self.negative_impact_factor,
+            (false, true) => (initial - next) * self.positive_impact_factor,
+            (false, false) => (initial - next) * self.negative_impact_factor,
+        }
+    }
+
+    // Estimates the execution price, price impact, fees and resulting leverage of an order.
+    // Funding, borrowing and UI fees are not accounted for.
+    // @open_interest: The open interest of the market before the order.
+    // @order: The order.
+    pub fn preview(&self, open_interest: &OpenInterest, order: &PreviewOrder) -> OrderPreview {
+        let price_impact_usd = self.price_impact(open_interest, order);
+        let position_fee_usd = order.size_delta_usd * self.position_fee_factor;
+        // A positive impact gives longs more tokens for their size on increases, and more USD for
+        // their tokens on decreases, the opposite for shorts.
+        let impact = match order.is_long == order.is_increase {
+            true => price_impact_usd,
+            false => -price_impact_usd,
+        };
+        let execution_price = match order.is_increase {
+            true => order.index_price * order.size_delta_usd / (order.size_delta_usd + impact),
+            false => order.index_price * (order.size_delta_usd - impact) / order.size_delta_usd,
+        };
+        let leverage = Some(order.collateral_usd - position_fee_usd)
+            .filter(|collateral| order.is_increase && *collateral > 0.0)
+            .map(|collateral| order.size_delta_usd / collateral);
+        OrderPreview {
+            execution_price,
+            price_impact_usd,
+            position_fee_usd,
+            leverage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> PreviewParams {
+        PreviewParams {
+            position_fee_factor: 0.001,
+            positive_impact_factor: 0.00001,
+            negative_impact_factor: 0.00002,
+            impact_exponent: 2.0,
+        }
+    }
+
+    fn order(is_long: bool, is_increase: bool) -> PreviewOrder {
+        PreviewOrder {
+            is_long,
+            is_increase,
+            size_delta_usd: 1000.0,
+            collateral_usd: 101.0,
+            index_price: 2000.0,
+        }
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let params = params();
+        let balanced = OpenInterest {
+            long_usd: 5000.0,
+            short_usd: 5000.0,
+        };
+        // Worsening the balance by 1000 USD.
+        assert_eq!(params.price_impact(&balanced, &order(true, true)), -20.0);
+        // Closing a 1000 USD imbalance.
+        let short_heavy = OpenInterest {
+            long_usd: 4000.0,
+            short_usd: 5000.0,
+        };
+        assert_eq!(params.price_impact(&short_heavy, &order(true, true)), 10.0);
+        // Flipping a 500 USD short imbalance into a 500 USD long one.
+        let flipped = OpenInterest {
+            long_usd: 4500.0,
+            short_usd: 5000.0,
+        };
+        assert_eq!(params.price_impact(&flipped, &order(true, true)), 2.5 - 5.0);
+    }
+
+    #[test]
+    fn test_preview() {
+        let params = params();
+        let short_heavy = OpenInterest {
+            long_usd: 4000.0,
+            short_usd: 5000.0,
+        };
+        let preview = params.preview(&short_heavy, &order(true, true));
+        assert_eq!(preview.price_impact_usd, 10.0);
+        assert_eq!(preview.position_fee_usd, 1.0);
+        assert_eq!(preview.leverage, Some(10.0));
+        assert!(preview.execution_price < 2000.0);
+
+        // Decreasing a long worsens the balance, the position receiving less than the index price.
+        let preview = params.preview(&short_heavy, &order(true, false));
+        assert_eq!(preview.leverage, None);
+        assert!(preview.price_impact_usd < 0.0);
+        assert!(preview.execution_price < 2000.0);
+    }
+}
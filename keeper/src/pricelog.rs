@@ -0,0 +1,627 @@
+#[cfg(feature = "liquidation")]
+use std::collections::HashMap;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cainome::cairo_serde::ContractAddress;
+use log::error;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::{
+    config,
+    error::KeeperError,
+    trade::{
+        price::{
+            feeds::{FeedId, MarketFeeds},
+            tokens::{TokenInfo, TokenRegistry},
+            utils::PriceInfo,
+        },
+        watchlist::TriggerWatchlist,
+    },
+    types::SatoruAction,
+};
+#[cfg(feature = "liquidation")]
+use crate::{
+    liquidation::LiquidationParams,
+    watch::{get_token_pair, WatchedPosition},
+};
+
+// The recorder set up at startup, prices are not recorded when no log is configured.
+static RECORDER: OnceLock<PriceRecorder> = OnceLock::new();
+
+fn price_log_error(reason: String) -> KeeperError {
+    KeeperError::PriceLogError(reason)
+}
+
+// A struct representing a price read from a feed, as recorded in the price log.
+// @observed_at: When the keeper read the price, a unix timestamp in milliseconds.
+// @base: The base of the pair, e.g. eth.
+// @quote: The quote of the pair, e.g. usd.
+// @price: The raw price.
+// @decimals: The decimals of the raw price.
+// @timestamp: The timestamp of the price on the feed, in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceObservation {
+    pub observed_at: u64,
+    pub base: String,
+    pub quote: String,
+    pub price: u128,
+    pub decimals: u32,
+    pub timestamp: u64,
+}
+
+impl PriceObservation {
+    // Builds the observation of a price returned by Pragma, None when its price is not hex.
+    // @observed_at: When the price got read, in milliseconds.
+    // @base: The base of the pair.
+    // @quote: The quote of the pair.
+    // @price_info: The price returned.
+    pub fn from_price_info(
+        observed_at: u64,
+        base: &str,
+        quote: &str,
+        price_info: &PriceInfo,
+    ) -> Option<Self> {
+        Some(PriceObservation {
+            observed_at,
+            base: base.to_lowercase(),
+            quote: quote.to_lowercase(),
+            price: u128::from_str_radix(price_info.price.trim_start_matches("0x"), 16).ok()?,
+            decimals: price_info.decimals as u32,
+            timestamp: price_info.timestamp,
+        })
+    }
+
+    // Returns the observation as a line of the price log, formatted as observed_at base/quote price
+    // decimals timestamp, e.g. 1711110660123 eth/usd 0x4f8b06508e 8 1711110660000.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {}/{} {:#x} {} {}",
+            self.observed_at, self.base, self.quote, self.price, self.decimals, self.timestamp
+        )
+    }
+
+    // Parses a line of the price log.
+    // @line: The line, without line break.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (observed_at, pair, price, decimals, timestamp) =
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [observed_at, pair, price, decimals, timestamp] => {
+                    (observed_at, pair, price, decimals, timestamp)
+                }
+                _ => return None,
+            };
+        let (base, quote) = pair.split_once('/')?;
+        Some(PriceObservation {
+            observed_at: observed_at.parse().ok()?,
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+            price: u128::from_str_radix(price.trim_start_matches("0x"), 16).ok()?,
+            decimals: decimals.parse().ok()?,
+            timestamp: timestamp.parse().ok()?,
+        })
+    }
+
+    pub fn pair(&self) -> (String, String) {
+        (self.base.clone(), self.quote.clone())
+    }
+
+    // The price scaled by its decimals.
+    pub fn value(&self) -> f64 {
+        self.price as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
+// A struct appending every price the keeper reads to the price log, so the engines can be fed the
+// exact same prices again with the replay-prices subcommand.
+// @file: The log file, opened in append mode.
+#[derive(Debug)]
+pub struct PriceRecorder {
+    pub file: Mutex<File>,
+}
+
+impl PriceRecorder {
+    pub fn open(path: &str) -> Result<Self, KeeperError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| price_log_error(format!("could not open {}: {}", path, e)))?;
+        Ok(PriceRecorder {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, observation: &PriceObservation) {
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{}", observation.to_line()) {
+            error!("Could not record price: {}", e);
+        }
+    }
+}
+
+// Sets up the price recorder if a price log is configured.
+pub fn init() -> Result<(), KeeperError> {
+    if let Some(path) = config::get_price_log() {
+        let _ = RECORDER.set(PriceRecorder::open(&path)?);
+    }
+    Ok(())
+}
+
+// Records a price read from Pragma, if the recorder is set up.
+// @base: The base of the pair.
+// @quote: The quote of the pair.
+// @price_info: The price returned.
+pub fn record_price(base: &str, quote: &str, price_info: &PriceInfo) {
+    #[cfg(feature = "grpc")]
+    crate::grpc::publish_price(price_info);
+    let recorder = match RECORDER.get() {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let observed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before unix epoch")
+        .as_millis() as u64;
+    match PriceObservation::from_price_info(observed_at, base, quote, price_info) {
+        Some(observation) => recorder.record(&observation),
+        None => error!("Could not record invalid price {}", price_info.price),
+    }
+}
+
+// Reads a price log, oldest observations first, observations read in the same millisecond
+// keeping their order.
+// @path: The path of the log.
+pub fn read_price_log(path: &str) -> Result<Vec<PriceObservation>, KeeperError> {
+    let log = fs::read_to_string(path)
+        .map_err(|e| price_log_error(format!("could not read {}: {}", path, e)))?;
+    let mut observations = log
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            PriceObservation::parse(line)
+                .ok_or_else(|| price_log_error(format!("invalid line {}: {}", index + 1, line)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    observations.sort_by_key(|observation| observation.observed_at);
+    Ok(observations)
+}
+
+// An enum representing what the engines did on a replayed price.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    // The price crossed the trigger price of an order, at the protocol precision.
+    Triggered {
+        key: String,
+        price: u128,
+    },
+    // A position got liquidatable, with how far past its liquidation price the index price is.
+    Liquidatable {
+        position: String,
+        price: f64,
+        distance: f64,
+    },
+    // A liquidatable position got back above its min collateral.
+    Recovered {
+        position: String,
+        price: f64,
+    },
+}
+
+// A struct representing what the engines did on a replayed price, and when.
+// @observed_at: When the price got read, in milliseconds.
+// @event: What the engines did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayedEvent {
+    pub observed_at: u64,
+    pub event: ReplayEvent,
+}
+
+// A struct representing a market whose trigger orders get replayed.
+// @market: The market token address.
+// @pair: The pair its index token gets priced with.
+// @index_token: The index token, trigger prices being at its protocol precision.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerMarket {
+    pub market: FieldElement,
+    pub pair: (String, String),
+    pub index_token: TokenInfo,
+}
+
+// Returns the markets whose trigger orders can be replayed, those whose index token is known to
+// the token registry, priced with the Pragma pair of their market feed or their symbol against USD.
+// @markets: The market token and index token addresses of the indexed markets.
+// @feeds: The oracle feeds of the market tokens.
+// @registry: The tokens the keeper reports prices for.
+pub fn get_trigger_markets(
+    markets: &[(String, String)],
+    feeds: &MarketFeeds,
+    registry: &TokenRegistry,
+) -> Vec<TriggerMarket> {
+    markets
+        .iter()
+        .filter_map(|(market, index_token)| {
+            let index_token = registry
+                .get(ContractAddress::from(
+                    FieldElement::from_hex_be(index_token).ok()?,
+                ))?
+                .clone();
+            let pair = match feeds.get(market).map(|feed| &feed.index.feed) {
+                Some(FeedId::Pragma { base, quote }) => (base.clone(), quote.clone()),
+                #[cfg(feature = "pyth")]
+                Some(FeedId::Pyth { .. }) => return None,
+                None => (index_token.symbol.to_lowercase(), "usd".to_owned()),
+            };
+            Some(TriggerMarket {
+                market: FieldElement::from_hex_be(market).ok()?,
+                pair,
+                index_token,
+            })
+        })
+        .collect()
+}
+
+// Feeds replayed prices to the trigger engine, returning the orders each price crossed.
+// @observations: The prices, oldest first.
+// @markets: The markets whose orders get replayed.
+// @watchlist: The trigger orders, the crossed ones being taken out.
+pub fn replay_triggers(
+    observations: &[PriceObservation],
+    markets: &[TriggerMarket],
+    watchlist: &mut TriggerWatchlist,
+) -> Vec<ReplayedEvent> {
+    let mut events = vec![];
+    for observation in observations {
+        for market in markets.iter().filter(|market| {
+            market.pair.0 == observation.base && market.pair.1 == observation.quote
+        }) {
+            // Observations not fitting a protocol price can not cross any trigger price.
+            let price = match market
+                .index_token
+                .to_protocol_price(observation.price, observation.decimals)
+                .map(|price| price.to_u128())
+            {
+                Ok(Some(price)) => price,
+                _ => continue,
+            };
+            for key in watchlist.take_crossed(market.market, price) {
+                events.push(ReplayedEvent {
+                    observed_at: observation.observed_at,
+                    event: ReplayEvent::Triggered { key, price },
+                });
+            }
+        }
+    }
+    events
+}
+
+// A struct representing a position whose liquidation gets replayed.
+// @market: The market of the position, as a felt in hex.
+// @index_pair: The pair its index token gets priced with.
+// @collateral_pair: The pair its collateral token gets priced with, None when it is the index token.
+// @position: The position.
+#[cfg(feature = "liquidation")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayPosition {
+    pub market: String,
+    pub index_pair: (String, String),
+    pub collateral_pair: Option<(String, String)>,
+    pub position: WatchedPosition,
+}
+
+#[cfg(feature = "liquidation")]
+impl ReplayPosition {
+    // Prices a position with the pairs the account watch prices it with, None when one of its
+    // tokens has no Pragma pair.
+    // @feeds: The oracle feeds of the market tokens.
+    // @position: The position.
+    pub fn new(feeds: &MarketFeeds, position: WatchedPosition) -> Option<Self> {
+        let index_pair = get_token_pair(
+            feeds,
+            &position.market,
+            position.index_token.as_deref()?,
+            position.index_symbol.as_deref(),
+        )?;
+        let collateral_pair = match position.collateral_is_index_token {
+            true => None,
+            false => Some(get_token_pair(
+                feeds,
+                &position.market,
+                &position.collateral_token,
+                position.collateral_symbol.as_deref(),
+            )?),
+        };
+        Some(ReplayPosition {
+            market: format!("{:#x}", FieldElement::from_hex_be(&position.market).ok()?),
+            index_pair,
+            collateral_pair,
+            position,
+        })
+    }
+}
+
+// A struct representing how the liquidations of positions went over replayed prices.
+// @events: When each position got liquidatable or recovered.
+// @closest: The position ids with the smallest distance to liquidation they reached, and when.
+#[cfg(feature = "liquidation")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiquidationReplay {
+    pub events: Vec<ReplayedEvent>,
+    pub closest: Vec<(String, u64, f64)>,
+}
+
+// Feeds replayed prices to the liquidation math, returning when each position got liquidatable,
+// positions being assessed once both their index and collateral prices got read.
+// @observations: The prices, oldest first.
+// @params: The market parameters positions get liquidated by.
+// @positions: The positions.
+#[cfg(feature = "liquidation")]
+pub fn replay_liquidations(
+    observations: &[PriceObservation],
+    params: &LiquidationParams,
+    positions: &[ReplayPosition],
+) -> LiquidationReplay {
+    let mut prices: HashMap<(String, String), f64> = HashMap::new();
+    let mut liquidatable: HashMap<String, bool> = HashMap::new();
+    let mut closest: HashMap<String, (u64, f64)> = HashMap::new();
+    let mut events = vec![];
+    for observation in observations {
+        let pair = observation.pair();
+        prices.insert(pair.clone(), observation.value());
+        for replayed in positions {
+            if replayed.index_pair != pair && replayed.collateral_pair.as_ref() != Some(&pair) {
+                continue;
+            }
+            let index_price = match prices.get(&replayed.index_pair) {
+                Some(price) => *price,
+                None => continue,
+            };
+            let collateral_price = match &replayed.collateral_pair {
+                Some(collateral_pair) => match prices.get(collateral_pair) {
+                    Some(price) => *price,
+                    None => continue,
+                },
+                None => index_price,
+            };
+            let state = match replayed.position.to_position_state(collateral_price) {
+                Some(state) => state,
+                None => continue,
+            };
+            let id = replayed.position.id();
+            let distance = params.distance_to_liquidation(&replayed.market, &state, index_price);
+            let entry = closest
+                .entry(id.clone())
+                .or_insert((observation.observed_at, distance));
+            if distance < entry.1 {
+                *entry = (observation.observed_at, distance);
+            }
+            let is_liquidatable = params.is_liquidatable(&replayed.market, &state, index_price);
+            if liquidatable
+                .insert(id.clone(), is_liquidatable)
+                .unwrap_or(false)
+                == is_liquidatable
+            {
+                continue;
+            }
+            events.push(ReplayedEvent {
+                observed_at: observation.observed_at,
+                event: match is_liquidatable {
+                    true => ReplayEvent::Liquidatable {
+                        position: id,
+                        price: index_price,
+                        distance,
+                    },
+                    false => ReplayEvent::Recovered {
+                        position: id,
+                        price: index_price,
+                    },
+                },
+            });
+        }
+    }
+    let mut closest: Vec<(String, u64, f64)> = closest
+        .into_iter()
+        .map(|(id, (observed_at, distance))| (id, observed_at, distance))
+        .collect();
+    closest.sort_by(|a, b| a.0.cmp(&b.0));
+    LiquidationReplay { events, closest }
+}
+
+// Loads the indexed trigger orders, executed and cancelled ones included, so a replay tells when
+// each would have been crossed whatever happened to it since.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account whose orders get loaded, as indexed, every account when None.
+pub async fn load_indexed_trigger_orders(
+    pool: &Pool<Postgres>,
+    account: Option<&str>,
+) -> Result<Vec<SatoruAction>, sqlx::Error> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT row_to_json(o)::TEXT FROM orders o
+         WHERE o.order_type IN ('LimitSwap', 'LimitIncrease', 'LimitDecrease', 'StopLossDecrease')
+             AND ($1::TEXT IS NULL OR o.account = felt_in($1))
+         ORDER BY o.block_number",
+    )
+    .bind(account)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(row_data,)| serde_json::from_str::<SatoruAction>(&row_data).ok())
+        .collect())
+}
+
+// Loads the market token and index token of the indexed markets.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_market_index_tokens(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT felt_out(market_token), felt_out(index_token) FROM market_created
+         WHERE market_token IS NOT NULL AND index_token IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETH: &str = "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7";
+
+    fn observation(observed_at: u64, base: &str, price: u128) -> PriceObservation {
+        PriceObservation {
+            observed_at,
+            base: base.to_owned(),
+            quote: "usd".to_owned(),
+            price: price * 10u128.pow(8),
+            decimals: 8,
+            timestamp: observed_at - observed_at % 60_000,
+        }
+    }
+
+    #[test]
+    fn test_observation_line() {
+        let observation = observation(1711110660123, "eth", 3000);
+        assert_eq!(
+            observation.to_line(),
+            "1711110660123 eth/usd 0x45d964b800 8 1711110660000"
+        );
+        assert_eq!(
+            PriceObservation::parse(&observation.to_line()),
+            Some(observation.clone())
+        );
+        assert_eq!(observation.value(), 3000.0);
+        assert!(PriceObservation::parse("1711110660123 eth/usd 0x45d964b800 8").is_none());
+        assert!(PriceObservation::parse("1711110660123 eth 0x45d964b800 8 0").is_none());
+    }
+
+    #[test]
+    fn test_read_price_log() {
+        let path = std::env::temp_dir().join(format!("keeper-prices-{}.log", std::process::id()));
+        fs::write(
+            &path,
+            "2000 eth/usd 0x1 8 0\n\n1000 usdc/usd 0x2 8 0\n2000 usdc/usd 0x3 8 0\n",
+        )
+        .unwrap();
+        let observations = read_price_log(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            observations
+                .iter()
+                .map(|observation| (observation.observed_at, observation.price))
+                .collect::<Vec<_>>(),
+            vec![(1000, 2), (2000, 1), (2000, 3)]
+        );
+        fs::write(&path, "2000 eth/usd 0x1 8 0\n2000 eth/usd\n").unwrap();
+        assert!(read_price_log(path.to_str().unwrap()).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_triggers() {
+        let registry = TokenRegistry::default();
+        assert!(get_trigger_markets(
+            &[("12".to_owned(), ETH.to_owned())],
+            &MarketFeeds::default(),
+            &registry
+        )
+        .is_empty());
+        let registry = TokenRegistry::from_env();
+        let markets = get_trigger_markets(
+            &[("12".to_owned(), ETH.to_owned())],
+            &MarketFeeds::default(),
+            &registry,
+        );
+        assert_eq!(markets[0].pair, ("eth".to_owned(), "usd".to_owned()));
+
+        // A long limit increase at 2900 USD, trigger prices having 12 decimals for ETH.
+        let mut watchlist = TriggerWatchlist::new();
+        watchlist.insert(&SatoruAction {
+            key: "0x1".to_owned(),
+            market: "0x12".to_owned(),
+            order_type: Some("LimitIncrease".to_owned()),
+            is_long: Some(true),
+            trigger_price: Some(2900 * 10u128.pow(12)),
+            ..Default::default()
+        });
+        let observations = vec![
+            observation(1000, "eth", 3000),
+            observation(2000, "usdc", 1),
+            observation(3000, "eth", 2890),
+            observation(4000, "eth", 2880),
+        ];
+        assert_eq!(
+            replay_triggers(&observations, &markets, &mut watchlist),
+            vec![ReplayedEvent {
+                observed_at: 3000,
+                event: ReplayEvent::Triggered {
+                    key: "0x1".to_owned(),
+                    price: 2890 * 10u128.pow(12),
+                },
+            }]
+        );
+        assert!(watchlist.is_empty());
+    }
+
+    #[cfg(feature = "liquidation")]
+    #[test]
+    fn test_replay_liquidations() {
+        let params = LiquidationParams {
+            default_min_collateral_factor: 0.01,
+            ..LiquidationParams::default()
+        };
+        // 10 ETH long bought at 1000 with 2000 USDC of collateral, liquidatable at about 810.
+        let position = ReplayPosition {
+            market: "0xb".to_owned(),
+            index_pair: ("eth".to_owned(), "usd".to_owned()),
+            collateral_pair: Some(("usdc".to_owned(), "usd".to_owned())),
+            position: WatchedPosition {
+                account: "0a".to_owned(),
+                market: "0b".to_owned(),
+                collateral_token: "0c".to_owned(),
+                is_long: true,
+                size_in_usd: format!("10000{}", "0".repeat(30)),
+                increased_usd: Some(format!("10000{}", "0".repeat(30))),
+                increased_tokens: Some(format!("10{}", "0".repeat(18))),
+                collateral_amount: format!("2000{}", "0".repeat(6)),
+                pending_fees_usd: "0".to_owned(),
+                index_token: Some("0d".to_owned()),
+                index_symbol: Some("ETH".to_owned()),
+                index_decimals: Some(18),
+                collateral_symbol: Some("USDC".to_owned()),
+                collateral_decimals: Some(6),
+                collateral_is_index_token: false,
+            },
+        };
+        let observations = vec![
+            // Not assessed before the collateral price got read.
+            observation(1000, "eth", 700),
+            observation(2000, "usdc", 1),
+            observation(3000, "eth", 900),
+            observation(4000, "eth", 800),
+        ];
+        let replay = replay_liquidations(&observations, &params, &[position]);
+        let id = "0a:0b:0c:true".to_owned();
+        assert_eq!(
+            replay
+                .events
+                .iter()
+                .map(|event| (event.observed_at, &event.event))
+                .map(|(observed_at, event)| match event {
+                    ReplayEvent::Liquidatable { position, .. } => (observed_at, position, true),
+                    ReplayEvent::Recovered { position, .. } => (observed_at, position, false),
+                    ReplayEvent::Triggered { key, .. } => (observed_at, key, false),
+                })
+                .collect::<Vec<_>>(),
+            vec![(2000, &id, true), (3000, &id, false), (4000, &id, true)]
+        );
+        assert_eq!(replay.closest[0].0, id);
+        assert_eq!(replay.closest[0].1, 2000);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{collections::HashMap, env, fs};
+
+use crate::error::KeeperError;
+
+// Prefix of the section headers of the profiles, e.g. [profile.sepolia].
+const PROFILE_SECTION: &str = "profile.";
+
+fn profile_error(reason: String) -> KeeperError {
+    KeeperError::ProfileError(reason)
+}
+
+// Parses a value of the config file: a quoted string, an array of quoted strings joined with
+// commas as the list variables are, or a bare number or boolean.
+// @value: The value, after the = sign.
+fn parse_value(value: &str) -> Option<String> {
+    let value = value.trim();
+    if let Some(items) = value
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(parse_value)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(","));
+    }
+    match value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(string) => Some(string.to_owned()),
+        None if !value.is_empty() && !value.contains(['"', ' ', '#']) => Some(value.to_owned()),
+        None => None,
+    }
+}
+
+// A struct representing the config file of the keeper, its variables shared by every environment
+// and those of each profile, e.g. the contract addresses, RPC endpoints, database URL and feeds
+// of sepolia and mainnet.
+// @shared: The variables before the first profile section, set whatever the profile.
+// @profiles: The variables of each profile, by profile name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigProfiles {
+    pub shared: Vec<(String, String)>,
+    pub profiles: HashMap<String, Vec<(String, String)>>,
+}
+
+impl ConfigProfiles {
+    // Parses a config file of NAME = value lines, the variables of a profile following its
+    // [profile.<name>] header and # starting the comments.
+    // @content: The content of the config file.
+    pub fn parse(content: &str) -> Result<Self, KeeperError> {
+        let mut config = ConfigProfiles::default();
+        let mut profile: Option<String> = None;
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| profile_error(format!("line {}: {}", index + 1, reason));
+            if let Some(section) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                let name = section
+                    .trim()
+                    .strip_prefix(PROFILE_SECTION)
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| invalid("sections must be [profile.<name>]"))?;
+                if config.profiles.contains_key(name) {
+                    return Err(invalid(&format!("profile {} defined twice", name)));
+                }
+                config.profiles.insert(name.to_owned(), vec![]);
+                profile = Some(name.to_owned());
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected NAME = value"))?;
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(invalid("invalid variable name"));
+            }
+            let value = parse_value(value)
+                .ok_or_else(|| invalid(&format!("invalid value for {}", name)))?;
+            let variables = match &profile {
+                Some(profile) => config.profiles.get_mut(profile).expect("Profile inserted"),
+                None => &mut config.shared,
+            };
+            variables.push((name.to_owned(), value));
+        }
+        Ok(config)
+    }
+
+    // Returns the variables of a profile, the shared ones first so the profile ones override them.
+    // @name: The profile name.
+    pub fn variables(&self, name: &str) -> Result<Vec<(String, String)>, KeeperError> {
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let mut known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            known.sort();
+            profile_error(format!(
+                "no profile {}, known profiles: {}",
+                name,
+                known.join(", ")
+            ))
+        })?;
+        Ok(self.shared.iter().chain(profile).cloned().collect())
+    }
+}
+
+// Loads a profile of the config file into the environment, before the .env file gets read.
+// Variables set in the environment win over the profile ones, which win over the .env ones.
+// Returns the number of variables set.
+// @path: The path of the config file.
+// @name: The profile selected.
+pub fn apply_profile(path: &str, name: &str) -> Result<usize, KeeperError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| profile_error(format!("could not read {}: {}", path, e)))?;
+    let variables = ConfigProfiles::parse(&content)?.variables(name)?;
+    // The last value of a variable wins, as the profile ones come after the shared ones.
+    let variables: HashMap<String, String> = variables.into_iter().collect();
+    let mut applied = 0;
+    for (name, value) in variables {
+        if env::var_os(&name).is_none() {
+            env::set_var(name, value);
+            applied += 1;
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# Shared by every environment.
+PRAGMA_API_KEY = "key"
+API_AUTH_ENABLED = true
+
+[profile.sepolia]
+RPC_URL = "https://sepolia.example"
+MARKET_FEEDS = ["0x12=pragma:eth/usd", "0x13=pragma:btc/usd"]
+
+[profile.mainnet]
+RPC_URL = "https://mainnet.example"
+"#;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value(" \"a b\" "), Some("a b".to_owned()));
+        assert_eq!(parse_value("30"), Some("30".to_owned()));
+        assert_eq!(parse_value("[\"a\", \"b\"]"), Some("a,b".to_owned()));
+        assert_eq!(parse_value("[]"), Some("".to_owned()));
+        assert_eq!(parse_value("\"a"), None);
+        assert_eq!(parse_value("a b"), None);
+        assert_eq!(parse_value(""), None);
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let config = ConfigProfiles::parse(CONFIG).unwrap();
+        assert_eq!(
+            config.variables("sepolia").unwrap(),
+            [
+                ("PRAGMA_API_KEY", "key"),
+                ("API_AUTH_ENABLED", "true"),
+                ("RPC_URL", "https://sepolia.example"),
+                ("MARKET_FEEDS", "0x12=pragma:eth/usd,0x13=pragma:btc/usd"),
+            ]
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        );
+        assert_eq!(config.variables("mainnet").unwrap().len(), 3);
+        assert!(config.variables("devnet").is_err());
+
+        for invalid in [
+            "[sepolia]",
+            "[profile.]",
+            "[profile.a]\n[profile.a]",
+            "RPC_URL",
+            "RPC URL = 1",
+            "RPC_URL = \"a\" # Trailing comment",
+        ] {
+            assert!(ConfigProfiles::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
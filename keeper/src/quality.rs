@@ -0,0 +1,441 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use log::{error, warn};
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::ConnectedAccount,
+    core::types::{BlockId, FieldElement, MaybePendingBlockWithTxHashes},
+    providers::Provider,
+};
+use tokio::time::sleep;
+
+use crate::{error::KeeperError, executor::KeeperContext};
+
+// Most blocks of executed orders checked per batch, each block timestamp being read from the chain.
+const BLOCKS_PER_CHECK: i64 = 100;
+
+// The oracle readings of the actions being executed, by key, until they finish.
+static READINGS: OnceLock<Mutex<HashMap<String, OracleReading>>> = OnceLock::new();
+
+fn quality_error(reason: String) -> KeeperError {
+    KeeperError::DataQualityError(reason)
+}
+
+fn readings() -> &'static Mutex<HashMap<String, OracleReading>> {
+    READINGS.get_or_init(Default::default)
+}
+
+// A struct representing the oracle price an action got executed with, in seconds.
+// @block_timestamp: The latest block timestamp when the price got fetched.
+// @price_timestamp: The timestamp of the price on its feed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OracleReading {
+    pub block_timestamp: u64,
+    pub price_timestamp: u64,
+}
+
+// Records the oracle price an action is getting executed with, replacing the one of its previous
+// attempt.
+// @key: The key of the action.
+// @reading: The oracle price read.
+pub fn record_oracle_reading(key: &str, reading: OracleReading) {
+    readings().lock().unwrap().insert(key.to_owned(), reading);
+}
+
+// Takes the oracle price the last execution of an action got sent with, if it got one from a feed.
+// @key: The key of the action.
+pub fn take_oracle_reading(key: &str) -> Option<OracleReading> {
+    readings().lock().unwrap().remove(key)
+}
+
+// Persists the oracle price an executed action got executed with, for the data-quality checks.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+// @transaction_hash: The hash of the execution transaction.
+// @reading: The oracle price of the execution.
+pub async fn record_execution_price(
+    pool: &Pool<Postgres>,
+    key: &str,
+    transaction_hash: FieldElement,
+    reading: OracleReading,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO keeper_execution_prices (key, transaction_hash, block_timestamp, price_timestamp)
+         VALUES ($1, $2, $3, $4) ON CONFLICT (key) DO UPDATE SET
+             transaction_hash = EXCLUDED.transaction_hash,
+             block_timestamp = EXCLUDED.block_timestamp,
+             price_timestamp = EXCLUDED.price_timestamp,
+             recorded_at = NOW()",
+    )
+    .bind(key)
+    .bind(format!("{:#x}", transaction_hash))
+    .bind(reading.block_timestamp as i64)
+    .bind(reading.price_timestamp as i64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// A struct representing an indexed order execution, with the oracle price the keeper executed it
+// with when it did.
+// @time_stamp: The event timestamp, as indexed.
+// @oracle_block_timestamp: The latest block timestamp when the keeper fetched the price.
+// @price_timestamp: The timestamp of the price on its feed.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ExecutedOrder {
+    pub key: String,
+    pub transaction_hash: String,
+    pub block_number: i64,
+    pub time_stamp: Option<String>,
+    pub oracle_block_timestamp: Option<i64>,
+    pub price_timestamp: Option<i64>,
+}
+
+// A struct representing timestamps of an executed order disagreeing with each other.
+// @issue: The kind of inconsistency.
+// @details: The timestamps involved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataQualityIssue {
+    pub key: String,
+    pub transaction_hash: String,
+    pub block_number: i64,
+    pub issue: &'static str,
+    pub details: String,
+}
+
+// Returns the indexed event timestamp of an executed order, None when it is not a unix timestamp.
+fn get_event_timestamp(order: &ExecutedOrder) -> Option<u64> {
+    order.time_stamp.as_ref()?.parse().ok()
+}
+
+// Returns the inconsistencies between the timestamps of an executed order: its indexed event
+// timestamp against the timestamp of its block on chain and against the executions of earlier
+// blocks, and the oracle price it got executed with against both.
+// @order: The executed order.
+// @block_timestamp: The timestamp of the block of the execution, read from the chain.
+// @previous_timestamp: The latest indexed timestamp of the executions of earlier blocks, if any.
+// @max_skew_secs: The largest tolerated difference between a price and its block timestamp.
+pub fn find_timestamp_issues(
+    order: &ExecutedOrder,
+    block_timestamp: u64,
+    previous_timestamp: Option<u64>,
+    max_skew_secs: u64,
+) -> Vec<DataQualityIssue> {
+    let mut issues = vec![];
+    let mut flag = |issue: &'static str, details: String| {
+        issues.push(DataQualityIssue {
+            key: order.key.clone(),
+            transaction_hash: order.transaction_hash.clone(),
+            block_number: order.block_number,
+            issue,
+            details,
+        })
+    };
+
+    match get_event_timestamp(order) {
+        Some(event_timestamp) => {
+            if event_timestamp != block_timestamp {
+                flag(
+                    "event_block_mismatch",
+                    format!(
+                        "event timestamp {}, block {} timestamp {}",
+                        event_timestamp, order.block_number, block_timestamp
+                    ),
+                );
+            }
+            if let Some(previous) =
+                previous_timestamp.filter(|previous| event_timestamp < *previous)
+            {
+                flag(
+                    "non_monotonic_timestamp",
+                    format!(
+                        "event timestamp {} before the one of an earlier block {}",
+                        event_timestamp, previous
+                    ),
+                );
+            }
+        }
+        None => flag(
+            "invalid_event_timestamp",
+            format!("event timestamp {:?}", order.time_stamp),
+        ),
+    }
+
+    if let Some(price_timestamp) = order.price_timestamp.map(|timestamp| timestamp as u64) {
+        if price_timestamp > block_timestamp {
+            flag(
+                "price_after_block",
+                format!(
+                    "price timestamp {}, block {} timestamp {}",
+                    price_timestamp, order.block_number, block_timestamp
+                ),
+            );
+        } else if block_timestamp - price_timestamp > max_skew_secs {
+            flag(
+                "oracle_price_skew",
+                format!(
+                    "price timestamp {} is {}s before block {} timestamp {}",
+                    price_timestamp,
+                    block_timestamp - price_timestamp,
+                    order.block_number,
+                    block_timestamp
+                ),
+            );
+        }
+    }
+    if let Some(oracle_block_timestamp) = order
+        .oracle_block_timestamp
+        .map(|timestamp| timestamp as u64)
+        .filter(|timestamp| *timestamp > block_timestamp)
+    {
+        flag(
+            "oracle_block_after_execution",
+            format!(
+                "price fetched at block timestamp {}, executed at block {} timestamp {}",
+                oracle_block_timestamp, order.block_number, block_timestamp
+            ),
+        );
+    }
+    issues
+}
+
+async fn get_block_timestamp_at<P: Provider + Sync>(
+    provider: &P,
+    block_number: u64,
+) -> Result<u64, KeeperError> {
+    match provider
+        .get_block_with_tx_hashes(BlockId::Number(block_number))
+        .await
+    {
+        Ok(MaybePendingBlockWithTxHashes::Block(block)) => Ok(block.timestamp),
+        Ok(MaybePendingBlockWithTxHashes::PendingBlock(block)) => Ok(block.timestamp),
+        Err(e) => Err(quality_error(format!(
+            "could not get block {}: {:?}",
+            block_number, e
+        ))),
+    }
+}
+
+async fn load_cursor(pool: &Pool<Postgres>) -> Result<i64, sqlx::Error> {
+    let cursor: Option<i64> =
+        sqlx::query_scalar("SELECT block_number FROM data_quality_cursor WHERE id = 1")
+            .fetch_optional(pool)
+            .await?;
+    Ok(cursor.unwrap_or_default())
+}
+
+// Records the issues of a batch along with the last block checked, so a batch whose issues could
+// not be recorded gets checked again.
+async fn record_issues(
+    pool: &Pool<Postgres>,
+    issues: &[DataQualityIssue],
+    block_number: i64,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    for issue in issues {
+        sqlx::query(
+            "INSERT INTO data_quality_issues (key, transaction_hash, block_number, issue, details)
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT (transaction_hash, key, issue) DO NOTHING",
+        )
+        .bind(&issue.key)
+        .bind(&issue.transaction_hash)
+        .bind(issue.block_number)
+        .bind(issue.issue)
+        .bind(&issue.details)
+        .execute(&mut *transaction)
+        .await?;
+    }
+    sqlx::query(
+        "INSERT INTO data_quality_cursor (id, block_number) VALUES (1, $1)
+         ON CONFLICT (id) DO UPDATE SET block_number = EXCLUDED.block_number, updated_at = NOW()",
+    )
+    .bind(block_number)
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await
+}
+
+// Checks the timestamps of the orders executed in the blocks after the last one checked, a batch
+// of blocks at a time, inserting the issues found into the data_quality_issues table. Returns the
+// issues found.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @provider: The provider the block timestamps are read through.
+// @previous_timestamp: The latest indexed timestamp of the executions checked before, updated.
+// @max_skew_secs: The largest tolerated difference between a price and its block timestamp.
+pub async fn check_data_quality<P: Provider + Sync>(
+    pool: &Pool<Postgres>,
+    provider: &P,
+    previous_timestamp: &mut Option<u64>,
+    max_skew_secs: u64,
+) -> Result<usize, KeeperError> {
+    let db_error = |e: sqlx::Error| quality_error(format!("{:?}", e));
+    let mut found = 0;
+    loop {
+        let cursor = load_cursor(pool).await.map_err(db_error)?;
+        let orders = sqlx::query_as::<_, ExecutedOrder>(
+            "SELECT felt_out(oe.key) AS key, felt_out(oe.transaction_hash) AS transaction_hash,
+                 oe.block_number, oe.time_stamp, p.block_timestamp AS oracle_block_timestamp,
+                 p.price_timestamp
+             FROM order_executed oe
+             LEFT JOIN keeper_execution_prices p ON p.key = felt_out(oe.key)
+             WHERE oe.key IS NOT NULL AND oe.block_number IN (
+                 SELECT DISTINCT block_number FROM order_executed
+                 WHERE block_number > $1 ORDER BY block_number LIMIT $2
+             )
+             ORDER BY oe.block_number, oe.transaction_hash",
+        )
+        .bind(cursor)
+        .bind(BLOCKS_PER_CHECK)
+        .fetch_all(pool)
+        .await
+        .map_err(db_error)?;
+        let last_block = match orders.last() {
+            Some(order) => order.block_number,
+            None => return Ok(found),
+        };
+
+        // Only moved on once the batch got recorded, a failed batch getting checked again.
+        let mut latest_timestamp = *previous_timestamp;
+        let mut issues = vec![];
+        let mut blocks = 0;
+        for block_orders in orders.chunk_by(|a, b| a.block_number == b.block_number) {
+            blocks += 1;
+            let block_timestamp =
+                get_block_timestamp_at(provider, block_orders[0].block_number as u64).await?;
+            for order in block_orders {
+                issues.extend(find_timestamp_issues(
+                    order,
+                    block_timestamp,
+                    latest_timestamp,
+                    max_skew_secs,
+                ));
+            }
+            latest_timestamp =
+                latest_timestamp.max(block_orders.iter().filter_map(get_event_timestamp).max());
+        }
+        record_issues(pool, &issues, last_block)
+            .await
+            .map_err(db_error)?;
+        *previous_timestamp = latest_timestamp;
+        found += issues.len();
+        // The blocks left get checked right away, not after waiting another interval.
+        if blocks < BLOCKS_PER_CHECK {
+            return Ok(found);
+        }
+    }
+}
+
+// Cross-checks the timestamps of the executed orders every interval, flagging the inconsistencies
+// into the data_quality_issues table, as they are the usual root cause of wrong PnL numbers.
+// @context: The keeper context, the oracle prices being compared with the max clock skew.
+// @interval: How often the checks run, None to disable them.
+pub async fn run_data_quality_checks(context: Arc<KeeperContext>, interval: Option<Duration>) {
+    let interval = match interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    let mut previous_timestamp = None;
+    loop {
+        sleep(interval).await;
+        match check_data_quality(
+            &context.pool,
+            context.account.provider(),
+            &mut previous_timestamp,
+            context.clock.max_skew_secs,
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(issues) => warn!("Flagged {} data-quality issues of executed orders", issues),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(time_stamp: Option<&str>, price_timestamp: Option<i64>) -> ExecutedOrder {
+        ExecutedOrder {
+            key: "0a".to_owned(),
+            transaction_hash: "0b".to_owned(),
+            block_number: 100,
+            time_stamp: time_stamp.map(str::to_owned),
+            oracle_block_timestamp: Some(1700000000),
+            price_timestamp,
+        }
+    }
+
+    fn issues(order: &ExecutedOrder, block_timestamp: u64, previous: Option<u64>) -> Vec<&str> {
+        find_timestamp_issues(order, block_timestamp, previous, 60)
+            .iter()
+            .map(|issue| issue.issue)
+            .collect()
+    }
+
+    #[test]
+    fn test_consistent_timestamps() {
+        let executed = order(Some("1700000010"), Some(1699999990));
+        assert!(issues(&executed, 1700000010, Some(1700000000)).is_empty());
+        // Orders executed by other keepers have no oracle reading.
+        assert!(issues(&order(Some("1700000010"), None), 1700000010, None).is_empty());
+    }
+
+    #[test]
+    fn test_event_timestamp_issues() {
+        assert_eq!(
+            issues(&order(Some("1700000011"), None), 1700000010, None),
+            vec!["event_block_mismatch"]
+        );
+        assert_eq!(
+            issues(
+                &order(Some("1700000010"), None),
+                1700000010,
+                Some(1700000020)
+            ),
+            vec!["non_monotonic_timestamp"]
+        );
+        assert_eq!(
+            issues(&order(None, None), 1700000010, None),
+            vec!["invalid_event_timestamp"]
+        );
+    }
+
+    #[test]
+    fn test_oracle_timestamp_issues() {
+        assert_eq!(
+            issues(
+                &order(Some("1700000010"), Some(1700000011)),
+                1700000010,
+                None
+            ),
+            vec!["price_after_block"]
+        );
+        let stale = find_timestamp_issues(
+            &order(Some("1700000010"), Some(1699999900)),
+            1700000010,
+            None,
+            60,
+        );
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].issue, "oracle_price_skew");
+        assert_eq!(
+            stale[0].details,
+            "price timestamp 1699999900 is 110s before block 100 timestamp 1700000010"
+        );
+        // The block the price got fetched at cannot come after the one executing it.
+        assert_eq!(
+            issues(
+                &order(Some("1699999990"), Some(1699999980)),
+                1699999990,
+                None
+            ),
+            vec!["oracle_block_after_execution"]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use log::error;
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+use tokio::time::sleep;
+
+use crate::config;
+
+// A struct representing a keeper instance as it registers itself.
+// @id: The instance id, unique among the keepers sharing the database.
+// @version: The keeper version.
+// @mode: The launch mode (execution, liquidation).
+// @features: The optional features the instance runs with.
+// @account_address: The keeper account address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeeperInstance {
+    pub id: String,
+    pub version: String,
+    pub mode: String,
+    pub features: Vec<String>,
+    pub account_address: String,
+}
+
+impl KeeperInstance {
+    pub fn new(mode: &str, account_address: String) -> Self {
+        KeeperInstance {
+            id: config::get_keeper_id(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            mode: mode.to_owned(),
+            features: get_enabled_features(),
+            account_address,
+        }
+    }
+}
+
+// Lists the optional features enabled by the configuration.
+fn get_enabled_features() -> Vec<String> {
+    let features = [
+        ("private_relay", config::get_submission_rpc_url().is_some()),
+        ("session_key", config::get_session_private_key().is_some()),
+        ("paymaster", config::get_paymaster_url().is_some()),
+        (
+            "disabled_order_types",
+            !config::get_disabled_order_types().is_empty(),
+        ),
+        ("min_order_sizes", !config::get_min_order_sizes().is_empty()),
+        ("market_allowlist", config::get_market_allowlist().is_some()),
+        ("price_bounds", !config::get_price_bounds().is_empty()),
+        ("stable_prices", !config::get_stable_prices().is_empty()),
+        ("batching", config::get_batch_window_ms() > 0),
+        ("congestion", config::get_gas_spike_price().is_some()),
+        (
+            "execution_queue",
+            config::get_execution_max_concurrent().is_some(),
+        ),
+        (
+            "market_lanes",
+            config::get_execution_market_max_concurrent().is_some(),
+        ),
+        (
+            "execution_fee_budget",
+            config::get_execution_max_in_flight_fee().is_some(),
+        ),
+        (
+            "crash_mode",
+            config::get_crash_mode_liquidations().is_some(),
+        ),
+        (
+            "on_chain_pause",
+            config::get_kill_switch_pause_key().is_some(),
+        ),
+        ("sentry", config::get_sentry_dsn().is_some()),
+        (
+            "position_scan",
+            cfg!(feature = "liquidation") && config::get_position_scan_interval_secs().is_some(),
+        ),
+        ("order_ttl", config::get_order_ttl_blocks().is_some()),
+        (
+            "market_schedules",
+            !config::get_market_schedules().is_empty(),
+        ),
+        (
+            "account_throttle",
+            config::get_account_max_orders_per_minute().is_some(),
+        ),
+        ("standby", config::get_keeper_standby()),
+        ("leader_lease", config::get_leader_lease_ms().is_some()),
+    ];
+    features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| feature.to_string())
+        .collect()
+}
+
+// A struct representing a registered keeper instance, as exposed to operators.
+// @alive: Whether the instance sent a heartbeat within the heartbeat timeout.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct KeeperStatus {
+    pub id: String,
+    pub version: String,
+    pub mode: String,
+    pub features: Vec<String>,
+    pub account_address: String,
+    pub started_at: String,
+    pub last_heartbeat: String,
+    pub alive: bool,
+}
+
+// Registers a keeper instance, replacing the registration of a previous run with the same id.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @instance: The keeper instance.
+pub async fn register_keeper(
+    pool: &Pool<Postgres>,
+    instance: &KeeperInstance,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO keepers (id, version, mode, features, account_address) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (id) DO UPDATE SET version = $2, mode = $3, features = $4, account_address = $5,
+         started_at = NOW(), last_heartbeat = NOW()",
+    )
+    .bind(&instance.id)
+    .bind(&instance.version)
+    .bind(&instance.mode)
+    .bind(&instance.features)
+    .bind(&instance.account_address)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Records a heartbeat of a keeper instance.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @id: The instance id.
+pub async fn record_heartbeat(pool: &Pool<Postgres>, id: &str) -> Result<(), Error> {
+    sqlx::query("UPDATE keepers SET last_heartbeat = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Sends heartbeats of a keeper instance forever, failed ones being retried on the next beat.
+// @pool: A connection pool for PostgreSQL.
+// @id: The instance id.
+pub async fn start_heartbeat(pool: Pool<Postgres>, id: String) {
+    let interval = Duration::from_secs(config::get_keeper_heartbeat_interval_secs());
+    loop {
+        sleep(interval).await;
+        if let Err(e) = record_heartbeat(&pool, &id).await {
+            error!("Could not record heartbeat: {:?}", e);
+        }
+    }
+}
+
+// Loads the registered keeper instances, most recently started first.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn get_keepers(pool: &Pool<Postgres>) -> Result<Vec<KeeperStatus>, Error> {
+    sqlx::query_as::<_, KeeperStatus>(
+        "SELECT id, version, mode, features, account_address, started_at::TEXT AS started_at,
+         last_heartbeat::TEXT AS last_heartbeat,
+         last_heartbeat > NOW() - make_interval(secs => $1) AS alive
+         FROM keepers ORDER BY started_at DESC",
+    )
+    .bind(config::get_keeper_heartbeat_timeout_secs() as f64)
+    .fetch_all(pool)
+    .await
+}
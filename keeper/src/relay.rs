@@ -0,0 +1,327 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+use starknet::{
+    accounts::{Account, Call, ConnectedAccount},
+    core::{
+        types::{BlockId, BlockTag, FieldElement, FunctionCall},
+        utils::{cairo_short_string_to_felt, get_selector_from_name},
+    },
+    providers::Provider,
+};
+
+use crate::{
+    clock::get_system_timestamp,
+    config,
+    contracts::{ContractAddresses, KeeperAccount},
+    error::KeeperError,
+    killswitch::KillSwitch,
+    paymaster::OutsideExecution,
+    session::AllowedMethod,
+    standby::Standby,
+    submitter::Submitter,
+};
+
+// Caller of the outside executions any address may send.
+const ANY_CALLER: &str = "ANY_CALLER";
+// What SNIP-6 accounts return for a valid signature.
+const VALID_SIGNATURE: &str = "VALID";
+
+fn relay_error(reason: String) -> KeeperError {
+    KeeperError::RelayError(reason)
+}
+
+// A struct representing what the keeper relays of the outside executions signed by traders, the
+// keeper paying their fees.
+// @allowed_methods: The only methods the relayed calls may call, e.g. the order creation of the
+// exchange router.
+// @min_validity_secs: How long an execution must stay valid for to get relayed, so it lands in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayPolicy {
+    pub allowed_methods: Vec<AllowedMethod>,
+    pub min_validity_secs: u64,
+}
+
+impl RelayPolicy {
+    // None when no method is allowed, the keeper then relaying nothing.
+    // @addresses: The keeper contract addresses the allowed method contracts resolve to.
+    pub fn from_env(addresses: &ContractAddresses) -> Result<Option<Self>, KeeperError> {
+        let allowed_methods = config::get_relay_allowed_methods()
+            .iter()
+            .map(|method| AllowedMethod::parse(method, addresses))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(match allowed_methods.is_empty() {
+            true => None,
+            false => Some(RelayPolicy {
+                allowed_methods,
+                min_validity_secs: config::get_relay_min_validity_secs(),
+            }),
+        })
+    }
+
+    // Checks an outside execution can be relayed: sendable by the keeper, valid now and for long
+    // enough, and only calling allowed methods.
+    // @execution: The outside execution signed by the trader.
+    // @relayer: The keeper account address sending it.
+    // @now: The current unix timestamp.
+    pub fn check(
+        &self,
+        execution: &OutsideExecution,
+        relayer: FieldElement,
+        now: u64,
+    ) -> Result<(), KeeperError> {
+        let any_caller = cairo_short_string_to_felt(ANY_CALLER).expect("Invalid short string");
+        if execution.caller != any_caller && execution.caller != relayer {
+            return Err(relay_error(format!(
+                "caller {:#x} is not the keeper",
+                execution.caller
+            )));
+        }
+        if now <= execution.execute_after {
+            return Err(relay_error(format!(
+                "executable after {} only",
+                execution.execute_after
+            )));
+        }
+        if now.saturating_add(self.min_validity_secs) >= execution.execute_before {
+            return Err(relay_error(format!(
+                "expiring at {}, less than {}s from now",
+                execution.execute_before, self.min_validity_secs
+            )));
+        }
+        if execution.calls.is_empty() {
+            return Err(relay_error("no calls".to_owned()));
+        }
+        for call in &execution.calls {
+            let allowed = self.allowed_methods.iter().any(|method| {
+                method.contract_address == call.to && method.selector == call.selector
+            });
+            if !allowed {
+                return Err(relay_error(format!(
+                    "selector {:#x} of contract {:#x} not allowed for relay",
+                    call.selector, call.to
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Builds the call to execute_from_outside_v2 making the trader account run its signed execution.
+// @account: The trader account.
+// @execution: The outside execution signed by the trader.
+// @signature: The trader signature.
+pub fn get_execute_from_outside_call(
+    account: FieldElement,
+    execution: &OutsideExecution,
+    signature: &[FieldElement],
+) -> Call {
+    let mut calldata = vec![
+        execution.caller,
+        execution.nonce,
+        FieldElement::from(execution.execute_after),
+        FieldElement::from(execution.execute_before),
+        FieldElement::from(execution.calls.len()),
+    ];
+    for call in &execution.calls {
+        calldata.push(call.to);
+        calldata.push(call.selector);
+        calldata.push(FieldElement::from(call.calldata.len()));
+        calldata.extend_from_slice(&call.calldata);
+    }
+    calldata.push(FieldElement::from(signature.len()));
+    calldata.extend_from_slice(signature);
+    Call {
+        to: account,
+        selector: get_selector_from_name("execute_from_outside_v2").unwrap(),
+        calldata,
+    }
+}
+
+// A struct relaying the outside executions of traders, e.g. gasless order creations, through the
+// keeper account.
+// @policy: What gets relayed.
+// @account: The keeper account, used to read the trader accounts.
+// @submitter: The keeper account sending the relayed executions.
+// @kill_switch: The emergency stop of the outgoing transactions, relays included.
+// @standby: The role of the instance, standbys relaying nothing until promoted.
+pub struct Relayer {
+    pub policy: RelayPolicy,
+    account: KeeperAccount,
+    submitter: Arc<Submitter>,
+    kill_switch: Arc<KillSwitch>,
+    standby: Arc<Standby>,
+}
+
+impl Relayer {
+    pub fn new(
+        policy: RelayPolicy,
+        account: KeeperAccount,
+        submitter: Arc<Submitter>,
+        kill_switch: Arc<KillSwitch>,
+        standby: Arc<Standby>,
+    ) -> Self {
+        Relayer {
+            policy,
+            account,
+            submitter,
+            kill_switch,
+            standby,
+        }
+    }
+
+    async fn call(
+        &self,
+        contract_address: FieldElement,
+        method: &str,
+        calldata: Vec<FieldElement>,
+    ) -> Result<Vec<FieldElement>, KeeperError> {
+        self.account
+            .provider()
+            .call(
+                FunctionCall {
+                    contract_address,
+                    entry_point_selector: get_selector_from_name(method).unwrap(),
+                    calldata,
+                },
+                BlockId::Tag(BlockTag::Latest),
+            )
+            .await
+            .map_err(|e| relay_error(format!("{} failed: {:?}", method, e)))
+    }
+
+    // Validates an outside execution signed by a trader and sends it, returns its transaction hash.
+    // The signature and nonce are checked by the trader account before anything gets sent.
+    // @trader: The trader account.
+    // @typed_data: The SNIP-12 typed data of the outside execution.
+    // @signature: The trader signature of the typed data.
+    pub async fn relay(
+        &self,
+        trader: FieldElement,
+        typed_data: &Value,
+        signature: Vec<FieldElement>,
+    ) -> Result<FieldElement, KeeperError> {
+        self.kill_switch.check()?;
+        self.standby.check()?;
+        // The typed data is read as the paymaster one, its errors being the trader's here.
+        let execution = OutsideExecution::from_typed_data(typed_data).map_err(|e| match e {
+            KeeperError::PaymasterError(reason) => relay_error(reason),
+            e => e,
+        })?;
+        self.policy
+            .check(&execution, self.account.address(), get_system_timestamp())?;
+
+        let nonce_valid = self
+            .call(
+                trader,
+                "is_valid_outside_execution_nonce",
+                vec![execution.nonce],
+            )
+            .await?;
+        if nonce_valid.first() != Some(&FieldElement::ONE) {
+            return Err(relay_error(format!(
+                "nonce {:#x} already used",
+                execution.nonce
+            )));
+        }
+        let message_hash = execution.message_hash(self.account.chain_id(), trader);
+        let mut calldata = vec![message_hash, FieldElement::from(signature.len())];
+        calldata.extend_from_slice(&signature);
+        let valid = self.call(trader, "is_valid_signature", calldata).await?;
+        if valid.first()
+            != Some(&cairo_short_string_to_felt(VALID_SIGNATURE).expect("Invalid short string"))
+        {
+            return Err(relay_error("invalid signature".to_owned()));
+        }
+
+        self.submitter
+            .send(vec![get_execute_from_outside_call(
+                trader, &execution, &signature,
+            )])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAYER: u64 = 0x77;
+
+    fn policy() -> RelayPolicy {
+        RelayPolicy {
+            allowed_methods: vec![AllowedMethod::parse(
+                "0x12:create_order",
+                &ContractAddresses::default(),
+            )
+            .unwrap()],
+            min_validity_secs: 30,
+        }
+    }
+
+    fn execution(caller: FieldElement, method: &str) -> OutsideExecution {
+        OutsideExecution {
+            caller,
+            nonce: FieldElement::ONE,
+            execute_after: 100,
+            execute_before: 1000,
+            calls: vec![Call {
+                to: FieldElement::from(0x12u64),
+                selector: get_selector_from_name(method).unwrap(),
+                calldata: vec![FieldElement::THREE],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_check_relay() {
+        let any_caller = cairo_short_string_to_felt(ANY_CALLER).unwrap();
+        let relayer = FieldElement::from(RELAYER);
+        let check = |execution: &OutsideExecution, now| policy().check(execution, relayer, now);
+
+        assert!(check(&execution(any_caller, "create_order"), 500).is_ok());
+        assert!(check(&execution(relayer, "create_order"), 500).is_ok());
+        assert!(check(&execution(FieldElement::TWO, "create_order"), 500).is_err());
+        // Not yet valid, or expiring before it would land.
+        assert!(check(&execution(any_caller, "create_order"), 100).is_err());
+        assert!(check(&execution(any_caller, "create_order"), 970).is_err());
+        assert!(check(&execution(any_caller, "cancel_order"), 500).is_err());
+        let no_calls = OutsideExecution {
+            calls: vec![],
+            ..execution(any_caller, "create_order")
+        };
+        assert!(check(&no_calls, 500).is_err());
+    }
+
+    #[test]
+    fn test_execute_from_outside_call() {
+        let execution = execution(FieldElement::from(RELAYER), "create_order");
+        let call = get_execute_from_outside_call(
+            FieldElement::from(0x5u64),
+            &execution,
+            &[FieldElement::ONE, FieldElement::TWO],
+        );
+        assert_eq!(call.to, FieldElement::from(0x5u64));
+        assert_eq!(
+            call.selector,
+            get_selector_from_name("execute_from_outside_v2").unwrap()
+        );
+        assert_eq!(
+            call.calldata,
+            vec![
+                FieldElement::from(RELAYER),
+                FieldElement::ONE,
+                FieldElement::from(100u64),
+                FieldElement::from(1000u64),
+                FieldElement::ONE,
+                FieldElement::from(0x12u64),
+                get_selector_from_name("create_order").unwrap(),
+                FieldElement::ONE,
+                FieldElement::THREE,
+                FieldElement::TWO,
+                FieldElement::ONE,
+                FieldElement::TWO,
+            ]
+        );
+    }
+}
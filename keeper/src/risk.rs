@@ -0,0 +1,599 @@
+use std::collections::HashMap;
+
+use log::debug;
+use satoru_client::amounts::TokenAmount;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::{
+    clock::get_system_timestamp,
+    config,
+    error::KeeperError,
+    liquidation::{LiquidationParams, PositionState},
+    trade::price::feeds::MarketFeeds,
+    watch::{get_cached_price, get_token_pair, get_watched_positions},
+};
+
+// The index price shocks simulated, as fractions of the current price.
+pub const PRICE_SHOCKS: [f64; 6] = [-0.2, -0.1, -0.05, 0.05, 0.1, 0.2];
+// Most rounds of liquidations moving the price into the next ones.
+const MAX_CASCADE_ROUNDS: usize = 10;
+// Liquidated sizes the cascade impact is expressed per, in USD.
+const CASCADE_IMPACT_SIZE_USD: f64 = 1_000_000.0;
+
+fn risk_error(reason: String) -> KeeperError {
+    KeeperError::RiskError(reason)
+}
+
+// A struct holding how the liquidation cascades get simulated.
+// @liquidation: The market parameters positions get liquidated by.
+// @feeds: The oracle feeds of the market tokens, priced by their symbol when unmapped.
+// @cascade_impact: How much the index price moves per million USD of positions liquidated, as a
+// fraction of the price, liquidated longs pushing it down and shorts up. None without cascade.
+#[derive(Debug, Clone)]
+pub struct RiskParams {
+    pub liquidation: LiquidationParams,
+    pub feeds: MarketFeeds,
+    pub cascade_impact: Option<f64>,
+}
+
+impl RiskParams {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        Ok(RiskParams {
+            liquidation: LiquidationParams::from_env(),
+            feeds: MarketFeeds::from_env()?,
+            cascade_impact: config::get_risk_cascade_impact_bps().map(|bps| bps / 10000.0),
+        })
+    }
+}
+
+// A struct representing an open position priced at the current index price.
+// @id: The id of the position, as the watch ones.
+// @market: The market of the position, as a 0x prefixed felt.
+// @state: The state of the position.
+// @index_price: The current index price of the market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricedPosition {
+    pub id: String,
+    pub account: String,
+    pub market: String,
+    pub state: PositionState,
+    pub index_price: f64,
+}
+
+// A struct representing a token amount of a market pool, at its current price.
+// @market: The market of the pool, as a 0x prefixed felt.
+// @amount: The amount of tokens, scaled by their decimals.
+// @price: The current price of the token.
+// @is_index_token: Whether the token is the index token of the market, its price then getting
+// shocked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolToken {
+    pub market: String,
+    pub amount: f64,
+    pub price: f64,
+    pub is_index_token: bool,
+}
+
+// A struct representing a position liquidated by a simulated shock.
+// @round: The cascade round the position got liquidated in, 0 for the shock itself.
+// @price: The index price it got liquidated at.
+// @bad_debt_usd: The losses its collateral does not cover, borne by the pool.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidatedPosition {
+    pub id: String,
+    pub account: String,
+    pub market: String,
+    pub is_long: bool,
+    pub size_in_usd: f64,
+    pub round: usize,
+    pub price: f64,
+    pub bad_debt_usd: f64,
+}
+
+// A struct representing how a market fares under a simulated shock.
+// @price: The current index price.
+// @shocked_price: The index price once the cascade settled.
+// @rounds: The number of cascade rounds which liquidated positions.
+// @pool_pnl_usd: What the pool gains from the PnL of its positions at the shocked prices, bad debt
+// deducted, negative when it loses.
+// @pool_value_usd: The value of the pool at the shocked prices, None when its tokens are unpriced.
+// @pool_impact: The PnL of the pool as a fraction of its value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarketShock {
+    pub market: String,
+    pub price: f64,
+    pub shocked_price: f64,
+    pub rounds: usize,
+    pub liquidated_positions: usize,
+    pub liquidated_size_usd: f64,
+    pub bad_debt_usd: f64,
+    pub pool_pnl_usd: f64,
+    pub pool_value_usd: Option<f64>,
+    pub pool_impact: Option<f64>,
+}
+
+// A struct representing the outcome of a simulated index price shock over every market.
+// @shock: The shock, as a fraction of the current prices.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShockReport {
+    pub shock: f64,
+    pub liquidated_positions: usize,
+    pub liquidated_size_usd: f64,
+    pub bad_debt_usd: f64,
+    pub markets: Vec<MarketShock>,
+    pub liquidations: Vec<LiquidatedPosition>,
+}
+
+// A struct representing the liquidation cascade report of the risk committee.
+// @generated_at: When the report got generated, as a unix timestamp.
+// @positions: The number of open positions simulated.
+// @unpriced_positions: The number of open positions left out, their prices or decimals unknown.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RiskReport {
+    pub generated_at: u64,
+    pub positions: usize,
+    pub unpriced_positions: usize,
+    pub shocks: Vec<ShockReport>,
+}
+
+// Returns the equity of a position at an index price, its collateral after PnL and pending fees.
+// @position: The state of the position.
+// @price: The index price.
+fn get_equity(position: &PositionState, price: f64) -> f64 {
+    position.collateral_amount * get_collateral_price(position, price) + get_pnl(position, price)
+        - position.pending_fees_usd
+}
+
+fn get_pnl(position: &PositionState, price: f64) -> f64 {
+    match position.is_long {
+        true => position.size_in_tokens * price - position.size_in_usd,
+        false => position.size_in_usd - position.size_in_tokens * price,
+    }
+}
+
+fn get_collateral_price(position: &PositionState, price: f64) -> f64 {
+    match position.collateral_is_index_token {
+        true => price,
+        false => position.collateral_price,
+    }
+}
+
+// Simulates a shock of the index price of a market, the positions it liquidates moving the price
+// into the next ones by the cascade impact until none gets liquidated.
+// @params: How the cascades get simulated.
+// @market: The market.
+// @positions: The open positions of the market.
+// @pool: The token amounts of the market pool.
+// @shock: The shock, as a fraction of the current price.
+pub fn simulate_market(
+    params: &RiskParams,
+    market: &str,
+    positions: &[&PricedPosition],
+    pool: &[&PoolToken],
+    shock: f64,
+) -> (MarketShock, Vec<LiquidatedPosition>) {
+    let price = positions
+        .first()
+        .map(|position| position.index_price)
+        .unwrap_or_default();
+    let mut shocked_price = price * (1.0 + shock);
+    let mut liquidated: Vec<LiquidatedPosition> = vec![];
+    let mut rounds = 0;
+    for round in 0..MAX_CASCADE_ROUNDS {
+        let newly_liquidated: Vec<LiquidatedPosition> = positions
+            .iter()
+            .filter(|position| !liquidated.iter().any(|l| l.id == position.id))
+            .filter(|position| {
+                params
+                    .liquidation
+                    .is_liquidatable(market, &position.state, shocked_price)
+            })
+            .map(|position| LiquidatedPosition {
+                id: position.id.clone(),
+                account: position.account.clone(),
+                market: market.to_owned(),
+                is_long: position.state.is_long,
+                size_in_usd: position.state.size_in_usd,
+                round,
+                price: shocked_price,
+                bad_debt_usd: (-get_equity(&position.state, shocked_price)).max(0.0),
+            })
+            .collect();
+        if newly_liquidated.is_empty() {
+            break;
+        }
+        rounds = round + 1;
+        // Liquidated longs get sold and shorts bought back, moving the price further.
+        let net_size_usd: f64 = newly_liquidated
+            .iter()
+            .map(|position| match position.is_long {
+                true => position.size_in_usd,
+                false => -position.size_in_usd,
+            })
+            .sum();
+        liquidated.extend(newly_liquidated);
+        match params.cascade_impact {
+            Some(impact) => {
+                let moved = shocked_price * (1.0 - impact * net_size_usd / CASCADE_IMPACT_SIZE_USD);
+                shocked_price = moved.max(0.0);
+            }
+            None => break,
+        }
+    }
+    // The pool is the counterparty of the positions, only getting the collateral of the
+    // liquidated ones losing more than it.
+    let pool_pnl_usd: f64 = positions
+        .iter()
+        .map(
+            |position| match liquidated.iter().find(|l| l.id == position.id) {
+                Some(l) => -get_pnl(&position.state, l.price) - l.bad_debt_usd,
+                None => -get_pnl(&position.state, shocked_price),
+            },
+        )
+        .sum();
+    let pool_value_usd = (!pool.is_empty()).then(|| {
+        pool.iter()
+            .map(|token| match token.is_index_token && price > 0.0 {
+                true => token.amount * token.price * shocked_price / price,
+                false => token.amount * token.price,
+            })
+            .sum::<f64>()
+    });
+    let report = MarketShock {
+        market: market.to_owned(),
+        price,
+        shocked_price,
+        rounds,
+        liquidated_positions: liquidated.len(),
+        liquidated_size_usd: liquidated.iter().map(|l| l.size_in_usd).sum(),
+        bad_debt_usd: liquidated.iter().map(|l| l.bad_debt_usd).sum(),
+        pool_pnl_usd,
+        pool_value_usd,
+        pool_impact: pool_value_usd
+            .filter(|value| *value > 0.0)
+            .map(|value| pool_pnl_usd / value),
+    };
+    (report, liquidated)
+}
+
+// Simulates a shock of every index price at once, markets with the largest bad debt first.
+// @params: How the cascades get simulated.
+// @positions: The open positions.
+// @pool: The token amounts of the market pools.
+// @shock: The shock, as a fraction of the current prices.
+pub fn simulate_shock(
+    params: &RiskParams,
+    positions: &[PricedPosition],
+    pool: &[PoolToken],
+    shock: f64,
+) -> ShockReport {
+    let mut by_market: HashMap<&str, Vec<&PricedPosition>> = HashMap::new();
+    for position in positions {
+        by_market
+            .entry(position.market.as_str())
+            .or_default()
+            .push(position);
+    }
+    let mut markets = vec![];
+    let mut liquidations = vec![];
+    for (market, positions) in by_market {
+        let market_pool: Vec<&PoolToken> =
+            pool.iter().filter(|token| token.market == market).collect();
+        let (report, liquidated) = simulate_market(params, market, &positions, &market_pool, shock);
+        markets.push(report);
+        liquidations.extend(liquidated);
+    }
+    markets.sort_by(|a, b| {
+        b.bad_debt_usd
+            .total_cmp(&a.bad_debt_usd)
+            .then(b.liquidated_size_usd.total_cmp(&a.liquidated_size_usd))
+            .then(a.market.cmp(&b.market))
+    });
+    liquidations.sort_by(|a, b| {
+        a.round
+            .cmp(&b.round)
+            .then(b.size_in_usd.total_cmp(&a.size_in_usd))
+            .then(a.id.cmp(&b.id))
+    });
+    ShockReport {
+        shock,
+        liquidated_positions: liquidations.len(),
+        liquidated_size_usd: markets
+            .iter()
+            .map(|market| market.liquidated_size_usd)
+            .sum(),
+        bad_debt_usd: markets.iter().map(|market| market.bad_debt_usd).sum(),
+        markets,
+        liquidations,
+    }
+}
+
+// Formats an indexed felt as the markets are reported, None if it is not one.
+fn to_market(felt: &str) -> Option<String> {
+    FieldElement::from_hex_be(felt)
+        .ok()
+        .map(|felt| format!("{:#x}", felt))
+}
+
+// A row of the pool amounts, as the latest pool_amount_updated of each market and token.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct PoolAmountRow {
+    market: String,
+    token: String,
+    amount: String,
+    symbol: Option<String>,
+    decimals: Option<i32>,
+    is_index_token: bool,
+}
+
+// Loads the latest pool amounts of the markets, with the tokens they get priced by.
+// @pool: A reference to a connection pool for PostgreSQL.
+async fn get_pool_amounts(pool: &Pool<Postgres>) -> Result<Vec<PoolAmountRow>, sqlx::Error> {
+    // Token addresses are compared as felts, whatever their prefix and padding.
+    sqlx::query_as::<_, PoolAmountRow>(
+        "WITH tokens_by_felt AS (
+             SELECT ltrim(regexp_replace(lower(address), '^0x', ''), '0') AS felt, symbol, decimals
+             FROM tokens
+         ),
+         markets_by_felt AS (
+             SELECT ltrim(felt_out(market_token), '0') AS felt, ltrim(felt_out(index_token), '0')
+             AS index_token FROM market_created
+         ),
+         latest AS (
+             SELECT DISTINCT ON (felt_out(market), felt_out(token)) felt_out(market) AS market,
+                 felt_out(token) AS token, next_value
+             FROM pool_amount_updated
+             ORDER BY felt_out(market), felt_out(token), block_number DESC
+         )
+         SELECT l.market, l.token, l.next_value::TEXT AS amount, t.symbol, t.decimals,
+             COALESCE(ltrim(l.token, '0') = m.index_token, FALSE) AS is_index_token
+         FROM latest l
+         LEFT JOIN markets_by_felt m ON m.felt = ltrim(l.market, '0')
+         LEFT JOIN tokens_by_felt t ON t.felt = ltrim(l.token, '0')
+         WHERE l.next_value > 0",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Simulates the liquidation cascades of the index price shocks from the indexed open positions
+// and pool amounts, priced at the current prices, for the risk committee. Positions and pool
+// tokens without price or decimals are left out.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @params: How the cascades get simulated.
+// @shocks: The shocks, as fractions of the current prices.
+pub async fn build_risk_report(
+    pool: &Pool<Postgres>,
+    params: &RiskParams,
+    shocks: &[f64],
+) -> Result<RiskReport, KeeperError> {
+    let positions = get_watched_positions(pool, None)
+        .await
+        .map_err(|e| risk_error(e.to_string()))?;
+    let mut prices = HashMap::new();
+    let mut priced = vec![];
+    let mut unpriced_positions = 0;
+    for position in &positions {
+        let market = to_market(&position.market);
+        let index_pair = position.index_token.as_deref().and_then(|token| {
+            get_token_pair(
+                &params.feeds,
+                &position.market,
+                token,
+                position.index_symbol.as_deref(),
+            )
+        });
+        let collateral_pair = get_token_pair(
+            &params.feeds,
+            &position.market,
+            &position.collateral_token,
+            position.collateral_symbol.as_deref(),
+        );
+        let (market, index_pair, collateral_pair) = match (market, index_pair, collateral_pair) {
+            (Some(market), Some(index_pair), Some(collateral_pair)) => {
+                (market, index_pair, collateral_pair)
+            }
+            _ => {
+                unpriced_positions += 1;
+                continue;
+            }
+        };
+        let index_price = get_cached_price(&mut prices, index_pair).await?;
+        let collateral_price = match position.collateral_is_index_token {
+            true => index_price,
+            false => get_cached_price(&mut prices, collateral_pair).await?,
+        };
+        match position.to_position_state(collateral_price) {
+            Some(state) => priced.push(PricedPosition {
+                id: position.id(),
+                account: position.account.clone(),
+                market,
+                state,
+                index_price,
+            }),
+            None => unpriced_positions += 1,
+        }
+    }
+    let mut pool_tokens = vec![];
+    for row in get_pool_amounts(pool)
+        .await
+        .map_err(|e| risk_error(e.to_string()))?
+    {
+        let pair = get_token_pair(
+            &params.feeds,
+            &row.market,
+            &row.token,
+            row.symbol.as_deref(),
+        );
+        let (market, pair, decimals) = match (to_market(&row.market), pair, row.decimals) {
+            (Some(market), Some(pair), Some(decimals)) => (market, pair, decimals),
+            _ => {
+                debug!("No price for pool token {} of {}", row.token, row.market);
+                continue;
+            }
+        };
+        let amount = match row.amount.parse::<TokenAmount>() {
+            Ok(amount) => amount.to_tokens_f64(decimals as i64),
+            Err(_) => continue,
+        };
+        pool_tokens.push(PoolToken {
+            market,
+            amount,
+            price: get_cached_price(&mut prices, pair).await?,
+            is_index_token: row.is_index_token,
+        });
+    }
+    Ok(RiskReport {
+        generated_at: get_system_timestamp(),
+        positions: priced.len(),
+        unpriced_positions,
+        shocks: shocks
+            .iter()
+            .map(|shock| simulate_shock(params, &priced, &pool_tokens, *shock))
+            .collect(),
+    })
+}
+
+// Formats a risk report as text, one line per shock and per market liquidating positions.
+// @report: The report.
+pub fn format_risk_report(report: &RiskReport) -> String {
+    let mut lines = vec![format!(
+        "{} positions simulated, {} without price",
+        report.positions, report.unpriced_positions
+    )];
+    for shock in &report.shocks {
+        lines.push(format!(
+            "{:+.0}%: {} positions liquidated, {:.2} USD, bad debt {:.2} USD",
+            shock.shock * 100.0,
+            shock.liquidated_positions,
+            shock.liquidated_size_usd,
+            shock.bad_debt_usd
+        ));
+        for market in shock
+            .markets
+            .iter()
+            .filter(|market| market.liquidated_positions > 0)
+        {
+            lines.push(format!(
+                "  {} at {:.4} after {} rounds: {} liquidated, {:.2} USD, bad debt {:.2} USD, \
+                 pool PnL {:.2} USD{}",
+                market.market,
+                market.shocked_price,
+                market.rounds,
+                market.liquidated_positions,
+                market.liquidated_size_usd,
+                market.bad_debt_usd,
+                market.pool_pnl_usd,
+                match market.pool_impact {
+                    Some(impact) => format!(" ({:+.2}% of the pool)", impact * 100.0),
+                    None => String::new(),
+                }
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(cascade_impact: Option<f64>) -> RiskParams {
+        RiskParams {
+            liquidation: LiquidationParams {
+                min_collateral_usd: 0.0,
+                default_min_collateral_factor: 0.01,
+                ..LiquidationParams::default()
+            },
+            feeds: MarketFeeds::default(),
+            cascade_impact,
+        }
+    }
+
+    // A long of 10 ETH bought at 1000, with a collateral in USDC.
+    fn position(id: &str, size_in_usd: f64, collateral_amount: f64) -> PricedPosition {
+        PricedPosition {
+            id: id.to_owned(),
+            account: "0a".to_owned(),
+            market: "0xb".to_owned(),
+            state: PositionState {
+                is_long: true,
+                size_in_usd,
+                size_in_tokens: size_in_usd / 1000.0,
+                collateral_amount,
+                collateral_price: 1.0,
+                collateral_is_index_token: false,
+                pending_fees_usd: 0.0,
+            },
+            index_price: 1000.0,
+        }
+    }
+
+    #[test]
+    fn test_simulate_shock() {
+        // Liquidated below 909.09 and 818.18.
+        let positions = vec![
+            position("a", 10000.0, 1000.0),
+            position("b", 10000.0, 2000.0),
+        ];
+        let report = simulate_shock(&params(None), &positions, &[], -0.05);
+        assert_eq!(report.liquidated_positions, 0);
+        // Traders lose 1000 USD to the pool.
+        assert!((report.markets[0].pool_pnl_usd - 1000.0).abs() < 1e-6);
+
+        let report = simulate_shock(&params(None), &positions, &[], -0.1);
+        assert_eq!(report.liquidated_positions, 1);
+        assert_eq!(report.liquidations[0].id, "a");
+        assert_eq!(report.bad_debt_usd, 0.0);
+
+        let report = simulate_shock(&params(None), &positions, &[], -0.2);
+        assert_eq!(report.liquidated_positions, 2);
+        // Position a loses 2000 USD with 1000 USD of collateral.
+        assert!((report.bad_debt_usd - 1000.0).abs() < 1e-6);
+        assert!((report.markets[0].pool_pnl_usd - 3000.0).abs() < 1e-6);
+        assert!(simulate_shock(&params(None), &positions, &[], 0.2)
+            .liquidations
+            .is_empty());
+    }
+
+    #[test]
+    fn test_liquidation_cascade() {
+        let positions = vec![
+            position("a", 1_000_000.0, 100_000.0),
+            position("b", 1_000_000.0, 140_000.0),
+        ];
+        let no_cascade = simulate_shock(&params(None), &positions, &[], -0.1);
+        assert_eq!(no_cascade.liquidated_positions, 1);
+        // Liquidating a moves the price 5% down, liquidating b.
+        let cascade = simulate_shock(&params(Some(0.05)), &positions, &[], -0.1);
+        assert_eq!(cascade.liquidated_positions, 2);
+        assert_eq!(cascade.markets[0].rounds, 2);
+        assert_eq!(cascade.liquidations[1].round, 1);
+        assert!((cascade.liquidations[1].price - 855.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pool_impact() {
+        let positions = vec![position("a", 10000.0, 1000.0)];
+        let pool = vec![
+            PoolToken {
+                market: "0xb".to_owned(),
+                amount: 10.0,
+                price: 1000.0,
+                is_index_token: true,
+            },
+            PoolToken {
+                market: "0xb".to_owned(),
+                amount: 10000.0,
+                price: 1.0,
+                is_index_token: false,
+            },
+        ];
+        let report = simulate_shock(&params(None), &positions, &pool, -0.2);
+        let market = &report.markets[0];
+        assert_eq!(market.pool_value_usd, Some(18000.0));
+        assert!((market.pool_impact.unwrap() - 1000.0 / 18000.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use cainome::cairo_serde::{ContractAddress, U256};
+use log::{error, info};
+use starknet::core::types::FieldElement;
+use tokio::{sync::Semaphore, task::JoinSet, time::sleep};
+
+use crate::{
+    config, error::KeeperError, executor::KeeperContext, keys::position_key,
+    trade::order::handle::Position,
+};
+
+// A struct holding how the DataStore positions get scanned.
+// @page_size: The number of position keys read per call.
+// @concurrency: The largest number of DataStore calls made at once.
+// @interval: The delay between two scans, positions never scanned when None.
+// @full_refresh_scans: Every how many scans the known positions get read again, only the opened
+// ones being read in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanParams {
+    pub page_size: u32,
+    pub concurrency: usize,
+    pub interval: Option<Duration>,
+    pub full_refresh_scans: u64,
+}
+
+impl ScanParams {
+    pub fn from_env() -> Self {
+        ScanParams {
+            page_size: config::get_position_scan_page_size().max(1),
+            concurrency: config::get_position_scan_concurrency().max(1),
+            interval: config::get_position_scan_interval_secs().map(Duration::from_secs),
+            full_refresh_scans: config::get_position_scan_full_refresh_scans().max(1),
+        }
+    }
+}
+
+// A struct representing the changes a scan found since the previous one.
+// @opened: The keys of the positions opened.
+// @updated: The keys of the known positions which changed, only found by full refreshes.
+// @closed: The keys of the positions closed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanDiff {
+    pub opened: Vec<FieldElement>,
+    pub updated: Vec<FieldElement>,
+    pub closed: Vec<FieldElement>,
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.opened.is_empty() && self.updated.is_empty() && self.closed.is_empty()
+    }
+}
+
+// A struct holding the open positions as of the last scan, for the liquidation and ADL keepers
+// to pick their candidates from without reading the DataStore.
+// @positions: The open positions, by key.
+// @scans: The number of scans made.
+#[derive(Debug, Default)]
+pub struct PositionBook {
+    pub positions: HashMap<FieldElement, Position>,
+    pub scans: u64,
+}
+
+impl PositionBook {
+    pub fn new() -> Self {
+        PositionBook::default()
+    }
+
+    // Applies a scan, returning what changed since the previous one.
+    // @keys: The keys of every open position.
+    // @read: The positions read by the scan, the opened ones at least.
+    pub fn apply(&mut self, keys: &HashSet<FieldElement>, read: Vec<Position>) -> ScanDiff {
+        let closed = self
+            .positions
+            .keys()
+            .filter(|key| !keys.contains(key))
+            .copied()
+            .collect();
+        let diff = self.merge(closed, keys, read);
+        self.scans += 1;
+        diff
+    }
+
+    // Applies the read of the positions of a single account, after one of them got increased or
+    // decreased, the positions of other accounts being left as they are.
+    // @account: The account whose positions got read.
+    // @keys: The keys of every open position of the account.
+    // @read: The positions of the account.
+    pub fn apply_account(
+        &mut self,
+        account: ContractAddress,
+        keys: &HashSet<FieldElement>,
+        read: Vec<Position>,
+    ) -> ScanDiff {
+        let closed = self
+            .positions
+            .values()
+            .filter(|position| position.account == account && !keys.contains(&position.key))
+            .map(|position| position.key)
+            .collect();
+        self.merge(closed, keys, read)
+    }
+
+    // Removes the closed positions and stores the read ones, returning what changed.
+    fn merge(
+        &mut self,
+        closed: Vec<FieldElement>,
+        keys: &HashSet<FieldElement>,
+        read: Vec<Position>,
+    ) -> ScanDiff {
+        let mut diff = ScanDiff {
+            closed,
+            ..ScanDiff::default()
+        };
+        for key in &diff.closed {
+            self.positions.remove(key);
+        }
+        for position in read {
+            // Positions closed between the key and position reads come back empty.
+            if position.size_in_usd == (U256 { low: 0, high: 0 }) || !keys.contains(&position.key) {
+                continue;
+            }
+            match self.positions.insert(position.key, position.clone()) {
+                None => diff.opened.push(position.key),
+                Some(previous) if previous != position => diff.updated.push(position.key),
+                Some(_) => {}
+            }
+        }
+        diff
+    }
+}
+
+// Drops the positions read whose key is not the one of their account, market, collateral token and
+// side, so a position never gets liquidated under the key of another one.
+// @read: The positions read from the DataStore.
+pub fn drop_mismatched_keys(read: Vec<Position>) -> Vec<Position> {
+    read.into_iter()
+        .filter(|position| {
+            let key = position_key(
+                position.account,
+                position.market,
+                position.collateral_token,
+                position.is_long,
+            );
+            if key != position.key {
+                error!(
+                    "Position {:#x} read with fields hashing to {:#x}, dropped",
+                    position.key, key
+                );
+            }
+            key == position.key
+        })
+        .collect()
+}
+
+// Returns the start and end indexes of the pages of position keys, the end being excluded.
+// @count: The number of positions.
+// @page_size: The number of keys per page.
+pub fn get_page_ranges(count: u32, page_size: u32) -> Vec<(u32, u32)> {
+    (0..count)
+        .step_by(page_size as usize)
+        .map(|start| (start, start.saturating_add(page_size).min(count)))
+        .collect()
+}
+
+// Runs reads in their own tasks, at most `concurrency` at once, the results in no given order.
+// @items: The items to read.
+// @concurrency: The largest number of reads made at once.
+// @read: The read of an item.
+async fn read_concurrently<I, T, F, Fut>(
+    items: Vec<I>,
+    concurrency: usize,
+    read: F,
+) -> Result<Vec<T>, KeeperError>
+where
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = Result<T, KeeperError>> + Send + 'static,
+    T: Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut reads = JoinSet::new();
+    for item in items {
+        let semaphore = Arc::clone(&semaphore);
+        let read = read(item);
+        reads.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("Semaphore closed");
+            read.await
+        });
+    }
+    let mut results = Vec::new();
+    while let Some(result) = reads.join_next().await {
+        results.push(
+            result
+                .map_err(|e| KeeperError::ExecutionError(format!("read panicked: {:?}", e)))??,
+        );
+    }
+    Ok(results)
+}
+
+// Scans the DataStore positions, reading their keys page by page and the positions the book does
+// not know yet, or all of them on full refreshes, then applies the scan to the book.
+// @context: The keeper context, the DataStore being read through its contracts.
+// @book: The positions as of the last scan.
+// @params: How the positions get scanned.
+pub async fn scan_positions(
+    context: &Arc<KeeperContext>,
+    book: &RwLock<PositionBook>,
+    params: &ScanParams,
+) -> Result<ScanDiff, KeeperError> {
+    let to_error = |e| KeeperError::ExecutionError(format!("could not scan positions: {:?}", e));
+    let count = context
+        .contracts
+        .data_store
+        .get_position_count()
+        .call()
+        .await
+        .map_err(to_error)?;
+
+    let pages = read_concurrently(
+        get_page_ranges(count, params.page_size),
+        params.concurrency,
+        |(start, end)| {
+            let context = Arc::clone(context);
+            async move {
+                context
+                    .contracts
+                    .data_store
+                    .get_position_keys(&start, &end)
+                    .call()
+                    .await
+                    .map_err(to_error)
+            }
+        },
+    )
+    .await?;
+    let keys: HashSet<FieldElement> = pages.into_iter().flatten().collect();
+
+    let to_read: Vec<FieldElement> = {
+        let book = book.read().unwrap();
+        let full_refresh = book.scans.is_multiple_of(params.full_refresh_scans);
+        keys.iter()
+            .filter(|key| full_refresh || !book.positions.contains_key(key))
+            .copied()
+            .collect()
+    };
+    let read = read_concurrently(to_read, params.concurrency, |key| {
+        let context = Arc::clone(context);
+        async move {
+            context
+                .contracts
+                .data_store
+                .get_position(&key)
+                .call()
+                .await
+                .map_err(to_error)
+        }
+    })
+    .await?;
+
+    Ok(book
+        .write()
+        .unwrap()
+        .apply(&keys, drop_mismatched_keys(read)))
+}
+
+// Reads the positions of an account and applies them to the book, so positions increased or
+// decreased get watched without waiting for the next scan.
+// @context: The keeper context, the DataStore being read through its contracts.
+// @book: The positions as of the last scan.
+// @params: How the positions get read.
+// @account: The account of the position increased or decreased.
+pub async fn refresh_account(
+    context: &Arc<KeeperContext>,
+    book: &RwLock<PositionBook>,
+    params: &ScanParams,
+    account: ContractAddress,
+) -> Result<ScanDiff, KeeperError> {
+    let to_error = move |e| {
+        KeeperError::ExecutionError(format!(
+            "could not read positions of {:#x}: {:?}",
+            FieldElement::from(account),
+            e
+        ))
+    };
+    let count = context
+        .contracts
+        .data_store
+        .get_account_position_count(&account)
+        .call()
+        .await
+        .map_err(to_error)?;
+
+    let pages = read_concurrently(
+        get_page_ranges(count, params.page_size),
+        params.concurrency,
+        |(start, end)| {
+            let context = Arc::clone(context);
+            async move {
+                context
+                    .contracts
+                    .data_store
+                    .get_account_position_keys(&account, &start, &end)
+                    .call()
+                    .await
+                    .map_err(to_error)
+            }
+        },
+    )
+    .await?;
+    let keys: HashSet<FieldElement> = pages.into_iter().flatten().collect();
+
+    let read = read_concurrently(keys.iter().copied().collect(), params.concurrency, |key| {
+        let context = Arc::clone(context);
+        async move {
+            context
+                .contracts
+                .data_store
+                .get_position(&key)
+                .call()
+                .await
+                .map_err(to_error)
+        }
+    })
+    .await?;
+
+    Ok(book
+        .write()
+        .unwrap()
+        .apply_account(account, &keys, drop_mismatched_keys(read)))
+}
+
+// Scans the DataStore positions every scan interval in the background, so scanning thousands of
+// positions never holds the executions back. The indexed position events keep the book current in
+// between, scans reconciling the positions whose events got missed.
+// @context: The keeper context, holding the position book.
+// @params: How the positions get scanned.
+pub async fn run_position_scanner(context: Arc<KeeperContext>, params: ScanParams) {
+    let interval = match params.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    loop {
+        match scan_positions(&context, &context.positions, &params).await {
+            Ok(diff) if !diff.is_empty() => info!(
+                "Scanned {} positions: {} opened, {} updated, {} closed",
+                context.positions.read().unwrap().positions.len(),
+                diff.opened.len(),
+                diff.updated.len(),
+                diff.closed.len()
+            ),
+            Ok(_) => {}
+            Err(e) => error!("{}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(key: u64, size_in_usd: u128) -> Position {
+        let zero = U256 { low: 0, high: 0 };
+        Position {
+            key: FieldElement::from(key),
+            account: ContractAddress::from(FieldElement::ONE),
+            market: ContractAddress::from(FieldElement::TWO),
+            collateral_token: ContractAddress::from(FieldElement::THREE),
+            size_in_usd: U256 {
+                low: size_in_usd,
+                high: 0,
+            },
+            size_in_tokens: zero,
+            collateral_amount: zero,
+            borrowing_factor: zero,
+            funding_fee_amount_per_size: zero,
+            long_token_claimable_funding_amount_per_size: zero,
+            short_token_claimable_funding_amount_per_size: zero,
+            increased_at_block: 1,
+            decreased_at_block: 0,
+            is_long: true,
+        }
+    }
+
+    #[test]
+    fn test_get_page_ranges() {
+        assert_eq!(get_page_ranges(0, 100), vec![]);
+        assert_eq!(get_page_ranges(100, 100), vec![(0, 100)]);
+        assert_eq!(
+            get_page_ranges(250, 100),
+            vec![(0, 100), (100, 200), (200, 250)]
+        );
+    }
+
+    #[test]
+    fn test_apply_scan() {
+        let mut book = PositionBook::new();
+        let keys = HashSet::from([FieldElement::from(1u8), FieldElement::from(2u8)]);
+        let diff = book.apply(&keys, vec![position(1, 10), position(2, 20)]);
+        assert_eq!(diff.opened.len(), 2);
+        assert_eq!(book.scans, 1);
+
+        // Position 1 got increased, 2 closed and 3 opened, 4 closed before being read.
+        let keys = HashSet::from([
+            FieldElement::from(1u8),
+            FieldElement::from(3u8),
+            FieldElement::from(4u8),
+        ]);
+        let diff = book.apply(
+            &keys,
+            vec![position(1, 15), position(3, 30), position(4, 0)],
+        );
+        assert_eq!(
+            diff,
+            ScanDiff {
+                opened: vec![FieldElement::from(3u8)],
+                updated: vec![FieldElement::from(1u8)],
+                closed: vec![FieldElement::from(2u8)],
+            }
+        );
+        assert_eq!(book.positions.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_account() {
+        let mut book = PositionBook::new();
+        let mut other = position(3, 30);
+        other.account = ContractAddress::from(FieldElement::TWO);
+        let keys = HashSet::from([
+            FieldElement::from(1u8),
+            FieldElement::from(2u8),
+            FieldElement::from(3u8),
+        ]);
+        book.apply(&keys, vec![position(1, 10), position(2, 20), other]);
+
+        // Position 2 of the account got closed, the position of the other account is kept.
+        let account = ContractAddress::from(FieldElement::ONE);
+        let keys = HashSet::from([FieldElement::from(1u8)]);
+        let diff = book.apply_account(account, &keys, vec![position(1, 10)]);
+        assert_eq!(
+            diff,
+            ScanDiff {
+                closed: vec![FieldElement::from(2u8)],
+                ..ScanDiff::default()
+            }
+        );
+        assert_eq!(book.positions.len(), 2);
+        assert_eq!(book.scans, 1);
+    }
+
+    #[test]
+    fn test_drop_mismatched_keys() {
+        let mut valid = position(0, 10);
+        valid.key = position_key(
+            valid.account,
+            valid.market,
+            valid.collateral_token,
+            valid.is_long,
+        );
+        let mut other_side = valid.clone();
+        other_side.is_long = false;
+        assert_eq!(
+            drop_mismatched_keys(vec![valid.clone(), other_side, position(1, 10)]),
+            vec![valid]
+        );
+    }
+}
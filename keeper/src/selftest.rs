@@ -0,0 +1,154 @@
+use log::info;
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{Account, ExecutionEncoding, SingleOwnerAccount},
+    core::{
+        chain_id,
+        types::{BlockId, FieldElement},
+    },
+    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    signers::{LocalWallet, SigningKey},
+};
+use url::Url;
+
+use crate::{
+    bench::load_order,
+    config,
+    contracts::Contracts,
+    error::KeeperError,
+    snapshot::{simulate_calls, SimulationResult},
+    trade::{order::handle::get_order_calls, revert::decode_revert_reason},
+};
+
+// The error of executions of orders missing from the DataStore, which never reached the protocol
+// logic.
+const EMPTY_ORDER: &str = "EMPTY_ORDER";
+
+// Checks the simulated execution of the self-test order reached the protocol: it either executed
+// or reverted with one of the Satoru errors the operator expects. Anything else, e.g. a missing
+// entry point or contract, means the bindings or the addresses are wrong. An order missing from
+// the DataStore is never accepted, its execution reverting before any price or market got used.
+// @revert_reason: The revert reason of the simulated execution, None if it succeeded.
+// @expected_errors: The Satoru errors the execution may revert with.
+pub fn check_simulation(
+    revert_reason: Option<&str>,
+    expected_errors: &[String],
+) -> Result<(), KeeperError> {
+    let reason = match revert_reason {
+        Some(reason) => reason,
+        None => return Ok(()),
+    };
+    match decode_revert_reason(reason) {
+        Some(error) if error == EMPTY_ORDER => Err(KeeperError::SelfTestError(
+            "the order is not pending at the simulated block".to_owned(),
+        )),
+        Some(error) if expected_errors.contains(&error) => Ok(()),
+        Some(error) => Err(KeeperError::SelfTestError(format!(
+            "execution reverted with {}",
+            error
+        ))),
+        None => Err(KeeperError::SelfTestError(format!(
+            "execution reverted: {}",
+            reason
+        ))),
+    }
+}
+
+// Simulates the execution of a known historical order without broadcasting it, validating the ABI
+// bindings, the contract addresses and the oracle configuration before the keeper gets ready. The
+// order gets loaded from the indexer with its market, and its execution simulated at a block it was
+// pending at. Skipped when no self-test order is configured.
+// @contracts: The keeper contracts, the simulation skipping the signature validation and fees of
+// their account.
+// @pool: A reference to a connection pool for PostgreSQL, the order being read from it.
+pub async fn run_self_test(
+    contracts: &Contracts,
+    pool: &Pool<Postgres>,
+) -> Result<(), KeeperError> {
+    let key = match config::get_self_test_order_key() {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    let felt_key = FieldElement::from_hex_be(&key)
+        .map_err(|_| KeeperError::SelfTestError(format!("invalid order key {}", key)))?;
+    let order = load_order(pool, &format!("{:#x}", felt_key))
+        .await
+        .map_err(|e| KeeperError::SelfTestError(format!("could not load order {}: {:?}", key, e)))?
+        .ok_or_else(|| KeeperError::SelfTestError(format!("order {} is not indexed", key)))?;
+    // The order is pending from the block it got created at until it got executed.
+    let block = match config::get_self_test_order_block() {
+        0 => order.block_number,
+        block => block,
+    };
+    let calls = get_order_calls(contracts, order).await?;
+    // The simulation reads the state of the block through a node of RPC_URL, from the keeper
+    // address, the signature being skipped.
+    let rpc_url = std::env::var("RPC_URL").map_err(|_| KeeperError::RpcUrlNotSet())?;
+    let provider =
+        JsonRpcClient::new(HttpTransport::new(Url::parse(&rpc_url).map_err(|e| {
+            KeeperError::ProviderUrlError(format!("invalid rpc url: {}", e))
+        })?));
+    let mut account = SingleOwnerAccount::new(
+        provider,
+        LocalWallet::from(SigningKey::from_secret_scalar(FieldElement::ONE)),
+        contracts.account.address(),
+        chain_id::TESTNET,
+        ExecutionEncoding::Legacy,
+    );
+    account.set_block_id(BlockId::Number(block));
+    let revert_reason = match simulate_calls(&account, calls).await {
+        SimulationResult::Succeeded => None,
+        SimulationResult::Reverted { reason, .. } => Some(reason),
+        SimulationResult::Failed { reason } => {
+            return Err(KeeperError::SelfTestError(format!(
+                "simulation failed: {}",
+                reason
+            )))
+        }
+    };
+    check_simulation(
+        revert_reason.as_deref(),
+        &config::get_self_test_expected_errors(),
+    )?;
+    info!(
+        "Self-test passed simulating order {} at block {}",
+        key, block
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_simulation() {
+        let expected_errors = vec!["MAX_PRICE_AGE_EXCEEDED".to_owned()];
+        assert!(check_simulation(None, &expected_errors).is_ok());
+        // 0x6d61785f70726963655f6167655f6578636565646564 is 'max_price_age_exceeded'.
+        assert!(check_simulation(
+            Some(
+                "Execution failed. Failure reason: 0x6d61785f70726963655f6167655f6578636565646564."
+            ),
+            &expected_errors
+        )
+        .is_ok());
+        // 0x656d7074795f6f72646572 is 'empty_order', an order the simulation never reached.
+        assert!(check_simulation(
+            Some("Execution failed. Failure reason: 0x656d7074795f6f72646572."),
+            &["EMPTY_ORDER".to_owned()]
+        )
+        .is_err());
+        // 0x696e73756666696369656e745f72657365727665 is 'insufficient_reserve'.
+        assert!(check_simulation(
+            Some("Execution failed. Failure reason: 0x696e73756666696369656e745f72657365727665."),
+            &expected_errors
+        )
+        .is_err());
+        assert!(check_simulation(
+            Some("Requested contract address is not deployed"),
+            &expected_errors
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,542 @@
+use async_trait::async_trait;
+use starknet::{
+    accounts::{
+        Account, Call, ConnectedAccount, ExecutionEncoder, ExecutionEncoding, RawDeclaration,
+        RawExecution, RawLegacyDeclaration, SingleOwnerAccount,
+    },
+    core::{
+        crypto::{compute_hash_on_elements, pedersen_hash},
+        types::{BlockId, FieldElement},
+        utils::{cairo_short_string_to_felt, get_selector_from_name, starknet_keccak},
+    },
+    providers::Provider,
+    signers::{LocalWallet, Signer},
+};
+
+use crate::{config, contracts::ContractAddresses, error::KeeperError};
+
+// SNIP-12 revision 0 types of the sessions of the Argent SessionKey plugin, which the account
+// checks the owner signature of and the called methods against on every transaction.
+const DOMAIN_TYPE: &str = "StarkNetDomain(chainId:felt)";
+const SESSION_TYPE: &str = "Session(key:felt,expires:felt,root:merkletree)";
+const POLICY_TYPE: &str = "Policy(contractAddress:felt,selector:selector)";
+// The account method selecting the plugin validating a transaction, called first.
+const USE_PLUGIN: &str = "use_plugin";
+
+// The methods the keeper calls, allowed by default when their contract is configured.
+const KEEPER_METHODS: [(&str, &str); 5] = [
+    ("ORACLE", "set_primary_price"),
+    ("ORDER_HANDLER", "execute_order"),
+    ("NEXT_ORDER_HANDLER", "execute_order"),
+    ("DEPOSIT_HANDLER", "execute_deposit"),
+    ("WITHDRAWAL_HANDLER", "execute_withdrawal"),
+];
+
+// Hashes two merkle tree nodes, the smallest first, so proofs need no side.
+fn hash_nodes(a: FieldElement, b: FieldElement) -> FieldElement {
+    match a <= b {
+        true => pedersen_hash(&a, &b),
+        false => pedersen_hash(&b, &a),
+    }
+}
+
+// A struct representing a method a session key is allowed to call.
+// @contract_address: The contract of the method.
+// @selector: The selector of the method.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllowedMethod {
+    pub contract_address: FieldElement,
+    pub selector: FieldElement,
+}
+
+impl AllowedMethod {
+    // The merkle tree leaf of the method, its Policy struct hash.
+    pub fn leaf(&self) -> FieldElement {
+        compute_hash_on_elements(&[
+            starknet_keccak(POLICY_TYPE.as_bytes()),
+            self.contract_address,
+            self.selector,
+        ])
+    }
+
+    // Parses an allowed method formatted as contract:method, the contract being either an address
+    // or the name of its env variable, e.g. ORDER_HANDLER:execute_order.
+    // @method: The allowed method.
+    // @addresses: The keeper contract addresses the contract names resolve to.
+    pub fn parse(method: &str, addresses: &ContractAddresses) -> Result<Self, KeeperError> {
+        let invalid = |reason: &str| {
+            KeeperError::SessionError(format!("invalid allowed method {}: {}", method, reason))
+        };
+        let (contract, name) = method
+            .split_once(':')
+            .ok_or_else(|| invalid("expected contract:method"))?;
+        let contract_address = match contract.starts_with("0x") {
+            true => FieldElement::from_hex_be(contract)
+                .map_err(|_| invalid("invalid contract address"))?,
+            false => addresses
+                .get(contract)
+                .map_err(|_| invalid("contract has no address"))?,
+        };
+        Ok(AllowedMethod {
+            contract_address,
+            selector: get_selector_from_name(name).map_err(|_| invalid("invalid method name"))?,
+        })
+    }
+}
+
+// A struct representing the session the account owner granted to the keeper hot key, as the Argent
+// SessionKey plugin checks it: every transaction starts with a use_plugin call carrying the
+// session, the merkle proofs of the called methods and the owner signature of the session, and
+// gets signed by the session key alone.
+// @plugin_class_hash: The class hash of the session plugin, as registered on the account.
+// @public_key: The public key of the session key.
+// @expires_at: The unix timestamp the session expires at.
+// @allowed_methods: The only methods the session key can call, the leaves of the session root.
+// @authorization: The owner signature of the session message hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub plugin_class_hash: FieldElement,
+    pub public_key: FieldElement,
+    pub expires_at: u64,
+    pub allowed_methods: Vec<AllowedMethod>,
+    pub authorization: Vec<FieldElement>,
+}
+
+impl Session {
+    pub fn from_env(
+        public_key: FieldElement,
+        addresses: &ContractAddresses,
+    ) -> Result<Self, KeeperError> {
+        let allowed_methods = match config::get_session_allowed_methods() {
+            methods if methods.is_empty() => KEEPER_METHODS
+                .iter()
+                .filter(|(contract, _)| addresses.get(contract).is_ok())
+                .map(|(contract, name)| {
+                    AllowedMethod::parse(&format!("{}:{}", contract, name), addresses)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            methods => methods
+                .iter()
+                .map(|method| AllowedMethod::parse(method, addresses))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        let authorization = config::get_session_authorization()
+            .iter()
+            .map(|felt| {
+                FieldElement::from_hex_be(felt).map_err(|_| {
+                    KeeperError::SessionError(format!("invalid session authorization {}", felt))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if allowed_methods.is_empty() {
+            return Err(KeeperError::SessionError(
+                "the session allows no method".to_owned(),
+            ));
+        }
+        let plugin_class_hash = config::get_session_plugin_class_hash();
+        Ok(Session {
+            plugin_class_hash: FieldElement::from_hex_be(&plugin_class_hash).map_err(|_| {
+                KeeperError::SessionError(format!(
+                    "invalid session plugin class hash {}",
+                    plugin_class_hash
+                ))
+            })?,
+            public_key,
+            expires_at: config::get_session_expires_at(),
+            allowed_methods,
+            authorization,
+        })
+    }
+
+    // Checks the session allows a multicall, so calls the account contract would reject never
+    // get sent.
+    // @calls: The calls of the multicall.
+    // @now: The current unix timestamp.
+    pub fn check_calls(&self, calls: &[Call], now: u64) -> Result<(), KeeperError> {
+        if now >= self.expires_at {
+            return Err(KeeperError::SessionError(format!(
+                "session expired at {}",
+                self.expires_at
+            )));
+        }
+        for call in calls {
+            let allowed = self.allowed_methods.iter().any(|method| {
+                method.contract_address == call.to && method.selector == call.selector
+            });
+            if !allowed {
+                return Err(KeeperError::SessionError(format!(
+                    "selector {:#x} of contract {:#x} not allowed for the session",
+                    call.selector, call.to
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // The layers of the merkle tree of the allowed methods, from the leaves to the root. Odd nodes
+    // get hashed with 0, as starknet.js builds SNIP-12 merkle trees.
+    fn tree(&self) -> Vec<Vec<FieldElement>> {
+        let mut layers = vec![self
+            .allowed_methods
+            .iter()
+            .map(AllowedMethod::leaf)
+            .collect::<Vec<_>>()];
+        while layers.last().map_or(0, Vec::len) > 1 || layers.len() == 1 {
+            let layer = layers.last().expect("the leaves are the first layer");
+            let next = layer
+                .chunks(2)
+                .map(|pair| hash_nodes(pair[0], pair.get(1).copied().unwrap_or_default()))
+                .collect();
+            layers.push(next);
+        }
+        layers
+    }
+
+    // The root of the merkle tree of the allowed methods.
+    pub fn root(&self) -> FieldElement {
+        self.tree()
+            .last()
+            .and_then(|root| root.first().copied())
+            .unwrap_or_default()
+    }
+
+    // Returns the merkle proof of an allowed method, from its sibling leaf up, None when the
+    // session does not allow it.
+    // @method: The method.
+    pub fn proof(&self, method: &AllowedMethod) -> Option<Vec<FieldElement>> {
+        let mut index = self
+            .allowed_methods
+            .iter()
+            .position(|allowed| allowed == method)?;
+        let tree = self.tree();
+        let mut proof = vec![];
+        for layer in &tree[..tree.len() - 1] {
+            proof.push(layer.get(index ^ 1).copied().unwrap_or_default());
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    // The SNIP-12 message hash of the session, which the account owner signs into authorization.
+    // @chain_id: The chain the account is deployed on.
+    // @account: The account address.
+    pub fn message_hash(&self, chain_id: FieldElement, account: FieldElement) -> FieldElement {
+        let domain_hash =
+            compute_hash_on_elements(&[starknet_keccak(DOMAIN_TYPE.as_bytes()), chain_id]);
+        let session_hash = compute_hash_on_elements(&[
+            starknet_keccak(SESSION_TYPE.as_bytes()),
+            self.public_key,
+            FieldElement::from(self.expires_at),
+            self.root(),
+        ]);
+        compute_hash_on_elements(&[
+            cairo_short_string_to_felt("StarkNet Message").expect("Invalid short string"),
+            domain_hash,
+            account,
+            session_hash,
+        ])
+    }
+
+    // Builds the use_plugin call a multicall starts with, carrying the session and the proofs of
+    // the called methods. Methods the session does not allow get a proof of zeros, check_calls
+    // keeping them from being sent.
+    // @account: The account address.
+    // @calls: The calls of the multicall.
+    pub fn plugin_call(&self, account: FieldElement, calls: &[Call]) -> Call {
+        let depth = self.tree().len() - 1;
+        let proofs: Vec<FieldElement> = calls
+            .iter()
+            .flat_map(|call| {
+                self.proof(&AllowedMethod {
+                    contract_address: call.to,
+                    selector: call.selector,
+                })
+                .unwrap_or_else(|| vec![FieldElement::ZERO; depth])
+            })
+            .collect();
+        let mut calldata = vec![
+            self.plugin_class_hash,
+            self.public_key,
+            FieldElement::from(self.expires_at),
+            self.root(),
+            FieldElement::from(depth),
+            FieldElement::from(proofs.len()),
+        ];
+        calldata.extend(proofs);
+        calldata.push(FieldElement::from(self.authorization.len()));
+        calldata.extend_from_slice(&self.authorization);
+        Call {
+            to: account,
+            selector: get_selector_from_name(USE_PLUGIN).expect("Invalid selector name"),
+            calldata,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionSignError {
+    #[error("session key signature failed: {0}")]
+    Signer(String),
+    #[error("session keys cannot declare classes")]
+    Declaration,
+}
+
+// An account contract executing through a session key, so the key on the keeper host cannot call
+// anything but the allowed methods even if compromised.
+// @inner: The account seen as owned by the session key, used to encode calls and read the chain.
+// @signer: The session key.
+// @session: The session granted to the key.
+pub struct SessionAccount<P: Provider + Send> {
+    inner: SingleOwnerAccount<P, LocalWallet>,
+    signer: LocalWallet,
+    session: Session,
+}
+
+impl<P: Provider + Sync + Send> SessionAccount<P> {
+    pub fn new(
+        provider: P,
+        signer: LocalWallet,
+        session: Session,
+        address: FieldElement,
+        chain_id: FieldElement,
+        encoding: ExecutionEncoding,
+    ) -> Self {
+        SessionAccount {
+            inner: SingleOwnerAccount::new(provider, signer.clone(), address, chain_id, encoding),
+            signer,
+            session,
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Sync + Send> Account for SessionAccount<P> {
+    type SignError = SessionSignError;
+
+    fn address(&self) -> FieldElement {
+        self.inner.address()
+    }
+
+    fn chain_id(&self) -> FieldElement {
+        self.inner.chain_id()
+    }
+
+    async fn sign_execution(
+        &self,
+        execution: &RawExecution,
+        query_only: bool,
+    ) -> Result<Vec<FieldElement>, Self::SignError> {
+        let transaction_hash =
+            execution.transaction_hash(self.chain_id(), self.address(), query_only, self);
+        let signature = self
+            .signer
+            .sign_hash(&transaction_hash)
+            .await
+            .map_err(|e| SessionSignError::Signer(format!("{:?}", e)))?;
+        Ok(vec![signature.r, signature.s])
+    }
+
+    async fn sign_declaration(
+        &self,
+        _declaration: &RawDeclaration,
+        _query_only: bool,
+    ) -> Result<Vec<FieldElement>, Self::SignError> {
+        Err(SessionSignError::Declaration)
+    }
+
+    async fn sign_legacy_declaration(
+        &self,
+        _legacy_declaration: &RawLegacyDeclaration,
+        _query_only: bool,
+    ) -> Result<Vec<FieldElement>, Self::SignError> {
+        Err(SessionSignError::Declaration)
+    }
+}
+
+// The calls get sent behind the use_plugin call, which the account strips before executing them.
+impl<P: Provider + Sync + Send> ExecutionEncoder for SessionAccount<P> {
+    fn encode_calls(&self, calls: &[Call]) -> Vec<FieldElement> {
+        let mut with_plugin = vec![self.session.plugin_call(self.address(), calls)];
+        with_plugin.extend_from_slice(calls);
+        self.inner.encode_calls(&with_plugin)
+    }
+}
+
+impl<P: Provider + Sync + Send> ConnectedAccount for SessionAccount<P> {
+    type Provider = P;
+
+    fn provider(&self) -> &Self::Provider {
+        self.inner.provider()
+    }
+
+    fn block_id(&self) -> BlockId {
+        self.inner.block_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn felt(hex: &str) -> FieldElement {
+        FieldElement::from_hex_be(hex).unwrap()
+    }
+
+    fn session() -> Session {
+        Session {
+            plugin_class_hash: felt("0x9"),
+            public_key: felt("0x5"),
+            expires_at: 1000,
+            allowed_methods: [
+                "0x12:execute_order",
+                "0x13:set_primary_price",
+                "0x14:execute_deposit",
+            ]
+            .iter()
+            .map(|method| AllowedMethod::parse(method, &ContractAddresses::default()).unwrap())
+            .collect(),
+            authorization: vec![felt("0x7"), felt("0x8")],
+        }
+    }
+
+    fn call(to: &str, method: &str) -> Call {
+        Call {
+            to: FieldElement::from_hex_be(to).unwrap(),
+            selector: get_selector_from_name(method).unwrap(),
+            calldata: vec![],
+        }
+    }
+
+    #[test]
+    fn test_parse_allowed_method() {
+        let addresses = ContractAddresses::resolve(&HashMap::new(), |name| {
+            (name == "ORDER_HANDLER").then(|| "0x12".to_owned())
+        })
+        .unwrap();
+        let execute_order = AllowedMethod {
+            contract_address: FieldElement::from_hex_be("0x12").unwrap(),
+            selector: get_selector_from_name("execute_order").unwrap(),
+        };
+        assert_eq!(
+            AllowedMethod::parse("0x12:execute_order", &addresses).unwrap(),
+            execute_order
+        );
+        assert_eq!(
+            AllowedMethod::parse("ORDER_HANDLER:execute_order", &addresses).unwrap(),
+            execute_order
+        );
+        assert!(AllowedMethod::parse("execute_order", &addresses).is_err());
+        assert!(AllowedMethod::parse("ORACLE:set_primary_price", &addresses).is_err());
+        assert!(
+            AllowedMethod::parse("SESSION_TEST_UNSET_CONTRACT:execute_order", &addresses).is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_calls() {
+        let session = session();
+        assert!(session
+            .check_calls(&[call("0x12", "execute_order")], 999)
+            .is_ok());
+        assert!(session
+            .check_calls(
+                &[call("0x12", "execute_order"), call("0x12", "transfer")],
+                999
+            )
+            .is_err());
+        assert!(session
+            .check_calls(&[call("0x13", "execute_order")], 999)
+            .is_err());
+        assert!(session
+            .check_calls(&[call("0x12", "execute_order")], 1000)
+            .is_err());
+    }
+
+    // The expected hashes were computed offline with the SNIP-12 revision 0 encoder of
+    // starknet-core 0.16, whose merkletree root matches, and the type hashes of the Argent
+    // SessionKey plugin.
+    #[test]
+    fn test_session_root() {
+        let session = session();
+        assert_eq!(
+            session.allowed_methods[0].leaf(),
+            felt("0x36c93e232852e4c839065b7541df448fcd6759d207683e40f20d87e36b91366")
+        );
+        assert_eq!(
+            session.root(),
+            felt("0x4ac1c377200b034d500f3eb298d60e91e8f759ce234c517f5e50d7f375890d0")
+        );
+        assert_eq!(
+            session.proof(&session.allowed_methods[0]).unwrap(),
+            vec![
+                felt("0x74452cdff3d0185b25aa7bbfb04c9e407aefad806cbfa37e8d5acefba086d5a"),
+                felt("0x39e7e93e1d9f831daf939b0b30e6889727bbd3090fbcfcd9b41ed7b2a5fd1a7"),
+            ]
+        );
+        // The odd leaf gets paired with 0.
+        assert_eq!(
+            session.proof(&session.allowed_methods[2]).unwrap(),
+            vec![
+                FieldElement::ZERO,
+                felt("0x1ebfe255eb684a1f353b411c0e03dee46af058f217264ba1d4cfe3e7890bb63"),
+            ]
+        );
+        let single = Session {
+            allowed_methods: session.allowed_methods[..1].to_vec(),
+            ..session.clone()
+        };
+        assert_eq!(
+            single.root(),
+            felt("0x1b481546358eb3545f9c8699500a34ef3452f304414380ecfc5895dee9c7a6")
+        );
+        assert_eq!(
+            session.message_hash(
+                cairo_short_string_to_felt("SN_GOERLI").unwrap(),
+                felt("0x1234")
+            ),
+            felt("0x398353d2601f5279173ff0765a60f5e6a438acb2c5dc6aea1b33519f0797d31")
+        );
+    }
+
+    #[test]
+    fn test_plugin_call() {
+        let session = session();
+        let calls = [
+            call("0x13", "set_primary_price"),
+            call("0x12", "execute_order"),
+        ];
+        let plugin_call = session.plugin_call(felt("0x1234"), &calls);
+
+        assert_eq!(plugin_call.to, felt("0x1234"));
+        assert_eq!(
+            plugin_call.selector,
+            get_selector_from_name("use_plugin").unwrap()
+        );
+        let proofs = [
+            session.proof(&session.allowed_methods[1]).unwrap(),
+            session.proof(&session.allowed_methods[0]).unwrap(),
+        ]
+        .concat();
+        let mut calldata = vec![
+            felt("0x9"),
+            felt("0x5"),
+            FieldElement::from(1000u64),
+            session.root(),
+            FieldElement::TWO,
+            FieldElement::from(4u8),
+        ];
+        calldata.extend(proofs);
+        calldata.extend([FieldElement::TWO, felt("0x7"), felt("0x8")]);
+        assert_eq!(plugin_call.calldata, calldata);
+
+        // Methods the session does not allow get a proof the plugin rejects.
+        let plugin_call = session.plugin_call(felt("0x1234"), &[call("0x12", "transfer")]);
+        assert_eq!(&plugin_call.calldata[6..8], &[FieldElement::ZERO; 2]);
+    }
+}
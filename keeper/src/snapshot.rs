@@ -0,0 +1,394 @@
+use std::{fs, path::PathBuf};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{Account, Call, ConnectedAccount, ExecutionEncoding, SingleOwnerAccount},
+    core::{
+        chain_id,
+        types::{BlockId, ExecuteInvocation, FieldElement, TransactionTrace},
+        utils::get_selector_from_name,
+    },
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::{LocalWallet, SigningKey},
+};
+
+use crate::{
+    config, error::KeeperError, logging::format_timestamp, trade::revert::decode_revert_reason,
+    types::SatoruAction,
+};
+
+// Method of the Oracle the executions set the price of their market with.
+const SET_PRIMARY_PRICE: &str = "set_primary_price";
+
+fn snapshot_error(reason: String) -> KeeperError {
+    KeeperError::SnapshotError(reason)
+}
+
+// A struct representing a call of an execution multicall, felts being hex encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallSnapshot {
+    pub to: String,
+    pub selector: String,
+    pub calldata: Vec<String>,
+}
+
+impl CallSnapshot {
+    pub fn from_call(call: &Call) -> Self {
+        CallSnapshot {
+            to: format!("{:#x}", call.to),
+            selector: format!("{:#x}", call.selector),
+            calldata: call
+                .calldata
+                .iter()
+                .map(|felt| format!("{:#x}", felt))
+                .collect(),
+        }
+    }
+
+    pub fn to_call(&self) -> Result<Call, KeeperError> {
+        let felt = |felt: &str| {
+            FieldElement::from_hex_be(felt)
+                .map_err(|_| snapshot_error(format!("invalid felt {}", felt)))
+        };
+        Ok(Call {
+            to: felt(&self.to)?,
+            selector: felt(&self.selector)?,
+            calldata: self
+                .calldata
+                .iter()
+                .map(|arg| felt(arg))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+// A struct representing a price an execution set, as decoded from its set_primary_price call.
+// @token: The token priced.
+// @price: The price, a decimal integer with the decimals of the protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceSnapshot {
+    pub token: String,
+    pub price: String,
+}
+
+// Decodes the prices set by the calls of an execution, the min and max prices of a Price struct
+// being the low and high felts of each u256.
+// @calls: The calls of the execution.
+pub fn get_set_prices(calls: &[Call]) -> Vec<PriceSnapshot> {
+    let selector = get_selector_from_name(SET_PRIMARY_PRICE).expect("Invalid method name");
+    let to_u128 = |felt: &FieldElement| u128::try_from(*felt).ok();
+    calls
+        .iter()
+        .filter(|call| call.selector == selector)
+        .filter_map(|call| match &call.calldata[..] {
+            [token, prices @ ..] if !prices.is_empty() => Some(PriceSnapshot {
+                token: format!("{:#x}", token),
+                price: prices
+                    .chunks(2)
+                    .map(|u256| match u256 {
+                        [low, high] if *high == FieldElement::ZERO => to_u128(low)
+                            .map(|low| low.to_string())
+                            .unwrap_or_else(|| format!("{:#x}", low)),
+                        _ => u256
+                            .iter()
+                            .map(|felt| format!("{:#x}", felt))
+                            .collect::<Vec<_>>()
+                            .join(":"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// An enum representing how a simulated execution went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum SimulationResult {
+    // The execution went through.
+    Succeeded,
+    // The execution reverted, with the raw reason and the Satoru error decoded from it.
+    Reverted {
+        reason: String,
+        error: Option<String>,
+    },
+    // The execution could not be simulated, e.g. as the node rejected it.
+    Failed {
+        reason: String,
+    },
+}
+
+// Simulates an execution multicall without broadcasting it, skipping the signature validation and
+// the fee charge, at the block the account reads from.
+// @account: The account simulating the execution.
+// @calls: The calls of the execution.
+pub async fn simulate_calls(
+    account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    calls: Vec<Call>,
+) -> SimulationResult {
+    match account
+        .execute(calls)
+        .max_fee(FieldElement::ZERO)
+        .simulate(true, true)
+        .await
+    {
+        Ok(simulation) => get_simulation_result(&simulation.transaction_trace),
+        Err(e) => SimulationResult::Failed {
+            reason: format!("{:?}", e),
+        },
+    }
+}
+
+// Returns how a simulated execution went from its trace.
+// @trace: The trace of the simulated transaction.
+pub fn get_simulation_result(trace: &TransactionTrace) -> SimulationResult {
+    match trace {
+        TransactionTrace::Invoke(trace) => match &trace.execute_invocation {
+            ExecuteInvocation::Success(_) => SimulationResult::Succeeded,
+            ExecuteInvocation::Reverted(reverted) => SimulationResult::Reverted {
+                error: decode_revert_reason(&reverted.revert_reason),
+                reason: reverted.revert_reason.clone(),
+            },
+        },
+        _ => SimulationResult::Failed {
+            reason: "simulation returned no invoke trace".to_owned(),
+        },
+    }
+}
+
+// A struct representing everything an execution that failed unexpectedly got built from, so the
+// exact call can be replayed locally with the replay-context subcommand.
+// @key: The key of the action.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The action as the keeper executed it.
+// @sender: The keeper account the execution got sent from.
+// @block_number: The latest block when the execution failed, the simulation running on it.
+// @captured_at: When the snapshot got taken, in RFC 3339 UTC.
+// @failure: Why the execution failed.
+// @prices: The prices the execution set.
+// @calls: The calls of the execution multicall, hooks included.
+// @simulation: How the execution went when simulated right after it failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionSnapshot {
+    pub key: String,
+    pub table: String,
+    pub action: SatoruAction,
+    pub sender: String,
+    pub block_number: u64,
+    pub captured_at: String,
+    pub failure: String,
+    pub prices: Vec<PriceSnapshot>,
+    pub calls: Vec<CallSnapshot>,
+    pub simulation: SimulationResult,
+}
+
+impl ExecutionSnapshot {
+    // Returns the calls of the execution, to replay them.
+    pub fn to_calls(&self) -> Result<Vec<Call>, KeeperError> {
+        self.calls.iter().map(CallSnapshot::to_call).collect()
+    }
+
+    // Returns the name the snapshot gets written to disk under, unique per action and time.
+    pub fn file_name(&self) -> String {
+        let captured_at: String = self
+            .captured_at
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        format!("{}-{}.json", self.key.trim_start_matches("0x"), captured_at)
+    }
+}
+
+// Takes the snapshot of a failed execution, simulating its calls again on the latest block.
+// @account: The keeper account, which sent the execution.
+// @table: The table the action comes from.
+// @action: The action executed.
+// @calls: The calls of the execution.
+// @failure: Why the execution failed.
+pub async fn capture_snapshot(
+    account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    table: &str,
+    action: &SatoruAction,
+    calls: Vec<Call>,
+    failure: &str,
+) -> ExecutionSnapshot {
+    let block_number = account.provider().block_number().await.unwrap_or_default();
+    ExecutionSnapshot {
+        key: action.key.clone(),
+        table: table.to_owned(),
+        action: action.clone(),
+        sender: format!("{:#x}", account.address()),
+        block_number,
+        captured_at: format_timestamp(crate::clock::get_system_timestamp() as u128 * 1000),
+        failure: failure.to_owned(),
+        prices: get_set_prices(&calls),
+        calls: calls.iter().map(CallSnapshot::from_call).collect(),
+        simulation: simulate_calls(account, calls).await,
+    }
+}
+
+// Records a snapshot in the keeper_execution_snapshots table, returns its id.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @snapshot: The snapshot.
+pub async fn save_snapshot(
+    pool: &Pool<Postgres>,
+    snapshot: &ExecutionSnapshot,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO keeper_execution_snapshots (key, table_name, snapshot) VALUES ($1, $2, $3)
+         RETURNING id",
+    )
+    .bind(&snapshot.key)
+    .bind(&snapshot.table)
+    .bind(serde_json::to_string(snapshot).expect("Could not encode snapshot."))
+    .fetch_one(pool)
+    .await
+}
+
+// Writes a snapshot as a JSON file of a directory, returns its path.
+// @dir: The directory, created when missing.
+// @snapshot: The snapshot.
+pub fn write_snapshot(dir: &str, snapshot: &ExecutionSnapshot) -> Result<PathBuf, KeeperError> {
+    let to_error = |e: std::io::Error| snapshot_error(format!("could not write to {}: {}", dir, e));
+    fs::create_dir_all(dir).map_err(to_error)?;
+    let path = PathBuf::from(dir).join(snapshot.file_name());
+    let json = serde_json::to_string_pretty(snapshot).expect("Could not encode snapshot.");
+    fs::write(&path, json).map_err(to_error)?;
+    Ok(path)
+}
+
+// Reads a snapshot written to disk, or exported from the keeper_execution_snapshots table.
+// @path: The path of the JSON file.
+pub fn read_snapshot(path: &str) -> Result<ExecutionSnapshot, KeeperError> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| snapshot_error(format!("could not read {}: {}", path, e)))?;
+    serde_json::from_str(&json).map_err(|e| snapshot_error(format!("invalid snapshot: {}", e)))
+}
+
+// Snapshots a failed execution into the database and, when configured, to disk, failures to
+// record it being only logged.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The keeper account, which sent the execution.
+// @table: The table the action comes from.
+// @action: The action executed.
+// @calls: The calls of the execution.
+// @failure: Why the execution failed.
+pub async fn record_failed_execution(
+    pool: &Pool<Postgres>,
+    account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    table: &str,
+    action: &SatoruAction,
+    calls: Vec<Call>,
+    failure: &str,
+) {
+    let snapshot = capture_snapshot(account, table, action, calls, failure).await;
+    match save_snapshot(pool, &snapshot).await {
+        Ok(id) => info!("Recorded execution snapshot {} of job {}", id, action.key),
+        Err(e) => error!("Could not record snapshot of job {}: {:?}", action.key, e),
+    }
+    if let Some(dir) = config::get_execution_snapshot_dir() {
+        match write_snapshot(&dir, &snapshot) {
+            Ok(path) => info!(
+                "Wrote execution snapshot of job {} to {:?}",
+                action.key, path
+            ),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+// Replays a snapshot, simulating its calls from its sender on the block it got taken at. The
+// signature being skipped, no key of the sender is needed.
+// @provider: The provider, of a node keeping the state of the snapshot block.
+// @snapshot: The snapshot.
+pub async fn replay_snapshot(
+    provider: JsonRpcClient<HttpTransport>,
+    snapshot: &ExecutionSnapshot,
+) -> Result<SimulationResult, KeeperError> {
+    let sender = FieldElement::from_hex_be(&snapshot.sender)
+        .map_err(|_| snapshot_error(format!("invalid sender {}", snapshot.sender)))?;
+    let mut account = SingleOwnerAccount::new(
+        provider,
+        LocalWallet::from(SigningKey::from_secret_scalar(FieldElement::ONE)),
+        sender,
+        chain_id::TESTNET,
+        ExecutionEncoding::Legacy,
+    );
+    account.set_block_id(BlockId::Number(snapshot.block_number));
+    Ok(simulate_calls(&account, snapshot.to_calls()?).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_snapshot_round_trip() {
+        let call = Call {
+            to: FieldElement::from(0x12_u8),
+            selector: get_selector_from_name("execute_order").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::TWO],
+        };
+        let snapshot = CallSnapshot::from_call(&call);
+        assert_eq!(snapshot.to, "0x12");
+        assert_eq!(snapshot.calldata, vec!["0x1", "0x2"]);
+        let replayed = snapshot.to_call().unwrap();
+        assert_eq!(
+            (replayed.to, replayed.selector, replayed.calldata),
+            (call.to, call.selector, call.calldata)
+        );
+        assert!(CallSnapshot {
+            to: "not a felt".to_owned(),
+            ..snapshot
+        }
+        .to_call()
+        .is_err());
+    }
+
+    #[test]
+    fn test_get_set_prices() {
+        let calls = vec![
+            Call {
+                to: FieldElement::from(0x12_u8),
+                selector: get_selector_from_name(SET_PRIMARY_PRICE).unwrap(),
+                // The min then max u256 of the price of token 0x34.
+                calldata: [0x34_u128, 3500, 0, 3510, 0]
+                    .map(FieldElement::from)
+                    .to_vec(),
+            },
+            Call {
+                to: FieldElement::from(0x13_u8),
+                selector: get_selector_from_name("execute_order").unwrap(),
+                calldata: vec![FieldElement::ONE],
+            },
+        ];
+        assert_eq!(
+            get_set_prices(&calls),
+            vec![PriceSnapshot {
+                token: "0x34".to_owned(),
+                price: "3500,3510".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simulation_result_json() {
+        let reverted = SimulationResult::Reverted {
+            reason: "Failure reason: 0x656d7074795f6f72646572.".to_owned(),
+            error: Some("EMPTY_ORDER".to_owned()),
+        };
+        let json = serde_json::to_value(&reverted).unwrap();
+        assert_eq!(json["status"], "reverted");
+        assert_eq!(
+            serde_json::from_value::<SimulationResult>(json).unwrap(),
+            reverted
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use satoru_client::polling::wait;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::{ConnectedAccount, SingleOwnerAccount},
+    core::types::FieldElement,
+    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    signers::LocalWallet,
+};
+use tokio::{
+    sync::Notify,
+    time::{sleep, timeout},
+};
+
+use crate::{clock::get_system_timestamp, config, error::KeeperError, state::is_job_claimed};
+
+fn standby_error(reason: String) -> KeeperError {
+    KeeperError::StandbyError(reason)
+}
+
+// Checks the leader lease outlasts the delay between two renewals, a poll waiting for its interval
+// then reading the nonce before renewing it, so an active instance renews its lease at least twice
+// before it expires.
+// @poll_interval: The delay between two polls.
+// @nonce_timeout: The longest a poll waits for the nonce.
+// @leader_lease: How long the leader lease lasts without renewal, if any.
+pub fn check_lease_timing(
+    poll_interval: Duration,
+    nonce_timeout: Duration,
+    leader_lease: Option<Duration>,
+) -> Result<(), KeeperError> {
+    match leader_lease {
+        Some(lease) if (poll_interval + nonce_timeout) * 2 > lease => Err(standby_error(format!(
+            "LEADER_LEASE_MS {} must be at least twice STANDBY_POLL_INTERVAL_MS {} plus \
+             STANDBY_NONCE_TIMEOUT_MS {}",
+            lease.as_millis(),
+            poll_interval.as_millis(),
+            nonce_timeout.as_millis()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// An enum representing how a standby got promoted to active.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Promotion {
+    // Through the admin API, the previous active keeper finishing the jobs it runs.
+    Manual,
+    // On the leader lease of the previous active keeper expiring, its jobs being taken over.
+    Failover,
+}
+
+// A struct representing the role of a keeper instance, as exposed to operators.
+// @active: Whether the instance executes, standbys only keeping their caches warm.
+// @instance_id: The id of the instance, holding the leader lease when active.
+// @nonce: The nonce of the keeper account at the last poll, None until read.
+// @leader_lease_ms: How long the leader lease lasts without renewal, None without automatic
+// failover.
+// @last_promotion: How the instance last got promoted, if it ever did.
+// @promoted_at: When it last got promoted, as a unix timestamp.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StandbyStatus {
+    pub active: bool,
+    pub instance_id: String,
+    pub nonce: Option<String>,
+    pub leader_lease_ms: Option<u64>,
+    pub last_promotion: Option<Promotion>,
+    pub promoted_at: Option<u64>,
+}
+
+// A struct holding whether a keeper instance executes or stands by, a standby keeping its
+// contracts, watchlists and nonce view warm without sending anything until promoted, through the
+// admin API or automatically once the active keeper stops renewing its leader lease.
+// @instance_id: The id of the instance.
+// @account_address: The keeper account, instances of the same account sharing a leader lease.
+// @leader_lease: How long the leader lease lasts without renewal, None without automatic failover.
+// @poll_interval: The delay between two polls of the lease and nonce.
+// @nonce_timeout: The longest a poll waits for the nonce before renewing the lease.
+// @renewed_at: When the instance last renewed the lease.
+#[derive(Debug)]
+pub struct Standby {
+    pub instance_id: String,
+    pub account_address: String,
+    pub leader_lease: Option<Duration>,
+    pub poll_interval: Duration,
+    pub nonce_timeout: Duration,
+    renewed_at: Mutex<Option<Instant>>,
+    active: AtomicBool,
+    promoted: Notify,
+    nonce: Mutex<Option<FieldElement>>,
+    last_promotion: Mutex<Option<(Promotion, u64)>>,
+}
+
+impl Standby {
+    pub fn new(
+        instance_id: String,
+        account_address: String,
+        active: bool,
+        leader_lease: Option<Duration>,
+        poll_interval: Duration,
+        nonce_timeout: Duration,
+    ) -> Self {
+        Standby {
+            instance_id,
+            account_address,
+            leader_lease,
+            poll_interval,
+            nonce_timeout,
+            renewed_at: Mutex::new(None),
+            active: AtomicBool::new(active),
+            promoted: Notify::new(),
+            nonce: Mutex::new(None),
+            last_promotion: Mutex::new(None),
+        }
+    }
+
+    pub fn from_env(instance_id: String, account_address: String) -> Self {
+        Standby::new(
+            instance_id,
+            account_address,
+            !config::get_keeper_standby(),
+            config::get_leader_lease_ms().map(Duration::from_millis),
+            Duration::from_millis(config::get_standby_poll_interval_ms().max(1)),
+            Duration::from_millis(config::get_standby_nonce_timeout_ms()),
+        )
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // Fails while standing by, for the paths sending transactions outside jobs, e.g. relays.
+    pub fn check(&self) -> Result<(), KeeperError> {
+        match self.is_active() {
+            true => Ok(()),
+            false => Err(standby_error(format!(
+                "instance {} is standing by",
+                self.instance_id
+            ))),
+        }
+    }
+
+    pub fn status(&self) -> StandbyStatus {
+        let last_promotion = *self.last_promotion.lock().unwrap();
+        StandbyStatus {
+            active: self.is_active(),
+            instance_id: self.instance_id.clone(),
+            nonce: self
+                .nonce
+                .lock()
+                .unwrap()
+                .map(|nonce| format!("{:#x}", nonce)),
+            leader_lease_ms: self.leader_lease.map(|lease| lease.as_millis() as u64),
+            last_promotion: last_promotion.map(|(promotion, _)| promotion),
+            promoted_at: last_promotion.map(|(_, promoted_at)| promoted_at),
+        }
+    }
+
+    // Makes the instance active, waking up the actions held while standing by. Returns false if
+    // it already was.
+    fn promote(&self, promotion: Promotion) -> bool {
+        if self.active.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        *self.last_promotion.lock().unwrap() = Some((promotion, get_system_timestamp()));
+        warn!(
+            "Instance {} promoted to active ({:?})",
+            self.instance_id, promotion
+        );
+        self.promoted.notify_waiters();
+        true
+    }
+
+    fn demote(&self, holder: Option<&str>) {
+        if self.active.swap(false, Ordering::Relaxed) {
+            warn!(
+                "Instance {} standing by, the leader lease being held by {}",
+                self.instance_id,
+                holder.unwrap_or("another instance")
+            );
+        }
+    }
+
+    // Returns whether the lease the instance holds may expire before the next poll renews it.
+    // @now: The current instant.
+    fn lease_expiring(&self, now: Instant) -> bool {
+        let lease = match self.leader_lease {
+            Some(lease) => lease,
+            None => return false,
+        };
+        match *self.renewed_at.lock().unwrap() {
+            Some(renewed_at) => now + self.poll_interval + self.nonce_timeout >= renewed_at + lease,
+            None => true,
+        }
+    }
+
+    // Takes or renews the leader lease of the account, returns whether the instance holds it.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @force: Whether the lease gets taken even if another instance holds it.
+    async fn acquire_lease(&self, pool: &Pool<Postgres>, force: bool) -> Result<bool, KeeperError> {
+        let lease = match self.leader_lease {
+            Some(lease) => lease,
+            None => return Ok(true),
+        };
+        let holder: Option<String> = sqlx::query_scalar(
+            "INSERT INTO keeper_leader (account_address, holder, expires_at)
+             VALUES ($1, $2, NOW() + make_interval(secs => $3))
+             ON CONFLICT (account_address) DO UPDATE SET holder = EXCLUDED.holder,
+                 expires_at = EXCLUDED.expires_at
+             WHERE keeper_leader.holder = EXCLUDED.holder OR keeper_leader.expires_at < NOW() OR $4
+             RETURNING holder",
+        )
+        .bind(&self.account_address)
+        .bind(&self.instance_id)
+        .bind(lease.as_secs_f64())
+        .bind(force)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| standby_error(format!("could not take leader lease: {:?}", e)))?;
+        if holder.is_some() {
+            *self.renewed_at.lock().unwrap() = Some(Instant::now());
+        }
+        Ok(holder.is_some())
+    }
+
+    async fn get_lease_holder(&self, pool: &Pool<Postgres>) -> Option<String> {
+        sqlx::query_scalar("SELECT holder FROM keeper_leader WHERE account_address = $1")
+            .bind(&self.account_address)
+            .fetch_optional(pool)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    // Settles the role the instance starts with: an active instance only starts active if no
+    // other instance holds the leader lease, standing by otherwise.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    pub async fn start(&self, pool: &Pool<Postgres>) -> Result<(), KeeperError> {
+        if self.is_active() && !self.acquire_lease(pool, false).await? {
+            let holder = self.get_lease_holder(pool).await;
+            self.demote(holder.as_deref());
+        }
+        info!(
+            "Instance {} starting {}",
+            self.instance_id,
+            match self.is_active() {
+                true => "active",
+                false => "as standby",
+            }
+        );
+        Ok(())
+    }
+
+    // Promotes the instance through the admin API, taking the leader lease from the active
+    // instance, which stands by at its next renewal. Returns false if it already was active.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    pub async fn promote_manually(&self, pool: &Pool<Postgres>) -> Result<bool, KeeperError> {
+        self.acquire_lease(pool, true).await?;
+        Ok(self.promote(Promotion::Manual))
+    }
+
+    // Waits for the next poll, then refreshes the nonce view and renews or takes the leader
+    // lease, standing by once another instance took it or once the lease may expire before it
+    // gets renewed, another instance then being free to take it. Returns the promotion when the
+    // lease got taken over from an active instance which stopped renewing it.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @account: The keeper account, whose nonce gets read.
+    pub async fn poll(
+        &self,
+        pool: &Pool<Postgres>,
+        account: &SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>,
+    ) -> Option<Promotion> {
+        sleep(self.poll_interval).await;
+        // The nonce read is bounded, the lease getting renewed after it.
+        match timeout(self.nonce_timeout, account.get_nonce()).await {
+            Ok(Ok(nonce)) => *self.nonce.lock().unwrap() = Some(nonce),
+            Ok(Err(e)) => error!("Could not read keeper nonce: {:?}", e),
+            Err(_) => error!(
+                "Keeper nonce not read within {}ms",
+                self.nonce_timeout.as_millis()
+            ),
+        }
+        self.leader_lease?;
+        match self.acquire_lease(pool, false).await {
+            Ok(true) if self.promote(Promotion::Failover) => Some(Promotion::Failover),
+            Ok(true) => None,
+            Ok(false) => {
+                let holder = self.get_lease_holder(pool).await;
+                self.demote(holder.as_deref());
+                None
+            }
+            Err(e) => {
+                error!("{}", e);
+                if self.is_active() && self.lease_expiring(Instant::now()) {
+                    self.demote(None);
+                }
+                None
+            }
+        }
+    }
+
+    // Waits for the instance to be active before an action gets claimed, returns false once the
+    // action got claimed by the active instance meanwhile.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @key: The key of the action.
+    pub async fn wait_until_active(&self, pool: &Pool<Postgres>, key: &str) -> bool {
+        loop {
+            if self.is_active() {
+                return true;
+            }
+            wait(self.poll_interval, &self.promoted).await;
+            if self.is_active() {
+                return true;
+            }
+            match is_job_claimed(pool, key).await {
+                Ok(true) => return false,
+                Ok(false) => {}
+                Err(e) => error!("Could not read job {}: {:?}", key, e),
+            }
+        }
+    }
+}
+
+// Polls the role of the instance forever, running the hook when it gets promoted by failover.
+// @standby: The role of the instance.
+// @pool: A connection pool for PostgreSQL.
+// @account: The keeper account.
+// @on_failover: Takes over the work of the previous active instance.
+pub async fn run_standby(
+    standby: Arc<Standby>,
+    pool: Pool<Postgres>,
+    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+    on_failover: impl Fn(),
+) {
+    loop {
+        if let Some(Promotion::Failover) = standby.poll(&pool, &account).await {
+            on_failover();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_standby(active: bool) -> Standby {
+        Standby::new(
+            "standby-1".to_owned(),
+            "0x1".to_owned(),
+            active,
+            None,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+        )
+    }
+
+    #[test]
+    fn test_promotion() {
+        let standby = new_standby(false);
+        assert!(standby.check().is_err());
+        assert!(standby.promote(Promotion::Failover));
+        assert!(standby.check().is_ok());
+        // Already active.
+        assert!(!standby.promote(Promotion::Manual));
+        let status = standby.status();
+        assert!(status.active);
+        assert_eq!(status.last_promotion, Some(Promotion::Failover));
+        assert!(status.promoted_at.is_some());
+
+        standby.demote(Some("standby-2"));
+        assert!(!standby.is_active());
+        assert_eq!(new_standby(true).status().last_promotion, None);
+    }
+
+    #[test]
+    fn test_check_lease_timing() {
+        let second = Duration::from_secs(1);
+        assert!(check_lease_timing(second, second, None).is_ok());
+        assert!(check_lease_timing(second, second, Some(second * 4)).is_ok());
+        assert!(check_lease_timing(second, second, Some(second * 3)).is_err());
+    }
+
+    #[test]
+    fn test_lease_expiring() {
+        let mut standby = new_standby(true);
+        assert!(!standby.lease_expiring(Instant::now()));
+        standby.leader_lease = Some(Duration::from_millis(100));
+        // Never renewed.
+        assert!(standby.lease_expiring(Instant::now()));
+        let renewed_at = Instant::now();
+        *standby.renewed_at.lock().unwrap() = Some(renewed_at);
+        assert!(!standby.lease_expiring(renewed_at));
+        // The next poll, 20ms away at most, would renew it past its expiry.
+        assert!(standby.lease_expiring(renewed_at + Duration::from_millis(80)));
+    }
+
+    #[tokio::test]
+    async fn test_held_actions_wake_up_on_promotion() {
+        let standby = Arc::new(new_standby(false));
+        let waiting = Arc::clone(&standby);
+        let waiter = tokio::spawn(async move {
+            waiting.promoted.notified().await;
+            waiting.is_active()
+        });
+        tokio::task::yield_now().await;
+        standby.promote(Promotion::Manual);
+        assert!(waiter.await.unwrap());
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, panic,
+    time::Duration,
+};
+
+use log::{error, info};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use starknet::{
+    core::types::{BlockId, BlockTag, FieldElement},
+    providers::Provider,
+};
+use url::Url;
+
+use crate::{
+    clock::Clock,
+    config,
+    contracts::{ContractAddresses, CONTRACT_NAMES},
+    error::KeeperError,
+    killswitch::KillSwitch,
+    paymaster::PaymasterConfig,
+    preview::PreviewParams,
+    relay::RelayPolicy,
+    standby::check_lease_timing,
+    trade::{
+        callback::CallbackGasCheck,
+        congestion::GasThrottle,
+        crash::CrashMode,
+        expiry::OrderExpiry,
+        hooks::ExecutionHooks,
+        policy::ExecutionPolicies,
+        price::{
+            bounds::PriceBounds, feeds::MarketFeeds, spread::PriceSpreads, stable::StablePrices,
+            tokens::TokenRegistry,
+        },
+        queue::ExecutionQueue,
+        requeue::RequeuePolicies,
+        schedule::MarketSchedules,
+        throttle::AccountThrottle,
+    },
+};
+
+// The documented configuration, the variables it sets being the ones dumped at startup.
+const ENV_EXAMPLE: &str = include_str!("../.env.example");
+// The variables holding secrets, never dumped.
+const SECRET_VARIABLES: [&str; 5] = [
+    "PRIVATE_KEY",
+    "SESSION_PRIVATE_KEY",
+    "PRAGMA_API_KEY",
+    "PAYMASTER_API_KEY",
+    "SENTRY_DSN",
+];
+// The variables holding URLs, which often carry API keys in their path or query.
+const URL_VARIABLES: [&str; 3] = ["RPC_URL", "SUBMISSION_RPC_URL", "PAYMASTER_URL"];
+// The tables and views the keeper reads and writes, created by sql/db_setup.sql.
+const REQUIRED_RELATIONS: [&str; 15] = [
+    "orders",
+    "deposits",
+    "withdrawals",
+    "keepers",
+    "api_keys",
+    "keeper_kill_switch",
+    "keeper_decisions",
+    "keeper_jobs",
+    "keeper_transaction_fees",
+    "keeper_transaction_actions",
+    "keeper_pnl",
+    "archived_daily_keeper_pnl",
+    "keeper_order_fees",
+    "pending_trigger_orders",
+    "account_webhooks",
+];
+
+// The configuration loaders, each parsing a part of the configuration and panicking on invalid
+// values, as they do when the keeper builds its components. Components left out of the build at
+// compile time have no checks.
+const CONFIG_CHECKS: &[(&str, fn())] = &[
+    ("TOKENS", || {
+        let _ = TokenRegistry::from_env();
+    }),
+    ("PRICE_BOUNDS", || {
+        let _ = PriceBounds::from_env();
+    }),
+    ("STABLE_PRICES", || {
+        let _ = StablePrices::from_env();
+    }),
+    ("PRICE_SPREADS_BPS", || {
+        let _ = PriceSpreads::from_env();
+    }),
+    ("requeue policies", || {
+        let _ = RequeuePolicies::from_env();
+    }),
+    ("execution policies", || {
+        let _ = ExecutionPolicies::from_env();
+    }),
+    ("callback gas check", || {
+        let _ = CallbackGasCheck::from_env();
+    }),
+    // Only the contracts set in the env resolve, discovery running after the config checks.
+    ("RELAY_ALLOWED_METHODS", || {
+        let addresses = ContractAddresses::resolve(&HashMap::new(), |name| {
+            env::var(name).ok().filter(|address| !address.is_empty())
+        });
+        if let Err(e) = addresses.and_then(|addresses| RelayPolicy::from_env(&addresses)) {
+            panic!("{}", e)
+        }
+    }),
+    ("EXECUTION_HOOKS", || {
+        if let Err(e) = ExecutionHooks::from_env() {
+            panic!("{}", e)
+        }
+    }),
+    ("MARKET_FEEDS", || {
+        if let Err(e) =
+            MarketFeeds::from_env().and_then(|feeds| feeds.check_tokens(&TokenRegistry::from_env()))
+        {
+            panic!("{}", e)
+        }
+    }),
+    ("MARKET_SCHEDULES", || {
+        if let Err(e) = MarketSchedules::from_env() {
+            panic!("{}", e)
+        }
+    }),
+    ("ORDER_TTL_BLOCKS", || {
+        let _ = OrderExpiry::from_env();
+    }),
+    ("account throttle", || {
+        let _ = AccountThrottle::from_env();
+    }),
+    ("LEADER_LEASE_MS", || {
+        if let Err(e) = check_lease_timing(
+            Duration::from_millis(config::get_standby_poll_interval_ms().max(1)),
+            Duration::from_millis(config::get_standby_nonce_timeout_ms()),
+            config::get_leader_lease_ms().map(Duration::from_millis),
+        ) {
+            panic!("{}", e)
+        }
+    }),
+    ("MAX_CLOCK_SKEW_SECS", || {
+        let _ = Clock::from_env();
+    }),
+    ("kill switch", || {
+        let _ = KillSwitch::from_env();
+    }),
+    #[cfg(feature = "liquidation")]
+    ("liquidation parameters", || {
+        let _ = crate::liquidation::LiquidationParams::from_env();
+    }),
+    ("preview parameters", || {
+        let _ = PreviewParams::from_env();
+    }),
+    ("paymaster", || {
+        let _ = PaymasterConfig::from_env();
+    }),
+    #[cfg(feature = "api")]
+    ("API_AUTH_ENABLED", || {
+        let _ = config::get_api_auth_enabled();
+    }),
+    ("ORACLE_MAX_BLOCK_RANGE", || {
+        let _ = config::get_oracle_max_block_range();
+    }),
+    ("BATCH_WINDOW_MS", || {
+        let _ = config::get_batch_window_ms();
+    }),
+    ("SHARE_BATCH_PRICES", || {
+        let _ = config::get_share_batch_prices();
+    }),
+    ("congestion", || {
+        let _ = GasThrottle::from_env();
+        let _ = config::get_gas_price_poll_interval_secs();
+    }),
+    ("FEE_TOKEN_ADDRESS", || {
+        FieldElement::from_hex_be(&config::get_fee_token_address())
+            .expect("Invalid fee token address");
+    }),
+    #[cfg(feature = "api")]
+    ("dashboard", || {
+        let _ = config::get_dashboard_refresh_secs();
+    }),
+    ("WEBHOOK_TIMEOUT_MS", || {
+        let _ = config::get_webhook_timeout_ms();
+    }),
+    ("execution queue", || {
+        let _ = ExecutionQueue::from_env();
+    }),
+    ("flash crash mode", || {
+        let _ = CrashMode::from_env();
+    }),
+    ("SELF_TEST_ORDER_BLOCK", || {
+        let _ = config::get_self_test_order_block();
+    }),
+    ("SESSION_EXPIRES_AT", || {
+        let _ = config::get_session_expires_at();
+    }),
+    ("receipt polling", || {
+        let _ = config::get_receipt_poll_min_interval_ms();
+        let _ = config::get_receipt_poll_max_interval_ms();
+    }),
+    #[cfg(feature = "liquidation")]
+    ("position scan", || {
+        let _ = crate::scanner::ScanParams::from_env();
+    }),
+    ("heartbeats", || {
+        let _ = config::get_keeper_heartbeat_interval_secs();
+        let _ = config::get_keeper_heartbeat_timeout_secs();
+    }),
+    ("KILL_SWITCH_POLL_INTERVAL_SECS", || {
+        let _ = config::get_kill_switch_poll_interval_secs();
+    }),
+    ("supervision", || {
+        let _ = config::get_supervisor_min_backoff_ms();
+        let _ = config::get_supervisor_max_backoff_ms();
+    }),
+];
+
+// Returns the variables a documented configuration sets, in order.
+// @env_example: The content of a .env.example file.
+pub fn get_config_variables(env_example: &str) -> Vec<&str> {
+    env_example
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, _)| name.trim())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// Redacts the value of a variable for the configuration dump, secrets being hidden and URLs
+// reduced to their scheme and host.
+// @name: The name of the variable.
+// @value: Its value.
+pub fn redact(name: &str, value: &str) -> String {
+    if SECRET_VARIABLES.contains(&name) {
+        return "<redacted>".to_owned();
+    }
+    if URL_VARIABLES.contains(&name) {
+        return match Url::parse(value) {
+            Ok(url) => format!(
+                "{}://{}{}",
+                url.scheme(),
+                url.host_str().unwrap_or_default(),
+                url.port()
+                    .map(|port| format!(":{}", port))
+                    .unwrap_or_default()
+            ),
+            Err(_) => "<invalid url>".to_owned(),
+        };
+    }
+    value.to_owned()
+}
+
+// Builds the startup dump of the effective configuration, secrets redacted: every documented
+// variable, null when unset, and the tokens and markets the keeper got configured with.
+pub fn get_config_dump() -> Value {
+    let variables: BTreeMap<&str, Option<String>> = get_config_variables(ENV_EXAMPLE)
+        .into_iter()
+        .map(|name| {
+            let value = env::var(name).ok().filter(|value| !value.is_empty());
+            (name, value.map(|value| redact(name, &value)))
+        })
+        .collect();
+    let tokens: Vec<Value> = TokenRegistry::from_env()
+        .tokens
+        .iter()
+        .map(|token| {
+            json!({
+                "symbol": token.symbol,
+                "address": format!("{:#x}", token.address),
+                "oracle_decimals": token.oracle_decimals,
+                "token_decimals": token.token_decimals,
+            })
+        })
+        .collect();
+    json!({
+        "variables": variables,
+        "tokens": tokens,
+        "market_allowlist": config::get_market_allowlist(),
+        "market_schedules": config::get_market_schedules(),
+    })
+}
+
+// Runs configuration checks, returns the errors of the failing ones.
+// @checks: The checks, named, each panicking on an invalid configuration.
+pub fn run_checks(checks: &[(&str, fn())]) -> Vec<String> {
+    // The checks panic on purpose, their panics are reported as errors instead of being printed.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let errors = checks
+        .iter()
+        .filter_map(|(name, check)| {
+            panic::catch_unwind(check).err().map(|e| {
+                let reason = match e.downcast_ref::<&str>() {
+                    Some(reason) => reason.to_string(),
+                    None => e.downcast_ref::<String>().cloned().unwrap_or_default(),
+                };
+                // Getters name their variable in their panic already.
+                match reason.contains(name) {
+                    true => reason,
+                    false => format!("{}: {}", name, reason),
+                }
+            })
+        })
+        .collect();
+    panic::set_hook(hook);
+    errors
+}
+
+// Logs the errors of the startup checks, returns them all at once.
+fn to_result(errors: Vec<String>) -> Result<(), KeeperError> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for e in &errors {
+        error!("Invalid configuration: {}", e);
+    }
+    Err(KeeperError::ConfigError(errors))
+}
+
+// Checks the configuration before anything gets built from it, so every invalid variable gets
+// reported at once instead of the keeper dying on the first one, possibly mid-run.
+pub fn check_config() -> Result<(), KeeperError> {
+    let mut errors = Vec::new();
+    match env::var("RPC_URL") {
+        Ok(rpc_url) if Url::parse(&rpc_url).is_err() => {
+            errors.push("RPC_URL: invalid url".to_owned())
+        }
+        Ok(_) => {}
+        Err(_) => errors.push("RPC_URL: not set".to_owned()),
+    }
+    let signer_key = match config::get_session_private_key() {
+        Some(_) => "SESSION_PRIVATE_KEY",
+        None => "PRIVATE_KEY",
+    };
+    for name in [signer_key, "PUBLIC_KEY"] {
+        match env::var(name) {
+            Ok(felt) if FieldElement::from_hex_be(&felt).is_err() => {
+                errors.push(format!("{}: invalid felt", name))
+            }
+            Ok(_) => {}
+            Err(_) => errors.push(format!("{}: not set", name)),
+        }
+    }
+    if config::get_session_private_key().is_some() {
+        match env::var("SESSION_PLUGIN_CLASS_HASH") {
+            Ok(felt) if FieldElement::from_hex_be(&felt).is_err() => {
+                errors.push("SESSION_PLUGIN_CLASS_HASH: invalid felt".to_owned())
+            }
+            Ok(_) => {}
+            Err(_) => errors.push("SESSION_PLUGIN_CLASS_HASH: not set".to_owned()),
+        }
+        // The session plugin only validates the transactions the keeper sends itself.
+        if config::get_paymaster_url().is_some() {
+            errors.push(
+                "SESSION_PRIVATE_KEY: session keys cannot sign paymaster outside executions"
+                    .to_owned(),
+            );
+        }
+    }
+    errors.extend(run_checks(CONFIG_CHECKS));
+    to_result(errors)
+}
+
+// Checks the deployment the keeper runs against once its contracts got loaded: every contract
+// has an address and is deployed, and the database has the keeper tables.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @provider: The provider the contracts are read through.
+// @addresses: The keeper contract addresses returned by load_contracts.
+pub async fn check_deployment<P: Provider + Sync>(
+    pool: &Pool<Postgres>,
+    provider: &P,
+    addresses: &ContractAddresses,
+) -> Result<(), KeeperError> {
+    let mut errors = Vec::new();
+    for name in CONTRACT_NAMES {
+        let address = match addresses.get(name) {
+            Ok(address) => address,
+            Err(_) => {
+                errors.push(format!("{}: no address", name));
+                continue;
+            }
+        };
+        if let Err(e) = provider
+            .get_class_hash_at(BlockId::Tag(BlockTag::Latest), address)
+            .await
+        {
+            errors.push(format!(
+                "{}: contract {:#x} not reachable: {:?}",
+                name, address, e
+            ));
+        }
+    }
+
+    let missing = sqlx::query_scalar::<_, String>(
+        "SELECT name FROM unnest($1::TEXT[]) AS name WHERE to_regclass(name) IS NULL",
+    )
+    .bind(REQUIRED_RELATIONS.to_vec())
+    .fetch_all(pool)
+    .await;
+    match missing {
+        Ok(missing) => errors.extend(
+            missing
+                .iter()
+                .map(|name| format!("database: missing {}, run sql/db_setup.sql", name)),
+        ),
+        Err(e) => errors.push(format!("database: not reachable: {:?}", e)),
+    }
+    to_result(errors)
+}
+
+// Dumps the effective configuration, then checks the deployment, reporting every error at once.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @provider: The provider the contracts are read through.
+// @addresses: The keeper contract addresses returned by load_contracts.
+pub async fn report_startup<P: Provider + Sync>(
+    pool: &Pool<Postgres>,
+    provider: &P,
+    addresses: &ContractAddresses,
+) -> Result<(), KeeperError> {
+    info!("Effective configuration: {}", get_config_dump());
+    check_deployment(pool, provider, addresses).await?;
+    info!("Startup checks passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_config_variables() {
+        let env_example =
+            "# RPC\nRPC_URL=\"http://127.0.0.1:5050\"\n\n# PRIVATE_KEY=\nPUBLIC_KEY=0x1\n";
+        assert_eq!(
+            get_config_variables(env_example),
+            vec!["RPC_URL", "PUBLIC_KEY"]
+        );
+        assert!(get_config_variables(ENV_EXAMPLE).contains(&"DATA_STORE"));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("PRIVATE_KEY", "0x123"), "<redacted>");
+        assert_eq!(
+            redact("RPC_URL", "https://rpc.example.com:8545/v3/secret?key=1"),
+            "https://rpc.example.com:8545"
+        );
+        assert_eq!(redact("MAX_CLOCK_SKEW_SECS", "60"), "60");
+    }
+
+    #[test]
+    fn test_run_checks() {
+        let checks: [(&str, fn()); 4] = [
+            ("valid", || {}),
+            ("spread", || panic!("must be a valid number")),
+            ("BATCH_WINDOW_MS", || {
+                panic!("BATCH_WINDOW_MS must be a valid number")
+            }),
+            ("token", || panic!("{}", "invalid address".to_owned())),
+        ];
+        assert_eq!(
+            run_checks(&checks),
+            vec![
+                "spread: must be a valid number".to_owned(),
+                "BATCH_WINDOW_MS must be a valid number".to_owned(),
+                "token: invalid address".to_owned()
+            ]
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+use starknet::core::types::FieldElement;
+
+use crate::{trade::receipt::ExecutionOutcome, types::SatoruAction};
+
+// An enum representing where a keeper job is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    // The action was picked up by this keeper, no transaction sent yet.
+    Claimed,
+    // The execution transaction was sent and awaits its receipt.
+    Submitted,
+    // The action got executed.
+    Done,
+    // The execution transaction reverted or did not execute the action.
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Claimed => "claimed",
+            JobStatus::Submitted => "submitted",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<JobStatus, Self::Err> {
+        match input {
+            "claimed" => Ok(JobStatus::Claimed),
+            "submitted" => Ok(JobStatus::Submitted),
+            "done" => Ok(JobStatus::Done),
+            "failed" => Ok(JobStatus::Failed),
+            _ => Err(()),
+        }
+    }
+}
+
+// A struct representing a persisted keeper job.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @status: The job lifecycle status.
+// @transaction_hash: The hash of the execution transaction, once submitted.
+// @attempts: The number of executions made for the action.
+// @row_data: The action to execute.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub table: String,
+    pub status: JobStatus,
+    pub transaction_hash: Option<FieldElement>,
+    pub attempts: u32,
+    pub row_data: SatoruAction,
+}
+
+// Claims an action for execution.
+// Returns false if the action was already claimed, so it is never executed twice.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @table: The table the action comes from.
+// @action: The action to claim.
+pub async fn claim_job(
+    pool: &Pool<Postgres>,
+    table: &str,
+    action: &SatoruAction,
+) -> Result<bool, Error> {
+    let row_data = serde_json::to_string(action).expect("Could not encode action.");
+    let result = sqlx::query(
+        "INSERT INTO keeper_jobs (key, table_name, status, row_data) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (key) DO NOTHING",
+    )
+    .bind(&action.key)
+    .bind(table)
+    .bind(JobStatus::Claimed.as_str())
+    .bind(row_data)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// Returns whether an action got claimed, e.g. by the active keeper while a standby holds it.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+pub async fn is_job_claimed(pool: &Pool<Postgres>, key: &str) -> Result<bool, Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM keeper_jobs WHERE key = $1)")
+        .bind(key)
+        .fetch_one(pool)
+        .await
+}
+
+// Counts a new execution attempt of a job, moving it back to claimed, and of its action.
+// Returns the number of attempts made so far, this one included, those of jobs since wiped too.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @table: The table the action comes from.
+// @key: The key of the action.
+pub async fn record_job_attempt(
+    pool: &Pool<Postgres>,
+    table: &str,
+    key: &str,
+) -> Result<u32, Error> {
+    let row: (i32,) = sqlx::query_as(
+        "UPDATE keeper_jobs SET status = $2, transaction_hash = NULL, attempts = attempts + 1,
+         updated_at = NOW() WHERE key = $1 RETURNING attempts",
+    )
+    .bind(key)
+    .bind(JobStatus::Claimed.as_str())
+    .fetch_one(pool)
+    .await?;
+    let action_attempts: i32 = sqlx::query_scalar(
+        "INSERT INTO keeper_action_attempts (key, table_name, attempts) VALUES ($1, $2, 1)
+         ON CONFLICT (key) DO UPDATE SET attempts = keeper_action_attempts.attempts + 1,
+             transaction_hash = NULL, last_attempted_at = NOW()
+         RETURNING attempts",
+    )
+    .bind(key)
+    .bind(table)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0.max(action_attempts) as u32)
+}
+
+// Records the transaction hash of a job once its execution got sent.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+// @transaction_hash: The hash of the execution transaction.
+pub async fn mark_job_submitted(
+    pool: &Pool<Postgres>,
+    key: &str,
+    transaction_hash: FieldElement,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE keeper_jobs SET status = $2, transaction_hash = $3, updated_at = NOW() WHERE key = $1",
+    )
+    .bind(key)
+    .bind(JobStatus::Submitted.as_str())
+    .bind(format!("{:#x}", transaction_hash))
+    .execute(pool)
+    .await?;
+    sqlx::query("UPDATE keeper_action_attempts SET transaction_hash = $2 WHERE key = $1")
+        .bind(key)
+        .bind(format!("{:#x}", transaction_hash))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Moves a job to its final status given the outcome of its execution transaction.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+// @outcome: The outcome parsed from the execution transaction receipt.
+pub async fn mark_job_finished(
+    pool: &Pool<Postgres>,
+    key: &str,
+    outcome: &ExecutionOutcome,
+) -> Result<(), Error> {
+    let status = match outcome {
+        ExecutionOutcome::Executed => JobStatus::Done,
+        _ => JobStatus::Failed,
+    };
+    let failure_reason = outcome.reason();
+    sqlx::query(
+        "UPDATE keeper_jobs SET status = $2, outcome = $3, failure_reason = $4, updated_at = NOW()
+         WHERE key = $1",
+    )
+    .bind(key)
+    .bind(status.as_str())
+    .bind(outcome.as_str())
+    .bind(failure_reason)
+    .execute(pool)
+    .await?;
+    sqlx::query("UPDATE keeper_action_attempts SET outcome = COALESCE(outcome, $2) WHERE key = $1")
+        .bind(key)
+        .bind(outcome.as_str())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// A struct representing the attempts made at executing an action, whatever the jobs it got
+// executed by.
+// @attempts: The number of executions sent for the action.
+// @transaction_hash: The hash of the last execution transaction, while it awaits its receipt.
+// @outcome: The outcome the action finished with, once finished.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionAttempt {
+    pub attempts: u32,
+    pub transaction_hash: Option<FieldElement>,
+    pub outcome: Option<String>,
+}
+
+// Loads the attempts made at executing an action, None when it never got attempted.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+pub async fn load_action_attempt(
+    pool: &Pool<Postgres>,
+    key: &str,
+) -> Result<Option<ActionAttempt>, Error> {
+    let row: Option<(i32, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT attempts, transaction_hash, outcome FROM keeper_action_attempts WHERE key = $1",
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+    Ok(
+        row.map(|(attempts, transaction_hash, outcome)| ActionAttempt {
+            attempts: attempts as u32,
+            transaction_hash: transaction_hash
+                .and_then(|hash| FieldElement::from_hex_be(&hash).ok()),
+            outcome,
+        }),
+    )
+}
+
+// Deletes the attempts older than the retention, returns how many got deleted.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @retention_secs: How long attempts are kept after the last one.
+pub async fn prune_action_attempts(
+    pool: &Pool<Postgres>,
+    retention_secs: u64,
+) -> Result<u64, Error> {
+    let result = sqlx::query(
+        "DELETE FROM keeper_action_attempts
+         WHERE last_attempted_at < NOW() - make_interval(secs => $1)",
+    )
+    .bind(retention_secs as f64)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Keeps a job in flight from being taken over as stuck, while its keeper still runs it.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the action.
+pub async fn touch_job(pool: &Pool<Postgres>, key: &str) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE keeper_jobs SET updated_at = NOW()
+         WHERE key = $1 AND status IN ('claimed', 'submitted')",
+    )
+    .bind(key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// A row of keeper_jobs as the jobs get loaded.
+type JobRow = (String, String, Option<String>, i32, String);
+
+fn to_jobs(rows: Vec<JobRow>) -> Vec<Job> {
+    rows.into_iter()
+        .filter_map(|(table, status, transaction_hash, attempts, row_data)| {
+            Some(Job {
+                table,
+                status: status.parse::<JobStatus>().ok()?,
+                transaction_hash: transaction_hash
+                    .and_then(|hash| FieldElement::from_hex_be(&hash).ok()),
+                attempts: attempts as u32,
+                row_data: serde_json::from_str::<SatoruAction>(&row_data).ok()?,
+            })
+        })
+        .collect()
+}
+
+// Loads the jobs left in flight by a previous run (claimed or submitted).
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_in_flight_jobs(pool: &Pool<Postgres>) -> Result<Vec<Job>, Error> {
+    let rows: Vec<JobRow> = sqlx::query_as(
+        "SELECT table_name, status, transaction_hash, attempts, row_data FROM keeper_jobs
+         WHERE status IN ('claimed', 'submitted') ORDER BY created_at",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(to_jobs(rows))
+}
+
+// Takes over the jobs in flight not updated for longer than the timeout, their keeper having
+// crashed mid-execution, refreshing them so no other keeper takes them over too.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @timeout_secs: How long a job in flight goes without update before being stuck.
+pub async fn take_over_stuck_jobs(
+    pool: &Pool<Postgres>,
+    timeout_secs: u64,
+) -> Result<Vec<Job>, Error> {
+    let rows: Vec<JobRow> = sqlx::query_as(
+        "UPDATE keeper_jobs SET updated_at = NOW()
+         WHERE status IN ('claimed', 'submitted') AND updated_at < NOW() - make_interval(secs => $1)
+         RETURNING table_name, status, transaction_hash, attempts, row_data",
+    )
+    .bind(timeout_secs as f64)
+    .fetch_all(pool)
+    .await?;
+    Ok(to_jobs(rows))
+}
+
+// Loads the trigger orders neither executed, cancelled nor claimed yet, oldest first, e.g. the
+// ones created while the keeper was down.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_pending_trigger_orders(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<SatoruAction>, Error> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT row_data FROM pending_trigger_orders")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(row_data,)| serde_json::from_str::<SatoruAction>(&row_data).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_status_round_trip() {
+        for status in [
+            JobStatus::Claimed,
+            JobStatus::Submitted,
+            JobStatus::Done,
+            JobStatus::Failed,
+        ] {
+            assert_eq!(status.as_str().parse::<JobStatus>(), Ok(status));
+        }
+        assert!("unknown".parse::<JobStatus>().is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use starknet::{
+    accounts::{Account, Call, SingleOwnerAccount},
+    core::types::FieldElement,
+    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    signers::LocalWallet,
+};
+
+use crate::{
+    clock::get_system_timestamp, error::KeeperError, paymaster::PaymasterAccount,
+    session::SessionAccount, trade::revert::to_execution_error,
+};
+
+// An enum representing the account sending the execution transactions, either signed by the
+// account owner key or by a session key restricted to the keeper methods, or relayed by a
+// paymaster paying the fees.
+pub enum Submitter {
+    Owner(SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>),
+    Session(SessionAccount<JsonRpcClient<HttpTransport>>),
+    Paymaster(PaymasterAccount),
+}
+
+impl Submitter {
+    // Sends a multicall, returns its transaction hash.
+    // @calls: The calls of the multicall.
+    pub async fn send(&self, calls: Vec<Call>) -> Result<FieldElement, KeeperError> {
+        match self {
+            Submitter::Owner(account) => account
+                .execute(calls)
+                .send()
+                .await
+                .map(|result| result.transaction_hash)
+                .map_err(to_execution_error),
+            Submitter::Session(account) => {
+                account
+                    .session()
+                    .check_calls(&calls, get_system_timestamp())?;
+                account
+                    .execute(calls)
+                    .send()
+                    .await
+                    .map(|result| result.transaction_hash)
+                    .map_err(to_execution_error)
+            }
+            Submitter::Paymaster(account) => account.send(calls).await,
+        }
+    }
+}
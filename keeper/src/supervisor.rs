@@ -0,0 +1,86 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use satoru_client::{polling::Backoff, sentry::capture_error};
+use tokio::{task, time::sleep};
+
+use crate::config;
+
+// Returns the backoff subsystems restart with, the delay doubling on every restart of a subsystem
+// failing again within the longest delay.
+pub fn restart_backoff() -> Backoff {
+    Backoff::new(
+        Duration::from_millis(config::get_supervisor_min_backoff_ms()),
+        Duration::from_millis(config::get_supervisor_max_backoff_ms()),
+    )
+}
+
+// Runs a subsystem in its own task, restarting it with backoff whenever it panics or fails, so a
+// failing subsystem never takes the others down with it. Returns once the subsystem finishes.
+// @name: The name of the subsystem, e.g. indexer.
+// @backoff: The delays between restarts.
+// @start: Starts a run of the subsystem.
+pub async fn supervise<F, Fut>(name: &str, mut backoff: Backoff, start: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    loop {
+        let started_at = Instant::now();
+        let reason = match task::spawn(start()).await {
+            Ok(Ok(())) => {
+                info!("Subsystem {} finished", name);
+                return;
+            }
+            Ok(Err(e)) => e,
+            Err(e) if e.is_panic() => "panicked".to_owned(),
+            Err(e) => {
+                error!("Subsystem {} cancelled: {}", name, e);
+                return;
+            }
+        };
+        // A subsystem running for longer than the longest delay restarts at once.
+        let delay = backoff.next(started_at.elapsed() >= backoff.max);
+        error!(
+            "Subsystem {} failed: {}, restarting in {:?}",
+            name, reason, delay
+        );
+        capture_error(
+            &format!("subsystem {} failed: {}", name, reason),
+            &[("subsystem", name)],
+        );
+        sleep(delay).await;
+        warn!("Restarting subsystem {}", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_supervise_restarts() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(5));
+        supervise("decoder", backoff, || {
+            let runs = Arc::clone(&runs);
+            async move {
+                match runs.fetch_add(1, Ordering::SeqCst) {
+                    0 => panic!("could not decode payload"),
+                    1 => Err("connection lost".to_owned()),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{sync::Arc, time::Duration};
+
+use cainome::cairo_serde::ContractAddress;
+use log::{error, info};
+use sqlx::{Pool, Postgres};
+use starknet::{
+    accounts::ConnectedAccount,
+    core::types::{FieldElement, MaybePendingTransactionReceipt},
+    providers::Provider,
+};
+use tokio::{task::JoinHandle, time::sleep};
+
+use crate::{
+    archive::archive_detail_rows,
+    config,
+    contracts::Contracts,
+    decisions::{record_decision, Decision},
+    error::KeeperError,
+    executor::{execute_job, KeeperContext},
+    state::{mark_job_finished, prune_action_attempts, take_over_stuck_jobs, touch_job, Job},
+    trade::receipt::{get_execution_outcome, ExecutionOutcome},
+};
+
+// A struct representing how the jobs stuck in flight get swept.
+// @interval: The delay between two sweeps, jobs never swept when None.
+// @timeout: How long a job in flight goes without update before being stuck, its keeper
+// refreshing it every third of it while running it.
+// @attempt_retention: How long the attempts at executing an action are kept after the last one,
+// pruned at every sweep, forever when None.
+// @archive_retention: How long the detail rows rolled into daily aggregates are kept, archived at
+// every sweep, forever when None.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepParams {
+    pub interval: Option<Duration>,
+    pub timeout: Duration,
+    pub attempt_retention: Option<Duration>,
+    pub archive_retention: Option<Duration>,
+}
+
+impl SweepParams {
+    pub fn from_env() -> Self {
+        SweepParams {
+            interval: config::get_stuck_job_sweep_interval_secs().map(Duration::from_secs),
+            timeout: Duration::from_secs(config::get_stuck_job_timeout_secs().max(3)),
+            attempt_retention: config::get_action_attempt_retention_days()
+                .map(|days| Duration::from_secs(days.saturating_mul(86_400))),
+            archive_retention: config::get_archive_retention_days()
+                .map(|days| Duration::from_secs(days.saturating_mul(86_400))),
+        }
+    }
+}
+
+// An enum representing what the sweeper does with a stuck job.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SweepDecision {
+    // The action settled, the job gets finished with the outcome.
+    Finalize(ExecutionOutcome),
+    // The action is still to execute, the job gets executed again.
+    Requeue,
+}
+
+// Decides what happens to a stuck job from what the chain says about its action.
+// @outcome: The outcome of its last execution transaction, if it has a receipt.
+// @on_chain: Whether the action is still in the DataStore.
+pub fn decide_stuck_job(outcome: Option<ExecutionOutcome>, on_chain: bool) -> SweepDecision {
+    match outcome {
+        Some(
+            outcome @ (ExecutionOutcome::Executed
+            | ExecutionOutcome::Cancelled(_)
+            | ExecutionOutcome::Frozen(_)),
+        ) => SweepDecision::Finalize(outcome),
+        _ if !on_chain => SweepDecision::Finalize(ExecutionOutcome::Settled),
+        _ => SweepDecision::Requeue,
+    }
+}
+
+// A struct refreshing a job in the background while its keeper runs it, so it never gets swept.
+pub struct JobLease(JoinHandle<()>);
+
+impl JobLease {
+    // @pool: A connection pool for PostgreSQL.
+    // @key: The key of the action.
+    pub fn hold(pool: Pool<Postgres>, key: String) -> Self {
+        let interval = SweepParams::from_env().timeout / 3;
+        JobLease(tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                if let Err(e) = touch_job(&pool, &key).await {
+                    error!("Could not refresh job {}: {:?}", key, e);
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for JobLease {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// Returns whether an action is still in the DataStore, removed actions reading as zeroed ones.
+// @contracts: The keeper contracts, the DataStore the action is read from.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @key: The key of the action.
+pub async fn is_on_chain(
+    contracts: &Contracts,
+    table: &str,
+    key: FieldElement,
+) -> Result<bool, KeeperError> {
+    let to_error = |e| KeeperError::ExecutionError(format!("could not read action: {:?}", e));
+    let data_store = &contracts.data_store;
+    let account = match table {
+        "orders" => {
+            data_store
+                .get_order(&key)
+                .call()
+                .await
+                .map_err(to_error)?
+                .account
+        }
+        "deposits" => {
+            data_store
+                .get_deposit(&key)
+                .call()
+                .await
+                .map_err(to_error)?
+                .account
+        }
+        "withdrawals" => {
+            data_store
+                .get_withdrawal(&key)
+                .call()
+                .await
+                .map_err(to_error)?
+                .account
+        }
+        other => {
+            return Err(KeeperError::ExecutionError(format!(
+                "no handler for table {}",
+                other
+            )))
+        }
+    };
+    Ok(account != ContractAddress::from(FieldElement::ZERO))
+}
+
+// Checks a stuck job against the chain, finishing it when its action settled and executing it
+// again otherwise.
+// @context: The keeper context.
+// @job: The stuck job, taken over by this keeper.
+async fn sweep_job(context: &Arc<KeeperContext>, job: Job) -> Result<(), KeeperError> {
+    let key = job.row_data.key.clone();
+    let key_felt = FieldElement::from_hex_be(&key).expect("Cannot convert string to felt");
+    let outcome = match job.transaction_hash {
+        Some(transaction_hash) => {
+            match context
+                .account
+                .provider()
+                .get_transaction_receipt(transaction_hash)
+                .await
+            {
+                Ok(MaybePendingTransactionReceipt::Receipt(receipt)) => {
+                    Some(get_execution_outcome(&receipt, &job.table, key_felt))
+                }
+                _ => None,
+            }
+        }
+        None => None,
+    };
+    let outcome_found = outcome.is_some();
+    let on_chain = is_on_chain(&context.contracts, &job.table, key_felt).await?;
+    match decide_stuck_job(outcome, on_chain) {
+        SweepDecision::Finalize(outcome) => {
+            info!("Finalizing stuck job {} with outcome {:?}", key, outcome);
+            let decision = match outcome {
+                ExecutionOutcome::Executed => Decision::Executed,
+                _ => Decision::Dropped,
+            };
+            record_decision(
+                &context.pool,
+                &job.table,
+                &key,
+                decision,
+                &format!("stuck, {:?}", outcome),
+            )
+            .await;
+            mark_job_finished(&context.pool, &key, &outcome)
+                .await
+                .map_err(|e| {
+                    KeeperError::ExecutionError(format!("could not finish job: {:?}", e))
+                })?;
+        }
+        SweepDecision::Requeue => {
+            info!("Requeuing stuck job {}", key);
+            // A transaction without receipt may still land, it gets tracked before sending another.
+            let pending_transaction = match outcome_found {
+                true => None,
+                false => job.transaction_hash,
+            };
+            tokio::spawn(execute_job(
+                Arc::clone(context),
+                job.table,
+                job.row_data,
+                job.attempts,
+                pending_transaction,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Sweeps the jobs stuck in flight every sweep interval, so the actions of a keeper that crashed
+// mid-execution never stay claimed forever.
+// @context: The keeper context.
+// @params: How the jobs get swept.
+pub async fn run_job_sweeper(context: Arc<KeeperContext>, params: SweepParams) {
+    let interval = match params.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    loop {
+        sleep(interval).await;
+        // The active keeper sweeps, standbys taking over its jobs on failover instead.
+        if !context.standby.is_active() {
+            continue;
+        }
+        if let Some(retention) = params.attempt_retention {
+            if let Err(e) = prune_action_attempts(&context.pool, retention.as_secs()).await {
+                error!("Could not prune action attempts: {:?}", e);
+            }
+        }
+        if let Some(retention) = params.archive_retention {
+            if let Err(e) = archive_detail_rows(&context.pool, retention.as_secs()).await {
+                error!("Could not archive detail rows: {:?}", e);
+            }
+        }
+        let jobs = match take_over_stuck_jobs(&context.pool, params.timeout.as_secs()).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                error!("Could not load stuck jobs: {:?}", e);
+                continue;
+            }
+        };
+        if !jobs.is_empty() {
+            info!("Sweeping {} stuck jobs", jobs.len());
+        }
+        for job in jobs {
+            let key = job.row_data.key.clone();
+            if let Err(e) = sweep_job(&context, job).await {
+                error!("Could not sweep job {}: {}", key, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide_stuck_job() {
+        assert_eq!(
+            decide_stuck_job(Some(ExecutionOutcome::Executed), false),
+            SweepDecision::Finalize(ExecutionOutcome::Executed)
+        );
+        assert_eq!(
+            decide_stuck_job(Some(ExecutionOutcome::Cancelled(None)), false),
+            SweepDecision::Finalize(ExecutionOutcome::Cancelled(None))
+        );
+        // Settled by another transaction, e.g. of another keeper.
+        assert_eq!(
+            decide_stuck_job(
+                Some(ExecutionOutcome::Reverted("EMPTY_ORDER".to_owned())),
+                false
+            ),
+            SweepDecision::Finalize(ExecutionOutcome::Settled)
+        );
+        assert_eq!(
+            decide_stuck_job(None, false),
+            SweepDecision::Finalize(ExecutionOutcome::Settled)
+        );
+        assert_eq!(
+            decide_stuck_job(
+                Some(ExecutionOutcome::Reverted(
+                    "MAX_PRICE_AGE_EXCEEDED".to_owned()
+                )),
+                true
+            ),
+            SweepDecision::Requeue
+        );
+        assert_eq!(decide_stuck_job(None, true), SweepDecision::Requeue);
+    }
+}
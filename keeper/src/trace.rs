@@ -0,0 +1,172 @@
+use serde::Serialize;
+use sqlx::error::Error;
+use sqlx::Pool;
+use sqlx::Postgres;
+
+use crate::decisions::{get_decisions, DecisionRecord};
+
+// A struct representing an order as indexed, from its creation to its settlement.
+// @status: The order status (pending, frozen, executed, cancelled).
+// @created_block: The block the order got created in.
+// @updates: The number of times the order got updated.
+// @frozen_reason: Why the order got frozen, if it did.
+// @executed_block: The block the order got executed in, if it did.
+// @keeper: The keeper which executed the order.
+// @cancelled_block: The block the order got cancelled in, if it did.
+// @cancelled_reason: Why the order got cancelled.
+// @total_duration_secs: The time between the order creation and its settlement.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct IndexedOrder {
+    pub account: Option<String>,
+    pub market: Option<String>,
+    pub order_type: Option<String>,
+    pub status: Option<String>,
+    pub created_block: Option<i64>,
+    pub updates: Option<i64>,
+    pub frozen_reason: Option<String>,
+    pub executed_block: Option<i64>,
+    pub keeper: Option<String>,
+    pub cancelled_block: Option<i64>,
+    pub cancelled_reason: Option<String>,
+    pub total_duration_secs: Option<i64>,
+}
+
+// A struct representing the execution job the keeper ran for an action.
+// @status: The job lifecycle status.
+// @transaction_hash: The hash of the last execution transaction sent.
+// @attempts: The number of executions made.
+// @outcome: The final execution outcome.
+// @failure_reason: Why the execution failed, if it did.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct JobResult {
+    pub status: String,
+    pub transaction_hash: Option<String>,
+    pub attempts: i32,
+    pub outcome: Option<String>,
+    pub failure_reason: Option<String>,
+}
+
+// A struct representing everything known about the execution of an order, combining the indexed
+// order with the keeper job and decisions.
+// @key: The key of the order.
+// @order: The order as indexed, if indexed yet.
+// @job: The keeper execution job, if the keeper ran one.
+// @decisions: The decisions the keeper made on the order, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExecutionTrace {
+    pub key: String,
+    pub order: Option<IndexedOrder>,
+    pub job: Option<JobResult>,
+    pub decisions: Vec<DecisionRecord>,
+}
+
+impl ExecutionTrace {
+    // Whether nothing is known about the order.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_none() && self.job.is_none() && self.decisions.is_empty()
+    }
+}
+
+// Loads the execution trace of an order.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @key: The key of the order.
+pub async fn get_execution_trace(
+    pool: &Pool<Postgres>,
+    key: &str,
+) -> Result<ExecutionTrace, Error> {
+    let order = sqlx::query_as::<_, IndexedOrder>(
+        "SELECT account, market, order_type, status, created_block, updates, frozen_reason,
+         executed_block, keeper, cancelled_block, cancelled_reason, total_duration_secs
+         FROM order_lifecycle WHERE key = $1",
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+    let job = sqlx::query_as::<_, JobResult>(
+        "SELECT status, transaction_hash, attempts, outcome, failure_reason FROM keeper_jobs
+         WHERE key = $1",
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+    Ok(ExecutionTrace {
+        key: key.to_owned(),
+        order,
+        job,
+        decisions: get_decisions(pool, key).await?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{connect_test_pool, test_key},
+        decisions::{record_decision, Decision},
+        state::{claim_job, record_job_attempt},
+        types::SatoruAction,
+    };
+
+    #[test]
+    fn test_empty_trace() {
+        let mut trace = ExecutionTrace {
+            key: "0x1".to_owned(),
+            order: None,
+            job: None,
+            decisions: vec![],
+        };
+        assert!(trace.is_empty());
+        trace.job = Some(JobResult {
+            status: "claimed".to_owned(),
+            transaction_hash: None,
+            attempts: 0,
+            outcome: None,
+            failure_reason: None,
+        });
+        assert!(!trace.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execution_trace() {
+        let Some(pool) = connect_test_pool().await else {
+            return;
+        };
+        let key = test_key("trace");
+        assert!(get_execution_trace(&pool, &key).await.unwrap().is_empty());
+
+        // A job claimed and attempted once, deferred on a clock skew before.
+        let action = SatoruAction {
+            key: key.clone(),
+            ..Default::default()
+        };
+        record_decision(&pool, "orders", &key, Decision::Deferred, "clock skew").await;
+        assert!(claim_job(&pool, "orders", &action).await.unwrap());
+        assert_eq!(record_job_attempt(&pool, "orders", &key).await.unwrap(), 1);
+
+        let trace = get_execution_trace(&pool, &key).await.unwrap();
+        assert_eq!(trace.key, key);
+        // Not indexed, the action only being known to the keeper.
+        assert_eq!(trace.order, None);
+        assert_eq!(
+            trace.job,
+            Some(JobResult {
+                status: "claimed".to_owned(),
+                transaction_hash: None,
+                attempts: 1,
+                outcome: None,
+                failure_reason: None,
+            })
+        );
+        assert_eq!(trace.decisions.len(), 1);
+        assert_eq!(trace.decisions[0].decision, "deferred");
+        assert_eq!(trace.decisions[0].reason, "clock skew");
+
+        for table in ["keeper_decisions", "keeper_jobs", "keeper_action_attempts"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE key = $1", table))
+                .bind(&key)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::info;
+use starknet::{accounts::Call, core::types::FieldElement};
+use tokio::{sync::oneshot, time::sleep};
+
+use crate::{config, error::KeeperError, killswitch::KillSwitch, submitter::Submitter};
+
+// The calls of an action waiting for the batch they get sent in, with where to report its result.
+type PendingCalls = (Vec<Call>, oneshot::Sender<Result<FieldElement, String>>);
+
+// Merges the multicalls of several actions into one, keeping the set price calls of every action
+// before its execution: the Oracle clears the primary prices after each execution, so a later
+// execution can not reuse the prices an earlier one set. The actions priced at the same block get
+// the same prices, fetched once.
+// @action_calls: The multicalls of the actions, each setting its prices before executing.
+pub fn merge_calls(action_calls: Vec<Vec<Call>>) -> Vec<Call> {
+    action_calls.into_iter().flatten().collect()
+}
+
+// A struct batching the executions sent within a window into a single multicall. A revert reverts the whole batch, each action getting
+// requeued according to the requeue policies.
+// @submitter: The account sending the multicalls.
+// @kill_switch: The kill switch checked before every multicall gets sent.
+// @window: How long the first action of a batch waits for others, batching is disabled when 0.
+// @pending: The actions waiting for the current batch to be sent.
+pub struct CallBatcher {
+    submitter: Arc<Submitter>,
+    kill_switch: Arc<KillSwitch>,
+    pub window: Duration,
+    pending: Mutex<Vec<PendingCalls>>,
+}
+
+impl CallBatcher {
+    pub fn new(submitter: Arc<Submitter>, kill_switch: Arc<KillSwitch>, window: Duration) -> Self {
+        CallBatcher {
+            submitter,
+            kill_switch,
+            window,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn from_env(submitter: Arc<Submitter>, kill_switch: Arc<KillSwitch>) -> Self {
+        CallBatcher::new(
+            submitter,
+            kill_switch,
+            Duration::from_millis(config::get_batch_window_ms()),
+        )
+    }
+
+    // Sends the multicall of an action within the current batch, returns the hash of the
+    // transaction it got sent in.
+    // @calls: The calls of the action.
+    pub async fn send(&self, calls: Vec<Call>) -> Result<FieldElement, KeeperError> {
+        self.send_within(calls, self.window).await
+    }
+
+    // Sends the multicall of an action within the current batch, waiting for the given window
+    // when it leads the batch, e.g. longer for liquidations during a flash crash.
+    // @calls: The calls of the action.
+    // @window: How long the action waits for others when it leads the batch, sent alone when 0.
+    pub async fn send_within(
+        &self,
+        calls: Vec<Call>,
+        window: Duration,
+    ) -> Result<FieldElement, KeeperError> {
+        self.kill_switch.check()?;
+        if window.is_zero() {
+            return self.submitter.send(calls).await;
+        }
+        let (sender, receiver) = oneshot::channel();
+        let leads_batch = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push((calls, sender));
+            pending.len() == 1
+        };
+        // The first action of a batch sends it once the window elapsed.
+        if leads_batch {
+            sleep(window).await;
+            let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+            let (action_calls, senders): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+            // The switch may have been engaged while the batch was filling up.
+            let result = match self.kill_switch.check() {
+                Ok(()) => {
+                    info!("Sending a batch of {} executions", senders.len());
+                    self.submitter.send(merge_calls(action_calls)).await
+                }
+                Err(e) => Err(e),
+            }
+            .map_err(|e| match e {
+                KeeperError::ExecutionError(reason) => reason,
+                e => e.to_string(),
+            });
+            for sender in senders {
+                let _ = sender.send(result.clone());
+            }
+        }
+        receiver
+            .await
+            .map_err(|_| KeeperError::ExecutionError("batch dropped".to_owned()))?
+            // Batches stopped by the switch get reported as such, their actions paused, not requeued.
+            .map_err(|reason| match self.kill_switch.check() {
+                Err(e) => e,
+                Ok(()) => KeeperError::ExecutionError(reason),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet::core::utils::get_selector_from_name;
+
+    use super::*;
+
+    fn call(method: &str, calldata: &[u64]) -> Call {
+        Call {
+            to: FieldElement::from_hex_be("0x12").unwrap(),
+            selector: get_selector_from_name(method).unwrap(),
+            calldata: calldata
+                .iter()
+                .map(|felt| FieldElement::from(*felt))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_merge_calls() {
+        let action_calls = vec![
+            vec![
+                call("set_primary_price", &[1, 2000]),
+                call("execute_order", &[10]),
+            ],
+            vec![
+                call("set_primary_price", &[1, 2000]),
+                call("execute_order", &[11]),
+            ],
+            vec![
+                call("set_primary_price", &[2, 1]),
+                call("execute_deposit", &[12]),
+            ],
+        ];
+
+        // The primary prices getting cleared after each execution, every execution sets them.
+        let merged = merge_calls(action_calls);
+        let expected = [
+            call("set_primary_price", &[1, 2000]),
+            call("execute_order", &[10]),
+            call("set_primary_price", &[1, 2000]),
+            call("execute_order", &[11]),
+            call("set_primary_price", &[2, 1]),
+            call("execute_deposit", &[12]),
+        ];
+        assert_eq!(merged.len(), expected.len());
+        for (call, expected) in merged.iter().zip(&expected) {
+            assert_eq!(call.selector, expected.selector);
+            assert_eq!(call.calldata, expected.calldata);
+        }
+    }
+}
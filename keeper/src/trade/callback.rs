@@ -0,0 +1,196 @@
+use log::warn;
+use starknet::core::{types::FieldElement, utils::cairo_short_string_to_felt};
+
+use crate::{
+    config, contracts::Contracts, error::KeeperError, trade::congestion::get_gas_price,
+    trade::policy::PolicyDecision, types::SatoruAction,
+};
+
+// Returns the DataStore key of the largest callback gas limit actions may set, as
+// keys::max_callback_gas_limit does.
+pub fn max_callback_gas_limit_key() -> FieldElement {
+    cairo_short_string_to_felt("MAX_CALLBACK_GAS_LIMIT").expect("Invalid short string")
+}
+
+// Returns whether an action calls a callback contract once executed.
+// @action: The action to execute.
+pub fn has_callback(action: &SatoruAction) -> bool {
+    // The indexer stores felts without 0x prefix, a zero address meaning no callback.
+    FieldElement::from_hex_be(&action.callback_contract)
+        .is_ok_and(|callback_contract| callback_contract != FieldElement::ZERO)
+}
+
+// Decides whether an action with a callback gets executed: skipped when its callback gas limit is
+// above the protocol maximum, the execution reverting on it, and flagged as unprofitable when its
+// execution fee does not cover the execution and callback gas at the current gas price.
+// Returns the decision and the unprofitability reason, if any.
+// @callback_gas_limit: The callback gas limit of the action.
+// @max_callback_gas_limit: The protocol maximum, 0 when not set.
+// @execution_fee: The execution fee of the action, in wei.
+// @execution_gas: The gas of an execution without its callback.
+// @gas_price: The L1 gas price in wei.
+pub fn check_callback_gas(
+    callback_gas_limit: u128,
+    max_callback_gas_limit: u128,
+    execution_fee: u128,
+    execution_gas: u128,
+    gas_price: u64,
+) -> (PolicyDecision, Option<String>) {
+    // A callback passed the maximum when the action got created, a 0 maximum means the key is not
+    // set on this deployment rather than callbacks being disabled since.
+    if max_callback_gas_limit > 0 && callback_gas_limit > max_callback_gas_limit {
+        return (
+            PolicyDecision::Skip(format!(
+                "callback gas limit {} above the maximum {}",
+                callback_gas_limit, max_callback_gas_limit
+            )),
+            None,
+        );
+    }
+    let cost = execution_gas
+        .saturating_add(callback_gas_limit)
+        .saturating_mul(gas_price as u128);
+    let unprofitable = match execution_fee < cost {
+        true => Some(format!(
+            "unprofitable, execution fee {} below the {} wei the execution and its callback cost at gas price {} wei",
+            execution_fee, cost, gas_price
+        )),
+        false => None,
+    };
+    (PolicyDecision::Execute, unprofitable)
+}
+
+// A struct representing the validation of the callback gas limits before execution, so actions
+// whose callback makes their execution revert or cost more than their fee are left alone.
+// @execution_gas: The gas of an execution without its callback, weighed with the callback gas
+// limit against the execution fee.
+// @skip_unprofitable: Whether unprofitable callbacks get skipped rather than only flagged.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallbackGasCheck {
+    pub execution_gas: u128,
+    pub skip_unprofitable: bool,
+}
+
+impl CallbackGasCheck {
+    pub fn from_env() -> Self {
+        CallbackGasCheck {
+            execution_gas: config::get_callback_execution_gas(),
+            skip_unprofitable: config::get_skip_unprofitable_callbacks(),
+        }
+    }
+
+    // Checks the callback gas limit of an action against the protocol maximum and its execution
+    // fee, actions without callback always being executed.
+    // @contracts: The keeper contracts, the DataStore the maximum is read from.
+    // @action: The action to execute.
+    pub async fn check(
+        &self,
+        contracts: &Contracts,
+        action: &SatoruAction,
+    ) -> Result<PolicyDecision, KeeperError> {
+        if !has_callback(action) {
+            return Ok(PolicyDecision::Execute);
+        }
+        let max_callback_gas_limit = contracts
+            .data_store
+            .get_felt252(&max_callback_gas_limit_key())
+            .call()
+            .await
+            .map_err(|e| {
+                KeeperError::ExecutionError(format!(
+                    "could not read max callback gas limit: {:?}",
+                    e
+                ))
+            })?;
+        let gas_price = get_gas_price(&contracts.account).await?;
+        Ok(self.decide(
+            &action.key,
+            check_callback_gas(
+                action.callback_gas_limit,
+                u128::try_from(max_callback_gas_limit).unwrap_or(u128::MAX),
+                action.execution_fee,
+                self.execution_gas,
+                gas_price,
+            ),
+        ))
+    }
+
+    // Decides on an action from its callback gas check, skipping it when unprofitable if the
+    // operator chose to, executing it at a loss otherwise.
+    // @key: The key of the action.
+    // @checked: The decision and unprofitability reason returned by check_callback_gas.
+    pub fn decide(&self, key: &str, checked: (PolicyDecision, Option<String>)) -> PolicyDecision {
+        match checked {
+            (_, Some(reason)) if self.skip_unprofitable => PolicyDecision::Skip(reason),
+            (decision, Some(reason)) => {
+                warn!("Executing action {} at a loss: {}", key, reason);
+                decision
+            }
+            (decision, None) => decision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_callback() {
+        let action = |callback_contract: &str| SatoruAction {
+            callback_contract: callback_contract.to_owned(),
+            ..Default::default()
+        };
+        assert!(has_callback(&action("0a")));
+        assert!(!has_callback(&action(&"0".repeat(64))));
+        assert!(!has_callback(&action("")));
+    }
+
+    #[test]
+    fn test_check_callback_gas() {
+        assert_eq!(
+            check_callback_gas(100, 200, 1_000, 0, 1),
+            (PolicyDecision::Execute, None)
+        );
+        assert!(matches!(
+            check_callback_gas(300, 200, 1_000, 0, 1),
+            (PolicyDecision::Skip(_), None)
+        ));
+        // No maximum read, the limit is left to the protocol.
+        assert_eq!(
+            check_callback_gas(300, 0, 1_000, 0, 1),
+            (PolicyDecision::Execute, None)
+        );
+        // 100 + 300 gas at 3 wei is above the 1000 wei fee.
+        assert!(matches!(
+            check_callback_gas(300, 0, 1_000, 100, 3),
+            (PolicyDecision::Execute, Some(_))
+        ));
+        assert_eq!(
+            check_callback_gas(u128::MAX, 0, u128::MAX, 1, u64::MAX),
+            (PolicyDecision::Execute, None)
+        );
+    }
+
+    #[test]
+    fn test_decide() {
+        let unprofitable = || check_callback_gas(300, 0, 1_000, 100, 3);
+        let check = CallbackGasCheck {
+            execution_gas: 100,
+            skip_unprofitable: false,
+        };
+        assert_eq!(check.decide("0x1", unprofitable()), PolicyDecision::Execute);
+        let check = CallbackGasCheck {
+            skip_unprofitable: true,
+            ..check
+        };
+        assert!(matches!(
+            check.decide("0x1", unprofitable()),
+            PolicyDecision::Skip(reason) if reason.starts_with("unprofitable")
+        ));
+        assert!(matches!(
+            check.decide("0x1", check_callback_gas(300, 200, 1_000, 0, 1)),
+            PolicyDecision::Skip(reason) if reason.starts_with("callback gas limit")
+        ));
+    }
+}
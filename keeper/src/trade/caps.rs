@@ -0,0 +1,296 @@
+use cainome::cairo_serde::{ContractAddress, U256};
+use satoru_client::amounts::{Price, TokenAmount, Usd};
+use starknet::core::{types::FieldElement, utils::cairo_short_string_to_felt};
+use starknet_crypto::poseidon_hash_many;
+
+use crate::{
+    contracts::Contracts,
+    error::KeeperError,
+    market_config::market_key,
+    trade::{policy::PolicyDecision, price::stable::get_stable_price, utils::get_primary_price},
+    types::SatoruAction,
+};
+
+// The order types increasing a position, the only ones checked against the market caps.
+const INCREASE_ORDER_TYPES: [&str; 2] = ["MarketIncrease", "LimitIncrease"];
+
+// Computes the DataStore key of the reserve factor of a market side, as keys::reserve_factor_key does.
+// @market: The market token address.
+// @is_long: Whether it is the long side.
+pub fn reserve_factor_key(market: FieldElement, is_long: bool) -> FieldElement {
+    poseidon_hash_many(&[
+        cairo_short_string_to_felt("RESERVE_FACTOR").expect("Invalid short string"),
+        market,
+        FieldElement::from(is_long as u8),
+    ])
+}
+
+// Decimals of the reserve factors, as precision::FLOAT_PRECISION.
+const FACTOR_DECIMALS: i64 = 20;
+
+// Computes the DataStore key of the open interest in tokens of a market side for a collateral token,
+// as keys::open_interest_in_tokens_key does.
+// @market: The market token address.
+// @collateral_token: The collateral token address.
+// @is_long: Whether it is the long side.
+pub fn open_interest_in_tokens_key(
+    market: FieldElement,
+    collateral_token: FieldElement,
+    is_long: bool,
+) -> FieldElement {
+    market_key(
+        "OPEN_INTEREST_IN_TOKENS",
+        market,
+        &[collateral_token, FieldElement::from(is_long as u8)],
+    )
+}
+
+// Converts a u256 read from the DataStore to u128, saturating the values that do not fit.
+fn to_u128(value: &U256) -> u128 {
+    match value.high {
+        0 => value.low,
+        _ => u128::MAX,
+    }
+}
+
+// Returns whether increasing the open interest of a market side by size_delta_usd goes above its cap,
+// in which case the execution deterministically reverts.
+// @open_interest: The current open interest of the side, both collateral tokens included.
+// @size_delta_usd: The size the order increases the position by.
+// @max_open_interest: The open interest cap of the side.
+pub fn exceeds_open_interest_cap(
+    open_interest: u128,
+    size_delta_usd: u128,
+    max_open_interest: u128,
+) -> bool {
+    open_interest.saturating_add(size_delta_usd) > max_open_interest
+}
+
+// Returns whether the reserved USD of a market side increased by size_delta_usd goes above the share
+// of its pool the reserve factor allows, in which case the execution deterministically reverts.
+// @reserved_usd: The reserved USD of the side, its open interest in tokens at the index token price
+// for the longs, its open interest for the shorts.
+// @size_delta_usd: The size the order increases the position by.
+// @pool_usd: The pool amount of the side token at its price.
+// @reserve_factor: The reserve factor of the side, with FACTOR_DECIMALS decimals.
+pub fn exceeds_reserve(
+    reserved_usd: Usd,
+    size_delta_usd: u128,
+    pool_usd: &Usd,
+    reserve_factor: u128,
+) -> bool {
+    reserved_usd + Usd::from_u128(size_delta_usd)
+        > pool_usd.apply_factor(reserve_factor, FACTOR_DECIMALS)
+}
+
+// Reads a u256 value of the DataStore.
+async fn read_u256(contracts: &Contracts, key: FieldElement) -> Result<U256, KeeperError> {
+    contracts
+        .data_store
+        .get_u256(&key)
+        .call()
+        .await
+        .map_err(|e| KeeperError::ExecutionError(format!("could not read caps: {:?}", e)))
+}
+
+// Checks an increase order against the reserve and open interest cap of its market side, skipping
+// it when the side has no reserve and deferring it while it is above them, as its execution would
+// revert on them until positions get closed or the pool grows. The reserve only gets checked when
+// the side token is priced. Other actions are always executed.
+// @contracts: The keeper contracts, the DataStore they are read from.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The action to execute.
+pub async fn check_increase_caps(
+    contracts: &Contracts,
+    table: &str,
+    action: &SatoruAction,
+) -> Result<PolicyDecision, KeeperError> {
+    let is_increase = action
+        .order_type
+        .as_deref()
+        .is_some_and(|order_type| INCREASE_ORDER_TYPES.contains(&order_type));
+    if table != "orders" || !is_increase {
+        return Ok(PolicyDecision::Execute);
+    }
+
+    let data_store = &contracts.data_store;
+    let to_error = |e| KeeperError::ExecutionError(format!("could not read caps: {:?}", e));
+
+    let market_address =
+        FieldElement::from_hex_be(&action.market).expect("Cannot convert string to felt");
+    let is_long = action.is_long.unwrap_or(false);
+    let market = data_store
+        .get_market(&ContractAddress::from(market_address))
+        .call()
+        .await
+        .map_err(to_error)?;
+
+    let reserve_factor =
+        to_u128(&read_u256(contracts, reserve_factor_key(market_address, is_long)).await?);
+    if reserve_factor == 0 {
+        return Ok(PolicyDecision::Skip(format!(
+            "market {} has no reserve for its {} side",
+            action.market,
+            if is_long { "long" } else { "short" }
+        )));
+    }
+
+    let mut open_interest: u128 = 0;
+    let mut open_interest_in_tokens: u128 = 0;
+    for collateral_token in [&market.long_token, &market.short_token] {
+        let open_interest_key = data_store
+            .get_open_interest_key(&market.market_token, collateral_token, &is_long)
+            .call()
+            .await
+            .map_err(to_error)?;
+        let collateral_open_interest = read_u256(contracts, open_interest_key).await?;
+        open_interest = open_interest.saturating_add(to_u128(&collateral_open_interest));
+        if is_long {
+            let collateral_open_interest_in_tokens = read_u256(
+                contracts,
+                open_interest_in_tokens_key(market_address, collateral_token.0, is_long),
+            )
+            .await?;
+            open_interest_in_tokens = open_interest_in_tokens
+                .saturating_add(to_u128(&collateral_open_interest_in_tokens));
+        }
+    }
+
+    let size_delta_usd = action.size_delta_usd.unwrap_or(0);
+    let side_token = if is_long {
+        market.long_token
+    } else {
+        market.short_token
+    };
+    // Longs get the primary price, the long token one, which only prices their reserve when the long
+    // token is the index token. Shorts get the stable price of the short token.
+    let side_price = match (is_long, market.index_token == market.long_token) {
+        (true, true) => Some(get_primary_price(action, contracts).await?.1),
+        (true, false) => None,
+        (false, _) => get_stable_price(contracts, side_token).await?,
+    };
+    if let Some(side_price) = side_price.filter(|price| price.high == 0) {
+        let side_price = Price::from_u128(side_price.low);
+        let pool_amount_key = data_store
+            .get_pool_amount_key(&market.market_token, &side_token)
+            .call()
+            .await
+            .map_err(to_error)?;
+        let pool_amount =
+            TokenAmount::from_u128(to_u128(&read_u256(contracts, pool_amount_key).await?));
+        let pool_usd = side_price.value_of(&pool_amount);
+        let reserved_usd = match is_long {
+            true => side_price.value_of(&TokenAmount::from_u128(open_interest_in_tokens)),
+            false => Usd::from_u128(open_interest),
+        };
+        if exceeds_reserve(
+            reserved_usd.clone(),
+            size_delta_usd,
+            &pool_usd,
+            reserve_factor,
+        ) {
+            return Ok(PolicyDecision::Defer(format!(
+                "reserved {} + {} above the {} reserve factor of the pool {} of market {}",
+                reserved_usd.raw(),
+                size_delta_usd,
+                reserve_factor,
+                pool_usd.raw(),
+                action.market
+            )));
+        }
+    }
+
+    let max_open_interest_key = data_store
+        .get_max_open_interest_key(&market.market_token, &is_long)
+        .call()
+        .await
+        .map_err(to_error)?;
+    let max_open_interest = read_u256(contracts, max_open_interest_key).await?;
+
+    if exceeds_open_interest_cap(open_interest, size_delta_usd, to_u128(&max_open_interest)) {
+        return Ok(PolicyDecision::Defer(format!(
+            "open interest {} + {} above the cap {} of market {}",
+            open_interest,
+            size_delta_usd,
+            to_u128(&max_open_interest),
+            action.market
+        )));
+    }
+    Ok(PolicyDecision::Execute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeds_open_interest_cap() {
+        assert!(!exceeds_open_interest_cap(50, 50, 100));
+        assert!(exceeds_open_interest_cap(50, 51, 100));
+        assert!(exceeds_open_interest_cap(0, 1, 0));
+        assert!(exceeds_open_interest_cap(u128::MAX, 1, u128::MAX - 1));
+    }
+
+    #[test]
+    fn test_exceeds_reserve() {
+        // A 1000 USD pool reserving half of it.
+        let usd = |value: u128| Usd::from_u128(value * 10u128.pow(30));
+        let half = 5 * 10u128.pow(19);
+        assert!(!exceeds_reserve(
+            usd(400),
+            100 * 10u128.pow(30),
+            &usd(1000),
+            half
+        ));
+        assert!(exceeds_reserve(
+            usd(400),
+            100 * 10u128.pow(30) + 1,
+            &usd(1000),
+            half
+        ));
+        assert!(exceeds_reserve(usd(0), 1, &usd(1000), 0));
+
+        // Longs reserve their open interest in tokens at the index token price, 0.1 ETH at 3000 USD
+        // against a 1 ETH pool.
+        let price = Price::from_u128(3000 * 10u128.pow(12));
+        let reserved = price.value_of(&TokenAmount::from_u128(10u128.pow(17)));
+        let pool = price.value_of(&TokenAmount::from_u128(10u128.pow(18)));
+        assert!(!exceeds_reserve(
+            reserved.clone(),
+            1200 * 10u128.pow(30),
+            &pool,
+            half
+        ));
+        assert!(exceeds_reserve(
+            reserved,
+            1201 * 10u128.pow(30),
+            &pool,
+            half
+        ));
+    }
+
+    #[test]
+    fn test_to_u128() {
+        assert_eq!(to_u128(&U256 { low: 42, high: 0 }), 42);
+        assert_eq!(to_u128(&U256 { low: 42, high: 1 }), u128::MAX);
+    }
+
+    #[test]
+    fn test_reserve_factor_key() {
+        let market = FieldElement::from_hex_be("0x12").unwrap();
+        assert_ne!(
+            reserve_factor_key(market, true),
+            reserve_factor_key(market, false)
+        );
+        let token = FieldElement::from_hex_be("0x34").unwrap();
+        assert_eq!(
+            open_interest_in_tokens_key(market, token, true),
+            poseidon_hash_many(&[
+                cairo_short_string_to_felt("OPEN_INTEREST_IN_TOKENS").unwrap(),
+                market,
+                token,
+                FieldElement::ONE,
+            ])
+        );
+    }
+}
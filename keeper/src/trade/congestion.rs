@@ -0,0 +1,213 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use starknet::{
+    accounts::{ConnectedAccount, SingleOwnerAccount},
+    core::types::{BlockId, BlockTag, MaybePendingBlockWithTxHashes},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+use tokio::time::sleep;
+
+use super::queue::ExecutionClass;
+use crate::{config, error::KeeperError, types::SatoruAction};
+
+// A struct deferring the low value executions while network fees spike, so the keeper only pays
+// congestion fees for the work worth it, such as liquidations, the deferred executions resuming
+// once fees normalize.
+// @spike_gas_price: The L1 gas price in wei congestion starts at, never congested when None.
+// @resume_gas_price: The L1 gas price in wei congestion ends below.
+// @min_execution_fee: The smallest execution fee of the executions kept while congested.
+// @min_size_usd: The smallest size of the orders kept while congested.
+// @gas_price: The last L1 gas price read, in wei.
+// @congested: Whether fees are spiking.
+#[derive(Debug, Default)]
+pub struct GasThrottle {
+    pub spike_gas_price: Option<u64>,
+    pub resume_gas_price: u64,
+    pub min_execution_fee: u128,
+    pub min_size_usd: u128,
+    gas_price: AtomicU64,
+    congested: AtomicBool,
+}
+
+impl GasThrottle {
+    pub fn new(
+        spike_gas_price: Option<u64>,
+        resume_gas_price: Option<u64>,
+        min_execution_fee: u128,
+        min_size_usd: u128,
+    ) -> Self {
+        GasThrottle {
+            spike_gas_price,
+            // Fees resume below the spike price unless a lower resume price avoids flapping.
+            resume_gas_price: resume_gas_price.or(spike_gas_price).unwrap_or(0),
+            min_execution_fee,
+            min_size_usd,
+            gas_price: AtomicU64::new(0),
+            congested: AtomicBool::new(false),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        GasThrottle::new(
+            config::get_gas_spike_price(),
+            config::get_gas_resume_price(),
+            config::get_gas_spike_min_execution_fee(),
+            config::get_gas_spike_min_size_usd(),
+        )
+    }
+
+    // Updates the congestion with a new gas price read, congestion starting at the spike price and
+    // ending below the resume price.
+    // @gas_price: The L1 gas price in wei.
+    pub fn set_gas_price(&self, gas_price: u64) {
+        let spike_gas_price = match self.spike_gas_price {
+            Some(spike_gas_price) => spike_gas_price,
+            None => return,
+        };
+        self.gas_price.store(gas_price, Ordering::Relaxed);
+        let was_congested = self.congested.load(Ordering::Relaxed);
+        let congested = match was_congested {
+            true => gas_price >= self.resume_gas_price,
+            false => gas_price >= spike_gas_price,
+        };
+        if congested != was_congested {
+            match congested {
+                true => warn!(
+                    "Gas price spiking at {} wei, deferring low value executions",
+                    gas_price
+                ),
+                false => info!(
+                    "Gas price back to {} wei, resuming deferred executions",
+                    gas_price
+                ),
+            }
+            self.congested.store(congested, Ordering::Relaxed);
+        }
+    }
+
+    // Returns why an execution gets deferred, None when it goes on: fees are not spiking, it is a
+    // liquidation, or its execution fee and size are above the floors.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub fn defer_reason(&self, table: &str, action: &SatoruAction) -> Option<String> {
+        if !self.congested.load(Ordering::Relaxed)
+            || ExecutionClass::of(table, action) == ExecutionClass::Liquidation
+        {
+            return None;
+        }
+        let gas_price = self.gas_price.load(Ordering::Relaxed);
+        if action.execution_fee < self.min_execution_fee {
+            return Some(format!(
+                "execution fee {} below the congestion floor {} at gas price {} wei",
+                action.execution_fee, self.min_execution_fee, gas_price
+            ));
+        }
+        match action.size_delta_usd {
+            Some(size) if table == "orders" && size < self.min_size_usd => Some(format!(
+                "size {} below the congestion floor {} at gas price {} wei",
+                size, self.min_size_usd, gas_price
+            )),
+            _ => None,
+        }
+    }
+}
+
+// Returns the L1 gas price of the latest block, in wei.
+// @account: The keeper account, used to read the chain.
+pub async fn get_gas_price(
+    account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+) -> Result<u64, KeeperError> {
+    let gas_price = match account
+        .provider()
+        .get_block_with_tx_hashes(BlockId::Tag(BlockTag::Latest))
+        .await
+    {
+        Ok(MaybePendingBlockWithTxHashes::Block(block)) => block.l1_gas_price.price_in_wei,
+        Ok(MaybePendingBlockWithTxHashes::PendingBlock(block)) => block.l1_gas_price.price_in_wei,
+        Err(e) => {
+            return Err(KeeperError::ExecutionError(format!(
+                "could not get latest gas price: {:?}",
+                e
+            )))
+        }
+    };
+    u64::try_from(gas_price).map_err(|_| {
+        KeeperError::ExecutionError(format!("gas price {:#x} out of range", gas_price))
+    })
+}
+
+// Reads the gas price every poll interval, updating the congestion of the throttle.
+// @throttle: The gas throttle.
+// @account: The keeper account, used to read the chain.
+pub async fn watch_gas_price(
+    throttle: &GasThrottle,
+    account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+) {
+    let interval = Duration::from_secs(config::get_gas_price_poll_interval_secs());
+    loop {
+        match get_gas_price(account).await {
+            Ok(gas_price) => throttle.set_gas_price(gas_price),
+            Err(e) => error!("{}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_type: &str, execution_fee: u128, size_delta_usd: u128) -> SatoruAction {
+        SatoruAction {
+            order_type: Some(order_type.to_owned()),
+            execution_fee,
+            size_delta_usd: Some(size_delta_usd),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_congestion() {
+        let throttle = GasThrottle::new(Some(100), Some(80), 10, 1000);
+        let small = order("MarketIncrease", 5, 5000);
+        assert!(throttle.defer_reason("orders", &small).is_none());
+
+        throttle.set_gas_price(120);
+        assert!(throttle.defer_reason("orders", &small).is_some());
+        assert!(throttle
+            .defer_reason("orders", &order("MarketIncrease", 20, 500))
+            .is_some());
+        assert!(throttle
+            .defer_reason("orders", &order("MarketIncrease", 20, 5000))
+            .is_none());
+        assert!(throttle
+            .defer_reason("orders", &order("Liquidation", 0, 0))
+            .is_none());
+
+        // Fees below the spike price but above the resume price keep the congestion.
+        throttle.set_gas_price(90);
+        assert!(throttle.defer_reason("orders", &small).is_some());
+        throttle.set_gas_price(70);
+        assert!(throttle.defer_reason("orders", &small).is_none());
+    }
+
+    #[test]
+    fn test_congestion_disabled() {
+        let throttle = GasThrottle::new(None, None, 10, 1000);
+        throttle.set_gas_price(u64::MAX);
+        assert!(throttle
+            .defer_reason("orders", &order("MarketIncrease", 0, 0))
+            .is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+use crate::{config, types::SatoruAction};
+
+// A struct representing the recent liquidations and until when the flash crash mode is engaged.
+#[derive(Debug, Default)]
+struct CrashState {
+    liquidations: VecDeque<Instant>,
+    engaged_until: Option<Instant>,
+}
+
+// A struct holding the flash crash mode, engaged when liquidations come in bursts so the keeper
+// liquidates as many positions as it can rather than one at a time: liquidations get batched in
+// larger multicalls, the paymaster fee cap gets raised and the min order sizes stop holding them
+// back.
+// @burst_liquidations: The number of liquidations within the burst window engaging the mode,
+// never engaged when None.
+// @burst_window: The window liquidations get counted over.
+// @duration: How long the mode stays engaged after the last burst.
+// @batch_window: How long liquidations wait to be batched with others while engaged.
+// @fee_cap_multiplier: The factor the paymaster max fee gets multiplied by while engaged.
+// @bypass_min_sizes: Whether liquidations below the min order size get executed while engaged.
+// @state: The recent liquidations and until when the mode is engaged.
+#[derive(Debug, Default)]
+pub struct CrashMode {
+    pub burst_liquidations: Option<usize>,
+    pub burst_window: Duration,
+    pub duration: Duration,
+    pub batch_window: Duration,
+    pub fee_cap_multiplier: f64,
+    pub bypass_min_sizes: bool,
+    state: Mutex<CrashState>,
+}
+
+// Checks whether an action is a liquidation.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @action: The action.
+pub fn is_liquidation(table: &str, action: &SatoruAction) -> bool {
+    table == "orders" && action.order_type.as_deref() == Some("Liquidation")
+}
+
+impl CrashMode {
+    pub fn from_env() -> Self {
+        CrashMode {
+            burst_liquidations: config::get_crash_mode_liquidations(),
+            burst_window: Duration::from_secs(config::get_crash_mode_window_secs()),
+            duration: Duration::from_secs(config::get_crash_mode_duration_secs()),
+            batch_window: Duration::from_millis(config::get_crash_mode_batch_window_ms()),
+            fee_cap_multiplier: config::get_crash_mode_fee_cap_multiplier(),
+            bypass_min_sizes: config::get_crash_mode_bypass_min_sizes(),
+            state: Mutex::new(CrashState::default()),
+        }
+    }
+
+    // Counts a new liquidation, engaging the mode once enough of them came within the window.
+    pub fn record_liquidation(&self) {
+        self.record_liquidation_at(Instant::now())
+    }
+
+    fn record_liquidation_at(&self, now: Instant) {
+        let burst_liquidations = match self.burst_liquidations {
+            Some(burst_liquidations) => burst_liquidations,
+            None => return,
+        };
+        let mut state = self.state.lock().unwrap();
+        while state
+            .liquidations
+            .front()
+            .is_some_and(|liquidation| now.duration_since(*liquidation) >= self.burst_window)
+        {
+            state.liquidations.pop_front();
+        }
+        state.liquidations.push_back(now);
+        if state.liquidations.len() >= burst_liquidations {
+            if state.engaged_until.is_none_or(|until| now >= until) {
+                warn!(
+                    "Flash crash mode engaged, {} liquidations within {:?}",
+                    state.liquidations.len(),
+                    self.burst_window
+                );
+            }
+            state.engaged_until = Some(now + self.duration);
+        }
+    }
+
+    // Checks whether the mode is engaged.
+    pub fn is_engaged(&self) -> bool {
+        self.is_engaged_at(Instant::now())
+    }
+
+    fn is_engaged_at(&self, now: Instant) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .engaged_until
+            .is_some_and(|until| now < until)
+    }
+
+    // Returns how long an execution waits to be batched with others, liquidations waiting for the
+    // crash batch window while engaged.
+    // @window: The batch window of the keeper.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action executed.
+    pub fn batch_window(&self, window: Duration, table: &str, action: &SatoruAction) -> Duration {
+        match is_liquidation(table, action) && self.is_engaged() {
+            true => window.max(self.batch_window),
+            false => window,
+        }
+    }
+
+    // Returns the paymaster max fee, raised by the fee cap multiplier while engaged.
+    // @max_fee: The max fee of the paymaster, if any.
+    pub fn fee_cap(&self, max_fee: Option<u128>) -> Option<u128> {
+        match self.is_engaged() {
+            true => max_fee.map(|max_fee| (max_fee as f64 * self.fee_cap_multiplier) as u128),
+            false => max_fee,
+        }
+    }
+
+    // Checks whether the min order sizes get checked for an action, liquidations being executed
+    // whatever their size while engaged if configured so.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub fn checks_min_sizes(&self, table: &str, action: &SatoruAction) -> bool {
+        !(self.bypass_min_sizes && is_liquidation(table, action) && self.is_engaged())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crash_mode() -> CrashMode {
+        CrashMode {
+            burst_liquidations: Some(3),
+            burst_window: Duration::from_secs(60),
+            duration: Duration::from_secs(300),
+            batch_window: Duration::from_millis(500),
+            fee_cap_multiplier: 2.0,
+            bypass_min_sizes: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_engage_crash_mode() {
+        let crash_mode = crash_mode();
+        let now = Instant::now();
+
+        // Liquidations spread over more than the window do not engage the mode.
+        crash_mode.record_liquidation_at(now);
+        crash_mode.record_liquidation_at(now + Duration::from_secs(30));
+        crash_mode.record_liquidation_at(now + Duration::from_secs(61));
+        assert!(!crash_mode.is_engaged_at(now + Duration::from_secs(61)));
+
+        let burst = now + Duration::from_secs(62);
+        crash_mode.record_liquidation_at(burst);
+        assert!(crash_mode.is_engaged_at(burst));
+        assert!(crash_mode.is_engaged_at(burst + Duration::from_secs(299)));
+        assert!(!crash_mode.is_engaged_at(burst + Duration::from_secs(300)));
+
+        let disabled = CrashMode::default();
+        for _ in 0..5 {
+            disabled.record_liquidation_at(now);
+        }
+        assert!(!disabled.is_engaged_at(now));
+    }
+
+    #[test]
+    fn test_crash_mode_limits() {
+        let crash_mode = crash_mode();
+        let liquidation = SatoruAction {
+            order_type: Some("Liquidation".to_owned()),
+            ..Default::default()
+        };
+        let order = SatoruAction {
+            order_type: Some("MarketIncrease".to_owned()),
+            ..Default::default()
+        };
+        let window = Duration::from_millis(100);
+
+        assert_eq!(
+            crash_mode.batch_window(window, "orders", &liquidation),
+            window
+        );
+        assert_eq!(crash_mode.fee_cap(Some(100)), Some(100));
+        assert!(crash_mode.checks_min_sizes("orders", &liquidation));
+
+        for _ in 0..3 {
+            crash_mode.record_liquidation();
+        }
+        assert_eq!(
+            crash_mode.batch_window(window, "orders", &liquidation),
+            Duration::from_millis(500)
+        );
+        assert_eq!(crash_mode.batch_window(window, "orders", &order), window);
+        assert_eq!(crash_mode.fee_cap(Some(100)), Some(200));
+        assert_eq!(crash_mode.fee_cap(None), None);
+        assert!(!crash_mode.checks_min_sizes("orders", &liquidation));
+        assert!(crash_mode.checks_min_sizes("orders", &order));
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use starknet::{
+    accounts::{ConnectedAccount, SingleOwnerAccount},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+
+use crate::{config, error::KeeperError, types::SatoruAction};
+
+// The order types waiting for their trigger price, the only ones expiring.
+const TRIGGER_ORDER_TYPES: [&str; 4] = [
+    "LimitSwap",
+    "LimitIncrease",
+    "LimitDecrease",
+    "StopLossDecrease",
+];
+
+// A struct representing the maximum age of the trigger orders the keeper keeps executing, so
+// stale intents never get executed long after they were placed.
+// @ttl_blocks: The number of blocks a trigger order stays executable after it got created or last
+// updated, no limit when None.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrderExpiry {
+    pub ttl_blocks: Option<u64>,
+}
+
+impl OrderExpiry {
+    pub fn from_env() -> Self {
+        OrderExpiry {
+            ttl_blocks: config::get_order_ttl_blocks(),
+        }
+    }
+
+    // Returns whether the TTL applies to an action, only trigger orders expiring.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub fn applies_to(&self, table: &str, action: &SatoruAction) -> bool {
+        self.ttl_blocks.is_some()
+            && table == "orders"
+            && action
+                .order_type
+                .as_deref()
+                .is_some_and(|order_type| TRIGGER_ORDER_TYPES.contains(&order_type))
+    }
+
+    // Returns the age in blocks of an order older than the TTL, None while it is executable.
+    // @action: The order to execute.
+    // @head: The current chain head.
+    pub fn expired_age(&self, action: &SatoruAction, head: u64) -> Option<u64> {
+        let ttl_blocks = self.ttl_blocks?;
+        // Orders never updated report 0, their creation block is used instead.
+        let updated_at_block = match action.updated_at_block {
+            0 => action.block_number,
+            updated_at_block => updated_at_block,
+        };
+        let age = head.saturating_sub(updated_at_block);
+        (age > ttl_blocks).then_some(age)
+    }
+
+    // Checks an action against the TTL, reading the chain head only for the actions it applies to.
+    // Returns the age in blocks of an expired order.
+    // @account: The keeper account, used to read the chain head.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub async fn check(
+        &self,
+        account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+        table: &str,
+        action: &SatoruAction,
+    ) -> Result<Option<u64>, KeeperError> {
+        if !self.applies_to(table, action) {
+            return Ok(None);
+        }
+        let head = account.provider().block_number().await.map_err(|e| {
+            KeeperError::ExecutionError(format!("could not get chain head: {:?}", e))
+        })?;
+        Ok(self.expired_age(action, head))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_type: &str, block_number: u64, updated_at_block: u64) -> SatoruAction {
+        SatoruAction {
+            order_type: Some(order_type.to_owned()),
+            block_number,
+            updated_at_block,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let expiry = OrderExpiry {
+            ttl_blocks: Some(100),
+        };
+        assert!(expiry.applies_to("orders", &order("LimitIncrease", 0, 0)));
+        assert!(expiry.applies_to("orders", &order("StopLossDecrease", 0, 0)));
+        assert!(!expiry.applies_to("orders", &order("MarketIncrease", 0, 0)));
+        assert!(!expiry.applies_to("deposits", &SatoruAction::default()));
+        assert!(!OrderExpiry::default().applies_to("orders", &order("LimitIncrease", 0, 0)));
+    }
+
+    #[test]
+    fn test_expired_age() {
+        let expiry = OrderExpiry {
+            ttl_blocks: Some(100),
+        };
+        assert_eq!(expiry.expired_age(&order("LimitSwap", 1000, 0), 1100), None);
+        assert_eq!(
+            expiry.expired_age(&order("LimitSwap", 1000, 0), 1101),
+            Some(101)
+        );
+        // An update renews the order.
+        assert_eq!(
+            expiry.expired_age(&order("LimitSwap", 1000, 1050), 1101),
+            None
+        );
+        assert_eq!(expiry.expired_age(&order("LimitSwap", 1000, 0), 900), None);
+    }
+}
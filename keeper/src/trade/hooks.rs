@@ -0,0 +1,367 @@
+use std::sync::Arc;
+
+use starknet::{
+    accounts::Call,
+    core::{types::FieldElement, utils::get_selector_from_name},
+};
+
+use crate::{config, error::KeeperError, types::SatoruAction};
+
+// Tables a hook applies to, `*` standing for all of them.
+const HOOK_TABLES: [&str; 4] = ["orders", "deposits", "withdrawals", "*"];
+
+// A trait letting operators add their own calls to the multicall executing an action, e.g. to
+// their accounting contract or a fee splitter, without changing how the execution gets built.
+// Calls returned by `before` are sent ahead of the oracle prices, those returned by `after`
+// behind the execution, all of them reverting together.
+pub trait CallHook: Send + Sync {
+    // Returns the calls sent before the execution of an action.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action executed.
+    fn before(&self, _table: &str, _action: &SatoruAction) -> Vec<Call> {
+        vec![]
+    }
+
+    // Returns the calls sent after the execution of an action.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action executed.
+    fn after(&self, _table: &str, _action: &SatoruAction) -> Vec<Call> {
+        vec![]
+    }
+}
+
+// An enum representing a calldata felt of a configured call, either constant or read from the
+// executed action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookArg {
+    Felt(FieldElement),
+    Key,
+    Account,
+    Market,
+    Receiver,
+}
+
+impl HookArg {
+    // Parses a calldata felt, given as a hex or decimal felt or as the name of an action field.
+    // @arg: The calldata felt.
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.trim() {
+            "key" => Some(HookArg::Key),
+            "account" => Some(HookArg::Account),
+            "market" => Some(HookArg::Market),
+            "receiver" => Some(HookArg::Receiver),
+            felt if felt.starts_with("0x") => {
+                FieldElement::from_hex_be(felt).ok().map(HookArg::Felt)
+            }
+            felt => FieldElement::from_dec_str(felt).ok().map(HookArg::Felt),
+        }
+    }
+
+    // Returns the felt of an action, None when its field is not a felt.
+    // @action: The action executed.
+    fn resolve(&self, action: &SatoruAction) -> Option<FieldElement> {
+        let field = match self {
+            HookArg::Felt(felt) => return Some(*felt),
+            HookArg::Key => &action.key,
+            HookArg::Account => &action.account,
+            HookArg::Market => &action.market,
+            HookArg::Receiver => &action.receiver,
+        };
+        // The indexer stores felts without 0x prefix.
+        FieldElement::from_hex_be(field).ok()
+    }
+}
+
+// A struct representing a call configured by the operator, sent around the executions of a table.
+// @after: Whether the call gets sent after the execution rather than before.
+// @table: The table whose executions get the call, None for every table.
+// @to: The contract called.
+// @selector: The selector of the method called.
+// @calldata: The calldata felts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfiguredCall {
+    pub after: bool,
+    pub table: Option<String>,
+    pub to: FieldElement,
+    pub selector: FieldElement,
+    pub calldata: Vec<HookArg>,
+}
+
+impl ConfiguredCall {
+    // Parses a hook entry formatted as position@table@contract@method@calldata, the position being
+    // before or after, the table * for every table and the calldata : separated felts or action
+    // fields (key, account, market, receiver), e.g. after@orders@0x12@record_execution@key:account.
+    // @entry: The hook entry.
+    pub fn parse(entry: &str) -> Result<Self, KeeperError> {
+        let invalid =
+            |reason: &str| KeeperError::HookError(format!("invalid hook {}: {}", entry, reason));
+        let (position, table, contract, method, calldata) =
+            match entry.split('@').collect::<Vec<_>>()[..] {
+                [position, table, contract, method] => (position, table, contract, method, ""),
+                [position, table, contract, method, calldata] => {
+                    (position, table, contract, method, calldata)
+                }
+                _ => return Err(invalid("expected position@table@contract@method@calldata")),
+            };
+        let after = match position.trim() {
+            "before" => false,
+            "after" => true,
+            _ => return Err(invalid("position must be before or after")),
+        };
+        let table = match table.trim() {
+            "*" => None,
+            table if HOOK_TABLES.contains(&table) => Some(table.to_owned()),
+            _ => return Err(invalid("table must be orders, deposits, withdrawals or *")),
+        };
+        Ok(ConfiguredCall {
+            after,
+            table,
+            to: FieldElement::from_hex_be(contract.trim())
+                .map_err(|_| invalid("invalid contract address"))?,
+            selector: get_selector_from_name(method.trim())
+                .map_err(|_| invalid("invalid method name"))?,
+            calldata: calldata
+                .split(':')
+                .filter(|arg| !arg.trim().is_empty())
+                .map(|arg| {
+                    HookArg::parse(arg).ok_or_else(|| invalid(&format!("invalid calldata {}", arg)))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // Builds the call for an action, None when the hook does not apply to its table or one of
+    // its fields is not a felt.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action executed.
+    fn build(&self, table: &str, action: &SatoruAction) -> Option<Call> {
+        if self
+            .table
+            .as_deref()
+            .is_some_and(|hook_table| hook_table != table)
+        {
+            return None;
+        }
+        Some(Call {
+            to: self.to,
+            selector: self.selector,
+            calldata: self
+                .calldata
+                .iter()
+                .map(|arg| arg.resolve(action))
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+// A struct representing the calls configured in EXECUTION_HOOKS, in their configuration order.
+#[derive(Debug, Clone, Default)]
+pub struct ConfiguredHooks {
+    pub calls: Vec<ConfiguredCall>,
+}
+
+impl ConfiguredHooks {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        Ok(ConfiguredHooks {
+            calls: config::get_execution_hooks()
+                .iter()
+                .map(|entry| ConfiguredCall::parse(entry))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn build(&self, after: bool, table: &str, action: &SatoruAction) -> Vec<Call> {
+        self.calls
+            .iter()
+            .filter(|call| call.after == after)
+            .filter_map(|call| call.build(table, action))
+            .collect()
+    }
+}
+
+impl CallHook for ConfiguredHooks {
+    fn before(&self, table: &str, action: &SatoruAction) -> Vec<Call> {
+        self.build(false, table, action)
+    }
+
+    fn after(&self, table: &str, action: &SatoruAction) -> Vec<Call> {
+        self.build(true, table, action)
+    }
+}
+
+// A struct holding the hooks the execution multicalls get composed with, the configured calls
+// first then the hooks registered by the binary in their registration order.
+#[derive(Clone, Default)]
+pub struct ExecutionHooks {
+    hooks: Vec<Arc<dyn CallHook>>,
+}
+
+impl ExecutionHooks {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        let configured = ConfiguredHooks::from_env()?;
+        let mut hooks = ExecutionHooks::default();
+        if !configured.calls.is_empty() {
+            hooks.register(Arc::new(configured));
+        }
+        Ok(hooks)
+    }
+
+    // Adds a hook to the executions, for binaries building the keeper as a library.
+    // @hook: The hook.
+    pub fn register(&mut self, hook: Arc<dyn CallHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    // Surrounds the calls executing an action with the calls of the hooks, the before calls in
+    // hook order and the after calls in reverse hook order, so each hook wraps the ones after it.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action executed.
+    // @calls: The calls executing the action.
+    pub fn compose(&self, table: &str, action: &SatoruAction, calls: Vec<Call>) -> Vec<Call> {
+        if self.hooks.is_empty() {
+            return calls;
+        }
+        let mut composed: Vec<Call> = self
+            .hooks
+            .iter()
+            .flat_map(|hook| hook.before(table, action))
+            .collect();
+        composed.extend(calls);
+        composed.extend(
+            self.hooks
+                .iter()
+                .rev()
+                .flat_map(|hook| hook.after(table, action)),
+        );
+        composed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action() -> SatoruAction {
+        SatoruAction {
+            key: "0a".to_owned(),
+            account: "0b".to_owned(),
+            market: "0c".to_owned(),
+            receiver: "0d".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn call(to: u64) -> Call {
+        Call {
+            to: FieldElement::from(to),
+            selector: FieldElement::ZERO,
+            calldata: vec![],
+        }
+    }
+
+    // A hook tagging its calls with its id, 1 before and 2 after.
+    struct TagHook(u64);
+
+    impl CallHook for TagHook {
+        fn before(&self, _table: &str, _action: &SatoruAction) -> Vec<Call> {
+            vec![call(self.0 * 10 + 1)]
+        }
+
+        fn after(&self, _table: &str, _action: &SatoruAction) -> Vec<Call> {
+            vec![call(self.0 * 10 + 2)]
+        }
+    }
+
+    #[test]
+    fn test_parse_configured_call() {
+        let hook =
+            ConfiguredCall::parse("after@orders@0x12@record_execution@key:account:0x5:7").unwrap();
+        assert_eq!(
+            hook,
+            ConfiguredCall {
+                after: true,
+                table: Some("orders".to_owned()),
+                to: FieldElement::from(0x12u64),
+                selector: get_selector_from_name("record_execution").unwrap(),
+                calldata: vec![
+                    HookArg::Key,
+                    HookArg::Account,
+                    HookArg::Felt(FieldElement::from(5u64)),
+                    HookArg::Felt(FieldElement::from(7u64)),
+                ],
+            }
+        );
+        let hook = ConfiguredCall::parse("before@*@0x12@ping").unwrap();
+        assert_eq!(
+            (hook.after, hook.table, hook.calldata),
+            (false, None, vec![])
+        );
+
+        for entry in [
+            "after@orders@0x12",
+            "during@orders@0x12@ping",
+            "after@positions@0x12@ping",
+            "after@orders@contract@ping",
+            "after@orders@0x12@ping@owner",
+        ] {
+            assert!(ConfiguredCall::parse(entry).is_err(), "{}", entry);
+        }
+    }
+
+    #[test]
+    fn test_build_configured_call() {
+        let hook = ConfiguredCall::parse("after@orders@0x12@record@key:market:receiver:9").unwrap();
+        let built = hook.build("orders", &action()).unwrap();
+        assert_eq!(built.to, FieldElement::from(0x12u64));
+        assert_eq!(
+            built.calldata,
+            [0x0a, 0x0c, 0x0d, 9].map(|felt: u64| FieldElement::from(felt))
+        );
+        assert!(hook.build("deposits", &action()).is_none());
+        let invalid = SatoruAction {
+            key: "not a felt".to_owned(),
+            ..action()
+        };
+        assert!(hook.build("orders", &invalid).is_none());
+    }
+
+    #[test]
+    fn test_compose() {
+        let to = |calls: Vec<Call>| calls.iter().map(|call| call.to).collect::<Vec<_>>();
+        let calls = vec![call(100), call(200)];
+        assert_eq!(
+            to(ExecutionHooks::default().compose("orders", &action(), calls.clone())),
+            to(calls.clone())
+        );
+
+        let mut hooks = ExecutionHooks::default();
+        hooks.register(Arc::new(TagHook(1)));
+        hooks.register(Arc::new(TagHook(2)));
+        assert_eq!(
+            to(hooks.compose("orders", &action(), calls)),
+            [11, 21, 100, 200, 22, 12].map(|to: u64| FieldElement::from(to))
+        );
+    }
+
+    #[test]
+    fn test_configured_hooks() {
+        let hooks = ConfiguredHooks {
+            calls: vec![
+                ConfiguredCall::parse("before@*@0x1@ping").unwrap(),
+                ConfiguredCall::parse("after@deposits@0x2@record@key").unwrap(),
+                ConfiguredCall::parse("after@orders@0x3@record@key").unwrap(),
+            ],
+        };
+        let to = |calls: Vec<Call>| calls.iter().map(|call| call.to).collect::<Vec<_>>();
+        assert_eq!(to(hooks.before("orders", &action())), [FieldElement::ONE]);
+        assert_eq!(
+            to(hooks.after("orders", &action())),
+            [FieldElement::from(3u64)]
+        );
+        assert_eq!(to(hooks.after("deposits", &action())), [FieldElement::TWO]);
+    }
+}
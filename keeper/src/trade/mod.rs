@@ -1,5 +1,23 @@
+pub mod batch;
+pub mod callback;
+pub mod caps;
+pub mod congestion;
+pub mod crash;
 pub mod deposit;
+pub mod expiry;
+pub mod hooks;
+pub mod oracle;
 pub mod order;
+pub mod policy;
 pub mod price;
+pub mod queue;
+pub mod receipt;
+pub mod requeue;
+pub mod revert;
+pub mod schedule;
+pub mod slippage;
+pub mod strategy;
+pub mod throttle;
 pub mod utils;
+pub mod watchlist;
 pub mod withdrawal;
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use starknet::{
+    accounts::{ConnectedAccount, SingleOwnerAccount},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider,
+    },
+    signers::LocalWallet,
+};
+
+use crate::{config, error::KeeperError, types::SatoruAction};
+
+// A struct representing the oracle block numbers the prices of an execution are reported at.
+// @min: The smallest block number, never before the action got created or updated.
+// @max: The largest block number, never after the chain head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OracleBlockWindow {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl OracleBlockWindow {
+    // Returns the compacted block numbers for prices of `tokens_count` tokens, one block number
+    // per token since block numbers take the whole 64 bits of a compacted slot.
+    pub fn compacted(&self, tokens_count: usize) -> (Vec<u64>, Vec<u64>) {
+        (vec![self.min; tokens_count], vec![self.max; tokens_count])
+    }
+}
+
+// Computes the oracle block window of an action, the oracle rejecting prices older than the
+// action (ORACLE_BLOCK_NUMBERS_ARE_SMALLER_THAN_REQUIRED), from a block after the chain head, or
+// spanning more than `max_block_range` blocks.
+// @updated_at_block: The block the action got created or last updated at.
+// @head: The current chain head block number.
+// @max_block_range: The largest allowed difference between the max and min block numbers.
+pub fn get_oracle_block_window(
+    updated_at_block: u64,
+    head: u64,
+    max_block_range: u64,
+) -> Result<OracleBlockWindow, KeeperError> {
+    if updated_at_block > head {
+        return Err(KeeperError::ExecutionError(format!(
+            "action updated at block {} after the chain head {}",
+            updated_at_block, head
+        )));
+    }
+    Ok(OracleBlockWindow {
+        min: updated_at_block.max(head.saturating_sub(max_block_range)),
+        max: head,
+    })
+}
+
+// Computes the oracle block window of an action from the current chain head.
+// @account: The keeper account, used to read the chain head.
+// @action: The action to execute.
+pub async fn fetch_oracle_block_window(
+    account: &Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+    action: &SatoruAction,
+) -> Result<OracleBlockWindow, KeeperError> {
+    let head =
+        account.provider().block_number().await.map_err(|e| {
+            KeeperError::ExecutionError(format!("could not get chain head: {:?}", e))
+        })?;
+    // Actions never updated report 0, their creation block is used instead.
+    let updated_at_block = match action.updated_at_block {
+        0 => action.block_number,
+        updated_at_block => updated_at_block,
+    };
+    get_oracle_block_window(updated_at_block, head, config::get_oracle_max_block_range())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_block_window() {
+        assert_eq!(
+            get_oracle_block_window(95, 100, 10).unwrap(),
+            OracleBlockWindow { min: 95, max: 100 }
+        );
+        assert_eq!(
+            get_oracle_block_window(80, 100, 10).unwrap(),
+            OracleBlockWindow { min: 90, max: 100 }
+        );
+        assert_eq!(
+            get_oracle_block_window(90, 100, 10).unwrap(),
+            OracleBlockWindow { min: 90, max: 100 }
+        );
+    }
+
+    #[test]
+    fn test_oracle_block_window_boundaries() {
+        assert_eq!(
+            get_oracle_block_window(100, 100, 10).unwrap(),
+            OracleBlockWindow { min: 100, max: 100 }
+        );
+        assert_eq!(
+            get_oracle_block_window(0, 5, 10).unwrap(),
+            OracleBlockWindow { min: 0, max: 5 }
+        );
+        assert_eq!(
+            get_oracle_block_window(50, 100, 0).unwrap(),
+            OracleBlockWindow { min: 100, max: 100 }
+        );
+        assert!(get_oracle_block_window(101, 100, 10).is_err());
+    }
+
+    #[test]
+    fn test_compacted_block_numbers() {
+        let window = OracleBlockWindow { min: 90, max: 100 };
+        assert_eq!(window.compacted(2), (vec![90, 90], vec![100, 100]));
+    }
+}
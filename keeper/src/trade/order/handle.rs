@@ -1,17 +1,18 @@
-use std::{env, sync::Arc, vec};
+use std::{sync::Arc, vec};
 
-use cainome::{
-    cairo_serde::{ContractAddress, U256},
-    rs::abigen,
-};
-use starknet::{
-    accounts::{Account, Call, SingleOwnerAccount},
-    core::types::FieldElement,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
-    signers::LocalWallet,
-};
+use cainome::rs::abigen;
+use starknet::{accounts::Call, core::types::FieldElement};
 
-use crate::{trade::utils::get_set_primary_price_call, types::SatoruAction};
+use crate::{
+    contracts::{Contracts, SetPricesTemplate},
+    error::KeeperError,
+    trade::{
+        oracle::{fetch_oracle_block_window, OracleBlockWindow},
+        order::version::{get_v1_execute_order_call, OrderHandlerVersion},
+        utils::{get_priced_template, get_set_primary_price_call},
+    },
+    types::SatoruAction,
+};
 
 abigen!(
     OrderHandler,
@@ -38,73 +39,78 @@ abigen!(
     }
 );
 
-pub async fn handle_order(
-    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+// Builds the multicall executing a order, setting the oracle price first, surrounded by the
+// calls of the execution hooks.
+pub async fn get_order_calls(
+    contracts: &Contracts,
     order: SatoruAction,
-) {
-    let set_price_call = get_set_primary_price_call(order.clone(), account.clone()).await;
-
-    let execute_order_call = get_execute_order_call(order, account.clone());
-
-    let _order_execution_multicall = account
-        .execute(vec![set_price_call, execute_order_call])
-        .send()
-        .await
-        .expect("Order execution multicall failed");
-    // TODO: poll transaction status
+) -> Result<Vec<Call>, KeeperError> {
+    let template = get_priced_template(&order, contracts).await?;
+    get_order_calls_with_prices(contracts, order, &template).await
 }
 
-fn get_execute_order_call(
+// Builds the multicall executing a order with the given SetPricesParams fields rather than the
+// configured ones of its market, e.g. to benchmark other oracle compositions.
+// @template: The constant SetPricesParams fields sent with the execution.
+pub async fn get_order_calls_with_prices(
+    contracts: &Contracts,
     order: SatoruAction,
-    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
-) -> Call {
-    let order_handler_address =
-        env::var("ORDER_HANDLER").expect("ORDER_HANDLER env variable not set");
-    let order_handler = OrderHandler::new(
-        FieldElement::from_hex_be(&order_handler_address)
-            .expect("Conversion error: order_handler_address"),
-        account.clone(),
-    );
+    template: &SetPricesTemplate,
+) -> Result<Vec<Call>, KeeperError> {
+    let set_price_call = get_set_primary_price_call(&order, contracts).await?;
+
+    let oracle_block_window = fetch_oracle_block_window(&contracts.account, &order).await?;
+    let execute_order_call =
+        get_execute_order_call(&order, contracts, template, oracle_block_window);
 
-    let set_prices_params: SetPricesParams = SetPricesParams {
-        signer_info: U256 { low: 1, high: 0 },
-        tokens: vec![
-            ContractAddress::from(
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ),
-            ContractAddress::from(
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ),
-        ],
-        compacted_min_oracle_block_numbers: vec![63970, 63970],
-        compacted_max_oracle_block_numbers: vec![64901, 64901],
-        compacted_oracle_timestamps: vec![171119803, 10],
-        compacted_decimals: vec![U256 { low: 1, high: 0 }, U256 { low: 1, high: 0 }],
-        compacted_min_prices: vec![U256 {
-            low: 2147483648010000,
-            high: 0,
-        }],
-        compacted_min_prices_indexes: vec![U256 { low: 0, high: 0 }],
-        compacted_max_prices: vec![U256 {
-            low: 2147483648010000,
-            high: 0,
-        }],
-        compacted_max_prices_indexes: vec![U256 { low: 0, high: 0 }],
-        signatures: vec![
-            vec![
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ],
-            vec![
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ],
-        ],
+    Ok(contracts
+        .hooks
+        .compose("orders", &order, vec![set_price_call, execute_order_call]))
+}
+
+// Completes the constant SetPricesParams fields with the oracle block numbers of an action.
+// @template: The constant fields.
+// @oracle_block_window: The oracle block window of the action.
+fn to_set_prices_params(
+    template: &SetPricesTemplate,
+    oracle_block_window: OracleBlockWindow,
+) -> SetPricesParams {
+    let (compacted_min_oracle_block_numbers, compacted_max_oracle_block_numbers) =
+        oracle_block_window.compacted(template.tokens.len());
+    SetPricesParams {
+        signer_info: template.signer_info,
+        tokens: template.tokens.clone(),
+        compacted_min_oracle_block_numbers,
+        compacted_max_oracle_block_numbers,
+        compacted_oracle_timestamps: template.compacted_oracle_timestamps.clone(),
+        compacted_decimals: template.compacted_decimals.clone(),
+        compacted_min_prices: template.compacted_min_prices.clone(),
+        compacted_min_prices_indexes: template.compacted_min_prices_indexes.clone(),
+        compacted_max_prices: template.compacted_max_prices.clone(),
+        compacted_max_prices_indexes: template.compacted_max_prices_indexes.clone(),
+        signatures: template.signatures.clone(),
         price_feed_tokens: vec![],
-    };
+    }
+}
 
-    order_handler.execute_order_getcall(
-        &FieldElement::from_hex_be(&order.key).expect("Cannot convert string to felt"),
-        &set_prices_params,
-    )
+// Builds the execute_order call of the handler of the order market, with the calldata of its ABI
+// version.
+fn get_execute_order_call(
+    order: &SatoruAction,
+    contracts: &Contracts,
+    template: &SetPricesTemplate,
+    oracle_block_window: OracleBlockWindow,
+) -> Call {
+    let key = FieldElement::from_hex_be(&order.key).expect("Cannot convert string to felt");
+    let params = to_set_prices_params(template, oracle_block_window);
+    match contracts.order_handler_for(&order.market) {
+        (address, OrderHandlerVersion::V1) => get_v1_execute_order_call(address, key, &params),
+        (address, OrderHandlerVersion::V2) if address == contracts.order_handler.address => {
+            contracts.order_handler.execute_order_getcall(&key, &params)
+        }
+        (address, OrderHandlerVersion::V2) => {
+            OrderHandler::new(address, Arc::clone(&contracts.account))
+                .execute_order_getcall(&key, &params)
+        }
+    }
 }
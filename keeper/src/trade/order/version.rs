@@ -0,0 +1,197 @@
+use cainome::cairo_serde::U256;
+use starknet::{
+    accounts::Call,
+    core::{types::FieldElement, utils::get_selector_from_name},
+};
+
+use crate::{config, error::KeeperError, trade::order::handle::SetPricesParams};
+
+// The OrderHandler ABI versions the keeper builds execute_order calls for, so it keeps executing
+// against the old handler while the new one gets rolled out.
+// V1: the handlers released before price feed tokens, their SetPricesParams ending with the
+// signatures.
+// V2: the bundled OrderHandler ABI, its SetPricesParams ending with the price feed tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderHandlerVersion {
+    V1,
+    V2,
+}
+
+impl OrderHandlerVersion {
+    // @version: The configured version, e.g. 2 for the bundled ABI.
+    pub fn from_config(version: u32) -> Result<Self, KeeperError> {
+        match version {
+            1 => Ok(OrderHandlerVersion::V1),
+            2 => Ok(OrderHandlerVersion::V2),
+            _ => Err(KeeperError::ContractDiscoveryError(format!(
+                "unknown OrderHandler version {}, expected 1 or 2",
+                version
+            ))),
+        }
+    }
+}
+
+// Serializes the calldata of execute_order for the V1 ABI, which the bundled ABI cannot encode.
+// @key: The key of the order.
+// @params: The SetPricesParams of the execution, its price feed tokens being left out.
+pub fn get_v1_execute_order_calldata(
+    key: FieldElement,
+    params: &SetPricesParams,
+) -> Vec<FieldElement> {
+    let mut calldata = vec![key];
+    let u64s = |calldata: &mut Vec<FieldElement>, values: &[u64]| {
+        calldata.push(values.len().into());
+        calldata.extend(values.iter().map(|value| FieldElement::from(*value)));
+    };
+    let u256s = |calldata: &mut Vec<FieldElement>, values: &[U256]| {
+        calldata.push(values.len().into());
+        for value in values {
+            push_u256(calldata, value);
+        }
+    };
+    push_u256(&mut calldata, &params.signer_info);
+    calldata.push(params.tokens.len().into());
+    calldata.extend(params.tokens.iter().map(|token| FieldElement::from(*token)));
+    u64s(&mut calldata, &params.compacted_min_oracle_block_numbers);
+    u64s(&mut calldata, &params.compacted_max_oracle_block_numbers);
+    u64s(&mut calldata, &params.compacted_oracle_timestamps);
+    u256s(&mut calldata, &params.compacted_decimals);
+    u256s(&mut calldata, &params.compacted_min_prices);
+    u256s(&mut calldata, &params.compacted_min_prices_indexes);
+    u256s(&mut calldata, &params.compacted_max_prices);
+    u256s(&mut calldata, &params.compacted_max_prices_indexes);
+    calldata.push(params.signatures.len().into());
+    for signature in &params.signatures {
+        calldata.push(signature.len().into());
+        calldata.extend(signature.iter().copied());
+    }
+    calldata
+}
+
+fn push_u256(calldata: &mut Vec<FieldElement>, value: &U256) {
+    calldata.push(value.low.into());
+    calldata.push(value.high.into());
+}
+
+// Builds the execute_order call of a V1 handler.
+// @address: The address of the handler.
+// @key: The key of the order.
+// @params: The SetPricesParams of the execution.
+pub fn get_v1_execute_order_call(
+    address: FieldElement,
+    key: FieldElement,
+    params: &SetPricesParams,
+) -> Call {
+    Call {
+        to: address,
+        selector: get_selector_from_name("execute_order").expect("Invalid method name"),
+        calldata: get_v1_execute_order_calldata(key, params),
+    }
+}
+
+// A struct representing the handler orders of some markets get executed against during a staged
+// upgrade, the other markets staying on ORDER_HANDLER.
+// @address: The address of the handler.
+// @version: The ABI version of the handler.
+// @markets: The markets moved to the handler, every market when empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NextOrderHandler {
+    pub address: FieldElement,
+    pub version: OrderHandlerVersion,
+    pub markets: Vec<FieldElement>,
+}
+
+impl NextOrderHandler {
+    // None when NEXT_ORDER_HANDLER is unset, every order then getting executed on ORDER_HANDLER.
+    pub fn from_env() -> Result<Option<Self>, KeeperError> {
+        let address = match config::get_next_order_handler() {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        let invalid = |value: &str| {
+            KeeperError::ContractDiscoveryError(format!("invalid next order handler {}", value))
+        };
+        Ok(Some(NextOrderHandler {
+            address: FieldElement::from_hex_be(&address).map_err(|_| invalid(&address))?,
+            version: OrderHandlerVersion::from_config(config::get_next_order_handler_version())?,
+            markets: config::get_next_order_handler_markets()
+                .iter()
+                .map(|market| FieldElement::from_hex_be(market).map_err(|_| invalid(market)))
+                .collect::<Result<Vec<_>, _>>()?,
+        }))
+    }
+
+    // Returns whether the orders of a market get executed against the handler.
+    // @market: The market of the order, as stored by the indexer.
+    pub fn handles(&self, market: &str) -> bool {
+        self.markets.is_empty()
+            || FieldElement::from_hex_be(market)
+                .map(|market| self.markets.contains(&market))
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cainome::cairo_serde::ContractAddress;
+
+    fn felt(value: u64) -> FieldElement {
+        FieldElement::from(value)
+    }
+
+    #[test]
+    fn test_v1_execute_order_calldata() {
+        let u256 = |low| U256 { low, high: 0 };
+        let params = SetPricesParams {
+            signer_info: u256(1),
+            tokens: vec![ContractAddress::from(felt(0xe7))],
+            compacted_min_oracle_block_numbers: vec![10],
+            compacted_max_oracle_block_numbers: vec![12],
+            compacted_oracle_timestamps: vec![171119803],
+            compacted_decimals: vec![u256(8)],
+            compacted_min_prices: vec![u256(2000)],
+            compacted_min_prices_indexes: vec![u256(0)],
+            compacted_max_prices: vec![u256(2001)],
+            compacted_max_prices_indexes: vec![u256(0)],
+            signatures: vec![vec![felt(0xa), felt(0xb)]],
+            price_feed_tokens: vec![ContractAddress::from(felt(0xf))],
+        };
+        let calldata: Vec<FieldElement> = [
+            0x42, 1, 0, 1, 0xe7, 1, 10, 1, 12, 1, 171119803, 1, 8, 0, 1, 2000, 0, 1, 0, 0, 1, 2001,
+            0, 1, 0, 0, 1, 2, 0xa, 0xb,
+        ]
+        .into_iter()
+        .map(felt)
+        .collect();
+        assert_eq!(get_v1_execute_order_calldata(felt(0x42), &params), calldata);
+        let call = get_v1_execute_order_call(felt(0x12), felt(0x42), &params);
+        assert_eq!(call.to, felt(0x12));
+        assert_eq!(
+            call.selector,
+            get_selector_from_name("execute_order").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_next_order_handler() {
+        assert_eq!(
+            OrderHandlerVersion::from_config(1).unwrap(),
+            OrderHandlerVersion::V1
+        );
+        assert!(OrderHandlerVersion::from_config(3).is_err());
+        let next = NextOrderHandler {
+            address: felt(0x12),
+            version: OrderHandlerVersion::V2,
+            markets: vec![felt(0x1a)],
+        };
+        assert!(next.handles("0x1a"));
+        assert!(next.handles("0x001a"));
+        assert!(!next.handles("0x1b"));
+        assert!(NextOrderHandler {
+            markets: vec![],
+            ..next
+        }
+        .handles("0x1b"));
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+
+use starknet::core::types::FieldElement;
+
+use crate::{config, types::SatoruAction};
+
+// Swap orders carry no position size, their collateral amount is compared to the minimum size instead.
+const SWAP_ORDER_TYPES: [&str; 2] = ["MarketSwap", "LimitSwap"];
+
+// An enum representing whether the keeper goes for an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyDecision {
+    Execute,
+    // The action is left to other keepers, with the reason why.
+    Skip(String),
+    // The action cannot execute yet, e.g. above a market cap that may free up, with the reason why.
+    Defer(String),
+}
+
+// A struct representing the execution policies set by the operator, evaluated on the action
+// alone so skipped actions never cost a price fetch.
+// @disabled_order_types: The order types never executed, e.g. LimitSwap.
+// @min_order_sizes: The minimum size of the orders executed, per order type.
+// @market_allowlist: The only markets actions get executed on, every market when None.
+// @liquidity_market_allowlist: The only markets deposits and withdrawals get executed on, on top
+// of the market allowlist, every market when None.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionPolicies {
+    pub disabled_order_types: HashSet<String>,
+    pub min_order_sizes: HashMap<String, u128>,
+    pub market_allowlist: Option<HashSet<FieldElement>>,
+    pub liquidity_market_allowlist: Option<HashSet<FieldElement>>,
+}
+
+// Parses allowlisted markets, panicking on invalid addresses.
+fn to_market_allowlist(markets: Vec<String>) -> HashSet<FieldElement> {
+    markets
+        .iter()
+        .map(|market| {
+            FieldElement::from_hex_be(market)
+                .unwrap_or_else(|_| panic!("Invalid allowlisted market {}", market))
+        })
+        .collect()
+}
+
+// Returns whether a market is allowlisted, every market being when there is no allowlist.
+fn is_allowlisted(allowlist: &Option<HashSet<FieldElement>>, market: &str) -> bool {
+    // Markets are compared as felts, the indexer stores them without 0x prefix nor trimmed zeros.
+    match allowlist {
+        Some(allowlist) => FieldElement::from_hex_be(market)
+            .map(|market| allowlist.contains(&market))
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+impl ExecutionPolicies {
+    pub fn from_env() -> Self {
+        ExecutionPolicies {
+            disabled_order_types: config::get_disabled_order_types().into_iter().collect(),
+            min_order_sizes: config::get_min_order_sizes().into_iter().collect(),
+            market_allowlist: config::get_market_allowlist().map(to_market_allowlist),
+            liquidity_market_allowlist: config::get_liquidity_market_allowlist()
+                .map(to_market_allowlist),
+        }
+    }
+
+    // Decides whether the keeper executes an action.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub fn evaluate(&self, table: &str, action: &SatoruAction) -> PolicyDecision {
+        self.evaluate_sized(table, action, true)
+    }
+
+    // Decides whether the keeper executes an action, the min order sizes being left out when
+    // check_min_sizes is false, e.g. for liquidations during a flash crash.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    // @check_min_sizes: Whether the min order sizes get checked.
+    pub fn evaluate_sized(
+        &self,
+        table: &str,
+        action: &SatoruAction,
+        check_min_sizes: bool,
+    ) -> PolicyDecision {
+        if !is_allowlisted(&self.market_allowlist, &action.market) {
+            return PolicyDecision::Skip(format!("market {} not allowlisted", action.market));
+        }
+
+        if table != "orders" {
+            // Unclaimed, the deposits and withdrawals of other markets are left to their keepers.
+            if !is_allowlisted(&self.liquidity_market_allowlist, &action.market) {
+                return PolicyDecision::Skip(format!(
+                    "market {} not allowlisted for liquidity",
+                    action.market
+                ));
+            }
+            return PolicyDecision::Execute;
+        }
+        let order_type = match &action.order_type {
+            Some(order_type) => order_type,
+            None => return PolicyDecision::Execute,
+        };
+        if self.disabled_order_types.contains(order_type) {
+            return PolicyDecision::Skip(format!("order type {} disabled", order_type));
+        }
+        if let Some(min_size) = self
+            .min_order_sizes
+            .get(order_type)
+            .filter(|_| check_min_sizes)
+        {
+            let size = if SWAP_ORDER_TYPES.contains(&order_type.as_str()) {
+                action.initial_collateral_delta_amount
+            } else {
+                action.size_delta_usd
+            };
+            if size.unwrap_or(0) < *min_size {
+                return PolicyDecision::Skip(format!(
+                    "{} order below the minimum size {}",
+                    order_type, min_size
+                ));
+            }
+        }
+        PolicyDecision::Execute
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_type: &str, size_delta_usd: u128, market: &str) -> SatoruAction {
+        SatoruAction {
+            order_type: Some(order_type.to_owned()),
+            size_delta_usd: Some(size_delta_usd),
+            initial_collateral_delta_amount: Some(0),
+            market: market.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluate_order_policies() {
+        let policies = ExecutionPolicies {
+            disabled_order_types: HashSet::from(["LimitSwap".to_owned()]),
+            min_order_sizes: HashMap::from([("MarketIncrease".to_owned(), 100)]),
+            market_allowlist: Some(HashSet::from([FieldElement::from_hex_be("0x12").unwrap()])),
+            liquidity_market_allowlist: None,
+        };
+        let market = format!("{:0>64}", "12");
+
+        assert_eq!(
+            policies.evaluate("orders", &order("Liquidation", 0, &market)),
+            PolicyDecision::Execute
+        );
+        assert_eq!(
+            policies.evaluate("orders", &order("MarketIncrease", 150, &market)),
+            PolicyDecision::Execute
+        );
+        assert!(matches!(
+            policies.evaluate("orders", &order("MarketIncrease", 50, &market)),
+            PolicyDecision::Skip(_)
+        ));
+        assert!(matches!(
+            policies.evaluate("orders", &order("LimitSwap", 150, &market)),
+            PolicyDecision::Skip(_)
+        ));
+        assert!(matches!(
+            policies.evaluate("orders", &order("Liquidation", 0, "13")),
+            PolicyDecision::Skip(_)
+        ));
+        assert_eq!(
+            policies.evaluate_sized("orders", &order("MarketIncrease", 50, &market), false),
+            PolicyDecision::Execute
+        );
+    }
+
+    #[test]
+    fn test_evaluate_liquidity_market_allowlist() {
+        let policies = ExecutionPolicies {
+            liquidity_market_allowlist: Some(HashSet::from([
+                FieldElement::from_hex_be("0x12").unwrap()
+            ])),
+            ..Default::default()
+        };
+        let action = |market: &str| SatoruAction {
+            market: market.to_owned(),
+            ..Default::default()
+        };
+        let market = format!("{:0>64}", "12");
+
+        assert_eq!(
+            policies.evaluate("deposits", &action(&market)),
+            PolicyDecision::Execute
+        );
+        assert!(matches!(
+            policies.evaluate("withdrawals", &action("13")),
+            PolicyDecision::Skip(_)
+        ));
+        // Orders are left to the market allowlist.
+        assert_eq!(
+            policies.evaluate("orders", &order("MarketIncrease", 0, "13")),
+            PolicyDecision::Execute
+        );
+    }
+
+    #[test]
+    fn test_evaluate_without_policies() {
+        let policies = ExecutionPolicies::default();
+        assert_eq!(
+            policies.evaluate("deposits", &SatoruAction::default()),
+            PolicyDecision::Execute
+        );
+        assert_eq!(
+            policies.evaluate("orders", &order("LimitSwap", 0, "12")),
+            PolicyDecision::Execute
+        );
+    }
+}
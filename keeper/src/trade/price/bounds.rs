@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use log::warn;
+
+use super::utils::PriceInfo;
+use crate::{config, error::KeeperError};
+
+// A struct holding hard price bounds per token, so a compromised or glitched feed never gets
+// absurd prices pushed on chain by the keeper.
+// @bounds: The minimum and maximum USD prices per token, tokens being named as on the feed.
+#[derive(Debug, Clone, Default)]
+pub struct PriceBounds {
+    pub bounds: HashMap<String, (f64, f64)>,
+}
+
+impl PriceBounds {
+    pub fn from_env() -> Self {
+        PriceBounds {
+            bounds: config::get_price_bounds()
+                .into_iter()
+                .map(|(token, min, max)| (token, (min, max)))
+                .collect(),
+        }
+    }
+
+    // Checks a fetched price lies within the bounds of its token, tokens without bounds always
+    // pass.
+    // @price_info: The price returned by the feed.
+    pub fn check(&self, price_info: &PriceInfo) -> Result<(), KeeperError> {
+        let token = price_info
+            .pair_id
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let (min, max) = match self.bounds.get(&token) {
+            Some(bounds) => *bounds,
+            None => return Ok(()),
+        };
+        let price = to_decimal_price(price_info)?;
+        if price < min || price > max {
+            warn!(
+                "ALERT: {} price {} out of its [{}, {}] bounds",
+                price_info.pair_id, price, min, max
+            );
+            return Err(KeeperError::PriceOutOfBounds(format!(
+                "{} price {} outside [{}, {}]",
+                price_info.pair_id, price, min, max
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Converts a feed price to its decimal value.
+fn to_decimal_price(price_info: &PriceInfo) -> Result<f64, KeeperError> {
+    let price =
+        u128::from_str_radix(price_info.price.trim_start_matches("0x"), 16).map_err(|_| {
+            KeeperError::PriceOutOfBounds(format!("invalid {} price", price_info.pair_id))
+        })?;
+    Ok(price as f64 / 10f64.powi(price_info.decimals as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_info(pair_id: &str, price: &str, decimals: u64) -> PriceInfo {
+        PriceInfo {
+            decimals,
+            num_sources_aggregated: 1,
+            pair_id: pair_id.to_owned(),
+            price: price.to_owned(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_price_bounds() {
+        let bounds = PriceBounds {
+            bounds: HashMap::from([("usdc".to_owned(), (0.95, 1.05))]),
+        };
+        // 0x5f5e100 is 1.00000000 with 8 decimals.
+        assert!(bounds
+            .check(&price_info("USDC/USD", "0x5f5e100", 8))
+            .is_ok());
+        // 0x2faf080 is 0.5 with 8 decimals.
+        assert!(bounds
+            .check(&price_info("USDC/USD", "0x2faf080", 8))
+            .is_err());
+        assert!(bounds
+            .check(&price_info("USDC/USD", "0x5f5e100", 6))
+            .is_err());
+        assert!(bounds.check(&price_info("ETH/USD", "0x1", 8)).is_ok());
+    }
+}
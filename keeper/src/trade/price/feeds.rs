@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use cainome::cairo_serde::ContractAddress;
+use starknet::core::types::FieldElement;
+
+use super::tokens::TokenRegistry;
+use crate::{config, error::KeeperError};
+
+// Number of hex digits of a Pyth price id, a 32 bytes hash which may not fit a felt.
+#[cfg(feature = "pyth")]
+const PYTH_PRICE_ID_LENGTH: usize = 64;
+
+// An enum representing the oracle feed a token price is read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedId {
+    // A Pragma pair, e.g. pragma:ETH/USD, named lowercase as on the Pragma API.
+    Pragma {
+        base: String,
+        quote: String,
+    },
+    // A Pyth price id, e.g. pyth:0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace.
+    #[cfg(feature = "pyth")]
+    Pyth {
+        price_id: String,
+    },
+}
+
+impl FeedId {
+    // Parses a feed identifier formatted as source:id, Pyth feeds only with the pyth feature.
+    // @feed: The feed identifier.
+    pub fn parse(feed: &str) -> Option<Self> {
+        let (source, id) = feed.trim().split_once(':')?;
+        match source.trim().to_lowercase().as_str() {
+            "pragma" => {
+                let (base, quote) = id.trim().split_once('/')?;
+                match base.trim().is_empty() || quote.trim().is_empty() {
+                    true => None,
+                    false => Some(FeedId::Pragma {
+                        base: base.trim().to_lowercase(),
+                        quote: quote.trim().to_lowercase(),
+                    }),
+                }
+            }
+            #[cfg(feature = "pyth")]
+            "pyth" => {
+                let price_id = id.trim().trim_start_matches("0x").to_lowercase();
+                match price_id.len() == PYTH_PRICE_ID_LENGTH
+                    && price_id.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    true => Some(FeedId::Pyth {
+                        price_id: format!("0x{}", price_id),
+                    }),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+// A struct representing a market token and the feed its price is read from.
+// @token: The token address.
+// @feed: The feed of its price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenFeed {
+    pub token: FieldElement,
+    pub feed: FeedId,
+}
+
+// A struct representing the feeds of the tokens of a market.
+// @index: The index token, priced by the market.
+// @long: The long collateral token.
+// @short: The short collateral token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketFeed {
+    pub index: TokenFeed,
+    pub long: TokenFeed,
+    pub short: TokenFeed,
+}
+
+impl MarketFeed {
+    // Returns the market tokens prices get sent for, without duplicates, e.g. the index token of
+    // ETH/USD[ETH-USDC] also being its long token, in index, long and short order.
+    pub fn tokens(&self) -> Vec<ContractAddress> {
+        let mut tokens: Vec<ContractAddress> = Vec::new();
+        for token in [self.index.token, self.long.token, self.short.token] {
+            let token = ContractAddress::from(token);
+            if !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+}
+
+// Parses a market feed entry formatted as market@index@long@short, each token being given as
+// address=feed, e.g. 0x12@0x49d3=pragma:ETH/USD@0x49d3=pragma:ETH/USD@0x53c9=pragma:USDC/USD.
+// Returns the market and its feeds.
+pub fn parse_market_feed(entry: &str) -> Result<(FieldElement, MarketFeed), KeeperError> {
+    let invalid = |reason: &str| {
+        KeeperError::FeedError(format!("invalid market feed entry {}: {}", entry, reason))
+    };
+    let (market, index, long, short) = match entry.split('@').collect::<Vec<_>>()[..] {
+        [market, index, long, short] => (market, index, long, short),
+        _ => return Err(invalid("expected market@index@long@short")),
+    };
+    let market =
+        FieldElement::from_hex_be(market.trim()).map_err(|_| invalid("invalid market address"))?;
+    let token_feed = |side: &str, token_feed: &str| {
+        let (token, feed) = token_feed
+            .split_once('=')
+            .ok_or_else(|| invalid(&format!("expected address=feed for the {} token", side)))?;
+        Ok(TokenFeed {
+            token: FieldElement::from_hex_be(token.trim())
+                .map_err(|_| invalid(&format!("invalid {} token address", side)))?,
+            feed: FeedId::parse(feed)
+                .ok_or_else(|| invalid(&format!("invalid {} token feed {}", side, feed)))?,
+        })
+    };
+    Ok((
+        market,
+        MarketFeed {
+            index: token_feed("index", index)?,
+            long: token_feed("long", long)?,
+            short: token_feed("short", short)?,
+        },
+    ))
+}
+
+// A struct holding the oracle feeds of the tokens of each market, so prices of markets listing
+// tokens besides ETH and USDC get read from the right feed and sent for the right tokens.
+// @markets: The feeds per market, markets without feeds using the keeper defaults.
+#[derive(Debug, Clone, Default)]
+pub struct MarketFeeds {
+    pub markets: HashMap<FieldElement, MarketFeed>,
+}
+
+impl MarketFeeds {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        let mut markets = HashMap::new();
+        for entry in config::get_market_feeds() {
+            let (market, feed) = parse_market_feed(&entry)?;
+            if markets.insert(market, feed).is_some() {
+                return Err(KeeperError::FeedError(format!(
+                    "market {:#x} mapped twice",
+                    market
+                )));
+            }
+        }
+        Ok(MarketFeeds { markets })
+    }
+
+    // Checks every mapped token is known to the token registry, the oracle getting wrong prices
+    // for tokens sent with the default decimal.
+    // @token_registry: The tokens the keeper reports prices for.
+    pub fn check_tokens(&self, token_registry: &TokenRegistry) -> Result<(), KeeperError> {
+        for (market, feed) in &self.markets {
+            for token in feed.tokens() {
+                if token_registry.get(token).is_none() {
+                    return Err(KeeperError::FeedError(format!(
+                        "token {:#x} of market {:#x} missing from TOKENS",
+                        FieldElement::from(token),
+                        market
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the feeds of a market, if mapped.
+    // @market: The market of the action, as stored by the indexer.
+    pub fn get(&self, market: &str) -> Option<&MarketFeed> {
+        // Markets are compared as felts, the indexer stores them without 0x prefix nor trimmed zeros.
+        self.markets.get(&FieldElement::from_hex_be(market).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "pyth")]
+    const PYTH_ETH_USD: &str = "0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+
+    #[test]
+    fn test_parse_feed_id() {
+        assert_eq!(
+            FeedId::parse("pragma:ETH/USD"),
+            Some(FeedId::Pragma {
+                base: "eth".to_owned(),
+                quote: "usd".to_owned(),
+            })
+        );
+        assert!(FeedId::parse("pragma:ETH").is_none());
+        assert!(FeedId::parse("chainlink:ETH/USD").is_none());
+    }
+
+    #[cfg(feature = "pyth")]
+    #[test]
+    fn test_parse_pyth_feed_id() {
+        assert_eq!(
+            FeedId::parse(&format!("pyth:{}", PYTH_ETH_USD)),
+            Some(FeedId::Pyth {
+                price_id: PYTH_ETH_USD.to_owned(),
+            })
+        );
+        assert!(FeedId::parse("pyth:0x12").is_none());
+    }
+
+    #[cfg(not(feature = "pyth"))]
+    #[test]
+    fn test_pyth_feeds_disabled() {
+        assert!(FeedId::parse(
+            "pyth:0xff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace"
+        )
+        .is_none());
+    }
+
+    #[cfg(feature = "pyth")]
+    #[test]
+    fn test_parse_market_feed() {
+        let (market, feed) = parse_market_feed(&format!(
+            "0x12@0x34=pyth:{}@0x34=pragma:ETH/USD@0x56=pragma:USDC/USD",
+            PYTH_ETH_USD
+        ))
+        .unwrap();
+        assert_eq!(market, FieldElement::from_hex_be("0x12").unwrap());
+        assert_eq!(
+            feed.tokens(),
+            vec![
+                ContractAddress::from(FieldElement::from_hex_be("0x34").unwrap()),
+                ContractAddress::from(FieldElement::from_hex_be("0x56").unwrap()),
+            ]
+        );
+        assert_eq!(
+            feed.short.feed,
+            FeedId::Pragma {
+                base: "usdc".to_owned(),
+                quote: "usd".to_owned(),
+            }
+        );
+        assert!(parse_market_feed("0x12@0x34=pragma:ETH/USD@0x56=pragma:USDC/USD").is_err());
+        assert!(parse_market_feed("0x12@0x34@0x34=pragma:ETH/USD@0x56=pragma:USDC/USD").is_err());
+        assert!(
+            parse_market_feed("0x12@0x34=pragma:ETH/USD@0x34=pragma:ETH/USD@0x56=pyth:0x1")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_market_feeds_get() {
+        let (market, feed) =
+            parse_market_feed("0x012@0x34=pragma:ETH/USD@0x34=pragma:ETH/USD@0x56=pragma:USDC/USD")
+                .unwrap();
+        let feeds = MarketFeeds {
+            markets: HashMap::from([(market, feed.clone())]),
+        };
+        assert_eq!(feeds.get("12"), Some(&feed));
+        assert!(feeds.get("0x13").is_none());
+        assert!(feeds.check_tokens(&TokenRegistry::default()).is_err());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod bounds;
 pub mod error;
+pub mod feeds;
+pub mod spread;
+pub mod stable;
+pub mod tokens;
 pub mod utils;
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use cainome::cairo_serde::{ContractAddress, U256};
+
+use super::tokens::TokenRegistry;
+use crate::{config, error::KeeperError, trade::utils::get_token_name_from_address};
+
+// Number of bits of a compacted price, the oracle packing 8 prices per uint256.
+const COMPACTED_PRICE_BIT_LENGTH: u32 = 32;
+const BPS_DIVISOR: u128 = 10_000;
+
+// A struct holding the spreads applied around the reported prices, the oracle using the min price
+// and the max price of a token depending on the side favouring the pool.
+// @default_bps: The spread applied to tokens without one, in basis points on each side.
+// @bps: The spreads per token, tokens being named as on the price feed.
+#[derive(Debug, Clone, Default)]
+pub struct PriceSpreads {
+    pub default_bps: u32,
+    pub bps: HashMap<String, u32>,
+}
+
+impl PriceSpreads {
+    pub fn from_env() -> Self {
+        PriceSpreads {
+            default_bps: config::get_default_price_spread_bps(),
+            bps: config::get_price_spreads_bps().into_iter().collect(),
+        }
+    }
+
+    // Returns the min and max prices of a token around its reported price.
+    // @token: The token name.
+    // @price: The reported price.
+    pub fn min_max(&self, token: &str, price: u128) -> (u128, u128) {
+        let bps = *self.bps.get(token).unwrap_or(&self.default_bps) as u128;
+        let spread = price * bps.min(BPS_DIVISOR) / BPS_DIVISOR;
+        (price - spread, price + spread)
+    }
+
+    // Returns the compacted min and max prices of the tokens, in the order of the tokens.
+    // @token_registry: The tokens named by their symbol, others by their default name.
+    // @tokens: The tokens the prices are reported for.
+    // @prices: The reported prices of the tokens.
+    pub fn compacted(
+        &self,
+        token_registry: &TokenRegistry,
+        tokens: &[ContractAddress],
+        prices: &[u128],
+    ) -> Result<(Vec<U256>, Vec<U256>), KeeperError> {
+        let (min_prices, max_prices): (Vec<u128>, Vec<u128>) = tokens
+            .iter()
+            .zip(prices)
+            .map(|(token, price)| match token_registry.get(*token) {
+                Some(info) => self.min_max(&info.symbol, *price),
+                None => self.min_max(&get_token_name_from_address(*token), *price),
+            })
+            .unzip();
+        Ok((compact_prices(&min_prices)?, compact_prices(&max_prices)?))
+    }
+}
+
+// Packs prices in slots of 32 bits, the first price taking the lowest bits.
+// @prices: The prices to pack, each fitting 32 bits.
+pub fn compact_prices(prices: &[u128]) -> Result<Vec<U256>, KeeperError> {
+    compact(prices, COMPACTED_PRICE_BIT_LENGTH)
+}
+
+// Packs values in uint256 slots of `bit_length` bits, the first value taking the lowest bits.
+// Values not fitting their slot are rejected rather than truncated into another price.
+// @values: The values to pack, each fitting `bit_length` bits.
+// @bit_length: The number of bits of a value, dividing 128.
+pub fn compact(values: &[u128], bit_length: u32) -> Result<Vec<U256>, KeeperError> {
+    let mask = (1u128 << bit_length) - 1;
+    if let Some(value) = values.iter().find(|value| **value > mask) {
+        return Err(KeeperError::ExecutionError(format!(
+            "{} does not fit a compacted slot of {} bits",
+            value, bit_length
+        )));
+    }
+    let per_half = (128 / bit_length) as usize;
+    Ok(values
+        .chunks(per_half * 2)
+        .map(|chunk| {
+            let mut slot = U256 { low: 0, high: 0 };
+            for (i, value) in chunk.iter().enumerate() {
+                match i < per_half {
+                    true => slot.low |= value << (i as u32 * bit_length),
+                    false => slot.high |= value << ((i - per_half) as u32 * bit_length),
+                }
+            }
+            slot
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_max() {
+        let spreads = PriceSpreads {
+            default_bps: 10,
+            bps: HashMap::from([("usdc".to_owned(), 0)]),
+        };
+        assert_eq!(spreads.min_max("eth", 500000), (499500, 500500));
+        assert_eq!(spreads.min_max("usdc", 10000), (10000, 10000));
+    }
+
+    #[test]
+    fn test_compact_prices() {
+        assert_eq!(
+            compact_prices(&[10000, 500000]).unwrap(),
+            vec![U256 {
+                low: 2147483648010000,
+                high: 0,
+            }]
+        );
+        let prices = compact_prices(&[1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[0].high, 5 | (6 << 32) | (7 << 64) | (8 << 96));
+        assert_eq!(prices[1], U256 { low: 9, high: 0 });
+        // Prices over 32 bits would spill into the next slot.
+        assert!(compact_prices(&[1, 1 << 32]).is_err());
+        assert!(compact(&[256], 8).is_err());
+    }
+}
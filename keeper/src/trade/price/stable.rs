@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use cainome::cairo_serde::{ContractAddress, U256};
+use log::warn;
+use starknet::core::{types::FieldElement, utils::cairo_short_string_to_felt};
+use starknet_crypto::poseidon_hash_many;
+
+use super::tokens::{to_oracle_price, TokenInfo};
+use crate::{config, contracts::Contracts, error::KeeperError};
+
+// Decimals of the fixed stable prices once converted to a feed price.
+const STABLE_PRICE_DECIMALS: u32 = 8;
+
+// Computes the DataStore key of the stable price of a token, as keys::stable_price_key does.
+// @token: The token address.
+pub fn stable_price_key(token: FieldElement) -> FieldElement {
+    poseidon_hash_many(&[
+        cairo_short_string_to_felt("STABLE_PRICE").expect("Invalid short string"),
+        token,
+    ])
+}
+
+// An enum representing where the price of a stable token comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StablePrice {
+    // A fixed USD price, e.g. 1.0.
+    Fixed(f64),
+    // The stable price the protocol configured in the DataStore.
+    DataStore,
+}
+
+impl StablePrice {
+    pub fn parse(price: &str) -> Option<Self> {
+        match price {
+            "datastore" => Some(StablePrice::DataStore),
+            price => price
+                .parse::<f64>()
+                .ok()
+                .filter(|price| *price > 0.0)
+                .map(StablePrice::Fixed),
+        }
+    }
+}
+
+// Converts a fixed USD price to the protocol price of a token.
+// @token: The token priced.
+// @price: The USD price of one token.
+pub fn to_protocol_price(token: &TokenInfo, price: f64) -> Result<U256, KeeperError> {
+    let feed_price = (price * 10f64.powi(STABLE_PRICE_DECIMALS as i32)).round() as u128;
+    to_oracle_price(&token.to_protocol_price(feed_price, STABLE_PRICE_DECIMALS)?)
+}
+
+// A struct holding the stablecoins priced without any feed, so USDC or USDT legs match the
+// protocol pricing and executions do not depend on a feed for them.
+// @prices: The stable prices per token, tokens being named as on the feed.
+#[derive(Debug, Clone, Default)]
+pub struct StablePrices {
+    pub prices: HashMap<String, StablePrice>,
+}
+
+impl StablePrices {
+    pub fn from_env() -> Self {
+        StablePrices {
+            prices: config::get_stable_prices()
+                .into_iter()
+                .map(|(token, price)| {
+                    let price = StablePrice::parse(&price).unwrap_or_else(|| {
+                        panic!(
+                            "Invalid stable price for {}, expected a USD price or datastore",
+                            token
+                        )
+                    });
+                    (token, price)
+                })
+                .collect(),
+        }
+    }
+}
+
+// Returns the protocol price of a stable token, None for other tokens and stable tokens whose
+// DataStore price is not set, which get their price from the feed.
+// @contracts: The keeper contracts, the DataStore stable prices are read from.
+// @token: The token priced.
+pub async fn get_stable_price(
+    contracts: &Contracts,
+    token: ContractAddress,
+) -> Result<Option<U256>, KeeperError> {
+    let token = match contracts.token_registry.get(token) {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    match contracts.stable_prices.prices.get(&token.symbol) {
+        Some(StablePrice::Fixed(price)) => Ok(Some(to_protocol_price(token, *price)?)),
+        Some(StablePrice::DataStore) => {
+            let price = contracts
+                .data_store
+                .get_u256(&stable_price_key(token.address))
+                .call()
+                .await
+                .map_err(|e| {
+                    KeeperError::ExecutionError(format!(
+                        "could not read stable price of {}: {:?}",
+                        token.symbol, e
+                    ))
+                })?;
+            if price == (U256 { low: 0, high: 0 }) {
+                warn!(
+                    "No stable price for {} in the DataStore, using the feed",
+                    token.symbol
+                );
+                return Ok(None);
+            }
+            Ok(Some(price))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stable_price() {
+        assert_eq!(StablePrice::parse("1"), Some(StablePrice::Fixed(1.0)));
+        assert_eq!(
+            StablePrice::parse("datastore"),
+            Some(StablePrice::DataStore)
+        );
+        assert!(StablePrice::parse("0").is_none());
+        assert!(StablePrice::parse("one").is_none());
+    }
+
+    #[test]
+    fn test_stable_protocol_price() {
+        let usdc = TokenInfo {
+            symbol: "usdc".to_owned(),
+            address: FieldElement::ONE,
+            oracle_decimals: 18,
+            token_decimals: 6,
+        };
+        // One USD per token unit with 6 decimals, at the protocol precision of 30 decimals.
+        assert_eq!(
+            to_protocol_price(&usdc, 1.0).unwrap(),
+            U256 {
+                low: 10u128.pow(24),
+                high: 0,
+            }
+        );
+        assert_eq!(
+            to_protocol_price(&usdc, 0.999).unwrap().low,
+            999 * 10u128.pow(21)
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use cainome::cairo_serde::{ContractAddress, U256};
+use satoru_client::amounts::{Price, USD_DECIMALS};
+use starknet::core::types::FieldElement;
+
+use super::spread::compact;
+use crate::{config, error::KeeperError};
+
+// Precision of the prices used by the protocol, a token price being the USD price of one unit of
+// the token with this many decimals minus the token decimals.
+const PRICE_PRECISION: u32 = USD_DECIMALS as u32;
+// Number of bits of a compacted decimal, the oracle packing 32 decimals per uint256.
+const COMPACTED_DECIMAL_BIT_LENGTH: u32 = 8;
+// Decimal reported for tokens missing from the registry.
+const DEFAULT_COMPACTED_DECIMAL: u128 = 1;
+
+// The tokens known without configuration, as symbol, address, oracle decimals and token decimals.
+const DEFAULT_TOKENS: [(&str, &str, u32, u32); 2] = [
+    (
+        "eth",
+        "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7",
+        8,
+        18,
+    ),
+    (
+        "usdc",
+        "0x053c91253bc9682c04929ca02ed00b3e423f6710d2ee7e0d5ebb06f3ecf368a8",
+        18,
+        6,
+    ),
+];
+
+// Converts a protocol price to the u256 the oracle gets, prices scaled from feed prices fitting
+// 128 bits.
+// @price: The protocol price.
+pub fn to_oracle_price(price: &Price) -> Result<U256, KeeperError> {
+    let low = price.to_u128().ok_or_else(|| {
+        KeeperError::ExecutionError(format!("{:?} is not a u128 protocol price", price))
+    })?;
+    Ok(U256 { low, high: 0 })
+}
+
+// A struct representing a token the keeper reports prices for.
+// @symbol: The token name on the price feed.
+// @address: The token contract address.
+// @oracle_decimals: The decimal of the compacted prices, the oracle multiplying them by 10^decimal.
+// @token_decimals: The decimals of the token amounts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub address: FieldElement,
+    pub oracle_decimals: u32,
+    pub token_decimals: u32,
+}
+
+impl TokenInfo {
+    // Scales a feed price to the protocol precision, the USD price of one token unit.
+    // @price: The raw feed price.
+    // @feed_decimals: The decimals of the feed price.
+    pub fn to_protocol_price(&self, price: u128, feed_decimals: u32) -> Result<Price, KeeperError> {
+        Ok(Price::from_u128(scale(
+            price,
+            feed_decimals,
+            PRICE_PRECISION - self.token_decimals,
+        )?))
+    }
+
+    // Scales a feed price to the compacted price the oracle multiplies by 10^oracle_decimals.
+    // @price: The raw feed price.
+    // @feed_decimals: The decimals of the feed price.
+    pub fn to_compacted_price(&self, price: u128, feed_decimals: u32) -> Result<u128, KeeperError> {
+        scale(
+            price,
+            feed_decimals + self.oracle_decimals,
+            PRICE_PRECISION - self.token_decimals,
+        )
+    }
+}
+
+// A struct holding the tokens the keeper reports prices for.
+// @tokens: The known tokens.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    pub tokens: Vec<TokenInfo>,
+}
+
+impl TokenRegistry {
+    // Loads the default tokens, overridden and extended by the configured ones.
+    pub fn from_env() -> Self {
+        let mut registry = TokenRegistry {
+            tokens: DEFAULT_TOKENS
+                .iter()
+                .map(
+                    |(symbol, address, oracle_decimals, token_decimals)| TokenInfo {
+                        symbol: symbol.to_string(),
+                        address: FieldElement::from_hex_be(address).expect("Invalid token address"),
+                        oracle_decimals: *oracle_decimals,
+                        token_decimals: *token_decimals,
+                    },
+                )
+                .collect(),
+        };
+        for (symbol, address, oracle_decimals, token_decimals) in config::get_tokens() {
+            let token = TokenInfo {
+                address: FieldElement::from_hex_be(&address)
+                    .unwrap_or_else(|_| panic!("Invalid address for token {}", symbol)),
+                symbol,
+                oracle_decimals,
+                token_decimals,
+            };
+            registry
+                .tokens
+                .retain(|known| known.address != token.address && known.symbol != token.symbol);
+            registry.tokens.push(token);
+        }
+        registry
+    }
+
+    pub fn get(&self, address: ContractAddress) -> Option<&TokenInfo> {
+        self.tokens
+            .iter()
+            .find(|token| ContractAddress::from(token.address) == address)
+    }
+
+    // Returns the compacted decimals of the tokens, in the order of the tokens.
+    // @tokens: The tokens the prices are reported for.
+    pub fn compacted_decimals(&self, tokens: &[ContractAddress]) -> Result<Vec<U256>, KeeperError> {
+        let decimals: Vec<u128> = tokens
+            .iter()
+            .map(|token| match self.get(*token) {
+                Some(token) => token.oracle_decimals as u128,
+                None => DEFAULT_COMPACTED_DECIMAL,
+            })
+            .collect();
+        compact(&decimals, COMPACTED_DECIMAL_BIT_LENGTH)
+    }
+}
+
+// Moves a value from `from` decimals to `to` decimals, truncating the dropped digits. Values not
+// fitting 128 bits once scaled are rejected.
+fn scale(value: u128, from: u32, to: u32) -> Result<u128, KeeperError> {
+    let overflow = || {
+        KeeperError::ExecutionError(format!(
+            "{} with {} decimals overflows once scaled to {} decimals",
+            value, from, to
+        ))
+    };
+    match to >= from {
+        true => 10u128
+            .checked_pow(to - from)
+            .and_then(|factor| value.checked_mul(factor))
+            .ok_or_else(overflow),
+        false => Ok(10u128
+            .checked_pow(from - to)
+            .map_or(0, |factor| value / factor)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TokenRegistry {
+        TokenRegistry {
+            tokens: DEFAULT_TOKENS
+                .iter()
+                .map(
+                    |(symbol, address, oracle_decimals, token_decimals)| TokenInfo {
+                        symbol: symbol.to_string(),
+                        address: FieldElement::from_hex_be(address).unwrap(),
+                        oracle_decimals: *oracle_decimals,
+                        token_decimals: *token_decimals,
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    fn token(registry: &TokenRegistry, symbol: &str) -> TokenInfo {
+        registry
+            .tokens
+            .iter()
+            .find(|token| token.symbol == symbol)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_volatile_token_prices() {
+        let eth = token(&registry(), "eth");
+        // 3000 USD with 8 feed decimals, for a token with 18 decimals.
+        let price = 3000 * 10u128.pow(8);
+        assert_eq!(
+            eth.to_protocol_price(price, 8).unwrap().to_u128().unwrap(),
+            3000 * 10u128.pow(12)
+        );
+        assert_eq!(
+            eth.to_protocol_price(price, 8)
+                .unwrap()
+                .to_usd_per_token_f64(eth.token_decimals as i64),
+            3000.0
+        );
+        let compacted = eth.to_compacted_price(price, 8).unwrap();
+        assert!(compacted < 1 << 32);
+        assert_eq!(
+            compacted * 10u128.pow(eth.oracle_decimals),
+            eth.to_protocol_price(price, 8).unwrap().to_u128().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stablecoin_prices() {
+        let usdc = token(&registry(), "usdc");
+        // 1 USD with 8 feed decimals, for a token with 6 decimals.
+        let price = 10u128.pow(8);
+        assert_eq!(
+            usdc.to_protocol_price(price, 8).unwrap().to_u128().unwrap(),
+            10u128.pow(24)
+        );
+        assert_eq!(
+            usdc.to_protocol_price(price, 8)
+                .unwrap()
+                .to_usd_per_token_f64(usdc.token_decimals as i64),
+            1.0
+        );
+        let compacted = usdc.to_compacted_price(price, 8).unwrap();
+        assert!(compacted < 1 << 32);
+        assert_eq!(
+            compacted * 10u128.pow(usdc.oracle_decimals),
+            usdc.to_protocol_price(price, 8).unwrap().to_u128().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scale_overflow() {
+        let eth = token(&registry(), "eth");
+        // A feed price with 0 decimals scaled to the 12 decimals of an ETH protocol price.
+        assert!(eth.to_protocol_price(u128::MAX / 10, 0).is_err());
+        // Dividing by more than 128 bits of decimals leaves nothing.
+        assert_eq!(scale(u128::MAX, 60, 12).unwrap(), 0);
+        assert_eq!(scale(123456, 3, 1).unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_compacted_decimals() {
+        let registry = registry();
+        let eth = ContractAddress::from(token(&registry, "eth").address);
+        let usdc = ContractAddress::from(token(&registry, "usdc").address);
+        let unknown = ContractAddress::from(FieldElement::ONE);
+        assert_eq!(
+            registry.compacted_decimals(&[eth, usdc, unknown]).unwrap(),
+            vec![U256 {
+                low: 8 | (18 << 8) | (1 << 16),
+                high: 0,
+            }]
+        );
+    }
+}
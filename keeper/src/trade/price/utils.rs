@@ -4,6 +4,7 @@ use serde::Deserialize;
 use std::env;
 
 use super::error::PragmaAPIError;
+use crate::pricelog::record_price;
 
 #[derive(Deserialize)]
 pub struct PathParams {
@@ -19,7 +20,7 @@ pub struct QueryParams {
     pub aggregation: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct PriceInfo {
     pub decimals: u64,
     pub num_sources_aggregated: u64,
@@ -28,6 +29,13 @@ pub struct PriceInfo {
     pub timestamp: u64,
 }
 
+impl PriceInfo {
+    // Returns the timestamp of the price in seconds, Pragma timestamping prices in milliseconds.
+    pub fn timestamp_secs(&self) -> u64 {
+        self.timestamp / 1000
+    }
+}
+
 pub async fn get_pragma_price(
     path: PathParams,
     query: QueryParams,
@@ -37,7 +45,10 @@ pub async fn get_pragma_price(
         path.base, path.quote, path.interval, query.aggregation, path.timestamp
     );
     match fetch_data(&api_url).await {
-        Ok(price_info) => Ok(price_info),
+        Ok(price_info) => {
+            record_price(&path.base, &path.quote, &price_info);
+            Ok(price_info)
+        }
         Err(err) => Err(err),
     }
 }
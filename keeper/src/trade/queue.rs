@@ -0,0 +1,692 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    sync::{oneshot, Notify},
+    time::sleep,
+};
+
+use super::crash::is_liquidation;
+use crate::{config, types::SatoruAction};
+
+// Order types executed at the current price, as soon as created.
+const MARKET_ORDER_TYPES: [&str; 3] = ["MarketIncrease", "MarketDecrease", "MarketSwap"];
+
+// An enum representing the priority class of an execution, the lowest class being sent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExecutionClass {
+    // Liquidations, which keep the pools solvent.
+    Liquidation,
+    // Market orders, deposits and withdrawals, waited for by their traders.
+    Market,
+    // Trigger orders, executed once their trigger price got crossed.
+    Trigger,
+}
+
+impl ExecutionClass {
+    // Returns the class of an action.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub fn of(table: &str, action: &SatoruAction) -> Self {
+        if is_liquidation(table, action) {
+            return ExecutionClass::Liquidation;
+        }
+        match (table, action.order_type.as_deref()) {
+            ("orders", Some(order_type)) if !MARKET_ORDER_TYPES.contains(&order_type) => {
+                ExecutionClass::Trigger
+            }
+            _ => ExecutionClass::Market,
+        }
+    }
+}
+
+// A struct representing the randomization of the executions, so keepers running this software
+// against the same actions do not all send them at the same instant and collide.
+// @max_delay: The largest delay an execution waits before taking a slot, drawn uniformly up to it.
+// @shuffle: Whether the waiting executions of a class go in random order rather than by fee.
+// @seed: The xorshift state the delays and ranks get drawn from, different on every run.
+#[derive(Debug, Default)]
+pub struct ExecutionJitter {
+    pub max_delay: Duration,
+    pub shuffle: bool,
+    seed: AtomicU64,
+}
+
+impl ExecutionJitter {
+    pub fn new(max_delay: Duration, shuffle: bool) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before unix epoch");
+        ExecutionJitter {
+            max_delay,
+            shuffle,
+            // Instances started at once still get their own draws, 0 being a fixed point.
+            seed: AtomicU64::new((now.as_nanos() as u64 ^ (process::id() as u64) << 32) | 1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        ExecutionJitter::new(
+            Duration::from_millis(config::get_execution_jitter_max_ms()),
+            config::get_execution_shuffle(),
+        )
+    }
+
+    fn next_random(&self) -> u64 {
+        let next = |mut seed: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        let previous = self
+            .seed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |seed| {
+                Some(next(seed))
+            })
+            .expect("Seed update never fails");
+        next(previous)
+    }
+
+    // Returns the delay an execution waits before taking a slot, none without jitter.
+    pub fn delay(&self) -> Duration {
+        match self.max_delay.as_millis() as u64 {
+            0 => Duration::ZERO,
+            max_delay => Duration::from_millis(self.next_random() % (max_delay + 1)),
+        }
+    }
+
+    // Returns the rank of an execution within its class, the same for all without shuffling so
+    // they go by fee.
+    pub fn rank(&self) -> u64 {
+        match self.shuffle {
+            true => self.next_random(),
+            false => 0,
+        }
+    }
+}
+
+// An execution waiting for a slot.
+#[derive(Debug)]
+struct QueuedExecution {
+    market: String,
+    class: ExecutionClass,
+    rank: u64,
+    execution_fee: u128,
+    queued_at: Instant,
+    // Notified with the execution fee the slot or budget got counted with.
+    sender: oneshot::Sender<u128>,
+}
+
+// Returns the index of the execution to send next: the longest waiting of the executions waiting
+// for longer than the starvation delay, otherwise the one of the lowest class, the rank drawn when
+// shuffling then the largest execution fee breaking ties, then the wait.
+// @queued: The waiting executions.
+// @now: The current instant.
+// @starvation_delay: The wait after which an execution goes first whatever its class.
+fn next_execution(
+    queued: &[QueuedExecution],
+    now: Instant,
+    starvation_delay: Duration,
+) -> Option<usize> {
+    let starved = queued
+        .iter()
+        .enumerate()
+        .filter(|(_, execution)| now.duration_since(execution.queued_at) >= starvation_delay)
+        .min_by_key(|(_, execution)| execution.queued_at)
+        .map(|(index, _)| index);
+    starved.or_else(|| {
+        queued
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, execution)| {
+                (
+                    execution.class,
+                    execution.rank,
+                    Reverse(execution.execution_fee),
+                    execution.queued_at,
+                )
+            })
+            .map(|(index, _)| index)
+    })
+}
+
+// The executions being sent across markets with the execution fees they hold, and the budget
+// requests of the lanes, one per lane waiting, with the priority of its next execution.
+#[derive(Debug, Default)]
+struct BudgetState {
+    running: usize,
+    in_flight_fee: u128,
+    queued: Vec<QueuedExecution>,
+}
+
+// The executions of a market being sent and waiting to be taken by its task.
+#[derive(Debug, Default)]
+struct MarketLane {
+    running: usize,
+    queued: Vec<QueuedExecution>,
+    wakeup: Arc<Notify>,
+}
+
+// The limits of the queue with the state its lane tasks and slots share.
+// @max_concurrent: The largest number of executions sent at once, no limit when None.
+// @market_max_concurrent: The largest number of executions of a market sent at once, no limit
+// when None.
+// @max_in_flight_fee: The largest sum of the execution fees of the executions sent at once, no
+// limit when None.
+// @starvation_delay: The wait after which an execution goes first whatever its class.
+#[derive(Debug, Default)]
+struct QueueShared {
+    max_concurrent: Option<usize>,
+    market_max_concurrent: Option<usize>,
+    max_in_flight_fee: Option<u128>,
+    starvation_delay: Duration,
+    budget: Mutex<BudgetState>,
+    lanes: Mutex<HashMap<String, MarketLane>>,
+}
+
+// Returns a budget request with the priority of an execution.
+fn budget_request(execution: &QueuedExecution) -> (QueuedExecution, oneshot::Receiver<u128>) {
+    let (sender, receiver) = oneshot::channel();
+    let request = QueuedExecution {
+        market: execution.market.clone(),
+        class: execution.class,
+        rank: execution.rank,
+        execution_fee: execution.execution_fee,
+        queued_at: execution.queued_at,
+        sender,
+    };
+    (request, receiver)
+}
+
+impl QueueShared {
+    // Returns whether an execution fits in the budget, an execution above the fee budget still
+    // going alone so it never waits for good.
+    fn fits(&self, budget: &BudgetState, execution_fee: u128) -> bool {
+        self.max_concurrent
+            .is_none_or(|max_concurrent| budget.running < max_concurrent)
+            && self.max_in_flight_fee.is_none_or(|max_fee| {
+                budget.running == 0 || budget.in_flight_fee + execution_fee <= max_fee
+            })
+    }
+
+    // Returns the index of the next execution of a lane, if it has a free slot.
+    fn next_in_lane(&self, lane: &MarketLane) -> Option<usize> {
+        if self
+            .market_max_concurrent
+            .is_some_and(|max_concurrent| lane.running >= max_concurrent)
+        {
+            return None;
+        }
+        next_execution(&lane.queued, Instant::now(), self.starvation_delay)
+    }
+
+    // Returns a budget request with the priority of the next execution of a market.
+    // @market: The market.
+    fn next_request(&self, market: &str) -> Option<(QueuedExecution, oneshot::Receiver<u128>)> {
+        let lanes = self.lanes.lock().unwrap();
+        let lane = lanes.get(market)?;
+        let index = self.next_in_lane(lane)?;
+        Some(budget_request(&lane.queued[index]))
+    }
+
+    // Gives the budget request of a market the priority of its next execution, as a new execution
+    // may go before the one it got made for.
+    // @market: The market.
+    fn refresh_request(&self, market: &str) {
+        let next = match self.next_request(market) {
+            Some((next, _)) => next,
+            None => return,
+        };
+        let mut budget = self.budget.lock().unwrap();
+        if let Some(request) = budget
+            .queued
+            .iter_mut()
+            .find(|request| request.market == market)
+        {
+            request.class = next.class;
+            request.rank = next.rank;
+            request.execution_fee = next.execution_fee;
+            request.queued_at = next.queued_at;
+        }
+    }
+
+    // Waits for the budget of the next execution of a market, by priority across markets.
+    // Returns the execution fee it got counted with.
+    // @request: The budget request, its sender being notified once granted.
+    // @receiver: Its receiver.
+    async fn reserve(&self, request: QueuedExecution, receiver: oneshot::Receiver<u128>) -> u128 {
+        {
+            let mut budget = self.budget.lock().unwrap();
+            if budget.queued.is_empty() && self.fits(&budget, request.execution_fee) {
+                budget.running += 1;
+                budget.in_flight_fee += request.execution_fee;
+                return request.execution_fee;
+            }
+            budget.queued.push(request);
+        }
+        // The budget is counted by the execution granting it, with the fee it got granted at.
+        receiver.await.unwrap_or_default()
+    }
+
+    // Releases the budget of an execution, granting it to the next waiting requests it fits.
+    // @execution_fee: The execution fee the execution got counted with.
+    fn release(&self, execution_fee: u128) {
+        let mut budget = self.budget.lock().unwrap();
+        budget.running -= 1;
+        budget.in_flight_fee -= execution_fee;
+        while let Some(index) =
+            next_execution(&budget.queued, Instant::now(), self.starvation_delay)
+        {
+            let execution_fee = budget.queued[index].execution_fee;
+            if !self.fits(&budget, execution_fee) {
+                return;
+            }
+            budget.running += 1;
+            budget.in_flight_fee += execution_fee;
+            let _ = budget.queued.swap_remove(index).sender.send(execution_fee);
+        }
+    }
+
+    // Counts an execution with another fee than its budget request got granted at.
+    // @granted_fee: The fee of the request.
+    // @execution_fee: The fee of the execution.
+    fn recount(&self, granted_fee: u128, execution_fee: u128) {
+        let mut budget = self.budget.lock().unwrap();
+        budget.in_flight_fee = budget.in_flight_fee - granted_fee + execution_fee;
+    }
+
+    // Takes the next execution of a market, its slot being counted.
+    // @market: The market.
+    fn take_next(&self, market: &str) -> Option<QueuedExecution> {
+        let mut lanes = self.lanes.lock().unwrap();
+        let lane = lanes.get_mut(market)?;
+        let index = self.next_in_lane(lane)?;
+        lane.running += 1;
+        Some(lane.queued.swap_remove(index))
+    }
+
+    // Releases the slot of an execution of a market, waking the task of its market up.
+    // @market: The market of the execution.
+    fn release_market(&self, market: &str) {
+        let mut lanes = self.lanes.lock().unwrap();
+        if let Some(lane) = lanes.get_mut(market) {
+            lane.running -= 1;
+            lane.wakeup.notify_one();
+        }
+    }
+}
+
+// Sends the executions of a market one at a time by priority, each once its lane has a free slot
+// and the budget fits it, so a market whose executions are slow to build, e.g. on a slow oracle,
+// only ever holds its own slots and never delays the executions of the other markets. The lane
+// waits for the budget with the priority of its next execution, taken once granted.
+// @shared: The state of the queue.
+// @market: The market.
+// @wakeup: Notified when an execution of the market got queued or released its slot.
+async fn run_market_lane(shared: Arc<QueueShared>, market: String, wakeup: Arc<Notify>) {
+    loop {
+        let (request, receiver) = match shared.next_request(&market) {
+            Some(request) => request,
+            None => {
+                wakeup.notified().await;
+                continue;
+            }
+        };
+        let granted_fee = shared.reserve(request, receiver).await;
+        let execution = match shared.take_next(&market) {
+            Some(execution) => execution,
+            None => {
+                shared.release(granted_fee);
+                continue;
+            }
+        };
+        shared.recount(granted_fee, execution.execution_fee);
+        // Executions whose job got dropped while waiting give their slot back.
+        if execution.sender.send(execution.execution_fee).is_err() {
+            shared.release(execution.execution_fee);
+            shared.release_market(&market);
+        }
+    }
+}
+
+// A struct queuing executions per market, each market getting its own task taking its waiting
+// executions by priority rather than in notification order: liquidations first, then market orders
+// by execution fee, then trigger orders. Executions waiting for longer than the starvation delay go
+// first, so a stream of liquidations never holds trigger orders back for good. The markets share a
+// budget of executions and execution fees in flight, granted by the same priority across markets.
+// @jitter: The randomization of the executions, none by default.
+// @shared: The limits of the queue, its lanes and budget.
+#[derive(Debug, Default)]
+pub struct ExecutionQueue {
+    pub jitter: ExecutionJitter,
+    shared: Arc<QueueShared>,
+}
+
+// A struct representing a slot of the execution queue, handed to the next execution once dropped.
+// @market: The market of the execution, None for the executions sent without any limit.
+pub struct QueueSlot {
+    shared: Arc<QueueShared>,
+    market: Option<String>,
+    execution_fee: u128,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(market) = &self.market {
+            self.shared.release(self.execution_fee);
+            self.shared.release_market(market);
+        }
+    }
+}
+
+impl ExecutionQueue {
+    pub fn new(max_concurrent: Option<usize>, starvation_delay: Duration) -> Self {
+        ExecutionQueue::with_budget(max_concurrent, None, None, starvation_delay)
+    }
+
+    // @max_concurrent: The largest number of executions sent at once, no limit when None.
+    // @market_max_concurrent: The largest number of executions of a market sent at once.
+    // @max_in_flight_fee: The largest sum of the execution fees of the executions sent at once.
+    // @starvation_delay: The wait after which an execution goes first whatever its class.
+    pub fn with_budget(
+        max_concurrent: Option<usize>,
+        market_max_concurrent: Option<usize>,
+        max_in_flight_fee: Option<u128>,
+        starvation_delay: Duration,
+    ) -> Self {
+        ExecutionQueue {
+            jitter: ExecutionJitter::default(),
+            shared: Arc::new(QueueShared {
+                max_concurrent,
+                market_max_concurrent,
+                max_in_flight_fee,
+                starvation_delay,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        ExecutionQueue {
+            jitter: ExecutionJitter::from_env(),
+            ..ExecutionQueue::with_budget(
+                config::get_execution_max_concurrent(),
+                config::get_execution_market_max_concurrent(),
+                config::get_execution_max_in_flight_fee(),
+                Duration::from_secs(config::get_execution_starvation_secs()),
+            )
+        }
+    }
+
+    // Whether the queue limits the executions at all, executions being sent as soon as notified
+    // otherwise.
+    fn is_limited(&self) -> bool {
+        self.shared.max_concurrent.is_some()
+            || self.shared.market_max_concurrent.is_some()
+            || self.shared.max_in_flight_fee.is_some()
+    }
+
+    // Returns the number of executions being sent and waiting for a slot, none being tracked
+    // without limits.
+    pub fn depth(&self) -> (usize, usize) {
+        let queued: usize = {
+            let lanes = self.shared.lanes.lock().unwrap();
+            lanes.values().map(|lane| lane.queued.len()).sum()
+        };
+        (self.shared.budget.lock().unwrap().running, queued)
+    }
+
+    // Returns the number of executions of each market being sent and waiting to be taken by its
+    // task, by market.
+    pub fn market_depths(&self) -> Vec<(String, usize, usize)> {
+        let lanes = self.shared.lanes.lock().unwrap();
+        let mut depths: Vec<(String, usize, usize)> = lanes
+            .iter()
+            .map(|(market, lane)| (market.clone(), lane.running, lane.queued.len()))
+            .collect();
+        depths.sort();
+        depths
+    }
+
+    // Waits for a slot to send an execution, the slot being held until dropped. The execution
+    // gets queued on the lane of its market, whose task is started with its first execution.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub async fn acquire(&self, table: &str, action: &SatoruAction) -> QueueSlot {
+        let delay = self.jitter.delay();
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
+        if !self.is_limited() {
+            return QueueSlot {
+                shared: Arc::clone(&self.shared),
+                market: None,
+                execution_fee: 0,
+            };
+        }
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut lanes = self.shared.lanes.lock().unwrap();
+            let lane = lanes.entry(action.market.clone()).or_insert_with(|| {
+                let lane = MarketLane::default();
+                tokio::spawn(run_market_lane(
+                    Arc::clone(&self.shared),
+                    action.market.clone(),
+                    Arc::clone(&lane.wakeup),
+                ));
+                lane
+            });
+            lane.queued.push(QueuedExecution {
+                market: action.market.clone(),
+                class: ExecutionClass::of(table, action),
+                rank: self.jitter.rank(),
+                execution_fee: action.execution_fee,
+                queued_at: Instant::now(),
+                sender,
+            });
+            lane.wakeup.notify_one();
+        }
+        self.shared.refresh_request(&action.market);
+        // The slot is counted by the lane task handing it over.
+        let _ = receiver.await;
+        QueueSlot {
+            shared: Arc::clone(&self.shared),
+            market: Some(action.market.clone()),
+            execution_fee: action.execution_fee,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(order_type: &str, execution_fee: u128) -> SatoruAction {
+        SatoruAction {
+            order_type: Some(order_type.to_owned()),
+            execution_fee,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_execution_class() {
+        let class = |table, order_type| ExecutionClass::of(table, &action(order_type, 0));
+        assert_eq!(class("orders", "Liquidation"), ExecutionClass::Liquidation);
+        assert_eq!(class("orders", "MarketSwap"), ExecutionClass::Market);
+        assert_eq!(class("orders", "StopLossDecrease"), ExecutionClass::Trigger);
+        assert_eq!(
+            ExecutionClass::of("deposits", &SatoruAction::default()),
+            ExecutionClass::Market
+        );
+    }
+
+    #[test]
+    fn test_next_execution() {
+        let now = Instant::now();
+        let queued = |class, execution_fee, waited: u64| QueuedExecution {
+            market: String::new(),
+            class,
+            rank: 0,
+            execution_fee,
+            queued_at: now - Duration::from_secs(waited),
+            sender: oneshot::channel().0,
+        };
+        let delay = Duration::from_secs(30);
+        let mut executions = vec![
+            queued(ExecutionClass::Trigger, 50, 10),
+            queued(ExecutionClass::Market, 10, 5),
+            queued(ExecutionClass::Market, 20, 1),
+        ];
+        assert_eq!(next_execution(&executions, now, delay), Some(2));
+
+        executions.push(queued(ExecutionClass::Liquidation, 0, 0));
+        assert_eq!(next_execution(&executions, now, delay), Some(3));
+
+        // The trigger order waited past the starvation delay, it goes first.
+        assert_eq!(
+            next_execution(&executions, now + Duration::from_secs(20), delay),
+            Some(0)
+        );
+        assert_eq!(next_execution(&[], now, delay), None);
+    }
+
+    #[test]
+    fn test_next_execution_shuffled() {
+        let now = Instant::now();
+        let queued = |class, rank, execution_fee| QueuedExecution {
+            market: String::new(),
+            class,
+            rank,
+            execution_fee,
+            queued_at: now,
+            sender: oneshot::channel().0,
+        };
+        let executions = vec![
+            queued(ExecutionClass::Market, 7, 100),
+            queued(ExecutionClass::Market, 3, 10),
+            queued(ExecutionClass::Trigger, 0, 1_000),
+        ];
+        // The rank goes before the fee, never before the class.
+        assert_eq!(
+            next_execution(&executions, now, Duration::from_secs(30)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        let jitter = ExecutionJitter::new(Duration::from_millis(50), true);
+        let delays: Vec<Duration> = (0..100).map(|_| jitter.delay()).collect();
+        assert!(delays
+            .iter()
+            .all(|delay| *delay <= Duration::from_millis(50)));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+        assert_ne!(jitter.rank(), jitter.rank());
+
+        let none = ExecutionJitter::default();
+        assert_eq!(none.delay(), Duration::ZERO);
+        assert_eq!((none.rank(), none.rank()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_by_priority() {
+        let queue = Arc::new(ExecutionQueue::new(Some(1), Duration::from_secs(60)));
+        let slot = queue.acquire("orders", &action("MarketIncrease", 0)).await;
+
+        let (order, sent) = std::sync::mpsc::channel();
+        let mut waiting = Vec::new();
+        for (order_type, execution_fee) in [
+            ("LimitIncrease", 100),
+            ("MarketIncrease", 5),
+            ("Liquidation", 0),
+        ] {
+            let queue = Arc::clone(&queue);
+            let order = order.clone();
+            waiting.push(tokio::spawn(async move {
+                let _slot = queue
+                    .acquire("orders", &action(order_type, execution_fee))
+                    .await;
+                order.send(order_type).unwrap();
+            }));
+            // Lets the execution get queued before the next one.
+            tokio::task::yield_now().await;
+        }
+        while queue.depth().1 < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.depth(), (1, 3));
+        drop(slot);
+        for task in waiting {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            sent.try_iter().collect::<Vec<_>>(),
+            vec!["Liquidation", "MarketIncrease", "LimitIncrease"]
+        );
+        assert_eq!(queue.depth(), (0, 0));
+    }
+
+    fn market_action(market: &str, execution_fee: u128) -> SatoruAction {
+        SatoruAction {
+            market: market.to_owned(),
+            ..action("MarketIncrease", execution_fee)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acquire_per_market() {
+        let queue = Arc::new(ExecutionQueue::with_budget(
+            Some(2),
+            Some(1),
+            None,
+            Duration::from_secs(60),
+        ));
+        let slot = queue.acquire("orders", &market_action("1a", 0)).await;
+        let waiting = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move {
+                let _slot = queue.acquire("orders", &market_action("1a", 0)).await;
+            })
+        };
+        while queue.depth().1 < 1 {
+            tokio::task::yield_now().await;
+        }
+        // The slow market holds its own slot only, the other market executing meanwhile.
+        let other = queue.acquire("orders", &market_action("1b", 0)).await;
+        assert_eq!(
+            queue.market_depths(),
+            vec![("1a".to_owned(), 1, 1), ("1b".to_owned(), 1, 0)]
+        );
+        drop(other);
+        drop(slot);
+        waiting.await.unwrap();
+        assert_eq!(queue.depth(), (0, 0));
+    }
+
+    #[test]
+    fn test_fee_budget() {
+        let shared = QueueShared {
+            max_in_flight_fee: Some(100),
+            ..Default::default()
+        };
+        let budget = |running, in_flight_fee| BudgetState {
+            running,
+            in_flight_fee,
+            queued: vec![],
+        };
+        assert!(shared.fits(&budget(1, 60), 40));
+        assert!(!shared.fits(&budget(1, 60), 50));
+        // An execution above the budget still goes alone.
+        assert!(shared.fits(&budget(0, 0), 500));
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use satoru_client::polling::{wait, Backoff};
+use starknet::{
+    core::{
+        types::{
+            Event, ExecutionResult, FieldElement, MaybePendingTransactionReceipt, StarknetError,
+            TransactionReceipt,
+        },
+        utils::get_selector_from_name,
+    },
+    providers::{Provider, ProviderError},
+};
+use tokio::{sync::Notify, time::Instant};
+
+use super::revert::{decode_panic_data, decode_revert_reason};
+use crate::config;
+
+// How long a transaction gets waited for before giving up on it.
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Polls the provider until the receipt of a transaction is included in a block, polling fast once
+// the transaction is pending and less and less often while it is unknown.
+// @provider: The Starknet provider to query.
+// @transaction_hash: The hash of the transaction to wait for.
+// @wakeup: Notified when the transaction may have progressed, triggering a poll at once.
+pub async fn wait_for_receipt<P: Provider>(
+    provider: &P,
+    transaction_hash: FieldElement,
+    wakeup: &Notify,
+) -> Result<TransactionReceipt, ProviderError> {
+    let deadline = Instant::now() + RECEIPT_TIMEOUT;
+    let mut backoff = Backoff::new(
+        Duration::from_millis(config::get_receipt_poll_min_interval_ms()),
+        Duration::from_millis(config::get_receipt_poll_max_interval_ms()),
+    );
+    loop {
+        let active = match provider.get_transaction_receipt(transaction_hash).await {
+            Ok(MaybePendingTransactionReceipt::Receipt(receipt)) => return Ok(receipt),
+            Ok(MaybePendingTransactionReceipt::PendingReceipt(_)) => true,
+            Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => false,
+            Err(err) => return Err(err),
+        };
+
+        if Instant::now() >= deadline {
+            return Err(ProviderError::StarknetError(
+                StarknetError::TransactionHashNotFound,
+            ));
+        }
+        wait(backoff.next(active), wakeup).await;
+    }
+}
+
+// An enum representing what an execution transaction actually did to the action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionOutcome {
+    // The Executed event for the action key got emitted.
+    Executed,
+    // The action got cancelled by the handler, e.g. on a failed execution, with the decoded reason.
+    Cancelled(Option<String>),
+    // The order got frozen by the handler, with the decoded reason.
+    Frozen(Option<String>),
+    // The whole transaction reverted, with the decoded Satoru error or the raw reason.
+    Reverted(String),
+    // The transaction succeeded without any event for the action key.
+    Unknown,
+    // The order got older than the TTL before getting executed, with its age in blocks.
+    Expired(u64),
+    // The order stayed above a market cap for its whole deferral budget, no transaction sent.
+    Capped(String),
+    // The action left the DataStore without the keeper seeing how, e.g. settled by another keeper
+    // while its own keeper was down.
+    Settled,
+}
+
+impl ExecutionOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionOutcome::Executed => "executed",
+            ExecutionOutcome::Cancelled(_) => "cancelled",
+            ExecutionOutcome::Frozen(_) => "frozen",
+            ExecutionOutcome::Reverted(_) => "reverted",
+            ExecutionOutcome::Unknown => "unknown",
+            ExecutionOutcome::Expired(_) => "expired",
+            ExecutionOutcome::Capped(_) => "capped",
+            ExecutionOutcome::Settled => "settled",
+        }
+    }
+
+    // Returns why the action did not execute, if known.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            ExecutionOutcome::Reverted(reason) | ExecutionOutcome::Capped(reason) => {
+                Some(reason.as_str())
+            }
+            ExecutionOutcome::Cancelled(reason) | ExecutionOutcome::Frozen(reason) => {
+                reason.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+// Returns the fee actually paid for a transaction.
+// @receipt: The receipt of the transaction.
+pub fn get_actual_fee(receipt: &TransactionReceipt) -> FieldElement {
+    match receipt {
+        TransactionReceipt::Invoke(receipt) => receipt.actual_fee.amount,
+        TransactionReceipt::L1Handler(receipt) => receipt.actual_fee.amount,
+        TransactionReceipt::Declare(receipt) => receipt.actual_fee.amount,
+        TransactionReceipt::Deploy(receipt) => receipt.actual_fee.amount,
+        TransactionReceipt::DeployAccount(receipt) => receipt.actual_fee.amount,
+    }
+}
+
+// Returns the event names (executed, cancelled, frozen) emitted by the handler of a table.
+fn outcome_event_names(table: &str) -> Option<(&'static str, &'static str, Option<&'static str>)> {
+    match table {
+        "orders" => Some(("OrderExecuted", "OrderCancelled", Some("OrderFrozen"))),
+        "deposits" => Some(("DepositExecuted", "DepositCancelled", None)),
+        "withdrawals" => Some(("WithdrawalExecuted", "WithdrawalCancelled", None)),
+        _ => None,
+    }
+}
+
+// Finds out the outcome of an execution transaction for an action.
+// @receipt: The receipt of the execution transaction.
+// @table: The table the action comes from (orders, deposits, withdrawals).
+// @key: The key of the action.
+pub fn get_execution_outcome(
+    receipt: &TransactionReceipt,
+    table: &str,
+    key: FieldElement,
+) -> ExecutionOutcome {
+    match (receipt.execution_result(), receipt) {
+        (ExecutionResult::Reverted { reason }, _) => {
+            ExecutionOutcome::Reverted(decode_revert_reason(reason).unwrap_or(reason.clone()))
+        }
+        (ExecutionResult::Succeeded, TransactionReceipt::Invoke(receipt)) => {
+            get_outcome_from_events(&receipt.events, table, key)
+        }
+        (ExecutionResult::Succeeded, _) => ExecutionOutcome::Unknown,
+    }
+}
+
+// Looks for the Executed/Cancelled/Frozen event of an action among the events of a receipt.
+// The action key is always the first data member of those events, followed by the reason
+// for cancelled and frozen ones.
+// @events: The events emitted by the transaction.
+// @table: The table the action comes from.
+// @key: The key of the action.
+pub fn get_outcome_from_events(
+    events: &[Event],
+    table: &str,
+    key: FieldElement,
+) -> ExecutionOutcome {
+    let (executed, cancelled, frozen) = match outcome_event_names(table) {
+        Some(names) => names,
+        None => return ExecutionOutcome::Unknown,
+    };
+    let selector = |name: &str| get_selector_from_name(name).expect("Invalid event name");
+
+    for event in events
+        .iter()
+        .filter(|event| event.data.first() == Some(&key))
+    {
+        match event.keys.first() {
+            Some(name) if *name == selector(executed) => return ExecutionOutcome::Executed,
+            Some(name) if *name == selector(cancelled) => {
+                return ExecutionOutcome::Cancelled(decode_panic_data(&event.data[1..]))
+            }
+            Some(name) if frozen.map(selector) == Some(*name) => {
+                return ExecutionOutcome::Frozen(decode_panic_data(&event.data[1..]))
+            }
+            _ => {}
+        }
+    }
+    ExecutionOutcome::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::core::utils::cairo_short_string_to_felt;
+
+    fn event(name: &str, key: FieldElement) -> Event {
+        Event {
+            from_address: FieldElement::ONE,
+            keys: vec![get_selector_from_name(name).unwrap()],
+            data: vec![
+                key,
+                cairo_short_string_to_felt("empty_order").unwrap(),
+                FieldElement::ZERO,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_outcome_from_events() {
+        let key = FieldElement::from(42_u64);
+        let other_key = FieldElement::from(43_u64);
+
+        let events = vec![
+            event("OrderCancelled", other_key),
+            event("OrderExecuted", key),
+        ];
+        assert_eq!(
+            get_outcome_from_events(&events, "orders", key),
+            ExecutionOutcome::Executed
+        );
+        assert_eq!(
+            get_outcome_from_events(&events, "orders", other_key),
+            ExecutionOutcome::Cancelled(Some("EMPTY_ORDER".to_owned()))
+        );
+
+        let events = vec![event("OrderFrozen", key)];
+        assert_eq!(
+            get_outcome_from_events(&events, "orders", key),
+            ExecutionOutcome::Frozen(Some("EMPTY_ORDER".to_owned()))
+        );
+        assert_eq!(
+            get_outcome_from_events(&events, "deposits", key),
+            ExecutionOutcome::Unknown
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use crate::config;
+
+// Satoru errors fixed by fetching fresh prices and sending the execution again, matched as whole
+// error names so e.g. ORDER_NOT_FULFILLABLE_AT_ACCEPTABLE_PRICE does not get new prices.
+const NEW_PRICES_ERRORS: [&str; 10] = [
+    "MAX_PRICE_AGE_EXCEEDED",
+    "MAX_REFPRICE_DEVIATION_EXCEEDED",
+    "BLOCK_NUMBERS_NOT_EQUAL",
+    "BLOCK_NUMBERS_NOT_SORTED",
+    "BLOCK_NUMBER_NOT_IN_RANGE",
+    "ORACLE_BLOCK_NUMBERS_NOT_WITHIN_RANGE",
+    "ORACLE_BLOCK_NUMBER_NOT_WITHIN_RANGE",
+    "ORACLE_BLOCK_NUMBERS_ARE_SMALLER_THAN_REQUIRED",
+    "ORACLE_TIMESTAMPS_ARE_SMALLER_THAN_REQUIRED",
+    "MIN_ORACLE_SIGNERS",
+];
+
+// Satoru errors that no retry will fix.
+const PERMANENT_ERRORS: [&str; 7] = [
+    "EMPTY_",
+    "NOT_FOUND",
+    "UNSUPPORTED",
+    "INVALID",
+    "EXECUTION_FEE",
+    "FROZEN",
+    "UNAUTHORIZED",
+];
+
+// An enum representing how a revert reason should be handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RevertClass {
+    // Retry right away, the handler fetches new prices.
+    RetryWithNewPrices,
+    // Retry after a delay, once market state may have changed.
+    RetryLater,
+    // Give up on the action.
+    Permanent,
+}
+
+// Classifies a decoded revert reason. Permanent patterns win over the retryable error names, so
+// e.g. INVALID_ORACLE_PRICE is never retried. Anything else, such as market state errors
+// (INSUFFICIENT_RESERVE, MAX_OPEN_INTEREST_EXCEEDED) or unknown reasons, is retried later.
+// @reason: The decoded Satoru error, or the raw revert reason.
+pub fn classify_revert(reason: &str) -> RevertClass {
+    let reason = reason.to_ascii_uppercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|pattern| reason.contains(pattern));
+    let is_named = |names: &[&str]| {
+        reason
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .any(|word| names.contains(&word))
+    };
+    if matches(&PERMANENT_ERRORS) {
+        RevertClass::Permanent
+    } else if is_named(&NEW_PRICES_ERRORS) {
+        RevertClass::RetryWithNewPrices
+    } else {
+        RevertClass::RetryLater
+    }
+}
+
+// A struct representing the retry budget of a revert class.
+// @max_attempts: Maximum number of executions of the action, first one included.
+// @delay: Delay before the next execution.
+#[derive(Debug, Clone, Copy)]
+pub struct RequeuePolicy {
+    pub max_attempts: u32,
+    pub delay: Duration,
+}
+
+impl RequeuePolicy {
+    // @attempts: The number of executions already made for the action.
+    pub fn decide(&self, attempts: u32) -> RequeueDecision {
+        if attempts < self.max_attempts {
+            RequeueDecision::Retry(self.delay)
+        } else {
+            RequeueDecision::Drop
+        }
+    }
+}
+
+// An enum representing what to do with a reverted action.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequeueDecision {
+    Retry(Duration),
+    Drop,
+}
+
+// A struct representing the requeue policies of the retryable revert classes, and of the orders
+// deferred while above a market cap.
+#[derive(Debug, Clone, Copy)]
+pub struct RequeuePolicies {
+    pub new_prices: RequeuePolicy,
+    pub later: RequeuePolicy,
+    pub capped: RequeuePolicy,
+}
+
+impl RequeuePolicies {
+    pub fn from_env() -> Self {
+        RequeuePolicies {
+            new_prices: RequeuePolicy {
+                max_attempts: config::get_retry_new_prices_max_attempts(),
+                delay: Duration::from_millis(config::get_retry_new_prices_delay_ms()),
+            },
+            later: RequeuePolicy {
+                max_attempts: config::get_retry_later_max_attempts(),
+                delay: Duration::from_secs(config::get_retry_later_delay_secs()),
+            },
+            capped: RequeuePolicy {
+                max_attempts: config::get_retry_capped_max_attempts(),
+                delay: Duration::from_secs(config::get_retry_capped_delay_secs()),
+            },
+        }
+    }
+
+    // Decides whether a reverted action gets executed again.
+    // @reason: The decoded revert reason.
+    // @attempts: The number of executions already made for the action.
+    pub fn decide(&self, reason: &str, attempts: u32) -> RequeueDecision {
+        let policy = match classify_revert(reason) {
+            RevertClass::RetryWithNewPrices => self.new_prices,
+            RevertClass::RetryLater => self.later,
+            RevertClass::Permanent => return RequeueDecision::Drop,
+        };
+        policy.decide(attempts)
+    }
+
+    // Decides whether an order deferred above a market cap gets checked again.
+    // @attempts: The number of executions already made for the order, deferrals included.
+    pub fn decide_capped(&self, attempts: u32) -> RequeueDecision {
+        self.capped.decide(attempts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_revert() {
+        assert_eq!(
+            classify_revert("BLOCK_NUMBERS_NOT_EQUAL"),
+            RevertClass::RetryWithNewPrices
+        );
+        assert_eq!(
+            classify_revert("INSUFFICIENT_RESERVE"),
+            RevertClass::RetryLater
+        );
+        assert_eq!(
+            classify_revert("INVALID_ORACLE_PRICE"),
+            RevertClass::Permanent
+        );
+        assert_eq!(classify_revert("EMPTY_ORDER"), RevertClass::Permanent);
+        assert_eq!(classify_revert("connection reset"), RevertClass::RetryLater);
+        // Only whole error names get new prices, not every error about a price or block number.
+        assert_eq!(
+            classify_revert("Execution failed (BLOCK_NUMBER_NOT_IN_RANGE)."),
+            RevertClass::RetryWithNewPrices
+        );
+        assert_eq!(
+            classify_revert("ORDER_NOT_FULFILLABLE_AT_ACCEPTABLE_PRICE"),
+            RevertClass::RetryLater
+        );
+        assert_eq!(
+            classify_revert("INVALID_ORDER_PRICE"),
+            RevertClass::Permanent
+        );
+        assert_eq!(
+            classify_revert("MAX_PRICE_AGE_EXCEEDED_BY_ORDER"),
+            RevertClass::RetryLater
+        );
+    }
+
+    #[test]
+    fn test_decide() {
+        let policies = RequeuePolicies {
+            new_prices: RequeuePolicy {
+                max_attempts: 2,
+                delay: Duration::ZERO,
+            },
+            later: RequeuePolicy {
+                max_attempts: 3,
+                delay: Duration::from_secs(30),
+            },
+            capped: RequeuePolicy {
+                max_attempts: 10,
+                delay: Duration::from_secs(60),
+            },
+        };
+        assert_eq!(
+            policies.decide("MAX_PRICE_AGE_EXCEEDED", 1),
+            RequeueDecision::Retry(Duration::ZERO)
+        );
+        assert_eq!(
+            policies.decide("MAX_PRICE_AGE_EXCEEDED", 2),
+            RequeueDecision::Drop
+        );
+        assert_eq!(
+            policies.decide("INSUFFICIENT_POOL_AMOUNT", 2),
+            RequeueDecision::Retry(Duration::from_secs(30))
+        );
+        assert_eq!(policies.decide("EMPTY_ORDER", 1), RequeueDecision::Drop);
+        assert_eq!(
+            policies.decide_capped(9),
+            RequeueDecision::Retry(Duration::from_secs(60))
+        );
+        assert_eq!(policies.decide_capped(10), RequeueDecision::Drop);
+    }
+}
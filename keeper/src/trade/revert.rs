@@ -0,0 +1,108 @@
+use std::fmt::Debug;
+
+use starknet::core::types::FieldElement;
+
+use crate::error::KeeperError;
+
+// Short strings added by the account contract and the Starknet OS around the actual panic data.
+const GENERIC_ERRORS: [&str; 4] = [
+    "ENTRYPOINT_FAILED",
+    "ENTRYPOINT_NOT_FOUND",
+    "argent/multicall-failed",
+    "Option::unwrap failed.",
+];
+
+// Decodes a felt holding a Cairo short string, returns None if it holds anything else.
+// @felt: The felt to decode.
+pub fn decode_short_string(felt: FieldElement) -> Option<String> {
+    let bytes: Vec<u8> = felt
+        .to_bytes_be()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    if bytes.is_empty() || !bytes.iter().all(|byte| (0x20..0x7f).contains(byte)) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Turns a Satoru error short string into its named constant, e.g.
+// 'insufficient_reserve' into INSUFFICIENT_RESERVE.
+fn to_error_name(short_string: &str) -> String {
+    short_string
+        .trim()
+        .chars()
+        .map(|c| match c {
+            ' ' | '-' => '_',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect()
+}
+
+// Finds the Satoru error among panic data felts.
+// @panic_data: The felts of a panic, or the reason of a cancelled/frozen event.
+pub fn decode_panic_data(panic_data: &[FieldElement]) -> Option<String> {
+    panic_data
+        .iter()
+        .filter_map(|felt| decode_short_string(*felt))
+        .find(|short_string| {
+            short_string.len() > 2 && !GENERIC_ERRORS.contains(&short_string.as_str())
+        })
+        .map(|short_string| to_error_name(&short_string))
+}
+
+// Decodes the revert reason of a transaction into the named Satoru error that caused it.
+// The revert reason embeds the panic data as hex felts, e.g.
+// "Execution failed. Failure reason: 0x696e73756666696369656e745f72657365727665 ('insufficient_reserve').".
+// @reason: The revert reason returned in the transaction receipt.
+pub fn decode_revert_reason(reason: &str) -> Option<String> {
+    let felts: Vec<FieldElement> = reason
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| word.starts_with("0x") && word.len() > 2)
+        .filter_map(|word| FieldElement::from_hex_be(word).ok())
+        .collect();
+    decode_panic_data(&felts)
+}
+
+// Wraps an error raised while sending an execution, decoding the Satoru error it carries.
+// Fee estimation simulates the transaction, so most reverts surface here before anything is sent.
+// @err: The account or provider error.
+pub fn to_execution_error<E: Debug>(err: E) -> KeeperError {
+    let raw = format!("{:?}", err);
+    KeeperError::ExecutionError(decode_revert_reason(&raw).unwrap_or(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::core::utils::cairo_short_string_to_felt;
+
+    #[test]
+    fn test_decode_short_string() {
+        let felt = cairo_short_string_to_felt("insufficient_reserve").unwrap();
+        assert_eq!(
+            decode_short_string(felt),
+            Some("insufficient_reserve".to_owned())
+        );
+        assert_eq!(decode_short_string(FieldElement::ZERO), None);
+        assert_eq!(
+            decode_short_string(FieldElement::from_hex_be("0x1f").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let reason = format!(
+            "Error in the called contract (0x0123):\nError at pc=0:104:\nExecution failed. Failure reason: {:#x} ('{}'), {:#x} ('ENTRYPOINT_FAILED').",
+            cairo_short_string_to_felt("block_numbers_not_equal").unwrap(),
+            "block_numbers_not_equal",
+            cairo_short_string_to_felt("ENTRYPOINT_FAILED").unwrap(),
+        );
+        assert_eq!(
+            decode_revert_reason(&reason),
+            Some("BLOCK_NUMBERS_NOT_EQUAL".to_owned())
+        );
+        assert_eq!(decode_revert_reason("Out of gas"), None);
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use starknet::core::types::FieldElement;
+
+use crate::{config, error::KeeperError};
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+const WEEK_SECS: u64 = 7 * DAY_SECS;
+// The unix epoch fell on a thursday, shifting timestamps by 3 days starts the weeks on mondays.
+const EPOCH_WEEKDAY: u64 = 3;
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+// Parses a weekday, 0 being monday.
+fn parse_weekday(day: &str) -> Option<u64> {
+    WEEKDAYS
+        .iter()
+        .position(|weekday| weekday.eq_ignore_ascii_case(day.trim()))
+        .map(|day| day as u64)
+}
+
+// Parses a UTC time of day formatted as HH:MM, 24:00 included, into seconds.
+fn parse_time(time: &str) -> Option<u64> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+    match hours * 60 + minutes {
+        time if minutes < 60 && time <= 24 * 60 => Some(time * 60),
+        _ => None,
+    }
+}
+
+// A struct representing a weekly trading window of a market, in seconds since monday 00:00 UTC.
+// @start: When the window opens.
+// @end: When the window closes, past the end of the week for windows wrapping to the next week.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradingWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TradingWindow {
+    // Returns whether the window is open at a time of the week.
+    // @week_secs: The seconds since monday 00:00 UTC.
+    pub fn contains(&self, week_secs: u64) -> bool {
+        (self.start..self.end).contains(&week_secs)
+            || (self.start..self.end).contains(&(week_secs + WEEK_SECS))
+    }
+
+    // Returns the seconds until the window next opens.
+    // @week_secs: The seconds since monday 00:00 UTC.
+    pub fn secs_until_open(&self, week_secs: u64) -> u64 {
+        (self.start + WEEK_SECS - week_secs) % WEEK_SECS
+    }
+}
+
+// Parses a market schedule entry formatted as market@days@open-close, the days being a day or a
+// range of days and the times UTC, e.g. 0x12@mon-fri@13:30-20:00. A close before the open time
+// closes the next day, e.g. sun-thu@22:00-21:00 for a market trading around the clock on weekdays.
+// Returns the market and its windows, one per day.
+pub fn parse_schedule(entry: &str) -> Result<(FieldElement, Vec<TradingWindow>), KeeperError> {
+    let invalid = |reason: &str| {
+        KeeperError::ScheduleError(format!("invalid market schedule {}: {}", entry, reason))
+    };
+    let mut parts = entry.split('@');
+    let (market, days, hours) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(market), Some(days), Some(hours), None) => (market, days, hours),
+        _ => return Err(invalid("expected market@days@open-close")),
+    };
+    let market = FieldElement::from_hex_be(market.trim()).map_err(|_| invalid("invalid market"))?;
+    let (first_day, last_day) = days.split_once('-').unwrap_or((days, days));
+    let first_day = parse_weekday(first_day).ok_or_else(|| invalid("invalid day"))?;
+    let last_day = parse_weekday(last_day).ok_or_else(|| invalid("invalid day"))?;
+    let (open, close) = hours
+        .split_once('-')
+        .ok_or_else(|| invalid("expected open-close"))?;
+    let open = parse_time(open).ok_or_else(|| invalid("invalid open time"))?;
+    let close = parse_time(close).ok_or_else(|| invalid("invalid close time"))?;
+    let duration = match close > open {
+        true => close - open,
+        false => close + DAY_SECS - open,
+    };
+
+    // Day ranges may wrap around the week, e.g. fri-mon.
+    let day_count = (last_day + 7 - first_day) % 7 + 1;
+    let windows = (0..day_count)
+        .map(|offset| {
+            let start = (first_day + offset) % 7 * DAY_SECS + open;
+            TradingWindow {
+                start,
+                end: start + duration,
+            }
+        })
+        .collect();
+    Ok((market, windows))
+}
+
+// A struct representing the trading hours of the markets which do not trade around the clock, e.g.
+// synthetic equity or FX markets, outside of which their actions are held.
+// @windows: The trading windows per market, markets without windows trading around the clock.
+#[derive(Debug, Clone, Default)]
+pub struct MarketSchedules {
+    pub windows: HashMap<FieldElement, Vec<TradingWindow>>,
+}
+
+impl MarketSchedules {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        let mut windows: HashMap<FieldElement, Vec<TradingWindow>> = HashMap::new();
+        for entry in config::get_market_schedules() {
+            let (market, market_windows) = parse_schedule(&entry)?;
+            windows.entry(market).or_default().extend(market_windows);
+        }
+        Ok(MarketSchedules { windows })
+    }
+
+    // Returns the seconds until a market opens, None while it is open.
+    // @market: The market of the action, as stored by the indexer.
+    // @now: The current unix timestamp.
+    pub fn secs_until_open(&self, market: &str, now: u64) -> Option<u64> {
+        // Markets are compared as felts, the indexer stores them without 0x prefix nor trimmed zeros.
+        let windows = self.windows.get(&FieldElement::from_hex_be(market).ok()?)?;
+        let week_secs = (now + EPOCH_WEEKDAY * DAY_SECS) % WEEK_SECS;
+        if windows.iter().any(|window| window.contains(week_secs)) {
+            return None;
+        }
+        windows
+            .iter()
+            .map(|window| window.secs_until_open(week_secs))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Monday 2024-01-01 00:00 UTC.
+    const MONDAY: u64 = 1704067200;
+
+    #[test]
+    fn test_parse_schedule() {
+        let (market, windows) = parse_schedule("0x12@fri-mon@13:30-20:00").unwrap();
+        assert_eq!(market, FieldElement::from_hex_be("0x12").unwrap());
+        assert_eq!(
+            windows
+                .iter()
+                .map(|window| window.start)
+                .collect::<Vec<_>>(),
+            vec![
+                4 * DAY_SECS + 48600,
+                5 * DAY_SECS + 48600,
+                6 * DAY_SECS + 48600,
+                48600
+            ]
+        );
+        assert!(windows
+            .iter()
+            .all(|window| window.end - window.start == 23400));
+
+        let (_, windows) = parse_schedule("0x12@sun@22:00-21:00").unwrap();
+        assert_eq!(windows[0].end - windows[0].start, 23 * 60 * 60);
+        assert!(parse_schedule("0x12@mon-fri").is_err());
+        assert!(parse_schedule("0x12@monday@13:30-20:00").is_err());
+        assert!(parse_schedule("0x12@mon@13:60-20:00").is_err());
+    }
+
+    #[test]
+    fn test_secs_until_open() {
+        let schedules = MarketSchedules {
+            windows: HashMap::from([parse_schedule("0x12@mon-fri@13:30-20:00").unwrap()]),
+        };
+        let market = format!("{:0>64}", "12");
+
+        assert_eq!(schedules.secs_until_open(&market, MONDAY + 14 * 3600), None);
+        assert_eq!(
+            schedules.secs_until_open(&market, MONDAY + 13 * 3600),
+            Some(1800)
+        );
+        // Closed from friday 20:00 until monday 13:30.
+        assert_eq!(
+            schedules.secs_until_open(&market, MONDAY + 4 * DAY_SECS + 20 * 3600),
+            Some(2 * DAY_SECS + 17 * 3600 + 1800)
+        );
+        assert_eq!(schedules.secs_until_open("13", MONDAY), None);
+    }
+
+    #[test]
+    fn test_window_wrapping_to_next_week() {
+        let schedules = MarketSchedules {
+            windows: HashMap::from([parse_schedule("0x12@sun@22:00-21:00").unwrap()]),
+        };
+        let market = format!("{:0>64}", "12");
+
+        assert_eq!(schedules.secs_until_open(&market, MONDAY + 3600), None);
+        assert_eq!(
+            schedules.secs_until_open(&market, MONDAY + 21 * 3600),
+            Some(6 * DAY_SECS + 3600)
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    config, contracts::Contracts, error::KeeperError, trade::policy::PolicyDecision,
+    trade::utils::get_primary_price, types::SatoruAction,
+};
+
+// The order types executed at the current price, the only ones whose slippage revert is known
+// before execution. Trigger orders get executed later, at the price that triggered them.
+const MARKET_POSITION_ORDER_TYPES: [&str; 2] = ["MarketIncrease", "MarketDecrease"];
+
+// Basis points in one.
+const BPS: u128 = 10_000;
+
+// Returns the share of a price in basis points, saturating the values that do not fit.
+fn bps_of(price: u128, bps: u128) -> u128 {
+    (price / BPS)
+        .saturating_mul(bps)
+        .saturating_add(price % BPS * bps / BPS)
+}
+
+// Checks the price an order executes at against its acceptable price, which the price must stay
+// at or below when buying the index token, a long increase or short decrease, and at or above when
+// selling it. The order gets skipped when the price is past its acceptable price by more than the
+// tolerance, its execution then reverting on slippage.
+// @order_type: The order type.
+// @is_long: Whether the order is on the long side.
+// @price: The price the order executes at.
+// @acceptable_price: The acceptable price of the order.
+// @tolerance_bps: How far past the acceptable price the price may be, in basis points of it, as
+// the price impact and the price moving until execution may bring it back.
+pub fn check_acceptable_price(
+    order_type: &str,
+    is_long: bool,
+    price: u128,
+    acceptable_price: u128,
+    tolerance_bps: u128,
+) -> PolicyDecision {
+    let is_increase = order_type == "MarketIncrease";
+    let tolerance = bps_of(acceptable_price, tolerance_bps);
+    let reverts = match is_increase == is_long {
+        true => price > acceptable_price.saturating_add(tolerance),
+        false => price < acceptable_price.saturating_sub(tolerance),
+    };
+    match reverts {
+        true => PolicyDecision::Skip(format!(
+            "price {} {} the acceptable price {}",
+            price,
+            if is_increase == is_long {
+                "above"
+            } else {
+                "below"
+            },
+            acceptable_price
+        )),
+        false => PolicyDecision::Execute,
+    }
+}
+
+// A struct representing the enforcement of the acceptable prices before execution, so market
+// orders whose slippage makes their execution revert are left alone rather than sent.
+// @enabled: Whether the acceptable prices get checked.
+// @tolerance_bps: How far past its acceptable price the price of an order may be, in basis points.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SlippageCheck {
+    pub enabled: bool,
+    pub tolerance_bps: u128,
+}
+
+impl SlippageCheck {
+    pub fn from_env() -> Self {
+        SlippageCheck {
+            enabled: config::get_enforce_acceptable_price(),
+            tolerance_bps: config::get_acceptable_price_tolerance_bps(),
+        }
+    }
+
+    // Checks the acceptable price of a market order against the price its execution would set,
+    // other actions and orders without acceptable price always being executed.
+    // @contracts: The keeper contracts the price is read with.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    pub async fn check(
+        &self,
+        contracts: &Contracts,
+        table: &str,
+        action: &SatoruAction,
+    ) -> Result<PolicyDecision, KeeperError> {
+        if !self.enabled || table != "orders" {
+            return Ok(PolicyDecision::Execute);
+        }
+        let order_type = match action.order_type.as_deref() {
+            Some(order_type) if MARKET_POSITION_ORDER_TYPES.contains(&order_type) => order_type,
+            _ => return Ok(PolicyDecision::Execute),
+        };
+        let (acceptable_price, is_long) = match (action.acceptable_price, action.is_long) {
+            (Some(acceptable_price), Some(is_long)) => (acceptable_price, is_long),
+            _ => return Ok(PolicyDecision::Execute),
+        };
+        let (market, price) = get_primary_price(action, contracts).await?;
+        // Prices of tokens missing from the registry are the feed ones, not comparable.
+        if price.high != 0 || contracts.token_registry.get(market.long_token).is_none() {
+            return Ok(PolicyDecision::Execute);
+        }
+        Ok(check_acceptable_price(
+            order_type,
+            is_long,
+            price.low,
+            acceptable_price,
+            self.tolerance_bps,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_acceptable_price() {
+        let check = |order_type, is_long, price| {
+            check_acceptable_price(order_type, is_long, price, 1_000, 0)
+        };
+        // Buying the index token, the price must stay at or below the acceptable price.
+        assert_eq!(
+            check("MarketIncrease", true, 1_000),
+            PolicyDecision::Execute
+        );
+        assert!(matches!(
+            check("MarketIncrease", true, 1_001),
+            PolicyDecision::Skip(_)
+        ));
+        assert!(matches!(
+            check("MarketDecrease", false, 1_001),
+            PolicyDecision::Skip(_)
+        ));
+        // Selling it, the price must stay at or above it.
+        assert_eq!(
+            check("MarketIncrease", false, 1_001),
+            PolicyDecision::Execute
+        );
+        assert!(matches!(
+            check("MarketIncrease", false, 999),
+            PolicyDecision::Skip(_)
+        ));
+        assert!(matches!(
+            check("MarketDecrease", true, 999),
+            PolicyDecision::Skip(_)
+        ));
+        assert_eq!(
+            check("MarketDecrease", true, 1_000),
+            PolicyDecision::Execute
+        );
+    }
+
+    #[test]
+    fn test_acceptable_price_tolerance() {
+        // 1% of 1000 past the acceptable price is tolerated.
+        assert_eq!(
+            check_acceptable_price("MarketIncrease", true, 1_010, 1_000, 100),
+            PolicyDecision::Execute
+        );
+        assert!(matches!(
+            check_acceptable_price("MarketIncrease", true, 1_011, 1_000, 100),
+            PolicyDecision::Skip(_)
+        ));
+        assert_eq!(
+            check_acceptable_price("MarketDecrease", true, 990, 1_000, 100),
+            PolicyDecision::Execute
+        );
+        // No limit on the acceptable price.
+        assert_eq!(
+            check_acceptable_price("MarketIncrease", true, u128::MAX, u128::MAX, 100),
+            PolicyDecision::Execute
+        );
+        assert_eq!(
+            check_acceptable_price("MarketDecrease", true, 0, 0, 100),
+            PolicyDecision::Execute
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::error;
+
+use crate::{
+    decisions::Decision, executor::KeeperContext, trade::caps::check_increase_caps,
+    trade::policy::PolicyDecision, types::SatoruAction,
+};
+
+// An enum representing what a strategy decides for a new action, before it gets claimed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyDecision {
+    // The action gets claimed and executed.
+    Execute,
+    // The action is refused by an operator policy, e.g. a disabled order type, with the reason why.
+    Reject(String),
+    // The action is left to other keepers, e.g. as its execution would revert, with the reason why.
+    Skip(String),
+    // The action gets decided on again after a delay, e.g. to time a liquidation, with the reason.
+    Defer(Duration, String),
+}
+
+impl StrategyDecision {
+    // Returns the decision recorded for the action, None when it gets executed.
+    pub fn recorded(&self) -> Option<(Decision, &str)> {
+        match self {
+            StrategyDecision::Execute => None,
+            StrategyDecision::Reject(reason) => Some((Decision::Rejected, reason)),
+            StrategyDecision::Skip(reason) => Some((Decision::Skipped, reason)),
+            StrategyDecision::Defer(_, reason) => Some((Decision::Deferred, reason)),
+        }
+    }
+}
+
+// Converts the decision of a check read on chain. A check that cannot be read lets the action
+// through, and so does a deferral, the executor waiting it out.
+// @decision: The decision of the check.
+fn from_check<E: std::fmt::Display>(decision: Result<PolicyDecision, E>) -> StrategyDecision {
+    match decision {
+        Ok(PolicyDecision::Skip(reason)) => StrategyDecision::Skip(reason),
+        Ok(PolicyDecision::Execute) | Ok(PolicyDecision::Defer(_)) => StrategyDecision::Execute,
+        Err(e) => {
+            error!("{}", e);
+            StrategyDecision::Execute
+        }
+    }
+}
+
+// A trait deciding whether the keeper goes for a new action, so operators can compile in their own
+// strategies, e.g. inventory-aware liquidation timing, without changing the executor. Strategies
+// usually wrap the DefaultStrategy, only deciding on the actions it lets through.
+#[async_trait]
+pub trait KeeperStrategy: Send + Sync {
+    // Decides on a new action, before it gets claimed.
+    // @table: The table the action comes from (orders, deposits, withdrawals).
+    // @action: The action to execute.
+    // @context: The keeper context, e.g. its contracts and the open positions.
+    async fn should_execute(
+        &self,
+        table: &str,
+        action: &SatoruAction,
+        context: &KeeperContext,
+    ) -> StrategyDecision;
+}
+
+// The strategy of the keeper out of the box: the execution policies, the account throttle, then
+// the market caps, the callback gas limit and the acceptable price of market orders, read on chain.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultStrategy;
+
+#[async_trait]
+impl KeeperStrategy for DefaultStrategy {
+    async fn should_execute(
+        &self,
+        table: &str,
+        action: &SatoruAction,
+        context: &KeeperContext,
+    ) -> StrategyDecision {
+        let check_min_sizes = context.crash_mode.checks_min_sizes(table, action);
+        if let PolicyDecision::Skip(reason) =
+            context
+                .execution_policies
+                .evaluate_sized(table, action, check_min_sizes)
+        {
+            return StrategyDecision::Reject(reason);
+        }
+        if table == "orders" && !context.throttle.allow(&action.account) {
+            return StrategyDecision::Reject(format!("account {} throttled", action.account));
+        }
+        // The checks read on chain come last, so refused actions never cost a read.
+        let caps = from_check(check_increase_caps(&context.contracts, table, action).await);
+        if caps != StrategyDecision::Execute {
+            return caps;
+        }
+        let callback_gas = from_check(context.callback_gas.check(&context.contracts, action).await);
+        if callback_gas != StrategyDecision::Execute {
+            return callback_gas;
+        }
+        from_check(
+            context
+                .slippage
+                .check(&context.contracts, table, action)
+                .await,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_check() {
+        assert_eq!(
+            from_check::<String>(Ok(PolicyDecision::Skip("callback gas".to_owned()))),
+            StrategyDecision::Skip("callback gas".to_owned())
+        );
+        assert_eq!(
+            from_check::<String>(Ok(PolicyDecision::Defer("above cap".to_owned()))),
+            StrategyDecision::Execute
+        );
+        assert_eq!(
+            from_check::<String>(Err("unreadable".to_owned())),
+            StrategyDecision::Execute
+        );
+    }
+
+    #[test]
+    fn test_recorded_decision() {
+        assert_eq!(StrategyDecision::Execute.recorded(), None);
+        assert_eq!(
+            StrategyDecision::Reject("disabled".to_owned()).recorded(),
+            Some((Decision::Rejected, "disabled"))
+        );
+        assert_eq!(
+            StrategyDecision::Defer(Duration::from_secs(5), "timing".to_owned()).recorded(),
+            Some((Decision::Deferred, "timing"))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use starknet::core::types::FieldElement;
+
+use crate::config;
+
+// The window the order count of an account is limited over.
+const THROTTLE_WINDOW: Duration = Duration::from_secs(60);
+
+// A struct limiting how many orders of a single trader account get executed per minute, so mass
+// tiny orders with underpriced execution fees cannot drain the keeper.
+// @max_orders_per_minute: The maximum number of orders executed per account and minute, no limit when None.
+// @trusted_accounts: The accounts never throttled.
+// @executions: The recent executions per account.
+#[derive(Debug, Default)]
+pub struct AccountThrottle {
+    pub max_orders_per_minute: Option<u32>,
+    pub trusted_accounts: HashSet<FieldElement>,
+    executions: Mutex<HashMap<FieldElement, VecDeque<Instant>>>,
+}
+
+impl AccountThrottle {
+    pub fn new(
+        max_orders_per_minute: Option<u32>,
+        trusted_accounts: HashSet<FieldElement>,
+    ) -> Self {
+        AccountThrottle {
+            max_orders_per_minute,
+            trusted_accounts,
+            executions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        AccountThrottle::new(
+            config::get_account_max_orders_per_minute(),
+            config::get_trusted_accounts()
+                .iter()
+                .map(|account| {
+                    FieldElement::from_hex_be(account)
+                        .unwrap_or_else(|_| panic!("Invalid trusted account {}", account))
+                })
+                .collect(),
+        )
+    }
+
+    // Counts an order of an account, returns false if the account is over its limit.
+    // @account: The account of the order.
+    pub fn allow(&self, account: &str) -> bool {
+        self.allow_at(account, Instant::now())
+    }
+
+    fn allow_at(&self, account: &str, now: Instant) -> bool {
+        let max_orders_per_minute = match self.max_orders_per_minute {
+            Some(max_orders_per_minute) => max_orders_per_minute as usize,
+            None => return true,
+        };
+        // Accounts are compared as felts, the indexer stores them without 0x prefix.
+        let account = match FieldElement::from_hex_be(account) {
+            Ok(account) => account,
+            Err(_) => return true,
+        };
+        if self.trusted_accounts.contains(&account) {
+            return true;
+        }
+
+        let mut executions = self.executions.lock().unwrap();
+        let account_executions = executions.entry(account).or_default();
+        while account_executions
+            .front()
+            .is_some_and(|execution| now.duration_since(*execution) >= THROTTLE_WINDOW)
+        {
+            account_executions.pop_front();
+        }
+        if account_executions.len() >= max_orders_per_minute {
+            return false;
+        }
+        account_executions.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_account() {
+        let throttle = AccountThrottle::new(
+            Some(2),
+            HashSet::from([FieldElement::from_hex_be("0x2").unwrap()]),
+        );
+        let now = Instant::now();
+
+        assert!(throttle.allow_at("1", now));
+        assert!(throttle.allow_at("0x1", now));
+        assert!(!throttle.allow_at("1", now + Duration::from_secs(30)));
+        assert!(throttle.allow_at("1", now + Duration::from_secs(60)));
+
+        for _ in 0..5 {
+            assert!(throttle.allow_at("2", now));
+        }
+    }
+
+    #[test]
+    fn test_throttle_disabled() {
+        let throttle = AccountThrottle::default();
+        for _ in 0..5 {
+            assert!(throttle.allow("1"));
+        }
+    }
+}
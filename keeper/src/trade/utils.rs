@@ -1,19 +1,35 @@
-use std::{env, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
 
 use cainome::cairo_serde::{ContractAddress, U256};
-use starknet::{
-    accounts::{Call, SingleOwnerAccount},
-    core::types::FieldElement,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
-    signers::LocalWallet,
-};
+use starknet::{accounts::Call, core::types::FieldElement};
 
 use crate::{
-    trade::order::handle::{DataStore, Market, Oracle},
-    trade::price::utils::{get_pragma_price, PathParams, QueryParams},
+    clock::{check_clock_skew, get_block_timestamp, get_system_timestamp},
+    contracts::{Contracts, SetPricesTemplate},
+    error::KeeperError,
+    quality::{record_oracle_reading, OracleReading},
+    trade::order::handle::Market,
+    trade::price::{
+        feeds::FeedId,
+        stable::get_stable_price,
+        tokens::to_oracle_price,
+        utils::{get_pragma_price, PathParams, PriceInfo, QueryParams},
+    },
     types::SatoruAction,
 };
 
+// The prices fetched at the latest block timestamp, by base and quote.
+type BlockPrices = (u64, HashMap<(String, String), PriceInfo>);
+
+static BLOCK_PRICES: OnceLock<Mutex<BlockPrices>> = OnceLock::new();
+
+fn block_prices() -> &'static Mutex<BlockPrices> {
+    BLOCK_PRICES.get_or_init(Default::default)
+}
+
 pub fn get_token_name_from_address(token_address: ContractAddress) -> String {
     match token_address {
         x if x
@@ -40,40 +56,210 @@ pub fn get_token_name_from_address(token_address: ContractAddress) -> String {
     }
 }
 
+// Builds the call setting the primary price of the market, stable tokens being priced as the
+// protocol prices them and other tokens at their feed price.
 pub async fn get_set_primary_price_call(
-    trade: SatoruAction,
-    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
-) -> Call {
-    let data_store_address = env::var("DATA_STORE").expect("DATA_STORE env variable not set");
-    let data_store = DataStore::new(
-        FieldElement::from_hex_be(&data_store_address)
-            .expect("Conversion error: data_store_address"),
-        account.clone(),
-    );
-
-    let oracle_address = env::var("ORACLE").expect("ORACLE env variable not set");
-    let oracle = Oracle::new(
-        FieldElement::from_hex_be(&oracle_address).expect("Conversion error: oracle_address"),
-        account.clone(),
-    );
+    trade: &SatoruAction,
+    contracts: &Contracts,
+) -> Result<Call, KeeperError> {
+    let (market, price) = get_primary_price(trade, contracts).await?;
+    Ok(contracts
+        .oracle
+        .set_primary_price_getcall(&market.long_token, &price))
+}
 
-    let market = data_store
-        .get_market(&ContractAddress::from(
-            FieldElement::from_hex_be(&trade.key).expect("Cannot convert string to felt"),
-        ))
+// Returns the market of an action with the primary price its execution gets, the price of its
+// long token.
+pub async fn get_primary_price(
+    trade: &SatoruAction,
+    contracts: &Contracts,
+) -> Result<(Market, U256), KeeperError> {
+    let market_address = FieldElement::from_hex_be(&trade.market).map_err(|e| {
+        KeeperError::ExecutionError(format!("invalid market {}: {:?}", trade.market, e))
+    })?;
+    let market = contracts
+        .data_store
+        .get_market(&ContractAddress::from(market_address))
         .call()
         .await
-        .expect("Could not get market");
+        .map_err(|e| {
+            KeeperError::ExecutionError(format!("could not get market {}: {:?}", trade.market, e))
+        })?;
+
+    let price = match get_stable_price(contracts, market.long_token).await? {
+        Some(price) => price,
+        None => get_feed_price(trade, contracts, &market).await?,
+    };
+    Ok((market, price))
+}
+
+// Fetches the price of the long token of a market at the latest block time, rejecting it if the
+// feed returns one timestamped too far from it.
+async fn get_feed_price(
+    trade: &SatoruAction,
+    contracts: &Contracts,
+    market: &Market,
+) -> Result<U256, KeeperError> {
+    // Markets mapped to feeds get the price of their long token read from its feed.
+    let (base, quote) = match contracts
+        .market_feeds
+        .get(&trade.market)
+        .map(|feed| &feed.long.feed)
+    {
+        Some(feed) => get_pragma_pair(feed, &trade.market)?,
+        None => (
+            get_token_name_from_address(market.long_token),
+            "usd".to_owned(),
+        ),
+    };
+    let block_timestamp = get_block_timestamp(&contracts.account).await?;
+    let price_info = get_checked_price_info(contracts, block_timestamp, base, quote).await?;
+    let price_timestamp = price_info.timestamp_secs();
+    record_oracle_reading(
+        &trade.key,
+        OracleReading {
+            block_timestamp,
+            price_timestamp,
+        },
+    );
+    // Prices of tokens missing from the registry are sent as returned by the feed.
+    let price = to_price(&price_info)?;
+    Ok(match contracts.token_registry.get(market.long_token) {
+        Some(token) => {
+            to_oracle_price(&token.to_protocol_price(price.low, price_info.decimals as u32)?)?
+        }
+        None => price,
+    })
+}
+
+// Returns the SetPricesParams fields an action gets executed with, the tokens of markets mapped to
+// feeds getting their fetched prices, spread into min and max prices. The fields of other markets
+// are sent as configured.
+pub async fn get_priced_template(
+    trade: &SatoruAction,
+    contracts: &Contracts,
+) -> Result<SetPricesTemplate, KeeperError> {
+    let template = contracts.set_prices_for(&trade.market);
+    let feed = match contracts.market_feeds.get(&trade.market) {
+        Some(feed) => feed,
+        None => return Ok(template.clone()),
+    };
+    let block_timestamp = get_block_timestamp(&contracts.account).await?;
+    let (mut prices, mut timestamps) = (vec![], vec![]);
+    for token in &template.tokens {
+        let token_feed = [&feed.index, &feed.long, &feed.short]
+            .into_iter()
+            .find(|token_feed| ContractAddress::from(token_feed.token) == *token)
+            .ok_or_else(|| {
+                KeeperError::FeedError(format!(
+                    "no feed for token {:#x} of market {}",
+                    FieldElement::from(*token),
+                    trade.market
+                ))
+            })?;
+        // Feed tokens are checked against the registry at startup.
+        let token_info = contracts.token_registry.get(*token).ok_or_else(|| {
+            KeeperError::FeedError(format!(
+                "token {:#x} missing from TOKENS",
+                FieldElement::from(*token)
+            ))
+        })?;
+        let (base, quote) = get_pragma_pair(&token_feed.feed, &trade.market)?;
+        let price_info = get_checked_price_info(contracts, block_timestamp, base, quote).await?;
+        prices.push(
+            token_info
+                .to_compacted_price(to_price(&price_info)?.low, price_info.decimals as u32)?,
+        );
+        timestamps.push(price_info.timestamp_secs());
+    }
+    template.with_prices(
+        &contracts.spreads,
+        &contracts.token_registry,
+        &prices,
+        timestamps,
+    )
+}
 
-    let price = price_setup(trade.block_number, market.clone()).await; // TODO use timestamp instead of block number
+// Returns the Pragma base and quote of a feed.
+// @market: The market the feed prices, for errors.
+#[cfg_attr(not(feature = "pyth"), allow(unused_variables))]
+fn get_pragma_pair(feed: &FeedId, market: &str) -> Result<(String, String), KeeperError> {
+    match feed {
+        FeedId::Pragma { base, quote } => Ok((base.clone(), quote.clone())),
+        #[cfg(feature = "pyth")]
+        FeedId::Pyth { price_id } => Err(KeeperError::ExecutionError(format!(
+            "no client for the Pyth feed {} of market {}",
+            price_id, market
+        ))),
+    }
+}
 
-    oracle.set_primary_price_getcall(&market.long_token, &price)
+// Fetches the price of a pair at a block timestamp, rejecting it when timestamped too far from the
+// block time or out of its bounds.
+// @block_timestamp: The latest block timestamp.
+async fn get_checked_price_info(
+    contracts: &Contracts,
+    block_timestamp: u64,
+    base: String,
+    quote: String,
+) -> Result<PriceInfo, KeeperError> {
+    let price_info = if contracts.share_block_prices {
+        get_block_price_info(block_timestamp, base, quote).await?
+    } else {
+        get_price_info(block_timestamp, base, quote).await?
+    };
+    // Pragma timestamps are in milliseconds, block timestamps in seconds.
+    check_clock_skew(
+        get_system_timestamp(),
+        block_timestamp,
+        Some(price_info.timestamp_secs()),
+        contracts.max_clock_skew_secs,
+    )?;
+    contracts.price_bounds.check(&price_info)?;
+    Ok(price_info)
 }
 
-pub async fn price_setup(timestamp: u64, market: Market) -> U256 {
+// Returns the price of a pair at a block timestamp, fetched once for all the executions priced at
+// it so that they all get the same price, the first one fetched being kept.
+// @block_timestamp: The latest block timestamp.
+async fn get_block_price_info(
+    block_timestamp: u64,
+    base: String,
+    quote: String,
+) -> Result<PriceInfo, KeeperError> {
+    let pair = (base, quote);
+    {
+        let prices = block_prices().lock().unwrap();
+        if prices.0 == block_timestamp {
+            if let Some(price_info) = prices.1.get(&pair) {
+                return Ok(price_info.clone());
+            }
+        }
+    }
+    let price_info = get_price_info(block_timestamp, pair.0.clone(), pair.1.clone()).await?;
+    let mut prices = block_prices().lock().unwrap();
+    if prices.0 < block_timestamp {
+        *prices = (block_timestamp, HashMap::new());
+    }
+    if prices.0 > block_timestamp {
+        return Ok(price_info);
+    }
+    Ok(prices.1.entry(pair).or_insert(price_info).clone())
+}
+
+pub async fn price_setup(timestamp: u64, market: Market) -> Result<U256, KeeperError> {
+    let base = get_token_name_from_address(market.long_token);
+    to_price(&get_price_info(timestamp, base, "usd".to_owned()).await?)
+}
+
+async fn get_price_info(
+    timestamp: u64,
+    base: String,
+    quote: String,
+) -> Result<PriceInfo, KeeperError> {
     let path = PathParams {
-        base: get_token_name_from_address(market.long_token).to_owned(),
-        quote: "usd".to_owned(),
+        base,
+        quote,
         timestamp: timestamp,
         interval: "1min".to_owned(),
     };
@@ -83,21 +269,31 @@ pub async fn price_setup(timestamp: u64, market: Market) -> U256 {
         aggregation: "median".to_owned(),
     };
 
-    let price_info = get_pragma_price(path, query)
+    let pair = format!("{}/{}", path.base, path.quote);
+    get_pragma_price(path, query)
         .await
-        .expect("Price did not get returned");
+        .map_err(|e| KeeperError::ExecutionError(format!("could not get {} price: {}", pair, e)))
+}
 
+fn to_price(price_info: &PriceInfo) -> Result<U256, KeeperError> {
     let price_uint = u128::from_str_radix(price_info.price.as_str().trim_start_matches("0x"), 16)
-        .expect("Could not convert hex price to uint");
+        .map_err(|e| {
+        KeeperError::ExecutionError(format!(
+            "invalid {} price {}: {}",
+            price_info.pair_id, price_info.price, e
+        ))
+    })?;
 
-    U256 {
+    Ok(U256 {
         low: price_uint,
         high: 0,
-    }
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::env;
+
     use crate::trade::price::error::PragmaAPIError;
 
     use super::*;
@@ -128,11 +324,32 @@ mod tests {
                     ),
                 };
 
-                let price = price_setup(1711110660, market).await;
+                let price = price_setup(1711110660, market).await.unwrap();
 
                 assert!(price > U256 { low: 3000, high: 0 })
             }
             Err(_) => {}
         }
     }
+
+    #[tokio::test]
+    async fn test_block_prices_shared() {
+        let price_info = PriceInfo {
+            decimals: 8,
+            num_sources_aggregated: 4,
+            pair_id: "BTC/USD".to_owned(),
+            price: "0x4f8b06508e".to_owned(),
+            timestamp: 1711110660000,
+        };
+        *block_prices().lock().unwrap() = (
+            1711110660,
+            HashMap::from([(("btc".to_owned(), "usd".to_owned()), price_info)]),
+        );
+        // The executions priced at the block get the price fetched for it, without fetching it.
+        let shared = get_block_price_info(1711110660, "btc".to_owned(), "usd".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(shared.price, "0x4f8b06508e");
+        assert_eq!(to_price(&shared).unwrap().low, 0x4f8b06508e);
+    }
 }
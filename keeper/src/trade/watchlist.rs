@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use starknet::core::types::FieldElement;
+
+use crate::types::SatoruAction;
+
+// An enum representing how the index price has to move past the trigger price of an order for it
+// to execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriggerDirection {
+    // The order executes once the price is at or below its trigger price.
+    Below,
+    // The order executes once the price is at or above its trigger price.
+    Above,
+}
+
+// Returns the trigger direction of an order, None for the orders without trigger price.
+// @order_type: The order type.
+// @is_long: Whether the order is on the long side.
+pub fn get_trigger_direction(order_type: &str, is_long: bool) -> Option<TriggerDirection> {
+    match (order_type, is_long) {
+        ("LimitIncrease", true) | ("LimitDecrease", false) | ("StopLossDecrease", true) => {
+            Some(TriggerDirection::Below)
+        }
+        ("LimitIncrease", false) | ("LimitDecrease", true) | ("StopLossDecrease", false) => {
+            Some(TriggerDirection::Above)
+        }
+        _ => None,
+    }
+}
+
+// The watched orders of a market, keyed by trigger price.
+#[derive(Debug, Default)]
+struct MarketWatchlist {
+    below: BTreeMap<u128, BTreeSet<String>>,
+    above: BTreeMap<u128, BTreeSet<String>>,
+}
+
+impl MarketWatchlist {
+    fn side(&mut self, direction: TriggerDirection) -> &mut BTreeMap<u128, BTreeSet<String>> {
+        match direction {
+            TriggerDirection::Below => &mut self.below,
+            TriggerDirection::Above => &mut self.above,
+        }
+    }
+}
+
+// A struct representing the trigger orders waiting for the index price of their market to cross
+// their trigger price. Orders are sorted by trigger price per market and direction, so a price
+// update only visits the orders it crossed rather than every pending order.
+// @markets: The watched orders per market.
+// @orders: The market, direction and trigger price of each watched order, by key.
+#[derive(Debug, Default)]
+pub struct TriggerWatchlist {
+    markets: HashMap<FieldElement, MarketWatchlist>,
+    orders: HashMap<String, (FieldElement, TriggerDirection, u128)>,
+}
+
+impl TriggerWatchlist {
+    pub fn new() -> Self {
+        TriggerWatchlist::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    // Watches a trigger order, an updated order replacing its previous trigger price. Returns
+    // false for the orders without trigger price, which are not watched.
+    // @order: The order to watch.
+    pub fn insert(&mut self, order: &SatoruAction) -> bool {
+        let direction = match order
+            .order_type
+            .as_deref()
+            .and_then(|order_type| get_trigger_direction(order_type, order.is_long?))
+        {
+            Some(direction) => direction,
+            None => return false,
+        };
+        let (market, trigger_price) = match (
+            FieldElement::from_hex_be(&order.market),
+            order.trigger_price,
+        ) {
+            (Ok(market), Some(trigger_price)) => (market, trigger_price),
+            _ => return false,
+        };
+        self.remove(&order.key);
+        self.markets
+            .entry(market)
+            .or_default()
+            .side(direction)
+            .entry(trigger_price)
+            .or_default()
+            .insert(order.key.clone());
+        self.orders
+            .insert(order.key.clone(), (market, direction, trigger_price));
+        true
+    }
+
+    // Stops watching an order, e.g. once executed or cancelled. Returns whether it was watched.
+    // @key: The order key.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let (market, direction, trigger_price) = match self.orders.remove(key) {
+            Some(order) => order,
+            None => return false,
+        };
+        if let Some(watchlist) = self.markets.get_mut(&market) {
+            let side = watchlist.side(direction);
+            if let Some(keys) = side.get_mut(&trigger_price) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    side.remove(&trigger_price);
+                }
+            }
+            if watchlist.below.is_empty() && watchlist.above.is_empty() {
+                self.markets.remove(&market);
+            }
+        }
+        true
+    }
+
+    // Returns the keys of the orders of a market crossed by its index price, lowest trigger
+    // prices first on each side.
+    // @market: The market token address.
+    // @price: The index price, in the same unit as the trigger prices.
+    pub fn crossed(&self, market: FieldElement, price: u128) -> Vec<String> {
+        let watchlist = match self.markets.get(&market) {
+            Some(watchlist) => watchlist,
+            None => return vec![],
+        };
+        watchlist
+            .below
+            .range(price..)
+            .chain(watchlist.above.range(..=price))
+            .flat_map(|(_, keys)| keys.iter().cloned())
+            .collect()
+    }
+
+    // Removes the orders of a market crossed by its index price from the watchlist, returning
+    // their keys.
+    // @market: The market token address.
+    // @price: The index price, in the same unit as the trigger prices.
+    pub fn take_crossed(&mut self, market: FieldElement, price: u128) -> Vec<String> {
+        let keys = self.crossed(market, price);
+        for key in &keys {
+            self.remove(key);
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(key: &str, order_type: &str, is_long: bool, trigger_price: u128) -> SatoruAction {
+        SatoruAction {
+            key: key.to_owned(),
+            market: "0x12".to_owned(),
+            order_type: Some(order_type.to_owned()),
+            is_long: Some(is_long),
+            trigger_price: Some(trigger_price),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_trigger_direction() {
+        assert_eq!(
+            get_trigger_direction("LimitIncrease", true),
+            Some(TriggerDirection::Below)
+        );
+        assert_eq!(
+            get_trigger_direction("StopLossDecrease", false),
+            Some(TriggerDirection::Above)
+        );
+        assert_eq!(get_trigger_direction("MarketIncrease", true), None);
+        assert_eq!(get_trigger_direction("LimitSwap", true), None);
+    }
+
+    #[test]
+    fn test_crossed() {
+        let market = FieldElement::from_hex_be("0x12").unwrap();
+        let mut watchlist = TriggerWatchlist::new();
+        assert!(watchlist.insert(&order("1", "LimitIncrease", true, 100)));
+        assert!(watchlist.insert(&order("2", "LimitIncrease", true, 90)));
+        assert!(watchlist.insert(&order("3", "LimitDecrease", true, 120)));
+        assert!(!watchlist.insert(&order("4", "MarketIncrease", true, 0)));
+        assert_eq!(watchlist.len(), 3);
+
+        assert!(watchlist.crossed(market, 105).is_empty());
+        assert_eq!(watchlist.crossed(market, 95), vec!["1"]);
+        assert_eq!(watchlist.crossed(market, 90), vec!["2", "1"]);
+        assert_eq!(watchlist.crossed(market, 120), vec!["3"]);
+        assert!(watchlist
+            .crossed(FieldElement::from_hex_be("0x13").unwrap(), 90)
+            .is_empty());
+
+        assert_eq!(watchlist.take_crossed(market, 95), vec!["1"]);
+        assert_eq!(watchlist.len(), 2);
+    }
+
+    #[test]
+    fn test_update_and_remove() {
+        let market = FieldElement::from_hex_be("0x12").unwrap();
+        let mut watchlist = TriggerWatchlist::new();
+        watchlist.insert(&order("1", "LimitIncrease", true, 100));
+        watchlist.insert(&order("1", "LimitIncrease", true, 80));
+        assert_eq!(watchlist.len(), 1);
+        assert!(watchlist.crossed(market, 95).is_empty());
+
+        assert!(watchlist.remove("1"));
+        assert!(!watchlist.remove("1"));
+        assert!(watchlist.is_empty());
+        assert!(watchlist.crossed(market, 0).is_empty());
+    }
+}
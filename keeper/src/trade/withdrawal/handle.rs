@@ -1,17 +1,17 @@
-use std::{env, sync::Arc, vec};
+use std::vec;
 
-use cainome::{
-    cairo_serde::{ContractAddress, U256},
-    rs::abigen,
-};
-use starknet::{
-    accounts::{Account, Call, SingleOwnerAccount},
-    core::types::FieldElement,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
-    signers::LocalWallet,
-};
+use cainome::rs::abigen;
+use starknet::{accounts::Call, core::types::FieldElement};
 
-use crate::{trade::utils::get_set_primary_price_call, types::SatoruAction};
+use crate::{
+    contracts::{Contracts, SetPricesTemplate},
+    error::KeeperError,
+    trade::{
+        oracle::{fetch_oracle_block_window, OracleBlockWindow},
+        utils::{get_priced_template, get_set_primary_price_call},
+    },
+    types::SatoruAction,
+};
 
 abigen!(
     WithdrawalHandler,
@@ -36,73 +36,59 @@ abigen!(
     }
 );
 
-pub async fn handle_withdrawal(
-    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
+// Builds the multicall executing a withdrawal, setting the oracle price first, surrounded by the
+// calls of the execution hooks.
+pub async fn get_withdrawal_calls(
+    contracts: &Contracts,
     withdrawal: SatoruAction,
-) {
-    let set_price_call = get_set_primary_price_call(withdrawal.clone(), account.clone()).await;
+) -> Result<Vec<Call>, KeeperError> {
+    let set_price_call = get_set_primary_price_call(&withdrawal, contracts).await?;
 
-    let execute_withdrawal_call = get_execute_withdrawal_call(withdrawal, account.clone());
+    let template = get_priced_template(&withdrawal, contracts).await?;
+    let oracle_block_window = fetch_oracle_block_window(&contracts.account, &withdrawal).await?;
+    let execute_withdrawal_call =
+        get_execute_withdrawal_call(&withdrawal, contracts, &template, oracle_block_window);
 
-    let _withdrawal_execution_multicall = account
-        .execute(vec![set_price_call, execute_withdrawal_call])
-        .send()
-        .await
-        .expect("Withdrawal execution multicall failed");
-    // TODO: poll transaction status
+    Ok(contracts.hooks.compose(
+        "withdrawals",
+        &withdrawal,
+        vec![set_price_call, execute_withdrawal_call],
+    ))
 }
 
-fn get_execute_withdrawal_call(
-    withdrawal: SatoruAction,
-    account: Arc<SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet>>,
-) -> Call {
-    let withdrawal_handler_address =
-        env::var("WITHDRAWAL_HANDLER").expect("WITHDRAWAL_HANDLER env variable not set");
-    let withdrawal_handler = WithdrawalHandler::new(
-        FieldElement::from_hex_be(&withdrawal_handler_address)
-            .expect("Conversion error: withdrawal_handler_address"),
-        account.clone(),
-    );
-
-    let set_prices_params: SetPricesParams = SetPricesParams {
-        signer_info: U256 { low: 1, high: 0 },
-        tokens: vec![
-            ContractAddress::from(
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ),
-            ContractAddress::from(
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ),
-        ],
-        compacted_min_oracle_block_numbers: vec![63970, 63970],
-        compacted_max_oracle_block_numbers: vec![64901, 64901],
-        compacted_oracle_timestamps: vec![171119803, 10],
-        compacted_decimals: vec![U256 { low: 1, high: 0 }, U256 { low: 1, high: 0 }],
-        compacted_min_prices: vec![U256 {
-            low: 2147483648010000,
-            high: 0,
-        }],
-        compacted_min_prices_indexes: vec![U256 { low: 0, high: 0 }],
-        compacted_max_prices: vec![U256 {
-            low: 2147483648010000,
-            high: 0,
-        }],
-        compacted_max_prices_indexes: vec![U256 { low: 0, high: 0 }],
-        signatures: vec![
-            vec![
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ],
-            vec![
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-                FieldElement::from_hex_be("0x").expect("Cannot convert string to felt"),
-            ],
-        ],
+// Completes the constant SetPricesParams fields with the oracle block numbers of an action.
+// @template: The constant fields.
+// @oracle_block_window: The oracle block window of the action.
+fn to_set_prices_params(
+    template: &SetPricesTemplate,
+    oracle_block_window: OracleBlockWindow,
+) -> SetPricesParams {
+    let (compacted_min_oracle_block_numbers, compacted_max_oracle_block_numbers) =
+        oracle_block_window.compacted(template.tokens.len());
+    SetPricesParams {
+        signer_info: template.signer_info,
+        tokens: template.tokens.clone(),
+        compacted_min_oracle_block_numbers,
+        compacted_max_oracle_block_numbers,
+        compacted_oracle_timestamps: template.compacted_oracle_timestamps.clone(),
+        compacted_decimals: template.compacted_decimals.clone(),
+        compacted_min_prices: template.compacted_min_prices.clone(),
+        compacted_min_prices_indexes: template.compacted_min_prices_indexes.clone(),
+        compacted_max_prices: template.compacted_max_prices.clone(),
+        compacted_max_prices_indexes: template.compacted_max_prices_indexes.clone(),
+        signatures: template.signatures.clone(),
         price_feed_tokens: vec![],
-    };
+    }
+}
 
-    withdrawal_handler.execute_withdrawal_getcall(
+fn get_execute_withdrawal_call(
+    withdrawal: &SatoruAction,
+    contracts: &Contracts,
+    template: &SetPricesTemplate,
+    oracle_block_window: OracleBlockWindow,
+) -> Call {
+    contracts.withdrawal_handler.execute_withdrawal_getcall(
         &FieldElement::from_hex_be(&withdrawal.key).expect("Cannot convert string to felt"),
-        &set_prices_params,
+        &to_set_prices_params(template, oracle_block_window),
     )
 }
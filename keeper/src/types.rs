@@ -64,6 +64,32 @@ pub struct Payload {
     pub row_data: SatoruAction,
 }
 
+// A struct representing a position increase or decrease indexed, its identity only.
+// @block_number: The block of the event.
+// @transaction_hash: The transaction of the event.
+// @account: The account of the position.
+// @market: The market of the position.
+// @collateral_token: The collateral token of the position.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PositionEvent {
+    pub block_number: i64,
+    pub transaction_hash: String,
+    pub account: String,
+    pub market: String,
+    pub collateral_token: String,
+}
+
+// A struct representing the payload of a position notification.
+// @table: The table name in the database, position_increase or position_decrease.
+// @action_type: The type of action (using the ActionType enum).
+// @row_data: The position event.
+#[derive(Deserialize, Debug)]
+pub struct PositionPayload {
+    pub table: String,
+    pub action_type: ActionType,
+    pub row_data: PositionEvent,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use log::{error, info, warn};
+use satoru_client::sentry::capture_error;
+use serde::Serialize;
+use sqlx::{Error, Pool, Postgres};
+use starknet::{
+    accounts::ConnectedAccount,
+    core::{
+        types::{BlockId, EventFilter, FieldElement},
+        utils::get_selector_from_name,
+    },
+    providers::Provider,
+};
+use tokio::time::sleep;
+
+use crate::{
+    contracts::{ContractAddresses, CONTRACT_NAMES},
+    error::KeeperError,
+    executor::KeeperContext,
+};
+
+// Event of the upgradeable contracts replacing their class, its data holding the new class hash.
+const UPGRADED_EVENT: &str = "Upgraded";
+// Events fetched per page.
+const EVENTS_PAGE_SIZE: u64 = 100;
+
+fn upgrade_error(reason: String) -> KeeperError {
+    KeeperError::UpgradeCheckError(reason)
+}
+
+// A struct representing a contract upgrade the keeper detected, before it gets recorded.
+// @contract: The contract name, as its env variable.
+// @address: The contract address.
+// @previous_class_hash: The class hash the contract had before, if known.
+// @class_hash: The class hash the contract got upgraded to.
+// @block_number: The block of the Upgraded event, None when caught by its class hash only.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedUpgrade {
+    pub contract: &'static str,
+    pub address: FieldElement,
+    pub previous_class_hash: Option<FieldElement>,
+    pub class_hash: FieldElement,
+    pub block_number: Option<u64>,
+}
+
+// A struct representing a contract upgrade, as stored in the keeper_contract_upgrades table.
+// Transactions stop until an operator acknowledged it, so the keeper never executes against logic
+// nobody reviewed.
+// @id: The id of the upgrade, to acknowledge it with.
+// @contract: The contract name, as its env variable.
+// @address: The contract address.
+// @previous_class_hash: The class hash the contract had before, if known.
+// @class_hash: The class hash the contract got upgraded to.
+// @block_number: The block of the Upgraded event, None when caught by its class hash only.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct ContractUpgrade {
+    pub id: i64,
+    pub contract: String,
+    pub address: String,
+    pub previous_class_hash: Option<String>,
+    pub class_hash: String,
+    pub block_number: Option<i64>,
+}
+
+impl ContractUpgrade {
+    // Describes the upgrade, as reported with the paused jobs.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} upgraded to class hash {}, acknowledge upgrade {} to resume",
+            self.contract, self.class_hash, self.id
+        )
+    }
+}
+
+// A struct representing the watch over the class hashes of the keeper contracts, catching their
+// upgrades from the Upgraded events they emit and, for classes replaced without one, from their
+// class hash.
+// @contracts: The contracts watched, by name.
+// @class_hashes: The class hash last seen for each contract address.
+// @next_block: The first block the events did not get read from yet, None before the first poll.
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeMonitor {
+    pub contracts: Vec<(&'static str, FieldElement)>,
+    class_hashes: HashMap<FieldElement, FieldElement>,
+    next_block: Option<u64>,
+}
+
+impl UpgradeMonitor {
+    // @contracts: The contracts watched, by name.
+    pub fn new(contracts: Vec<(&'static str, FieldElement)>) -> Self {
+        UpgradeMonitor {
+            contracts,
+            ..UpgradeMonitor::default()
+        }
+    }
+
+    // Watches the keeper contracts at the addresses returned by load_contracts.
+    // @addresses: The keeper contract addresses.
+    pub fn from_addresses(addresses: &ContractAddresses) -> Result<Self, KeeperError> {
+        let contracts = CONTRACT_NAMES
+            .iter()
+            .map(|name| {
+                addresses
+                    .get(name)
+                    .map(|address| (*name, address))
+                    .map_err(|_| upgrade_error(format!("no address for {}", name)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(UpgradeMonitor::new(contracts))
+    }
+
+    // Records the class hash of a contract, returns the upgrade when it changed. The first class
+    // hash seen for a contract is the one it is expected to keep.
+    // @contract: The contract name.
+    // @address: The contract address.
+    // @class_hash: The class hash read, or emitted with an Upgraded event.
+    // @block_number: The block of the Upgraded event, if any.
+    pub fn observe(
+        &mut self,
+        contract: &'static str,
+        address: FieldElement,
+        class_hash: FieldElement,
+        block_number: Option<u64>,
+    ) -> Option<DetectedUpgrade> {
+        match self.class_hashes.insert(address, class_hash) {
+            Some(previous) if previous != class_hash => Some(DetectedUpgrade {
+                contract,
+                address,
+                previous_class_hash: Some(previous),
+                class_hash,
+                block_number,
+            }),
+            Some(_) => None,
+            // An Upgraded event before any class hash got read still is an upgrade.
+            None if block_number.is_some() => Some(DetectedUpgrade {
+                contract,
+                address,
+                previous_class_hash: None,
+                class_hash,
+                block_number,
+            }),
+            None => None,
+        }
+    }
+
+    // Reads the Upgraded events a contract emitted over a range of blocks, oldest first.
+    // @provider: The provider used to read the events.
+    // @address: The contract address.
+    // @from_block: The first block of the range.
+    // @to_block: The last block of the range.
+    async fn read_upgraded_events<P: Provider + Sync>(
+        provider: &P,
+        address: FieldElement,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<(u64, FieldElement)>, KeeperError> {
+        let selector = get_selector_from_name(UPGRADED_EVENT).expect("Invalid event name");
+        let mut events = vec![];
+        let mut continuation_token = None;
+        loop {
+            let filter = EventFilter {
+                from_block: Some(BlockId::Number(from_block)),
+                to_block: Some(BlockId::Number(to_block)),
+                address: Some(address),
+                keys: Some(vec![vec![selector]]),
+            };
+            let page = provider
+                .get_events(filter, continuation_token, EVENTS_PAGE_SIZE)
+                .await
+                .map_err(|e| upgrade_error(format!("could not read events: {:?}", e)))?;
+            events.extend(page.events.iter().filter_map(|event| {
+                // Components emitting the class hash as a key rather than as data are covered too.
+                let class_hash = event.data.first().or_else(|| event.keys.get(1))?;
+                Some((event.block_number.unwrap_or(to_block), *class_hash))
+            }));
+            match page.continuation_token {
+                Some(token) => continuation_token = Some(token),
+                None => return Ok(events),
+            }
+        }
+    }
+
+    // Reads the Upgraded events emitted since the previous poll and the class hashes of the
+    // contracts, returns the upgrades they show.
+    // @provider: The provider used to read the chain.
+    pub async fn poll<P: Provider + Sync>(
+        &mut self,
+        provider: &P,
+    ) -> Result<Vec<DetectedUpgrade>, KeeperError> {
+        let latest_block = provider
+            .block_number()
+            .await
+            .map_err(|e| upgrade_error(format!("could not read block number: {:?}", e)))?;
+        let mut upgrades = vec![];
+        let contracts = self.contracts.clone();
+        if let Some(from_block) = self.next_block.filter(|block| *block <= latest_block) {
+            for (contract, address) in contracts.iter() {
+                for (block_number, class_hash) in
+                    Self::read_upgraded_events(provider, *address, from_block, latest_block).await?
+                {
+                    upgrades.extend(self.observe(
+                        contract,
+                        *address,
+                        class_hash,
+                        Some(block_number),
+                    ));
+                }
+            }
+        }
+        for (contract, address) in contracts.iter() {
+            let class_hash = provider
+                .get_class_hash_at(BlockId::Number(latest_block), *address)
+                .await
+                .map_err(|e| {
+                    upgrade_error(format!("could not get class hash of {}: {:?}", contract, e))
+                })?;
+            upgrades.extend(self.observe(contract, *address, class_hash, None));
+        }
+        self.next_block = Some(latest_block + 1);
+        Ok(upgrades)
+    }
+}
+
+// Records a detected upgrade, returns whether it is new, every instance sharing the database
+// detecting the same ones.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @upgrade: The upgrade detected.
+pub async fn record_upgrade(
+    pool: &Pool<Postgres>,
+    upgrade: &DetectedUpgrade,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO keeper_contract_upgrades (contract, address, previous_class_hash, class_hash,
+             block_number)
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (address, class_hash) DO NOTHING",
+    )
+    .bind(upgrade.contract)
+    .bind(format!("{:#x}", upgrade.address))
+    .bind(
+        upgrade
+            .previous_class_hash
+            .map(|class_hash| format!("{:#x}", class_hash)),
+    )
+    .bind(format!("{:#x}", upgrade.class_hash))
+    .bind(upgrade.block_number.map(|block| block as i64))
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Loads the upgrades no operator acknowledged yet, oldest first.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn load_pending_upgrades(pool: &Pool<Postgres>) -> Result<Vec<ContractUpgrade>, Error> {
+    sqlx::query_as::<_, ContractUpgrade>(
+        "SELECT id, contract, address, previous_class_hash, class_hash, block_number
+         FROM keeper_contract_upgrades WHERE acknowledged_at IS NULL ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Acknowledges an upgrade once its logic got reviewed, returns whether it was pending.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @id: The id of the upgrade.
+pub async fn acknowledge_upgrade(pool: &Pool<Postgres>, id: i64) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE keeper_contract_upgrades SET acknowledged_at = NOW()
+         WHERE id = $1 AND acknowledged_at IS NULL",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Watches the keeper contracts for upgrades every interval, forever. Each new upgrade gets
+// recorded and alerted on, the kill switch then holding the transactions of every instance until
+// an operator acknowledges it. Upgrades that could not be recorded are retried at the next poll.
+// @context: The keeper context.
+// @interval: The delay between two polls, upgrades only caught at startup when None.
+pub async fn run_upgrade_monitor(context: Arc<KeeperContext>, interval: Option<Duration>) {
+    let interval = match interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    let mut monitor = UpgradeMonitor::from_addresses(&context.contracts.addresses)
+        .expect("Could not watch contract upgrades.");
+    let mut unrecorded: Vec<DetectedUpgrade> = vec![];
+    loop {
+        match monitor.poll(context.account.provider()).await {
+            Ok(upgrades) => unrecorded.extend(upgrades),
+            Err(e) => error!("{}", e),
+        }
+        let mut failed = vec![];
+        for upgrade in unrecorded.drain(..) {
+            match record_upgrade(&context.pool, &upgrade).await {
+                Ok(true) => {
+                    warn!(
+                        "ALERT: {} at {:#x} upgraded to class hash {:#x}, stopping transactions until acknowledged",
+                        upgrade.contract, upgrade.address, upgrade.class_hash
+                    );
+                    capture_error(
+                        &format!("contract upgraded to {:#x}", upgrade.class_hash),
+                        &[("contract", upgrade.contract)],
+                    );
+                }
+                Ok(false) => info!(
+                    "{} upgrade to {:#x} already recorded",
+                    upgrade.contract, upgrade.class_hash
+                ),
+                Err(e) => {
+                    error!("Could not record {} upgrade: {:?}", upgrade.contract, e);
+                    failed.push(upgrade);
+                }
+            }
+        }
+        unrecorded = failed;
+        match load_pending_upgrades(&context.pool).await {
+            Ok(upgrades) => context.kill_switch.set_pending_upgrades(upgrades),
+            Err(e) => error!("Could not load contract upgrades: {:?}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe_upgrades() {
+        let address = FieldElement::from(0x12_u8);
+        let (one, two) = (FieldElement::ONE, FieldElement::TWO);
+        let mut monitor = UpgradeMonitor::new(vec![("ORDER_HANDLER", address)]);
+        // The first class hash read is the expected one.
+        assert_eq!(monitor.observe("ORDER_HANDLER", address, one, None), None);
+        assert_eq!(monitor.observe("ORDER_HANDLER", address, one, None), None);
+        assert_eq!(
+            monitor.observe("ORDER_HANDLER", address, two, Some(7)),
+            Some(DetectedUpgrade {
+                contract: "ORDER_HANDLER",
+                address,
+                previous_class_hash: Some(one),
+                class_hash: two,
+                block_number: Some(7),
+            })
+        );
+        // The class hash read afterwards matches the event, the upgrade is not reported twice.
+        assert_eq!(monitor.observe("ORDER_HANDLER", address, two, None), None);
+
+        let other = FieldElement::from(0x13_u8);
+        assert!(monitor
+            .observe("ORACLE", other, one, Some(8))
+            .is_some_and(|upgrade| upgrade.previous_class_hash.is_none()));
+    }
+
+    #[test]
+    fn test_describe_upgrade() {
+        let upgrade = ContractUpgrade {
+            id: 3,
+            contract: "ORDER_HANDLER".to_owned(),
+            address: "0x12".to_owned(),
+            previous_class_hash: Some("0x1".to_owned()),
+            class_hash: "0x2".to_owned(),
+            block_number: None,
+        };
+        assert_eq!(
+            upgrade.describe(),
+            "ORDER_HANDLER upgraded to class hash 0x2, acknowledge upgrade 3 to resume"
+        );
+    }
+}
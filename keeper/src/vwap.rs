@@ -0,0 +1,63 @@
+use serde::Serialize;
+use sqlx::{error::Error, Pool, Postgres};
+use starknet::core::types::FieldElement;
+
+use crate::{competition::to_indexed_address, history::scale_amount};
+
+// Decimals of the USD amounts of the protocol.
+const USD_DECIMALS: u32 = 30;
+// Most days of the VWAP series returned at once.
+pub const MAX_VWAP_DAYS: i64 = 3650;
+
+// A struct representing the volume-weighted average execution price of a market over a UTC day,
+// prices being at the index token protocol precision, weighted by the size delta in index tokens.
+// @day: The day, as YYYY-MM-DD.
+// @vwap: The VWAP of the increases and decreases.
+// @increase_vwap: The VWAP of the increases, None without increase that day.
+// @decrease_vwap: The VWAP of the decreases, None without decrease that day.
+// @increase_volume_usd: The size of the increases, in USD.
+// @decrease_volume_usd: The size of the decreases, in USD.
+// @executions: The number of increases and decreases.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct DailyVwap {
+    pub day: String,
+    pub vwap: Option<String>,
+    pub increase_vwap: Option<String>,
+    pub decrease_vwap: Option<String>,
+    pub increase_volume_usd: String,
+    pub decrease_volume_usd: String,
+    pub executions: i64,
+}
+
+// Loads the daily VWAP series of a market from the market_daily_vwap view, oldest day first, as
+// the benchmark the analytics and backtests compare executions against. Prices are rounded to
+// integers, as the protocol ones.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @market: The market token address.
+// @days: How many days back the series starts, today included.
+pub async fn get_market_vwap(
+    pool: &Pool<Postgres>,
+    market: FieldElement,
+    days: i64,
+) -> Result<Vec<DailyVwap>, Error> {
+    let mut series = sqlx::query_as::<_, DailyVwap>(
+        "SELECT day::TEXT AS day, ROUND(vwap)::TEXT AS vwap,
+             ROUND(increase_vwap)::TEXT AS increase_vwap,
+             ROUND(decrease_vwap)::TEXT AS decrease_vwap,
+             increase_volume_usd::TEXT AS increase_volume_usd,
+             decrease_volume_usd::TEXT AS decrease_volume_usd, executions
+         FROM market_daily_vwap
+         WHERE felt_out(market) = $1
+             AND day > (NOW() AT TIME ZONE 'UTC')::DATE - $2::INTEGER
+         ORDER BY day",
+    )
+    .bind(to_indexed_address(market))
+    .bind(days)
+    .fetch_all(pool)
+    .await?;
+    for day in &mut series {
+        day.increase_volume_usd = scale_amount(&day.increase_volume_usd, USD_DECIMALS);
+        day.decrease_volume_usd = scale_amount(&day.decrease_volume_usd, USD_DECIMALS);
+    }
+    Ok(series)
+}
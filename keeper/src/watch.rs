@@ -0,0 +1,848 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info};
+use satoru_client::amounts::{TokenAmount, Usd};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+use tokio::time::sleep;
+
+use crate::{
+    clock::get_system_timestamp,
+    competition::to_indexed_address,
+    config,
+    error::KeeperError,
+    liquidation::{LiquidationParams, PositionState},
+    trade::price::{
+        feeds::{FeedId, MarketFeeds},
+        utils::{get_pragma_price, PathParams, QueryParams},
+    },
+    webhooks::{check_webhook_url, get_webhook, sign_receipt, webhook_client, SIGNATURE_HEADER},
+};
+
+fn watch_error(reason: String) -> KeeperError {
+    KeeperError::WatchError(reason)
+}
+
+// A struct representing a watched account and its alert thresholds, as stored in the
+// account_watches table.
+// @account: The account, as a 0x prefixed felt like the webhook ones.
+// @max_leverage: The leverage above which its positions get alerted on, never when None.
+// @min_liquidation_distance: The distance to liquidation, as a fraction of the index price, below
+// which its positions get alerted on, never when None.
+// @telegram_chat_id: The Telegram chat alerts also get sent to, if any.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct AccountWatch {
+    pub account: String,
+    pub max_leverage: Option<f64>,
+    pub min_liquidation_distance: Option<f64>,
+    pub telegram_chat_id: Option<String>,
+}
+
+// Registers the watch of an account, replacing the previous one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @watch: The watch of the account.
+pub async fn save_watch(pool: &Pool<Postgres>, watch: &AccountWatch) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO account_watches (account, max_leverage, min_liquidation_distance,
+         telegram_chat_id) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (account) DO UPDATE SET max_leverage = $2, min_liquidation_distance = $3,
+         telegram_chat_id = $4, updated_at = NOW()",
+    )
+    .bind(&watch.account)
+    .bind(watch.max_leverage)
+    .bind(watch.min_liquidation_distance)
+    .bind(&watch.telegram_chat_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Removes the watch of an account, returns whether there was one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_watches table.
+pub async fn delete_watch(pool: &Pool<Postgres>, account: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM account_watches WHERE account = $1")
+        .bind(account)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Loads the watch of an account, if any.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_watches table.
+pub async fn get_watch(
+    pool: &Pool<Postgres>,
+    account: &str,
+) -> Result<Option<AccountWatch>, sqlx::Error> {
+    sqlx::query_as::<_, AccountWatch>(
+        "SELECT account, max_leverage, min_liquidation_distance, telegram_chat_id
+         FROM account_watches WHERE account = $1",
+    )
+    .bind(account)
+    .fetch_optional(pool)
+    .await
+}
+
+// Loads every watched account.
+// @pool: A reference to a connection pool for PostgreSQL.
+pub async fn get_watches(pool: &Pool<Postgres>) -> Result<Vec<AccountWatch>, sqlx::Error> {
+    sqlx::query_as::<_, AccountWatch>(
+        "SELECT account, max_leverage, min_liquidation_distance, telegram_chat_id
+         FROM account_watches ORDER BY created_at",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// A struct representing an open position of a watched account, derived from its indexed orders,
+// amounts being the raw indexed ones.
+// @account: The account of the position, as indexed.
+// @market: The market of the position, as indexed.
+// @collateral_token: The collateral token of the position.
+// @is_long: Whether the position is long.
+// @size_in_usd: The position size in USD, with 30 decimals.
+// @increased_usd: The size its increases added, with 30 decimals, None when none got indexed with
+// its execution price.
+// @increased_tokens: The index tokens its increases bought at their execution price.
+// @collateral_amount: The collateral deposited minus the collateral withdrawn, fees not deducted.
+// @pending_fees_usd: The borrowing fees still to be paid, with 30 decimals.
+// @index_token: The index token of the market, None when the market is not indexed.
+// @index_symbol: The symbol of the index token in the tokens table, if any.
+// @index_decimals: The decimals of the index token, None when unknown.
+// @collateral_symbol: The symbol of the collateral token in the tokens table, if any.
+// @collateral_decimals: The decimals of the collateral token, None when unknown.
+// @collateral_is_index_token: Whether the collateral is the index token.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct WatchedPosition {
+    pub account: String,
+    pub market: String,
+    pub collateral_token: String,
+    pub is_long: bool,
+    pub size_in_usd: String,
+    pub increased_usd: Option<String>,
+    pub increased_tokens: Option<String>,
+    pub collateral_amount: String,
+    pub pending_fees_usd: String,
+    pub index_token: Option<String>,
+    pub index_symbol: Option<String>,
+    pub index_decimals: Option<i32>,
+    pub collateral_symbol: Option<String>,
+    pub collateral_decimals: Option<i32>,
+    pub collateral_is_index_token: bool,
+}
+
+impl WatchedPosition {
+    // Identifies the position, alerts being cooled down per position.
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.account, self.market, self.collateral_token, self.is_long
+        )
+    }
+
+    // Returns the state of the position, its size in tokens being the one of its average entry
+    // price, None when the decimals of its tokens or the prices of its increases are unknown.
+    // @collateral_price: The price of the collateral token.
+    pub fn to_position_state(&self, collateral_price: f64) -> Option<PositionState> {
+        let usd = |amount: &str| amount.parse::<Usd>().ok().map(|usd| usd.to_usd_f64());
+        let tokens = |amount: &str, decimals: i32| {
+            amount
+                .parse::<TokenAmount>()
+                .ok()
+                .map(|amount| amount.to_tokens_f64(decimals as i64))
+        };
+        let size_in_usd = usd(&self.size_in_usd)?;
+        let increased_usd = usd(self.increased_usd.as_deref()?)?;
+        let increased_tokens = tokens(self.increased_tokens.as_deref()?, self.index_decimals?)?;
+        if increased_usd <= 0.0 || increased_tokens <= 0.0 {
+            return None;
+        }
+        Some(PositionState {
+            is_long: self.is_long,
+            size_in_usd,
+            size_in_tokens: size_in_usd * increased_tokens / increased_usd,
+            collateral_amount: tokens(&self.collateral_amount, self.collateral_decimals?)?.max(0.0),
+            collateral_price,
+            collateral_is_index_token: self.collateral_is_index_token,
+            pending_fees_usd: usd(&self.pending_fees_usd)?,
+        })
+    }
+}
+
+// Loads the open positions of an account derived from its indexed orders, largest first.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as indexed, every account when None.
+pub async fn get_watched_positions(
+    pool: &Pool<Postgres>,
+    account: Option<&str>,
+) -> Result<Vec<WatchedPosition>, sqlx::Error> {
+    // Token addresses are compared as felts, whatever their prefix and padding.
+    sqlx::query_as::<_, WatchedPosition>(
+        "WITH tokens_by_felt AS (
+             SELECT ltrim(regexp_replace(lower(address), '^0x', ''), '0') AS felt, symbol, decimals
+             FROM tokens
+         ),
+         markets_by_felt AS (
+             SELECT ltrim(felt_out(market_token), '0') AS felt, ltrim(felt_out(index_token), '0')
+             AS index_token FROM market_created
+         ),
+         executed AS (
+             SELECT o.account, o.market, o.initial_collateral_token, o.is_long, o.size_delta_usd,
+                 o.initial_collateral_delta_amount,
+                 o.order_type IN ('MarketIncrease', 'LimitIncrease') AS is_increase,
+                 pi.size_delta_usd AS executed_usd, pi.execution_price AS price
+             FROM orders o
+             JOIN order_executed oe ON oe.key = o.key
+             -- The increase the execution made, at the price it executed at.
+             LEFT JOIN position_increase pi
+                 ON o.order_type IN ('MarketIncrease', 'LimitIncrease')
+                 AND pi.transaction_hash = oe.transaction_hash
+                 AND o.account = felt_in(pi.account) AND o.market = felt_in(pi.market)
+             WHERE ($1::TEXT IS NULL OR o.account = felt_in($1))
+                 AND o.order_type IN ('MarketIncrease', 'LimitIncrease',
+                 'MarketDecrease', 'LimitDecrease', 'StopLossDecrease', 'Liquidation')
+         ),
+         positions AS (
+             SELECT felt_out(account) AS account, felt_out(market) AS market,
+                 initial_collateral_token AS collateral_token, is_long,
+                 SUM(CASE WHEN is_increase THEN size_delta_usd ELSE -size_delta_usd END)
+                 AS size_in_usd,
+                 SUM(executed_usd) FILTER (WHERE is_increase AND price > 0) AS increased_usd,
+                 SUM(executed_usd / price) FILTER (WHERE is_increase AND price > 0)
+                 AS increased_tokens,
+                 SUM(CASE WHEN is_increase THEN initial_collateral_delta_amount
+                     ELSE -initial_collateral_delta_amount END) AS collateral_amount
+             FROM executed
+             GROUP BY account, market, initial_collateral_token, is_long
+         )
+         SELECT p.account, p.market, p.collateral_token, p.is_long,
+             p.size_in_usd::TEXT AS size_in_usd, p.increased_usd::TEXT AS increased_usd,
+             p.increased_tokens::TEXT AS increased_tokens,
+             COALESCE(p.collateral_amount, 0)::TEXT AS collateral_amount,
+             COALESCE(b.pending_borrowing_fees_usd, 0)::TEXT AS pending_fees_usd,
+             m.index_token, ti.symbol AS index_symbol, ti.decimals AS index_decimals,
+             tc.symbol AS collateral_symbol, tc.decimals AS collateral_decimals,
+             COALESCE(ltrim(regexp_replace(lower(p.collateral_token), '^0x', ''), '0')
+                 = m.index_token, FALSE) AS collateral_is_index_token
+         FROM positions p
+         LEFT JOIN position_borrowing_fees b ON b.account = p.account AND b.market = p.market
+             AND b.collateral_token = p.collateral_token AND b.is_long = p.is_long
+         LEFT JOIN markets_by_felt m ON m.felt = ltrim(p.market, '0')
+         LEFT JOIN tokens_by_felt ti ON ti.felt = m.index_token
+         LEFT JOIN tokens_by_felt tc
+             ON tc.felt = ltrim(regexp_replace(lower(p.collateral_token), '^0x', ''), '0')
+         WHERE p.size_in_usd > 0
+         ORDER BY p.size_in_usd DESC",
+    )
+    .bind(account)
+    .fetch_all(pool)
+    .await
+}
+
+// A struct representing how risky a position is at the current index price.
+// @price: The index price.
+// @leverage: The size of the position over its collateral after PnL and pending fees, infinite
+// once they cover no more of it.
+// @liquidation_price: The estimated index price the position gets liquidatable at, if any.
+// @liquidation_distance: How far the index price is from it, as a fraction of the price.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionRisk {
+    pub price: f64,
+    pub leverage: f64,
+    pub liquidation_price: Option<f64>,
+    pub liquidation_distance: f64,
+}
+
+// Assesses the risk of a position at an index price.
+// @params: The market parameters positions get liquidated by.
+// @market: The market of the position.
+// @position: The current state of the position.
+// @price: The index price.
+pub fn assess_position(
+    params: &LiquidationParams,
+    market: &str,
+    position: &PositionState,
+    price: f64,
+) -> PositionRisk {
+    let pnl = match position.is_long {
+        true => position.size_in_tokens * price - position.size_in_usd,
+        false => position.size_in_usd - position.size_in_tokens * price,
+    };
+    let collateral_usd = match position.collateral_is_index_token {
+        true => position.collateral_amount * price,
+        false => position.collateral_amount * position.collateral_price,
+    };
+    let equity = collateral_usd + pnl - position.pending_fees_usd;
+    PositionRisk {
+        price,
+        leverage: match equity > 0.0 {
+            true => position.size_in_usd / equity,
+            false => f64::INFINITY,
+        },
+        liquidation_price: params.estimate(market, position).liquidation_price,
+        liquidation_distance: params.distance_to_liquidation(market, position, price),
+    }
+}
+
+// An enum representing the thresholds a position crossed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskAlert {
+    // The position is more leveraged than the max leverage.
+    Leverage { leverage: f64, max_leverage: f64 },
+    // The index price is closer to liquidating the position than the min distance.
+    Liquidation { distance: f64, min_distance: f64 },
+}
+
+impl RiskAlert {
+    pub fn describe(&self) -> String {
+        match self {
+            RiskAlert::Leverage { leverage, .. } if leverage.is_infinite() => {
+                "no collateral left after PnL and fees".to_owned()
+            }
+            RiskAlert::Leverage {
+                leverage,
+                max_leverage,
+            } => format!("leverage {:.2}x above {:.2}x", leverage, max_leverage),
+            RiskAlert::Liquidation { distance, .. } if *distance <= 0.0 => {
+                "past its liquidation price".to_owned()
+            }
+            RiskAlert::Liquidation {
+                distance,
+                min_distance,
+            } => format!(
+                "{:.2}% from liquidation, within {:.2}%",
+                distance * 100.0,
+                min_distance * 100.0
+            ),
+        }
+    }
+}
+
+impl AccountWatch {
+    // Returns the thresholds of the watch a position crossed.
+    // @risk: The risk of the position.
+    pub fn alerts(&self, risk: &PositionRisk) -> Vec<RiskAlert> {
+        let mut alerts = vec![];
+        if let Some(max_leverage) = self.max_leverage {
+            if risk.leverage > max_leverage {
+                alerts.push(RiskAlert::Leverage {
+                    leverage: risk.leverage,
+                    max_leverage,
+                });
+            }
+        }
+        if let Some(min_distance) = self.min_liquidation_distance {
+            if risk.liquidation_distance < min_distance {
+                alerts.push(RiskAlert::Liquidation {
+                    distance: risk.liquidation_distance,
+                    min_distance,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+// Formats the alert text sent to Telegram.
+// @position: The position at risk.
+// @risk: Its risk.
+// @alerts: The thresholds it crossed.
+pub fn format_alert(
+    position: &WatchedPosition,
+    risk: &PositionRisk,
+    alerts: &[RiskAlert],
+) -> String {
+    let liquidation_price = match risk.liquidation_price {
+        Some(price) => format!("{:.4}", price),
+        None => "none".to_owned(),
+    };
+    format!(
+        "{} {} position of market {}: {}. Index price {:.4}, liquidation price {}.",
+        position.index_symbol.as_deref().unwrap_or("Index token"),
+        if position.is_long { "long" } else { "short" },
+        position.market,
+        alerts
+            .iter()
+            .map(RiskAlert::describe)
+            .collect::<Vec<_>>()
+            .join(", "),
+        risk.price,
+        liquidation_price
+    )
+}
+
+// A struct holding when each position got alerted on last, so a position staying at risk gets an
+// alert every cooldown only.
+// @cooldown: How long a position goes without another alert.
+// @alerted: When each position got alerted on last, by position id.
+#[derive(Debug, Clone, Default)]
+pub struct AlertCooldown {
+    pub cooldown: Duration,
+    alerted: HashMap<String, Instant>,
+}
+
+impl AlertCooldown {
+    pub fn new(cooldown: Duration) -> Self {
+        AlertCooldown {
+            cooldown,
+            alerted: HashMap::new(),
+        }
+    }
+
+    // Returns whether a position at risk gets alerted on, recording the alert if so.
+    // @id: The position id.
+    // @now: The current instant.
+    pub fn allow(&mut self, id: &str, now: Instant) -> bool {
+        match self.alerted.get(id) {
+            Some(alerted) if now.duration_since(*alerted) < self.cooldown => false,
+            _ => {
+                self.alerted.insert(id.to_owned(), now);
+                true
+            }
+        }
+    }
+
+    // Forgets a position no longer at risk, so it gets alerted on as soon as it is again.
+    // @id: The position id.
+    pub fn clear(&mut self, id: &str) {
+        self.alerted.remove(id);
+    }
+}
+
+// Returns the Pragma pair a token of a market gets priced with, the one of its market feed if
+// mapped to a Pragma pair, its symbol against USD otherwise.
+// @feeds: The oracle feeds of the market tokens.
+// @market: The market, as indexed.
+// @token: The token.
+// @symbol: The symbol of the token in the tokens table, if any.
+pub fn get_token_pair(
+    feeds: &MarketFeeds,
+    market: &str,
+    token: &str,
+    symbol: Option<&str>,
+) -> Option<(String, String)> {
+    let token = FieldElement::from_hex_be(token).ok()?;
+    if let Some(feed) = feeds.get(market) {
+        for token_feed in [&feed.index, &feed.long, &feed.short] {
+            match &token_feed.feed {
+                FeedId::Pragma { base, quote } if token_feed.token == token => {
+                    return Some((base.clone(), quote.clone()))
+                }
+                // Pyth feeds have no client, the symbol pair being used instead.
+                _ => {}
+            }
+        }
+    }
+    symbol.map(|symbol| (symbol.to_lowercase(), "usd".to_owned()))
+}
+
+// Fetches the current price of a Pragma pair, scaled by its decimals.
+// @pair: The base and quote of the pair.
+async fn get_pair_price(pair: &(String, String)) -> Result<f64, KeeperError> {
+    let price_info = get_pragma_price(
+        PathParams {
+            base: pair.0.clone(),
+            quote: pair.1.clone(),
+            timestamp: get_system_timestamp(),
+            interval: "1min".to_owned(),
+        },
+        QueryParams {
+            routing: false,
+            aggregation: "median".to_owned(),
+        },
+    )
+    .await
+    .map_err(|e| watch_error(format!("no price for {}/{}: {}", pair.0, pair.1, e)))?;
+    let price = u128::from_str_radix(price_info.price.trim_start_matches("0x"), 16)
+        .map_err(|e| watch_error(format!("invalid price {}: {}", price_info.price, e)))?;
+    Ok(price as f64 / 10f64.powi(price_info.decimals as i32))
+}
+
+// Returns the price of a pair, fetching it once per check of the watched accounts.
+// @prices: The prices fetched during the check, by pair.
+// @pair: The base and quote of the pair.
+pub async fn get_cached_price(
+    prices: &mut HashMap<(String, String), f64>,
+    pair: (String, String),
+) -> Result<f64, KeeperError> {
+    if let Some(price) = prices.get(&pair) {
+        return Ok(*price);
+    }
+    let price = get_pair_price(&pair).await?;
+    prices.insert(pair, price);
+    Ok(price)
+}
+
+// A struct sending the alerts of the watched accounts to their webhook and Telegram chat.
+// @client: The HTTP client, timing out after the webhook timeout.
+// @telegram_bot_token: The token of the Telegram bot alerts get sent by, None without Telegram.
+// @telegram_api_url: The URL of the Telegram Bot API.
+pub struct AlertSender {
+    client: reqwest::Client,
+    telegram_bot_token: Option<String>,
+    telegram_api_url: String,
+}
+
+impl AlertSender {
+    pub fn from_env() -> Self {
+        AlertSender {
+            client: webhook_client(Duration::from_millis(config::get_webhook_timeout_ms())),
+            telegram_bot_token: config::get_telegram_bot_token(),
+            telegram_api_url: config::get_telegram_api_url(),
+        }
+    }
+
+    // Posts an alert to the webhook of the account, signed as the receipts are, if it registered
+    // one.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @account: The account, as stored in the account_watches table.
+    // @alert: The alert body.
+    async fn send_webhook(
+        &self,
+        pool: &Pool<Postgres>,
+        account: &str,
+        alert: &Value,
+    ) -> Result<(), KeeperError> {
+        let webhook = match get_webhook(pool, account)
+            .await
+            .map_err(|e| watch_error(e.to_string()))?
+        {
+            Some(webhook) => webhook,
+            None => return Ok(()),
+        };
+        check_webhook_url(&webhook.url).map_err(|e| watch_error(format!("{}: {}", account, e)))?;
+        let body = alert.to_string();
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, sign_receipt(&webhook.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| watch_error(format!("could not alert {}: {}", account, e)))?;
+        if !response.status().is_success() {
+            return Err(watch_error(format!(
+                "callback of {} answered {}",
+                account,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    // Sends an alert text to a Telegram chat through the bot, if one is configured.
+    // @chat_id: The chat.
+    // @text: The alert text.
+    async fn send_telegram(&self, chat_id: &str, text: &str) -> Result<(), KeeperError> {
+        let token = match &self.telegram_bot_token {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        let response = self
+            .client
+            .post(format!(
+                "{}/bot{}/sendMessage",
+                self.telegram_api_url.trim_end_matches('/'),
+                token
+            ))
+            .json(&json!({ "chat_id": chat_id, "text": text }))
+            .send()
+            .await
+            .map_err(|e| watch_error(format!("could not alert chat {}: {}", chat_id, e)))?;
+        if !response.status().is_success() {
+            return Err(watch_error(format!(
+                "Telegram answered {} for chat {}",
+                response.status(),
+                chat_id
+            )));
+        }
+        Ok(())
+    }
+
+    // Sends the alert of a position to the webhook and Telegram chat of its account, logging
+    // failures, one channel failing never holding the other back.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @watch: The watch of the account.
+    // @position: The position at risk.
+    // @risk: Its risk.
+    // @alerts: The thresholds it crossed.
+    pub async fn send(
+        &self,
+        pool: &Pool<Postgres>,
+        watch: &AccountWatch,
+        position: &WatchedPosition,
+        risk: &PositionRisk,
+        alerts: &[RiskAlert],
+    ) {
+        let alert = json!({
+            "type": "position_risk",
+            "account": watch.account,
+            "market": position.market,
+            "collateral_token": position.collateral_token,
+            "is_long": position.is_long,
+            "risk": risk,
+            "alerts": alerts.iter().map(RiskAlert::describe).collect::<Vec<_>>(),
+            "timestamp": get_system_timestamp(),
+        });
+        if let Err(e) = self.send_webhook(pool, &watch.account, &alert).await {
+            error!("{}", e);
+        }
+        if let Some(chat_id) = &watch.telegram_chat_id {
+            let text = format_alert(position, risk, alerts);
+            if let Err(e) = self.send_telegram(chat_id, &text).await {
+                error!("{}", e);
+            }
+        }
+    }
+}
+
+// A struct representing how the watched accounts get checked.
+// @interval: The delay between two checks, accounts never checked when None.
+// @cooldown: How long a position alerted on goes without another alert.
+// @liquidation: The market parameters liquidation prices get estimated with.
+// @feeds: The oracle feeds of the market tokens, priced by their symbol when unmapped.
+#[derive(Debug, Clone)]
+pub struct WatchParams {
+    pub interval: Option<Duration>,
+    pub cooldown: Duration,
+    pub liquidation: LiquidationParams,
+    pub feeds: MarketFeeds,
+}
+
+impl WatchParams {
+    pub fn from_env() -> Result<Self, KeeperError> {
+        Ok(WatchParams {
+            interval: config::get_watch_interval_secs().map(Duration::from_secs),
+            cooldown: Duration::from_secs(config::get_watch_alert_cooldown_secs()),
+            liquidation: LiquidationParams::from_env(),
+            feeds: MarketFeeds::from_env()?,
+        })
+    }
+}
+
+// Checks the positions of a watched account against the current prices, alerting on the ones
+// crossing its thresholds.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @params: How the accounts get checked.
+// @sender: The alert sender.
+// @cooldown: When each position got alerted on last.
+// @prices: The prices fetched during this check, by pair.
+// @watch: The watch of the account.
+async fn check_account(
+    pool: &Pool<Postgres>,
+    params: &WatchParams,
+    sender: &AlertSender,
+    cooldown: &mut AlertCooldown,
+    prices: &mut HashMap<(String, String), f64>,
+    watch: &AccountWatch,
+) -> Result<(), KeeperError> {
+    let account = FieldElement::from_hex_be(&watch.account)
+        .map_err(|_| watch_error(format!("invalid account {}", watch.account)))?;
+    let positions = get_watched_positions(pool, Some(&to_indexed_address(account)))
+        .await
+        .map_err(|e| watch_error(e.to_string()))?;
+    for position in positions {
+        let id = position.id();
+        let index_pair = position.index_token.as_deref().and_then(|token| {
+            get_token_pair(
+                &params.feeds,
+                &position.market,
+                token,
+                position.index_symbol.as_deref(),
+            )
+        });
+        let collateral_pair = get_token_pair(
+            &params.feeds,
+            &position.market,
+            &position.collateral_token,
+            position.collateral_symbol.as_deref(),
+        );
+        let (index_pair, collateral_pair) = match (index_pair, collateral_pair) {
+            (Some(index_pair), Some(collateral_pair)) => (index_pair, collateral_pair),
+            _ => {
+                debug!("No price feed for position {}, not watched", id);
+                continue;
+            }
+        };
+        let index_price = get_cached_price(prices, index_pair).await?;
+        let collateral_price = match position.collateral_is_index_token {
+            true => index_price,
+            false => get_cached_price(prices, collateral_pair).await?,
+        };
+        let state = match position.to_position_state(collateral_price) {
+            Some(state) => state,
+            None => {
+                debug!("Unknown token decimals or entry price of position {}", id);
+                continue;
+            }
+        };
+        let market = format!(
+            "{:#x}",
+            FieldElement::from_hex_be(&position.market)
+                .map_err(|_| watch_error(format!("invalid market {}", position.market)))?
+        );
+        let risk = assess_position(&params.liquidation, &market, &state, index_price);
+        let alerts = watch.alerts(&risk);
+        if alerts.is_empty() {
+            cooldown.clear(&id);
+            continue;
+        }
+        if cooldown.allow(&id, Instant::now()) {
+            info!(
+                "Alerting {} on position {}: {:?}",
+                watch.account, id, alerts
+            );
+            sender.send(pool, watch, &position, &risk, &alerts).await;
+        }
+    }
+    Ok(())
+}
+
+// Checks the positions of the watched accounts every watch interval, alerting their owners when
+// one gets too leveraged or too close to liquidation, from the indexed positions and the price
+// feed only.
+// @pool: A connection pool for PostgreSQL.
+// @params: How the accounts get checked.
+pub async fn run_account_watch(pool: Pool<Postgres>, params: WatchParams) {
+    let interval = match params.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+    let sender = AlertSender::from_env();
+    let mut cooldown = AlertCooldown::new(params.cooldown);
+    loop {
+        match get_watches(&pool).await {
+            Ok(watches) => {
+                let mut prices = HashMap::new();
+                for watch in &watches {
+                    if let Err(e) =
+                        check_account(&pool, &params, &sender, &mut cooldown, &mut prices, watch)
+                            .await
+                    {
+                        error!("Could not check account {}: {}", watch.account, e);
+                    }
+                }
+            }
+            Err(e) => error!("Could not load watched accounts: {:?}", e),
+        }
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> LiquidationParams {
+        LiquidationParams {
+            min_collateral_usd: 0.0,
+            default_min_collateral_factor: 0.01,
+            ..LiquidationParams::default()
+        }
+    }
+
+    // 10 ETH long bought at 1000 with 2000 USDC of collateral.
+    fn position() -> WatchedPosition {
+        WatchedPosition {
+            account: "0a".to_owned(),
+            market: "0b".to_owned(),
+            collateral_token: "0c".to_owned(),
+            is_long: true,
+            size_in_usd: format!("10000{}", "0".repeat(30)),
+            increased_usd: Some(format!("10000{}", "0".repeat(30))),
+            increased_tokens: Some(format!("10{}", "0".repeat(18))),
+            collateral_amount: format!("2000{}", "0".repeat(6)),
+            pending_fees_usd: "0".to_owned(),
+            index_token: Some("0d".to_owned()),
+            index_symbol: Some("ETH".to_owned()),
+            index_decimals: Some(18),
+            collateral_symbol: Some("USDC".to_owned()),
+            collateral_decimals: Some(6),
+            collateral_is_index_token: false,
+        }
+    }
+
+    fn watch() -> AccountWatch {
+        AccountWatch {
+            account: "0xa".to_owned(),
+            max_leverage: Some(10.0),
+            min_liquidation_distance: Some(0.05),
+            telegram_chat_id: None,
+        }
+    }
+
+    #[test]
+    fn test_to_position_state() {
+        let state = position().to_position_state(1.0).unwrap();
+        assert_eq!(state.size_in_usd, 10000.0);
+        assert!((state.size_in_tokens - 10.0).abs() < 1e-9);
+        assert_eq!(state.collateral_amount, 2000.0);
+        // Half the position closed at the average entry price.
+        let decreased = WatchedPosition {
+            size_in_usd: format!("5000{}", "0".repeat(30)),
+            ..position()
+        };
+        let state = decreased.to_position_state(1.0).unwrap();
+        assert!((state.size_in_tokens - 5.0).abs() < 1e-9);
+        let unknown = WatchedPosition {
+            index_decimals: None,
+            ..position()
+        };
+        assert!(unknown.to_position_state(1.0).is_none());
+    }
+
+    #[test]
+    fn test_position_alerts() {
+        let state = position().to_position_state(1.0).unwrap();
+        let risk = assess_position(&params(), "0xb", &state, 1000.0);
+        assert!((risk.leverage - 5.0).abs() < 1e-9);
+        // Liquidated once 2000 - 10 * (1000 - price) falls to 100.
+        assert!((risk.liquidation_price.unwrap() - 810.0).abs() < 1e-9);
+        assert!(watch().alerts(&risk).is_empty());
+
+        // 2.4% from liquidation at 830, 300 of collateral left after the losses.
+        let risk = assess_position(&params(), "0xb", &state, 830.0);
+        let alerts = watch().alerts(&risk);
+        assert_eq!(alerts.len(), 2);
+        assert!(matches!(alerts[0], RiskAlert::Leverage { leverage, .. } if leverage > 33.0));
+        assert!(matches!(alerts[1], RiskAlert::Liquidation { distance, .. } if distance < 0.05));
+        assert!(format_alert(&position(), &risk, &alerts).starts_with("ETH long position"));
+
+        let risk = assess_position(&params(), "0xb", &state, 700.0);
+        assert_eq!(risk.leverage, f64::INFINITY);
+        assert_eq!(
+            watch().alerts(&risk)[0].describe(),
+            "no collateral left after PnL and fees"
+        );
+    }
+
+    #[test]
+    fn test_alert_cooldown() {
+        let mut cooldown = AlertCooldown::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(cooldown.allow("a", now));
+        assert!(!cooldown.allow("a", now + Duration::from_secs(30)));
+        assert!(cooldown.allow("b", now + Duration::from_secs(30)));
+        assert!(cooldown.allow("a", now + Duration::from_secs(60)));
+        // Back to safety, then at risk again.
+        cooldown.clear("a");
+        assert!(cooldown.allow("a", now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_get_token_pair() {
+        let feeds = MarketFeeds::default();
+        assert_eq!(
+            get_token_pair(&feeds, "0b", "0d", Some("ETH")),
+            Some(("eth".to_owned(), "usd".to_owned()))
+        );
+        assert_eq!(get_token_pair(&feeds, "0b", "0d", None), None);
+    }
+}
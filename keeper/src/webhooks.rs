@@ -0,0 +1,390 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use log::{error, info};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{Pool, Postgres};
+use starknet::core::types::FieldElement;
+use url::Url;
+
+use crate::{
+    clock::get_system_timestamp, config, error::KeeperError, trade::receipt::ExecutionOutcome,
+    types::SatoruAction,
+};
+
+// The header receipts get signed in, as sha256=<hex HMAC-SHA256 of the body>.
+pub const SIGNATURE_HEADER: &str = "X-Keeper-Signature";
+
+// A struct representing the callback of an account, as stored in the account_webhooks table.
+// @account: The account the receipts of the orders of get sent, as a 0x prefixed felt.
+// @url: The URL receipts get posted to.
+// @secret: The secret receipts get signed with, shared with the account owner.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Webhook {
+    pub account: String,
+    pub url: String,
+    pub secret: String,
+}
+
+// Returns an account formatted as stored in the account_webhooks table, None if it is not a felt.
+// Accounts are compared as felts, the indexer stores them without 0x prefix.
+// @account: The account.
+pub fn to_webhook_account(account: &str) -> Option<String> {
+    FieldElement::from_hex_be(account)
+        .ok()
+        .map(|account| format!("{:#x}", account))
+}
+
+// Whether an address is publicly routable, callbacks never being posted to the loopback, private,
+// link-local (e.g. the 169.254.169.254 cloud metadata service) or otherwise reserved ranges.
+// @ip: The address.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Shared address space, 100.64.0.0/10, and the reserved 240.0.0.0/4.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7, and link-local, fe80::/10, addresses.
+                    || (segment & 0xfe00) == 0xfc00
+                    || (segment & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+// Checks a resolved callback address is public and is not the admin API of the keeper.
+fn check_webhook_address(address: SocketAddr) -> Result<(), String> {
+    let admin_api = config::get_admin_api_address().parse::<SocketAddr>().ok();
+    if !is_public_ip(address.ip()) || Some(address) == admin_api {
+        return Err(format!("the URL must not resolve to {}", address.ip()));
+    }
+    Ok(())
+}
+
+// Checks a callback URL can receive receipts, only http and https URLs of hosts not being IPs of
+// private networks being posted to. Host names get checked once resolved, see
+// resolve_webhook_url.
+// @url: The callback URL.
+pub fn check_webhook_url(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+        _ => None,
+    };
+    match url.scheme() {
+        "http" | "https" if url.host().is_some() => {
+            if let (Some(ip), Some(port)) = (ip, url.port_or_known_default()) {
+                check_webhook_address(SocketAddr::new(ip, port))?;
+            }
+            Ok(url)
+        }
+        _ => Err("the URL must be http or https".to_owned()),
+    }
+}
+
+// Checks a callback URL as check_webhook_url does, its host name resolving to public addresses
+// only. Callbacks get resolved again when posted to, by the webhook client.
+// @url: The callback URL.
+pub async fn resolve_webhook_url(url: &str) -> Result<Url, String> {
+    let url = check_webhook_url(url)?;
+    let (host, port) = (
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default(),
+    );
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("could not resolve {}: {}", host, e))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(format!("could not resolve {}", host));
+    }
+    addresses.into_iter().try_for_each(check_webhook_address)?;
+    Ok(url)
+}
+
+// A resolver failing on host names resolving to private addresses, so callbacks changing their
+// DNS records after being registered still cannot reach the keeper network.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            // The port is the one of the URL, only the address is checked.
+            if let Some(address) = addresses.iter().find(|address| !is_public_ip(address.ip())) {
+                return Err(format!("{} resolves to {}", name.as_str(), address.ip()).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Builds the HTTP client callbacks get posted with, resolving their hosts to public addresses only
+// and not following redirects, which could point to private ones.
+// @timeout: How long callbacks get to answer.
+pub fn webhook_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::none())
+        .build()
+        .expect("Could not build the webhook client.")
+}
+
+// Registers the callback of an account, replacing the previous one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @webhook: The callback of the account.
+pub async fn save_webhook(pool: &Pool<Postgres>, webhook: &Webhook) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO account_webhooks (account, url, secret) VALUES ($1, $2, $3)
+         ON CONFLICT (account) DO UPDATE SET url = $2, secret = $3, updated_at = NOW()",
+    )
+    .bind(&webhook.account)
+    .bind(&webhook.url)
+    .bind(&webhook.secret)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Removes the callback of an account, returns whether there was one.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_webhooks table.
+pub async fn delete_webhook(pool: &Pool<Postgres>, account: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM account_webhooks WHERE account = $1")
+        .bind(account)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Loads the callback of an account, if any.
+// @pool: A reference to a connection pool for PostgreSQL.
+// @account: The account, as stored in the account_webhooks table.
+pub async fn get_webhook(
+    pool: &Pool<Postgres>,
+    account: &str,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(
+        "SELECT account, url, secret FROM account_webhooks WHERE account = $1",
+    )
+    .bind(account)
+    .fetch_optional(pool)
+    .await
+}
+
+// Signs a receipt body, for callbacks to check it comes from the keeper.
+// @secret: The secret of the callback.
+// @body: The receipt body.
+pub fn sign_receipt(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", signature)
+}
+
+// Returns the receipt of a finished order.
+// @action: The order.
+// @outcome: How its execution ended.
+// @transaction_hash: The last transaction sent for it, if any.
+// @timestamp: When it finished, as a unix timestamp.
+pub fn get_receipt(
+    action: &SatoruAction,
+    outcome: &ExecutionOutcome,
+    transaction_hash: Option<FieldElement>,
+    timestamp: u64,
+) -> Value {
+    json!({
+        "key": action.key,
+        "account": to_webhook_account(&action.account),
+        "market": action.market,
+        "order_type": action.order_type,
+        "outcome": outcome.as_str(),
+        "executed": *outcome == ExecutionOutcome::Executed,
+        "reason": outcome.reason(),
+        "transaction_hash": transaction_hash.map(|hash| format!("{:#x}", hash)),
+        "timestamp": timestamp,
+    })
+}
+
+// A struct posting the receipts of the finished orders to the callbacks of their accounts.
+// @client: The HTTP client, timing out after the webhook timeout.
+pub struct WebhookSender {
+    client: reqwest::Client,
+}
+
+impl WebhookSender {
+    pub fn from_env() -> Self {
+        WebhookSender {
+            client: webhook_client(Duration::from_millis(config::get_webhook_timeout_ms())),
+        }
+    }
+
+    // Posts the receipt of a finished order to the callback of its account, if it registered one.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @action: The order.
+    // @outcome: How its execution ended.
+    // @transaction_hash: The last transaction sent for it, if any.
+    pub async fn send_receipt(
+        &self,
+        pool: &Pool<Postgres>,
+        action: &SatoruAction,
+        outcome: &ExecutionOutcome,
+        transaction_hash: Option<FieldElement>,
+    ) -> Result<(), KeeperError> {
+        let to_error = |e: String| {
+            KeeperError::ExecutionError(format!("could not send receipt of {}: {}", action.key, e))
+        };
+        let account = match to_webhook_account(&action.account) {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let webhook = match get_webhook(pool, &account)
+            .await
+            .map_err(|e| to_error(e.to_string()))?
+        {
+            Some(webhook) => webhook,
+            None => return Ok(()),
+        };
+        // Callbacks registered before their URLs got checked against private addresses.
+        check_webhook_url(&webhook.url).map_err(to_error)?;
+        let body =
+            get_receipt(action, outcome, transaction_hash, get_system_timestamp()).to_string();
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, sign_receipt(&webhook.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| to_error(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(to_error(format!("callback answered {}", response.status())));
+        }
+        info!("Sent receipt of {} to {}", action.key, webhook.url);
+        Ok(())
+    }
+
+    // Sends the receipt of a finished order, logging failures, receipts never holding jobs back.
+    // @pool: A reference to a connection pool for PostgreSQL.
+    // @action: The order.
+    // @outcome: How its execution ended.
+    // @transaction_hash: The last transaction sent for it, if any.
+    pub async fn notify(
+        &self,
+        pool: &Pool<Postgres>,
+        action: &SatoruAction,
+        outcome: &ExecutionOutcome,
+        transaction_hash: Option<FieldElement>,
+    ) {
+        if let Err(e) = self
+            .send_receipt(pool, action, outcome, transaction_hash)
+            .await
+        {
+            error!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_receipt() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign_receipt("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_get_receipt() {
+        let action = SatoruAction {
+            key: "0x12".to_owned(),
+            account: "1a".to_owned(),
+            order_type: Some("MarketIncrease".to_owned()),
+            ..Default::default()
+        };
+        let receipt = get_receipt(
+            &action,
+            &ExecutionOutcome::Reverted("MaxOpenInterestExceeded".to_owned()),
+            Some(FieldElement::from(255u8)),
+            1704067200,
+        );
+        assert_eq!(receipt["account"], "0x1a");
+        assert_eq!(receipt["outcome"], "reverted");
+        assert_eq!(receipt["executed"], false);
+        assert_eq!(receipt["reason"], "MaxOpenInterestExceeded");
+        assert_eq!(receipt["transaction_hash"], "0xff");
+    }
+
+    #[test]
+    fn test_check_webhook_url() {
+        assert!(check_webhook_url("https://ui.example/receipts").is_ok());
+        assert!(check_webhook_url("ftp://ui.example/receipts").is_err());
+        assert!(check_webhook_url("receipts").is_err());
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://127.0.0.1:8081/kill-switch",
+            "http://10.0.0.1/receipts",
+            "http://[::1]/receipts",
+            "http://[::ffff:192.168.1.1]/receipts",
+            "http://[fd00::1]/receipts",
+        ] {
+            assert!(check_webhook_url(url).is_err(), "{}", url);
+        }
+        assert!(check_webhook_url("https://1.1.1.1/receipts").is_ok());
+        assert_eq!(to_webhook_account("001a"), Some("0x1a".to_owned()));
+        assert_eq!(to_webhook_account("account"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_webhook_url() {
+        assert!(resolve_webhook_url("http://localhost:8081/kill-switch")
+            .await
+            .is_err());
+        assert!(resolve_webhook_url("ftp://localhost/receipts")
+            .await
+            .is_err());
+        assert!(!is_public_ip("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_ip("fe80::1".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+    }
+}
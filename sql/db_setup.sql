@@ -1,5 +1,29 @@
 \c zohal
 
+-- Felt conversion helpers. Felts are stored as 64 char hex strings without 0x prefix unless the
+-- database got migrated to binary storage with migrate_felt_bytea.sql. The views, functions and
+-- notifications below read and write them through felt_out and felt_in, so this script can be run
+-- again on either storage.
+CREATE OR REPLACE FUNCTION felt_to_bytea(value TEXT) RETURNS BYTEA AS $$
+  SELECT decode(lpad(regexp_replace(lower(value), '^0x', ''), 64, '0'), 'hex');
+$$ LANGUAGE SQL IMMUTABLE STRICT;
+
+CREATE OR REPLACE FUNCTION bytea_to_felt(value BYTEA) RETURNS TEXT AS $$
+  SELECT encode(value, 'hex');
+$$ LANGUAGE SQL IMMUTABLE STRICT;
+
+-- Converts stored felts back to hex strings whatever their storage type, for the views.
+CREATE OR REPLACE FUNCTION felt_out(value TEXT) RETURNS TEXT AS 'SELECT value' LANGUAGE SQL IMMUTABLE STRICT;
+CREATE OR REPLACE FUNCTION felt_out(value BYTEA) RETURNS TEXT AS 'SELECT bytea_to_felt(value)' LANGUAGE SQL IMMUTABLE STRICT;
+
+-- Converts indexed felts to their storage type, kept when the binary storage migration already redefined it.
+DO $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM pg_proc WHERE proname = 'felt_in') THEN
+    CREATE FUNCTION felt_in(value TEXT) RETURNS TEXT AS 'SELECT value' LANGUAGE SQL IMMUTABLE STRICT;
+  END IF;
+END $$;
+
 CREATE TABLE IF NOT EXISTS last_indexed_block (
     id SERIAL PRIMARY KEY,
     block_number BIGINT NOT NULL
@@ -7,8 +31,47 @@ CREATE TABLE IF NOT EXISTS last_indexed_block (
 
 INSERT INTO last_indexed_block (block_number) VALUES (0) ON CONFLICT (id) DO NOTHING;
 
+-- Cursor of each indexer shard, along with the events and block range it indexes.
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+    shard TEXT PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    events TEXT NOT NULL DEFAULT '',
+    from_block BIGINT NOT NULL DEFAULT 0,
+    to_block BIGINT,
+    heartbeat_at BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+-- Unix timestamp of the last heartbeat of each shard, shards without one for SHARD_TTL_SECS no longer holding
+-- their events and block range.
+ALTER TABLE indexer_cursors ADD COLUMN IF NOT EXISTS heartbeat_at BIGINT NOT NULL DEFAULT 0;
+
+-- Archive of the raw events the indexer fetched, as handed to the event handlers, so every table derived from
+-- them can be rebuilt with `rebuild --to <block>` without the chain. Events seen pending get their block and
+-- timestamp updated once included, id keeping the order they got fetched in.
+CREATE TABLE IF NOT EXISTS raw_events (
+    id BIGSERIAL PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    data TEXT NOT NULL,
+    sender_address TEXT,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS raw_events_event_idx ON raw_events (transaction_hash, key, md5(data));
+CREATE INDEX IF NOT EXISTS raw_events_block_number_idx ON raw_events (block_number, id);
+
+-- Token metadata, filled in by the operator. Addresses are stored as indexed, 64 char hex
+-- strings without 0x prefix.
+CREATE TABLE IF NOT EXISTS tokens (
+    address TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    decimals INTEGER NOT NULL
+);
+
 CREATE TABLE IF NOT EXISTS orders (
     block_number BIGINT NOT NULL,
+    time_stamp TEXT,
     transaction_hash TEXT NOT NULL,
     key TEXT,
     order_type TEXT,
@@ -32,6 +95,15 @@ CREATE TABLE IF NOT EXISTS orders (
     is_frozen BOOLEAN
 );
 
+-- Amounts scaled by their decimals at decode time, only filled in when the indexer runs with
+-- NORMALIZE_AMOUNTS=true. The raw amounts stay authoritative.
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS size_delta_usd_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS initial_collateral_delta_amount_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS trigger_price_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS acceptable_price_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS execution_fee_scaled NUMERIC;
+
 CREATE TABLE IF NOT EXISTS deposits (
     block_number BIGINT NOT NULL,
     time_stamp TEXT,
@@ -46,13 +118,19 @@ CREATE TABLE IF NOT EXISTS deposits (
     initial_short_token TEXT,
     long_token_swap_path TEXT,
     short_token_swap_path TEXT,
-    initial_long_token_amount BIGINT,
-    initial_short_token_amount BIGINT,
-    min_market_tokens BIGINT,
+    initial_long_token_amount NUMERIC,
+    initial_short_token_amount NUMERIC,
+    min_market_tokens NUMERIC,
     updated_at_block BIGINT,
-    execution_fee BIGINT,
+    execution_fee NUMERIC,
     callback_gas_limit BIGINT
 );
+
+ALTER TABLE deposits
+    ADD COLUMN IF NOT EXISTS initial_long_token_amount_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS initial_short_token_amount_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS min_market_tokens_scaled NUMERIC;
+
 CREATE TABLE IF NOT EXISTS withdrawals (
     block_number BIGINT NOT NULL,
     time_stamp TEXT,
@@ -65,14 +143,45 @@ CREATE TABLE IF NOT EXISTS withdrawals (
     market TEXT,
     long_token_swap_path TEXT,
     short_token_swap_path TEXT,
-    market_token_amount BIGINT,
-    min_long_token_amount BIGINT,
-    min_short_token_amount BIGINT,
+    market_token_amount NUMERIC,
+    min_long_token_amount NUMERIC,
+    min_short_token_amount NUMERIC,
     updated_at_block BIGINT,
-    execution_fee BIGINT,
+    execution_fee NUMERIC,
     callback_gas_limit BIGINT
 );
 
+ALTER TABLE withdrawals
+    ADD COLUMN IF NOT EXISTS market_token_amount_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS min_long_token_amount_scaled NUMERIC,
+    ADD COLUMN IF NOT EXISTS min_short_token_amount_scaled NUMERIC;
+
+-- Deposit and withdrawal amounts used to be BIGINT, too small for token amounts with 18 decimals.
+-- The views reading them get dropped to widen them, and recreated below.
+DO $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM information_schema.columns
+             WHERE table_name IN ('deposits', 'withdrawals') AND data_type = 'bigint'
+               AND column_name IN ('initial_long_token_amount', 'initial_short_token_amount',
+                   'min_market_tokens', 'market_token_amount', 'min_long_token_amount',
+                   'min_short_token_amount', 'execution_fee')) THEN
+    DROP VIEW IF EXISTS keeper_backlog;
+    DROP VIEW IF EXISTS deposits_human;
+    DROP VIEW IF EXISTS withdrawals_human;
+    ALTER TABLE deposits
+        ALTER COLUMN initial_long_token_amount TYPE NUMERIC,
+        ALTER COLUMN initial_short_token_amount TYPE NUMERIC,
+        ALTER COLUMN min_market_tokens TYPE NUMERIC,
+        ALTER COLUMN execution_fee TYPE NUMERIC;
+    ALTER TABLE withdrawals
+        ALTER COLUMN market_token_amount TYPE NUMERIC,
+        ALTER COLUMN min_long_token_amount TYPE NUMERIC,
+        ALTER COLUMN min_short_token_amount TYPE NUMERIC,
+        ALTER COLUMN execution_fee TYPE NUMERIC;
+    RAISE NOTICE 'Deposit and withdrawal amounts widened to NUMERIC';
+  END IF;
+END $$;
+
 CREATE TABLE IF NOT EXISTS market_created (
     block_number BIGINT NOT NULL,
     time_stamp TEXT,
@@ -140,9 +249,941 @@ CREATE TABLE IF NOT EXISTS order_executed (
     block_number BIGINT NOT NULL,
     time_stamp TEXT,
     transaction_hash TEXT NOT NULL,
-    key TEXT,
+    key TEXT NOT NULL,
     secondary_order_type TEXT,
-    PRIMARY KEY (block_number, transaction_hash)
+    keeper TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+-- A batch executes several orders in one transaction, so the key is part of the primary key.
+ALTER TABLE order_executed ALTER COLUMN key SET NOT NULL;
+ALTER TABLE order_executed DROP CONSTRAINT IF EXISTS order_executed_pkey;
+ALTER TABLE order_executed ADD PRIMARY KEY (block_number, transaction_hash, key);
+
+CREATE TABLE IF NOT EXISTS order_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS order_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    size_delta_usd NUMERIC,
+    acceptable_price NUMERIC,
+    trigger_price NUMERIC,
+    min_output_amount NUMERIC,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS order_frozen (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS deposit_executed (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS deposit_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS withdrawal_executed (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS withdrawal_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+-- Executions per keeper address, with the latency between order creation and execution
+-- and the execution fees captured, to compare our keeper against its competitors.
+CREATE OR REPLACE VIEW keeper_competition_stats AS
+SELECT
+    oe.keeper,
+    COUNT(*) AS executions,
+    percentile_cont(0.5) WITHIN GROUP (
+        ORDER BY oe.time_stamp::BIGINT - o.time_stamp::BIGINT
+    ) AS median_latency_secs,
+    COALESCE(SUM(o.execution_fee), 0) AS fees_captured
+FROM order_executed oe
+JOIN orders o ON o.key = oe.key
+WHERE oe.keeper IS NOT NULL
+GROUP BY oe.keeper;
+
+-- Actions created but neither executed nor cancelled yet, per market. The pending amount is the
+-- size in USD for orders, the long and short token amounts for deposits and the market token
+-- amount for withdrawals.
+CREATE OR REPLACE VIEW keeper_backlog AS
+SELECT felt_out(o.market) AS market, 'orders' AS action_type, COUNT(*) AS pending, COALESCE(SUM(o.size_delta_usd), 0) AS pending_amount,
+    MIN(o.time_stamp) AS oldest_time_stamp
+FROM orders o
+WHERE NOT EXISTS (SELECT 1 FROM order_executed e WHERE e.key = o.key)
+    AND NOT EXISTS (SELECT 1 FROM order_cancelled c WHERE c.key = o.key)
+GROUP BY o.market
+UNION ALL
+SELECT felt_out(d.market) AS market, 'deposits' AS action_type, COUNT(*) AS pending,
+    COALESCE(SUM(d.initial_long_token_amount + d.initial_short_token_amount), 0) AS pending_amount,
+    MIN(d.time_stamp) AS oldest_time_stamp
+FROM deposits d
+WHERE NOT EXISTS (SELECT 1 FROM deposit_executed e WHERE e.key = d.key)
+    AND NOT EXISTS (SELECT 1 FROM deposit_cancelled c WHERE c.key = d.key)
+GROUP BY d.market
+UNION ALL
+SELECT felt_out(w.market) AS market, 'withdrawals' AS action_type, COUNT(*) AS pending,
+    COALESCE(SUM(w.market_token_amount), 0) AS pending_amount,
+    MIN(w.time_stamp) AS oldest_time_stamp
+FROM withdrawals w
+WHERE NOT EXISTS (SELECT 1 FROM withdrawal_executed e WHERE e.key = w.key)
+    AND NOT EXISTS (SELECT 1 FROM withdrawal_cancelled c WHERE c.key = w.key)
+GROUP BY w.market;
+
+-- Lifecycle of each order by key, from its creation through its updates and freezes to its
+-- execution or cancellation, with the durations between the stages in seconds. Frozen orders
+-- can still get executed or cancelled. The settlement duration runs from the last update.
+CREATE OR REPLACE VIEW order_lifecycle AS
+SELECT
+    felt_out(o.key) AS key,
+    felt_out(o.account) AS account,
+    felt_out(o.market) AS market,
+    o.order_type,
+    CASE
+        WHEN e.key IS NOT NULL THEN 'executed'
+        WHEN c.key IS NOT NULL THEN 'cancelled'
+        WHEN f.key IS NOT NULL THEN 'frozen'
+        ELSE 'pending'
+    END AS status,
+    o.block_number AS created_block,
+    o.time_stamp::BIGINT AS created_at,
+    COALESCE(u.updates, 0) AS updates,
+    u.last_updated_at,
+    f.frozen_at,
+    f.reason AS frozen_reason,
+    e.block_number AS executed_block,
+    e.time_stamp::BIGINT AS executed_at,
+    e.keeper,
+    c.block_number AS cancelled_block,
+    c.time_stamp::BIGINT AS cancelled_at,
+    c.reason AS cancelled_reason,
+    f.frozen_at - o.time_stamp::BIGINT AS time_to_freeze_secs,
+    COALESCE(e.time_stamp, c.time_stamp)::BIGINT - COALESCE(u.last_updated_at, o.time_stamp::BIGINT)
+        AS settlement_duration_secs,
+    COALESCE(e.time_stamp, c.time_stamp)::BIGINT - o.time_stamp::BIGINT AS total_duration_secs
+FROM orders o
+LEFT JOIN (
+    SELECT key, COUNT(*) AS updates, MAX(time_stamp::BIGINT) AS last_updated_at
+    FROM order_updated
+    GROUP BY key
+) u ON u.key = o.key
+LEFT JOIN (
+    SELECT DISTINCT ON (key) key, time_stamp::BIGINT AS frozen_at, reason
+    FROM order_frozen
+    ORDER BY key, block_number DESC
+) f ON f.key = o.key
+LEFT JOIN order_executed e ON e.key = o.key
+LEFT JOIN order_cancelled c ON c.key = o.key;
+
+CREATE TABLE IF NOT EXISTS funding_fee_amount_per_size_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta NUMERIC,
+    next_value NUMERIC,
+    PRIMARY KEY (block_number, transaction_hash, market, collateral_token, is_long)
+);
+
+CREATE TABLE IF NOT EXISTS claimable_funding_amount_per_size_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta NUMERIC,
+    next_value NUMERIC,
+    PRIMARY KEY (block_number, transaction_hash, market, collateral_token, is_long)
+);
+
+-- Funding paid or received by each open position on every funding amount per size update, in
+-- collateral token units: the position size in USD times the update delta, divided by the 10^30
+-- float precision.
+CREATE TABLE IF NOT EXISTS funding_payments (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    direction TEXT NOT NULL,
+    size_in_usd NUMERIC NOT NULL,
+    amount_per_size_delta NUMERIC NOT NULL,
+    amount NUMERIC NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token, is_long, direction)
+);
+
+CREATE INDEX IF NOT EXISTS funding_payments_account_idx ON funding_payments (account);
+
+-- Records the funding paid (direction 'paid') or received ('received') by the positions open on a
+-- market side when its funding amount per size gets updated. Position sizes are the net of the size
+-- deltas of the indexed position increases and decreases before the update, their side being the one
+-- of the order executed in the same transaction. Funding gets paid in the collateral token of the
+-- positions, while the claimable funding of a token accrues to every position of the side, whatever its
+-- collateral. Returns the number of payments recorded.
+CREATE OR REPLACE FUNCTION record_funding_payments(
+    p_block_number BIGINT,
+    p_time_stamp TEXT,
+    p_transaction_hash TEXT,
+    p_market TEXT,
+    p_collateral_token TEXT,
+    p_is_long BOOLEAN,
+    p_delta NUMERIC,
+    p_direction TEXT
+) RETURNS INTEGER AS $$
+DECLARE
+  recorded INTEGER;
+BEGIN
+  INSERT INTO funding_payments (
+    block_number, time_stamp, transaction_hash, account, market, collateral_token, is_long,
+    direction, size_in_usd, amount_per_size_delta, amount
+  )
+  SELECT
+    p_block_number, p_time_stamp, p_transaction_hash, p.account, p_market,
+    p_collateral_token, p_is_long, p_direction, p.size_in_usd, p_delta,
+    TRUNC(p.size_in_usd * p_delta / 1e30)
+  FROM (
+    SELECT c.account, SUM(c.size_delta_usd) AS size_in_usd
+    FROM (
+      SELECT block_number, transaction_hash, account, market, collateral_token, size_delta_usd
+      FROM position_increase
+      UNION ALL
+      SELECT block_number, transaction_hash, account, market, collateral_token, -size_delta_usd
+      FROM position_decrease
+    ) c
+    WHERE c.market = p_market
+      AND c.block_number < p_block_number
+      AND (p_direction = 'received' OR c.collateral_token = p_collateral_token)
+      AND EXISTS (
+        SELECT 1 FROM order_executed oe
+        JOIN orders o ON o.key = oe.key
+        WHERE oe.transaction_hash = c.transaction_hash
+          AND o.account = felt_in(c.account)
+          AND o.market = felt_in(c.market)
+          AND o.is_long = p_is_long
+      )
+    GROUP BY c.account
+  ) p
+  WHERE p.size_in_usd > 0
+  ON CONFLICT DO NOTHING;
+  GET DIAGNOSTICS recorded = ROW_COUNT;
+  RETURN recorded;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TABLE IF NOT EXISTS cumulative_borrowing_factor_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta NUMERIC,
+    next_value NUMERIC,
+    PRIMARY KEY (block_number, transaction_hash, market, is_long)
+);
+
+-- Borrowing fees accrued by each open position on every cumulative borrowing factor update, in USD
+-- with 30 decimals: the position size in USD times the factor delta, divided by the 10^30 float
+-- precision.
+CREATE TABLE IF NOT EXISTS borrowing_fee_accruals (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    size_in_usd NUMERIC NOT NULL,
+    borrowing_factor_delta NUMERIC NOT NULL,
+    amount_usd NUMERIC NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token, is_long)
+);
+
+-- Records the borrowing fees accrued by the positions open on a market side when its cumulative
+-- borrowing factor gets updated. Position sizes are the net of the size deltas of the indexed position
+-- increases and decreases before the update, their side being the one of the order executed in the same
+-- transaction, as in record_funding_payments. Returns the number of accruals recorded.
+CREATE OR REPLACE FUNCTION record_borrowing_fee_accruals(
+    p_block_number BIGINT,
+    p_time_stamp TEXT,
+    p_transaction_hash TEXT,
+    p_market TEXT,
+    p_is_long BOOLEAN,
+    p_delta NUMERIC
+) RETURNS INTEGER AS $$
+DECLARE
+  recorded INTEGER;
+BEGIN
+  INSERT INTO borrowing_fee_accruals (
+    block_number, time_stamp, transaction_hash, account, market, collateral_token, is_long,
+    size_in_usd, borrowing_factor_delta, amount_usd
+  )
+  SELECT
+    p_block_number, p_time_stamp, p_transaction_hash, p.account, p_market,
+    p.collateral_token, p_is_long, p.size_in_usd, p_delta, TRUNC(p.size_in_usd * p_delta / 1e30)
+  FROM (
+    SELECT c.account, c.collateral_token, SUM(c.size_delta_usd) AS size_in_usd
+    FROM (
+      SELECT block_number, transaction_hash, account, market, collateral_token, size_delta_usd
+      FROM position_increase
+      UNION ALL
+      SELECT block_number, transaction_hash, account, market, collateral_token, -size_delta_usd
+      FROM position_decrease
+    ) c
+    WHERE c.market = p_market
+      AND c.block_number < p_block_number
+      AND EXISTS (
+        SELECT 1 FROM order_executed oe
+        JOIN orders o ON o.key = oe.key
+        WHERE oe.transaction_hash = c.transaction_hash
+          AND o.account = felt_in(c.account)
+          AND o.market = felt_in(c.market)
+          AND o.is_long = p_is_long
+      )
+    GROUP BY c.account, c.collateral_token
+  ) p
+  WHERE p.size_in_usd > 0
+  ON CONFLICT DO NOTHING;
+  GET DIAGNOSTICS recorded = ROW_COUNT;
+  RETURN recorded;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Open interest of each market per side, the net size in USD of the executed increase and decrease
+-- orders, with 30 decimals.
+CREATE OR REPLACE VIEW market_open_interest AS
+SELECT
+    felt_out(o.market) AS market,
+    COALESCE(SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease') THEN o.size_delta_usd
+        ELSE -o.size_delta_usd END) FILTER (WHERE o.is_long), 0) AS long_open_interest_usd,
+    COALESCE(SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease') THEN o.size_delta_usd
+        ELSE -o.size_delta_usd END) FILTER (WHERE NOT o.is_long), 0) AS short_open_interest_usd
+FROM orders o
+JOIN order_executed oe ON oe.key = o.key
+WHERE o.order_type IN ('MarketIncrease', 'LimitIncrease', 'MarketDecrease', 'LimitDecrease', 'StopLossDecrease', 'Liquidation')
+GROUP BY o.market;
+
+-- Views exposing the indexed actions with their amounts scaled by the token decimals, so BI tools
+-- can query them without embedding the scaling logic. USD values and prices carry 30 decimals,
+-- prices being per smallest token unit, market tokens and execution fees carry 18 decimals.
+
+-- Divides a raw amount by 10^decimals, NULL when the decimals are unknown.
+CREATE OR REPLACE FUNCTION scale_amount(amount NUMERIC, decimals INTEGER) RETURNS NUMERIC AS $$
+  SELECT amount / power(10::NUMERIC, decimals);
+$$ LANGUAGE SQL IMMUTABLE STRICT;
+
+CREATE OR REPLACE VIEW orders_human AS
+SELECT
+    o.block_number,
+    o.time_stamp,
+    o.transaction_hash,
+    felt_out(o.key) AS key,
+    o.order_type,
+    felt_out(o.account) AS account,
+    felt_out(o.market) AS market,
+    it.symbol AS index_token,
+    o.initial_collateral_token,
+    ct.symbol AS collateral_token,
+    o.is_long,
+    o.is_frozen,
+    scale_amount(o.size_delta_usd, 30) AS size_delta_usd,
+    scale_amount(o.initial_collateral_delta_amount, ct.decimals) AS initial_collateral_delta_amount,
+    scale_amount(o.trigger_price, 30 - it.decimals) AS trigger_price,
+    scale_amount(o.acceptable_price, 30 - it.decimals) AS acceptable_price,
+    scale_amount(o.execution_fee, 18) AS execution_fee
+FROM orders o
+LEFT JOIN market_created mc ON mc.market_token = felt_out(o.market)
+LEFT JOIN tokens it ON it.address = mc.index_token
+LEFT JOIN tokens ct ON ct.address = o.initial_collateral_token;
+
+CREATE OR REPLACE VIEW deposits_human AS
+SELECT
+    d.block_number,
+    d.time_stamp,
+    d.transaction_hash,
+    felt_out(d.key) AS key,
+    felt_out(d.account) AS account,
+    felt_out(d.market) AS market,
+    lt.symbol AS initial_long_token,
+    st.symbol AS initial_short_token,
+    scale_amount(d.initial_long_token_amount, lt.decimals) AS initial_long_token_amount,
+    scale_amount(d.initial_short_token_amount, st.decimals) AS initial_short_token_amount,
+    scale_amount(d.min_market_tokens, 18) AS min_market_tokens,
+    scale_amount(d.execution_fee, 18) AS execution_fee
+FROM deposits d
+LEFT JOIN tokens lt ON lt.address = d.initial_long_token
+LEFT JOIN tokens st ON st.address = d.initial_short_token;
+
+CREATE OR REPLACE VIEW withdrawals_human AS
+SELECT
+    w.block_number,
+    w.time_stamp,
+    w.transaction_hash,
+    felt_out(w.key) AS key,
+    felt_out(w.account) AS account,
+    felt_out(w.market) AS market,
+    scale_amount(w.market_token_amount, 18) AS market_token_amount,
+    lt.symbol AS long_token,
+    scale_amount(w.min_long_token_amount, lt.decimals) AS min_long_token_amount,
+    st.symbol AS short_token,
+    scale_amount(w.min_short_token_amount, st.decimals) AS min_short_token_amount,
+    scale_amount(w.execution_fee, 18) AS execution_fee
+FROM withdrawals w
+LEFT JOIN market_created mc ON mc.market_token = felt_out(w.market)
+LEFT JOIN tokens lt ON lt.address = mc.long_token
+LEFT JOIN tokens st ON st.address = mc.short_token;
+
+-- Open positions, rebuilt from the executed increase and decrease orders of each account, market,
+-- collateral token and side. Sizes ignore the funding and fees settled on the collateral.
+CREATE OR REPLACE VIEW positions_human AS
+SELECT
+    felt_out(o.account) AS account,
+    felt_out(o.market) AS market,
+    o.initial_collateral_token,
+    ct.symbol AS collateral_token,
+    o.is_long,
+    scale_amount(SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease') THEN o.size_delta_usd
+        ELSE -o.size_delta_usd END), 30) AS size_usd,
+    scale_amount(SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease') THEN o.initial_collateral_delta_amount
+        ELSE -o.initial_collateral_delta_amount END), ct.decimals) AS collateral_amount,
+    MAX(oe.time_stamp) AS last_updated_time_stamp
+FROM orders o
+JOIN order_executed oe ON oe.key = o.key
+LEFT JOIN tokens ct ON ct.address = o.initial_collateral_token
+WHERE o.order_type IN ('MarketIncrease', 'LimitIncrease', 'MarketDecrease', 'LimitDecrease', 'StopLossDecrease', 'Liquidation')
+GROUP BY o.account, o.market, o.initial_collateral_token, ct.symbol, ct.decimals, o.is_long
+HAVING SUM(CASE WHEN o.order_type IN ('MarketIncrease', 'LimitIncrease') THEN o.size_delta_usd
+    ELSE -o.size_delta_usd END) > 0;
+
+-- Positions opened or increased, only their identity being indexed, the keeper reading the position itself
+-- from the DataStore.
+CREATE TABLE IF NOT EXISTS position_increase (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token)
+);
+
+-- Positions decreased or closed, only their identity being indexed, the keeper reading the position itself
+-- from the DataStore.
+CREATE TABLE IF NOT EXISTS position_decrease (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token)
+);
+
+-- The executions of the position changes, decoded by the indexer since the VWAP series got added.
+ALTER TABLE position_increase
+    ADD COLUMN IF NOT EXISTS execution_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS size_delta_usd NUMERIC,
+    ADD COLUMN IF NOT EXISTS size_delta_in_tokens NUMERIC;
+ALTER TABLE position_decrease
+    ADD COLUMN IF NOT EXISTS execution_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS size_delta_usd NUMERIC,
+    ADD COLUMN IF NOT EXISTS size_delta_in_tokens NUMERIC;
+
+-- Open positions with the borrowing fees they accrued since first opened, and the ones still
+-- pending, accrued since their last increase or decrease settled the previous ones. Positions are
+-- the net of the size deltas of the indexed position increases and decreases, their side being the
+-- one of the order executed in the same transaction, as in record_borrowing_fee_accruals.
+CREATE OR REPLACE VIEW position_borrowing_fees AS
+WITH positions AS (
+    SELECT
+        c.account,
+        c.market,
+        c.collateral_token,
+        s.is_long,
+        SUM(c.size_delta_usd) AS size_in_usd,
+        MAX(c.block_number) AS last_updated_block
+    FROM (
+        SELECT block_number, transaction_hash, account, market, collateral_token, size_delta_usd
+        FROM position_increase
+        UNION ALL
+        SELECT block_number, transaction_hash, account, market, collateral_token, -size_delta_usd
+        FROM position_decrease
+    ) c
+    JOIN LATERAL (
+        SELECT o.is_long
+        FROM order_executed oe
+        JOIN orders o ON o.key = oe.key
+        WHERE oe.transaction_hash = c.transaction_hash
+          AND o.account = felt_in(c.account)
+          AND o.market = felt_in(c.market)
+        LIMIT 1
+    ) s ON TRUE
+    GROUP BY c.account, c.market, c.collateral_token, s.is_long
+    HAVING SUM(c.size_delta_usd) > 0
+)
+SELECT
+    p.account,
+    p.market,
+    p.collateral_token,
+    p.is_long,
+    p.size_in_usd,
+    p.last_updated_block,
+    COALESCE(SUM(a.amount_usd), 0) AS cumulative_borrowing_fees_usd,
+    COALESCE(SUM(a.amount_usd) FILTER (WHERE a.block_number > p.last_updated_block), 0)
+        AS pending_borrowing_fees_usd
+FROM positions p
+LEFT JOIN borrowing_fee_accruals a ON a.account = p.account
+    AND a.market = p.market
+    AND a.collateral_token = p.collateral_token
+    AND a.is_long = p.is_long
+GROUP BY p.account, p.market, p.collateral_token, p.is_long, p.size_in_usd, p.last_updated_block;
+
+-- Daily volume-weighted average execution price of the position increases and decreases per market, in UTC
+-- days, as a benchmark series for the analytics and backtests. Prices are weighted by the size delta in index
+-- tokens, at the index token protocol precision; a side without execution that day is NULL.
+CREATE OR REPLACE VIEW market_daily_vwap AS
+WITH executions AS (
+    SELECT market, time_stamp, TRUE AS is_increase, execution_price, size_delta_usd, size_delta_in_tokens
+    FROM position_increase
+    UNION ALL
+    SELECT market, time_stamp, FALSE, execution_price, size_delta_usd, size_delta_in_tokens
+    FROM position_decrease
+)
+SELECT market,
+    (to_timestamp(time_stamp::BIGINT) AT TIME ZONE 'UTC')::DATE AS day,
+    SUM(execution_price * size_delta_in_tokens) / NULLIF(SUM(size_delta_in_tokens), 0) AS vwap,
+    SUM(execution_price * size_delta_in_tokens) FILTER (WHERE is_increase)
+        / NULLIF(SUM(size_delta_in_tokens) FILTER (WHERE is_increase), 0) AS increase_vwap,
+    SUM(execution_price * size_delta_in_tokens) FILTER (WHERE NOT is_increase)
+        / NULLIF(SUM(size_delta_in_tokens) FILTER (WHERE NOT is_increase), 0) AS decrease_vwap,
+    COALESCE(SUM(size_delta_usd) FILTER (WHERE is_increase), 0) AS increase_volume_usd,
+    COALESCE(SUM(size_delta_usd) FILTER (WHERE NOT is_increase), 0) AS decrease_volume_usd,
+    COUNT(*) AS executions
+FROM executions
+WHERE time_stamp ~ '^[0-9]+$' AND execution_price IS NOT NULL AND size_delta_in_tokens > 0
+GROUP BY market, day;
+
+-- Config changes of the market parameters, e.g. fee factors, reserve factors and max open interest,
+-- so analytics and backtests use the values in force at each block rather than the current ones.
+-- is_long is NULL for the parameters applying to both sides.
+CREATE TABLE IF NOT EXISTS market_params_history (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    parameter TEXT NOT NULL,
+    is_long BOOLEAN,
+    value NUMERIC NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS market_params_history_update_idx
+    ON market_params_history (block_number, transaction_hash, market, parameter, COALESCE(is_long::TEXT, ''));
+CREATE INDEX IF NOT EXISTS market_params_history_market_idx
+    ON market_params_history (market, parameter, block_number);
+
+-- Returns the value of a market parameter in force at a block, the last one set at or before it,
+-- NULL when it never got set. A value set for both sides counts for either side.
+CREATE OR REPLACE FUNCTION market_param_at(
+    p_market TEXT,
+    p_parameter TEXT,
+    p_is_long BOOLEAN,
+    p_block_number BIGINT
+) RETURNS NUMERIC AS $$
+  SELECT value FROM market_params_history
+  WHERE market = p_market
+    AND parameter = p_parameter
+    AND (is_long = p_is_long OR is_long IS NULL)
+    AND block_number <= p_block_number
+  ORDER BY block_number DESC, (is_long IS NULL)
+  LIMIT 1;
+$$ LANGUAGE SQL STABLE;
+
+-- Fields of indexed orders disagreeing with the DataStore, flagged by the indexer reconciliation.
+CREATE TABLE IF NOT EXISTS reconciliation_mismatches (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    field TEXT NOT NULL,
+    indexed TEXT NOT NULL,
+    on_chain TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Keeper instances sharing the database, with their configuration and last heartbeat.
+CREATE TABLE IF NOT EXISTS keepers (
+    id TEXT PRIMARY KEY,
+    version TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    features TEXT[] NOT NULL DEFAULT '{}',
+    account_address TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_heartbeat TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Keys clients authenticate to the admin API with when API_AUTH_ENABLED is set, stored as the hex
+-- sha256 of the key, e.g. INSERT INTO api_keys (key_hash, name) VALUES (encode(sha256('<key>'), 'hex'), 'ui').
+-- Viewer keys (formerly read-only) can call every read route, operator keys also engage the kill switch and schedule
+-- maintenance windows, admin keys call every route, e.g. to acknowledge contract upgrades, and account keys only the
+-- routes filtered on their account.
+CREATE TABLE IF NOT EXISTS api_keys (
+    key_hash TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    scope TEXT NOT NULL DEFAULT 'viewer',
+    account TEXT,
+    requests_per_minute INTEGER NOT NULL DEFAULT 60,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ,
+    CHECK (scope <> 'account' OR account IS NOT NULL)
+);
+
+ALTER TABLE api_keys ALTER COLUMN scope SET DEFAULT 'viewer';
+ALTER TABLE api_keys DROP CONSTRAINT IF EXISTS api_keys_scope_check;
+ALTER TABLE api_keys ADD CONSTRAINT api_keys_scope_check
+    CHECK (scope IN ('viewer', 'read-only', 'operator', 'admin', 'account'));
+
+-- Callbacks the receipts of the executed or failed orders of an account get posted to, signed with
+-- the secret of the account, accounts being stored as 0x prefixed felts.
+CREATE TABLE IF NOT EXISTS account_webhooks (
+    account TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Accounts watched for risky positions, alerts going to the webhook of the account and to its Telegram chat
+-- once a position is more leveraged than max_leverage or closer to liquidation than min_liquidation_distance,
+-- a fraction of the index price. Accounts are stored as 0x prefixed felts, as the webhook ones.
+CREATE TABLE IF NOT EXISTS account_watches (
+    account TEXT PRIMARY KEY,
+    max_leverage DOUBLE PRECISION,
+    min_liquidation_distance DOUBLE PRECISION,
+    telegram_chat_id TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The managed execution preferences of the traders, registered through the admin API, which the
+-- account-service keepers read before acting on behalf of an account.
+CREATE TABLE IF NOT EXISTS account_delegations (
+    account TEXT PRIMARY KEY,
+    auto_claim_funding BOOLEAN NOT NULL DEFAULT FALSE,
+    auto_cancel_stale_orders BOOLEAN NOT NULL DEFAULT FALSE,
+    stale_order_secs BIGINT,
+    notification_channels TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The kill switch engaged by an operator through the admin API, a single row every keeper instance
+-- polls, stopping their outgoing transactions while engaged.
+CREATE TABLE IF NOT EXISTS keeper_kill_switch (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    engaged BOOLEAN NOT NULL,
+    reason TEXT NOT NULL DEFAULT '',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The maintenance windows scheduled through the admin API, e.g. for coordinated contract upgrades,
+-- every keeper instance stopping its outgoing transactions while one lasts.
+CREATE TABLE IF NOT EXISTS keeper_maintenance_windows (
+    id BIGSERIAL PRIMARY KEY,
+    starts_at BIGINT NOT NULL,
+    ends_at BIGINT NOT NULL CHECK (ends_at > starts_at),
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The upgrades of the keeper contracts detected from their Upgraded events or class hashes, every
+-- keeper instance stopping its outgoing transactions until an operator acknowledged them.
+CREATE TABLE IF NOT EXISTS keeper_contract_upgrades (
+    id BIGSERIAL PRIMARY KEY,
+    contract TEXT NOT NULL,
+    address TEXT NOT NULL,
+    previous_class_hash TEXT,
+    class_hash TEXT NOT NULL,
+    block_number BIGINT,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    acknowledged_at TIMESTAMPTZ,
+    UNIQUE (address, class_hash)
+);
+
+-- The orders the indexer diverged from the DataStore on, seen by two checks in a row: unindexed
+-- ones pending on-chain only, stale ones pending in the indexer only. Recorded once per order and
+-- kind, alerting on new rows only.
+CREATE TABLE IF NOT EXISTS keeper_divergences (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    repaired BOOLEAN NOT NULL DEFAULT FALSE,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (key, kind)
+);
+
+-- Every decision the keeper made on an action, with its reason, to answer why an action did or
+-- did not get executed.
+CREATE TABLE IF NOT EXISTS keeper_decisions (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    decision TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS keeper_decisions_key_idx ON keeper_decisions (key);
+
+CREATE TABLE IF NOT EXISTS keeper_jobs (
+    key TEXT PRIMARY KEY,
+    table_name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    transaction_hash TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    outcome TEXT,
+    failure_reason TEXT,
+    row_data TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The actions the keeper attempted to execute, by key, kept apart from keeper_jobs and pruned on
+-- their own so a wiped jobs table never gets an action resubmitted: a restarted keeper waits on
+-- the last transaction sent instead, and skips the actions which already finished.
+CREATE TABLE IF NOT EXISTS keeper_action_attempts (
+    key TEXT PRIMARY KEY,
+    table_name TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    transaction_hash TEXT,
+    outcome TEXT,
+    first_attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS keeper_action_attempts_last_attempted_at_idx
+    ON keeper_action_attempts (last_attempted_at);
+
+-- Trigger orders neither executed, cancelled, claimed nor finished by the keeper yet, as notified
+-- to the keeper, which loads them at startup so orders created while it was down still get executed.
+CREATE OR REPLACE VIEW pending_trigger_orders AS
+SELECT (to_jsonb(o) || jsonb_build_object('key', felt_out(o.key), 'account', felt_out(o.account),
+    'market', felt_out(o.market)))::TEXT AS row_data
+FROM orders o
+WHERE o.order_type IN ('LimitSwap', 'LimitIncrease', 'LimitDecrease', 'StopLossDecrease')
+    AND NOT EXISTS (SELECT 1 FROM order_executed e WHERE e.key = o.key)
+    AND NOT EXISTS (SELECT 1 FROM order_cancelled c WHERE c.key = o.key)
+    AND NOT EXISTS (SELECT 1 FROM keeper_jobs j WHERE j.key = felt_out(o.key))
+    AND NOT EXISTS (
+        SELECT 1 FROM keeper_action_attempts a
+        WHERE a.key = felt_out(o.key) AND a.outcome IS NOT NULL
+    )
+ORDER BY o.block_number;
+
+-- Snapshots of the executions which reverted for good, as JSON: the action, the prices and calls it got executed
+-- with and how its simulation went right after, replayed with the replay-context subcommand.
+CREATE TABLE IF NOT EXISTS keeper_execution_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    snapshot TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS keeper_execution_snapshots_key_idx ON keeper_execution_snapshots (key);
+
+-- Fees paid by the keeper for each execution transaction it sent, against the keeper fees it earned
+-- from the actions the transaction settled, net of their refunds.
+CREATE TABLE IF NOT EXISTS keeper_transaction_fees (
+    transaction_hash TEXT PRIMARY KEY,
+    key TEXT NOT NULL,
+    actual_fee NUMERIC NOT NULL,
+    execution_fee_earned NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The actions sent in each execution transaction, batches sending several that share its fee.
+CREATE TABLE IF NOT EXISTS keeper_transaction_actions (
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (transaction_hash, key)
+);
+
+-- The fee economics of every action the keeper settled: the execution fee the trader paid, how
+-- the settlement split it between the keeper and a refund to the trader, and the fees the keeper
+-- paid for all of its transactions on the action.
+CREATE TABLE IF NOT EXISTS keeper_order_fees (
+    key TEXT PRIMARY KEY,
+    table_name TEXT NOT NULL,
+    account TEXT NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    execution_fee NUMERIC NOT NULL,
+    keeper_fee NUMERIC NOT NULL,
+    refund NUMERIC NOT NULL,
+    gas_spent NUMERIC NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS keeper_order_fees_account_idx ON keeper_order_fees (account);
+
+-- Keeper PnL aggregated per day and per week from keeper_transaction_fees, booked as fees get recorded.
+CREATE TABLE IF NOT EXISTS keeper_pnl (
+    period TEXT NOT NULL,
+    period_start TIMESTAMPTZ NOT NULL,
+    transactions BIGINT NOT NULL,
+    fees_paid NUMERIC NOT NULL,
+    fees_earned NUMERIC NOT NULL,
+    pnl NUMERIC NOT NULL,
+    PRIMARY KEY (period, period_start)
+);
+
+-- Daily aggregates of the detail rows pruned after ARCHIVE_RETENTION_DAYS, in UTC days, so long-horizon
+-- analytics keep working once the details got deleted. Rows only ever get added to, a day being archived
+-- whole, and again for detail rows indexed after it was, e.g. by a backfill.
+CREATE TABLE IF NOT EXISTS archived_daily_keeper_pnl (
+    day DATE PRIMARY KEY,
+    transactions BIGINT NOT NULL,
+    fees_paid NUMERIC NOT NULL,
+    fees_earned NUMERIC NOT NULL,
+    pnl NUMERIC NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS archived_daily_funding (
+    day DATE NOT NULL,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    direction TEXT NOT NULL,
+    payments BIGINT NOT NULL,
+    amount NUMERIC NOT NULL,
+    PRIMARY KEY (day, account, market, collateral_token, is_long, direction)
+);
+
+CREATE INDEX IF NOT EXISTS archived_daily_funding_account_idx ON archived_daily_funding (account);
+
+-- Swap volumes per market and token pair, in token units; markets and tokens not indexed are ''.
+CREATE TABLE IF NOT EXISTS archived_daily_swap_volumes (
+    day DATE NOT NULL,
+    market TEXT NOT NULL,
+    token_in TEXT NOT NULL,
+    token_out TEXT NOT NULL,
+    swaps BIGINT NOT NULL,
+    amount_in NUMERIC NOT NULL,
+    amount_out NUMERIC NOT NULL,
+    PRIMARY KEY (day, market, token_in, token_out)
+);
+
+-- Swap fees per market, token and action, in token units; markets, tokens and actions not indexed are ''.
+CREATE TABLE IF NOT EXISTS archived_daily_swap_fees (
+    day DATE NOT NULL,
+    market TEXT NOT NULL,
+    token TEXT NOT NULL,
+    action TEXT NOT NULL,
+    collections BIGINT NOT NULL,
+    fee_receiver_amount NUMERIC NOT NULL,
+    fee_amount_for_pool NUMERIC NOT NULL,
+    ui_fee_amount NUMERIC NOT NULL,
+    PRIMARY KEY (day, market, token, action)
+);
+
+-- Snapshots of the keeper metrics taken every METRICS_HISTORY_INTERVAL_SECS by each instance, for post-incident
+-- analysis without a Prometheus stack. Jobs are counted over every instance sharing the database, the executed
+-- and failed ones over the interval before the snapshot. Chain reads left NULL when they failed.
+CREATE TABLE IF NOT EXISTS keeper_metrics_history (
+    id BIGSERIAL PRIMARY KEY,
+    instance_id TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    latest_block BIGINT,
+    indexed_block BIGINT,
+    indexer_lag BIGINT,
+    balance TEXT,
+    pending_actions BIGINT NOT NULL,
+    claimed_jobs BIGINT NOT NULL,
+    submitted_jobs BIGINT NOT NULL,
+    running_executions BIGINT NOT NULL,
+    queued_executions BIGINT NOT NULL,
+    executed_jobs BIGINT NOT NULL,
+    failed_jobs BIGINT NOT NULL,
+    success_rate DOUBLE PRECISION
+);
+CREATE INDEX IF NOT EXISTS keeper_metrics_history_recorded_at_idx ON keeper_metrics_history (recorded_at);
+
+-- The oracle price each executed action got executed with, read by the data-quality checks: the latest block
+-- timestamp when the price got fetched and the timestamp of the price on its feed, in seconds.
+CREATE TABLE IF NOT EXISTS keeper_execution_prices (
+    key TEXT PRIMARY KEY,
+    transaction_hash TEXT NOT NULL,
+    block_timestamp BIGINT NOT NULL,
+    price_timestamp BIGINT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Timestamps of executed orders disagreeing with each other, flagged by the data-quality checks every
+-- DATA_QUALITY_INTERVAL_SECS: indexed event timestamps against the chain block timestamps, and oracle price
+-- timestamps against both. These mismatches are the usual root cause of wrong PnL numbers.
+CREATE TABLE IF NOT EXISTS data_quality_issues (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    issue TEXT NOT NULL,
+    details TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (transaction_hash, key, issue)
+);
+
+-- The last block whose executed orders got checked, so the checks resume after a restart.
+CREATE TABLE IF NOT EXISTS data_quality_cursor (
+    id INTEGER PRIMARY KEY DEFAULT 1,
+    block_number BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The leader lease of the keepers sharing an account, renewed by the active one every
+-- STANDBY_POLL_INTERVAL_MS and taken over by a standby once it expires after LEADER_LEASE_MS.
+CREATE TABLE IF NOT EXISTS keeper_leader (
+    account_address TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
 );
 
 -- Drop the existing function and triggers if it exists
@@ -151,15 +1192,15 @@ DROP TRIGGER IF EXISTS orders_notify_insert ON orders;
 
 DROP FUNCTION IF EXISTS orders_update_notify();
 
--- Add a table update notification function
+-- Add a table update notification function, notifying the felts as hex strings whatever their storage
 CREATE OR REPLACE FUNCTION orders_update_notify() RETURNS trigger AS $$
 DECLARE
-  payload json;
+  payload jsonb;
 BEGIN
   IF TG_OP = 'INSERT' OR TG_OP = 'UPDATE' THEN
-    payload = row_to_json(NEW);
+    payload = to_jsonb(NEW) || jsonb_build_object('key', felt_out(NEW.key), 'account', felt_out(NEW.account), 'market', felt_out(NEW.market));
   ELSE
-    payload = row_to_json(OLD);
+    payload = to_jsonb(OLD) || jsonb_build_object('key', felt_out(OLD.key), 'account', felt_out(OLD.account), 'market', felt_out(OLD.market));
   END IF;
   PERFORM pg_notify('orders_update', json_build_object('table', TG_TABLE_NAME, 'action_type', TG_OP, 'row_data', payload)::text);
   RETURN NEW;
@@ -178,15 +1219,15 @@ DROP TRIGGER IF EXISTS withdrawals_notify_insert ON withdrawals;
 
 DROP FUNCTION IF EXISTS withdrawals_update_notify();
 
--- Add a table update notification function
+-- Add a table update notification function, notifying the felts as hex strings whatever their storage
 CREATE OR REPLACE FUNCTION withdrawals_update_notify() RETURNS trigger AS $$
 DECLARE
-  payload json;
+  payload jsonb;
 BEGIN
   IF TG_OP = 'INSERT' OR TG_OP = 'UPDATE' THEN
-    payload = row_to_json(NEW);
+    payload = to_jsonb(NEW) || jsonb_build_object('key', felt_out(NEW.key), 'account', felt_out(NEW.account), 'market', felt_out(NEW.market));
   ELSE
-    payload = row_to_json(OLD);
+    payload = to_jsonb(OLD) || jsonb_build_object('key', felt_out(OLD.key), 'account', felt_out(OLD.account), 'market', felt_out(OLD.market));
   END IF;
   PERFORM pg_notify('withdrawals_update', json_build_object('table', TG_TABLE_NAME, 'action_type', TG_OP, 'row_data', payload)::text);
   RETURN NEW;
@@ -305,4 +1346,23 @@ $$ LANGUAGE plpgsql;
 CREATE TRIGGER pool_amount_updated_notify_update AFTER UPDATE ON pool_amount_updated FOR EACH ROW EXECUTE PROCEDURE pool_amount_updated_update_notify();
 
 -- Add INSERT row trigger
-CREATE TRIGGER pool_amount_updated_notify_insert AFTER INSERT ON pool_amount_updated FOR EACH ROW EXECUTE PROCEDURE pool_amount_updated_update_notify();
\ No newline at end of file
+CREATE TRIGGER pool_amount_updated_notify_insert AFTER INSERT ON pool_amount_updated FOR EACH ROW EXECUTE PROCEDURE pool_amount_updated_update_notify();
+
+-- Drop the existing function and triggers if it exists
+DROP TRIGGER IF EXISTS position_increase_notify_insert ON position_increase;
+DROP TRIGGER IF EXISTS position_decrease_notify_insert ON position_decrease;
+
+DROP FUNCTION IF EXISTS positions_update_notify();
+
+-- Add a position update notification function, shared by the increases and decreases so the keeper
+-- listens to a single channel
+CREATE OR REPLACE FUNCTION positions_update_notify() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('positions_update', json_build_object('table', TG_TABLE_NAME, 'action_type', TG_OP, 'row_data', row_to_json(NEW))::text);
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Add INSERT row triggers
+CREATE TRIGGER position_increase_notify_insert AFTER INSERT ON position_increase FOR EACH ROW EXECUTE PROCEDURE positions_update_notify();
+CREATE TRIGGER position_decrease_notify_insert AFTER INSERT ON position_decrease FOR EACH ROW EXECUTE PROCEDURE positions_update_notify();
@@ -0,0 +1,62 @@
+\c zohal
+
+-- Migrates the keys, accounts and markets of the indexed actions from 64 char hex strings to
+-- BYTEA, halving their size and speeding up the joins on them. Run after db_setup.sql, the
+-- indexer keeps inserting hex strings, felt_in converting them to the new storage type. Running it
+-- again on a migrated database only recreates the views.
+
+BEGIN;
+
+DO $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM information_schema.columns
+             WHERE table_name = 'orders' AND column_name = 'key' AND data_type = 'text') THEN
+    -- The views reading the migrated columns get recreated by db_setup.sql below.
+    DROP VIEW IF EXISTS keeper_competition_stats;
+    DROP VIEW IF EXISTS keeper_backlog;
+    DROP VIEW IF EXISTS order_lifecycle;
+    DROP VIEW IF EXISTS position_borrowing_fees;
+    DROP VIEW IF EXISTS market_open_interest;
+    DROP VIEW IF EXISTS pending_trigger_orders;
+    DROP VIEW IF EXISTS positions_human;
+    DROP VIEW IF EXISTS orders_human;
+    DROP VIEW IF EXISTS deposits_human;
+    DROP VIEW IF EXISTS withdrawals_human;
+
+    ALTER TABLE orders
+        ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key),
+        ALTER COLUMN account TYPE BYTEA USING felt_to_bytea(account),
+        ALTER COLUMN market TYPE BYTEA USING felt_to_bytea(market);
+    ALTER TABLE deposits
+        ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key),
+        ALTER COLUMN account TYPE BYTEA USING felt_to_bytea(account),
+        ALTER COLUMN market TYPE BYTEA USING felt_to_bytea(market);
+    ALTER TABLE withdrawals
+        ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key),
+        ALTER COLUMN account TYPE BYTEA USING felt_to_bytea(account),
+        ALTER COLUMN market TYPE BYTEA USING felt_to_bytea(market);
+    ALTER TABLE order_executed ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE order_cancelled ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE order_updated ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE order_frozen ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE deposit_executed ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE deposit_cancelled ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE withdrawal_executed ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+    ALTER TABLE withdrawal_cancelled ALTER COLUMN key TYPE BYTEA USING felt_to_bytea(key);
+  END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS orders_key_idx ON orders (key);
+CREATE INDEX IF NOT EXISTS orders_account_idx ON orders (account);
+CREATE INDEX IF NOT EXISTS orders_market_idx ON orders (market);
+CREATE INDEX IF NOT EXISTS deposits_key_idx ON deposits (key);
+CREATE INDEX IF NOT EXISTS withdrawals_key_idx ON withdrawals (key);
+CREATE INDEX IF NOT EXISTS order_executed_key_idx ON order_executed (key);
+
+-- db_setup.sql keeps a felt_in already defined, so the indexed hex strings keep getting converted.
+DROP FUNCTION IF EXISTS felt_in(TEXT);
+CREATE FUNCTION felt_in(value TEXT) RETURNS BYTEA AS 'SELECT felt_to_bytea(value)' LANGUAGE SQL IMMUTABLE STRICT;
+
+COMMIT;
+
+\ir db_setup.sql
@@ -0,0 +1,361 @@
+-- The indexer tables of db_setup.sql for the SQLite store, applied by the indexer on startup. Columns keep their
+-- names and Postgres types, except the NUMERIC amounts: stored as TEXT, as SQLite would round the 30 decimals
+-- amounts to floats. Keeper tables, views and notifications stay Postgres only.
+
+CREATE TABLE IF NOT EXISTS last_indexed_block (
+    id INTEGER PRIMARY KEY,
+    block_number BIGINT NOT NULL
+);
+
+-- Cursor of each indexer shard, along with the events and block range it indexes.
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+    shard TEXT PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    events TEXT NOT NULL DEFAULT '',
+    from_block BIGINT NOT NULL DEFAULT 0,
+    to_block BIGINT,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS raw_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    data TEXT NOT NULL,
+    sender_address TEXT,
+    archived_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX IF NOT EXISTS raw_events_event_idx ON raw_events (transaction_hash, key, data);
+CREATE INDEX IF NOT EXISTS raw_events_block_number_idx ON raw_events (block_number, id);
+
+CREATE TABLE IF NOT EXISTS tokens (
+    address TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    decimals INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS orders (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    order_type TEXT,
+    decrease_position_swap_type TEXT,
+    account TEXT,
+    receiver TEXT,
+    callback_contract TEXT,
+    ui_fee_receiver TEXT,
+    market TEXT,
+    initial_collateral_token TEXT,
+    swap_path TEXT,
+    size_delta_usd TEXT,
+    initial_collateral_delta_amount TEXT,
+    trigger_price TEXT,
+    acceptable_price TEXT,
+    execution_fee TEXT,
+    callback_gas_limit TEXT,
+    min_output_amount TEXT,
+    updated_at_block BIGINT,
+    is_long BOOLEAN,
+    is_frozen BOOLEAN,
+    size_delta_usd_scaled TEXT,
+    initial_collateral_delta_amount_scaled TEXT,
+    trigger_price_scaled TEXT,
+    acceptable_price_scaled TEXT,
+    execution_fee_scaled TEXT
+);
+
+CREATE TABLE IF NOT EXISTS deposits (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT,
+    receiver TEXT,
+    callback_contract TEXT,
+    ui_fee_receiver TEXT,
+    market TEXT,
+    initial_long_token TEXT,
+    initial_short_token TEXT,
+    long_token_swap_path TEXT,
+    short_token_swap_path TEXT,
+    initial_long_token_amount BIGINT,
+    initial_short_token_amount BIGINT,
+    min_market_tokens BIGINT,
+    updated_at_block BIGINT,
+    execution_fee BIGINT,
+    callback_gas_limit BIGINT,
+    initial_long_token_amount_scaled TEXT,
+    initial_short_token_amount_scaled TEXT,
+    min_market_tokens_scaled TEXT
+);
+
+CREATE TABLE IF NOT EXISTS withdrawals (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT,
+    receiver TEXT,
+    callback_contract TEXT,
+    ui_fee_receiver TEXT,
+    market TEXT,
+    long_token_swap_path TEXT,
+    short_token_swap_path TEXT,
+    market_token_amount BIGINT,
+    min_long_token_amount BIGINT,
+    min_short_token_amount BIGINT,
+    updated_at_block BIGINT,
+    execution_fee BIGINT,
+    callback_gas_limit BIGINT,
+    market_token_amount_scaled TEXT,
+    min_long_token_amount_scaled TEXT,
+    min_short_token_amount_scaled TEXT
+);
+
+CREATE TABLE IF NOT EXISTS market_created (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    creator TEXT,
+    market_token TEXT,
+    index_token TEXT,
+    long_token TEXT,
+    short_token TEXT,
+    market_type TEXT
+);
+
+CREATE TABLE IF NOT EXISTS swap_fees_collected (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    market TEXT,
+    token TEXT,
+    token_price BIGINT,
+    action TEXT,
+    fee_receiver_amount BIGINT,
+    fee_amount_for_pool BIGINT,
+    amount_after_fees BIGINT,
+    ui_fee_receiver TEXT,
+    ui_fee_receiver_factor BIGINT,
+    ui_fee_amount BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS swap_info (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    order_key TEXT,
+    market TEXT,
+    receiver TEXT,
+    token_in TEXT,
+    token_out TEXT,
+    token_in_price BIGINT,
+    token_out_price BIGINT,
+    amount_in BIGINT,
+    amount_in_after_fees BIGINT,
+    amount_out BIGINT,
+    price_impact_usd_mag BIGINT,
+    price_impact_usd_sign BOOLEAN,
+    price_impact_amount_mag BIGINT,
+    price_impact_amount_sign BOOLEAN
+);
+
+CREATE TABLE IF NOT EXISTS pool_amount_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    market TEXT,
+    token TEXT,
+    delta_mag BIGINT,
+    delta_sign BOOLEAN,
+    next_value BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS order_executed (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    secondary_order_type TEXT,
+    keeper TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS order_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS order_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    size_delta_usd TEXT,
+    acceptable_price TEXT,
+    trigger_price TEXT,
+    min_output_amount TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS order_frozen (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS deposit_executed (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS deposit_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS withdrawal_executed (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS withdrawal_cancelled (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (block_number, transaction_hash, key)
+);
+
+CREATE TABLE IF NOT EXISTS funding_fee_amount_per_size_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta TEXT,
+    next_value TEXT,
+    PRIMARY KEY (block_number, transaction_hash, market, collateral_token, is_long)
+);
+
+CREATE TABLE IF NOT EXISTS claimable_funding_amount_per_size_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta TEXT,
+    next_value TEXT,
+    PRIMARY KEY (block_number, transaction_hash, market, collateral_token, is_long)
+);
+
+CREATE TABLE IF NOT EXISTS funding_payments (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    direction TEXT NOT NULL,
+    size_in_usd TEXT NOT NULL,
+    amount_per_size_delta TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token, is_long, direction)
+);
+
+CREATE INDEX IF NOT EXISTS funding_payments_account_idx ON funding_payments (account);
+
+CREATE TABLE IF NOT EXISTS cumulative_borrowing_factor_updated (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    delta TEXT,
+    next_value TEXT,
+    PRIMARY KEY (block_number, transaction_hash, market, is_long)
+);
+
+CREATE TABLE IF NOT EXISTS borrowing_fee_accruals (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    is_long BOOLEAN NOT NULL,
+    size_in_usd TEXT NOT NULL,
+    borrowing_factor_delta TEXT NOT NULL,
+    amount_usd TEXT NOT NULL,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token, is_long)
+);
+
+CREATE TABLE IF NOT EXISTS position_increase (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    execution_price TEXT,
+    size_delta_usd TEXT,
+    size_delta_in_tokens TEXT,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token)
+);
+
+CREATE TABLE IF NOT EXISTS position_decrease (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT NOT NULL,
+    market TEXT NOT NULL,
+    collateral_token TEXT NOT NULL,
+    execution_price TEXT,
+    size_delta_usd TEXT,
+    size_delta_in_tokens TEXT,
+    PRIMARY KEY (block_number, transaction_hash, account, market, collateral_token)
+);
+
+CREATE TABLE IF NOT EXISTS market_params_history (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    market TEXT NOT NULL,
+    parameter TEXT NOT NULL,
+    is_long BOOLEAN,
+    value TEXT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS market_params_history_update_idx
+    ON market_params_history (block_number, transaction_hash, market, parameter, COALESCE(is_long, ''));
+CREATE INDEX IF NOT EXISTS market_params_history_market_idx
+    ON market_params_history (market, parameter, block_number);
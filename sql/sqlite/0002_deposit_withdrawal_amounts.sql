@@ -0,0 +1,60 @@
+-- Deposit and withdrawal amounts were created as BIGINT, SQLite turning the amounts past 64 bits into
+-- floats. They get stored as TEXT like the other amounts, SQLite not altering column types in place.
+
+ALTER TABLE deposits RENAME TO deposits_bigint;
+
+CREATE TABLE deposits (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT,
+    receiver TEXT,
+    callback_contract TEXT,
+    ui_fee_receiver TEXT,
+    market TEXT,
+    initial_long_token TEXT,
+    initial_short_token TEXT,
+    long_token_swap_path TEXT,
+    short_token_swap_path TEXT,
+    initial_long_token_amount TEXT,
+    initial_short_token_amount TEXT,
+    min_market_tokens TEXT,
+    updated_at_block BIGINT,
+    execution_fee TEXT,
+    callback_gas_limit BIGINT,
+    initial_long_token_amount_scaled TEXT,
+    initial_short_token_amount_scaled TEXT,
+    min_market_tokens_scaled TEXT
+);
+
+INSERT INTO deposits SELECT * FROM deposits_bigint;
+DROP TABLE deposits_bigint;
+
+ALTER TABLE withdrawals RENAME TO withdrawals_bigint;
+
+CREATE TABLE withdrawals (
+    block_number BIGINT NOT NULL,
+    time_stamp TEXT,
+    transaction_hash TEXT NOT NULL,
+    key TEXT,
+    account TEXT,
+    receiver TEXT,
+    callback_contract TEXT,
+    ui_fee_receiver TEXT,
+    market TEXT,
+    long_token_swap_path TEXT,
+    short_token_swap_path TEXT,
+    market_token_amount TEXT,
+    min_long_token_amount TEXT,
+    min_short_token_amount TEXT,
+    updated_at_block BIGINT,
+    execution_fee TEXT,
+    callback_gas_limit BIGINT,
+    market_token_amount_scaled TEXT,
+    min_long_token_amount_scaled TEXT,
+    min_short_token_amount_scaled TEXT
+);
+
+INSERT INTO withdrawals SELECT * FROM withdrawals_bigint;
+DROP TABLE withdrawals_bigint;
@@ -0,0 +1,4 @@
+-- Unix timestamp of the last heartbeat of each shard, shards without one for SHARD_TTL_SECS no longer holding
+-- their events and block range.
+
+ALTER TABLE indexer_cursors ADD COLUMN heartbeat_at BIGINT NOT NULL DEFAULT 0;